@@ -259,6 +259,7 @@ fn bench_key_folding_overhead(c: &mut Criterion) {
             key_folding: Some(KeyFoldingMode::Off),
             flatten_depth: None,
             replacer: None,
+            ..EncodeOptions::default()
         });
         b.iter(|| encode(black_box(json.clone()), options.clone()));
     });
@@ -270,6 +271,7 @@ fn bench_key_folding_overhead(c: &mut Criterion) {
             key_folding: Some(KeyFoldingMode::Safe),
             flatten_depth: None,
             replacer: None,
+            ..EncodeOptions::default()
         });
         b.iter(|| encode(black_box(json.clone()), options.clone()));
     });
@@ -319,6 +321,7 @@ fn bench_compression_ratio(c: &mut Criterion) {
         key_folding: Some(KeyFoldingMode::Safe),
         flatten_depth: None,
         replacer: None,
+        ..EncodeOptions::default()
     });
     let nested_toon_folded = encode(nested, options_folded);
 
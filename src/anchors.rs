@@ -0,0 +1,308 @@
+//! Anchors and aliases for repeated nested objects.
+//!
+//! Documents often repeat the same configuration block under several keys.
+//! With anchors enabled, the encoder writes the first copy with an anchor
+//! after its key and replaces each later copy with an alias:
+//!
+//! ```text
+//! staging: &a1
+//!   timeout: 30
+//!   retries: 3
+//! production: *a1
+//! ```
+//!
+//! Both passes work on lines: [`emit_anchors`] rewrites encoder output and
+//! [`expand_anchors`] rewrites decoder input before any structure is decoded,
+//! so the rest of the pipeline never sees the markers. Only the value of a
+//! `key:` line (or `- key:` list item) is read as a marker; an unquoted
+//! string that would look like one is quoted by the encoder. An alias must
+//! follow the end of its anchor's block, which rules out cycles.
+
+use std::collections::HashMap;
+
+use crate::decode::parser::{parse_array_header_line, parse_key_token};
+use crate::decode::scanner::{Depth, ParsedLine};
+use crate::error::{Result, ToonError};
+use crate::shared::constants::{DEFAULT_DELIMITER, DOUBLE_QUOTE, LIST_ITEM_PREFIX, SPACE};
+
+const ANCHOR: char = '&';
+const ALIAS: char = '*';
+
+/// Blocks shorter than this are written out again; an alias would save
+/// little or nothing.
+const MIN_ANCHOR_LINES: usize = 2;
+
+/// Expanded aliases may grow the input to at most this many times its size...
+const MAX_EXPANSION_FACTOR: usize = 64;
+/// ...or to this many bytes, whichever is larger, so that aliases of aliases
+/// cannot blow a short document up exponentially.
+const MIN_EXPANSION_BUDGET: usize = 1 << 20;
+
+/// Replace repeated nested objects in encoded `lines` with aliases to their
+/// first occurrence.
+#[must_use]
+pub fn emit_anchors(mut lines: Vec<String>, indent: usize) -> Vec<String> {
+    if indent == 0 {
+        return lines;
+    }
+    for line in &mut lines {
+        quote_marker_value(line);
+    }
+
+    let blocks: Vec<Option<Block>> = (0..lines.len())
+        .map(|index| block_at(&lines, index, indent))
+        .collect();
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for block in blocks.iter().flatten() {
+        *counts.entry(block.body.as_str()).or_default() += 1;
+    }
+
+    // First pass: anchor every repeated block at its first occurrence and
+    // alias the rest. An anchor can end up unused when all its later copies
+    // sit inside a block that was itself aliased.
+    let mut anchor_ids: HashMap<&str, usize> = HashMap::new();
+    let mut used = Vec::new();
+    let mut marked = Vec::with_capacity(lines.len());
+    let mut index = 0;
+    while index < lines.len() {
+        let repeated = blocks[index]
+            .as_ref()
+            .filter(|block| counts[block.body.as_str()] > 1);
+        let Some(block) = repeated else {
+            marked.push((index, None));
+            index += 1;
+            continue;
+        };
+        if let Some(&id) = anchor_ids.get(block.body.as_str()) {
+            used[id] = true;
+            marked.push((index, Some((ALIAS, id))));
+            index = block.end;
+        } else {
+            anchor_ids.insert(block.body.as_str(), used.len());
+            marked.push((index, Some((ANCHOR, used.len()))));
+            used.push(false);
+            index += 1;
+        }
+    }
+
+    // Second pass: drop unused anchors and number the rest in order.
+    let mut names = vec![0; used.len()];
+    let mut next_name = 1;
+    marked
+        .into_iter()
+        .map(|(index, marker)| {
+            let line = &lines[index];
+            match marker {
+                Some((ANCHOR, id)) if used[id] => {
+                    names[id] = next_name;
+                    next_name += 1;
+                    format!("{line} {ANCHOR}a{}", names[id])
+                }
+                Some((ALIAS, id)) => format!("{line} {ALIAS}a{}", names[id]),
+                _ => line.clone(),
+            }
+        })
+        .collect()
+}
+
+/// Replace each alias in parsed `lines` with a copy of its anchor's block.
+///
+/// # Errors
+///
+/// Returns an error for an alias to an unknown anchor or to an anchor whose
+/// block contains the alias, and when the expanded lines would be more than
+/// [`MAX_EXPANSION_FACTOR`] times the size of the input.
+pub fn expand_anchors(lines: Vec<ParsedLine>, indent: usize) -> Result<Vec<ParsedLine>> {
+    let input_size = lines.iter().map(line_size).sum::<usize>();
+    let mut budget = input_size
+        .saturating_mul(MAX_EXPANSION_FACTOR)
+        .max(MIN_EXPANSION_BUDGET)
+        - input_size;
+    let mut anchors: HashMap<String, Vec<ParsedLine>> = HashMap::new();
+    let mut open: Vec<OpenAnchor> = Vec::new();
+    let mut out = Vec::with_capacity(lines.len());
+
+    for line in lines {
+        close_anchors(&mut open, &out, line.depth, &mut anchors);
+        let Some((base, marker_start, (sigil, name))) = marker_in_line(&line) else {
+            out.push(line);
+            continue;
+        };
+        let name = name.to_string();
+        let line_number = line.line_number;
+        let content = line.content[..marker_start].trim_end().to_string();
+        out.push(ParsedLine {
+            raw: reindent(line.indent, &content),
+            content,
+            ..line
+        });
+
+        if sigil == ANCHOR {
+            open.push(OpenAnchor {
+                name,
+                base,
+                start: out.len(),
+            });
+            continue;
+        }
+        if open.iter().any(|anchor| anchor.name == name) {
+            return Err(ToonError::parse(
+                line_number,
+                format!("Alias *{name} is inside the block it refers to"),
+            ));
+        }
+        let block = anchors
+            .get(&name)
+            .ok_or_else(|| ToonError::parse(line_number, format!("Unknown anchor *{name}")))?;
+        let block_size = block.iter().map(line_size).sum::<usize>();
+        budget = budget.checked_sub(block_size).ok_or_else(|| {
+            ToonError::parse(
+                line_number,
+                format!(
+                    "Alias *{name} expands the document past {MAX_EXPANSION_FACTOR} times its size"
+                ),
+            )
+        })?;
+        out.extend(block.iter().map(|copy| {
+            let depth = base + copy.depth;
+            ParsedLine {
+                raw: reindent(depth * indent, &copy.content),
+                indent: depth * indent,
+                depth,
+                ..copy.clone()
+            }
+        }));
+    }
+    Ok(out)
+}
+
+/// What a line counts against the expansion budget: its text plus one for
+/// the line itself, so that empty lines are not free.
+const fn line_size(line: &ParsedLine) -> usize {
+    line.raw.len() + 1
+}
+
+/// An anchor whose block has not ended yet.
+struct OpenAnchor {
+    name: String,
+    /// Depth of the block's top-level lines.
+    base: Depth,
+    /// Index of the block's first line in the output.
+    start: usize,
+}
+
+/// Record the blocks of open anchors that end before a line at `depth`.
+fn close_anchors(
+    open: &mut Vec<OpenAnchor>,
+    out: &[ParsedLine],
+    depth: Depth,
+    anchors: &mut HashMap<String, Vec<ParsedLine>>,
+) {
+    while let Some(anchor) = open.pop_if(|anchor| depth < anchor.base) {
+        let block = out[anchor.start..]
+            .iter()
+            .map(|line| ParsedLine {
+                depth: line.depth - anchor.base,
+                ..line.clone()
+            })
+            .collect();
+        anchors.insert(anchor.name, block);
+    }
+}
+
+/// A `key:` line with no value and the nested lines under it.
+struct Block {
+    /// Index just past the block's last line.
+    end: usize,
+    /// The nested lines with the block's own indentation removed.
+    body: String,
+}
+
+fn block_at(lines: &[String], index: usize, indent: usize) -> Option<Block> {
+    let line = &lines[index];
+    let depth = line_depth(line, indent);
+    let content = line.trim_start_matches(SPACE);
+    let (field, base) = content
+        .strip_prefix(LIST_ITEM_PREFIX)
+        .map_or((content, depth + 1), |field| (field, depth + 2));
+    let (_, value) = split_key_value(field)?;
+    if !value.is_empty() {
+        return None;
+    }
+
+    let end = lines[index + 1..]
+        .iter()
+        .position(|line| line_depth(line, indent) < base)
+        .map_or(lines.len(), |offset| index + 1 + offset);
+    if end - index - 1 < MIN_ANCHOR_LINES {
+        return None;
+    }
+    let body = lines[index + 1..end]
+        .iter()
+        .map(|line| &line[base * indent..])
+        .collect::<Vec<_>>()
+        .join("\n");
+    Some(Block { end, body })
+}
+
+fn line_depth(line: &str, indent: usize) -> Depth {
+    (line.len() - line.trim_start_matches(SPACE).len()) / indent
+}
+
+/// Quote an unquoted `key: value` value that would read as a marker.
+fn quote_marker_value(line: &mut String) {
+    let content = line.trim_start_matches(SPACE);
+    let field = content.strip_prefix(LIST_ITEM_PREFIX).unwrap_or(content);
+    let Some((_, value)) = split_key_value(field) else {
+        return;
+    };
+    if parse_marker(value).is_some() {
+        let start = line.len() - value.len();
+        line.insert(start, DOUBLE_QUOTE);
+        line.push(DOUBLE_QUOTE);
+    }
+}
+
+/// The body depth, byte offset, and marker of a line ending in a marker.
+fn marker_in_line(line: &ParsedLine) -> Option<(Depth, usize, (char, &str))> {
+    let content = line.content.trim_end();
+    let (field, base) = content
+        .strip_prefix(LIST_ITEM_PREFIX)
+        .map_or((content, line.depth + 1), |field| (field, line.depth + 2));
+    let (_, value) = split_key_value(field)?;
+    let marker = parse_marker(value)?;
+    Some((base, content.len() - value.len(), marker))
+}
+
+/// Split a non-array `key: value` field into its key part and trimmed value.
+fn split_key_value(field: &str) -> Option<(&str, &str)> {
+    if matches!(
+        parse_array_header_line(field, DEFAULT_DELIMITER),
+        Ok(Some(_))
+    ) {
+        return None;
+    }
+    let (_, end, _) = parse_key_token(field, 0).ok()?;
+    Some((&field[..end], field[end..].trim()))
+}
+
+/// Parse `&name` or `*name`, where `name` is ASCII letters, digits, `_`,
+/// or `-`.
+fn parse_marker(value: &str) -> Option<(char, &str)> {
+    let sigil = value
+        .chars()
+        .next()
+        .filter(|ch| *ch == ANCHOR || *ch == ALIAS)?;
+    let name = &value[1..];
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-');
+    valid.then_some((sigil, name))
+}
+
+fn reindent(indent: usize, content: &str) -> String {
+    let mut raw = " ".repeat(indent);
+    raw.push_str(content);
+    raw
+}
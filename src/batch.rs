@@ -0,0 +1,563 @@
+//! Converting many files at once (`toon batch`).
+//!
+//! [`convert_batch`] runs a list of [`ConversionJob`]s on a pool of worker
+//! threads. Each job names its input, where to write the result, and its own
+//! options; results come back in job order, with each error prefixed by the
+//! path it concerns. [`batch_error`] folds the failures into one error for
+//! reporting, and [`jobs_for_paths`] builds jobs from files and directories
+//! the way the CLI does. [`plan_batch`] reports what a run would do without
+//! writing anything, and [`BatchSummary`] totals what a run did.
+//!
+//! A job whose output is another job's input is refused rather than run, so
+//! `x.json` and `x.toon` side by side are never converted over each other.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use crate::JsonValue;
+use crate::codec::{Decoder, Encoder};
+use crate::error::{Result, ToonError};
+use crate::json::json_stringify_lines;
+use crate::manifest::collect_files;
+use crate::options::{DecodeOptions, EncodeOptions};
+use crate::parallel::worker_count;
+use crate::shared::string_utils::estimate_tokens;
+
+/// Which way a job converts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// JSON to TOON.
+    Encode,
+    /// TOON to JSON.
+    Decode,
+}
+
+impl Direction {
+    /// `Encode` for a `.json` path, `Decode` for anything else.
+    #[must_use]
+    pub fn for_path(path: &Path) -> Self {
+        if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+        {
+            Self::Encode
+        } else {
+            Self::Decode
+        }
+    }
+
+    /// Extension of the files this direction writes.
+    #[must_use]
+    pub const fn output_extension(self) -> &'static str {
+        match self {
+            Self::Encode => "toon",
+            Self::Decode => "json",
+        }
+    }
+}
+
+/// What a directory walk does with the symbolic links it finds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SymlinkPolicy {
+    /// Leave links out, as if they were not there.
+    #[default]
+    Skip,
+    /// Follow links that resolve inside the directory being walked; a link
+    /// that resolves outside it is an error.
+    Follow,
+}
+
+/// One file to convert.
+#[derive(Clone)]
+pub struct ConversionJob {
+    pub input: PathBuf,
+    /// File to write the result to. When `None`, the result is returned in
+    /// [`ConversionOutput::text`] instead. An existing symbolic link at this
+    /// path is never written through.
+    pub output: Option<PathBuf>,
+    /// Replace an existing output file whose content differs from the
+    /// result. Without it such a job fails, so that a file of the other
+    /// format next to the input, which may be a source in its own right, is
+    /// never converted over.
+    pub overwrite: bool,
+    pub direction: Direction,
+    /// Options for encoding JSON input.
+    pub encode_options: Option<EncodeOptions>,
+    /// Options for decoding TOON input. Their indentation is also used for
+    /// the JSON output.
+    pub decode_options: Option<DecodeOptions>,
+}
+
+impl ConversionJob {
+    /// Convert `input` next to itself: `data.json` to `data.toon` and
+    /// `data.toon` to `data.json`, with default options and without
+    /// overwriting an existing file.
+    #[must_use]
+    pub fn new(input: impl Into<PathBuf>) -> Self {
+        let input = input.into();
+        let direction = Direction::for_path(&input);
+        Self {
+            output: Some(input.with_extension(direction.output_extension())),
+            overwrite: false,
+            input,
+            direction,
+            encode_options: None,
+            decode_options: None,
+        }
+    }
+}
+
+/// What a successful job produced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConversionOutput {
+    pub input: PathBuf,
+    /// The file written, if the job had an output path.
+    pub output: Option<PathBuf>,
+    /// The converted text, if the job had no output path.
+    pub text: Option<String>,
+    pub input_bytes: usize,
+    pub output_bytes: usize,
+    /// Estimated tokens of the JSON side of the conversion: the input when
+    /// encoding, the output when decoding.
+    pub json_tokens: usize,
+    /// Estimated tokens of the TOON side of the conversion.
+    pub toon_tokens: usize,
+}
+
+/// Convert one file.
+///
+/// # Errors
+///
+/// Returns an error if the input cannot be read or parsed, or the output
+/// cannot be encoded or written, or already exists with different content
+/// and the job does not allow overwriting it.
+pub fn convert_file(job: &ConversionJob) -> Result<ConversionOutput> {
+    let input = std::fs::read_to_string(&job.input)
+        .map_err(|err| ToonError::file_read(job.input.clone(), err))?;
+    let text = match job.direction {
+        Direction::Encode => {
+            let value: serde_json::Value =
+                serde_json::from_str(&input).map_err(|err| ToonError::json_parse(&err))?;
+            Encoder::new(job.encode_options.clone()).try_encode(JsonValue::from(value))?
+        }
+        Direction::Decode => {
+            let decoder = Decoder::new(job.decode_options.clone());
+            let value = decoder.try_decode(&input)?;
+            json_stringify_lines(&value, decoder.options().indent).concat()
+        }
+    };
+
+    let output_bytes = text.len();
+    let (json_tokens, toon_tokens) = match job.direction {
+        Direction::Encode => (estimate_tokens(&input), estimate_tokens(&text)),
+        Direction::Decode => (estimate_tokens(&text), estimate_tokens(&input)),
+    };
+    let text = match &job.output {
+        Some(path) => {
+            if let Some(parent) = path
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+            {
+                std::fs::create_dir_all(parent)
+                    .map_err(|err| ToonError::file_create(parent.to_path_buf(), err))?;
+            }
+            if std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_symlink()) {
+                return Err(ToonError::message(format!(
+                    "{}: output is a symbolic link; not writing through it",
+                    path.display()
+                )));
+            }
+            let text = format!("{text}\n");
+            match std::fs::read(path) {
+                Ok(existing) if existing == text.as_bytes() => {}
+                Ok(_) if !job.overwrite => return Err(existing_output_error(path)),
+                _ => std::fs::write(path, text)
+                    .map_err(|err| ToonError::file_write(path.clone(), err))?,
+            }
+            None
+        }
+        None => Some(text),
+    };
+    Ok(ConversionOutput {
+        input: job.input.clone(),
+        output: job.output.clone(),
+        text,
+        input_bytes: input.len(),
+        output_bytes,
+        json_tokens,
+        toon_tokens,
+    })
+}
+
+fn existing_output_error(path: &Path) -> ToonError {
+    ToonError::message(format!(
+        "{}: output already exists with different content; not replacing it without overwrite",
+        path.display()
+    ))
+}
+
+/// For each job, an error if its output is the input of a job in `jobs`, or
+/// the output of a job with a different input.
+///
+/// Paths are compared after resolving them, so `dir/x.toon` and
+/// `dir/../dir/x.toon` are the same file.
+fn output_collisions(jobs: &[ConversionJob]) -> Vec<Option<ToonError>> {
+    let inputs: HashMap<PathBuf, &Path> = jobs
+        .iter()
+        .map(|job| (resolved_path(&job.input), job.input.as_path()))
+        .collect();
+    let mut writers: HashMap<PathBuf, Vec<(PathBuf, &Path)>> = HashMap::new();
+    for job in jobs {
+        if let Some(output) = &job.output {
+            writers
+                .entry(resolved_path(output))
+                .or_default()
+                .push((resolved_path(&job.input), &job.input));
+        }
+    }
+    jobs.iter()
+        .map(|job| {
+            let output = job.output.as_deref()?;
+            let resolved = resolved_path(output);
+            if let Some(other) = inputs.get(&resolved) {
+                return Some(ToonError::message(format!(
+                    "{}: output {} is also an input of this batch ({}); not converting one over the other",
+                    job.input.display(),
+                    output.display(),
+                    other.display()
+                )));
+            }
+            let input = resolved_path(&job.input);
+            let (_, other) = writers[&resolved].iter().find(|(other, _)| *other != input)?;
+            Some(ToonError::message(format!(
+                "{}: output {} is also the output of {}; not converting both into one file",
+                job.input.display(),
+                output.display(),
+                other.display()
+            )))
+        })
+        .collect()
+}
+
+/// `path` with links and `..` resolved, as far as it exists.
+fn resolved_path(path: &Path) -> PathBuf {
+    if let Ok(resolved) = std::fs::canonicalize(path) {
+        return resolved;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => {
+            std::fs::canonicalize(parent).map_or_else(|_| path.to_path_buf(), |dir| dir.join(name))
+        }
+        _ => std::env::current_dir().map_or_else(|_| path.to_path_buf(), |dir| dir.join(path)),
+    }
+}
+
+/// Convert every job on up to `concurrency` worker threads, returning one
+/// result per job in job order. A `concurrency` of 0 uses one thread per
+/// available core.
+///
+/// A failed job does not stop the others; its error names its input file.
+/// A job whose output is another job's input, or another job's output,
+/// fails without running.
+#[must_use]
+pub fn convert_batch(
+    jobs: Vec<ConversionJob>,
+    concurrency: usize,
+) -> Vec<Result<ConversionOutput>> {
+    let collisions = output_collisions(&jobs);
+    let mut results = Vec::with_capacity(jobs.len());
+    let mut pending = Vec::new();
+    for ((index, job), collision) in jobs.into_iter().enumerate().zip(collisions) {
+        if collision.is_none() {
+            pending.push((index, job));
+        }
+        results.push(collision.map(Err));
+    }
+    let results = Mutex::new(results);
+    let workers = worker_count(concurrency).min(pending.len());
+    let queue = Mutex::new(pending.into_iter());
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    let next = queue.lock().unwrap_or_else(PoisonError::into_inner).next();
+                    let Some((index, job)) = next else {
+                        break;
+                    };
+                    let result = convert_file(&job).map_err(|err| {
+                        ToonError::message(format!("{}: {err}", job.input.display()))
+                    });
+                    results.lock().unwrap_or_else(PoisonError::into_inner)[index] = Some(result);
+                }
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap_or_else(PoisonError::into_inner)
+        .into_iter()
+        .map(|result| result.unwrap_or_else(|| Err(ToonError::message("Job did not run"))))
+        .collect()
+}
+
+/// What running a job would do to its output file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlannedAction {
+    /// Write an output file that does not exist yet (or return the text, for
+    /// a job without an output path).
+    Convert,
+    /// Replace an existing output file with different content.
+    Overwrite,
+    /// Leave the output file alone: it already has the converted content.
+    UpToDate,
+}
+
+/// A job as [`plan_batch`] expects it to run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedJob {
+    pub input: PathBuf,
+    pub output: Option<PathBuf>,
+    pub action: PlannedAction,
+    /// Size in bytes of the output the job would write.
+    pub output_bytes: usize,
+}
+
+/// Convert every job in memory, as [`convert_batch`] would, and compare the
+/// results with the existing output files instead of writing them.
+///
+/// Output sizes come from the actual conversion, so they are exact, and a
+/// job that would fail reports the same error here.
+#[must_use]
+pub fn plan_batch(jobs: Vec<ConversionJob>, concurrency: usize) -> Vec<Result<PlannedJob>> {
+    let collisions = output_collisions(&jobs);
+    let outputs: Vec<(Option<PathBuf>, bool)> = jobs
+        .iter()
+        .map(|job| (job.output.clone(), job.overwrite))
+        .collect();
+    let in_memory = jobs
+        .into_iter()
+        .map(|job| ConversionJob {
+            output: None,
+            ..job
+        })
+        .collect();
+    convert_batch(in_memory, concurrency)
+        .into_iter()
+        .zip(outputs)
+        .zip(collisions)
+        .map(|((result, (output, overwrite)), collision)| {
+            if let Some(collision) = collision {
+                return Err(collision);
+            }
+            let converted = result?;
+            let text = format!("{}\n", converted.text.unwrap_or_default());
+            let action = match output.as_deref().map(|path| (path, std::fs::read(path))) {
+                None | Some((_, Err(_))) => PlannedAction::Convert,
+                Some((_, Ok(existing))) if existing == text.as_bytes() => PlannedAction::UpToDate,
+                Some((_, Ok(_))) if overwrite => PlannedAction::Overwrite,
+                Some((path, Ok(_))) => return Err(existing_output_error(path)),
+            };
+            Ok(PlannedJob {
+                input: converted.input,
+                output,
+                action,
+                output_bytes: text.len(),
+            })
+        })
+        .collect()
+}
+
+/// Totals for a finished [`convert_batch`] run, for an end-of-run report.
+///
+/// Byte and token counts cover the jobs that succeeded. Its `Display` is a
+/// one-line summary; [`to_json`](Self::to_json) has the same figures for
+/// logs and audits.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchSummary {
+    /// Jobs run, failed or not.
+    pub documents: usize,
+    pub failures: usize,
+    pub input_bytes: usize,
+    pub output_bytes: usize,
+    /// Estimated tokens of the JSON side of every conversion.
+    pub json_tokens: usize,
+    /// Estimated tokens of the TOON side of every conversion.
+    pub toon_tokens: usize,
+    /// Wall-clock time of the run.
+    pub elapsed: Duration,
+}
+
+impl BatchSummary {
+    /// Total the results of a run that took `elapsed`.
+    #[must_use]
+    pub fn new(results: &[Result<ConversionOutput>], elapsed: Duration) -> Self {
+        let mut summary = Self {
+            documents: results.len(),
+            elapsed,
+            ..Self::default()
+        };
+        for result in results {
+            match result {
+                Ok(output) => {
+                    summary.input_bytes += output.input_bytes;
+                    summary.output_bytes += output.output_bytes;
+                    summary.json_tokens += output.json_tokens;
+                    summary.toon_tokens += output.toon_tokens;
+                }
+                Err(_) => summary.failures += 1,
+            }
+        }
+        summary
+    }
+
+    /// Estimated tokens TOON saves over JSON; negative if it costs more.
+    #[must_use]
+    #[allow(clippy::cast_possible_wrap)]
+    pub const fn saved_tokens(&self) -> i64 {
+        self.json_tokens as i64 - self.toon_tokens as i64
+    }
+
+    /// Documents per second.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn documents_per_second(&self) -> f64 {
+        self.per_second(self.documents as f64)
+    }
+
+    /// Input bytes read per second.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn bytes_per_second(&self) -> f64 {
+        self.per_second(self.input_bytes as f64)
+    }
+
+    fn per_second(&self, count: f64) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 { count / seconds } else { 0.0 }
+    }
+
+    #[must_use]
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::Object(vec![
+            ("documents".to_string(), self.documents.into()),
+            ("failures".to_string(), self.failures.into()),
+            ("inputBytes".to_string(), self.input_bytes.into()),
+            ("outputBytes".to_string(), self.output_bytes.into()),
+            ("jsonTokens".to_string(), self.json_tokens.into()),
+            ("toonTokens".to_string(), self.toon_tokens.into()),
+            ("savedTokens".to_string(), self.saved_tokens().into()),
+            (
+                "elapsedSeconds".to_string(),
+                self.elapsed.as_secs_f64().into(),
+            ),
+            (
+                "documentsPerSecond".to_string(),
+                self.documents_per_second().into(),
+            ),
+            ("bytesPerSecond".to_string(), self.bytes_per_second().into()),
+        ])
+    }
+}
+
+impl fmt::Display for BatchSummary {
+    #[allow(clippy::cast_precision_loss)]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let saved = self.saved_tokens();
+        let percent = if self.json_tokens > 0 {
+            saved as f64 / self.json_tokens as f64 * 100.0
+        } else {
+            0.0
+        };
+        write!(
+            f,
+            "{} documents, {} failed; {} -> {} bytes; ~{} (JSON) vs ~{} (TOON) tokens, \
+             saved ~{saved} ({percent:.1}%); {:.3}s, {:.1} documents/s, {:.0} bytes/s",
+            self.documents,
+            self.failures,
+            self.input_bytes,
+            self.output_bytes,
+            self.json_tokens,
+            self.toon_tokens,
+            self.elapsed.as_secs_f64(),
+            self.documents_per_second(),
+            self.bytes_per_second()
+        )
+    }
+}
+
+/// One error listing every failed job, or `None` if all succeeded.
+#[must_use]
+pub fn batch_error<T>(results: &[Result<T>]) -> Option<ToonError> {
+    let failures = results
+        .iter()
+        .filter_map(|result| result.as_ref().err())
+        .map(|err| format!("\n  {err}"))
+        .collect::<Vec<_>>();
+    if failures.is_empty() {
+        return None;
+    }
+    Some(ToonError::message(format!(
+        "{} of {} files failed to convert:{}",
+        failures.len(),
+        results.len(),
+        failures.concat()
+    )))
+}
+
+/// Jobs for `paths`: each file is converted by its extension, and each
+/// directory contributes every `.json` and `.toon` file under it, sorted.
+///
+/// Outputs go next to their inputs, or under `out_dir` when given: a file
+/// argument by its file name, and files found in a directory argument at
+/// their path relative to it. Only jobs writing under `out_dir` may overwrite
+/// an existing file; next to the inputs, one may be a source file.
+///
+/// Links inside directories are handled by `symlinks`; files and
+/// directories named in `paths` are always used, links or not.
+///
+/// # Errors
+///
+/// Returns an error if a path does not exist, a directory cannot be read, or
+/// a followed link resolves outside its directory.
+pub fn jobs_for_paths(
+    paths: &[PathBuf],
+    out_dir: Option<&Path>,
+    symlinks: SymlinkPolicy,
+) -> Result<Vec<ConversionJob>> {
+    let mut jobs = Vec::new();
+    for path in paths {
+        let metadata =
+            std::fs::metadata(path).map_err(|err| ToonError::file_read(path.clone(), err))?;
+        let (root, files) = if metadata.is_dir() {
+            let mut files = Vec::new();
+            collect_files(path, symlinks, &mut files)?;
+            files.sort();
+            (path.as_path(), files)
+        } else {
+            (
+                path.parent().unwrap_or_else(|| Path::new("")),
+                vec![path.clone()],
+            )
+        };
+        for file in files {
+            let mut job = ConversionJob::new(&file);
+            if let Some(out_dir) = out_dir {
+                job.overwrite = true;
+                let relative = file.strip_prefix(root).unwrap_or(&file);
+                job.output = Some(
+                    out_dir
+                        .join(relative)
+                        .with_extension(job.direction.output_extension()),
+                );
+            }
+            jobs.push(job);
+        }
+    }
+    Ok(jobs)
+}
@@ -0,0 +1,149 @@
+//! Encode and decode throughput on real data.
+//!
+//! Which options are fastest depends on the shape of the data: a delimiter
+//! that forces quoting, or key folding on deep objects, costs more on some
+//! documents than on others. [`run_bench`] encodes a value and decodes the
+//! result a number of times with the given options and reports the time of
+//! each run, from which [`Timings`] gives percentiles and [`BenchReport`]
+//! throughput in bytes, lines, and events per second.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use crate::error::{Result, ToonError};
+use crate::options::{DecodeOptions, EncodeOptions};
+use crate::sink::EventSink;
+use crate::{Decoder, Encoder, JsonStreamEvent, JsonValue};
+
+/// Outcome of [`run_bench`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BenchReport {
+    /// Size of the TOON text each run encodes to and decodes from.
+    pub toon_bytes: usize,
+    /// Lines in that text.
+    pub lines: usize,
+    /// Events decoding it emits.
+    pub events: usize,
+    pub encode: Timings,
+    pub decode: Timings,
+}
+
+impl BenchReport {
+    /// Throughput of a run that took `elapsed`, measured against the TOON
+    /// text.
+    #[must_use]
+    pub fn throughput(&self, elapsed: Duration) -> Throughput {
+        let seconds = elapsed.as_secs_f64().max(f64::MIN_POSITIVE);
+        #[allow(clippy::cast_precision_loss)]
+        let per_second = |count: usize| count as f64 / seconds;
+        Throughput {
+            megabytes_per_second: per_second(self.toon_bytes) / 1_000_000.0,
+            lines_per_second: per_second(self.lines),
+            events_per_second: per_second(self.events),
+        }
+    }
+}
+
+/// Rates of one run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Throughput {
+    /// Decimal megabytes (10^6 bytes) per second.
+    pub megabytes_per_second: f64,
+    pub lines_per_second: f64,
+    pub events_per_second: f64,
+}
+
+/// The durations of the runs of one operation, fastest first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Timings {
+    samples: Vec<Duration>,
+}
+
+impl Timings {
+    fn new(mut samples: Vec<Duration>) -> Self {
+        samples.sort_unstable();
+        Self { samples }
+    }
+
+    /// Every run's duration, fastest first.
+    #[must_use]
+    pub fn samples(&self) -> &[Duration] {
+        &self.samples
+    }
+
+    /// The duration `percent` of the runs took at most (nearest rank), or
+    /// zero without runs.
+    #[must_use]
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    pub fn percentile(&self, percent: f64) -> Duration {
+        let Some(last) = self.samples.len().checked_sub(1) else {
+            return Duration::ZERO;
+        };
+        let rank = (percent.clamp(0.0, 100.0) / 100.0 * self.samples.len() as f64).ceil();
+        self.samples[(rank as usize).saturating_sub(1).min(last)]
+    }
+}
+
+/// Encode `value` and decode the output `iterations` times each, timing
+/// every run.
+///
+/// The value is copied for each encode before its timer starts, so the
+/// copy is not measured; converting input to a [`JsonValue`] is not either.
+///
+/// # Errors
+///
+/// Returns an error if `iterations` is zero, or if the value cannot be
+/// encoded or its encoding decoded with `decode_options`.
+pub fn run_bench(
+    value: &JsonValue,
+    encode_options: Option<EncodeOptions>,
+    decode_options: Option<DecodeOptions>,
+    iterations: usize,
+) -> Result<BenchReport> {
+    if iterations == 0 {
+        return Err(ToonError::message("Benchmark needs at least one iteration"));
+    }
+    let encoder = Encoder::new(encode_options);
+    let decoder = Decoder::new(decode_options);
+
+    let toon = encoder.try_encode(value.clone())?;
+    let lines = toon.lines().count();
+    let mut events = EventCount(0);
+    decoder.decode_into_sink(toon.lines().map(str::to_string), &mut events)?;
+
+    let mut encode = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let input = value.clone();
+        let started = Instant::now();
+        black_box(encoder.try_encode(input)?);
+        encode.push(started.elapsed());
+    }
+    let mut decode = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let started = Instant::now();
+        black_box(decoder.try_decode(&toon)?);
+        decode.push(started.elapsed());
+    }
+
+    Ok(BenchReport {
+        toon_bytes: toon.len(),
+        lines,
+        events: events.0,
+        encode: Timings::new(encode),
+        decode: Timings::new(decode),
+    })
+}
+
+/// Counts every event, closing ones included.
+struct EventCount(usize);
+
+impl EventSink for EventCount {
+    fn event(&mut self, _event: JsonStreamEvent) -> Result<()> {
+        self.0 += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+//! What this build of the crate supports (`toon --capabilities`).
+//!
+//! [`capabilities`] reports the crate version, the TOON spec versions it
+//! implements, the cargo features it was compiled with, and the options used
+//! when none are given, so tools driving `toon` can check the installed build
+//! before relying on an optional subcommand or extension.
+
+use serde::Serialize;
+
+use crate::options::{
+    DecodeOptions, EncodeOptions, resolve_decode_options, resolve_encode_options,
+};
+use crate::shared::directive::DIRECTIVE_VERSION;
+
+/// TOON spec version this crate implements.
+pub const SPEC_VERSION: &str = "3.0";
+
+/// Cargo features of this crate, paired with whether this build enabled them.
+const FEATURES: &[(&str, bool)] = &[
+    ("cli", cfg!(feature = "cli")),
+    ("internals", cfg!(feature = "internals")),
+    ("conformance", cfg!(feature = "conformance")),
+    ("async-stream", cfg!(feature = "async-stream")),
+    ("wasm", cfg!(feature = "wasm")),
+    ("difftest", cfg!(feature = "difftest")),
+    ("lsp", cfg!(feature = "lsp")),
+    ("arrow", cfg!(feature = "arrow")),
+    ("sqlite", cfg!(feature = "sqlite")),
+    ("server", cfg!(feature = "server")),
+    ("raw", cfg!(feature = "raw")),
+];
+
+/// Description of this build. Serializes with camelCase field names.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    /// Crate version (`0.2.1`).
+    pub version: &'static str,
+    /// TOON spec versions that encode and decode follow.
+    pub spec_versions: Vec<&'static str>,
+    /// Newest `#%toon` directive version the decoder reads.
+    pub directive_version: u32,
+    /// Enabled cargo features, in manifest order.
+    pub features: Vec<&'static str>,
+    /// Encode settings used when an option is left unset. A `null`
+    /// `flattenDepth` means unlimited.
+    pub default_encode_options: EncodeOptions,
+    /// Decode settings used when an option is left unset.
+    pub default_decode_options: DecodeOptions,
+}
+
+impl Capabilities {
+    /// Whether this build enabled the cargo feature `name`.
+    #[must_use]
+    pub fn has_feature(&self, name: &str) -> bool {
+        self.features.contains(&name)
+    }
+}
+
+/// Describe this build.
+#[must_use]
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        spec_versions: vec![SPEC_VERSION],
+        directive_version: DIRECTIVE_VERSION,
+        features: FEATURES
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| *name)
+            .collect(),
+        default_encode_options: default_encode_options(),
+        default_decode_options: default_decode_options(),
+    }
+}
+
+fn default_encode_options() -> EncodeOptions {
+    let resolved = resolve_encode_options(None);
+    EncodeOptions {
+        indent: Some(resolved.indent),
+        delimiter: Some(resolved.delimiter),
+        key_folding: Some(resolved.key_folding),
+        flatten_depth: (resolved.flatten_depth != usize::MAX).then_some(resolved.flatten_depth),
+        replacer: None,
+        empty_string: Some(resolved.empty_string),
+        typed_headers: Some(resolved.typed_headers),
+        columnar: Some(resolved.columnar),
+        sparse_threshold: resolved.sparse_threshold,
+        run_length: resolved.run_length,
+        long_string: Some(resolved.long_string),
+        emit_directive: Some(resolved.emit_directive),
+        anchors: Some(resolved.anchors),
+        serde_key_order: Some(resolved.serde_key_order),
+        column_order: None,
+        columns: resolved.columns,
+        row_filter: None,
+        row_encoder: None,
+        align_columns: Some(resolved.align_columns),
+        lossless_numbers: Some(resolved.lossless_numbers),
+        always_mark_delimiter: Some(resolved.always_mark_delimiter),
+        nfc: Some(resolved.nfc),
+        reject_invisible: Some(resolved.reject_invisible),
+    }
+}
+
+fn default_decode_options() -> DecodeOptions {
+    let resolved = resolve_decode_options(None);
+    DecodeOptions {
+        indent: Some(resolved.indent),
+        strict: Some(resolved.strict),
+        expand_paths: Some(resolved.expand_paths),
+        missing_cell: Some(resolved.missing_cell),
+        empty_cell: Some(resolved.empty_cell),
+        column_types: resolved.column_types,
+        typed_headers: Some(resolved.typed_headers),
+        columnar: Some(resolved.columnar),
+        run_length: Some(resolved.run_length),
+        sparse: Some(resolved.sparse),
+        pedantic: Some(resolved.pedantic),
+        tab_width: resolved.tab_width,
+        infer_lengths: Some(resolved.infer_lengths),
+        anchors: Some(resolved.anchors),
+        interpolate_env: Some(resolved.interpolate_env),
+        lossless_numbers: Some(resolved.lossless_numbers),
+        delimiter: resolved.delimiter,
+        ..DecodeOptions::default()
+    }
+}
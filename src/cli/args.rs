@@ -1,9 +1,14 @@
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
+use crate::detect::{FormatGuess, detect_format};
+use crate::error::ToonError;
+use crate::options::{ExpandPathsMode, KeyFoldingMode, PathPattern, parse_delimiter};
+
 /// TOON CLI — Convert between JSON and TOON formats
 #[derive(Parser, Debug)]
 #[command(name = "toon", version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true)]
 #[allow(clippy::struct_excessive_bools)]
 #[command(after_help = "EXAMPLES:
     toon input.json                  # Encode JSON to TOON (stdout)
@@ -11,8 +16,23 @@ use std::path::PathBuf;
     toon input.json -o output.toon   # Encode to file
     cat data.json | toon --encode    # Encode from stdin
     cat data.toon | toon --decode    # Decode from stdin
-    toon input.json --stats          # Show token statistics")]
+    toon input.json --stats          # Show token statistics
+    toon input.json -o out.toon --also-json out.json
+    toon explain input.toon          # Annotate TOON with structure comments
+    toon debug-decode input.toon     # Trace the decoder line by line
+    toon viz input.toon              # Diagram keys and array shapes
+    cat data | toon pipe --to toon   # Auto-detect JSON or TOON and convert
+    toon batch data/ --out-dir out/  # Convert every .json and .toon file in parallel
+    toon input.json --profile llm-compact
+    toon input.json --columns users=id,name,email
+    toon input.json --transform rules.toml
+    toon --jsonrpc                   # Long-lived JSON-RPC server on stdio
+    toon --jsonrpc --jobs 8          # ...answering requests on 8 threads
+    toon --capabilities --json       # Describe this build for other tools")]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Input file path (omit or use "-" to read from stdin)
     #[arg(value_name = "INPUT")]
     pub input: Option<PathBuf>,
@@ -21,41 +41,752 @@ pub struct Args {
     #[arg(short, long, value_name = "FILE")]
     pub output: Option<PathBuf>,
 
-    /// Encode JSON to TOON (auto-detected by default)
+    /// Also write the normalized input JSON to FILE, from the same parse as the TOON (encode only)
+    #[arg(long, value_name = "FILE")]
+    pub also_json: Option<PathBuf>,
+
+    /// Encode JSON to TOON (auto-detected from the extension or stdin content)
     #[arg(short, long, conflicts_with = "decode")]
     pub encode: bool,
 
-    /// Decode TOON to JSON (auto-detected by default)
+    /// Decode TOON to JSON (auto-detected from the extension or stdin content)
     #[arg(short, long, conflicts_with = "encode")]
     pub decode: bool,
 
-    /// Delimiter for arrays: comma (,), tab (\t), or pipe (|)
-    #[arg(long, default_value = ",", value_parser = parse_delimiter)]
-    pub delimiter: char,
+    /// Start from a preset of options; flags given explicitly override it
+    #[arg(long, value_enum, value_name = "NAME")]
+    pub profile: Option<ProfileArg>,
 
-    /// Indentation size (spaces)
-    #[arg(long, default_value = "2", value_parser = clap::value_parser!(u8).range(0..=16))]
-    pub indent: u8,
+    /// Delimiter for arrays: comma (,), tab (\t), or pipe (|); on decode, assumed for headers without one [default: ,]
+    #[arg(long, value_parser = parse_delimiter)]
+    pub delimiter: Option<char>,
+
+    /// Indentation size (spaces) [default: 2]
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=16))]
+    pub indent: Option<u8>,
 
     /// Disable strict mode for decoding (allows lenient parsing)
     #[arg(long = "no-strict")]
     pub no_strict: bool,
 
-    /// Key folding mode: off or safe
-    #[arg(long, value_enum, default_value = "off")]
-    pub key_folding: KeyFoldingArg,
+    /// Reject TOON that is not in canonical form, listing every violation (decode only)
+    #[arg(long)]
+    pub pedantic: bool,
+
+    /// Key folding mode: off or safe [default: off]
+    #[arg(long, value_enum)]
+    pub key_folding: Option<KeyFoldingArg>,
 
     /// Maximum folded segment count when key folding is enabled
     #[arg(long, value_name = "N")]
     pub flatten_depth: Option<usize>,
 
-    /// Path expansion mode: off or safe (decode only)
-    #[arg(long, value_enum, default_value = "off")]
-    pub expand_paths: ExpandPathsArg,
+    /// Path expansion mode: off or safe (decode only) [default: off]
+    #[arg(long, value_enum)]
+    pub expand_paths: Option<ExpandPathsArg>,
 
     /// Show token statistics (encode only)
     #[arg(long)]
     pub stats: bool,
+
+    /// Calibrate --stats token estimates with a JSON array of {"text", "tokens"} samples
+    #[arg(long, value_name = "FILE")]
+    pub token_samples: Option<PathBuf>,
+
+    /// Abort if conversion would lose information (precision, duplicate keys)
+    #[arg(long)]
+    pub fail_on_lossy: bool,
+
+    /// Warn about output language models tend to misread, with the option that avoids it (encode only)
+    #[arg(long)]
+    pub lint: bool,
+
+    /// Emit/read column type annotations in tabular headers ({id:int,name:str})
+    #[arg(long)]
+    pub typed_headers: bool,
+
+    /// Encode tabular arrays as one inline array per field; reassemble rows on decode
+    #[arg(long)]
+    pub columnar: bool,
+
+    /// Pad tabular cells so delimiters line up in columns (encode only)
+    #[arg(long)]
+    pub align_columns: bool,
+
+    /// Mark the delimiter in every array header, even the default comma: `[2,]` (encode only)
+    #[arg(long)]
+    pub always_mark_delimiter: bool,
+
+    /// Normalize keys and strings to Unicode NFC (encode only)
+    #[arg(long)]
+    pub nfc: bool,
+
+    /// Fail on keys and strings with zero-width or bidi control characters instead of quoting them (encode only)
+    #[arg(long)]
+    pub reject_invisible: bool,
+
+    /// Keep numbers as written (1.230, 1e10, -0) instead of reformatting them (decode only)
+    #[arg(long)]
+    pub lossless_numbers: bool,
+
+    /// Write primitive arrays with at least this fraction of nulls as `index: value` lines; read them back on decode
+    #[arg(long, value_name = "FRACTION", num_args = 0..=1, default_missing_value = "0.5")]
+    pub sparse_threshold: Option<f64>,
+
+    /// Write runs of at least MIN identical values as `value*count`; expand them on decode
+    #[arg(long, value_name = "MIN", num_args = 0..=1, default_missing_value = "3")]
+    pub run_length: Option<usize>,
+
+    /// Write repeated nested objects once and alias later copies (`*a1`); expand aliases on decode
+    #[arg(long)]
+    pub anchors: bool,
+
+    /// Keep only FIELDS of the objects in arrays at PATH, e.g. users=id,name (encode only; repeatable)
+    #[arg(long, value_name = "PATH=FIELDS", value_parser = parse_columns)]
+    pub columns: Vec<(PathPattern, Vec<String>)>,
+
+    /// Apply drop/rename/truncate/quantize rules from a JSON or TOML file (encode only)
+    #[arg(long, value_name = "RULES")]
+    pub transform: Option<PathBuf>,
+
+    /// Replace `key: !include path` values with the decoded file, relative to the input (decode only)
+    #[arg(long)]
+    pub allow_includes: bool,
+
+    /// Refuse includes that resolve outside DIR, by absolute path, `..`, or symlink (decode only)
+    #[arg(long, value_name = "DIR", requires = "allow_includes")]
+    pub include_root: Option<PathBuf>,
+
+    /// Replace `${VAR}` in string values with environment variables (decode only)
+    #[arg(long)]
+    pub interpolate_env: bool,
+
+    /// Start the output with a `#%toon` line recording delimiter and indent (encode only)
+    #[arg(long)]
+    pub directive: bool,
+
+    /// Abort before reading more than BYTES of input
+    #[arg(long, value_name = "BYTES")]
+    pub max_input_bytes: Option<usize>,
+
+    /// Abort before writing more than BYTES of output
+    #[arg(long, value_name = "BYTES")]
+    pub max_output_bytes: Option<usize>,
+
+    /// Answer encode/decode/stats JSON-RPC requests on stdin, one message per line
+    #[arg(long, conflicts_with_all = ["input", "output", "encode", "decode"])]
+    pub jsonrpc: bool,
+
+    /// Answer --jsonrpc requests on N worker threads, replying in request order (0: one per core; default 1)
+    #[arg(
+        long,
+        value_name = "N",
+        requires = "jsonrpc",
+        conflicts_with_all = ["input", "output", "encode", "decode"]
+    )]
+    pub jobs: Option<usize>,
+
+    /// Print the version, spec versions, enabled features, and default options of this build
+    #[arg(long, conflicts_with_all = ["input", "output", "encode", "decode", "jsonrpc"])]
+    pub capabilities: bool,
+
+    /// Print --capabilities as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Re-print a TOON document with comments describing its structure
+    Explain(ExplainArgs),
+
+    /// Show, line by line, the events the decoder emits and the checks it runs
+    DebugDecode(DebugDecodeArgs),
+
+    /// Estimate the LLM token count of a document, or list its lexical tokens
+    Tokens(TokensArgs),
+
+    /// Report nesting depth, value counts, and sizes of a document
+    Stats(StatsArgs),
+
+    /// Draw the structure of a document (keys, types, array shapes) as DOT or Mermaid
+    Viz(VizArgs),
+
+    /// Read JSON or TOON (detected from the content), transform it, and write either format
+    Pipe(PipeArgs),
+
+    /// Encode a document repeatedly and check that every output is byte-identical
+    AuditDeterminism(AuditDeterminismArgs),
+
+    /// Time encoding and decoding a document with the given options
+    Bench(BenchArgs),
+
+    /// Merge two edited versions of a TOON or JSON document against their common base
+    Merge3(Merge3Args),
+
+    /// List converted files with sizes, token counts, and canonical content hashes
+    Manifest(ManifestArgs),
+
+    /// Convert many files in parallel: .json to .toon and .toon to .json
+    Batch(BatchArgs),
+
+    /// Print or replace the ---toon frontmatter of a markdown document
+    Frontmatter(FrontmatterArgs),
+
+    /// Fill `${placeholders}` in a TOON template from a variables document
+    Render(RenderArgs),
+
+    /// Structural diff, with the arguments git passes to an external diff driver
+    GitDiff(GitDiffArgs),
+
+    /// Structural merge, with the arguments git passes to a merge driver
+    GitMerge(GitMergeArgs),
+
+    /// Compare encode/decode output against an external reference CLI
+    #[cfg(feature = "difftest")]
+    Difftest(DifftestArgs),
+
+    /// Run a Language Server Protocol server over stdio
+    #[cfg(feature = "lsp")]
+    Lsp,
+
+    /// Import a tabular array into a database table, or export a query as one
+    #[cfg(feature = "sqlite")]
+    Sql(SqlArgs),
+
+    /// Serve `/encode` and `/decode` over HTTP
+    #[cfg(feature = "server")]
+    Serve(ServeArgs),
+}
+
+#[derive(clap::Args, Debug)]
+#[command(after_help = "EXAMPLES:
+    toon explain data.toon
+    cat data.toon | toon explain")]
+pub struct ExplainArgs {
+    /// Input TOON file (omit or use "-" to read from stdin)
+    #[arg(value_name = "INPUT")]
+    pub input: Option<PathBuf>,
+
+    /// Indentation size (spaces)
+    #[arg(long, default_value = "2", value_parser = clap::value_parser!(u8).range(0..=16))]
+    pub indent: u8,
+
+    /// Disable strict mode (allows lenient parsing)
+    #[arg(long = "no-strict")]
+    pub no_strict: bool,
+
+    /// Read column type annotations in tabular headers ({id:int,name:str})
+    #[arg(long)]
+    pub typed_headers: bool,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(after_help = "EXAMPLES:
+    toon debug-decode data.toon
+    toon debug-decode --no-strict data.toon")]
+pub struct DebugDecodeArgs {
+    /// Input TOON file (omit or use "-" to read from stdin)
+    #[arg(value_name = "INPUT")]
+    pub input: Option<PathBuf>,
+
+    /// Indentation size (spaces)
+    #[arg(long, default_value = "2", value_parser = clap::value_parser!(u8).range(0..=16))]
+    pub indent: u8,
+
+    /// Disable strict mode (allows lenient parsing)
+    #[arg(long = "no-strict")]
+    pub no_strict: bool,
+
+    /// Read column type annotations in tabular headers ({id:int,name:str})
+    #[arg(long)]
+    pub typed_headers: bool,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(after_help = "EXAMPLES:
+    toon tokens data.toon
+    toon tokens --syntax data.toon   # one JSON object per token
+    toon tokens data.toon --token-samples counts.json")]
+pub struct TokensArgs {
+    /// Input file (omit or use "-" to read from stdin)
+    #[arg(value_name = "INPUT")]
+    pub input: Option<PathBuf>,
+
+    /// Print the TOON lexical tokens (kind and byte span) as JSON lines
+    #[arg(long)]
+    pub syntax: bool,
+
+    /// Calibrate the estimate with a JSON array of {"text", "tokens"} samples
+    #[arg(long, value_name = "FILE", conflicts_with = "syntax")]
+    pub token_samples: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(after_help = "EXAMPLES:
+    toon stats data.toon
+    toon stats --streaming huge.toon --json")]
+pub struct StatsArgs {
+    /// Input file, TOON or .json (omit or use "-" to read from stdin)
+    #[arg(value_name = "INPUT")]
+    pub input: Option<PathBuf>,
+
+    /// Read TOON input line by line and never build the decoded value
+    #[arg(long)]
+    pub streaming: bool,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    /// Indentation size (spaces)
+    #[arg(long, default_value = "2", value_parser = clap::value_parser!(u8).range(0..=16))]
+    pub indent: u8,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(after_help = "EXAMPLES:
+    toon viz payload.toon | dot -Tsvg -o payload.svg
+    toon viz payload.json --format mermaid >> SCHEMA.md")]
+pub struct VizArgs {
+    /// Input file, TOON or .json (omit or use "-" to read from stdin)
+    #[arg(value_name = "INPUT")]
+    pub input: Option<PathBuf>,
+
+    /// Diagram language
+    #[arg(long, value_enum, default_value = "dot")]
+    pub format: VizFormatArg,
+
+    /// Output file path (stdout if omitted)
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Indentation size (spaces)
+    #[arg(long, default_value = "2", value_parser = clap::value_parser!(u8).range(0..=16))]
+    pub indent: u8,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(after_help = "EXAMPLES:
+    cat data.json | toon pipe --to toon --key-folding safe
+    cat data.toon | toon pipe --transform rules.toml --expand-paths safe
+    toon pipe data.txt --to json -o data.json")]
+pub struct PipeArgs {
+    /// Input file, JSON or TOON (omit or use "-" to read from stdin)
+    #[arg(value_name = "INPUT")]
+    pub input: Option<PathBuf>,
+
+    /// Output file path (stdout if omitted)
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Input format; detected from the content if omitted
+    #[arg(long, value_enum)]
+    pub from: Option<FormatArg>,
+
+    /// Output format; the input format if omitted
+    #[arg(long, value_enum)]
+    pub to: Option<FormatArg>,
+
+    /// Apply drop/rename/truncate/quantize rules from a JSON or TOML file
+    #[arg(long, value_name = "RULES")]
+    pub transform: Option<PathBuf>,
+
+    /// Expand dotted keys (a.b: 1) into nested objects
+    #[arg(long, value_enum, default_value = "off")]
+    pub expand_paths: ExpandPathsArg,
+
+    /// Fold single-key object chains into dotted keys in TOON output
+    #[arg(long, value_enum, default_value = "off")]
+    pub key_folding: KeyFoldingArg,
+
+    /// Delimiter for TOON output arrays: comma (,), tab (\t), or pipe (|)
+    #[arg(long, default_value = ",", value_parser = parse_delimiter)]
+    pub delimiter: char,
+
+    /// Indentation size (spaces)
+    #[arg(long, default_value = "2", value_parser = clap::value_parser!(u8).range(0..=16))]
+    pub indent: u8,
+
+    /// Disable strict mode for TOON input
+    #[arg(long = "no-strict")]
+    pub no_strict: bool,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(after_help = "EXAMPLES:
+    toon audit-determinism data.json --runs 5
+    toon audit-determinism data.toon --runs 20 --threads 4")]
+pub struct AuditDeterminismArgs {
+    /// Input file, JSON (.json) or TOON
+    #[arg(value_name = "INPUT")]
+    pub input: PathBuf,
+
+    /// Encodes per thread
+    #[arg(long, default_value = "5", value_parser = clap::value_parser!(u32).range(1..))]
+    pub runs: u32,
+
+    /// Threads encoding at the same time
+    #[arg(long, default_value = "1", value_parser = clap::value_parser!(u32).range(1..))]
+    pub threads: u32,
+
+    /// Indentation size (spaces)
+    #[arg(long, default_value = "2", value_parser = clap::value_parser!(u8).range(0..=16))]
+    pub indent: u8,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(after_help = "EXAMPLES:
+    toon bench --input big.json --iterations 20
+    toon bench --input big.json --delimiter tab --key-folding safe")]
+pub struct BenchArgs {
+    /// Input file, JSON (.json) or TOON
+    #[arg(short, long, value_name = "FILE")]
+    pub input: PathBuf,
+
+    /// Encodes and decodes to time
+    #[arg(long, default_value = "20", value_parser = clap::value_parser!(u32).range(1..))]
+    pub iterations: u32,
+
+    /// Start from an encode preset; flags given explicitly override it
+    #[arg(long, value_enum, value_name = "NAME")]
+    pub profile: Option<ProfileArg>,
+
+    /// Delimiter for arrays: comma (,), tab (\t), or pipe (|) [default: ,]
+    #[arg(long, value_parser = parse_delimiter)]
+    pub delimiter: Option<char>,
+
+    /// Indentation size (spaces) [default: 2]
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=16))]
+    pub indent: Option<u8>,
+
+    /// Key folding mode; decoding expands the folded keys again [default: off]
+    #[arg(long, value_enum)]
+    pub key_folding: Option<KeyFoldingArg>,
+
+    /// Disable strict mode when decoding
+    #[arg(long = "no-strict")]
+    pub no_strict: bool,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(after_help = "EXAMPLES:
+    toon merge3 base.toon ours.toon theirs.toon
+    toon merge3 base.json ours.json theirs.json -o merged.json")]
+pub struct Merge3Args {
+    /// Common ancestor of both versions
+    #[arg(value_name = "BASE")]
+    pub base: PathBuf,
+
+    /// Our version; its extension (.json or .toon) sets the output format
+    #[arg(value_name = "OURS")]
+    pub ours: PathBuf,
+
+    /// Their version
+    #[arg(value_name = "THEIRS")]
+    pub theirs: PathBuf,
+
+    /// Output file path (stdout if omitted)
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Indentation size (spaces)
+    #[arg(long, default_value = "2", value_parser = clap::value_parser!(u8).range(0..=16))]
+    pub indent: u8,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(after_help = "EXAMPLES:
+    toon manifest out/
+    toon manifest out/ --format toon -o MANIFEST.toon")]
+pub struct ManifestArgs {
+    /// Directory to scan for .toon and .json files
+    #[arg(value_name = "DIR")]
+    pub dir: PathBuf,
+
+    /// Manifest format
+    #[arg(long, value_enum, default_value = "json")]
+    pub format: ManifestFormat,
+
+    /// Output file path (stdout if omitted)
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+#[allow(clippy::struct_excessive_bools)]
+#[command(after_help = "EXAMPLES:
+    toon batch data/                 # every .json and .toon file under data/
+    toon batch a.json b.toon --out-dir out/
+    toon batch data/ --jobs 4         # convert at most 4 files at a time
+    toon batch data/ --dry-run        # list what would be written, write nothing
+    toon batch data/ --summary-json   # end with a JSON summary of the run on stderr")]
+pub struct BatchArgs {
+    /// Files, and directories to search for .json and .toon files
+    #[arg(value_name = "PATH", required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// Write outputs under DIR instead of next to their inputs
+    #[arg(long, value_name = "DIR")]
+    pub out_dir: Option<PathBuf>,
+
+    /// Indentation size (spaces)
+    #[arg(long, default_value = "2", value_parser = clap::value_parser!(u8).range(0..=16))]
+    pub indent: u8,
+
+    /// Delimiter for tabular arrays: , (comma), \t or tab, | (pipe)
+    #[arg(long, default_value = ",", value_parser = parse_delimiter)]
+    pub delimiter: char,
+
+    /// Disable strict mode when decoding
+    #[arg(long = "no-strict")]
+    pub no_strict: bool,
+
+    /// Follow symlinks found in directories when they resolve inside the
+    /// directory (one that escapes it is an error); by default they are skipped
+    #[arg(long)]
+    pub follow_symlinks: bool,
+
+    /// Replace existing files next to their inputs whose content differs
+    /// from the output (outputs under --out-dir are always replaced)
+    #[arg(long)]
+    pub overwrite: bool,
+
+    /// List the files that would be converted, overwritten, or skipped as up
+    /// to date, with their output sizes, without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Convert up to N files at once (0: one per core)
+    #[arg(long, value_name = "N", default_value = "0")]
+    pub jobs: usize,
+
+    /// When done, print a summary to stderr: files converted and failed,
+    /// bytes and estimated tokens in and out, elapsed time, and throughput
+    #[arg(long, conflicts_with = "dry_run")]
+    pub summary: bool,
+
+    /// Like --summary, as one line of JSON
+    #[arg(long, conflicts_with_all = ["dry_run", "summary"])]
+    pub summary_json: bool,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(after_help = "EXAMPLES:
+    toon frontmatter prompt.md                    # print the frontmatter as TOON
+    toon frontmatter prompt.md --json
+    toon frontmatter prompt.md --set vars.json -o prompt.md")]
+pub struct FrontmatterArgs {
+    /// Markdown document (omit or use "-" to read from stdin)
+    #[arg(value_name = "INPUT")]
+    pub input: Option<PathBuf>,
+
+    /// Replace the frontmatter with this document (.json read as JSON, otherwise TOON)
+    #[arg(long, value_name = "FILE")]
+    pub set: Option<PathBuf>,
+
+    /// Print the frontmatter as JSON
+    #[arg(long, conflicts_with = "set")]
+    pub json: bool,
+
+    /// Output file path (stdout if omitted)
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Indentation size (spaces)
+    #[arg(long, default_value = "2", value_parser = clap::value_parser!(u8).range(0..=16))]
+    pub indent: u8,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(after_help = "EXAMPLES:
+    toon render prompt.toon --vars vars.json
+    toon render prompt.toon --vars vars.toon --json -o payload.json")]
+pub struct RenderArgs {
+    /// Template TOON file (omit or use "-" to read from stdin)
+    #[arg(value_name = "TEMPLATE")]
+    pub template: Option<PathBuf>,
+
+    /// Variables document (.json read as JSON, otherwise TOON); `${a.b}` reads nested keys
+    #[arg(long, value_name = "FILE")]
+    pub vars: PathBuf,
+
+    /// Print the result as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    /// Output file path (stdout if omitted)
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Indentation size (spaces)
+    #[arg(long, default_value = "2", value_parser = clap::value_parser!(u8).range(0..=16))]
+    pub indent: u8,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(after_help = "SETUP:
+    echo '*.toon diff=toon' >> .gitattributes
+    git config diff.toon.command 'toon git-diff'")]
+pub struct GitDiffArgs {
+    /// Path of the file in the repository; a .json extension reads both versions as JSON
+    #[arg(value_name = "PATH")]
+    pub path: PathBuf,
+
+    /// Old version of the file
+    #[arg(value_name = "OLD_FILE")]
+    pub old_file: Option<PathBuf>,
+
+    #[arg(value_name = "OLD_HEX")]
+    pub old_hex: Option<String>,
+
+    #[arg(value_name = "OLD_MODE")]
+    pub old_mode: Option<String>,
+
+    /// New version of the file
+    #[arg(value_name = "NEW_FILE")]
+    pub new_file: Option<PathBuf>,
+
+    #[arg(value_name = "NEW_HEX")]
+    pub new_hex: Option<String>,
+
+    #[arg(value_name = "NEW_MODE")]
+    pub new_mode: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(after_help = "SETUP:
+    echo '*.toon merge=toon' >> .gitattributes
+    git config merge.toon.driver 'toon git-merge %O %A %B %L %P'")]
+pub struct GitMergeArgs {
+    /// Common ancestor version (%O)
+    #[arg(value_name = "BASE")]
+    pub base: PathBuf,
+
+    /// Current version (%A); the merge result is written back to it
+    #[arg(value_name = "CURRENT")]
+    pub current: PathBuf,
+
+    /// Other branch's version (%B)
+    #[arg(value_name = "OTHER")]
+    pub other: PathBuf,
+
+    /// Conflict marker size (%L)
+    #[arg(value_name = "MARKER_SIZE")]
+    pub marker_size: Option<usize>,
+
+    /// Path of the file in the repository (%P); a .json extension reads all versions as JSON
+    #[arg(value_name = "PATH")]
+    pub path: Option<PathBuf>,
+}
+
+#[cfg(feature = "difftest")]
+#[derive(clap::Args, Debug)]
+#[command(after_help = "EXAMPLES:
+    toon difftest --ref \"node toon-cli\"
+    toon difftest --ref \"npx @toon-format/cli\" --cases 500 --seed 7")]
+pub struct DifftestArgs {
+    /// Reference command (program and arguments), invoked with --encode/--decode
+    #[arg(long = "ref", value_name = "CMD")]
+    pub reference: String,
+
+    /// Number of generated cases
+    #[arg(long, default_value = "100")]
+    pub cases: usize,
+
+    /// Seed for the case generator
+    #[arg(long, default_value = "0")]
+    pub seed: u64,
+
+    /// Maximum nesting depth of generated documents
+    #[arg(long, default_value = "3")]
+    pub max_depth: usize,
+
+    /// Delimiter passed to both implementations
+    #[arg(long, default_value = ",", value_parser = parse_delimiter)]
+    pub delimiter: char,
+
+    /// Indentation size passed to both implementations
+    #[arg(long, default_value = "2", value_parser = clap::value_parser!(u8).range(0..=16))]
+    pub indent: u8,
+}
+
+#[cfg(feature = "sqlite")]
+#[derive(clap::Args, Debug)]
+#[command(after_help = "EXAMPLES:
+    toon sql --db data.sqlite --table users users.toon       # import
+    toon sql --db data.sqlite --table users                  # export the table
+    toon sql --db data.sqlite --query 'SELECT id, name FROM users' --key users")]
+pub struct SqlArgs {
+    /// Database file (created on import if missing)
+    #[arg(long, value_name = "FILE")]
+    pub db: PathBuf,
+
+    /// Table to import into, or to export when no query is given
+    #[arg(long, value_name = "NAME")]
+    pub table: Option<String>,
+
+    /// Query whose result is exported
+    #[arg(long, value_name = "SQL", conflicts_with = "input")]
+    pub query: Option<String>,
+
+    /// Key of the tabular array to import (default: the first one), or of the exported array
+    #[arg(long, value_name = "KEY")]
+    pub key: Option<String>,
+
+    /// TOON file to import (use "-" for stdin); omit to export
+    #[arg(value_name = "INPUT")]
+    pub input: Option<PathBuf>,
+
+    /// Output file path for exports (stdout if omitted)
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Indentation size (spaces)
+    #[arg(long, default_value = "2", value_parser = clap::value_parser!(u8).range(0..=16))]
+    pub indent: u8,
+}
+
+#[cfg(feature = "server")]
+#[derive(clap::Args, Debug)]
+#[command(after_help = "EXAMPLES:
+    toon serve --listen 127.0.0.1:8080
+    curl --data-binary @data.json 'http://127.0.0.1:8080/encode?delimiter=tab'
+    curl --data-binary @data.toon 'http://127.0.0.1:8080/decode?indent=0'")]
+pub struct ServeArgs {
+    /// Address to listen on
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8080")]
+    pub listen: String,
+
+    /// Largest accepted request body; larger ones get a 413
+    #[arg(long, value_name = "BYTES", default_value = "16777216")]
+    pub max_body_bytes: u64,
+
+    /// Most connections handled at once; more wait to be accepted
+    #[arg(long, value_name = "N", default_value = "64", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_connections: u64,
+
+    /// Close a connection whose reads or writes stall for this many seconds (0: never)
+    #[arg(long, value_name = "SECONDS", default_value = "30")]
+    pub timeout: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ManifestFormat {
+    Json,
+    Toon,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FormatArg {
+    Json,
+    Toon,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum VizFormatArg {
+    Dot,
+    Mermaid,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
@@ -64,23 +795,62 @@ pub enum KeyFoldingArg {
     Safe,
 }
 
+impl From<KeyFoldingArg> for KeyFoldingMode {
+    fn from(arg: KeyFoldingArg) -> Self {
+        match arg {
+            KeyFoldingArg::Off => Self::Off,
+            KeyFoldingArg::Safe => Self::Safe,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
 pub enum ExpandPathsArg {
     Off,
     Safe,
 }
 
-fn parse_delimiter(s: &str) -> Result<char, String> {
-    match s {
-        "," | "comma" => Ok(','),
-        "|" | "pipe" => Ok('|'),
-        "\\t" | "\t" | "tab" => Ok('\t'),
-        _ => Err(format!(
-            "Invalid delimiter \"{s}\". Valid delimiters are: comma (,), tab (\\t), pipe (|)"
-        )),
+impl From<ExpandPathsArg> for ExpandPathsMode {
+    fn from(arg: ExpandPathsArg) -> Self {
+        match arg {
+            ExpandPathsArg::Off => Self::Off,
+            ExpandPathsArg::Safe => Self::Safe,
+        }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ProfileArg {
+    /// Encode: tab delimiters, safe key folding
+    LlmCompact,
+    /// Encode: four-space indentation with a `#%toon` directive
+    HumanReadable,
+    /// Encode: the spec's canonical form, every extension off
+    Canonical,
+    /// Decode: strict validation (the default)
+    Strict,
+    /// Decode: hand-typed input (tabs, omitted lengths, strict off)
+    Lenient,
+    /// Decode: lenient, with missing and empty cells read as null
+    Repair,
+}
+
+/// Parse a `--columns` value: `users=id,name` or `orders.*.items=sku,qty`.
+fn parse_columns(s: &str) -> crate::error::Result<(PathPattern, Vec<String>)> {
+    let (path, fields) = s.split_once('=').ok_or_else(|| {
+        ToonError::message(format!(
+            "Invalid columns \"{s}\". Expected PATH=FIELDS, e.g. users=id,name"
+        ))
+    })?;
+    let fields = fields
+        .split(',')
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+        .map(str::to_string)
+        .collect();
+    Ok((PathPattern::new(path.trim()), fields))
+}
+
 impl Args {
     /// Detect the operation mode based on flags and file extension.
     #[must_use]
@@ -94,15 +864,15 @@ impl Args {
         }
 
         // Auto-detect based on file extension
-        if let Some(ref path) = self.input {
-            if let Some(ext) = path.extension() {
-                let ext = ext.to_string_lossy().to_lowercase();
-                if ext == "json" {
-                    return Mode::Encode;
-                }
-                if ext == "toon" {
-                    return Mode::Decode;
-                }
+        if let Some(ref path) = self.input
+            && let Some(ext) = path.extension()
+        {
+            let ext = ext.to_string_lossy().to_lowercase();
+            if ext == "json" {
+                return Mode::Encode;
+            }
+            if ext == "toon" {
+                return Mode::Decode;
             }
         }
 
@@ -110,6 +880,20 @@ impl Args {
         Mode::Encode
     }
 
+    /// Detect the operation mode once the input has been read: without
+    /// `--encode` or `--decode`, stdin that looks like TOON is decoded.
+    #[must_use]
+    pub fn detect_mode_for_input(&self, input: &str) -> Mode {
+        if self.is_stdin()
+            && !self.encode
+            && !self.decode
+            && detect_format(input) == FormatGuess::Toon
+        {
+            return Mode::Decode;
+        }
+        self.detect_mode()
+    }
+
     /// Returns true if reading from stdin.
     #[must_use]
     pub fn is_stdin(&self) -> bool {
@@ -129,27 +913,56 @@ mod tests {
 
     #[test]
     fn test_parse_delimiter() {
-        assert_eq!(parse_delimiter(","), Ok(','));
-        assert_eq!(parse_delimiter("|"), Ok('|'));
-        assert_eq!(parse_delimiter("\\t"), Ok('\t'));
-        assert_eq!(parse_delimiter("tab"), Ok('\t'));
+        assert_eq!(parse_delimiter(",").ok(), Some(','));
+        assert_eq!(parse_delimiter("|").ok(), Some('|'));
+        assert_eq!(parse_delimiter("\\t").ok(), Some('\t'));
+        assert_eq!(parse_delimiter("tab").ok(), Some('\t'));
         assert!(parse_delimiter("invalid").is_err());
     }
 
     #[test]
     fn test_detect_mode_explicit_flags() {
         let args = Args {
+            command: None,
             input: None,
             output: None,
+            also_json: None,
             encode: true,
             decode: false,
-            delimiter: ',',
-            indent: 2,
+            profile: None,
+            delimiter: None,
+            indent: None,
             no_strict: false,
-            key_folding: KeyFoldingArg::Off,
+            pedantic: false,
+            key_folding: None,
             flatten_depth: None,
-            expand_paths: ExpandPathsArg::Off,
+            expand_paths: None,
             stats: false,
+            token_samples: None,
+            fail_on_lossy: false,
+            lint: false,
+            typed_headers: false,
+            columnar: false,
+            align_columns: false,
+            lossless_numbers: false,
+            sparse_threshold: None,
+            run_length: None,
+            anchors: false,
+            columns: Vec::new(),
+            transform: None,
+            allow_includes: false,
+            include_root: None,
+            interpolate_env: false,
+            directive: false,
+            max_input_bytes: None,
+            max_output_bytes: None,
+            jsonrpc: false,
+            jobs: None,
+            capabilities: false,
+            json: false,
+            always_mark_delimiter: false,
+            nfc: false,
+            reject_invisible: false,
         };
         assert_eq!(args.detect_mode(), Mode::Encode);
     }
@@ -157,18 +970,60 @@ mod tests {
     #[test]
     fn test_detect_mode_by_extension() {
         let args = Args {
+            command: None,
             input: Some(PathBuf::from("data.toon")),
             output: None,
+            also_json: None,
             encode: false,
             decode: false,
-            delimiter: ',',
-            indent: 2,
+            profile: None,
+            delimiter: None,
+            indent: None,
             no_strict: false,
-            key_folding: KeyFoldingArg::Off,
+            pedantic: false,
+            key_folding: None,
             flatten_depth: None,
-            expand_paths: ExpandPathsArg::Off,
+            expand_paths: None,
             stats: false,
+            token_samples: None,
+            fail_on_lossy: false,
+            lint: false,
+            typed_headers: false,
+            columnar: false,
+            align_columns: false,
+            lossless_numbers: false,
+            sparse_threshold: None,
+            run_length: None,
+            anchors: false,
+            columns: Vec::new(),
+            transform: None,
+            allow_includes: false,
+            include_root: None,
+            interpolate_env: false,
+            directive: false,
+            max_input_bytes: None,
+            max_output_bytes: None,
+            jsonrpc: false,
+            jobs: None,
+            capabilities: false,
+            json: false,
+            always_mark_delimiter: false,
+            nfc: false,
+            reject_invisible: false,
         };
         assert_eq!(args.detect_mode(), Mode::Decode);
     }
+
+    #[test]
+    fn test_detect_mode_for_stdin_content() {
+        let args = Args::parse_from(["toon"]);
+        assert_eq!(args.detect_mode_for_input("id: 1\n"), Mode::Decode);
+        assert_eq!(args.detect_mode_for_input(r#"{"id": 1}"#), Mode::Encode);
+        assert_eq!(args.detect_mode_for_input("42"), Mode::Encode);
+
+        let args = Args::parse_from(["toon", "--encode"]);
+        assert_eq!(args.detect_mode_for_input("id: 1\n"), Mode::Encode);
+        let args = Args::parse_from(["toon", "data.json"]);
+        assert_eq!(args.detect_mode_for_input("id: 1\n"), Mode::Encode);
+    }
 }
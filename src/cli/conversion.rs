@@ -1,19 +1,19 @@
-use crate::cli::json_stream::json_stream_from_events;
-use crate::cli::json_stringify::json_stringify_lines;
+use crate::decode::columnar::reassemble_columnar;
 use crate::decode::decoders as decoder_impl;
-use crate::decode::event_builder::{build_node_from_events, node_to_json};
+use crate::decode::event_builder::{ValueSink, build_node_from_events, node_to_json};
 use crate::decode::expand::expand_paths_safe;
 use crate::error::{Result, ToonError};
-use crate::options::{
-    DecodeOptions, DecodeStreamOptions, EncodeOptions, ExpandPathsMode, resolve_decode_options,
-};
+use crate::json::{JsonWriterSink, json_stream_from_events, json_stringify_lines};
+use crate::options::{DecodeOptions, EncodeOptions, ExpandPathsMode, resolve_decode_options};
+use crate::sink::{EventSink, TeeSink};
 use crate::{JsonValue, StringOrNumberOrBoolOrNull};
+use std::io::Write;
 
 /// Encode JSON input to TOON lines.
 ///
 /// # Errors
 ///
-/// Returns an error if the JSON input is invalid.
+/// Returns an error if the JSON input is invalid or cannot be encoded.
 pub fn encode_to_toon_lines(
     input_json: &str,
     options: Option<EncodeOptions>,
@@ -21,7 +21,37 @@ pub fn encode_to_toon_lines(
     let value: serde_json::Value =
         serde_json::from_str(input_json).map_err(|err| ToonError::json_parse(&err))?;
     let converted = JsonValue::from(value);
-    Ok(crate::encode::encode_lines(converted, options))
+    crate::encode::try_encode_lines(converted, options)
+}
+
+/// Encode JSON input to TOON lines and write the normalized input as JSON to
+/// `json_writer`.
+///
+/// Both outputs are built from one event stream, so they always describe the
+/// same content.
+///
+/// # Errors
+///
+/// Returns an error if the JSON input is invalid, cannot be encoded, or the
+/// JSON cannot be written.
+pub fn encode_to_toon_lines_and_json<W: Write>(
+    input_json: &str,
+    options: Option<EncodeOptions>,
+    json_writer: W,
+    json_indent: usize,
+) -> Result<(Vec<String>, W)> {
+    let value: serde_json::Value =
+        serde_json::from_str(input_json).map_err(|err| ToonError::json_parse(&err))?;
+    let mut tee = TeeSink(
+        JsonWriterSink::new(json_writer, json_indent),
+        ValueSink::new(),
+    );
+    for event in crate::encode::encode_stream_events(JsonValue::from(value), None) {
+        tee.event(event)?;
+    }
+    let TeeSink(json, parsed) = tee;
+    let lines = crate::encode::try_encode_lines(parsed.finish()?, options)?;
+    Ok((lines, json.finish()?))
 }
 
 /// Decode TOON input into JSON output chunks.
@@ -32,42 +62,44 @@ pub fn encode_to_toon_lines(
 pub fn decode_to_json_chunks(input: &str, options: Option<DecodeOptions>) -> Result<Vec<String>> {
     let resolved = resolve_decode_options(options);
 
-    if resolved.expand_paths == ExpandPathsMode::Safe {
+    if resolved.expand_paths == ExpandPathsMode::Safe || resolved.columnar {
         let value = decode_to_value(input, &resolved)?;
         return Ok(json_stringify_lines(&value, resolved.indent));
     }
 
-    let events = decode_events(input, resolved.indent, resolved.strict)?;
+    let events = decode_events(input, &resolved)?;
     json_stream_from_events(events, resolved.indent)
 }
 
-fn decode_events(input: &str, indent: usize, strict: bool) -> Result<Vec<crate::JsonStreamEvent>> {
+fn decode_events(
+    input: &str,
+    options: &crate::options::ResolvedDecodeOptions,
+) -> Result<Vec<crate::JsonStreamEvent>> {
     let lines = input
         .split('\n')
         .map(std::string::ToString::to_string)
         .collect::<Vec<_>>();
 
-    decoder_impl::decode_stream_sync(
-        lines,
-        Some(DecodeStreamOptions {
-            indent: Some(indent),
-            strict: Some(strict),
-        }),
-    )
+    decoder_impl::decode_stream_sync(lines, Some(options.stream_options()))
 }
 
 fn decode_to_value(
     input: &str,
     options: &crate::options::ResolvedDecodeOptions,
 ) -> Result<JsonValue> {
-    let events = decode_events(input, options.indent, options.strict)?;
+    let events = decode_events(input, options)?;
     let mut node = build_node_from_events(events)?;
 
     if options.expand_paths == ExpandPathsMode::Safe {
         node = expand_paths_safe(node, options.strict)?;
     }
 
-    Ok(node_to_json(node))
+    let value = node_to_json(node);
+    if options.columnar {
+        reassemble_columnar(value)
+    } else {
+        Ok(value)
+    }
 }
 
 #[must_use]
@@ -0,0 +1,139 @@
+//! Ctrl-C handling for the CLI.
+//!
+//! The first Ctrl-C sets a shared flag. Conversion and output stages poll it,
+//! so the CLI can stop, remove any partially written output file, and exit
+//! with [`EXIT_INTERRUPTED`]. A second Ctrl-C exits immediately.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use crate::error::{Result, ToonError};
+
+/// Exit code for a run cancelled with Ctrl-C (128 + SIGINT, as shells report it).
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Cancellation flag set by the Ctrl-C handler.
+#[derive(Debug, Clone, Default)]
+pub struct Interrupt {
+    flag: Arc<AtomicBool>,
+}
+
+impl Interrupt {
+    /// Install the Ctrl-C handler and return the flag it sets.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the handler cannot be registered.
+    pub fn install() -> Result<Self> {
+        let interrupt = Self::default();
+
+        #[cfg(not(target_family = "wasm"))]
+        {
+            use signal_hook::consts::SIGINT;
+            use signal_hook::flag;
+
+            let install_error = |e| ToonError::io("Failed to install Ctrl-C handler", None, e);
+            // Registered first, so it only fires once the flag is already set.
+            flag::register_conditional_shutdown(
+                SIGINT,
+                EXIT_INTERRUPTED,
+                Arc::clone(&interrupt.flag),
+            )
+            .map_err(install_error)?;
+            flag::register(SIGINT, Arc::clone(&interrupt.flag)).map_err(install_error)?;
+        }
+
+        Ok(interrupt)
+    }
+
+    /// Mark the run as interrupted, as the Ctrl-C handler does.
+    pub fn trigger(&self) {
+        self.flag.store(true, Ordering::SeqCst);
+    }
+
+    #[must_use]
+    pub fn is_interrupted(&self) -> bool {
+        self.flag.load(Ordering::SeqCst)
+    }
+
+    /// Fail with [`ToonError::Interrupted`] once Ctrl-C has been pressed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ToonError::Interrupted`] if the flag is set.
+    pub fn check(&self) -> Result<()> {
+        if self.is_interrupted() {
+            Err(ToonError::Interrupted)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Run `work` on a worker thread and wait for it, returning early if
+    /// Ctrl-C is pressed first.
+    ///
+    /// An interrupted worker is abandoned rather than stopped; the CLI exits
+    /// right after, which ends it.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error from `work`, or [`ToonError::Interrupted`] if the run
+    /// was cancelled.
+    pub fn run<T, F>(&self, work: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> Result<T> + Send + 'static,
+    {
+        self.check()?;
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(work());
+        });
+
+        loop {
+            match receiver.recv_timeout(POLL_INTERVAL) {
+                Ok(result) => return result,
+                Err(RecvTimeoutError::Timeout) => self.check()?,
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(ToonError::message("Conversion worker exited unexpectedly"));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_returns_the_worker_result() {
+        let interrupt = Interrupt::default();
+        assert_eq!(interrupt.run(|| Ok(42)).unwrap(), 42);
+    }
+
+    #[test]
+    fn run_stops_waiting_once_interrupted() {
+        let interrupt = Interrupt::default();
+        let trigger = interrupt.clone();
+        let result = interrupt.run(move || {
+            trigger.trigger();
+            thread::sleep(Duration::from_secs(60));
+            Ok(())
+        });
+        assert!(matches!(result, Err(ToonError::Interrupted)));
+    }
+
+    #[test]
+    fn check_fails_after_trigger() {
+        let interrupt = Interrupt::default();
+        assert!(interrupt.check().is_ok());
+        interrupt.trigger();
+        assert!(matches!(interrupt.check(), Err(ToonError::Interrupted)));
+    }
+}
@@ -1,15 +1,64 @@
 pub mod args;
 pub mod conversion;
-pub mod json_stream;
-pub mod json_stringify;
+pub mod interrupt;
 
+/// JSON output from decoded events, now in [`crate::json`].
+#[deprecated(note = "moved to `toon::json`")]
+pub mod json_stream {
+    pub use crate::json::stream::*;
+}
+
+/// JSON output from a decoded value, now in [`crate::json`].
+#[deprecated(note = "moved to `toon::json`")]
+pub mod json_stringify {
+    pub use crate::json::stringify::*;
+}
+
+use crate::JsonValue;
+use crate::batch::{
+    BatchSummary, PlannedAction, PlannedJob, SymlinkPolicy, batch_error, convert_batch,
+    jobs_for_paths, plan_batch,
+};
+use crate::bench::run_bench as bench_document;
+use crate::capabilities::capabilities;
+use crate::decode::event_builder::{json_to_node, node_to_json};
+use crate::decode::expand::expand_paths_safe;
+use crate::decode::trace::trace_decode;
+use crate::decode::{ToonReader, decode_into_sink};
+use crate::detect::{FormatGuess, detect_format};
+use crate::determinism::audit_determinism;
+use crate::diff::diff;
+use crate::encode::lint::lint_for_llm;
+use crate::encode::replacer;
 use crate::error::{Result, ToonError};
-use crate::options::{DecodeOptions, EncodeOptions, ExpandPathsMode, KeyFoldingMode};
-use args::{Args, ExpandPathsArg, KeyFoldingArg, Mode};
-use clap::Parser;
+use crate::explain::explain_toon;
+use crate::frontmatter::{
+    from_markdown_frontmatter, split_markdown_frontmatter, to_markdown_frontmatter,
+};
+use crate::json::json_stringify_lines;
+use crate::lex::lex_toon;
+use crate::lossy;
+use crate::manifest::{ManifestEntry, build_manifest};
+use crate::merge::{Conflict, merge3};
+use crate::options::{
+    DecodeOptions, DecodeStreamOptions, EncodeOptions, ExpandPathsMode, IncludeResolver,
+    KeyFoldingMode, VariableResolver, resolve_decode_options, resolve_encode_options,
+};
+use crate::sink::{EventSink, StatsSink};
+use crate::tokens::{TokenEstimator, calibrate, samples_from_json};
+use crate::viz::{VizFormat, visualize};
+use args::{
+    Args, AuditDeterminismArgs, BatchArgs, BenchArgs, Command, DebugDecodeArgs, ExpandPathsArg,
+    ExplainArgs, FormatArg, FrontmatterArgs, GitDiffArgs, GitMergeArgs, KeyFoldingArg,
+    ManifestArgs, ManifestFormat, Merge3Args, Mode, PipeArgs, ProfileArg, RenderArgs, StatsArgs,
+    TokensArgs, VizArgs, VizFormatArg,
+};
+use clap::{Parser, ValueEnum};
+use interrupt::Interrupt;
 use std::fs::File;
-use std::io::{self, BufWriter, Read, Write};
-use std::path::Path;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
 /// Runs the CLI entrypoint.
 ///
@@ -18,41 +67,913 @@ use std::path::Path;
 /// Returns an error if parsing, encoding, decoding, or I/O fails.
 pub fn run() -> Result<()> {
     let args = Args::parse();
-    let mode = args.detect_mode();
 
-    match mode {
-        Mode::Encode => run_encode(&args),
-        Mode::Decode => run_decode(&args),
+    if let Some(Command::Explain(explain_args)) = &args.command {
+        return run_explain(explain_args);
+    }
+
+    if let Some(Command::DebugDecode(debug_args)) = &args.command {
+        return run_debug_decode(debug_args);
+    }
+
+    if let Some(Command::Tokens(tokens_args)) = &args.command {
+        return run_tokens(tokens_args);
+    }
+
+    if let Some(Command::Stats(stats_args)) = &args.command {
+        return run_stats(stats_args);
+    }
+
+    if let Some(Command::Viz(viz_args)) = &args.command {
+        return run_viz(viz_args);
+    }
+
+    if let Some(Command::Pipe(pipe_args)) = &args.command {
+        return run_pipe(pipe_args);
+    }
+
+    if let Some(Command::AuditDeterminism(audit_args)) = &args.command {
+        return run_audit_determinism(audit_args);
+    }
+
+    if let Some(Command::Bench(bench_args)) = &args.command {
+        return run_bench(bench_args);
+    }
+
+    if let Some(Command::Merge3(merge_args)) = &args.command {
+        return run_merge3(merge_args);
+    }
+
+    if let Some(Command::Manifest(manifest_args)) = &args.command {
+        return run_manifest(manifest_args);
+    }
+
+    if let Some(Command::Batch(batch_args)) = &args.command {
+        return run_batch(batch_args);
+    }
+
+    if let Some(Command::Frontmatter(frontmatter_args)) = &args.command {
+        return run_frontmatter(frontmatter_args);
+    }
+
+    if let Some(Command::Render(render_args)) = &args.command {
+        return run_render(render_args);
+    }
+    if let Some(Command::GitDiff(diff_args)) = &args.command {
+        return run_git_diff(diff_args);
+    }
+
+    if let Some(Command::GitMerge(merge_args)) = &args.command {
+        return run_git_merge(merge_args);
+    }
+
+    if args.jsonrpc {
+        return match args.jobs {
+            None | Some(1) => crate::jsonrpc::run_jsonrpc(io::stdin().lock(), io::stdout().lock()),
+            Some(jobs) => crate::jsonrpc::run_jsonrpc_parallel(
+                io::BufReader::new(io::stdin()),
+                io::stdout().lock(),
+                jobs,
+            ),
+        };
+    }
+
+    if args.capabilities {
+        return print_capabilities(args.json);
+    }
+    if args.json {
+        return Err(ToonError::message(
+            "--json is only valid with --capabilities",
+        ));
+    }
+
+    #[cfg(feature = "difftest")]
+    if let Some(Command::Difftest(difftest_args)) = &args.command {
+        return run_difftest(difftest_args);
+    }
+
+    #[cfg(feature = "lsp")]
+    if matches!(args.command, Some(Command::Lsp)) {
+        return crate::lsp::run_server(io::stdin().lock(), io::stdout().lock());
+    }
+
+    #[cfg(feature = "sqlite")]
+    if let Some(Command::Sql(sql_args)) = &args.command {
+        return run_sql(sql_args);
+    }
+
+    #[cfg(feature = "server")]
+    if let Some(Command::Serve(serve_args)) = &args.command {
+        let listener = std::net::TcpListener::bind(&serve_args.listen)
+            .map_err(|e| ToonError::io("Failed to listen", None, e))?;
+        let address = listener
+            .local_addr()
+            .map_err(|e| ToonError::io("Failed to listen", None, e))?;
+        eprintln!("Listening on http://{address}");
+        let options = crate::server::ServeOptions {
+            max_body_bytes: serve_args.max_body_bytes,
+            max_connections: usize::try_from(serve_args.max_connections).unwrap_or(usize::MAX),
+            timeout: std::time::Duration::from_secs(serve_args.timeout),
+        };
+        return crate::server::serve_with(&listener, options);
+    }
+
+    let interrupt = Interrupt::install()?;
+    let input = read_input(&args, &interrupt)?;
+
+    match args.detect_mode_for_input(&input) {
+        Mode::Encode => run_encode(&args, input, &interrupt),
+        Mode::Decode => run_decode(&args, input, &interrupt),
     }
 }
 
-fn run_encode(args: &Args) -> Result<()> {
-    // Read input (JSON)
-    let input = read_input(args)?;
+#[cfg(feature = "difftest")]
+fn run_difftest(args: &args::DifftestArgs) -> Result<()> {
+    use crate::difftest::{DiffTestOptions, ReferenceCommand, run_difftest};
 
-    // Build encode options
-    let options = EncodeOptions {
+    let reference = ReferenceCommand::parse(&args.reference)?;
+    let options = DiffTestOptions {
+        cases: args.cases,
+        seed: args.seed,
+        max_depth: args.max_depth,
+        indent: usize::from(args.indent),
+        delimiter: args.delimiter,
+    };
+
+    let report = run_difftest(&reference, &options)?;
+
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    for divergence in &report.divergences {
+        writeln!(
+            handle,
+            "case {} ({}): reference output differs",
+            divergence.case,
+            divergence.direction.as_str()
+        )
+        .map_err(ToonError::stdout_write)?;
+        for (label, text) in [
+            ("input", &divergence.input),
+            ("reference", &divergence.reference),
+            ("toon", &divergence.actual),
+        ] {
+            writeln!(handle, "  {label}:").map_err(ToonError::stdout_write)?;
+            for line in text.split('\n') {
+                writeln!(handle, "    {line}").map_err(ToonError::stdout_write)?;
+            }
+        }
+    }
+
+    eprintln!(
+        "difftest: {} cases, {} divergences",
+        report.cases_run,
+        report.divergences.len()
+    );
+
+    if report.is_clean() {
+        Ok(())
+    } else {
+        Err(ToonError::message(format!(
+            "{} divergences from reference",
+            report.divergences.len()
+        )))
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn run_sql(args: &args::SqlArgs) -> Result<()> {
+    use crate::sql::{export_query, import_rows, quote_identifier, sql_error};
+    use crate::tabular::TabularReader;
+
+    let mut conn = rusqlite::Connection::open(&args.db).map_err(|err| sql_error(&err))?;
+    let indent = usize::from(args.indent);
+
+    if let Some(input) = &args.input {
+        let Some(table) = &args.table else {
+            return Err(ToonError::message("--table is required to import"));
+        };
+        let text = read_subcommand_input(Some(input))?;
+        let rows = TabularReader::find(text.as_bytes(), args.key.as_deref())?;
+        let count = import_rows(&mut conn, table, rows)?;
+        eprintln!("Imported {count} rows into {table}");
+        return Ok(());
+    }
+
+    let query = match (&args.query, &args.table) {
+        (Some(query), _) => query.clone(),
+        (None, Some(table)) => format!("SELECT * FROM {}", quote_identifier(table)),
+        (None, None) => {
+            return Err(ToonError::message(
+                "Give an INPUT to import, or --query or --table to export",
+            ));
+        }
+    };
+    let key = args.key.as_deref().or(args.table.as_deref());
+    let output = export_query(&conn, &query, key, Vec::new(), indent)?;
+    if let Some(path) = &args.output {
+        return std::fs::write(path, output).map_err(|e| ToonError::file_write(path.clone(), e));
+    }
+    io::stdout()
+        .lock()
+        .write_all(&output)
+        .map_err(ToonError::stdout_write)
+}
+
+fn print_capabilities(json: bool) -> Result<()> {
+    let capabilities = capabilities();
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    if json {
+        serde_json::to_writer_pretty(&mut handle, &capabilities)?;
+        return writeln!(handle).map_err(ToonError::stdout_write);
+    }
+    let features = if capabilities.features.is_empty() {
+        "(none)".to_string()
+    } else {
+        capabilities.features.join(", ")
+    };
+    writeln!(
+        handle,
+        "toon {}\nspec: {}\ndirective: version {}\nfeatures: {features}",
+        capabilities.version,
+        capabilities.spec_versions.join(", "),
+        capabilities.directive_version,
+    )
+    .map_err(ToonError::stdout_write)
+}
+
+fn run_explain(args: &ExplainArgs) -> Result<()> {
+    let input = read_subcommand_input(args.input.as_deref())?;
+    let options = DecodeOptions {
+        indent: Some(usize::from(args.indent)),
+        strict: Some(!args.no_strict),
+        typed_headers: Some(args.typed_headers),
+        ..DecodeOptions::default()
+    };
+    let explained = explain_toon(&input, Some(options))?;
+
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    writeln!(handle, "{}", explained.trim_end_matches('\n')).map_err(ToonError::stdout_write)
+}
+
+fn run_debug_decode(args: &DebugDecodeArgs) -> Result<()> {
+    let input = read_subcommand_input(args.input.as_deref())?;
+    let options = DecodeOptions {
         indent: Some(usize::from(args.indent)),
-        delimiter: Some(args.delimiter),
-        key_folding: Some(match args.key_folding {
-            KeyFoldingArg::Off => KeyFoldingMode::Off,
-            KeyFoldingArg::Safe => KeyFoldingMode::Safe,
-        }),
-        flatten_depth: args.flatten_depth,
-        replacer: None,
+        strict: Some(!args.no_strict),
+        typed_headers: Some(args.typed_headers),
+        ..DecodeOptions::default()
+    };
+    let mut trace = trace_decode(&input, Some(options));
+    // The error goes to stderr through the usual path rather than into the trace.
+    let error = trace.error.take();
+
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    write!(handle, "{trace}").map_err(ToonError::stdout_write)?;
+    error.map_or(Ok(()), Err)
+}
+
+fn run_tokens(args: &TokensArgs) -> Result<()> {
+    let input = read_subcommand_input(args.input.as_deref())?;
+
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    if !args.syntax {
+        let estimator = token_estimator(args.token_samples.as_deref())?;
+        return writeln!(handle, "{}", estimator.estimate(&input)).map_err(ToonError::stdout_write);
+    }
+    for token in lex_toon(&input) {
+        let line = serde_json::json!({
+            "kind": token.kind.as_str(),
+            "start": token.span.start,
+            "end": token.span.end,
+            "text": &input[token.span],
+        });
+        writeln!(handle, "{line}").map_err(ToonError::stdout_write)?;
+    }
+    Ok(())
+}
+
+fn run_stats(args: &StatsArgs) -> Result<()> {
+    let indent = usize::from(args.indent);
+    let options = DecodeStreamOptions {
+        indent: Some(indent),
+        ..DecodeStreamOptions::default()
+    };
+    let path = args.input.as_deref().filter(|path| path.as_os_str() != "-");
+    let mut stats = StatsSink::new();
+
+    if args.streaming {
+        if path.is_some_and(is_json_path) {
+            return Err(ToonError::message("--streaming reads TOON input only"));
+        }
+        let reader: Box<dyn BufRead> = match path {
+            Some(path) => Box::new(BufReader::new(
+                File::open(path).map_err(|e| ToonError::file_read(path.to_path_buf(), e))?,
+            )),
+            None => Box::new(io::stdin().lock()),
+        };
+        for event in ToonReader::with_options(reader, Some(options))? {
+            stats.event(event?)?;
+        }
+    } else {
+        let input = read_subcommand_input(path)?;
+        if path.is_some_and(is_json_path) {
+            let value = parse_document(&input, true, indent)?;
+            for event in crate::encode::encode_stream_events(value, None) {
+                stats.event(event)?;
+            }
+        } else {
+            decode_into_sink(
+                input.split('\n').map(str::to_string),
+                Some(options),
+                &mut stats,
+            )?;
+        }
+    }
+
+    let output = render_document(stats.to_json(), args.json, indent)?;
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    writeln!(handle, "{output}").map_err(ToonError::stdout_write)
+}
+
+fn run_viz(args: &VizArgs) -> Result<()> {
+    let path = args.input.as_deref().filter(|path| path.as_os_str() != "-");
+    let input = read_subcommand_input(path)?;
+    let value = parse_document(
+        &input,
+        path.is_some_and(is_json_path),
+        usize::from(args.indent),
+    )?;
+    let format = match args.format {
+        VizFormatArg::Dot => VizFormat::Dot,
+        VizFormatArg::Mermaid => VizFormat::Mermaid,
+    };
+    let diagram = visualize(&value, format);
+    if let Some(path) = &args.output {
+        return std::fs::write(path, diagram).map_err(|e| ToonError::file_write(path.clone(), e));
+    }
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    handle
+        .write_all(diagram.as_bytes())
+        .map_err(ToonError::stdout_write)
+}
+
+fn run_pipe(args: &PipeArgs) -> Result<()> {
+    let input = read_subcommand_input(args.input.as_deref())?;
+    let indent = usize::from(args.indent);
+    let strict = !args.no_strict;
+    let expand = args.expand_paths == ExpandPathsArg::Safe;
+    let json_input = args.from.map_or_else(
+        || detect_format(&input) != FormatGuess::Toon,
+        |from| from == FormatArg::Json,
+    );
+
+    let mut value = if json_input {
+        let value: serde_json::Value =
+            serde_json::from_str(&input).map_err(|err| ToonError::json_parse(&err))?;
+        let value = JsonValue::from(value);
+        if expand {
+            node_to_json(expand_paths_safe(json_to_node(value), strict)?)
+        } else {
+            value
+        }
+    } else {
+        let options = DecodeOptions {
+            indent: Some(indent),
+            strict: Some(strict),
+            expand_paths: expand.then_some(ExpandPathsMode::Safe),
+            ..DecodeOptions::default()
+        };
+        crate::decode::try_decode(&input, Some(options))?
+    };
+    if let Some(path) = &args.transform {
+        value = replacer::apply_replacer(&value, &replacer::from_spec(&read_file(path, None)?)?);
+    }
+
+    let output = if args.to.map_or(json_input, |to| to == FormatArg::Json) {
+        json_stringify_lines(&value, indent).concat()
+    } else {
+        let options = EncodeOptions {
+            indent: Some(indent),
+            delimiter: Some(args.delimiter),
+            key_folding: (args.key_folding == KeyFoldingArg::Safe).then_some(KeyFoldingMode::Safe),
+            ..EncodeOptions::default()
+        };
+        crate::encode::try_encode(value, Some(options))?
+    };
+    if let Some(path) = &args.output {
+        return std::fs::write(path, format!("{output}\n"))
+            .map_err(|e| ToonError::file_write(path.clone(), e));
+    }
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    writeln!(handle, "{output}").map_err(ToonError::stdout_write)
+}
+
+fn run_audit_determinism(args: &AuditDeterminismArgs) -> Result<()> {
+    let indent = usize::from(args.indent);
+    let value = read_document(&args.input, is_json_path(&args.input), indent)?;
+    let options = EncodeOptions {
+        indent: Some(indent),
+        ..EncodeOptions::default()
+    };
+    let report = audit_determinism(
+        &value,
+        Some(&options),
+        args.runs as usize,
+        args.threads as usize,
+    )?;
+    if let Some(mismatch) = report.mismatch {
+        return Err(ToonError::message(format!(
+            "Nondeterministic output: thread {} run {} differs at line {}\n  expected: {}\n  actual:   {}",
+            mismatch.thread, mismatch.run, mismatch.line, mismatch.expected, mismatch.actual
+        )));
+    }
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    writeln!(
+        handle,
+        "Deterministic: {} runs produced identical {}-byte output",
+        report.runs, report.output_bytes
+    )
+    .map_err(ToonError::stdout_write)
+}
+
+fn run_bench(args: &BenchArgs) -> Result<()> {
+    let value = read_document(&args.input, is_json_path(&args.input), 2)?;
+    let profile = encode_profile(args.profile)?;
+    let encode_options = EncodeOptions {
+        indent: args.indent.map(usize::from).or(profile.indent),
+        delimiter: args.delimiter.or(profile.delimiter),
+        key_folding: args.key_folding.map(Into::into).or(profile.key_folding),
+        ..profile
+    };
+    let resolved = resolve_encode_options(Some(encode_options.clone()));
+    let decode_options = DecodeOptions {
+        indent: Some(resolved.indent),
+        strict: Some(!args.no_strict),
+        expand_paths: (resolved.key_folding == KeyFoldingMode::Safe)
+            .then_some(ExpandPathsMode::Safe),
+        ..DecodeOptions::default()
+    };
+    let report = bench_document(
+        &value,
+        Some(encode_options),
+        Some(decode_options),
+        args.iterations as usize,
+    )?;
+
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    writeln!(
+        handle,
+        "{}: {} bytes of TOON, {} lines, {} events; {} iterations, rates at p50",
+        args.input.display(),
+        report.toon_bytes,
+        report.lines,
+        report.events,
+        args.iterations
+    )
+    .map_err(ToonError::stdout_write)?;
+    writeln!(
+        handle,
+        "{:<8}{:>12}{:>12}{:>12}{:>10}{:>14}{:>14}",
+        "", "p50", "p90", "p99", "MB/s", "lines/s", "events/s"
+    )
+    .map_err(ToonError::stdout_write)?;
+    for (name, timings) in [("encode", &report.encode), ("decode", &report.decode)] {
+        let [p50, p90, p99] = [50.0, 90.0, 99.0].map(|percent| timings.percentile(percent));
+        let rate = report.throughput(p50);
+        writeln!(
+            handle,
+            "{name:<8}{:>12}{:>12}{:>12}{:>10.1}{:>14.0}{:>14.0}",
+            format!("{p50:.3?}"),
+            format!("{p90:.3?}"),
+            format!("{p99:.3?}"),
+            rate.megabytes_per_second,
+            rate.lines_per_second,
+            rate.events_per_second
+        )
+        .map_err(ToonError::stdout_write)?;
+    }
+    Ok(())
+}
+
+fn run_merge3(args: &Merge3Args) -> Result<()> {
+    let indent = usize::from(args.indent);
+    let base = read_document(&args.base, is_json_path(&args.base), indent)?;
+    let ours = read_document(&args.ours, is_json_path(&args.ours), indent)?;
+    let theirs = read_document(&args.theirs, is_json_path(&args.theirs), indent)?;
+
+    let merged = merge3(&base, &ours, &theirs).map_err(|conflicts| report_conflicts(&conflicts))?;
+    let output = render_document(merged, is_json_path(&args.ours), indent)?;
+    if let Some(path) = &args.output {
+        return std::fs::write(path, format!("{output}\n"))
+            .map_err(|e| ToonError::file_write(path.clone(), e));
+    }
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    writeln!(handle, "{output}").map_err(ToonError::stdout_write)
+}
+
+fn run_manifest(args: &ManifestArgs) -> Result<()> {
+    let entries = build_manifest(&args.dir)?;
+    let sum = |field: fn(&ManifestEntry) -> usize| {
+        JsonValue::from(entries.iter().map(field).sum::<usize>())
+    };
+    let totals = JsonValue::Object(vec![
+        ("files".to_string(), JsonValue::from(entries.len())),
+        ("bytes".to_string(), sum(|entry| entry.bytes)),
+        ("tokens".to_string(), sum(|entry| entry.tokens)),
+    ]);
+    let manifest = JsonValue::Object(vec![
+        (
+            "files".to_string(),
+            JsonValue::Array(entries.iter().map(ManifestEntry::to_json).collect()),
+        ),
+        ("totals".to_string(), totals),
+    ]);
+
+    let output = render_document(manifest, args.format == ManifestFormat::Json, 2)?;
+    if let Some(path) = &args.output {
+        return std::fs::write(path, format!("{output}\n"))
+            .map_err(|e| ToonError::file_write(path.clone(), e));
+    }
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    writeln!(handle, "{output}").map_err(ToonError::stdout_write)
+}
+
+fn run_batch(args: &BatchArgs) -> Result<()> {
+    let started = Instant::now();
+    let indent = usize::from(args.indent);
+    let symlinks = if args.follow_symlinks {
+        SymlinkPolicy::Follow
+    } else {
+        SymlinkPolicy::Skip
+    };
+    let mut jobs = jobs_for_paths(&args.paths, args.out_dir.as_deref(), symlinks)?;
+    for job in &mut jobs {
+        job.overwrite |= args.overwrite;
+        job.encode_options = Some(EncodeOptions {
+            indent: Some(indent),
+            delimiter: Some(args.delimiter),
+            ..EncodeOptions::default()
+        });
+        job.decode_options = Some(DecodeOptions {
+            indent: Some(indent),
+            strict: Some(!args.no_strict),
+            ..DecodeOptions::default()
+        });
+    }
+    if args.dry_run {
+        return print_batch_plan(&plan_batch(jobs, args.jobs));
+    }
+    let results = convert_batch(jobs, args.jobs);
+
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    for output in results.iter().flatten() {
+        if let Some(path) = &output.output {
+            writeln!(handle, "{} -> {}", output.input.display(), path.display())
+                .map_err(ToonError::stdout_write)?;
+        }
+    }
+    if args.summary || args.summary_json {
+        let summary = BatchSummary::new(&results, started.elapsed());
+        if args.summary_json {
+            eprintln!("{}", render_document(summary.to_json(), true, 0)?);
+        } else {
+            eprintln!("batch: {summary}");
+        }
+    }
+    batch_error(&results).map_or(Ok(()), Err)
+}
+
+/// One line per planned file, then totals; failures are reported as they
+/// would be by a real run.
+fn print_batch_plan(plan: &[Result<PlannedJob>]) -> Result<()> {
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    let mut counts = [0usize; 3];
+    let mut bytes = 0;
+    for job in plan.iter().flatten() {
+        let Some(output) = &job.output else {
+            continue;
+        };
+        let (index, label, size) = match job.action {
+            PlannedAction::Convert => (0, "convert", format!("{} bytes", job.output_bytes)),
+            PlannedAction::Overwrite => (1, "overwrite", format!("{} bytes", job.output_bytes)),
+            PlannedAction::UpToDate => (2, "skip", "up to date".to_string()),
+        };
+        counts[index] += 1;
+        if job.action != PlannedAction::UpToDate {
+            bytes += job.output_bytes;
+        }
+        writeln!(
+            handle,
+            "{label:<9} {} -> {} ({size})",
+            job.input.display(),
+            output.display()
+        )
+        .map_err(ToonError::stdout_write)?;
+    }
+    let [convert, overwrite, skip] = counts;
+    writeln!(
+        handle,
+        "dry run: {convert} to convert, {overwrite} to overwrite, {skip} up to date; \
+         {bytes} bytes would be written"
+    )
+    .map_err(ToonError::stdout_write)?;
+    batch_error(plan).map_or(Ok(()), Err)
+}
+
+fn run_frontmatter(args: &FrontmatterArgs) -> Result<()> {
+    let indent = usize::from(args.indent);
+    let doc = read_subcommand_input(args.input.as_deref())?;
+    let output = if let Some(set) = &args.set {
+        let value = read_document(set, is_json_path(set), indent)?;
+        let body = split_markdown_frontmatter(&doc).map_or(doc.as_str(), |(_, body)| body);
+        let options = EncodeOptions {
+            indent: Some(indent),
+            ..EncodeOptions::default()
+        };
+        to_markdown_frontmatter(value, body, Some(options))?
+    } else {
+        let options = DecodeOptions {
+            indent: Some(indent),
+            ..DecodeOptions::default()
+        };
+        let (value, _) = from_markdown_frontmatter(&doc, Some(options))?;
+        format!("{}\n", render_document(value, args.json, indent)?)
+    };
+
+    if let Some(path) = &args.output {
+        return std::fs::write(path, output).map_err(|e| ToonError::file_write(path.clone(), e));
+    }
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    handle
+        .write_all(output.as_bytes())
+        .map_err(ToonError::stdout_write)
+}
+
+fn run_render(args: &RenderArgs) -> Result<()> {
+    let indent = usize::from(args.indent);
+    let vars = read_document(&args.vars, is_json_path(&args.vars), indent)?;
+    let template = read_subcommand_input(args.template.as_deref())?;
+    let options = DecodeOptions {
+        indent: Some(indent),
+        variable_resolver: Some(VariableResolver::from_value(vars)),
+        ..DecodeOptions::default()
+    };
+    let value = crate::decode::try_decode(&template, Some(options))?;
+    let output = format!("{}\n", render_document(value, args.json, indent)?);
+
+    if let Some(path) = &args.output {
+        return std::fs::write(path, output).map_err(|e| ToonError::file_write(path.clone(), e));
+    }
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    handle
+        .write_all(output.as_bytes())
+        .map_err(ToonError::stdout_write)
+}
+
+/// External diff driver: git runs `<command> path old-file old-hex old-mode
+/// new-file new-hex new-mode`, or just `<command> path` for an unmerged path.
+fn run_git_diff(args: &GitDiffArgs) -> Result<()> {
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    let (Some(old_file), Some(new_file)) = (&args.old_file, &args.new_file) else {
+        return writeln!(handle, "* Unmerged path {}", args.path.display())
+            .map_err(ToonError::stdout_write);
+    };
+
+    let json = is_json_path(&args.path);
+    let old_text = read_file(old_file, None)?;
+    let new_text = read_file(new_file, None)?;
+    if old_text == new_text {
+        return Ok(());
+    }
+    let old = parse_document(&old_text, json, 2)?;
+    let new = parse_document(&new_text, json, 2)?;
+
+    let path = args.path.display();
+    writeln!(handle, "diff --toon a/{path} b/{path}").map_err(ToonError::stdout_write)?;
+    let changes = diff(&old, &new);
+    if changes.is_empty() {
+        return writeln!(handle, "  (formatting changes only)").map_err(ToonError::stdout_write);
+    }
+    for change in changes {
+        writeln!(handle, "{change}").map_err(ToonError::stdout_write)?;
+    }
+    Ok(())
+}
+
+/// Merge driver: git runs `<command> %O %A %B %L %P` and expects the result
+/// in `%A`, with a non-zero exit status if conflicts remain.
+fn run_git_merge(args: &GitMergeArgs) -> Result<()> {
+    let json = args.path.as_deref().is_some_and(is_json_path);
+    let base = read_document(&args.base, json, 2)?;
+    let ours_text = read_file(&args.current, None)?;
+    let ours = parse_document(&ours_text, json, 2)?;
+    let theirs_text = read_file(&args.other, None)?;
+    let theirs = parse_document(&theirs_text, json, 2)?;
+
+    let (output, result) = match merge3(&base, &ours, &theirs) {
+        Ok(merged) => (format!("{}\n", render_document(merged, json, 2)?), Ok(())),
+        Err(conflicts) => {
+            // Leave both versions in the file for manual resolution.
+            let marker_size = args.marker_size.unwrap_or(7);
+            let output = format!(
+                "{} ours\n{}\n{}\n{}\n{} theirs\n",
+                "<".repeat(marker_size),
+                ours_text.trim_end_matches('\n'),
+                "=".repeat(marker_size),
+                theirs_text.trim_end_matches('\n'),
+                ">".repeat(marker_size),
+            );
+            (output, Err(report_conflicts(&conflicts)))
+        }
+    };
+    std::fs::write(&args.current, output)
+        .map_err(|e| ToonError::file_write(args.current.clone(), e))?;
+    result
+}
+
+/// Print each conflict with its three sides to stderr, returning the error
+/// to exit with.
+fn report_conflicts(conflicts: &[Conflict]) -> ToonError {
+    for conflict in conflicts {
+        eprintln!("CONFLICT {conflict}");
+        for (label, side) in [
+            ("base", &conflict.base),
+            ("ours", &conflict.ours),
+            ("theirs", &conflict.theirs),
+        ] {
+            let side = side.clone().map_or_else(
+                || "(absent)".to_string(),
+                |value| serde_json::Value::from(value).to_string(),
+            );
+            eprintln!("  {label}: {side}");
+        }
+    }
+    ToonError::message(format!("{} merge conflicts", conflicts.len()))
+}
+
+fn is_json_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+fn read_document(path: &Path, json: bool, indent: usize) -> Result<JsonValue> {
+    parse_document(&read_file(path, None)?, json, indent)
+}
+
+/// Parse a whole document as JSON or TOON.
+fn parse_document(input: &str, json: bool, indent: usize) -> Result<JsonValue> {
+    if json {
+        let value: serde_json::Value =
+            serde_json::from_str(input).map_err(|err| ToonError::json_parse(&err))?;
+        return Ok(JsonValue::from(value));
+    }
+    let options = DecodeOptions {
+        indent: Some(indent),
+        ..DecodeOptions::default()
+    };
+    crate::decode::try_decode(input, Some(options))
+}
+
+fn render_document(value: JsonValue, json: bool, indent: usize) -> Result<String> {
+    if json {
+        return Ok(json_stringify_lines(&value, indent).concat());
+    }
+    let options = EncodeOptions {
+        indent: Some(indent),
+        ..EncodeOptions::default()
+    };
+    crate::encode::try_encode(value, Some(options))
+}
+
+/// The default token estimator, or one calibrated on a samples file.
+fn token_estimator(samples: Option<&Path>) -> Result<TokenEstimator> {
+    samples.map_or_else(
+        || Ok(TokenEstimator::default()),
+        |path| calibrate(&samples_from_json(&read_file(path, None)?)?),
+    )
+}
+
+/// Read a subcommand's input file, or stdin when it is omitted or `-`.
+fn read_subcommand_input(path: Option<&Path>) -> Result<String> {
+    match path {
+        Some(path) if path.as_os_str() != "-" => read_file(path, None),
+        _ => read_stdin(None),
+    }
+}
+
+/// Build encode options from the flags. Flags left at their defaults fall
+/// back to the profile.
+fn encode_options(args: &Args) -> Result<EncodeOptions> {
+    let profile = encode_profile(args.profile)?;
+    let replacer = match &args.transform {
+        Some(path) => Some(replacer::from_spec(&read_file(path, None)?)?),
+        None => None,
     };
+    Ok(EncodeOptions {
+        indent: args.indent.map(usize::from).or(profile.indent),
+        delimiter: args.delimiter.or(profile.delimiter),
+        key_folding: args.key_folding.map(Into::into).or(profile.key_folding),
+        flatten_depth: args.flatten_depth.or(profile.flatten_depth),
+        replacer,
+        empty_string: profile.empty_string,
+        typed_headers: args.typed_headers.then_some(true).or(profile.typed_headers),
+        columnar: args.columnar.then_some(true).or(profile.columnar),
+        sparse_threshold: args.sparse_threshold.or(profile.sparse_threshold),
+        run_length: args.run_length.or(profile.run_length),
+        long_string: profile.long_string,
+        emit_directive: args.directive.then_some(true).or(profile.emit_directive),
+        anchors: args.anchors.then_some(true).or(profile.anchors),
+        serde_key_order: profile.serde_key_order,
+        column_order: None,
+        columns: (!args.columns.is_empty())
+            .then(|| args.columns.iter().cloned().collect())
+            .or(profile.columns),
+        row_filter: None,
+        row_encoder: None,
+        align_columns: args.align_columns.then_some(true).or(profile.align_columns),
+        lossless_numbers: profile.lossless_numbers,
+        always_mark_delimiter: args
+            .always_mark_delimiter
+            .then_some(true)
+            .or(profile.always_mark_delimiter),
+        nfc: args.nfc.then_some(true).or(profile.nfc),
+        reject_invisible: args
+            .reject_invisible
+            .then_some(true)
+            .or(profile.reject_invisible),
+    })
+}
+
+fn run_encode(args: &Args, input: String, interrupt: &Interrupt) -> Result<()> {
+    let options = encode_options(args)?;
 
-    // Encode
-    let toon_lines = conversion::encode_to_toon_lines(&input, Some(options))?;
+    // Encode on a worker so Ctrl-C can cancel it
+    let fail_on_lossy = args.fail_on_lossy;
+    let lint = args.lint;
+    let also_json = args.also_json.clone();
+    let json_indent = args.indent.map_or(2, usize::from);
+    let (input, toon_lines) = interrupt.run(move || {
+        if fail_on_lossy {
+            let mut sites = lossy::scan_json(&input)?;
+            if options.nfc == Some(true) {
+                let value: serde_json::Value =
+                    serde_json::from_str(&input).map_err(|err| ToonError::json_parse(&err))?;
+                sites.extend(lossy::scan_nfc_key_collisions(&JsonValue::from(value)));
+            }
+            lossy::ensure_lossless(&sites)?;
+        }
+        if lint {
+            let value: serde_json::Value =
+                serde_json::from_str(&input).map_err(|err| ToonError::json_parse(&err))?;
+            for advice in lint_for_llm(&JsonValue::from(value), Some(options.clone())) {
+                eprintln!("warning: {advice}");
+            }
+        }
+        let Some(json_path) = also_json else {
+            let toon_lines = conversion::encode_to_toon_lines(&input, Some(options))?;
+            return Ok((input, toon_lines));
+        };
+        let file =
+            File::create(&json_path).map_err(|e| ToonError::file_write(json_path.clone(), e))?;
+        let (toon_lines, mut writer) = conversion::encode_to_toon_lines_and_json(
+            &input,
+            Some(options),
+            BufWriter::new(file),
+            json_indent,
+        )?;
+        writer
+            .write_all(b"\n")
+            .and_then(|()| writer.flush())
+            .map_err(|e| ToonError::file_write(json_path, e))?;
+        Ok((input, toon_lines))
+    })?;
 
     // Output
+    check_output_limit(args, joined_len(&toon_lines, 1))?;
+    write_parts(args, &toon_lines, b"\n", interrupt)?;
     if args.stats {
         let toon_output = toon_lines.join("\n");
-        write_output(args, toon_output.as_bytes())?;
 
-        // Calculate token estimates (simple heuristic: ~4 chars per token)
-        let json_tokens = estimate_tokens(&input);
-        let toon_tokens = estimate_tokens(&toon_output);
+        // Calculate token estimates (heuristic, calibrated if samples are given)
+        let estimator = token_estimator(args.token_samples.as_deref())?;
+        let json_tokens = estimator.estimate(&input);
+        let toon_tokens = estimator.estimate(&toon_output);
         let diff = json_tokens.saturating_sub(toon_tokens);
         #[allow(clippy::cast_precision_loss)]
         let percent = if json_tokens > 0 {
@@ -67,9 +988,6 @@ fn run_encode(args: &Args) -> Result<()> {
         if diff > 0 {
             eprintln!("Saved ~{diff} tokens (-{percent:.1}%)");
         }
-    } else {
-        // Streaming output
-        write_lines(args, &toon_lines)?;
     }
 
     // Success message to stderr if writing to file
@@ -82,25 +1000,59 @@ fn run_encode(args: &Args) -> Result<()> {
     Ok(())
 }
 
-fn run_decode(args: &Args) -> Result<()> {
-    // Read input (TOON)
-    let input = read_input(args)?;
-
+fn run_decode(args: &Args, input: String, interrupt: &Interrupt) -> Result<()> {
     // Build decode options
+    // Flags left at their defaults fall back to the profile
+    let profile = decode_profile(args.profile)?;
     let options = DecodeOptions {
-        indent: Some(usize::from(args.indent)),
-        strict: Some(!args.no_strict),
-        expand_paths: Some(match args.expand_paths {
-            ExpandPathsArg::Off => ExpandPathsMode::Off,
-            ExpandPathsArg::Safe => ExpandPathsMode::Safe,
-        }),
+        indent: args.indent.map(usize::from).or(profile.indent),
+        strict: args.no_strict.then_some(false).or(profile.strict),
+        expand_paths: args.expand_paths.map(Into::into).or(profile.expand_paths),
+        missing_cell: profile.missing_cell,
+        empty_cell: profile.empty_cell,
+        column_types: profile.column_types,
+        typed_headers: args.typed_headers.then_some(true).or(profile.typed_headers),
+        columnar: args.columnar.then_some(true).or(profile.columnar),
+        run_length: args
+            .run_length
+            .is_some()
+            .then_some(true)
+            .or(profile.run_length),
+        sparse: args
+            .sparse_threshold
+            .is_some()
+            .then_some(true)
+            .or(profile.sparse),
+        pedantic: args.pedantic.then_some(true).or(profile.pedantic),
+        tab_width: profile.tab_width,
+        infer_lengths: profile.infer_lengths,
+        anchors: args.anchors.then_some(true).or(profile.anchors),
+        include_resolver: args.allow_includes.then(|| file_include_resolver(args)),
+        interpolate_env: args
+            .interpolate_env
+            .then_some(true)
+            .or(profile.interpolate_env),
+        lossless_numbers: args
+            .lossless_numbers
+            .then_some(true)
+            .or(profile.lossless_numbers),
+        delimiter: args.delimiter.or(profile.delimiter),
+        ..DecodeOptions::default()
     };
 
-    // Decode to JSON chunks
-    let json_chunks = conversion::decode_to_json_chunks(&input, Some(options))?;
+    // Decode to JSON chunks on a worker so Ctrl-C can cancel it
+    let fail_on_lossy = args.fail_on_lossy;
+    let json_chunks = interrupt.run(move || {
+        if fail_on_lossy {
+            let stream_options = resolve_decode_options(Some(options.clone())).stream_options();
+            lossy::ensure_lossless(&lossy::scan_toon(&input, Some(stream_options))?)?;
+        }
+        conversion::decode_to_json_chunks(&input, Some(options))
+    })?;
 
     // Write output
-    write_chunks(args, &json_chunks)?;
+    check_output_limit(args, joined_len(&json_chunks, 0))?;
+    write_parts(args, &json_chunks, b"", interrupt)?;
 
     // Success message to stderr if writing to file
     if let Some(ref output_path) = args.output {
@@ -112,111 +1064,200 @@ fn run_decode(args: &Args) -> Result<()> {
     Ok(())
 }
 
-fn read_input(args: &Args) -> Result<String> {
+/// Encode options of an encode `--profile`, or the defaults without one.
+fn encode_profile(profile: Option<ProfileArg>) -> Result<EncodeOptions> {
+    match profile {
+        None => Ok(EncodeOptions::default()),
+        Some(ProfileArg::LlmCompact) => Ok(EncodeOptions::llm_compact()),
+        Some(ProfileArg::HumanReadable) => Ok(EncodeOptions::human_readable()),
+        Some(ProfileArg::Canonical) => Ok(EncodeOptions::canonical()),
+        Some(profile) => Err(profile_mismatch(profile, "decoding")),
+    }
+}
+
+/// Decode options of a decode `--profile`, or the defaults without one.
+fn decode_profile(profile: Option<ProfileArg>) -> Result<DecodeOptions> {
+    match profile {
+        None => Ok(DecodeOptions::default()),
+        Some(ProfileArg::Strict) => Ok(DecodeOptions::strict()),
+        Some(ProfileArg::Lenient) => Ok(DecodeOptions::lenient()),
+        Some(ProfileArg::Repair) => Ok(DecodeOptions::repair()),
+        Some(profile) => Err(profile_mismatch(profile, "encoding")),
+    }
+}
+
+fn profile_mismatch(profile: ProfileArg, mode: &str) -> ToonError {
+    let name = profile
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default();
+    ToonError::message(format!("--profile {name} applies to {mode} only"))
+}
+
+/// Resolve include paths as files relative to the input file's directory,
+/// or the working directory for stdin, kept under `--include-root` if given.
+fn file_include_resolver(args: &Args) -> IncludeResolver {
+    let base = args
+        .input
+        .as_deref()
+        .filter(|_| !args.is_stdin())
+        .and_then(Path::parent)
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    match &args.include_root {
+        Some(root) => IncludeResolver::sandboxed(base, root),
+        None => IncludeResolver::new(move |path| read_file(&base.join(path), None)),
+    }
+}
+
+/// Read the input on a worker thread, since a blocked read (for example an
+/// idle stdin pipe) would otherwise ignore Ctrl-C.
+fn read_input(args: &Args, interrupt: &Interrupt) -> Result<String> {
+    let limit = args.max_input_bytes;
     if args.is_stdin() {
-        read_stdin()
+        interrupt.run(move || read_stdin(limit))
     } else {
         let path = args
             .input
-            .as_ref()
+            .clone()
             .ok_or_else(|| ToonError::message("No input file specified"))?;
-        read_file(path)
+        interrupt.run(move || read_file(&path, limit))
     }
 }
 
-fn read_stdin() -> Result<String> {
-    let mut buffer = String::new();
-    io::stdin()
-        .read_to_string(&mut buffer)
-        .map_err(ToonError::stdin_read)?;
-    Ok(buffer)
+fn read_stdin(limit: Option<usize>) -> Result<String> {
+    let bytes = read_limited(io::stdin().lock(), limit).map_err(ToonError::stdin_read)?;
+    check_input_limit(bytes.len(), limit)?;
+    String::from_utf8(bytes).map_err(|e| {
+        ToonError::stdin_read(io::Error::new(io::ErrorKind::InvalidData, e.utf8_error()))
+    })
 }
 
-fn read_file(path: &Path) -> Result<String> {
-    std::fs::read_to_string(path).map_err(|e| ToonError::file_read(path.to_path_buf(), e))
-}
+fn read_file(path: &Path, limit: Option<usize>) -> Result<String> {
+    let Some(limit) = limit else {
+        return std::fs::read_to_string(path)
+            .map_err(|e| ToonError::file_read(path.to_path_buf(), e));
+    };
 
-fn write_output(args: &Args, data: &[u8]) -> Result<()> {
-    if let Some(ref path) = args.output {
-        let mut file = File::create(path).map_err(|e| ToonError::file_create(path.clone(), e))?;
-        file.write_all(data)
-            .map_err(|e| ToonError::file_write(path.clone(), e))?;
-        // Add trailing newline for file output
-        file.write_all(b"\n")
-            .map_err(|e| ToonError::file_write(path.clone(), e))?;
-    } else {
-        let stdout = io::stdout();
-        let mut handle = stdout.lock();
-        handle.write_all(data).map_err(ToonError::stdout_write)?;
-        handle.write_all(b"\n").map_err(ToonError::stdout_write)?;
+    // Check the size up front so an oversized file is rejected without
+    // reading it; the bounded read below still covers pipes and special files.
+    if let Ok(metadata) = std::fs::metadata(path) {
+        let size = usize::try_from(metadata.len()).unwrap_or(usize::MAX);
+        check_input_limit(size, Some(limit))?;
     }
-    Ok(())
+
+    let file = File::open(path).map_err(|e| ToonError::file_read(path.to_path_buf(), e))?;
+    let bytes =
+        read_limited(file, Some(limit)).map_err(|e| ToonError::file_read(path.to_path_buf(), e))?;
+    check_input_limit(bytes.len(), Some(limit))?;
+    String::from_utf8(bytes).map_err(|e| {
+        ToonError::file_read(
+            path.to_path_buf(),
+            io::Error::new(io::ErrorKind::InvalidData, e.utf8_error()),
+        )
+    })
 }
 
-fn write_lines(args: &Args, lines: &[String]) -> Result<()> {
-    if let Some(ref path) = args.output {
-        let file = File::create(path).map_err(|e| ToonError::file_create(path.clone(), e))?;
-        let mut writer = BufWriter::new(file);
-
-        for (i, line) in lines.iter().enumerate() {
-            if i > 0 {
-                writer
-                    .write_all(b"\n")
-                    .map_err(|e| ToonError::file_write(path.clone(), e))?;
-            }
-            writer
-                .write_all(line.as_bytes())
-                .map_err(|e| ToonError::file_write(path.clone(), e))?;
-        }
-        // Trailing newline
-        writer
-            .write_all(b"\n")
-            .map_err(|e| ToonError::file_write(path.clone(), e))?;
-    } else {
-        let stdout = io::stdout();
-        let mut handle = stdout.lock();
+/// Read at most one byte past `limit`, which is enough to tell that the
+/// input is too large without buffering the rest of it.
+fn read_limited(reader: impl Read, limit: Option<usize>) -> io::Result<Vec<u8>> {
+    let cap = limit.map_or(u64::MAX, |limit| {
+        u64::try_from(limit).unwrap_or(u64::MAX).saturating_add(1)
+    });
+    let mut bytes = Vec::new();
+    reader.take(cap).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
 
-        for (i, line) in lines.iter().enumerate() {
-            if i > 0 {
-                handle.write_all(b"\n").map_err(ToonError::stdout_write)?;
-            }
-            handle
-                .write_all(line.as_bytes())
-                .map_err(ToonError::stdout_write)?;
-        }
-        // Trailing newline
-        handle.write_all(b"\n").map_err(ToonError::stdout_write)?;
+fn check_input_limit(size: usize, limit: Option<usize>) -> Result<()> {
+    match limit {
+        Some(limit) if size > limit => Err(ToonError::limit("input bytes", limit, size)),
+        _ => Ok(()),
     }
-    Ok(())
 }
 
-fn write_chunks(args: &Args, chunks: &[String]) -> Result<()> {
-    if let Some(ref path) = args.output {
-        let file = File::create(path).map_err(|e| ToonError::file_create(path.clone(), e))?;
-        let mut writer = BufWriter::new(file);
+/// Check the final output size before anything is written, so an oversized
+/// result never leaves a truncated output file behind.
+fn check_output_limit(args: &Args, size: usize) -> Result<()> {
+    match args.max_output_bytes {
+        Some(limit) if size > limit => Err(ToonError::limit("output bytes", limit, size)),
+        _ => Ok(()),
+    }
+}
+
+/// Byte length of `parts` written with `separator_len` bytes between them
+/// plus the trailing newline.
+fn joined_len(parts: &[String], separator_len: usize) -> usize {
+    let content: usize = parts.iter().map(String::len).sum();
+    content + separator_len * parts.len().saturating_sub(1) + 1
+}
 
-        for chunk in chunks {
-            writer
-                .write_all(chunk.as_bytes())
-                .map_err(|e| ToonError::file_write(path.clone(), e))?;
+/// Write `parts` joined by `separator`, followed by a trailing newline.
+///
+/// An output file is written to a temporary file beside it and renamed over
+/// it only once writing succeeds, so a failed or interrupted write leaves
+/// any previous output untouched and no truncated file behind.
+fn write_parts(
+    args: &Args,
+    parts: &[String],
+    separator: &[u8],
+    interrupt: &Interrupt,
+) -> Result<()> {
+    if let Some(ref path) = args.output {
+        let temp = temp_output_path(path);
+        let file = File::options()
+            .write(true)
+            .create_new(true)
+            .open(&temp)
+            .map_err(|e| ToonError::file_create(temp.clone(), e))?;
+        let result = write_parts_to(BufWriter::new(file), parts, separator, interrupt, |e| {
+            ToonError::file_write(temp.clone(), e)
+        })
+        .and_then(|()| {
+            std::fs::rename(&temp, path).map_err(|e| ToonError::file_write(path.clone(), e))
+        });
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temp);
         }
-        // Trailing newline
-        writer
-            .write_all(b"\n")
-            .map_err(|e| ToonError::file_write(path.clone(), e))?;
+        result
     } else {
         let stdout = io::stdout();
-        let mut handle = stdout.lock();
+        write_parts_to(
+            stdout.lock(),
+            parts,
+            separator,
+            interrupt,
+            ToonError::stdout_write,
+        )
+    }
+}
 
-        for chunk in chunks {
-            handle
-                .write_all(chunk.as_bytes())
-                .map_err(ToonError::stdout_write)?;
+/// A hidden file name beside `path`, unique to this process, to write
+/// output into before renaming it over `path`.
+fn temp_output_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map_or_else(|| "output".into(), |name| name.to_string_lossy());
+    path.with_file_name(format!(".{name}.{}.tmp", std::process::id()))
+}
+
+fn write_parts_to(
+    mut writer: impl Write,
+    parts: &[String],
+    separator: &[u8],
+    interrupt: &Interrupt,
+    write_error: impl Fn(io::Error) -> ToonError,
+) -> Result<()> {
+    for (i, part) in parts.iter().enumerate() {
+        interrupt.check()?;
+        if i > 0 {
+            writer.write_all(separator).map_err(&write_error)?;
         }
-        // Trailing newline
-        handle.write_all(b"\n").map_err(ToonError::stdout_write)?;
+        writer.write_all(part.as_bytes()).map_err(&write_error)?;
     }
-    Ok(())
+    // Trailing newline
+    writer.write_all(b"\n").map_err(&write_error)?;
+    writer.flush().map_err(&write_error)
 }
 
 fn format_input_label(args: &Args) -> String {
@@ -228,12 +1269,3 @@ fn format_input_label(args: &Args) -> String {
         "stdin".to_string()
     }
 }
-
-/// Simple token estimation heuristic (roughly 4 chars per token for English/code).
-/// This matches the behavior of tokenx used in the legacy CLI.
-fn estimate_tokens(text: &str) -> usize {
-    // Simple heuristic: count non-whitespace chars / 4, with minimum of word count
-    let char_estimate = text.chars().filter(|c| !c.is_whitespace()).count() / 4;
-    let word_estimate = text.split_whitespace().count();
-    char_estimate.max(word_estimate).max(1)
-}
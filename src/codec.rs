@@ -0,0 +1,171 @@
+//! Reusable, thread-safe encoders and decoders.
+//!
+//! [`Encoder`] and [`Decoder`] resolve their options once and can then be
+//! cloned or shared (they are `Send + Sync`) across threads, so a server
+//! configures a codec at startup instead of rebuilding options per request.
+//! The free functions [`crate::try_encode`] and [`crate::try_decode`] are
+//! one-shot wrappers around them.
+//!
+//! ```
+//! use toon::{Decoder, Encoder, EncodeOptions, JsonValue};
+//!
+//! let encoder = Encoder::new(Some(EncodeOptions {
+//!     delimiter: Some('|'),
+//!     ..EncodeOptions::default()
+//! }));
+//! let value = JsonValue::from(serde_json::json!({"tags": ["a", "b"]}));
+//! let text = encoder.encode(&value);
+//! assert_eq!(text, "tags[2|]: a|b");
+//! assert_eq!(Decoder::default().decode(&text), value);
+//! ```
+
+use crate::JsonValue;
+use crate::decode::decoders::decode_into_sink_with;
+use crate::decode::event_builder::ValueSink;
+use crate::decode::finish_value;
+use crate::encode::{encode_document, prepare_value};
+use crate::error::Result;
+use crate::options::{
+    DecodeOptions, DecodeStreamOptions, EncodeOptions, ResolvedDecodeOptions,
+    ResolvedEncodeOptions, resolve_decode_options, resolve_encode_options,
+};
+use crate::sink::EventSink;
+
+/// Encodes values with options resolved once.
+#[derive(Clone)]
+pub struct Encoder {
+    options: ResolvedEncodeOptions,
+}
+
+impl Encoder {
+    #[must_use]
+    pub fn new(options: Option<EncodeOptions>) -> Self {
+        Self {
+            options: resolve_encode_options(options),
+        }
+    }
+
+    /// The options this encoder applies.
+    #[must_use]
+    pub const fn options(&self) -> &ResolvedEncodeOptions {
+        &self.options
+    }
+
+    /// Encode a value into a TOON string.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value cannot be encoded. Use [`Encoder::try_encode`]
+    /// to handle encode errors without panicking.
+    #[must_use]
+    pub fn encode(&self, value: impl Into<JsonValue>) -> String {
+        self.try_encode(value).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Encode a value into a TOON string.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ToonError::Encode`](crate::error::ToonError::Encode) with
+    /// the path of the offending value if it cannot be encoded.
+    pub fn try_encode(&self, value: impl Into<JsonValue>) -> Result<String> {
+        Ok(self.try_encode_lines(value)?.join("\n"))
+    }
+
+    /// Encode a value into TOON lines.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ToonError::Encode`](crate::error::ToonError::Encode) with
+    /// the path of the offending value if it cannot be encoded.
+    pub fn try_encode_lines(&self, value: impl Into<JsonValue>) -> Result<Vec<String>> {
+        let prepared = prepare_value(value.into(), &self.options);
+        encode_document(&prepared, &self.options)
+    }
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+/// Decodes TOON with options resolved once.
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    options: ResolvedDecodeOptions,
+    stream_options: DecodeStreamOptions,
+}
+
+impl Decoder {
+    #[must_use]
+    pub fn new(options: Option<DecodeOptions>) -> Self {
+        let options = resolve_decode_options(options);
+        let stream_options = options.stream_options();
+        Self {
+            options,
+            stream_options,
+        }
+    }
+
+    /// The options this decoder applies.
+    #[must_use]
+    pub const fn options(&self) -> &ResolvedDecodeOptions {
+        &self.options
+    }
+
+    /// Decode a TOON string into a value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if decoding fails due to malformed input or strict-mode
+    /// validation errors. Use [`Decoder::try_decode`] for a fallible version.
+    #[must_use]
+    pub fn decode(&self, input: &str) -> JsonValue {
+        self.try_decode(input).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Decode a TOON string into a value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if decoding fails due to malformed input or
+    /// strict-mode validation errors.
+    pub fn try_decode(&self, input: &str) -> Result<JsonValue> {
+        self.try_decode_lines(input.split('\n').map(str::to_string))
+    }
+
+    /// Decode TOON lines into a value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if decoding fails due to malformed input or
+    /// strict-mode validation errors.
+    pub fn try_decode_lines(&self, lines: impl IntoIterator<Item = String>) -> Result<JsonValue> {
+        let mut sink = ValueSink::new();
+        decode_into_sink_with(lines, &self.stream_options, &mut sink, None)?;
+        finish_value(sink, &self.options)
+    }
+
+    /// Decode TOON lines, pushing each event into `sink` as it is produced.
+    /// Path expansion and columnar reassembly are value-level options and do
+    /// not apply to events.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if decoding fails, or if the sink returns one. The
+    /// sink has already received the events before the error.
+    pub fn decode_into_sink(
+        &self,
+        lines: impl IntoIterator<Item = String>,
+        sink: &mut impl EventSink,
+    ) -> Result<()> {
+        decode_into_sink_with(lines, &self.stream_options, sink, None)
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
@@ -25,8 +25,8 @@
 use crate::JsonStreamEvent;
 use crate::decode::decoders as decoder_impl;
 use crate::decode::parser::{
-    is_array_header_content, is_key_value_content, parse_array_header_line, parse_key_token,
-    parse_primitive_token,
+    is_array_header_content, is_key_value_content, number_as_written, parse_array_header_line,
+    parse_key_token, parse_primitive_token,
 };
 use crate::decode::scanner::{
     Depth, ParsedLine, StreamingScanState, create_scan_state, parse_line_incremental,
@@ -34,6 +34,7 @@ use crate::decode::scanner::{
 use crate::error::{Result, ToonError};
 use crate::options::DecodeStreamOptions;
 use crate::shared::constants::{COLON, DEFAULT_DELIMITER, LIST_ITEM_PREFIX};
+use crate::shared::directive::Directive;
 use crate::shared::string_utils::find_closing_quote;
 use asupersync::stream::{Stream, StreamExt, iter};
 use std::collections::VecDeque;
@@ -120,6 +121,17 @@ impl<I: Iterator<Item = String>> AsyncDecodeStream<I> {
         self.options.strict.unwrap_or(true)
     }
 
+    /// Parse a primitive token, keeping a number as written in lossless
+    /// number mode.
+    fn parse_primitive(&self, token: &str) -> Result<crate::JsonPrimitive> {
+        let primitive = parse_primitive_token(token)?;
+        if self.options.lossless_numbers.unwrap_or(false) {
+            Ok(number_as_written(primitive, token))
+        } else {
+            Ok(primitive)
+        }
+    }
+
     /// Process the next available event or line
     fn process_next(&mut self) -> Result<Option<JsonStreamEvent>> {
         // Return queued events first
@@ -143,11 +155,20 @@ impl<I: Iterator<Item = String>> AsyncDecodeStream<I> {
         }
 
         // Get next line
-        let Some(raw_line) = self.lines.next() else {
+        let Some(mut raw_line) = self.lines.next() else {
             self.lines_exhausted = true;
             return self.finalize();
         };
 
+        // A leading #%toon directive sets the indentation and is otherwise
+        // treated as a blank line
+        if self.scan_state.line_number == 0
+            && let Some(directive) = Directive::parse(&raw_line)?
+        {
+            self.options.indent = directive.indent.or(self.options.indent);
+            raw_line.clear();
+        }
+
         // Parse the line (capture values before borrowing scan_state)
         let indent_size = self.indent_size();
         let strict = self.strict();
@@ -199,7 +220,7 @@ impl<I: Iterator<Item = String>> AsyncDecodeStream<I> {
         // Single primitive value
         self.state = DecoderState::Finished;
         Ok(Some(JsonStreamEvent::Primitive {
-            value: parse_primitive_token(line.content.trim())?,
+            value: self.parse_primitive(line.content.trim())?,
         }))
     }
 
@@ -276,7 +297,7 @@ impl<I: Iterator<Item = String>> AsyncDecodeStream<I> {
             was_quoted: is_quoted,
         });
         self.event_queue.push_back(JsonStreamEvent::Primitive {
-            value: parse_primitive_token(rest)?,
+            value: self.parse_primitive(rest)?,
         });
 
         Ok(())
@@ -343,13 +364,7 @@ impl<I: Iterator<Item = String>> AsyncDecodeStream<I> {
         // Use sync decoder on buffered lines
         let raw_lines: Vec<String> = self.line_buffer.iter().map(|p| p.raw.clone()).collect();
 
-        let events = decoder_impl::decode_stream_sync(
-            raw_lines,
-            Some(DecodeStreamOptions {
-                indent: self.options.indent,
-                strict: self.options.strict,
-            }),
-        )?;
+        let events = decoder_impl::decode_stream_sync(raw_lines, Some(self.options.clone()))?;
 
         // Queue all events
         self.event_queue.extend(events);
@@ -438,14 +453,7 @@ pub async fn try_decode_async(
     let resolved = resolve_decode_options(options);
     let lines: Vec<String> = input.split('\n').map(String::from).collect();
 
-    let events = try_decode_stream_async(
-        lines,
-        Some(DecodeStreamOptions {
-            indent: Some(resolved.indent),
-            strict: Some(resolved.strict),
-        }),
-    )
-    .await?;
+    let events = try_decode_stream_async(lines, Some(resolved.stream_options())).await?;
 
     let mut node = build_node_from_events(events)?;
 
@@ -453,7 +461,12 @@ pub async fn try_decode_async(
         node = expand_paths_safe(node, resolved.strict)?;
     }
 
-    Ok(node_to_json(node))
+    let value = node_to_json(node);
+    if resolved.columnar {
+        crate::decode::columnar::reassemble_columnar(value)
+    } else {
+        Ok(value)
+    }
 }
 
 #[cfg(test)]
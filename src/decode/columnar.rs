@@ -0,0 +1,107 @@
+//! Reassembly of rows from the columnar layout written by
+//! `EncodeOptions::columnar`.
+
+use crate::encode::columnar::is_marker_like;
+use crate::error::{Result, ToonError};
+use crate::shared::constants::COLUMNAR_MARKER;
+use crate::{JsonObject, JsonValue, StringOrNumberOrBoolOrNull};
+
+/// Turn every object marked with an `@rows` count back into an array of row
+/// objects.
+///
+/// Unmarked objects are left alone, whatever their shape. The `@` the
+/// encoder added to keys that read like the marker is stripped.
+///
+/// The encoder escapes data keys that read like the marker, so an unescaped
+/// `@rows` key is taken as its marker. An object carrying one must have the
+/// exact shape the encoder writes: the marker first, a positive count, then
+/// one or more fields that are each an array of that many primitives.
+///
+/// # Errors
+///
+/// Returns an error for an object with an `@rows` key in any other shape,
+/// rather than guessing whether it is data or a damaged columnar section.
+pub fn reassemble_columnar(value: JsonValue) -> Result<JsonValue> {
+    match value {
+        JsonValue::Primitive(_) => Ok(value),
+        #[cfg(feature = "raw")]
+        JsonValue::Raw(_) => Ok(value),
+        JsonValue::Array(items) => items
+            .into_iter()
+            .map(reassemble_columnar)
+            .collect::<Result<_>>()
+            .map(JsonValue::Array),
+        JsonValue::Object(entries) => {
+            let mut marker = None;
+            let mut fields = JsonObject::with_capacity(entries.len());
+            for (index, (key, value)) in entries.into_iter().enumerate() {
+                if key == COLUMNAR_MARKER {
+                    if index != 0 {
+                        return Err(not_a_section("the marker must be the object's first key"));
+                    }
+                    marker = Some(value);
+                } else if is_marker_like(&key) {
+                    fields.push((key[1..].to_string(), reassemble_columnar(value)?));
+                } else {
+                    fields.push((key, reassemble_columnar(value)?));
+                }
+            }
+            match marker {
+                Some(count) => rows_from_columns(fields, &count),
+                None => Ok(JsonValue::Object(fields)),
+            }
+        }
+    }
+}
+
+fn not_a_section(reason: &str) -> ToonError {
+    ToonError::message(format!(
+        "Object with a `{COLUMNAR_MARKER}` key is not a columnar section: {reason} \
+         (a data key is written as `@{COLUMNAR_MARKER}`)"
+    ))
+}
+
+fn rows_from_columns(columns: JsonObject, count: &JsonValue) -> Result<JsonValue> {
+    let length = row_count(count)
+        .filter(|&length| length > 0)
+        .ok_or_else(|| not_a_section("the count must be a positive integer"))?;
+    if columns.is_empty() {
+        return Err(not_a_section("it has no fields"));
+    }
+    let mut rows: Vec<JsonObject> = (0..length)
+        .map(|_| Vec::with_capacity(columns.len()))
+        .collect();
+    for (field, column) in columns {
+        let JsonValue::Array(column) = column else {
+            return Err(not_a_section(&format!("field '{field}' must be an array")));
+        };
+        if column.len() != length
+            || !column
+                .iter()
+                .all(|cell| matches!(cell, JsonValue::Primitive(_)))
+        {
+            return Err(not_a_section(&format!(
+                "field '{field}' must hold {length} primitive values"
+            )));
+        }
+        for (row, cell) in rows.iter_mut().zip(column) {
+            row.push((field.clone(), cell));
+        }
+    }
+    Ok(JsonValue::Array(
+        rows.into_iter().map(JsonValue::Object).collect(),
+    ))
+}
+
+fn row_count(count: &JsonValue) -> Option<usize> {
+    match count {
+        JsonValue::Primitive(StringOrNumberOrBoolOrNull::Int(count)) => {
+            usize::try_from(*count).ok()
+        }
+        JsonValue::Primitive(StringOrNumberOrBoolOrNull::UInt(count)) => {
+            usize::try_from(*count).ok()
+        }
+        JsonValue::Primitive(StringOrNumberOrBoolOrNull::NumberLexeme(text)) => text.parse().ok(),
+        _ => None,
+    }
+}
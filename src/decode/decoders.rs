@@ -1,24 +1,108 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
 use crate::JsonStreamEvent;
+use crate::anchors::expand_anchors;
+use crate::decode::interpolate::InterpolatingSink;
+use crate::decode::lenient::{expand_indent_tabs, infer_array_lengths};
 use crate::decode::parser::{
-    FieldName, is_array_header_content, is_key_value_content, map_row_values_to_primitives,
-    parse_array_header_line, parse_delimited_values, parse_key_token, parse_primitive_token,
+    FieldName, coerce_to_column_type, expand_runs, is_array_header_content, is_key_value_content,
+    map_row_values_to_primitives, number_as_written, parse_array_header_line,
+    parse_delimited_values, parse_field_type_annotations, parse_key_token, parse_primitive_token,
+    parse_sparse_entry,
 };
+use crate::decode::pedantic::check_pedantic;
 use crate::decode::scanner::{
-    Depth, ParsedLine, StreamingLineCursor, create_scan_state, parse_lines_sync,
+    Depth, ParsedLine, StreamingLineCursor, StreamingScanState, create_scan_state, parse_lines_sync,
 };
+use crate::decode::trace::DecodeTrace;
 use crate::decode::validation::{
     assert_expected_count, validate_no_blank_lines_in_range, validate_no_extra_list_items,
     validate_no_extra_tabular_rows,
 };
 use crate::error::{Result, ToonError};
-use crate::options::DecodeStreamOptions;
-use crate::shared::constants::{COLON, DEFAULT_DELIMITER, LIST_ITEM_MARKER, LIST_ITEM_PREFIX};
+use crate::options::{
+    ColumnType, DecodeStreamOptions, EmptyCellMode, MissingCellMode, VariableResolver,
+};
+use crate::shared::constants::{
+    COLON, DEFAULT_DELIMITER, INCLUDE_PREFIX, LIST_ITEM_MARKER, LIST_ITEM_PREFIX,
+};
+use crate::shared::directive::Directive;
 use crate::shared::string_utils::find_closing_quote;
+use crate::sink::{EventMetadata, EventSink};
 
 #[derive(Debug, Clone, Copy)]
-pub struct DecoderContext {
+#[allow(clippy::struct_excessive_bools)]
+pub struct DecoderContext<'a> {
     pub indent: usize,
+    /// Delimiter of array headers that do not declare one (`[2]`).
+    pub delimiter: char,
     pub strict: bool,
+    pub missing_cell: MissingCellMode,
+    pub empty_cell: EmptyCellMode,
+    pub column_types: Option<&'a HashMap<String, ColumnType>>,
+    pub typed_headers: bool,
+    pub run_length: bool,
+    /// Read `index: value` entry lines as a sparse array.
+    pub sparse: bool,
+    /// How many more `null`s sparse arrays may fill in this document.
+    pub sparse_budget: &'a Cell<usize>,
+    /// How many more cells `value*count` runs may expand to in this document.
+    pub run_budget: &'a Cell<usize>,
+    /// Keep numbers as written, as `NumberLexeme` values.
+    pub lossless_numbers: bool,
+    /// Options for included documents; `!include` values are only resolved
+    /// when these carry an include resolver.
+    pub stream_options: &'a DecodeStreamOptions,
+    /// Paths of the documents currently being included, outermost first.
+    pub include_stack: &'a [String],
+    /// Where `toon debug-decode` records the strict-mode checks it runs.
+    pub trace: Option<&'a RefCell<DecodeTrace>>,
+}
+
+impl DecoderContext<'_> {
+    /// Parse a primitive token, keeping a number as written in lossless
+    /// number mode.
+    fn parse_primitive(&self, token: &str) -> Result<crate::JsonPrimitive> {
+        parse_primitive_token(token).map(|primitive| self.keep_lexeme(primitive, token))
+    }
+
+    /// Parse row cells, keeping numbers as written in lossless number mode.
+    fn parse_row(
+        &self,
+        values: &[String],
+        line_number: usize,
+    ) -> Result<Vec<crate::JsonPrimitive>> {
+        let primitives = map_row_values_to_primitives(values, self.empty_cell, line_number)?;
+        Ok(primitives
+            .into_iter()
+            .zip(values)
+            .map(|(primitive, value)| self.keep_lexeme(primitive, value))
+            .collect())
+    }
+
+    fn keep_lexeme(&self, primitive: crate::JsonPrimitive, token: &str) -> crate::JsonPrimitive {
+        if self.lossless_numbers {
+            number_as_written(primitive, token)
+        } else {
+            primitive
+        }
+    }
+
+    /// Note a strict-mode check in the trace, if one is being recorded.
+    fn trace_check(&self, check: impl FnOnce() -> String) {
+        if self.strict
+            && let Some(trace) = self.trace
+        {
+            trace.borrow_mut().record_check(check());
+        }
+    }
+
+    fn assert_expected_count(&self, actual: usize, expected: usize, item_type: &str) -> Result<()> {
+        self.trace_check(|| format!("{item_type}: expected {expected}, found {actual}"));
+        assert_expected_count(actual, expected, item_type, self.strict)
+    }
 }
 
 /// Decode TOON input into a stream of JSON events.
@@ -31,47 +115,173 @@ pub fn decode_stream_sync(
     source: impl IntoIterator<Item = String>,
     options: Option<DecodeStreamOptions>,
 ) -> Result<Vec<JsonStreamEvent>> {
-    let options = options.unwrap_or(DecodeStreamOptions {
-        indent: None,
-        strict: None,
+    let mut events = Vec::new();
+    decode_into_sink(source, options, &mut events)?;
+    Ok(events)
+}
+
+/// Decode TOON input, pushing each event into `sink` as it is produced.
+///
+/// A leading `#%toon` directive line sets the indentation, taking precedence
+/// over `options.indent`. When decoding fails, the sink has already received
+/// the events before the error.
+///
+/// # Errors
+///
+/// Returns an error if scanning or decoding fails, or if the sink returns one.
+pub fn decode_into_sink(
+    source: impl IntoIterator<Item = String>,
+    options: Option<DecodeStreamOptions>,
+    sink: &mut impl EventSink,
+) -> Result<()> {
+    decode_into_sink_with(source, &options.unwrap_or_default(), sink, None)
+}
+
+/// [`decode_into_sink`] with borrowed options, recording each line read and
+/// each strict-mode check in `trace` when given.
+pub(crate) fn decode_into_sink_with(
+    source: impl IntoIterator<Item = String>,
+    options: &DecodeStreamOptions,
+    sink: &mut impl EventSink,
+    trace: Option<&Rc<RefCell<DecodeTrace>>>,
+) -> Result<()> {
+    decode_lines_after(source, options, 0, sink, trace)
+}
+
+/// Decode a section of a document that starts after its first
+/// `lines_before` lines, as if it were a whole document, so that line
+/// numbers in events and errors match the full input.
+pub(crate) fn decode_section_into_sink(
+    source: impl IntoIterator<Item = String>,
+    options: &DecodeStreamOptions,
+    lines_before: usize,
+    sink: &mut impl EventSink,
+) -> Result<()> {
+    decode_lines_after(source, options, lines_before, sink, None)
+}
+
+fn decode_lines_after(
+    source: impl IntoIterator<Item = String>,
+    options: &DecodeStreamOptions,
+    lines_before: usize,
+    sink: &mut impl EventSink,
+    trace: Option<&Rc<RefCell<DecodeTrace>>>,
+) -> Result<()> {
+    let resolver = options.variable_resolver.clone().or_else(|| {
+        options
+            .interpolate_env
+            .unwrap_or(false)
+            .then(VariableResolver::env)
     });
+    match resolver {
+        Some(resolver) => {
+            let strict = options.strict.unwrap_or(true);
+            let mut sink = InterpolatingSink::new(sink, resolver, strict);
+            decode_document(source, options, &[], lines_before, &mut sink, trace)
+        }
+        None => decode_document(source, options, &[], lines_before, sink, trace),
+    }
+}
+
+fn decode_document(
+    source: impl IntoIterator<Item = String>,
+    options: &DecodeStreamOptions,
+    include_stack: &[String],
+    lines_before: usize,
+    events: &mut dyn EventSink,
+    trace: Option<&Rc<RefCell<DecodeTrace>>>,
+) -> Result<()> {
+    let mut source = source.into_iter().peekable();
+    let directive = match source.peek() {
+        Some(first) => Directive::parse(first)?,
+        None => None,
+    };
+    let sparse_budget = Cell::new(0);
+    let run_budget = Cell::new(0);
     let context = DecoderContext {
-        indent: options.indent.unwrap_or(2),
+        indent: directive
+            .and_then(|directive| directive.indent)
+            .or(options.indent)
+            .unwrap_or(2),
+        delimiter: directive
+            .and_then(|directive| directive.delimiter)
+            .or(options.delimiter)
+            .unwrap_or(DEFAULT_DELIMITER),
         strict: options.strict.unwrap_or(true),
+        missing_cell: options.missing_cell.unwrap_or(MissingCellMode::Null),
+        empty_cell: options.empty_cell.unwrap_or(EmptyCellMode::EmptyString),
+        column_types: options.column_types.as_ref(),
+        typed_headers: options.typed_headers.unwrap_or(false),
+        run_length: options.run_length.unwrap_or(false),
+        sparse: options.sparse.unwrap_or(false),
+        sparse_budget: &sparse_budget,
+        run_budget: &run_budget,
+        lossless_numbers: options.lossless_numbers.unwrap_or(false),
+        stream_options: options,
+        include_stack,
+        trace: trace.map(|trace| &**trace),
     };
 
-    let mut scan_state = create_scan_state();
-    let lines = parse_lines_sync(source, context.indent, context.strict, &mut scan_state)?;
+    // The directive line is decoded as a blank line so that line numbers in
+    // errors still match the input.
+    let tab_width = options.tab_width;
+    let source = source.enumerate().map(|(index, line)| {
+        if index == 0 && directive.is_some() {
+            String::new()
+        } else if let Some(width) = tab_width {
+            expand_indent_tabs(line, width)
+        } else {
+            line
+        }
+    });
+    let mut scan_state = StreamingScanState {
+        line_number: lines_before,
+        ..create_scan_state()
+    };
+    let mut lines = if options.pedantic.unwrap_or(false) {
+        let source = source.collect::<Vec<_>>();
+        check_pedantic(&source)?;
+        parse_lines_sync(source, context.indent, context.strict, &mut scan_state)?
+    } else {
+        parse_lines_sync(source, context.indent, context.strict, &mut scan_state)?
+    };
+    if options.infer_lengths.unwrap_or(false) {
+        infer_array_lengths(&mut lines);
+    }
+    if options.anchors.unwrap_or(false) {
+        lines = expand_anchors(lines, context.indent)?;
+    }
+    if context.sparse || context.run_length {
+        set_expansion_budgets(&lines, context);
+    }
     let mut cursor = StreamingLineCursor::new(lines, scan_state.blank_lines);
-
-    let mut events = Vec::new();
+    if let Some(trace) = trace {
+        cursor = cursor.with_trace(Rc::clone(trace));
+    }
 
     let first = cursor.peek_sync().cloned();
     let Some(first) = first else {
-        events.push(JsonStreamEvent::StartObject);
-        events.push(JsonStreamEvent::EndObject);
-        return Ok(events);
+        start_object(events, None)?;
+        return events.event(JsonStreamEvent::EndObject);
     };
 
-    if is_array_header_content(&first.content) {
-        if let Some(header_info) = parse_array_header_line(&first.content, DEFAULT_DELIMITER)? {
-            cursor.advance_sync();
-            decode_array_from_header_sync(&mut events, header_info, &mut cursor, 0, context)?;
-            return Ok(events);
-        }
+    if is_array_header_content(&first.content)
+        && let Some(header_info) = parse_array_header_line(&first.content, context.delimiter)?
+    {
+        cursor.advance_sync();
+        return decode_array_from_header_sync(events, header_info, &mut cursor, 0, context);
     }
 
     cursor.advance_sync();
     let has_more = !cursor.at_end_sync();
     if !has_more && !is_key_value_line_sync(&first) {
-        events.push(JsonStreamEvent::Primitive {
-            value: parse_primitive_token(first.content.trim())?,
+        return events.event(JsonStreamEvent::Primitive {
+            value: context.parse_primitive(first.content.trim())?,
         });
-        return Ok(events);
     }
 
-    events.push(JsonStreamEvent::StartObject);
-    decode_key_value_sync(&mut events, &first.content, &mut cursor, 0, context)?;
+    start_object(events, Some(first.line_number))?;
+    decode_key_value_sync(events, &first.content, &mut cursor, 0, context)?;
 
     while !cursor.at_end_sync() {
         let line = cursor.peek_sync().cloned();
@@ -82,63 +292,133 @@ pub fn decode_stream_sync(
             break;
         }
         cursor.advance_sync();
-        decode_key_value_sync(&mut events, &line.content, &mut cursor, 0, context)?;
+        decode_key_value_sync(events, &line.content, &mut cursor, 0, context)?;
     }
 
-    events.push(JsonStreamEvent::EndObject);
-    Ok(events)
+    events.event(JsonStreamEvent::EndObject)
+}
+
+/// Open an object that starts on `source_line`.
+fn start_object(events: &mut dyn EventSink, source_line: Option<usize>) -> Result<()> {
+    events.event_with_metadata(
+        JsonStreamEvent::StartObject,
+        EventMetadata {
+            declared_length: None,
+            source_line,
+        },
+    )
 }
 
 fn decode_key_value_sync(
-    events: &mut Vec<JsonStreamEvent>,
+    events: &mut dyn EventSink,
     content: &str,
     cursor: &mut StreamingLineCursor,
     base_depth: Depth,
     options: DecoderContext,
 ) -> Result<()> {
-    if let Some(header_info) = parse_array_header_line(content, DEFAULT_DELIMITER)? {
-        if let Some(key) = header_info.header.key.clone() {
-            events.push(JsonStreamEvent::Key {
-                key,
-                was_quoted: header_info.header.key_was_quoted,
-            });
-            decode_array_from_header_sync(events, header_info, cursor, base_depth, options)?;
-            return Ok(());
-        }
+    if let Some(header_info) = parse_array_header_line(content, options.delimiter)?
+        && let Some(key) = header_info.header.key.clone()
+    {
+        events.event(JsonStreamEvent::Key {
+            key,
+            was_quoted: header_info.header.key_was_quoted,
+        })?;
+        decode_array_from_header_sync(events, header_info, cursor, base_depth, options)?;
+        return Ok(());
     }
 
-    let (key, end, is_quoted) = parse_key_token(content, 0)?;
+    let (key, end, is_quoted) = match parse_key_token(content, 0) {
+        Ok(parsed) => parsed,
+        #[cfg(feature = "raw")]
+        Err(_) if !options.strict => {
+            // Keyed by its own text, marked quoted so it is never expanded.
+            events.event(JsonStreamEvent::Key {
+                key: content.to_string(),
+                was_quoted: true,
+            })?;
+            return decode_raw_sync(events, content, cursor, base_depth, options);
+        }
+        Err(err) => return Err(err),
+    };
     let rest = content[end..].trim();
 
-    events.push(JsonStreamEvent::Key {
+    events.event(JsonStreamEvent::Key {
         key,
         was_quoted: is_quoted,
-    });
+    })?;
 
     if rest.is_empty() {
+        let key_line = cursor.current().map(|line| line.line_number);
         let next_line = cursor.peek_sync();
-        if let Some(next) = next_line {
-            if next.depth > base_depth {
-                events.push(JsonStreamEvent::StartObject);
-                decode_object_fields_sync(events, cursor, base_depth + 1, options)?;
-                events.push(JsonStreamEvent::EndObject);
-                return Ok(());
-            }
+        if let Some(next) = next_line
+            && next.depth > base_depth
+        {
+            start_object(events, key_line)?;
+            decode_object_fields_sync(events, cursor, base_depth + 1, options)?;
+            events.event(JsonStreamEvent::EndObject)?;
+            return Ok(());
         }
 
-        events.push(JsonStreamEvent::StartObject);
-        events.push(JsonStreamEvent::EndObject);
+        start_object(events, key_line)?;
+        events.event(JsonStreamEvent::EndObject)?;
         return Ok(());
     }
 
-    events.push(JsonStreamEvent::Primitive {
-        value: parse_primitive_token(rest)?,
-    });
+    if let Some(path) = include_path(rest, options) {
+        return decode_include(events, path, options);
+    }
+    events.event(JsonStreamEvent::Primitive {
+        value: options.parse_primitive(rest)?,
+    })?;
     Ok(())
 }
 
+/// The path of an unquoted `!include path` value, when includes are enabled.
+fn include_path<'a>(value: &'a str, options: DecoderContext) -> Option<&'a str> {
+    options.stream_options.include_resolver.as_ref()?;
+    let path = value.strip_prefix(INCLUDE_PREFIX)?.trim();
+    (!path.is_empty()).then_some(path)
+}
+
+/// Decode the document named by an `!include` value in place of the value.
+fn decode_include(events: &mut dyn EventSink, path: &str, options: DecoderContext) -> Result<()> {
+    let Some(resolver) = &options.stream_options.include_resolver else {
+        return Ok(());
+    };
+    if options
+        .include_stack
+        .iter()
+        .any(|included| included == path)
+    {
+        let mut cycle = options.include_stack.to_vec();
+        cycle.push(path.to_string());
+        return Err(ToonError::message(format!(
+            "Include cycle: {}",
+            cycle.join(" -> ")
+        )));
+    }
+
+    let source = resolver.resolve(path)?;
+    let mut include_stack = options.include_stack.to_vec();
+    include_stack.push(path.to_string());
+    // Buffered so that only errors in the included document get its path.
+    let mut included = Vec::new();
+    decode_document(
+        source.split('\n').map(str::to_string),
+        options.stream_options,
+        &include_stack,
+        0,
+        &mut included,
+        None,
+    )
+    .map_err(|err| ToonError::message(format!("{path}: {err}")))?;
+    included
+        .into_iter()
+        .try_for_each(|event| events.event(event))
+}
+
 fn decode_object_fields_sync(
-    events: &mut Vec<JsonStreamEvent>,
+    events: &mut dyn EventSink,
     cursor: &mut StreamingLineCursor,
     base_depth: Depth,
     options: DecoderContext,
@@ -170,68 +450,218 @@ fn decode_object_fields_sync(
 }
 
 fn decode_array_from_header_sync(
-    events: &mut Vec<JsonStreamEvent>,
+    events: &mut dyn EventSink,
     header_info: crate::decode::parser::ArrayHeaderParseResult,
     cursor: &mut StreamingLineCursor,
     base_depth: Depth,
     options: DecoderContext,
 ) -> Result<()> {
-    let header = header_info.header;
+    let mut header = header_info.header;
     let inline_values = header_info.inline_values;
 
-    events.push(JsonStreamEvent::StartArray {
-        length: header.length,
-    });
+    if options.typed_headers
+        && let Some(fields) = header.fields.as_mut()
+    {
+        let line_number = cursor.current().map_or(0, |line| line.line_number);
+        parse_field_type_annotations(fields, line_number)?;
+    }
+
+    events.event_with_metadata(
+        JsonStreamEvent::StartArray {
+            length: header.length,
+        },
+        EventMetadata {
+            declared_length: Some(header.length),
+            source_line: cursor.current().map(|line| line.line_number),
+        },
+    )?;
 
     if let Some(inline_values) = inline_values {
-        decode_inline_primitive_array_sync(events, &header, &inline_values, options)?;
-        events.push(JsonStreamEvent::EndArray);
+        let line_number = cursor.current().map_or(0, |line| line.line_number);
+        decode_inline_primitive_array_sync(events, &header, &inline_values, line_number, options)?;
+        events.event(JsonStreamEvent::EndArray)?;
         return Ok(());
     }
 
-    if let Some(fields) = &header.fields {
-        if !fields.is_empty() {
-            decode_tabular_array_sync(events, &header, cursor, base_depth, options)?;
-            events.push(JsonStreamEvent::EndArray);
-            return Ok(());
-        }
+    if let Some(fields) = &header.fields
+        && !fields.is_empty()
+    {
+        decode_tabular_array_sync(events, &header, cursor, base_depth, options)?;
+        events.event(JsonStreamEvent::EndArray)?;
+        return Ok(());
+    }
+
+    if options.sparse
+        && let Some(next) = cursor.peek_sync()
+        && next.depth == base_depth + 1
+        && parse_sparse_entry(&next.content).is_some()
+    {
+        decode_sparse_array_sync(events, &header, cursor, base_depth, options)?;
+        events.event(JsonStreamEvent::EndArray)?;
+        return Ok(());
     }
 
     decode_list_array_sync(events, &header, cursor, base_depth, options)?;
-    events.push(JsonStreamEvent::EndArray);
+    events.event(JsonStreamEvent::EndArray)?;
+    Ok(())
+}
+
+/// Decode `index: value` entry lines; every index that is not listed is `null`.
+fn decode_sparse_array_sync(
+    events: &mut dyn EventSink,
+    header: &crate::decode::parser::ArrayHeaderInfo,
+    cursor: &mut StreamingLineCursor,
+    base_depth: Depth,
+    options: DecoderContext,
+) -> Result<()> {
+    let entry_depth = base_depth + 1;
+    let mut next_index = 0usize;
+    let mut start_line: Option<usize> = None;
+    let mut end_line: Option<usize> = None;
+
+    while let Some(line) = cursor.peek_sync().cloned() {
+        if line.depth != entry_depth {
+            break;
+        }
+        let Some((index, value)) = parse_sparse_entry(&line.content) else {
+            return Err(ToonError::parse(
+                line.line_number,
+                "Expected an `index: value` entry in sparse array",
+            ));
+        };
+        if index >= header.length {
+            return Err(ToonError::parse(
+                line.line_number,
+                format!(
+                    "Sparse array index {index} is out of range for length {}",
+                    header.length
+                ),
+            ));
+        }
+        if index < next_index {
+            return Err(ToonError::parse(
+                line.line_number,
+                format!("Sparse array index {index} is not in increasing order"),
+            ));
+        }
+        if value.is_empty() {
+            return Err(ToonError::parse(
+                line.line_number,
+                format!("Sparse array entry {index} has no value"),
+            ));
+        }
+
+        cursor.advance_sync();
+        if start_line.is_none() {
+            start_line = Some(line.line_number);
+        }
+        end_line = Some(line.line_number);
+
+        push_nulls(events, index - next_index, line.line_number, options)?;
+        events.event(JsonStreamEvent::Primitive {
+            value: options.parse_primitive(value)?,
+        })?;
+        next_index = index + 1;
+    }
+    let line_number =
+        end_line.unwrap_or_else(|| cursor.current().map_or(0, |line| line.line_number));
+    push_nulls(events, header.length - next_index, line_number, options)?;
+
+    if options.strict
+        && let (Some(start), Some(end)) = (start_line, end_line)
+    {
+        options.trace_check(|| format!("no blank lines inside sparse array (lines {start}-{end})"));
+        validate_no_blank_lines_in_range(
+            start,
+            end,
+            cursor.get_blank_lines(),
+            options.strict,
+            "sparse array",
+        )?;
+    }
     Ok(())
 }
 
+/// Sparse arrays in a document may fill this many `null`s per byte of input...
+const SPARSE_NULLS_PER_INPUT_BYTE: usize = 64;
+/// ...but always at least this many, so short documents can still declare
+/// long mostly-empty vectors.
+const MIN_SPARSE_NULL_BUDGET: usize = 1 << 20;
+
+/// Runs in a document may expand to this many cells per byte of input...
+const RUN_CELLS_PER_INPUT_BYTE: usize = 64;
+/// ...but always at least this many, so short documents can still hold
+/// long runs.
+const MIN_RUN_CELL_BUDGET: usize = 1 << 20;
+
+/// Size the sparse-null and run-cell budgets to the document's input.
+fn set_expansion_budgets(lines: &[ParsedLine], context: DecoderContext) {
+    let input_len = lines.iter().map(|line| line.raw.len() + 1).sum::<usize>();
+    context.sparse_budget.set(
+        input_len
+            .saturating_mul(SPARSE_NULLS_PER_INPUT_BYTE)
+            .max(MIN_SPARSE_NULL_BUDGET),
+    );
+    context.run_budget.set(
+        input_len
+            .saturating_mul(RUN_CELLS_PER_INPUT_BYTE)
+            .max(MIN_RUN_CELL_BUDGET),
+    );
+}
+
+/// Fill `count` unlisted sparse indices with `null`, drawing on the document's
+/// budget so a huge declared length cannot expand a few bytes of input into
+/// an unbounded number of events.
+fn push_nulls(
+    events: &mut dyn EventSink,
+    count: usize,
+    line_number: usize,
+    options: DecoderContext,
+) -> Result<()> {
+    let budget = options.sparse_budget.get();
+    if count > budget {
+        return Err(ToonError::parse(
+            line_number,
+            format!("Sparse array fills {count} nulls, more than this input can plausibly declare"),
+        ));
+    }
+    options.sparse_budget.set(budget - count);
+    (0..count).try_for_each(|_| {
+        events.event(JsonStreamEvent::Primitive {
+            value: crate::StringOrNumberOrBoolOrNull::Null,
+        })
+    })
+}
+
 fn decode_inline_primitive_array_sync(
-    events: &mut Vec<JsonStreamEvent>,
+    events: &mut dyn EventSink,
     header: &crate::decode::parser::ArrayHeaderInfo,
     inline_values: &str,
+    line_number: usize,
     options: DecoderContext,
 ) -> Result<()> {
     if inline_values.trim().is_empty() {
-        assert_expected_count(0, header.length, "inline array items", options.strict)?;
+        options.assert_expected_count(0, header.length, "inline array items")?;
         return Ok(());
     }
 
-    let values = parse_delimited_values(inline_values, header.delimiter);
-    let primitives = map_row_values_to_primitives(&values)?;
+    let mut values = parse_delimited_values(inline_values, header.delimiter);
+    if options.run_length {
+        values = expand_runs(values, header.length, options.run_budget, line_number)?;
+    }
+    let primitives = options.parse_row(&values, line_number)?;
 
-    assert_expected_count(
-        primitives.len(),
-        header.length,
-        "inline array items",
-        options.strict,
-    )?;
+    options.assert_expected_count(primitives.len(), header.length, "inline array items")?;
 
     for primitive in primitives {
-        events.push(JsonStreamEvent::Primitive { value: primitive });
+        events.event(JsonStreamEvent::Primitive { value: primitive })?;
     }
 
     Ok(())
 }
 
 fn decode_tabular_array_sync(
-    events: &mut Vec<JsonStreamEvent>,
+    events: &mut dyn EventSink,
     header: &crate::decode::parser::ArrayHeaderInfo,
     cursor: &mut StreamingLineCursor,
     base_depth: Depth,
@@ -263,15 +693,17 @@ fn decode_tabular_array_sync(
                 .fields
                 .as_ref()
                 .ok_or_else(|| ToonError::message("Tabular array is missing header fields"))?;
-            assert_expected_count(
-                values.len(),
-                fields.len(),
-                "tabular row values",
-                options.strict,
-            )?;
+            options.assert_expected_count(values.len(), fields.len(), "tabular row values")?;
 
-            let primitives = map_row_values_to_primitives(&values)?;
-            yield_object_from_fields(events, fields, &primitives);
+            let mut primitives = options.parse_row(&values, line.line_number)?;
+            apply_column_types(&mut primitives, &values, fields, options, line.line_number)?;
+            yield_object_from_fields(
+                events,
+                fields,
+                &primitives,
+                options.missing_cell,
+                line.line_number,
+            )?;
 
             row_count += 1;
         } else {
@@ -279,26 +711,29 @@ fn decode_tabular_array_sync(
         }
     }
 
-    assert_expected_count(row_count, header.length, "tabular rows", options.strict)?;
+    options.assert_expected_count(row_count, header.length, "tabular rows")?;
 
-    if options.strict {
-        if let (Some(start), Some(end)) = (start_line, end_line) {
-            validate_no_blank_lines_in_range(
-                start,
-                end,
-                cursor.get_blank_lines(),
-                options.strict,
-                "tabular array",
-            )?;
-        }
+    if options.strict
+        && let (Some(start), Some(end)) = (start_line, end_line)
+    {
+        options
+            .trace_check(|| format!("no blank lines inside tabular array (lines {start}-{end})"));
+        validate_no_blank_lines_in_range(
+            start,
+            end,
+            cursor.get_blank_lines(),
+            options.strict,
+            "tabular array",
+        )?;
     }
 
+    options.trace_check(|| format!("no tabular rows beyond {}", header.length));
     validate_no_extra_tabular_rows(cursor.peek_sync(), row_depth, header, options.strict)?;
     Ok(())
 }
 
 fn decode_list_array_sync(
-    events: &mut Vec<JsonStreamEvent>,
+    events: &mut dyn EventSink,
     header: &crate::decode::parser::ArrayHeaderInfo,
     cursor: &mut StreamingLineCursor,
     base_depth: Depth,
@@ -309,7 +744,7 @@ fn decode_list_array_sync(
     let mut start_line: Option<usize> = None;
     let mut end_line: Option<usize> = None;
 
-    while !cursor.at_end_sync() && item_count < header.length {
+    while !cursor.at_end_sync() {
         let line = cursor.peek_sync().cloned();
         let Some(line) = line else {
             break;
@@ -321,6 +756,9 @@ fn decode_list_array_sync(
         let is_list_item =
             line.content.starts_with(LIST_ITEM_PREFIX) || line.content == LIST_ITEM_MARKER;
         if line.depth == item_depth && is_list_item {
+            if item_count >= header.length {
+                break;
+            }
             if start_line.is_none() {
                 start_line = Some(line.line_number);
             }
@@ -334,29 +772,32 @@ fn decode_list_array_sync(
 
             item_count += 1;
         } else {
+            #[cfg(feature = "raw")]
+            if !options.strict && line.depth == item_depth {
+                cursor.advance_sync();
+                decode_raw_sync(events, &line.content, cursor, item_depth, options)?;
+                continue;
+            }
             break;
         }
     }
 
-    assert_expected_count(
-        item_count,
-        header.length,
-        "list array items",
-        options.strict,
-    )?;
+    options.assert_expected_count(item_count, header.length, "list array items")?;
 
-    if options.strict {
-        if let (Some(start), Some(end)) = (start_line, end_line) {
-            validate_no_blank_lines_in_range(
-                start,
-                end,
-                cursor.get_blank_lines(),
-                options.strict,
-                "list array",
-            )?;
-        }
+    if options.strict
+        && let (Some(start), Some(end)) = (start_line, end_line)
+    {
+        options.trace_check(|| format!("no blank lines inside list array (lines {start}-{end})"));
+        validate_no_blank_lines_in_range(
+            start,
+            end,
+            cursor.get_blank_lines(),
+            options.strict,
+            "list array",
+        )?;
     }
 
+    options.trace_check(|| format!("no list array items beyond {}", header.length));
     validate_no_extra_list_items(
         cursor.peek_sync(),
         item_depth,
@@ -366,8 +807,30 @@ fn decode_list_array_sync(
     Ok(())
 }
 
+/// Emit a line the decoder does not understand, and the lines nested under
+/// it, as one raw event. Nested lines keep their indentation relative to the
+/// first line.
+#[cfg(feature = "raw")]
+fn decode_raw_sync(
+    events: &mut dyn EventSink,
+    content: &str,
+    cursor: &mut StreamingLineCursor,
+    base_depth: Depth,
+    options: DecoderContext,
+) -> Result<()> {
+    let base_indent = base_depth * options.indent;
+    let mut text = content.to_string();
+    while let Some(line) = cursor.peek_sync().filter(|line| line.depth > base_depth) {
+        text.push('\n');
+        text.push_str(&" ".repeat(line.indent.saturating_sub(base_indent)));
+        text.push_str(&line.content);
+        cursor.advance_sync();
+    }
+    events.event(JsonStreamEvent::Raw { text })
+}
+
 fn decode_list_item_sync(
-    events: &mut Vec<JsonStreamEvent>,
+    events: &mut dyn EventSink,
     cursor: &mut StreamingLineCursor,
     base_depth: Depth,
     options: DecoderContext,
@@ -377,8 +840,8 @@ fn decode_list_item_sync(
         .ok_or_else(|| ToonError::message("Expected list item"))?;
 
     if line.content == LIST_ITEM_MARKER {
-        events.push(JsonStreamEvent::StartObject);
-        events.push(JsonStreamEvent::EndObject);
+        start_object(events, Some(line.line_number))?;
+        events.event(JsonStreamEvent::EndObject)?;
         return Ok(());
     }
 
@@ -391,69 +854,62 @@ fn decode_list_item_sync(
     };
 
     if after_hyphen.trim().is_empty() {
-        events.push(JsonStreamEvent::StartObject);
-        events.push(JsonStreamEvent::EndObject);
+        start_object(events, Some(line.line_number))?;
+        events.event(JsonStreamEvent::EndObject)?;
         return Ok(());
     }
 
-    if is_array_header_content(&after_hyphen) {
-        if let Some(header_info) = parse_array_header_line(&after_hyphen, DEFAULT_DELIMITER)? {
-            decode_array_from_header_sync(events, header_info, cursor, base_depth, options)?;
-            return Ok(());
-        }
+    if is_array_header_content(&after_hyphen)
+        && let Some(header_info) = parse_array_header_line(&after_hyphen, options.delimiter)?
+    {
+        decode_array_from_header_sync(events, header_info, cursor, base_depth, options)?;
+        return Ok(());
     }
 
-    if let Some(header_info) = parse_array_header_line(&after_hyphen, DEFAULT_DELIMITER)? {
-        if header_info.header.key.is_some() && header_info.header.fields.is_some() {
-            let header = header_info.header;
-            events.push(JsonStreamEvent::StartObject);
-            events.push(JsonStreamEvent::Key {
-                key: header.key.clone().unwrap_or_default(),
-                was_quoted: header.key_was_quoted,
-            });
-            decode_array_from_header_sync(
-                events,
-                crate::decode::parser::ArrayHeaderParseResult {
-                    header,
-                    inline_values: header_info.inline_values,
-                },
-                cursor,
-                base_depth + 1,
-                options,
-            )?;
+    if let Some(header_info) = parse_array_header_line(&after_hyphen, options.delimiter)?
+        && header_info.header.key.is_some()
+        && header_info.header.fields.is_some()
+    {
+        let header = header_info.header;
+        start_object(events, Some(line.line_number))?;
+        events.event(JsonStreamEvent::Key {
+            key: header.key.clone().unwrap_or_default(),
+            was_quoted: header.key_was_quoted,
+        })?;
+        decode_array_from_header_sync(
+            events,
+            crate::decode::parser::ArrayHeaderParseResult {
+                header,
+                inline_values: header_info.inline_values,
+            },
+            cursor,
+            base_depth + 1,
+            options,
+        )?;
 
-            let follow_depth = base_depth + 1;
-            while !cursor.at_end_sync() {
-                let next_line = cursor.peek_sync().cloned();
-                let Some(next_line) = next_line else {
-                    break;
-                };
-                if next_line.depth < follow_depth {
-                    break;
-                }
-                if next_line.depth == follow_depth
-                    && !next_line.content.starts_with(LIST_ITEM_PREFIX)
-                {
-                    cursor.advance_sync();
-                    decode_key_value_sync(
-                        events,
-                        &next_line.content,
-                        cursor,
-                        follow_depth,
-                        options,
-                    )?;
-                } else {
-                    break;
-                }
+        let follow_depth = base_depth + 1;
+        while !cursor.at_end_sync() {
+            let next_line = cursor.peek_sync().cloned();
+            let Some(next_line) = next_line else {
+                break;
+            };
+            if next_line.depth < follow_depth {
+                break;
+            }
+            if next_line.depth == follow_depth && !next_line.content.starts_with(LIST_ITEM_PREFIX) {
+                cursor.advance_sync();
+                decode_key_value_sync(events, &next_line.content, cursor, follow_depth, options)?;
+            } else {
+                break;
             }
-
-            events.push(JsonStreamEvent::EndObject);
-            return Ok(());
         }
+
+        events.event(JsonStreamEvent::EndObject)?;
+        return Ok(());
     }
 
     if is_key_value_content(&after_hyphen) {
-        events.push(JsonStreamEvent::StartObject);
+        start_object(events, Some(line.line_number))?;
         decode_key_value_sync(events, &after_hyphen, cursor, base_depth + 1, options)?;
 
         let follow_depth = base_depth + 1;
@@ -473,41 +929,90 @@ fn decode_list_item_sync(
             }
         }
 
-        events.push(JsonStreamEvent::EndObject);
+        events.event(JsonStreamEvent::EndObject)?;
         return Ok(());
     }
 
-    events.push(JsonStreamEvent::Primitive {
-        value: parse_primitive_token(&after_hyphen)?,
-    });
+    if let Some(path) = include_path(&after_hyphen, options) {
+        return decode_include(events, path, options);
+    }
+    events.event(JsonStreamEvent::Primitive {
+        value: options.parse_primitive(&after_hyphen)?,
+    })?;
+    Ok(())
+}
+
+/// Coerce row cells to explicit `column_types`, then to typed header
+/// annotations. Explicit types always reject mismatches; header annotations
+/// only do so in strict mode.
+fn apply_column_types(
+    primitives: &mut [crate::JsonPrimitive],
+    values: &[String],
+    fields: &[FieldName],
+    options: DecoderContext,
+    line_number: usize,
+) -> Result<()> {
+    for (idx, field) in fields.iter().enumerate() {
+        let explicit = options
+            .column_types
+            .and_then(|column_types| column_types.get(&field.name))
+            .copied();
+        let Some(column_type) = explicit.or(field.column_type) else {
+            continue;
+        };
+        let (Some(primitive), Some(raw)) = (primitives.get_mut(idx), values.get(idx)) else {
+            continue;
+        };
+        match coerce_to_column_type(primitive, raw, column_type) {
+            Some(coerced) => *primitive = coerced,
+            None if explicit.is_some() || options.strict => {
+                return Err(ToonError::validation(
+                    line_number,
+                    format!(
+                        "Column '{}' expects {}, found '{}'",
+                        field.name,
+                        column_type.as_str(),
+                        raw.trim()
+                    ),
+                ));
+            }
+            None => {}
+        }
+    }
     Ok(())
 }
 
 fn yield_object_from_fields(
-    events: &mut Vec<JsonStreamEvent>,
+    events: &mut dyn EventSink,
     fields: &[FieldName],
     primitives: &[crate::JsonPrimitive],
-) {
-    events.push(JsonStreamEvent::StartObject);
+    missing_cell: MissingCellMode,
+    line_number: usize,
+) -> Result<()> {
+    start_object(events, Some(line_number))?;
     for (idx, field) in fields.iter().enumerate() {
-        events.push(JsonStreamEvent::Key {
+        let value = match (primitives.get(idx), missing_cell) {
+            (Some(value), _) => value.clone(),
+            (None, MissingCellMode::Null) => crate::StringOrNumberOrBoolOrNull::Null,
+            (None, MissingCellMode::Omit) => continue,
+            (None, MissingCellMode::Error) => {
+                return Err(ToonError::validation(
+                    line_number,
+                    format!("Tabular row is missing a value for field '{}'", field.name),
+                ));
+            }
+        };
+        events.event(JsonStreamEvent::Key {
             key: field.name.clone(),
             was_quoted: field.was_quoted,
-        });
-        if let Some(value) = primitives.get(idx) {
-            events.push(JsonStreamEvent::Primitive {
-                value: value.clone(),
-            });
-        } else {
-            events.push(JsonStreamEvent::Primitive {
-                value: crate::StringOrNumberOrBoolOrNull::Null,
-            });
-        }
+        })?;
+        events.event(JsonStreamEvent::Primitive { value })?;
     }
-    events.push(JsonStreamEvent::EndObject);
+    events.event(JsonStreamEvent::EndObject)?;
+    Ok(())
 }
 
-fn is_key_value_line_sync(line: &ParsedLine) -> bool {
+pub(crate) fn is_key_value_line_sync(line: &ParsedLine) -> bool {
     let content = line.content.as_str();
     if content.starts_with('"') {
         if let Some(closing) = find_closing_quote(content, 0) {
@@ -1,6 +1,7 @@
 use std::collections::HashSet;
 
 use crate::error::{Result, ToonError};
+use crate::sink::EventSink;
 use crate::{JsonPrimitive, JsonStreamEvent, JsonValue};
 
 #[derive(Debug, Clone, PartialEq)]
@@ -8,6 +9,8 @@ pub enum NodeValue {
     Primitive(JsonPrimitive),
     Array(Vec<Self>),
     Object(ObjectNode),
+    #[cfg(feature = "raw")]
+    Raw(String),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -28,12 +31,46 @@ enum BuildContext {
     },
 }
 
-#[derive(Debug, Clone)]
-struct BuildState {
+/// An [`EventSink`] that builds the value described by the events.
+#[derive(Debug, Clone, Default)]
+pub struct ValueSink {
     stack: Vec<BuildContext>,
     root: Option<NodeValue>,
 }
 
+impl ValueSink {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The built node tree, which keeps track of quoted keys.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the events did not describe exactly one complete
+    /// value.
+    pub fn finish_node(self) -> Result<NodeValue> {
+        finalize_state(self)
+    }
+
+    /// The built value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the events did not describe exactly one complete
+    /// value.
+    pub fn finish(self) -> Result<JsonValue> {
+        self.finish_node().map(node_to_json)
+    }
+}
+
+impl EventSink for ValueSink {
+    fn event(&mut self, event: JsonStreamEvent) -> Result<()> {
+        apply_event(self, event)
+    }
+}
+
 /// Build a decoded node tree from a stream of events.
 ///
 /// # Errors
@@ -43,16 +80,11 @@ struct BuildState {
 pub fn build_node_from_events(
     events: impl IntoIterator<Item = JsonStreamEvent>,
 ) -> Result<NodeValue> {
-    let mut state = BuildState {
-        stack: Vec::new(),
-        root: None,
-    };
-
+    let mut sink = ValueSink::new();
     for event in events {
-        apply_event(&mut state, event)?;
+        sink.event(event)?;
     }
-
-    finalize_state(state)
+    sink.finish_node()
 }
 
 pub fn node_to_json(value: NodeValue) -> JsonValue {
@@ -65,11 +97,31 @@ pub fn node_to_json(value: NodeValue) -> JsonValue {
                 .map(|(key, value)| (key, node_to_json(value)))
                 .collect(),
         ),
+        #[cfg(feature = "raw")]
+        NodeValue::Raw(text) => JsonValue::Raw(text),
+    }
+}
+
+/// The node tree of a value. No key counts as quoted, so every dotted key is
+/// a candidate for path expansion.
+pub fn json_to_node(value: JsonValue) -> NodeValue {
+    match value {
+        JsonValue::Primitive(value) => NodeValue::Primitive(value),
+        JsonValue::Array(items) => NodeValue::Array(items.into_iter().map(json_to_node).collect()),
+        JsonValue::Object(entries) => NodeValue::Object(ObjectNode {
+            entries: entries
+                .into_iter()
+                .map(|(key, value)| (key, json_to_node(value)))
+                .collect(),
+            quoted_keys: HashSet::new(),
+        }),
+        #[cfg(feature = "raw")]
+        JsonValue::Raw(text) => NodeValue::Raw(text),
     }
 }
 
 #[allow(clippy::too_many_lines)]
-fn apply_event(state: &mut BuildState, event: JsonStreamEvent) -> Result<()> {
+fn apply_event(state: &mut ValueSink, event: JsonStreamEvent) -> Result<()> {
     match event {
         JsonStreamEvent::StartObject => {
             state.stack.push(BuildContext::Object {
@@ -166,37 +218,35 @@ fn apply_event(state: &mut BuildState, event: JsonStreamEvent) -> Result<()> {
                 quoted_keys.insert(key);
             }
         }
-        JsonStreamEvent::Primitive { value } => {
-            if state.stack.is_empty() {
-                state.root = Some(NodeValue::Primitive(value));
-                return Ok(());
-            }
+        JsonStreamEvent::Primitive { value } => push_leaf(state, NodeValue::Primitive(value))?,
+        #[cfg(feature = "raw")]
+        JsonStreamEvent::Raw { text } => push_leaf(state, NodeValue::Raw(text))?,
+    }
 
-            match state.stack.last_mut() {
-                Some(BuildContext::Object {
-                    entries,
-                    current_key,
-                    ..
-                }) => {
-                    let Some(key) = current_key.take() else {
-                        return Err(ToonError::message(
-                            "Primitive event without preceding key in object",
-                        ));
-                    };
-                    entries.push((key, NodeValue::Primitive(value)));
-                }
-                Some(BuildContext::Array { items }) => {
-                    items.push(NodeValue::Primitive(value));
-                }
-                None => {}
-            }
+    Ok(())
+}
+
+fn push_leaf(state: &mut ValueSink, node: NodeValue) -> Result<()> {
+    match state.stack.last_mut() {
+        Some(BuildContext::Object {
+            entries,
+            current_key,
+            ..
+        }) => {
+            let Some(key) = current_key.take() else {
+                return Err(ToonError::message(
+                    "Primitive event without preceding key in object",
+                ));
+            };
+            entries.push((key, node));
         }
+        Some(BuildContext::Array { items }) => items.push(node),
+        None => state.root = Some(node),
     }
-
     Ok(())
 }
 
-fn finalize_state(state: BuildState) -> Result<NodeValue> {
+fn finalize_state(state: ValueSink) -> Result<NodeValue> {
     if !state.stack.is_empty() {
         return Err(ToonError::event_stream(
             "Incomplete event stream: stack not empty at end",
@@ -21,6 +21,8 @@ pub fn expand_paths_safe(value: NodeValue, strict: bool) -> Result<NodeValue> {
         }
         NodeValue::Object(obj) => Ok(NodeValue::Object(expand_object(obj, strict)?)),
         NodeValue::Primitive(value) => Ok(NodeValue::Primitive(value)),
+        #[cfg(feature = "raw")]
+        NodeValue::Raw(text) => Ok(NodeValue::Raw(text)),
     }
 }
 
@@ -179,5 +181,7 @@ const fn node_type_name(value: &NodeValue) -> &'static str {
         NodeValue::Primitive(_) => "primitive",
         NodeValue::Array(_) => "array",
         NodeValue::Object(_) => "object",
+        #[cfg(feature = "raw")]
+        NodeValue::Raw(_) => "raw text",
     }
 }
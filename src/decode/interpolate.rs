@@ -0,0 +1,126 @@
+//! `${NAME}` interpolation in decoded string values.
+//!
+//! With [`DecodeOptions::interpolate_env`](crate::options::DecodeOptions::interpolate_env)
+//! or a [`VariableResolver`], every string value (quoted or not, including
+//! tabular cells) has `${NAME}` replaced by the variable's value, so a TOON
+//! file can serve as configuration with environment overrides:
+//!
+//! ```text
+//! database:
+//!   host: ${DB_HOST:-localhost}
+//!   password: ${DB_PASSWORD}
+//! ```
+//!
+//! `${NAME:-default}` falls back to `default` when `NAME` is undefined, and
+//! `$${` writes a literal `${`. Keys are never interpolated. An undefined
+//! variable without a default is an error in strict mode and left as written
+//! otherwise.
+
+use std::borrow::Cow;
+
+use crate::error::{Result, ToonError};
+use crate::options::VariableResolver;
+use crate::sink::{EventMetadata, EventSink};
+use crate::{JsonStreamEvent, StringOrNumberOrBoolOrNull};
+
+/// An [`EventSink`] that interpolates string primitives before passing the
+/// events on.
+#[derive(Debug)]
+pub struct InterpolatingSink<S> {
+    inner: S,
+    resolver: VariableResolver,
+    strict: bool,
+}
+
+impl<S: EventSink> InterpolatingSink<S> {
+    pub const fn new(inner: S, resolver: VariableResolver, strict: bool) -> Self {
+        Self {
+            inner,
+            resolver,
+            strict,
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: EventSink> EventSink for InterpolatingSink<S> {
+    fn event(&mut self, mut event: JsonStreamEvent) -> Result<()> {
+        if let JsonStreamEvent::Primitive {
+            value: StringOrNumberOrBoolOrNull::String(text),
+        } = &mut event
+            && let Cow::Owned(interpolated) = interpolate(text, &self.resolver, self.strict)?
+        {
+            *text = interpolated;
+        }
+        self.inner.event(event)
+    }
+
+    fn event_with_metadata(
+        &mut self,
+        event: JsonStreamEvent,
+        metadata: EventMetadata,
+    ) -> Result<()> {
+        // Only primitives are interpolated, and they carry no metadata.
+        self.inner.event_with_metadata(event, metadata)
+    }
+}
+
+/// Replace `${NAME}` references in `text`.
+///
+/// # Errors
+///
+/// Returns an error in strict mode for an undefined variable without a
+/// default, and for an unterminated `${`.
+pub fn interpolate<'a>(
+    text: &'a str,
+    resolver: &VariableResolver,
+    strict: bool,
+) -> Result<Cow<'a, str>> {
+    if !text.contains("${") {
+        return Ok(Cow::Borrowed(text));
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            out.push_str(&rest[..start - 1]);
+            out.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        out.push_str(&rest[..start]);
+        let Some(len) = rest[start + 2..].find('}') else {
+            if strict {
+                return Err(ToonError::message(format!(
+                    "Unterminated variable reference in \"{text}\""
+                )));
+            }
+            rest = &rest[start..];
+            break;
+        };
+        let reference = &rest[start + 2..start + 2 + len];
+        let (name, default) = match reference.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (reference, None),
+        };
+        match resolver.lookup(name) {
+            Some(value) => out.push_str(&value),
+            None => match default {
+                Some(default) => out.push_str(default),
+                None if strict => {
+                    return Err(ToonError::message(format!(
+                        "Undefined variable ${{{name}}}"
+                    )));
+                }
+                None => out.push_str(&rest[start..start + 3 + len]),
+            },
+        }
+        rest = &rest[start + 3 + len..];
+    }
+    out.push_str(rest);
+    Ok(Cow::Owned(out))
+}
@@ -0,0 +1,84 @@
+//! Input normalization for the lenient decode profile
+//! ([`crate::options::DecodeProfile::Lenient`]).
+//!
+//! Hand-typed TOON often mixes tabs into its indentation and leaves array
+//! lengths out (`tags[]: a,b`). These passes rewrite such input into the
+//! form the decoder expects before any structure is decoded.
+
+use crate::decode::parser::{parse_array_header_line, parse_delimited_values};
+use crate::decode::scanner::ParsedLine;
+use crate::shared::constants::{
+    CLOSE_BRACKET, COMMA, DEFAULT_DELIMITER, LIST_ITEM_PREFIX, OPEN_BRACKET, PIPE, SPACE, TAB,
+};
+use crate::shared::string_utils::find_unquoted_char;
+
+/// Replace tabs in the leading whitespace of `line` with spaces, advancing
+/// to the next multiple of `tab_width` columns for each tab.
+#[must_use]
+pub fn expand_indent_tabs(line: String, tab_width: usize) -> String {
+    let indent_end = line
+        .find(|ch| ch != SPACE && ch != TAB)
+        .unwrap_or(line.len());
+    if !line[..indent_end].contains(TAB) {
+        return line;
+    }
+
+    let mut columns = 0;
+    for ch in line[..indent_end].chars() {
+        columns = if ch == TAB && tab_width > 0 {
+            (columns / tab_width + 1) * tab_width
+        } else {
+            columns + 1
+        };
+    }
+    let mut expanded = " ".repeat(columns);
+    expanded.push_str(&line[indent_end..]);
+    expanded
+}
+
+/// Fill in empty array lengths (`[]`, `[|]`, `[\t]`) from the inline value
+/// count or the number of rows or items nested under the header.
+pub fn infer_array_lengths(lines: &mut [ParsedLine]) {
+    for index in 0..lines.len() {
+        let line = &lines[index];
+        let (header, body_depth) = match line.content.strip_prefix(LIST_ITEM_PREFIX) {
+            Some(rest) if rest.starts_with(OPEN_BRACKET) => (rest, line.depth + 1),
+            Some(rest) => (rest, line.depth + 2),
+            None => (line.content.as_str(), line.depth + 1),
+        };
+        let Some(open) = find_unquoted_char(header, OPEN_BRACKET, 0) else {
+            continue;
+        };
+        let bracket = &header[open + 1..];
+        let is_empty = match bracket.chars().next() {
+            Some(CLOSE_BRACKET) => true,
+            Some(COMMA | PIPE | TAB) => bracket[1..].starts_with(CLOSE_BRACKET),
+            _ => false,
+        };
+        if !is_empty {
+            continue;
+        }
+
+        // Parse with a placeholder length to find the delimiter and any
+        // inline values.
+        let insert_at = open + 1;
+        let probe = format!("{}0{}", &header[..insert_at], &header[insert_at..]);
+        let Ok(Some(parsed)) = parse_array_header_line(&probe, DEFAULT_DELIMITER) else {
+            continue;
+        };
+        let length = match parsed.inline_values {
+            Some(values) => parse_delimited_values(&values, parsed.header.delimiter).len(),
+            None => lines[index + 1..]
+                .iter()
+                .take_while(|nested| nested.depth >= body_depth)
+                .filter(|nested| nested.depth == body_depth)
+                .count(),
+        };
+
+        let content_at = line.content.len() - header.len() + insert_at;
+        let length = length.to_string();
+        let line = &mut lines[index];
+        line.content.insert_str(content_at, &length);
+        line.raw.insert_str(line.indent + content_at, &length);
+    }
+}
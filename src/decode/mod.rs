@@ -1,24 +1,49 @@
+pub mod columnar;
+#[cfg_attr(not(feature = "internals"), doc(hidden))]
 pub mod decoders;
 pub mod event_builder;
 pub mod expand;
+pub mod interpolate;
+pub mod lenient;
+#[cfg_attr(not(feature = "internals"), doc(hidden))]
 pub mod parser;
+pub mod pedantic;
+pub mod reader;
+pub mod resume;
+#[cfg_attr(not(feature = "internals"), doc(hidden))]
 pub mod scanner;
+#[cfg(feature = "serde")]
+pub mod serde;
+pub mod trace;
+#[cfg_attr(not(feature = "internals"), doc(hidden))]
 pub mod validation;
 
 #[cfg(feature = "async-stream")]
 mod async_decode;
 
+use std::ops::ControlFlow;
+
+use crate::codec::Decoder;
+use crate::decode::columnar::reassemble_columnar;
 use crate::decode::decoders as decoder_impl;
-use crate::decode::event_builder::{build_node_from_events, node_to_json};
+use crate::decode::event_builder::{ValueSink, node_to_json};
 use crate::decode::expand::expand_paths_safe;
 use crate::error::Result;
-use crate::options::{DecodeOptions, DecodeStreamOptions, ExpandPathsMode, resolve_decode_options};
+use crate::options::{
+    DecodeOptions, DecodeStreamOptions, ExpandPathsMode, ResolvedDecodeOptions,
+    resolve_decode_options,
+};
+use crate::schema::{Schema, SchemaSink, Violation};
+use crate::sink::EventSink;
 use crate::{JsonStreamEvent, JsonValue};
 
 #[cfg(feature = "async-stream")]
 pub use async_decode::{
     AsyncDecodeStream, decode_stream_async, try_decode_async, try_decode_stream_async,
 };
+pub use reader::ToonReader;
+#[cfg(feature = "serde")]
+pub use serde::from_toon_str;
 
 /// Try to decode a TOON string into a JSON value, returning a Result.
 ///
@@ -59,22 +84,62 @@ pub fn try_decode_from_lines(
     lines: impl IntoIterator<Item = String>,
     options: Option<DecodeOptions>,
 ) -> Result<JsonValue> {
-    let resolved = resolve_decode_options(options);
-    let events = decoder_impl::decode_stream_sync(
-        lines,
-        Some(DecodeStreamOptions {
-            indent: Some(resolved.indent),
-            strict: Some(resolved.strict),
-        }),
-    )?;
+    Decoder::new(options).try_decode_lines(lines)
+}
 
-    let mut node = build_node_from_events(events)?;
+/// Apply the value-level decode options to a built value.
+pub(crate) fn finish_value(sink: ValueSink, resolved: &ResolvedDecodeOptions) -> Result<JsonValue> {
+    let mut node = sink.finish_node()?;
 
     if resolved.expand_paths == ExpandPathsMode::Safe {
         node = expand_paths_safe(node, resolved.strict)?;
     }
 
-    Ok(node_to_json(node))
+    let value = node_to_json(node);
+    if resolved.columnar {
+        reassemble_columnar(value)
+    } else {
+        Ok(value)
+    }
+}
+
+/// Try to decode a TOON string into a value typed by `schema`, returning the
+/// value and every place it does not match the schema.
+///
+/// Typing happens while the value is built, so schema paths refer to the
+/// document as written, before path expansion.
+///
+/// # Errors
+///
+/// Returns an error if decoding fails due to malformed input or strict-mode validation errors.
+/// Schema violations are returned, not raised.
+pub fn try_decode_with_schema(
+    input: &str,
+    schema: &Schema,
+    options: Option<DecodeOptions>,
+) -> Result<(JsonValue, Vec<Violation>)> {
+    let resolved = resolve_decode_options(options);
+    let lines = input.split('\n').map(str::to_string);
+    let mut sink = SchemaSink::new(ValueSink::new(), schema);
+    decoder_impl::decode_into_sink(lines, Some(resolved.stream_options()), &mut sink)?;
+    let (sink, violations) = sink.into_parts();
+    Ok((finish_value(sink, &resolved)?, violations))
+}
+
+/// Decode a TOON string into a value typed by `schema`, with the schema
+/// violations found.
+///
+/// # Panics
+///
+/// Panics if decoding fails due to malformed input or strict-mode validation errors.
+/// Use [`try_decode_with_schema`] for a fallible version that returns `Result`.
+#[must_use]
+pub fn decode_with_schema(
+    input: &str,
+    schema: &Schema,
+    options: Option<DecodeOptions>,
+) -> (JsonValue, Vec<Violation>) {
+    try_decode_with_schema(input, schema, options).unwrap_or_else(|err| panic!("{err}"))
 }
 
 #[must_use]
@@ -120,6 +185,86 @@ pub fn decode_stream_sync(
     try_decode_stream_sync(lines, options).unwrap_or_else(|err| panic!("{err}"))
 }
 
+/// Decode TOON lines into an iterator of events, produced as it is advanced.
+///
+/// Unlike [`try_decode_stream_sync`], a consumer can stop early without the
+/// rest of the lines being read. Lines are decoded a section at a time, as
+/// [`ToonReader`] does; with `anchors`, `infer_lengths`, or `pedantic` set,
+/// the whole document is decoded on the first call instead. The events
+/// before an error come first, then the error, and then the iterator ends.
+///
+/// ```
+/// use toon::{JsonStreamEvent, decode_events_iter};
+///
+/// let lines = ["name: demo", "users[2]{id}:", "  1", "  2"].map(String::from);
+/// let first_key = decode_events_iter(lines, None)
+///     .find_map(|event| match event {
+///         Ok(JsonStreamEvent::Key { key, .. }) => Some(key),
+///         _ => None,
+///     });
+/// assert_eq!(first_key.as_deref(), Some("name"));
+/// ```
+pub fn decode_events_iter(
+    lines: impl IntoIterator<Item = String>,
+    options: Option<DecodeStreamOptions>,
+) -> impl Iterator<Item = Result<JsonStreamEvent>> {
+    reader::events_iter(lines, options)
+}
+
+/// Decode TOON lines, passing each event to `visit` until it returns
+/// [`ControlFlow::Break`]. Returns whether decoding stopped there.
+///
+/// An [`EventSink`] can only stop [`decode_into_sink`] by returning an
+/// error; `visit` stops without one, and the lines after the event it stops
+/// at are not read (see [`decode_events_iter`]).
+///
+/// ```
+/// use std::ops::ControlFlow;
+/// use toon::{JsonStreamEvent, decode_until};
+///
+/// let lines = ["a: 1", "rows[2]{id}:", "  1", "  2"].map(String::from);
+/// let mut keys = 0;
+/// let stopped = decode_until(lines, None, |event| {
+///     if matches!(event, JsonStreamEvent::Key { .. }) {
+///         keys += 1;
+///     }
+///     if keys == 2 { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+/// })?;
+/// assert!(stopped.is_break());
+/// # Ok::<(), toon::error::ToonError>(())
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if decoding fails before `visit` stops it.
+pub fn decode_until(
+    lines: impl IntoIterator<Item = String>,
+    options: Option<DecodeStreamOptions>,
+    mut visit: impl FnMut(JsonStreamEvent) -> ControlFlow<()>,
+) -> Result<ControlFlow<()>> {
+    for event in reader::events_iter(lines, options) {
+        if visit(event?).is_break() {
+            return Ok(ControlFlow::Break(()));
+        }
+    }
+    Ok(ControlFlow::Continue(()))
+}
+
+/// Decode TOON lines, pushing each event into `sink` as it is produced
+/// instead of collecting them.
+///
+/// # Errors
+///
+/// Returns an error if decoding fails due to malformed input or strict-mode validation errors,
+/// or if the sink returns one. The sink has already received the events before the error.
+pub fn decode_into_sink(
+    lines: impl IntoIterator<Item = String>,
+    options: Option<DecodeStreamOptions>,
+    sink: &mut impl EventSink,
+) -> Result<()> {
+    decoder_impl::decode_into_sink(lines, options, sink)
+}
+
 /// Try to decode TOON lines into a stream of events asynchronously, returning a Result.
 ///
 /// This is the fallible version of [`decode_stream`]. Use this when you want to handle
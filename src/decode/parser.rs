@@ -1,6 +1,10 @@
+use std::cell::Cell;
+
 use crate::error::{Result, ToonError};
+use crate::options::{ColumnType, EmptyCellMode};
 use crate::shared::constants::{
-    BACKSLASH, CLOSE_BRACE, CLOSE_BRACKET, COLON, DOUBLE_QUOTE, OPEN_BRACE, OPEN_BRACKET, PIPE, TAB,
+    BACKSLASH, CLOSE_BRACE, CLOSE_BRACKET, COLON, COMMA, DOUBLE_QUOTE, OPEN_BRACE, OPEN_BRACKET,
+    PIPE, RUN_MARKER, TAB,
 };
 use crate::shared::literal_utils::{is_boolean_or_null_literal, is_numeric_literal};
 use crate::shared::string_utils::{find_closing_quote, find_unquoted_char, unescape_string};
@@ -18,6 +22,8 @@ pub struct ArrayHeaderInfo {
 pub struct FieldName {
     pub name: String,
     pub was_quoted: bool,
+    /// Type annotation from a typed header (`{id:int}`), if any.
+    pub column_type: Option<ColumnType>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -70,13 +76,11 @@ pub fn parse_array_header_line(
         .find(COLON)
         .map(|idx| bracket_end + 1 + idx);
 
-    if let (Some(brace_start), Some(colon_after_bracket)) = (brace_start, colon_after_bracket) {
-        if brace_start < colon_after_bracket {
-            if let Some(found_end) = content[brace_start..].find(CLOSE_BRACE) {
-                let found_end = brace_start + found_end;
-                brace_end = found_end + 1;
-            }
-        }
+    if let (Some(brace_start), Some(colon_after_bracket)) = (brace_start, colon_after_bracket)
+        && brace_start < colon_after_bracket
+        && let Some(found_end) = find_unquoted_char(content, CLOSE_BRACE, brace_start)
+    {
+        brace_end = found_end + 1;
     }
 
     let colon_index = content[brace_end..].find(COLON).map(|idx| brace_end + idx);
@@ -103,27 +107,29 @@ pub fn parse_array_header_line(
         return Ok(None);
     };
 
-    let mut fields: Option<Vec<FieldName>> = None;
-    if let Some(brace_start) = brace_start {
-        if brace_start < colon_index {
-            if let Some(found_end) = content[brace_start..].find(CLOSE_BRACE) {
-                let found_end = brace_start + found_end;
-                if found_end < colon_index {
-                    let fields_content = &content[brace_start + 1..found_end];
-                    let parsed_fields = parse_delimited_values(fields_content, delimiter)
-                        .into_iter()
-                        .map(|field| {
-                            let trimmed = field.trim();
-                            let was_quoted = trimmed.starts_with(DOUBLE_QUOTE);
-                            let name = parse_string_literal(trimmed)?;
-                            Ok(FieldName { name, was_quoted })
-                        })
-                        .collect::<Result<Vec<_>>>()?;
-                    fields = Some(parsed_fields);
-                }
-            }
-        }
-    }
+    let fields = if let Some(brace_start) = brace_start
+        && brace_start < colon_index
+        && let Some(found_end) = find_unquoted_char(content, CLOSE_BRACE, brace_start)
+        && found_end < colon_index
+    {
+        let fields_content = &content[brace_start + 1..found_end];
+        let parsed_fields = parse_delimited_values(fields_content, delimiter)
+            .into_iter()
+            .map(|field| {
+                let trimmed = field.trim();
+                let was_quoted = trimmed.starts_with(DOUBLE_QUOTE);
+                let name = parse_string_literal(trimmed)?;
+                Ok(FieldName {
+                    name,
+                    was_quoted,
+                    column_type: None,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Some(parsed_fields)
+    } else {
+        None
+    };
 
     Ok(Some(ArrayHeaderParseResult {
         header: ArrayHeaderInfo {
@@ -156,6 +162,9 @@ pub fn parse_bracket_segment(seg: &str, default_delimiter: char) -> Result<(usiz
     } else if content.ends_with(PIPE) {
         delimiter = PIPE;
         content.pop();
+    } else if content.ends_with(COMMA) {
+        delimiter = COMMA;
+        content.pop();
     }
 
     let length = content
@@ -207,16 +216,106 @@ pub fn parse_delimited_values(input: &str, delimiter: char) -> Vec<String> {
 
 /// Map delimited string values into JSON primitives.
 ///
+/// Unquoted empty cells are mapped according to `empty_cell`.
+///
 /// # Errors
 ///
-/// Returns an error if any token is a malformed quoted string.
-pub fn map_row_values_to_primitives(values: &[String]) -> Result<Vec<crate::JsonPrimitive>> {
+/// Returns an error if any token is a malformed quoted string, or if a cell
+/// is empty and `empty_cell` is [`EmptyCellMode::Error`].
+pub fn map_row_values_to_primitives(
+    values: &[String],
+    empty_cell: EmptyCellMode,
+    line_number: usize,
+) -> Result<Vec<crate::JsonPrimitive>> {
     values
         .iter()
-        .map(|value| parse_primitive_token(value))
+        .enumerate()
+        .map(|(idx, value)| {
+            if !value.trim().is_empty() {
+                return parse_primitive_token(value);
+            }
+            match empty_cell {
+                EmptyCellMode::EmptyString => {
+                    Ok(crate::StringOrNumberOrBoolOrNull::String(String::new()))
+                }
+                EmptyCellMode::Null => Ok(crate::StringOrNumberOrBoolOrNull::Null),
+                EmptyCellMode::Error => Err(ToonError::validation(
+                    line_number,
+                    format!(
+                        "Empty cell at position {}; write \"\" for an empty string",
+                        idx + 1
+                    ),
+                )),
+            }
+        })
         .collect()
 }
 
+/// Split type annotations (`id:int`) off unquoted tabular header fields.
+///
+/// # Errors
+///
+/// Returns an error if an annotation names an unknown column type.
+pub fn parse_field_type_annotations(fields: &mut [FieldName], line_number: usize) -> Result<()> {
+    for field in fields.iter_mut().filter(|field| !field.was_quoted) {
+        let Some((name, annotation)) = field.name.split_once(COLON) else {
+            continue;
+        };
+        let column_type = ColumnType::from_annotation(annotation.trim()).ok_or_else(|| {
+            ToonError::parse(
+                line_number,
+                format!("Unknown column type '{annotation}' for field '{name}'"),
+            )
+        })?;
+        field.column_type = Some(column_type);
+        field.name = name.trim().to_string();
+    }
+    Ok(())
+}
+
+/// Coerce a parsed cell to a column type.
+///
+/// `raw` is the cell token `primitive` was parsed from. Returns `None` if
+/// the cell cannot be converted; `null` is accepted for every type.
+#[must_use]
+pub fn coerce_to_column_type(
+    primitive: &crate::JsonPrimitive,
+    raw: &str,
+    column_type: ColumnType,
+) -> Option<crate::JsonPrimitive> {
+    use crate::StringOrNumberOrBoolOrNull as P;
+
+    match (column_type, primitive) {
+        (ColumnType::Auto, _)
+        | (_, P::Null)
+        | (ColumnType::String, P::String(_))
+        | (ColumnType::Number | ColumnType::Integer, P::Int(_) | P::UInt(_))
+        | (ColumnType::Number, P::Number(_) | P::NumberLexeme(_))
+        | (ColumnType::Bool, P::Bool(_)) => Some(primitive.clone()),
+        (ColumnType::Integer, P::Number(number)) => {
+            (number.fract() == 0.0).then(|| primitive.clone())
+        }
+        (ColumnType::Integer, P::NumberLexeme(_)) => {
+            primitive.is_integer().then(|| primitive.clone())
+        }
+        (ColumnType::String, _) => Some(P::String(raw.trim().to_string())),
+        (ColumnType::Number | ColumnType::Integer, P::String(text)) => text
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|number| {
+                number.is_finite() && (column_type == ColumnType::Number || number.fract() == 0.0)
+            })
+            .map(|_| P::from_number_lexeme(text.trim())),
+        (ColumnType::Bool, P::String(text)) => match text.trim() {
+            "true" => Some(P::Bool(true)),
+            "false" => Some(P::Bool(false)),
+            _ => None,
+        },
+        (ColumnType::Number | ColumnType::Integer | ColumnType::Bool, _) => None,
+    }
+}
+
 /// Parse a primitive token into a JSON primitive.
 ///
 /// # Errors
@@ -244,13 +343,11 @@ pub fn parse_primitive_token(token: &str) -> Result<crate::JsonPrimitive> {
     }
 
     if is_numeric_literal(trimmed) {
-        let parsed = trimmed.parse::<f64>().unwrap_or(f64::NAN);
-        let normalized = if parsed == 0.0 && parsed.is_sign_negative() {
-            0.0
-        } else {
-            parsed
-        };
-        return Ok(crate::StringOrNumberOrBoolOrNull::Number(normalized));
+        #[cfg(feature = "arbitrary-precision")]
+        let number = crate::StringOrNumberOrBoolOrNull::from_decimal(trimmed);
+        #[cfg(not(feature = "arbitrary-precision"))]
+        let number = crate::StringOrNumberOrBoolOrNull::from_number_lexeme(trimmed);
+        return Ok(number);
     }
 
     Ok(crate::StringOrNumberOrBoolOrNull::String(
@@ -258,6 +355,17 @@ pub fn parse_primitive_token(token: &str) -> Result<crate::JsonPrimitive> {
     ))
 }
 
+/// `primitive`, parsed from `token`, kept as the text of the token if it is
+/// a number, for lossless number mode.
+#[must_use]
+pub fn number_as_written(primitive: crate::JsonPrimitive, token: &str) -> crate::JsonPrimitive {
+    if primitive.is_number() {
+        crate::StringOrNumberOrBoolOrNull::NumberLexeme(token.trim().to_string())
+    } else {
+        primitive
+    }
+}
+
 /// Parse a quoted string literal, unescaping escape sequences.
 ///
 /// # Errors
@@ -340,6 +448,72 @@ pub fn is_array_header_content(content: &str) -> bool {
         && find_unquoted_char(content, COLON, 0).is_some()
 }
 
+/// Expand `value*count` cells into `count` copies of `value`.
+///
+/// The expanded row may not exceed the declared array length, and the cells
+/// runs add are drawn from `budget`. The header and the run count come from
+/// the same input, so only a budget tied to the size of that input keeps a
+/// hostile run from allocating without bound.
+///
+/// # Errors
+///
+/// Returns an error if the runs expand past `length` values or past the
+/// budget.
+pub fn expand_runs(
+    values: Vec<String>,
+    length: usize,
+    budget: &Cell<usize>,
+    line_number: usize,
+) -> Result<Vec<String>> {
+    let mut expanded = Vec::with_capacity(values.len());
+    for value in values {
+        let Some((cell, count)) = split_run(&value) else {
+            expanded.push(value);
+            continue;
+        };
+        if count > length.saturating_sub(expanded.len()) {
+            return Err(ToonError::parse(
+                line_number,
+                format!("Run '{value}' expands past the array length {length}"),
+            ));
+        }
+        let remaining = budget.get();
+        if count > remaining {
+            return Err(ToonError::parse(
+                line_number,
+                format!("Run '{value}' expands to more cells than this input can plausibly hold"),
+            ));
+        }
+        budget.set(remaining - count);
+        expanded.extend(std::iter::repeat_n(cell.to_string(), count));
+    }
+    Ok(expanded)
+}
+
+/// Split a run cell (`0*998`, `"a b"*3`) into its value and count.
+fn split_run(value: &str) -> Option<(&str, usize)> {
+    let trimmed = value.trim();
+    let (cell, count) = trimmed.rsplit_once(RUN_MARKER)?;
+    if cell.is_empty()
+        || count.is_empty()
+        || !count.bytes().all(|byte| byte.is_ascii_digit())
+        || (cell.starts_with(DOUBLE_QUOTE) && (cell.len() < 2 || !cell.ends_with(DOUBLE_QUOTE)))
+    {
+        return None;
+    }
+    Some((cell, count.parse().ok()?))
+}
+
+/// Split a sparse array entry line (`12: value`) into its index and value.
+#[must_use]
+pub fn parse_sparse_entry(content: &str) -> Option<(usize, &str)> {
+    let (index, value) = content.split_once(COLON)?;
+    if index.is_empty() || !index.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    Some((index.parse().ok()?, value.trim()))
+}
+
 #[must_use]
 pub fn is_key_value_content(content: &str) -> bool {
     find_unquoted_char(content, COLON, 0).is_some()
@@ -0,0 +1,205 @@
+//! Pedantic grammar checks (`DecodeOptions::pedantic`).
+//!
+//! The decoder tolerates some whitespace and header formatting that the
+//! spec's canonical form does not allow: extra spaces after a colon or
+//! around delimiters, trailing whitespace, and loosely written headers.
+//! These checks report every such deviation, so documents can be verified
+//! as canonical rather than merely decodable.
+
+use std::fmt;
+
+use crate::error::{Result, ToonError};
+use crate::lex::{Token, TokenKind, lex_toon};
+use crate::shared::constants::{
+    CLOSE_BRACE, CLOSE_BRACKET, COMMA, OPEN_BRACE, OPEN_BRACKET, PIPE, SPACE, TAB,
+};
+
+/// A place where the input deviates from the canonical grammar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PedanticViolation {
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based byte column.
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PedanticViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+/// Report every pedantic grammar violation in `input`, in order.
+#[must_use]
+pub fn pedantic_violations(input: &str) -> Vec<PedanticViolation> {
+    let line_starts = std::iter::once(0)
+        .chain(input.match_indices('\n').map(|(index, _)| index + 1))
+        .collect::<Vec<_>>();
+    let mut checker = Checker {
+        input,
+        line_starts: &line_starts,
+        violations: Vec::new(),
+    };
+
+    for (index, &start) in line_starts.iter().enumerate() {
+        let end = line_starts
+            .get(index + 1)
+            .map_or(input.len(), |next| next - 1);
+        let line = &input[start..end];
+        let trimmed = line.trim_end();
+        if trimmed.len() < line.len() {
+            checker.report(start + trimmed.len(), "trailing whitespace");
+        }
+    }
+
+    let tokens = lex_toon(input);
+    for (index, token) in tokens.iter().enumerate() {
+        let previous = index.checked_sub(1).map(|index| &tokens[index]);
+        let next = tokens.get(index + 1);
+        checker.token(token, previous, next);
+    }
+
+    checker
+        .violations
+        .sort_by_key(|violation| (violation.line, violation.column));
+    checker.violations
+}
+
+/// Fail with every pedantic violation in `lines` (see [`pedantic_violations`]).
+///
+/// # Errors
+///
+/// Returns a validation error at the first violation, listing all of them.
+pub fn check_pedantic(lines: &[String]) -> Result<()> {
+    let violations = pedantic_violations(&lines.join("\n"));
+    let Some(first) = violations.first() else {
+        return Ok(());
+    };
+    let listed = violations
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ");
+    let count = violations.len();
+    let noun = if count == 1 {
+        "violation"
+    } else {
+        "violations"
+    };
+    Err(ToonError::validation(
+        first.line,
+        format!("{count} pedantic {noun}: {listed}"),
+    ))
+}
+
+struct Checker<'a> {
+    input: &'a str,
+    line_starts: &'a [usize],
+    violations: Vec<PedanticViolation>,
+}
+
+impl Checker<'_> {
+    fn token(&mut self, token: &Token, previous: Option<&Token>, next: Option<&Token>) {
+        let next_on_line = next.filter(|next| self.same_line(token, next));
+        match token.kind {
+            TokenKind::Colon => {
+                if let Some(previous) = previous.filter(|previous| self.same_line(previous, token))
+                    && previous.span.end < token.span.start
+                {
+                    self.report(previous.span.end, "unexpected whitespace before ':'");
+                }
+                if let Some(next) = next_on_line
+                    && &self.input[token.span.end..next.span.start] != " "
+                {
+                    self.report(token.span.end, "expected a single space after ':'");
+                }
+            }
+            TokenKind::ListMarker => {
+                if let Some(next) = next_on_line
+                    && &self.input[token.span.end..next.span.start] != " "
+                {
+                    self.report(token.span.end, "expected a single space after '-'");
+                }
+            }
+            TokenKind::Delimiter => {
+                if let Some(previous) = previous.filter(|previous| {
+                    previous.kind == TokenKind::Cell && previous.span.end < token.span.start
+                }) {
+                    self.report(previous.span.end, "unexpected whitespace before delimiter");
+                }
+                if next_on_line.is_some_and(|next| {
+                    next.kind == TokenKind::Cell && token.span.end < next.span.start
+                }) {
+                    self.report(token.span.end, "unexpected whitespace after delimiter");
+                }
+            }
+            TokenKind::Header => {
+                if let Some(previous) = previous.filter(|previous| previous.kind == TokenKind::Key)
+                    && previous.span.end < token.span.start
+                {
+                    self.report(previous.span.end, "unexpected whitespace before '['");
+                }
+                self.header(token);
+            }
+            _ => {}
+        }
+    }
+
+    /// Check `[N<delimiter>]{fields}` for a canonical length, delimiter
+    /// marker, and field list.
+    fn header(&mut self, token: &Token) {
+        let header = &self.input[token.span.clone()];
+        let Some(close) = header.find(CLOSE_BRACKET) else {
+            return;
+        };
+        let bracket = &header[OPEN_BRACKET.len_utf8()..close];
+        let (length, delimiter) = match bracket.chars().last() {
+            Some(marker @ (COMMA | PIPE | TAB)) => (&bracket[..bracket.len() - 1], marker),
+            _ => (bracket, COMMA),
+        };
+        if length.is_empty()
+            || !length.bytes().all(|byte| byte.is_ascii_digit())
+            || (length.len() > 1 && length.starts_with('0'))
+        {
+            self.report(
+                token.span.start + 1,
+                format!("array length '{length}' is not a canonical integer"),
+            );
+        }
+
+        let fields = &header[close + 1..];
+        let Some(inner) = fields
+            .strip_prefix(OPEN_BRACE)
+            .and_then(|fields| fields.strip_suffix(CLOSE_BRACE))
+        else {
+            return;
+        };
+        let mut offset = token.span.start + close + 2;
+        for field in inner.split(delimiter) {
+            if field.is_empty() {
+                self.report(offset, "empty field name in header");
+            } else if field.trim_matches([SPACE, TAB]).len() < field.len() {
+                self.report(offset, "unexpected whitespace around field name");
+            }
+            offset += field.len() + delimiter.len_utf8();
+        }
+    }
+
+    fn same_line(&self, first: &Token, second: &Token) -> bool {
+        !self.input[first.span.start..second.span.start].contains('\n')
+    }
+
+    fn report(&mut self, offset: usize, message: impl Into<String>) {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        self.violations.push(PedanticViolation {
+            line,
+            column: offset - self.line_starts[line - 1] + 1,
+            message: message.into(),
+        });
+    }
+}
@@ -0,0 +1,169 @@
+//! Pull decoding from a reader.
+//!
+//! [`ToonReader`] yields the events of a document as it reads the lines
+//! from a [`BufRead`], instead of collecting the lines first as
+//! [`decode_into_sink`](crate::decode_into_sink) does. Only one section is
+//! held at a time: a field of the root object, or an item of an array that
+//! is the root or the value of a root field. Memory is bounded for documents
+//! whose bulk is in such arrays: a file of a million table rows or list
+//! items is read in constant memory. Sections are not split any further, so
+//! a field or item holding most of the document is read whole.
+//!
+//! ```
+//! use std::io::Cursor;
+//! use toon::decode::ToonReader;
+//! use toon::decode_stream_sync;
+//!
+//! let input = "name: demo\nusers[2]{id,name}:\n  1,Ada\n  2,Bob";
+//! let events = ToonReader::new(Cursor::new(input)).collect::<Result<Vec<_>, _>>()?;
+//! assert_eq!(events, decode_stream_sync(input.lines().map(String::from), None));
+//! # Ok::<(), toon::error::ToonError>(())
+//! ```
+
+use std::io::{BufRead, Read};
+
+use crate::JsonStreamEvent;
+use crate::decode::decoders as decoder_impl;
+use crate::decode::resume::{ResumableDecoder, whole_document_option};
+use crate::error::{Result, ToonError};
+use crate::options::DecodeStreamOptions;
+
+/// An iterator over the events of the document a reader holds. See the
+/// [module documentation](self).
+///
+/// After an error, the iterator ends.
+///
+/// # Memory
+///
+/// The lines and events of one section are buffered before any of its
+/// events are yielded, so memory is bounded by the largest section rather
+/// than by the document. Sections stop at the second level: a root field
+/// whose value is a nested object, or an array item that is one, is read
+/// whole, however large. Keep large data in a root-level array, or an array
+/// under a root field, to read it in constant memory.
+pub struct ToonReader<R> {
+    decoder: ResumableDecoder<R>,
+    /// Events of the last section decoded, not yet yielded.
+    events: std::vec::IntoIter<JsonStreamEvent>,
+    /// The error that stopped decoding, yielded after the events before it.
+    error: Option<ToonError>,
+    done: bool,
+}
+
+impl<R: BufRead> ToonReader<R> {
+    /// Read the document `reader` holds with the default options.
+    #[must_use]
+    pub fn new(reader: R) -> Self {
+        Self::from_decoder(ResumableDecoder::with_supported(
+            reader,
+            DecodeStreamOptions::default(),
+        ))
+    }
+
+    /// Read the document `reader` holds with `options`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `options` ask for anchors, inferred lengths, or
+    /// pedantic checks, which need the whole document at once.
+    pub fn with_options(reader: R, options: Option<DecodeStreamOptions>) -> Result<Self> {
+        ResumableDecoder::new(reader, options).map(Self::from_decoder)
+    }
+
+    fn from_decoder(decoder: ResumableDecoder<R>) -> Self {
+        Self {
+            decoder,
+            events: Vec::new().into_iter(),
+            error: None,
+            done: false,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for ToonReader<R> {
+    type Item = Result<JsonStreamEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.events.next() {
+                return Some(Ok(event));
+            }
+            if self.done {
+                return self.error.take().map(Err);
+            }
+            let mut events = Vec::new();
+            match self.decoder.step(&mut events) {
+                Ok(more) => self.done = !more,
+                Err(err) => {
+                    self.error = Some(err);
+                    self.done = true;
+                }
+            }
+            self.events = events.into_iter();
+        }
+    }
+}
+
+/// The events of the document `lines` hold, decoded as the iterator is
+/// advanced. Options that need the whole document at once decode it all on
+/// the first call instead.
+pub(crate) fn events_iter(
+    lines: impl IntoIterator<Item = String>,
+    options: Option<DecodeStreamOptions>,
+) -> impl Iterator<Item = Result<JsonStreamEvent>> {
+    let options = options.unwrap_or_default();
+    let (lazy, eager) = if whole_document_option(&options).is_some() {
+        let events = match decoder_impl::decode_stream_sync(lines, Some(options)) {
+            Ok(events) => events.into_iter().map(Ok).collect(),
+            Err(err) => vec![Err(err)],
+        };
+        (None, Some(events))
+    } else {
+        let source = LineSource {
+            lines: lines.into_iter(),
+            buffer: Vec::new(),
+            position: 0,
+        };
+        let decoder = ResumableDecoder::with_supported(source, options);
+        (Some(ToonReader::from_decoder(decoder)), None)
+    };
+    lazy.into_iter()
+        .flatten()
+        .chain(eager.into_iter().flatten())
+}
+
+/// Lines read as the bytes of a document, one at a time.
+struct LineSource<I> {
+    lines: I,
+    /// The current line and its line feed.
+    buffer: Vec<u8>,
+    /// Bytes of `buffer` already read.
+    position: usize,
+}
+
+impl<I: Iterator<Item = String>> Read for LineSource<I> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let available = self.fill_buf()?;
+        let read = available.len().min(buf.len());
+        buf[..read].copy_from_slice(&available[..read]);
+        self.consume(read);
+        Ok(read)
+    }
+}
+
+impl<I: Iterator<Item = String>> BufRead for LineSource<I> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        if self.position == self.buffer.len()
+            && let Some(line) = self.lines.next()
+        {
+            self.buffer = line.into_bytes();
+            self.buffer.push(b'\n');
+            self.position = 0;
+        }
+        Ok(&self.buffer[self.position..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position += amount;
+    }
+}
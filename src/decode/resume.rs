@@ -0,0 +1,665 @@
+//! Decoding that can stop and resume.
+//!
+//! [`ResumableDecoder`] reads a document from a [`BufRead`] one section at
+//! a time: a field of the root object, or an item of an array that is the
+//! root or the value of a root field (a table of a million rows, say). Each
+//! section is decoded as the whole-document decoder would decode it, and
+//! between sections [`ResumableDecoder::checkpoint`] captures everything
+//! needed to go on: the byte offset of the next section, the scanner state,
+//! and where the decoder is in the document. A long-running ingestion job
+//! can save the checkpoint after every few thousand lines, and after a
+//! restart continue from it with [`ResumableDecoder::resume`] on the same
+//! file instead of starting over.
+//!
+//! With the `serde` feature, [`DecodeCheckpoint`] implements `Serialize` and
+//! `Deserialize`. Options are not part of a checkpoint; resume with the ones
+//! decoding started with. Sparse arrays and nested arrays are decoded as one
+//! section with the field or item holding them, and the options that need
+//! the whole document at once (`anchors`, `infer_lengths`, and `pedantic`)
+//! are not supported.
+//!
+//! ```
+//! use std::io::Cursor;
+//! use toon::decode::resume::ResumableDecoder;
+//! use toon::decode_stream_sync;
+//!
+//! let input = "name: demo\nusers[3]{id,name}:\n  1,Ada\n  2,Bob\n  3,Cy";
+//! let mut events = Vec::new();
+//! let mut decoder = ResumableDecoder::new(Cursor::new(input), None)?;
+//! decoder.decode_lines(3, &mut events)?;
+//! let checkpoint = decoder.checkpoint();
+//!
+//! // Later, possibly in another process:
+//! let mut decoder = ResumableDecoder::resume(Cursor::new(input), checkpoint, None)?;
+//! while decoder.step(&mut events)? {}
+//! assert_eq!(events, decode_stream_sync(input.lines().map(String::from), None));
+//! # Ok::<(), toon::error::ToonError>(())
+//! ```
+
+use std::io::{BufRead, Seek, SeekFrom};
+
+use crate::JsonStreamEvent;
+use crate::decode::decoders::{decode_section_into_sink, is_key_value_line_sync};
+use crate::decode::lenient::expand_indent_tabs;
+use crate::decode::parser::{
+    ArrayHeaderParseResult, is_array_header_content, parse_array_header_line,
+    parse_field_type_annotations,
+};
+use crate::decode::scanner::{
+    BlankLineInfo, ParsedLine, StreamingScanState, parse_line_incremental,
+};
+use crate::decode::validation::assert_expected_count;
+use crate::error::{Result, ToonError};
+use crate::options::DecodeStreamOptions;
+use crate::shared::constants::{DEFAULT_DELIMITER, LIST_ITEM_MARKER, LIST_ITEM_PREFIX};
+use crate::shared::directive::Directive;
+use crate::shared::string_utils::find_closing_quote;
+use crate::sink::{EventMetadata, EventSink};
+
+/// Where a [`ResumableDecoder`] is in a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub enum DecodePosition {
+    /// Nothing decoded yet.
+    Start,
+    /// Between the fields of the root object.
+    Fields,
+    /// Between the items of an array, which is the value of a root field
+    /// when `in_object` is set and the root otherwise.
+    Items {
+        /// The array header after its length, such as `|]{id|name}:`.
+        header_rest: String,
+        in_object: bool,
+        tabular: bool,
+        /// The length written in the header.
+        expected: usize,
+        /// Items decoded so far.
+        decoded: usize,
+    },
+    /// The whole document has been decoded.
+    Finished,
+}
+
+/// Everything a [`ResumableDecoder`] needs to continue decoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct DecodeCheckpoint {
+    /// Byte offset of the first line not yet decoded.
+    pub offset: u64,
+    /// The number of lines before `offset`, and the blank lines since the
+    /// last line decoded, which strict mode rejects inside an array.
+    pub scan: StreamingScanState,
+    /// The indentation in use, from the options or a `#%toon` directive.
+    pub indent: usize,
+    /// The delimiter of headers that do not declare one, from the options
+    /// or a `#%toon` directive.
+    pub delimiter: char,
+    pub position: DecodePosition,
+}
+
+/// A non-blank line, with where it starts and the blank lines before it.
+struct Line {
+    raw: String,
+    parsed: ParsedLine,
+    start: u64,
+    blank_lines: Vec<BlankLineInfo>,
+}
+
+/// Decodes a document from a reader a section at a time. See the
+/// [module documentation](self).
+pub struct ResumableDecoder<R> {
+    reader: R,
+    options: DecodeStreamOptions,
+    /// The state after the last section decoded.
+    checkpoint: DecodeCheckpoint,
+    /// The first line of the next section, already read.
+    pending: Option<Line>,
+    /// The scanner state after the last line read.
+    scan: StreamingScanState,
+    /// Byte offset after the last line read.
+    offset: u64,
+}
+
+impl<R: BufRead> ResumableDecoder<R> {
+    /// Start decoding the document `reader` holds.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `options` ask for anchors, inferred lengths, or
+    /// pedantic checks.
+    pub fn new(reader: R, options: Option<DecodeStreamOptions>) -> Result<Self> {
+        let options = supported(options.unwrap_or_default())?;
+        Ok(Self::with_supported(reader, options))
+    }
+
+    /// Start decoding with options already known to be supported.
+    pub(crate) fn with_supported(reader: R, options: DecodeStreamOptions) -> Self {
+        let checkpoint = DecodeCheckpoint {
+            offset: 0,
+            scan: StreamingScanState {
+                line_number: 0,
+                blank_lines: Vec::new(),
+            },
+            indent: options.indent.unwrap_or(2),
+            delimiter: options.delimiter.unwrap_or(DEFAULT_DELIMITER),
+            position: DecodePosition::Start,
+        };
+        Self::at(reader, checkpoint, options)
+    }
+
+    fn at(reader: R, checkpoint: DecodeCheckpoint, options: DecodeStreamOptions) -> Self {
+        let options = DecodeStreamOptions {
+            indent: Some(checkpoint.indent),
+            delimiter: Some(checkpoint.delimiter),
+            ..options
+        };
+        Self {
+            reader,
+            options,
+            scan: checkpoint.scan.clone(),
+            offset: checkpoint.offset,
+            checkpoint,
+            pending: None,
+        }
+    }
+
+    /// The state to resume from after the sections decoded so far.
+    #[must_use]
+    pub fn checkpoint(&self) -> DecodeCheckpoint {
+        self.checkpoint.clone()
+    }
+
+    /// Whether the whole document has been decoded.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.checkpoint.position == DecodePosition::Finished
+    }
+
+    /// Decode sections until at least `lines` more lines have been read or
+    /// the document ends. Returns whether there is more to decode.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if decoding fails or `sink` returns one. The
+    /// decoder cannot continue after an error; resume from the last
+    /// checkpoint instead.
+    pub fn decode_lines(&mut self, lines: usize, sink: &mut impl EventSink) -> Result<bool> {
+        let target = self.checkpoint.scan.line_number.saturating_add(lines);
+        while self.checkpoint.scan.line_number < target {
+            if !self.step(sink)? {
+                return Ok(false);
+            }
+        }
+        Ok(!self.is_finished())
+    }
+
+    /// Decode the next section. Returns whether there is more to decode.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if decoding fails or `sink` returns one. The
+    /// decoder cannot continue after an error; resume from the last
+    /// checkpoint instead.
+    pub fn step(&mut self, sink: &mut impl EventSink) -> Result<bool> {
+        match self.checkpoint.position.clone() {
+            DecodePosition::Start => self.start(sink)?,
+            DecodePosition::Fields => self.field(sink)?,
+            DecodePosition::Items {
+                header_rest,
+                in_object,
+                tabular,
+                expected,
+                decoded,
+            } => self.item(sink, header_rest, in_object, tabular, expected, decoded)?,
+            DecodePosition::Finished => {}
+        }
+        self.save();
+        Ok(!self.is_finished())
+    }
+
+    /// Decode the first line and decide how to go on.
+    fn start(&mut self, sink: &mut impl EventSink) -> Result<()> {
+        let Some(first) = self.next_line()? else {
+            sink.event_with_metadata(JsonStreamEvent::StartObject, EventMetadata::default())?;
+            sink.event(JsonStreamEvent::EndObject)?;
+            self.finish();
+            return Ok(());
+        };
+        if let Some(header) = root_array_header(&first, self.checkpoint.delimiter)? {
+            if header.inline_values.is_none() {
+                let mut section = vec![first];
+                if let Some(stream) = self.array_items(&mut section, &header)? {
+                    return self.start_items(sink, &section[0], &header, stream, false);
+                }
+                return self.decode_rest(sink, section);
+            }
+            return self.decode_rest(sink, vec![first]);
+        }
+        if !is_key_value_line_sync(&first.parsed) {
+            return self.decode_rest(sink, vec![first]);
+        }
+        sink.event_with_metadata(
+            JsonStreamEvent::StartObject,
+            EventMetadata {
+                declared_length: None,
+                source_line: Some(first.parsed.line_number),
+            },
+        )?;
+        self.pending = Some(first);
+        self.checkpoint.position = DecodePosition::Fields;
+        self.field(sink)
+    }
+
+    /// Decode the next field of the root object, or start streaming its
+    /// array value.
+    fn field(&mut self, sink: &mut impl EventSink) -> Result<()> {
+        let Some(first) = self.next_line()? else {
+            sink.event(JsonStreamEvent::EndObject)?;
+            self.finish();
+            return Ok(());
+        };
+        let mut section = vec![first];
+        if let Some(header) =
+            parse_array_header_line(&section[0].parsed.content, self.checkpoint.delimiter)?
+            && let Some(key) = header.header.key.clone()
+            && header.inline_values.is_none()
+        {
+            if let Some(stream) = self.array_items(&mut section, &header)? {
+                sink.event(JsonStreamEvent::Key {
+                    key,
+                    was_quoted: header.header.key_was_quoted,
+                })?;
+                return self.start_items(sink, &section[0], &header, stream, true);
+            }
+        } else {
+            self.read_nested(&mut section, 0)?;
+        }
+        self.decode_section(sink, &section, None)
+    }
+
+    /// Read the line after an array header. Returns whether the array's
+    /// items can be decoded one at a time, and otherwise reads the rest of
+    /// the array into `section`.
+    fn array_items(
+        &mut self,
+        section: &mut Vec<Line>,
+        header: &ArrayHeaderParseResult,
+    ) -> Result<Option<bool>> {
+        let depth = section[0].parsed.depth;
+        let Some(next) = self.next_line()? else {
+            return Ok(None);
+        };
+        let tabular = header
+            .header
+            .fields
+            .as_ref()
+            .is_some_and(|fields| !fields.is_empty());
+        let list_item = next.parsed.content.starts_with(LIST_ITEM_PREFIX)
+            || next.parsed.content == LIST_ITEM_MARKER;
+        if next.parsed.depth == depth + 1 && (tabular || list_item) {
+            self.pending = Some(next);
+            return Ok(Some(tabular));
+        }
+        if next.parsed.depth > depth {
+            section.push(next);
+            self.read_nested(section, depth)?;
+        } else {
+            self.pending = Some(next);
+        }
+        Ok(None)
+    }
+
+    /// Open a streamed array whose header is `header_line`.
+    fn start_items(
+        &mut self,
+        sink: &mut impl EventSink,
+        header_line: &Line,
+        header: &ArrayHeaderParseResult,
+        tabular: bool,
+        in_object: bool,
+    ) -> Result<()> {
+        let line_number = header_line.parsed.line_number;
+        if self.options.typed_headers.unwrap_or(false)
+            && let Some(fields) = header.header.fields.clone().as_mut()
+        {
+            parse_field_type_annotations(fields, line_number)?;
+        }
+        let length = header.header.length;
+        sink.event_with_metadata(
+            JsonStreamEvent::StartArray { length },
+            EventMetadata {
+                declared_length: Some(length),
+                source_line: Some(line_number),
+            },
+        )?;
+        self.checkpoint.position = DecodePosition::Items {
+            header_rest: header_after_length(&header_line.parsed.content).to_string(),
+            in_object,
+            tabular,
+            expected: length,
+            decoded: 0,
+        };
+        Ok(())
+    }
+
+    /// Decode the next item of a streamed array, or close it.
+    fn item(
+        &mut self,
+        sink: &mut impl EventSink,
+        header_rest: String,
+        in_object: bool,
+        tabular: bool,
+        expected: usize,
+        decoded: usize,
+    ) -> Result<()> {
+        let strict = self.options.strict.unwrap_or(true);
+        let what = if tabular {
+            "tabular rows"
+        } else {
+            "list array items"
+        };
+        let first = match self.next_line()? {
+            Some(line) if line.parsed.depth > 0 => line,
+            next => {
+                self.pending = next;
+                return self.end_items(sink, what, expected, decoded, in_object);
+            }
+        };
+        if strict && decoded == expected {
+            return Err(ToonError::message(format!(
+                "Expected {expected} {what}, but found more"
+            )));
+        }
+        if strict
+            && decoded > 0
+            && let Some(blank) = first.blank_lines.first()
+        {
+            let context = if tabular {
+                "tabular array"
+            } else {
+                "list array"
+            };
+            return Err(ToonError::message(format!(
+                "Line {}: Blank lines inside {context} are not allowed in strict mode",
+                blank.line_number
+            )));
+        }
+        let mut section = vec![first];
+        self.read_nested(&mut section, 1)?;
+        self.decode_section(sink, &section, Some(format!("[1{header_rest}")))?;
+        self.checkpoint.position = DecodePosition::Items {
+            header_rest,
+            in_object,
+            tabular,
+            expected,
+            decoded: decoded + 1,
+        };
+        Ok(())
+    }
+
+    /// Close a streamed array, and decode the next field after it.
+    fn end_items(
+        &mut self,
+        sink: &mut impl EventSink,
+        what: &str,
+        expected: usize,
+        decoded: usize,
+        in_object: bool,
+    ) -> Result<()> {
+        assert_expected_count(decoded, expected, what, self.options.strict.unwrap_or(true))?;
+        sink.event(JsonStreamEvent::EndArray)?;
+        if !in_object {
+            self.finish();
+            return Ok(());
+        }
+        self.checkpoint.position = DecodePosition::Fields;
+        self.field(sink)
+    }
+
+    /// Decode everything from `section` on as one section.
+    fn decode_rest(&mut self, sink: &mut impl EventSink, mut section: Vec<Line>) -> Result<()> {
+        while let Some(line) = self.next_line()? {
+            section.push(line);
+        }
+        let lines_before = section[0].parsed.line_number - 1;
+        let lines = with_blank_lines(&section);
+        decode_section_into_sink(lines, &self.options, lines_before, sink)?;
+        self.finish();
+        Ok(())
+    }
+
+    /// Decode `section` as a field of the root object, or as an item of a
+    /// streamed array under a one-item copy of its header.
+    fn decode_section(
+        &self,
+        sink: &mut impl EventSink,
+        section: &[Line],
+        header: Option<String>,
+    ) -> Result<()> {
+        let first = section[0].parsed.line_number;
+        let mut lines = with_blank_lines(section);
+        let mut unwrapped = Unwrapped {
+            inner: sink,
+            depth: 0,
+        };
+        match header {
+            Some(header) => {
+                lines.insert(0, header);
+                decode_section_into_sink(lines, &self.options, first - 2, &mut unwrapped)
+            }
+            None => decode_section_into_sink(lines, &self.options, first - 1, &mut unwrapped),
+        }
+    }
+
+    /// Add the lines nested deeper than `depth` to `section`.
+    fn read_nested(&mut self, section: &mut Vec<Line>, depth: usize) -> Result<()> {
+        while let Some(line) = self.next_line()? {
+            if line.parsed.depth <= depth {
+                self.pending = Some(line);
+                break;
+            }
+            section.push(line);
+        }
+        Ok(())
+    }
+
+    /// The next non-blank line, from `pending` or the reader.
+    fn next_line(&mut self) -> Result<Option<Line>> {
+        if let Some(line) = self.pending.take() {
+            return Ok(Some(line));
+        }
+        let mut raw = String::new();
+        loop {
+            raw.clear();
+            let read = self
+                .reader
+                .read_line(&mut raw)
+                .map_err(|e| ToonError::io("Failed to read TOON input", None, e))?;
+            if read == 0 {
+                return Ok(None);
+            }
+            let start = self.offset;
+            self.offset += read as u64;
+            raw.truncate(raw.trim_end_matches(['\n', '\r']).len());
+            if self.scan.line_number == 0
+                && let Some(directive) = Directive::parse(&raw)?
+            {
+                let indent = directive.indent.or(self.options.indent).unwrap_or(2);
+                self.options.indent = Some(indent);
+                self.checkpoint.indent = indent;
+                let delimiter = directive
+                    .delimiter
+                    .or(self.options.delimiter)
+                    .unwrap_or(DEFAULT_DELIMITER);
+                self.options.delimiter = Some(delimiter);
+                self.checkpoint.delimiter = delimiter;
+                self.scan.line_number = 1;
+                continue;
+            }
+            if let Some(width) = self.options.tab_width {
+                raw = expand_indent_tabs(raw, width);
+            }
+            let indent = self.options.indent.unwrap_or(2);
+            let strict = self.options.strict.unwrap_or(true);
+            if let Some(parsed) = parse_line_incremental(&raw, &mut self.scan, indent, strict)? {
+                let blank_lines = std::mem::take(&mut self.scan.blank_lines);
+                return Ok(Some(Line {
+                    raw,
+                    parsed,
+                    start,
+                    blank_lines,
+                }));
+            }
+        }
+    }
+
+    /// Record the state before `pending`, or after the last line read
+    /// without it, in the checkpoint.
+    fn save(&mut self) {
+        if let Some(line) = &self.pending {
+            self.checkpoint.offset = line.start;
+            self.checkpoint.scan = StreamingScanState {
+                line_number: line.parsed.line_number - 1,
+                blank_lines: line.blank_lines.clone(),
+            };
+        } else {
+            self.checkpoint.offset = self.offset;
+            self.checkpoint.scan = self.scan.clone();
+        }
+    }
+
+    fn finish(&mut self) {
+        self.checkpoint.position = DecodePosition::Finished;
+    }
+}
+
+impl<R: BufRead + Seek> ResumableDecoder<R> {
+    /// Continue decoding the document `reader` holds from `checkpoint`,
+    /// with the options decoding started with.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `reader` cannot seek to the checkpoint, or if
+    /// `options` ask for anchors, inferred lengths, or pedantic checks.
+    pub fn resume(
+        mut reader: R,
+        checkpoint: DecodeCheckpoint,
+        options: Option<DecodeStreamOptions>,
+    ) -> Result<Self> {
+        let options = supported(options.unwrap_or_default())?;
+        reader
+            .seek(SeekFrom::Start(checkpoint.offset))
+            .map_err(|e| ToonError::io("Failed to seek TOON input", None, e))?;
+        Ok(Self::at(reader, checkpoint, options))
+    }
+}
+
+fn supported(options: DecodeStreamOptions) -> Result<DecodeStreamOptions> {
+    if let Some(name) = whole_document_option(&options) {
+        return Err(ToonError::message(format!(
+            "Resumable decoding does not support {name}"
+        )));
+    }
+    Ok(options)
+}
+
+/// The first option set in `options` that needs the whole document at once.
+pub(crate) fn whole_document_option(options: &DecodeStreamOptions) -> Option<&'static str> {
+    [
+        (options.anchors, "anchors"),
+        (options.infer_lengths, "infer_lengths"),
+        (options.pedantic, "pedantic"),
+    ]
+    .into_iter()
+    .find_map(|(set, name)| set.unwrap_or(false).then_some(name))
+}
+
+/// The raw lines of `section`, with the blank lines between them restored.
+fn with_blank_lines(section: &[Line]) -> Vec<String> {
+    let mut lines = Vec::with_capacity(section.len());
+    let mut next_number = section[0].parsed.line_number;
+    for line in section {
+        while next_number < line.parsed.line_number {
+            lines.push(String::new());
+            next_number += 1;
+        }
+        lines.push(line.raw.clone());
+        next_number += 1;
+    }
+    lines
+}
+
+/// The header of an array at the root, if `line` is one.
+fn root_array_header(line: &Line, delimiter: char) -> Result<Option<ArrayHeaderParseResult>> {
+    if !is_array_header_content(&line.parsed.content) {
+        return Ok(None);
+    }
+    Ok(parse_array_header_line(&line.parsed.content, delimiter)?
+        .filter(|header| header.header.key.is_none()))
+}
+
+/// The part of an array header line after the length, e.g. `|]{a|b}:` of
+/// `rows[2|]{a|b}:`.
+fn header_after_length(content: &str) -> &str {
+    let key_end = if content.starts_with('"') {
+        find_closing_quote(content, 0).map_or(0, |closing| closing + 1)
+    } else {
+        0
+    };
+    let open = content[key_end..]
+        .find('[')
+        .map_or(key_end, |open| key_end + open + 1);
+    content[open..].trim_start_matches(|c: char| c.is_ascii_digit())
+}
+
+/// Passes events on without the outermost container, which a section is
+/// wrapped in to decode it on its own.
+struct Unwrapped<'a, S> {
+    inner: &'a mut S,
+    depth: usize,
+}
+
+impl<S: EventSink> Unwrapped<'_, S> {
+    /// Whether `event` is the outermost container's start or end.
+    const fn is_outermost(&mut self, event: &JsonStreamEvent) -> bool {
+        match event {
+            JsonStreamEvent::StartObject | JsonStreamEvent::StartArray { .. } => {
+                self.depth += 1;
+                self.depth == 1
+            }
+            JsonStreamEvent::EndObject | JsonStreamEvent::EndArray => {
+                self.depth -= 1;
+                self.depth == 0
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<S: EventSink> EventSink for Unwrapped<'_, S> {
+    fn event(&mut self, event: JsonStreamEvent) -> Result<()> {
+        if self.is_outermost(&event) {
+            return Ok(());
+        }
+        self.inner.event(event)
+    }
+
+    fn event_with_metadata(
+        &mut self,
+        event: JsonStreamEvent,
+        metadata: EventMetadata,
+    ) -> Result<()> {
+        if self.is_outermost(&event) {
+            return Ok(());
+        }
+        self.inner.event_with_metadata(event, metadata)
+    }
+}
@@ -1,3 +1,7 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::decode::trace::DecodeTrace;
 use crate::error::{Result, ToonError};
 use crate::shared::constants::{SPACE, TAB};
 
@@ -13,13 +17,25 @@ pub struct ParsedLine {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
 pub struct BlankLineInfo {
     pub line_number: usize,
     pub indent: usize,
     pub depth: Depth,
 }
 
-#[derive(Debug, Clone)]
+/// Scanner state carried from line to line: how many lines have been read,
+/// and the blank lines among them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
 pub struct StreamingScanState {
     pub line_number: usize,
     pub blank_lines: Vec<BlankLineInfo>,
@@ -91,7 +107,7 @@ pub fn parse_line_incremental(
                     ),
                 ));
             }
-        } else if indent > 0 && indent % indent_size != 0 {
+        } else if indent > 0 && !indent.is_multiple_of(indent_size) {
             return Err(ToonError::invalid_indentation(
                 line_number,
                 indent_size,
@@ -143,6 +159,7 @@ pub struct StreamingLineCursor {
     index: usize,
     last_line: Option<ParsedLine>,
     blank_lines: Vec<BlankLineInfo>,
+    trace: Option<Rc<RefCell<DecodeTrace>>>,
 }
 
 impl StreamingLineCursor {
@@ -153,9 +170,17 @@ impl StreamingLineCursor {
             index: 0,
             last_line: None,
             blank_lines,
+            trace: None,
         }
     }
 
+    /// Record each line in `trace` as it is consumed.
+    #[must_use]
+    pub fn with_trace(mut self, trace: Rc<RefCell<DecodeTrace>>) -> Self {
+        self.trace = Some(trace);
+        self
+    }
+
     #[must_use]
     pub fn get_blank_lines(&self) -> &[BlankLineInfo] {
         &self.blank_lines
@@ -170,6 +195,7 @@ impl StreamingLineCursor {
         if self.index < self.lines.len() {
             // Store index instead of cloning
             self.last_line = Some(self.lines[self.index].clone());
+            self.trace_line();
             self.index += 1;
         }
     }
@@ -178,6 +204,7 @@ impl StreamingLineCursor {
         if self.index < self.lines.len() {
             let line = self.lines[self.index].clone();
             self.last_line = Some(line.clone());
+            self.trace_line();
             self.index += 1;
             Some(line)
         } else {
@@ -185,13 +212,19 @@ impl StreamingLineCursor {
         }
     }
 
+    fn trace_line(&self) {
+        if let Some(trace) = &self.trace {
+            trace.borrow_mut().enter_line(&self.lines[self.index]);
+        }
+    }
+
     #[must_use]
     pub const fn current(&self) -> Option<&ParsedLine> {
         self.last_line.as_ref()
     }
 
     #[must_use]
-    pub fn at_end_sync(&self) -> bool {
+    pub const fn at_end_sync(&self) -> bool {
         self.index >= self.lines.len()
     }
 }
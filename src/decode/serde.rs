@@ -0,0 +1,467 @@
+//! Decoding TOON straight into any `T: Deserialize`.
+//!
+//! [`from_toon_str`] mirrors [`crate::decode`], taking the same
+//! [`DecodeOptions`], but produces a Rust value instead of a [`JsonValue`].
+//! [`Deserializer`] reads the decoder's event stream directly, without a
+//! [`JsonValue`] or `serde_json::Value` in between, and follows
+//! `serde_json`'s data model: objects fill structs and maps, arrays fill
+//! sequences and tuples, null is `None` and `()`, and enum variants are
+//! either a string naming a unit variant or a single-key object named after
+//! the variant. Integers, and integral floats within ±2^53, fill any
+//! integer type that holds them, and map keys are parsed when the map has integer keys.
+//!
+//! Path expansion and columnar reassembly rewrite the whole value, so with
+//! either option the value is built first and deserialized with
+//! [`from_value`].
+//!
+//! ```
+//! use serde::Deserialize;
+//! use toon::from_toon_str;
+//!
+//! #[derive(Debug, PartialEq, Deserialize)]
+//! struct User {
+//!     id: u32,
+//!     name: String,
+//! }
+//!
+//! let users: Vec<User> = from_toon_str("[2]{id,name}:\n  1,Ada\n  2,Bob", None)?;
+//! assert_eq!(users[1], User { id: 2, name: "Bob".to_string() });
+//! # Ok::<(), toon::error::ToonError>(())
+//! ```
+
+use std::fmt::Display;
+use std::iter::Peekable;
+use std::vec;
+
+use ::serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Unexpected, Visitor};
+use ::serde::forward_to_deserialize_any;
+
+use crate::codec::Decoder;
+use crate::encode::emit_events;
+use crate::error::{Result, ToonError};
+use crate::options::{DecodeOptions, ExpandPathsMode};
+use crate::shared::constants::MAX_EXACT_INTEGER;
+use crate::{JsonStreamEvent, JsonValue, StringOrNumberOrBoolOrNull};
+
+/// Decode a TOON string and deserialize it into a `T`.
+///
+/// # Errors
+///
+/// Returns an error if decoding fails due to malformed input or strict-mode
+/// validation errors, or [`ToonError::Deserialize`] with the path of the
+/// offending value if the decoded value does not fit `T`.
+pub fn from_toon_str<T: DeserializeOwned>(
+    input: &str,
+    options: Option<DecodeOptions>,
+) -> Result<T> {
+    let decoder = Decoder::new(options);
+    let resolved = decoder.options();
+    if resolved.expand_paths == ExpandPathsMode::Safe || resolved.columnar {
+        return from_value(&decoder.try_decode(input)?);
+    }
+    let mut events = Vec::new();
+    decoder.decode_into_sink(input.split('\n').map(str::to_string), &mut events)?;
+    from_events(events)
+}
+
+/// Deserialize a [`JsonValue`] into a `T`.
+///
+/// # Errors
+///
+/// Returns [`ToonError::Deserialize`] with the path of the offending value
+/// if `value` does not fit `T`.
+pub fn from_value<T: DeserializeOwned>(value: &JsonValue) -> Result<T> {
+    let mut events = Vec::new();
+    emit_events(value, &mut events);
+    from_events(events)
+}
+
+/// Deserialize the events of one value, as produced by
+/// [`decode_stream_sync`](crate::decode_stream_sync), into a `T`.
+///
+/// # Errors
+///
+/// Returns [`ToonError::Deserialize`] with the path of the offending value
+/// if the events do not fit `T` or do not form exactly one value.
+pub fn from_events<T: DeserializeOwned>(events: Vec<JsonStreamEvent>) -> Result<T> {
+    let mut deserializer = Deserializer::new(events);
+    let value = T::deserialize(&mut deserializer)?;
+    deserializer
+        .events
+        .next()
+        .map_or(Ok(value), |event| Err(unexpected(&event)))
+}
+
+impl de::Error for ToonError {
+    fn custom<T: Display>(message: T) -> Self {
+        Self::deserialize("", message.to_string())
+    }
+}
+
+/// A `serde::Deserializer` reading a stream of [`JsonStreamEvent`]s.
+#[derive(Debug)]
+pub struct Deserializer {
+    events: Peekable<vec::IntoIter<JsonStreamEvent>>,
+}
+
+impl Deserializer {
+    /// A deserializer over the events of one value.
+    #[must_use]
+    pub fn new(events: Vec<JsonStreamEvent>) -> Self {
+        Self {
+            events: events.into_iter().peekable(),
+        }
+    }
+
+    fn next_event(&mut self) -> Result<JsonStreamEvent> {
+        self.events
+            .next()
+            .ok_or_else(|| ToonError::deserialize("", "unexpected end of events"))
+    }
+
+    fn peek_is(&mut self, event: &JsonStreamEvent) -> bool {
+        self.events.peek() == Some(event)
+    }
+
+    /// Consume the event closing a container of which the visitor read
+    /// `read` entries, failing with the full length if it left some unread.
+    fn end(&mut self, close: &JsonStreamEvent, read: usize, expected: &str) -> Result<()> {
+        let mut length = read;
+        while !self.peek_is(close) {
+            if *close == JsonStreamEvent::EndObject {
+                self.skip_value()?;
+            }
+            self.skip_value()?;
+            length += 1;
+        }
+        self.next_event()?;
+        if length > read {
+            return Err(de::Error::invalid_length(length, &expected));
+        }
+        Ok(())
+    }
+
+    /// Skip the next key or value, with everything nested in it.
+    fn skip_value(&mut self) -> Result<()> {
+        let mut depth = 0_usize;
+        loop {
+            match self.next_event()? {
+                JsonStreamEvent::StartObject | JsonStreamEvent::StartArray { .. } => depth += 1,
+                JsonStreamEvent::EndObject | JsonStreamEvent::EndArray => {
+                    depth = depth.saturating_sub(1);
+                }
+                _ => {}
+            }
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn unexpected(event: &JsonStreamEvent) -> ToonError {
+    ToonError::deserialize("", format!("unexpected event {event:?}"))
+}
+
+fn visit_primitive<'de, V: Visitor<'de>>(
+    value: StringOrNumberOrBoolOrNull,
+    visitor: V,
+) -> Result<V::Value> {
+    match value {
+        StringOrNumberOrBoolOrNull::String(text) => visitor.visit_string(text),
+        StringOrNumberOrBoolOrNull::Number(number) => visit_number(number, visitor),
+        StringOrNumberOrBoolOrNull::Int(value) => visitor.visit_i64(value),
+        StringOrNumberOrBoolOrNull::UInt(value) => visitor.visit_u64(value),
+        StringOrNumberOrBoolOrNull::NumberLexeme(text) => visit_primitive(
+            StringOrNumberOrBoolOrNull::from_number_lexeme(&text),
+            visitor,
+        ),
+        StringOrNumberOrBoolOrNull::Bool(value) => visitor.visit_bool(value),
+        StringOrNumberOrBoolOrNull::Null => visitor.visit_unit(),
+    }
+}
+
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::float_cmp
+)]
+fn visit_number<'de, V: Visitor<'de>>(value: f64, visitor: V) -> Result<V::Value> {
+    if value.fract() == 0.0 && value.abs() <= MAX_EXACT_INTEGER {
+        if value < 0.0 {
+            visitor.visit_i64(value as i64)
+        } else {
+            visitor.visit_u64(value as u64)
+        }
+    } else {
+        visitor.visit_f64(value)
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer {
+    type Error = ToonError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.next_event()? {
+            JsonStreamEvent::Primitive { value } => visit_primitive(value, visitor),
+            JsonStreamEvent::StartArray { length } => {
+                let mut items = ArrayAccess {
+                    de: &mut *self,
+                    index: 0,
+                    length,
+                };
+                let value = visitor.visit_seq(&mut items)?;
+                let read = items.index;
+                self.end(&JsonStreamEvent::EndArray, read, "fewer elements in array")?;
+                Ok(value)
+            }
+            JsonStreamEvent::StartObject => {
+                let mut entries = ObjectAccess {
+                    de: &mut *self,
+                    key: None,
+                    read: 0,
+                };
+                let value = visitor.visit_map(&mut entries)?;
+                let read = entries.read;
+                self.end(&JsonStreamEvent::EndObject, read, "fewer elements in map")?;
+                Ok(value)
+            }
+            #[cfg(feature = "raw")]
+            JsonStreamEvent::Raw { text } => visitor.visit_string(text),
+            event => Err(unexpected(&event)),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let null = JsonStreamEvent::Primitive {
+            value: StringOrNumberOrBoolOrNull::Null,
+        };
+        if self.peek_is(&null) {
+            self.next_event()?;
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self.next_event()? {
+            JsonStreamEvent::Primitive {
+                value: StringOrNumberOrBoolOrNull::String(variant),
+            } => visitor.visit_enum(variant.into_deserializer()),
+            JsonStreamEvent::StartObject => {
+                let variant = match self.next_event()? {
+                    JsonStreamEvent::Key { key, .. } => key,
+                    event => return Err(unexpected(&event)),
+                };
+                let value = visitor
+                    .visit_enum(VariantAccess {
+                        de: &mut *self,
+                        variant: &variant,
+                    })
+                    .map_err(|err| err.with_key_prefix(&variant))?;
+                if !self.peek_is(&JsonStreamEvent::EndObject) {
+                    return Err(ToonError::deserialize(
+                        "",
+                        "expected an object with a single key naming the enum variant",
+                    ));
+                }
+                self.next_event().map(|_| value)
+            }
+            JsonStreamEvent::Primitive { value } => Err(de::Error::invalid_type(
+                unexpected_primitive(&value),
+                &"an enum variant",
+            )),
+            event => Err(unexpected(&event)),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+fn unexpected_primitive(value: &StringOrNumberOrBoolOrNull) -> Unexpected<'_> {
+    match value {
+        StringOrNumberOrBoolOrNull::String(text) => Unexpected::Str(text),
+        StringOrNumberOrBoolOrNull::Number(number) => Unexpected::Float(*number),
+        StringOrNumberOrBoolOrNull::Int(value) => Unexpected::Signed(*value),
+        StringOrNumberOrBoolOrNull::UInt(value) => Unexpected::Unsigned(*value),
+        StringOrNumberOrBoolOrNull::NumberLexeme(text) => Unexpected::Other(text),
+        StringOrNumberOrBoolOrNull::Bool(value) => Unexpected::Bool(*value),
+        StringOrNumberOrBoolOrNull::Null => Unexpected::Unit,
+    }
+}
+
+struct ArrayAccess<'a> {
+    de: &'a mut Deserializer,
+    index: usize,
+    length: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for ArrayAccess<'_> {
+    type Error = ToonError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if self.de.peek_is(&JsonStreamEvent::EndArray) {
+            return Ok(None);
+        }
+        let index = self.index;
+        self.index += 1;
+        seed.deserialize(&mut *self.de)
+            .map(Some)
+            .map_err(|err| err.with_index_prefix(index))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.length.saturating_sub(self.index))
+    }
+}
+
+struct ObjectAccess<'a> {
+    de: &'a mut Deserializer,
+    key: Option<String>,
+    read: usize,
+}
+
+impl<'de> de::MapAccess<'de> for ObjectAccess<'_> {
+    type Error = ToonError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if self.de.peek_is(&JsonStreamEvent::EndObject) {
+            return Ok(None);
+        }
+        let key = match self.de.next_event()? {
+            JsonStreamEvent::Key { key, .. } => key,
+            event => return Err(unexpected(&event)),
+        };
+        let value = seed
+            .deserialize(KeyDeserializer(&key))
+            .map_err(|err| err.with_key_prefix(&key))?;
+        self.key = Some(key);
+        self.read += 1;
+        Ok(Some(value))
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let key = self.key.take().unwrap_or_default();
+        seed.deserialize(&mut *self.de)
+            .map_err(|err| err.with_key_prefix(&key))
+    }
+}
+
+/// The value of a variant written as `{variant: value}`.
+struct VariantAccess<'a> {
+    de: &'a mut Deserializer,
+    variant: &'a str,
+}
+
+impl<'de> de::EnumAccess<'de> for VariantAccess<'_> {
+    type Error = ToonError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        let variant = seed.deserialize(KeyDeserializer(self.variant))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for VariantAccess<'_> {
+    type Error = ToonError;
+
+    fn unit_variant(self) -> Result<()> {
+        de::Deserialize::deserialize(self.de)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self.de)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(self.de, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_map(self.de, visitor)
+    }
+}
+
+/// Deserializes an object key, parsing it for maps with integer keys.
+struct KeyDeserializer<'a>(&'a str);
+
+macro_rules! deserialize_integer_key {
+    ($($method:ident => $visit:ident),* $(,)?) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+            match self.0.parse() {
+                Ok(value) => visitor.$visit(value),
+                Err(_) => Err(de::Error::invalid_value(Unexpected::Str(self.0), &visitor)),
+            }
+        }
+    )*};
+}
+
+impl<'de> de::Deserializer<'de> for KeyDeserializer<'_> {
+    type Error = ToonError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_str(self.0)
+    }
+
+    deserialize_integer_key! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_enum(self.0.into_deserializer())
+    }
+
+    forward_to_deserialize_any! {
+        bool f32 f64 char str string bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
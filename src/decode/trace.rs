@@ -0,0 +1,135 @@
+//! Line-by-line record of a decode (`toon debug-decode`).
+//!
+//! [`trace_decode`] decodes a document and notes, for each input line the
+//! decoder reads, its depth, the events emitted while it was the current
+//! line, and the strict-mode checks run there. Events are shown in the JSON
+//! Lines form of [`events_to_jsonl`](crate::json::events_to_jsonl), so a
+//! trace can be cut down to its events and replayed.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::JsonStreamEvent;
+use crate::decode::decoders::decode_into_sink_with;
+use crate::decode::scanner::{Depth, ParsedLine};
+use crate::error::{Result, ToonError};
+use crate::json::events_to_jsonl;
+use crate::options::{DecodeOptions, resolve_decode_options};
+use crate::sink::EventSink;
+
+/// One input line and what the decoder did while it was the current line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceLine {
+    /// 1-based line number in the input.
+    pub line_number: usize,
+    /// Indentation depth of the line.
+    pub depth: Depth,
+    /// The line without its indentation.
+    pub content: String,
+    /// Events emitted from reading this line until the next one was read,
+    /// including the ends of the containers it closed.
+    pub events: Vec<JsonStreamEvent>,
+    /// Strict-mode checks that passed (or, for the last line of a failed
+    /// decode, were attempted) while this line was current.
+    pub checks: Vec<String>,
+}
+
+/// The record of one decode. Displays as a readable report.
+#[derive(Debug, Default)]
+pub struct DecodeTrace {
+    /// Lines in the order the decoder read them. Blank lines and a leading
+    /// `#%toon` directive are not read as lines and do not appear.
+    pub lines: Vec<TraceLine>,
+    /// Events emitted before any line was read (an empty document).
+    pub leading_events: Vec<JsonStreamEvent>,
+    /// Why decoding stopped, if it failed.
+    pub error: Option<ToonError>,
+}
+
+impl DecodeTrace {
+    pub(crate) fn enter_line(&mut self, line: &ParsedLine) {
+        self.lines.push(TraceLine {
+            line_number: line.line_number,
+            depth: line.depth,
+            content: line.content.clone(),
+            events: Vec::new(),
+            checks: Vec::new(),
+        });
+    }
+
+    pub(crate) fn record_check(&mut self, check: String) {
+        if let Some(line) = self.lines.last_mut() {
+            line.checks.push(check);
+        }
+    }
+
+    fn record_event(&mut self, event: JsonStreamEvent) {
+        match self.lines.last_mut() {
+            Some(line) => line.events.push(event),
+            None => self.leading_events.push(event),
+        }
+    }
+}
+
+impl fmt::Display for DecodeTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let write_events = |f: &mut fmt::Formatter<'_>, events: &[JsonStreamEvent]| {
+            for record in events_to_jsonl(events.iter().cloned()).lines() {
+                writeln!(f, "  event {record}")?;
+            }
+            Ok(())
+        };
+        write_events(f, &self.leading_events)?;
+        let mut previous_depth = None;
+        for line in &self.lines {
+            write!(f, "line {} depth {}", line.line_number, line.depth)?;
+            match previous_depth {
+                Some(previous) if previous != line.depth => {
+                    write!(f, " (from {previous})")?;
+                }
+                _ => {}
+            }
+            writeln!(f, ": {}", line.content)?;
+            write_events(f, &line.events)?;
+            for check in &line.checks {
+                writeln!(f, "  check {check}")?;
+            }
+            previous_depth = Some(line.depth);
+        }
+        if let Some(error) = &self.error {
+            writeln!(f, "error: {error}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Decode `input` and record what the decoder did on each line.
+///
+/// The trace covers the event stream, before paths are expanded and
+/// columnar arrays are reassembled. A failed decode is recorded in
+/// [`DecodeTrace::error`], with the lines read up to the failure.
+#[must_use]
+pub fn trace_decode(input: &str, options: Option<DecodeOptions>) -> DecodeTrace {
+    let resolved = resolve_decode_options(options);
+    let trace = Rc::new(RefCell::new(DecodeTrace::default()));
+    let mut sink = TraceSink(Rc::clone(&trace));
+    let result = decode_into_sink_with(
+        input.split('\n').map(str::to_string),
+        &resolved.stream_options(),
+        &mut sink,
+        Some(&trace),
+    );
+    let mut trace = trace.take();
+    trace.error = result.err();
+    trace
+}
+
+struct TraceSink(Rc<RefCell<DecodeTrace>>);
+
+impl EventSink for TraceSink {
+    fn event(&mut self, event: JsonStreamEvent) -> Result<()> {
+        self.0.borrow_mut().record_event(event);
+        Ok(())
+    }
+}
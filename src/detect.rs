@@ -0,0 +1,81 @@
+//! Telling JSON from TOON by looking at the text.
+//!
+//! [`detect_format`] checks only the first characters and the first line, so
+//! it costs the same for a large document as for a small one. It does not
+//! validate: a guess of [`FormatGuess::Json`] means the text can only be JSON
+//! if it is valid at all.
+//!
+//! ```
+//! use toon::detect::{FormatGuess, detect_format};
+//!
+//! assert_eq!(detect_format(r#"{"id": 1}"#), FormatGuess::Json);
+//! assert_eq!(detect_format("[2]: a,b"), FormatGuess::Toon);
+//! assert_eq!(detect_format("id: 1\nname: Ada"), FormatGuess::Toon);
+//! assert_eq!(detect_format("42"), FormatGuess::Ambiguous);
+//! ```
+
+/// The format a text appears to be in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatGuess {
+    Json,
+    Toon,
+    /// Empty, a scalar (`42`, `true`, `"text"`) that reads the same in
+    /// both formats, or text that fits neither.
+    Ambiguous,
+}
+
+/// Guess whether `input` is JSON or TOON.
+///
+/// Objects (`{`) and arrays other than a TOON root header (`[3]:`,
+/// `[2|]{a|b}:`) are JSON. A quoted string followed by `:` or `[` is a TOON
+/// key. Anything else that is not a JSON scalar, such as `key: value` or a
+/// `#%toon` directive, is TOON.
+#[must_use]
+pub fn detect_format(input: &str) -> FormatGuess {
+    let trimmed = input.trim();
+    match trimmed.chars().next() {
+        None => FormatGuess::Ambiguous,
+        Some('[') if is_root_header(trimmed) => FormatGuess::Toon,
+        Some('{' | '[') => FormatGuess::Json,
+        Some('"') => after_quoted(trimmed),
+        Some(_) if is_json_scalar(trimmed) => FormatGuess::Ambiguous,
+        Some(_) => FormatGuess::Toon,
+    }
+}
+
+/// `[N]` with an optional delimiter marker, followed by `:` or a field list.
+fn is_root_header(text: &str) -> bool {
+    let rest = text[1..].trim_start_matches(|c: char| c.is_ascii_digit());
+    if rest.len() == text.len() - 1 {
+        return false;
+    }
+    let rest = rest.strip_prefix([',', '\t', '|']).unwrap_or(rest);
+    rest.starts_with("]:") || rest.starts_with("]{")
+}
+
+/// Classify text starting with a quoted string by what follows the string.
+fn after_quoted(text: &str) -> FormatGuess {
+    let mut escaped = false;
+    for (index, c) in text.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => {
+                let rest = text[index + 1..].trim_start_matches([' ', '\t']);
+                return if rest.starts_with([':', '[']) {
+                    FormatGuess::Toon
+                } else {
+                    FormatGuess::Ambiguous
+                };
+            }
+            _ => {}
+        }
+    }
+    FormatGuess::Ambiguous
+}
+
+fn is_json_scalar(text: &str) -> bool {
+    matches!(text, "true" | "false" | "null")
+        || (text.starts_with(|c: char| c == '-' || c.is_ascii_digit())
+            && serde_json::from_str::<serde_json::Number>(text).is_ok())
+}
@@ -0,0 +1,118 @@
+//! Checks that encoding is deterministic.
+//!
+//! The same value and options must always produce byte-identical TOON, or
+//! caches, manifests, and prompt diffs keyed on the output break.
+//! [`audit_determinism`] encodes a value repeatedly, on one or several
+//! threads at once, and reports the first output that differs from the
+//! first run.
+
+use std::thread;
+
+use crate::error::{Result, ToonError};
+use crate::options::EncodeOptions;
+use crate::{JsonValue, try_encode};
+
+/// Outcome of [`audit_determinism`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeterminismReport {
+    /// Encodes performed, across all threads.
+    pub runs: usize,
+    /// Size of the first run's output.
+    pub output_bytes: usize,
+    /// The first run whose output differed, if any.
+    pub mismatch: Option<Mismatch>,
+}
+
+impl DeterminismReport {
+    #[must_use]
+    pub const fn is_deterministic(&self) -> bool {
+        self.mismatch.is_none()
+    }
+}
+
+/// A run whose output differed from the first run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub thread: usize,
+    /// Run number on its thread, from 0.
+    pub run: usize,
+    /// First differing line, from 1.
+    pub line: usize,
+    /// That line in the first run's output (empty past its end).
+    pub expected: String,
+    /// That line in the differing output (empty past its end).
+    pub actual: String,
+}
+
+/// Encode `value` `runs` times on each of `threads` threads running at the
+/// same time, and compare every output with the first.
+///
+/// # Errors
+///
+/// Returns an error if `runs` or `threads` is zero, or if the value cannot be
+/// encoded.
+pub fn audit_determinism(
+    value: &JsonValue,
+    options: Option<&EncodeOptions>,
+    runs: usize,
+    threads: usize,
+) -> Result<DeterminismReport> {
+    if runs == 0 || threads == 0 {
+        return Err(ToonError::message(
+            "Determinism audit needs at least one run and one thread",
+        ));
+    }
+    let encode = || try_encode(value.clone(), options.cloned());
+    let expected = encode()?;
+
+    let outputs = thread::scope(|scope| {
+        // Spawn every worker before joining any, so that they run at once.
+        #[allow(clippy::needless_collect)]
+        let workers: Vec<_> = (0..threads)
+            .map(|_| scope.spawn(|| (0..runs).map(|_| encode()).collect::<Vec<_>>()))
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect::<Vec<_>>()
+    });
+
+    let mut mismatch = None;
+    for (thread, outputs) in outputs.into_iter().enumerate() {
+        for (run, output) in outputs.into_iter().enumerate() {
+            let output = output?;
+            if mismatch.is_none() && output != expected {
+                mismatch = Some(first_difference(&expected, &output, thread, run));
+            }
+        }
+    }
+    Ok(DeterminismReport {
+        runs: runs * threads,
+        output_bytes: expected.len(),
+        mismatch,
+    })
+}
+
+fn first_difference(expected: &str, actual: &str, thread: usize, run: usize) -> Mismatch {
+    let mut expected_lines = expected.split('\n');
+    let mut actual_lines = actual.split('\n');
+    let mut line = 1;
+    loop {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(left), Some(right)) if left == right => line += 1,
+            (left, right) => {
+                return Mismatch {
+                    thread,
+                    run,
+                    line,
+                    expected: left.unwrap_or_default().to_string(),
+                    actual: right.unwrap_or_default().to_string(),
+                };
+            }
+        }
+    }
+}
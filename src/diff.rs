@@ -0,0 +1,130 @@
+//! Structural diff of JSON values.
+//!
+//! [`diff`] compares two documents key by key and index by index and lists
+//! the values that were added, removed, or changed, each with its path. It is
+//! the counterpart of [`crate::merge::merge3`] and backs `toon git-diff`.
+
+use std::fmt;
+
+use crate::JsonValue;
+use crate::options::{PathSegment, format_path};
+
+/// One difference between two documents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added {
+        path: Vec<PathSegment>,
+        value: JsonValue,
+    },
+    Removed {
+        path: Vec<PathSegment>,
+        value: JsonValue,
+    },
+    Modified {
+        path: Vec<PathSegment>,
+        old: JsonValue,
+        new: JsonValue,
+    },
+}
+
+impl Change {
+    #[must_use]
+    pub fn path(&self) -> &[PathSegment] {
+        match self {
+            Self::Added { path, .. } | Self::Removed { path, .. } | Self::Modified { path, .. } => {
+                path
+            }
+        }
+    }
+}
+
+/// One line per change: `+ path: value`, `- path: value`, or
+/// `~ path: old -> new`, with values as compact JSON.
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = format_path(self.path());
+        let path = if path.is_empty() { "(root)" } else { &path };
+        match self {
+            Self::Added { value, .. } => write!(f, "+ {path}: {}", compact(value)),
+            Self::Removed { value, .. } => write!(f, "- {path}: {}", compact(value)),
+            Self::Modified { old, new, .. } => {
+                write!(f, "~ {path}: {} -> {}", compact(old), compact(new))
+            }
+        }
+    }
+}
+
+fn compact(value: &JsonValue) -> String {
+    serde_json::Value::from(value.clone()).to_string()
+}
+
+/// List the changes from `old` to `new`, in document order.
+///
+/// Objects are compared per key, so reordering keys is not a change. Arrays
+/// are compared per index, with items past the shorter array reported as
+/// added or removed.
+#[must_use]
+pub fn diff(old: &JsonValue, new: &JsonValue) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_into(old, new, &mut Vec::new(), &mut changes);
+    changes
+}
+
+fn diff_into(
+    old: &JsonValue,
+    new: &JsonValue,
+    path: &mut Vec<PathSegment>,
+    changes: &mut Vec<Change>,
+) {
+    match (old, new) {
+        (JsonValue::Object(old), JsonValue::Object(new)) => {
+            for (key, old_value) in old {
+                path.push(PathSegment::Key(key.clone()));
+                match new.iter().find(|(new_key, _)| new_key == key) {
+                    Some((_, new_value)) => diff_into(old_value, new_value, path, changes),
+                    None => changes.push(Change::Removed {
+                        path: path.clone(),
+                        value: old_value.clone(),
+                    }),
+                }
+                path.pop();
+            }
+            for (key, new_value) in new {
+                if !old.iter().any(|(old_key, _)| old_key == key) {
+                    path.push(PathSegment::Key(key.clone()));
+                    changes.push(Change::Added {
+                        path: path.clone(),
+                        value: new_value.clone(),
+                    });
+                    path.pop();
+                }
+            }
+        }
+        (JsonValue::Array(old), JsonValue::Array(new)) => {
+            for index in 0..old.len().max(new.len()) {
+                path.push(PathSegment::Index(index));
+                match (old.get(index), new.get(index)) {
+                    (Some(old_value), Some(new_value)) => {
+                        diff_into(old_value, new_value, path, changes);
+                    }
+                    (Some(old_value), None) => changes.push(Change::Removed {
+                        path: path.clone(),
+                        value: old_value.clone(),
+                    }),
+                    (None, Some(new_value)) => changes.push(Change::Added {
+                        path: path.clone(),
+                        value: new_value.clone(),
+                    }),
+                    (None, None) => {}
+                }
+                path.pop();
+            }
+        }
+        _ if old != new => changes.push(Change::Modified {
+            path: path.clone(),
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => {}
+    }
+}
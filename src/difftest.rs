@@ -0,0 +1,425 @@
+//! Differential testing against an external reference implementation.
+//!
+//! Generates deterministic JSON documents, pipes them through a reference
+//! encoder/decoder command (for example the TypeScript `toon` CLI), and
+//! reports every case where the reference output diverges from ours.
+//!
+//! Two directions are checked per case:
+//! - **encode**: the JSON document is encoded by both implementations and the
+//!   TOON text is compared byte-for-byte (ignoring trailing newlines).
+//! - **decode**: our TOON output is decoded by both implementations and the
+//!   resulting values are compared structurally.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::error::{Result, ToonError};
+use crate::options::{DecodeOptions, EncodeOptions};
+use crate::shared::constants::DEFAULT_DELIMITER;
+use crate::{JsonValue, StringOrNumberOrBoolOrNull};
+
+const SAMPLE_STRINGS: &[&str] = &[
+    "",
+    "hello",
+    "with space",
+    " padded ",
+    "a,b",
+    "a|b",
+    "a:b",
+    "-dash",
+    "- item",
+    "true",
+    "null",
+    "42",
+    "05",
+    "1e5",
+    "quote\"d",
+    "back\\slash",
+    "new\nline",
+    "tab\there",
+    "[x]",
+    "{y}",
+    "unicode \u{2713}",
+];
+
+const SAMPLE_KEYS: &[&str] = &[
+    "id",
+    "name",
+    "value",
+    "a.b",
+    "with space",
+    "",
+    "123",
+    "key-dash",
+    "_under",
+    "[k]",
+];
+
+const SAMPLE_NUMBERS: &[f64] = &[0.0, 1.0, -1.0, 42.0, 3.5, -0.25, 1e21, 1e-7, 123_456_789.0];
+
+/// Configuration for a differential test run.
+#[derive(Debug, Clone)]
+pub struct DiffTestOptions {
+    /// Number of generated cases.
+    pub cases: usize,
+    /// Seed for the deterministic case generator.
+    pub seed: u64,
+    /// Maximum nesting depth of generated documents.
+    pub max_depth: usize,
+    /// Indentation passed to both implementations.
+    pub indent: usize,
+    /// Delimiter passed to both implementations.
+    pub delimiter: char,
+}
+
+impl Default for DiffTestOptions {
+    fn default() -> Self {
+        Self {
+            cases: 100,
+            seed: 0,
+            max_depth: 3,
+            indent: 2,
+            delimiter: DEFAULT_DELIMITER,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffDirection {
+    Encode,
+    Decode,
+}
+
+impl DiffDirection {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Encode => "encode",
+            Self::Decode => "decode",
+        }
+    }
+}
+
+/// A single case where the reference output differs from ours.
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    pub case: usize,
+    pub direction: DiffDirection,
+    pub input: String,
+    pub reference: String,
+    pub actual: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DiffReport {
+    pub cases_run: usize,
+    pub divergences: Vec<Divergence>,
+}
+
+impl DiffReport {
+    #[must_use]
+    pub const fn is_clean(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+/// An external command implementing the reference CLI conventions
+/// (`--encode`/`--decode`, input on stdin, output on stdout).
+#[derive(Debug, Clone)]
+pub struct ReferenceCommand {
+    program: String,
+    args: Vec<String>,
+}
+
+impl ReferenceCommand {
+    /// Parse a whitespace-separated command line such as `node toon-cli`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the command is empty.
+    pub fn parse(command: &str) -> Result<Self> {
+        let mut parts = command.split_whitespace().map(str::to_string);
+        let program = parts
+            .next()
+            .ok_or_else(|| ToonError::message("Reference command is empty"))?;
+        Ok(Self {
+            program,
+            args: parts.collect(),
+        })
+    }
+
+    /// Run the command with extra arguments, feeding `input` on stdin.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the command cannot be spawned or exits unsuccessfully.
+    pub fn run(&self, extra_args: &[String], input: &str) -> Result<String> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .args(extra_args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| ToonError::io("Failed to spawn reference command", None, e))?;
+
+        // Feed stdin from its own thread so a reference that writes a lot of
+        // output before draining its input cannot deadlock on full pipes.
+        let writer = child.stdin.take().map(|mut stdin| {
+            let input = input.as_bytes().to_vec();
+            std::thread::spawn(move || stdin.write_all(&input))
+        });
+
+        let output = child
+            .wait_with_output()
+            .map_err(|e| ToonError::io("Failed to wait for reference command", None, e))?;
+
+        if let Some(writer) = writer {
+            match writer.join() {
+                Ok(Ok(())) => {}
+                // A reference that exits without reading all of stdin is judged
+                // by its exit status below, not by the broken pipe.
+                Ok(Err(e)) if e.kind() == std::io::ErrorKind::BrokenPipe => {}
+                Ok(Err(e)) => {
+                    return Err(ToonError::io(
+                        "Failed to write to reference command",
+                        None,
+                        e,
+                    ));
+                }
+                Err(_) => {
+                    return Err(ToonError::message(
+                        "Reference command stdin writer panicked",
+                    ));
+                }
+            }
+        }
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(ToonError::message(format!(
+                "Reference command exited with {}: {}",
+                output.status,
+                stderr.trim()
+            )));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// Run generated cases through the reference command and collect divergences.
+///
+/// A reference that exits unsuccessfully on a case is recorded as a
+/// divergence rather than aborting the run.
+///
+/// # Errors
+///
+/// Returns an error if the reference command cannot be spawned.
+pub fn run_difftest(reference: &ReferenceCommand, options: &DiffTestOptions) -> Result<DiffReport> {
+    let mut rng = SplitMix64::new(options.seed);
+    let mut report = DiffReport::default();
+    let flags = [
+        "--indent".to_string(),
+        options.indent.to_string(),
+        "--delimiter".to_string(),
+        options.delimiter.to_string(),
+    ];
+
+    for case in 0..options.cases {
+        let value = generate_value(&mut rng, options.max_depth);
+        let json = serde_json::to_string(&serde_json::Value::from(value.clone()))
+            .map_err(|e| ToonError::json_stringify(&e))?;
+
+        let ours = crate::encode::encode(
+            value,
+            Some(EncodeOptions {
+                indent: Some(options.indent),
+                delimiter: Some(options.delimiter),
+                ..EncodeOptions::default()
+            }),
+        );
+
+        let mut encode_args = vec!["--encode".to_string()];
+        encode_args.extend(flags.iter().cloned());
+        match run_reference(reference, &encode_args, &json)? {
+            Ok(theirs) => {
+                let theirs = theirs.trim_end_matches('\n');
+                if theirs != ours {
+                    report.divergences.push(Divergence {
+                        case,
+                        direction: DiffDirection::Encode,
+                        input: json.clone(),
+                        reference: theirs.to_string(),
+                        actual: ours.clone(),
+                    });
+                }
+            }
+            Err(message) => report.divergences.push(Divergence {
+                case,
+                direction: DiffDirection::Encode,
+                input: json.clone(),
+                reference: message,
+                actual: ours.clone(),
+            }),
+        }
+
+        let our_decoded = crate::decode::try_decode(
+            &ours,
+            Some(DecodeOptions {
+                indent: Some(options.indent),
+                ..DecodeOptions::default()
+            }),
+        )
+        .map_or_else(|err| format!("error: {err}"), |value| format_value(&value));
+
+        let mut decode_args = vec!["--decode".to_string()];
+        decode_args.extend(flags.iter().cloned());
+        let their_decoded = match run_reference(reference, &decode_args, &ours)? {
+            Ok(text) => match serde_json::from_str::<serde_json::Value>(&text) {
+                Ok(parsed) => format_value(&JsonValue::from(parsed)),
+                Err(err) => format!("error: invalid JSON from reference: {err}"),
+            },
+            Err(message) => message,
+        };
+
+        if their_decoded != our_decoded {
+            report.divergences.push(Divergence {
+                case,
+                direction: DiffDirection::Decode,
+                input: ours,
+                reference: their_decoded,
+                actual: our_decoded,
+            });
+        }
+
+        report.cases_run += 1;
+    }
+
+    Ok(report)
+}
+
+/// Generate the JSON documents used by [`run_difftest`] without running them.
+#[must_use]
+pub fn generate_cases(options: &DiffTestOptions) -> Vec<JsonValue> {
+    let mut rng = SplitMix64::new(options.seed);
+    (0..options.cases)
+        .map(|_| generate_value(&mut rng, options.max_depth))
+        .collect()
+}
+
+/// Run the reference once. The outer `Result` is a fatal I/O failure (the
+/// command could not be spawned); the inner one is a per-case failure that
+/// gets recorded as a divergence.
+fn run_reference(
+    reference: &ReferenceCommand,
+    args: &[String],
+    input: &str,
+) -> Result<std::result::Result<String, String>> {
+    match reference.run(args, input) {
+        Ok(output) => Ok(Ok(output)),
+        Err(err @ ToonError::Io { .. }) => Err(err),
+        Err(err) => Ok(Err(format!("error: {err}"))),
+    }
+}
+
+fn format_value(value: &JsonValue) -> String {
+    crate::json::json_stringify_lines(value, 0).concat()
+}
+
+fn generate_value(rng: &mut SplitMix64, max_depth: usize) -> JsonValue {
+    if max_depth == 0 {
+        return generate_primitive(rng);
+    }
+    match rng.below(6) {
+        0 => generate_primitive(rng),
+        1 => generate_object(rng, max_depth),
+        2 => generate_primitive_array(rng),
+        3 => generate_tabular_array(rng),
+        4 => JsonValue::Array(
+            (0..rng.below(4))
+                .map(|_| generate_primitive_array(rng))
+                .collect(),
+        ),
+        _ => JsonValue::Array(
+            (0..rng.below(4))
+                .map(|_| generate_value(rng, max_depth - 1))
+                .collect(),
+        ),
+    }
+}
+
+fn generate_object(rng: &mut SplitMix64, max_depth: usize) -> JsonValue {
+    let mut entries: Vec<(String, JsonValue)> = Vec::new();
+    for _ in 0..rng.below(5) {
+        let key = rng.pick(SAMPLE_KEYS).to_string();
+        if entries.iter().any(|(existing, _)| *existing == key) {
+            continue;
+        }
+        entries.push((key, generate_value(rng, max_depth - 1)));
+    }
+    JsonValue::Object(entries)
+}
+
+fn generate_primitive_array(rng: &mut SplitMix64) -> JsonValue {
+    JsonValue::Array((0..rng.below(5)).map(|_| generate_primitive(rng)).collect())
+}
+
+fn generate_tabular_array(rng: &mut SplitMix64) -> JsonValue {
+    let mut fields: Vec<&str> = Vec::new();
+    for _ in 0..=rng.below(3) {
+        let key = rng.pick(SAMPLE_KEYS);
+        if !fields.contains(key) {
+            fields.push(key);
+        }
+    }
+    let rows = (0..=rng.below(3))
+        .map(|_| {
+            JsonValue::Object(
+                fields
+                    .iter()
+                    .map(|field| ((*field).to_string(), generate_primitive(rng)))
+                    .collect(),
+            )
+        })
+        .collect();
+    JsonValue::Array(rows)
+}
+
+fn generate_primitive(rng: &mut SplitMix64) -> JsonValue {
+    let primitive = match rng.below(4) {
+        0 => StringOrNumberOrBoolOrNull::String(rng.pick(SAMPLE_STRINGS).to_string()),
+        1 => StringOrNumberOrBoolOrNull::Number(*rng.pick(SAMPLE_NUMBERS)),
+        2 => StringOrNumberOrBoolOrNull::Bool(rng.below(2) == 0),
+        _ => StringOrNumberOrBoolOrNull::Null,
+    };
+    JsonValue::Primitive(primitive)
+}
+
+/// Small deterministic PRNG so generated cases are reproducible from a seed.
+struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    const fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    const fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    const fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
@@ -19,9 +19,8 @@
 //! }
 //! ```
 
-use crate::encode::encoders;
-use crate::encode::normalize::normalize_json_value;
-use crate::encode::replacer::apply_replacer;
+use crate::encode::{encode_document, prepare_value};
+use crate::error::Result;
 use crate::options::{EncodeOptions, ResolvedEncodeOptions, resolve_encode_options};
 use crate::shared::validation::is_valid_unquoted_key;
 use crate::{JsonStreamEvent, JsonValue};
@@ -43,18 +42,24 @@ pub struct AsyncEncodeStream {
 
 impl AsyncEncodeStream {
     /// Create a new async encode stream from a JSON value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value cannot be encoded. Use [`Self::try_new`] to handle
+    /// the error without panicking.
     #[must_use]
     pub fn new(input: impl Into<JsonValue>, options: Option<EncodeOptions>) -> Self {
-        let resolved = resolve_encode_options(options);
-        let normalized = normalize_json_value(input.into());
-        let replaced = if let Some(replacer) = &resolved.replacer {
-            apply_replacer(&normalized, replacer)
-        } else {
-            normalized
-        };
-        let lines = encoders::encode_json_value(&replaced, &resolved);
+        Self::try_new(input, options).unwrap_or_else(|err| panic!("{err}"))
+    }
 
-        Self { lines, index: 0 }
+    /// Create a new async encode stream from a JSON value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value cannot be encoded.
+    pub fn try_new(input: impl Into<JsonValue>, options: Option<EncodeOptions>) -> Result<Self> {
+        let lines = encode_prepared(input.into(), options)?;
+        Ok(Self { lines, index: 0 })
     }
 
     /// Get the total number of lines.
@@ -117,18 +122,13 @@ impl AsyncEncodeEventStream {
     #[must_use]
     pub fn new(input: impl Into<JsonValue>, options: Option<EncodeOptions>) -> Self {
         let resolved = resolve_encode_options(options);
-        let normalized = normalize_json_value(input.into());
-        let replaced = if let Some(replacer) = &resolved.replacer {
-            apply_replacer(&normalized, replacer)
-        } else {
-            normalized
-        };
+        let prepared = prepare_value(input.into(), &resolved);
 
         Self {
             stack: Vec::new(),
             options: resolved,
             started: false,
-            root: Some(replaced),
+            root: Some(prepared),
         }
     }
 
@@ -208,6 +208,8 @@ impl AsyncEncodeEventStream {
                 });
                 Some(JsonStreamEvent::StartObject)
             }
+            #[cfg(feature = "raw")]
+            JsonValue::Raw(text) => Some(JsonStreamEvent::Raw { text }),
         }
     }
 }
@@ -224,21 +226,31 @@ impl Stream for AsyncEncodeEventStream {
 ///
 /// This function creates an async stream and collects all lines. The async
 /// wrapper provides yield points for cooperative scheduling.
+///
+/// # Panics
+///
+/// Panics if the value cannot be encoded. Use [`try_encode_lines_async`] to
+/// handle the error without panicking.
 pub async fn encode_lines_async(
     input: impl Into<JsonValue>,
     options: Option<EncodeOptions>,
 ) -> Vec<String> {
-    let input = input.into();
+    try_encode_lines_async(input, options)
+        .await
+        .unwrap_or_else(|err| panic!("{err}"))
+}
 
+/// Encode a JSON value to TOON lines asynchronously.
+///
+/// # Errors
+///
+/// Returns an error if the value cannot be encoded.
+pub async fn try_encode_lines_async(
+    input: impl Into<JsonValue>,
+    options: Option<EncodeOptions>,
+) -> Result<Vec<String>> {
     // Use asupersync's iter() to create a yielding stream from the lines
-    let resolved = resolve_encode_options(options);
-    let normalized = normalize_json_value(input);
-    let replaced = if let Some(replacer) = &resolved.replacer {
-        apply_replacer(&normalized, replacer)
-    } else {
-        normalized
-    };
-    let lines = encoders::encode_json_value(&replaced, &resolved);
+    let lines = encode_prepared(input.into(), options)?;
 
     // Wrap lines in an async stream for yield points
     let line_stream = iter(lines.clone());
@@ -246,15 +258,38 @@ pub async fn encode_lines_async(
     // Count forces iteration with yield points
     let _count = line_stream.count().await;
 
-    lines
+    Ok(lines)
 }
 
 /// Encode a JSON value to a TOON string asynchronously.
+///
+/// # Panics
+///
+/// Panics if the value cannot be encoded. Use [`try_encode_async`] to handle
+/// the error without panicking.
 pub async fn encode_async(input: impl Into<JsonValue>, options: Option<EncodeOptions>) -> String {
     let lines = encode_lines_async(input, options).await;
     lines.join("\n")
 }
 
+/// Encode a JSON value to a TOON string asynchronously.
+///
+/// # Errors
+///
+/// Returns an error if the value cannot be encoded.
+pub async fn try_encode_async(
+    input: impl Into<JsonValue>,
+    options: Option<EncodeOptions>,
+) -> Result<String> {
+    Ok(try_encode_lines_async(input, options).await?.join("\n"))
+}
+
+fn encode_prepared(input: JsonValue, options: Option<EncodeOptions>) -> Result<Vec<String>> {
+    let resolved = resolve_encode_options(options);
+    let prepared = prepare_value(input, &resolved);
+    encode_document(&prepared, &resolved)
+}
+
 /// Encode a JSON value to events asynchronously.
 ///
 /// Returns a vector of `JsonStreamEvent` items representing the structure.
@@ -296,6 +331,20 @@ mod tests {
         assert!(!stream.is_empty());
     }
 
+    #[test]
+    fn test_try_new_reports_encode_errors() {
+        let value = JsonValue::Object(vec![(
+            "name".to_string(),
+            JsonValue::Primitive(StringOrNumberOrBoolOrNull::String("a\u{200B}".to_string())),
+        )]);
+        let options = EncodeOptions {
+            reject_invisible: Some(true),
+            ..EncodeOptions::default()
+        };
+        assert!(AsyncEncodeStream::try_new(value.clone(), Some(options)).is_err());
+        assert_eq!(AsyncEncodeStream::try_new(value, None).unwrap().len(), 1);
+    }
+
     #[test]
     fn test_async_encode_event_stream() {
         let value = JsonValue::Object(vec![(
@@ -0,0 +1,116 @@
+//! Struct-of-arrays ("columnar") layout for arrays of objects.
+//!
+//! A tabular array such as `[{id: 1, name: a}, {id: 2, name: b}]` becomes an
+//! object with a row count under the `@rows` marker key and one array per
+//! field (`{"@rows": 2, id: [1, 2], name: [a, b]}`), which the encoder then
+//! writes as inline arrays:
+//!
+//! ```text
+//! users:
+//!   "@rows": 2
+//!   id[2]: 1,2
+//!   name[2]: a,b
+//! ```
+//!
+//! A key that reads as the marker with one or more extra `@` in front
+//! (`@rows`, `@@rows`, ...) gets one more, so the marker is unambiguous.
+//! Decoding with `DecodeOptions::columnar` strips that `@` and reassembles
+//! the rows of marked sections only.
+
+use crate::encode::normalize::is_json_primitive;
+use crate::shared::constants::COLUMNAR_MARKER;
+use crate::{JsonArray, JsonValue, StringOrNumberOrBoolOrNull};
+
+/// Rewrite every tabular array of objects in `value` into columnar form.
+#[must_use]
+pub fn to_columnar(value: JsonValue) -> JsonValue {
+    match value {
+        JsonValue::Primitive(_) => value,
+        #[cfg(feature = "raw")]
+        JsonValue::Raw(_) => value,
+        JsonValue::Array(items) => {
+            let items: JsonArray = items.into_iter().map(to_columnar).collect();
+            match columnar_fields(&items) {
+                Some(fields) => columns_from_rows(&fields, items),
+                None => JsonValue::Array(items),
+            }
+        }
+        JsonValue::Object(entries) => JsonValue::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| (escape_marker(key), to_columnar(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// Whether `key` is the columnar marker with zero or more extra `@` in
+/// front.
+#[must_use]
+pub fn is_marker_like(key: &str) -> bool {
+    key.strip_prefix('@')
+        .is_some_and(|rest| rest.trim_start_matches('@') == &COLUMNAR_MARKER[1..])
+}
+
+fn escape_marker(key: String) -> String {
+    if is_marker_like(&key) {
+        format!("@{key}")
+    } else {
+        key
+    }
+}
+
+/// Field order for a non-empty array of objects that share the same keys and
+/// hold only primitives; `None` if the array is not tabular.
+fn columnar_fields(items: &JsonArray) -> Option<Vec<String>> {
+    let Some(JsonValue::Object(first)) = items.first() else {
+        return None;
+    };
+    if first.is_empty() {
+        return None;
+    }
+    let fields: Vec<String> = first.iter().map(|(key, _)| key.clone()).collect();
+    let tabular = items.iter().all(|item| match item {
+        JsonValue::Object(entries) => {
+            entries.len() == fields.len()
+                && fields.iter().all(|field| {
+                    entries
+                        .iter()
+                        .find(|(key, _)| key == field)
+                        .is_some_and(|(_, value)| is_json_primitive(value))
+                })
+        }
+        _ => false,
+    });
+    tabular.then_some(fields)
+}
+
+/// Build the marked columnar object. The row objects were already rewritten,
+/// so their keys are escaped.
+fn columns_from_rows(fields: &[String], rows: JsonArray) -> JsonValue {
+    let length = rows.len();
+    let mut columns: Vec<(String, JsonArray)> = fields
+        .iter()
+        .map(|field| (field.clone(), Vec::with_capacity(length)))
+        .collect();
+    for row in rows {
+        let JsonValue::Object(mut entries) = row else {
+            continue;
+        };
+        for (field, column) in &mut columns {
+            if let Some(idx) = entries.iter().position(|(key, _)| key == field) {
+                column.push(entries.swap_remove(idx).1);
+            }
+        }
+    }
+    let marker = JsonValue::Primitive(StringOrNumberOrBoolOrNull::from_u64(length as u64));
+    JsonValue::Object(
+        std::iter::once((COLUMNAR_MARKER.to_string(), marker))
+            .chain(
+                columns
+                    .into_iter()
+                    .map(|(field, column)| (field, JsonValue::Array(column))),
+            )
+            .collect(),
+    )
+}
@@ -0,0 +1,181 @@
+//! Header column control for tabular arrays: projection to selected
+//! fields and header ordering.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::encode::normalize::is_json_primitive;
+use crate::options::{ColumnOrder, PathPattern, PathSegment};
+use crate::{JsonArray, JsonValue};
+
+/// A [`ColumnOrder`] that puts the listed fields first, in list order.
+#[must_use]
+pub fn column_priority<S: Into<String>>(names: impl IntoIterator<Item = S>) -> ColumnOrder {
+    let names: Vec<String> = names.into_iter().map(Into::into).collect();
+    Arc::new(move |_key, _fields| names.clone())
+}
+
+/// Reorder the fields of every tabular array in `value` with `order`.
+///
+/// The header becomes the returned names that are fields, in the returned
+/// order, followed by the fields it left out in their original order. Rows
+/// are rewritten to match, so the array stays tabular.
+#[must_use]
+pub fn apply_column_order(value: JsonValue, order: &ColumnOrder) -> JsonValue {
+    reorder(value, "", order)
+}
+
+fn reorder(value: JsonValue, key: &str, order: &ColumnOrder) -> JsonValue {
+    match value {
+        JsonValue::Object(entries) => JsonValue::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| {
+                    let value = reorder(value, &key, order);
+                    (key, value)
+                })
+                .collect(),
+        ),
+        JsonValue::Array(rows) => match tabular_header(&rows) {
+            Some(header) => {
+                let fields = ordered_fields(&header, &order(key, &header));
+                JsonValue::Array(reorder_rows(rows, &fields))
+            }
+            None => JsonValue::Array(
+                rows.into_iter()
+                    .map(|item| reorder(item, "", order))
+                    .collect(),
+            ),
+        },
+        JsonValue::Primitive(_) => value,
+        #[cfg(feature = "raw")]
+        JsonValue::Raw(_) => value,
+    }
+}
+
+fn ordered_fields(header: &[String], requested: &[String]) -> Vec<String> {
+    let mut fields: Vec<String> = Vec::with_capacity(header.len());
+    for name in requested {
+        if header.contains(name) && !fields.contains(name) {
+            fields.push(name.clone());
+        }
+    }
+    for name in header {
+        if !fields.contains(name) {
+            fields.push(name.clone());
+        }
+    }
+    fields
+}
+
+fn reorder_rows(rows: JsonArray, fields: &[String]) -> JsonArray {
+    rows.into_iter()
+        .map(|row| match row {
+            JsonValue::Object(mut entries) => {
+                entries.sort_by_key(|(key, _)| fields.iter().position(|field| field == key));
+                JsonValue::Object(entries)
+            }
+            other => other,
+        })
+        .collect()
+}
+
+/// Keep only the selected fields, in the listed order, in the objects of
+/// each array whose path matches a pattern in `columns`.
+///
+/// Fields an object lacks stay missing, and non-object items are left
+/// alone. Dropping nested fields this way often turns a list into a table.
+#[must_use]
+#[allow(clippy::implicit_hasher)]
+pub fn apply_column_projection(
+    value: JsonValue,
+    columns: &HashMap<PathPattern, Vec<String>>,
+) -> JsonValue {
+    project(value, columns, &mut Vec::new())
+}
+
+fn project(
+    value: JsonValue,
+    columns: &HashMap<PathPattern, Vec<String>>,
+    path: &mut Vec<PathSegment>,
+) -> JsonValue {
+    match value {
+        JsonValue::Object(entries) => JsonValue::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| {
+                    path.push(PathSegment::Key(key.clone()));
+                    let value = project(value, columns, path);
+                    path.pop();
+                    (key, value)
+                })
+                .collect(),
+        ),
+        JsonValue::Array(items) => {
+            let selected = columns
+                .iter()
+                .find_map(|(pattern, fields)| pattern.matches(path).then_some(fields));
+            if let Some(fields) = selected {
+                return JsonValue::Array(
+                    items
+                        .into_iter()
+                        .map(|item| select_fields(item, fields))
+                        .collect(),
+                );
+            }
+            JsonValue::Array(
+                items
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, item)| {
+                        path.push(PathSegment::Index(index));
+                        let item = project(item, columns, path);
+                        path.pop();
+                        item
+                    })
+                    .collect(),
+            )
+        }
+        JsonValue::Primitive(_) => value,
+        #[cfg(feature = "raw")]
+        JsonValue::Raw(_) => value,
+    }
+}
+
+fn select_fields(item: JsonValue, fields: &[String]) -> JsonValue {
+    let JsonValue::Object(mut entries) = item else {
+        return item;
+    };
+    let mut selected = Vec::with_capacity(fields.len());
+    for field in fields {
+        if let Some(index) = entries.iter().position(|(key, _)| key == field) {
+            selected.push(entries.swap_remove(index));
+        }
+    }
+    JsonValue::Object(selected)
+}
+
+/// The header the encoder writes `rows` under as a table, if it writes them
+/// as one: every row an object with the first row's keys, all primitive.
+fn tabular_header(rows: &JsonArray) -> Option<Vec<String>> {
+    let Some(JsonValue::Object(first)) = rows.first() else {
+        return None;
+    };
+    if first.is_empty() {
+        return None;
+    }
+    let header: Vec<String> = first.iter().map(|(key, _)| key.clone()).collect();
+    let tabular = rows.iter().all(|row| {
+        let JsonValue::Object(entries) = row else {
+            return false;
+        };
+        entries.len() == header.len()
+            && header.iter().all(|key| {
+                entries
+                    .iter()
+                    .find(|(k, _)| k == key)
+                    .is_some_and(|(_, value)| is_json_primitive(value))
+            })
+    });
+    tabular.then_some(header)
+}
@@ -1,49 +1,89 @@
 use std::collections::HashSet;
 
 use crate::encode::folding::try_fold_key_chain;
-use crate::encode::normalize::{
-    is_array_of_arrays, is_array_of_objects, is_array_of_primitives, is_empty_object,
-    is_json_primitive,
-};
 use crate::encode::primitives::{
-    encode_and_join_primitives, encode_key, encode_primitive, format_header,
+    encode_and_join_primitives, encode_and_join_runs, encode_key, encode_primitive_cells,
+    encode_primitive_ref, format_header_with_encoded_key,
 };
-use crate::options::ResolvedEncodeOptions;
+use crate::encode::rows::{AlignedRows, RowEncoder, render_rows};
+use crate::error::{Result, ToonError};
+use crate::options::{ColumnType, ResolvedEncodeOptions};
 use crate::shared::constants::{DOT, LIST_ITEM_MARKER, LIST_ITEM_PREFIX};
-use crate::{JsonArray, JsonObject, JsonPrimitive, JsonValue};
+use crate::{JsonPrimitiveRef, JsonValue, JsonValueRef};
+
+/// Encode a normalized JSON value into TOON lines.
+///
+/// # Errors
+///
+/// Returns [`ToonError::Encode`] with the offending path if a tabular row
+/// cannot be rendered (missing header field or non-primitive cell).
+pub fn encode_json_value(
+    value: &JsonValue,
+    options: &ResolvedEncodeOptions,
+) -> Result<Vec<String>> {
+    encode_value_ref(&value.into(), options)
+}
 
-#[must_use]
-pub fn encode_json_value(value: &JsonValue, options: &ResolvedEncodeOptions) -> Vec<String> {
+/// Encode a borrowed view of a normalized JSON value into TOON lines, as
+/// [`encode_json_value`] does.
+///
+/// # Errors
+///
+/// Returns [`ToonError::Encode`] with the offending path if a tabular row
+/// cannot be rendered (missing header field or non-primitive cell).
+pub fn encode_value_ref(
+    value: &JsonValueRef<'_>,
+    options: &ResolvedEncodeOptions,
+) -> Result<Vec<String>> {
     let estimated_lines = estimate_line_count(value);
     let mut out = Vec::with_capacity(estimated_lines);
     match value {
-        JsonValue::Primitive(primitive) => {
-            let encoded = encode_primitive(primitive, options.delimiter);
+        JsonValueRef::Primitive(primitive) => {
+            let encoded = encode_primitive_ref(primitive, options.delimiter);
             if !encoded.is_empty() {
                 out.push(encoded);
             }
         }
-        JsonValue::Array(items) => {
-            encode_array_lines(None, items, 0, options, &mut out);
+        JsonValueRef::Array(items) => {
+            encode_array_lines(None, items, 0, options, &mut out)?;
         }
-        JsonValue::Object(entries) => {
-            encode_object_lines(entries, 0, options, None, None, None, &mut out);
+        JsonValueRef::Object(entries) => {
+            encode_object_lines(entries, 0, options, None, None, None, &mut out)?;
         }
+        #[cfg(feature = "raw")]
+        JsonValueRef::Raw(text) => push_raw_lines(text, 0, options.indent, &mut out),
     }
-    out
+    Ok(out)
+}
+
+/// Encode a normalized JSON value as a list item (`- ...`) at `depth`.
+///
+/// # Errors
+///
+/// Returns [`ToonError::Encode`] with the offending path if a nested tabular
+/// row cannot be rendered.
+pub fn encode_list_item(
+    value: &JsonValue,
+    depth: usize,
+    options: &ResolvedEncodeOptions,
+) -> Result<Vec<String>> {
+    let value = JsonValueRef::from(value);
+    let mut out = Vec::with_capacity(estimate_line_count(&value));
+    encode_list_item_value_lines(&value, depth, options, &mut out)?;
+    Ok(out)
 }
 
 fn encode_object_lines(
-    value: &JsonObject,
+    value: &[(&str, JsonValueRef<'_>)],
     depth: usize,
     options: &ResolvedEncodeOptions,
     root_literal_keys: Option<&HashSet<String>>,
     path_prefix: Option<&str>,
     remaining_depth: Option<usize>,
     out: &mut Vec<String>,
-) {
+) -> Result<()> {
     // Collect keys as references to avoid cloning
-    let keys: Vec<&str> = value.iter().map(|(key, _)| key.as_str()).collect();
+    let keys: Vec<&str> = value.iter().map(|(key, _)| *key).collect();
 
     let mut root_literal_set = HashSet::new();
     let root_literal_keys = if depth == 0 && root_literal_keys.is_none() {
@@ -70,14 +110,15 @@ fn encode_object_lines(
             path_prefix,
             effective_flatten_depth,
             out,
-        );
+        )?;
     }
+    Ok(())
 }
 
 #[allow(clippy::too_many_arguments)]
 fn encode_key_value_pair_lines(
     key: &str,
-    value: &JsonValue,
+    value: &JsonValueRef<'_>,
     depth: usize,
     options: &ResolvedEncodeOptions,
     siblings: &[&str],
@@ -85,7 +126,7 @@ fn encode_key_value_pair_lines(
     path_prefix: Option<&str>,
     flatten_depth: usize,
     out: &mut Vec<String>,
-) {
+) -> Result<()> {
     let current_path =
         path_prefix.map_or_else(|| key.to_string(), |prefix| format!("{prefix}{DOT}{key}"));
 
@@ -98,34 +139,38 @@ fn encode_key_value_pair_lines(
         path_prefix,
         flatten_depth,
     ) {
-        let encoded_key = encode_key(&folded.folded_key);
+        // Folded segments are identifiers, and the path is written bare.
+        let encoded_key = folded.folded_key.clone();
 
         if folded.remainder.is_none() {
             match folded.leaf_value {
-                JsonValue::Primitive(primitive) => {
-                    let encoded = encode_primitive(&primitive, options.delimiter);
+                JsonValueRef::Primitive(primitive) => {
+                    let encoded = encode_primitive_ref(primitive, options.delimiter);
                     out.push(indented_key_value_line(
                         depth,
                         &encoded_key,
                         &encoded,
                         options.indent,
                     ));
-                    return;
+                    return Ok(());
                 }
-                JsonValue::Array(items) => {
-                    encode_array_lines(Some(&folded.folded_key), &items, depth, options, out);
-                    return;
+                JsonValueRef::Array(items) => {
+                    return encode_array_lines(Some(&encoded_key), items, depth, options, out)
+                        .map_err(|err| err.with_key_prefix(&folded.folded_key));
                 }
-                JsonValue::Object(entries) => {
-                    if is_empty_object(&entries) {
+                JsonValueRef::Object(entries) => {
+                    if entries.is_empty() {
                         out.push(indented_key_colon_line(depth, &encoded_key, options.indent));
-                        return;
+                        return Ok(());
                     }
                 }
+                // Raw text carries no key, so the chain is written unfolded.
+                #[cfg(feature = "raw")]
+                JsonValueRef::Raw(_) => {}
             }
         }
 
-        if let Some(JsonValue::Object(entries)) = folded.remainder {
+        if let Some(JsonValueRef::Object(entries)) = folded.remainder {
             out.push(indented_key_colon_line(depth, &encoded_key, options.indent));
             let remaining_depth = flatten_depth.saturating_sub(folded.segment_count);
             let folded_path = if let Some(prefix) = path_prefix {
@@ -133,24 +178,24 @@ fn encode_key_value_pair_lines(
             } else {
                 folded.folded_key.clone()
             };
-            encode_object_lines(
-                &entries,
+            return encode_object_lines(
+                entries,
                 depth + 1,
                 options,
                 root_literal_keys,
                 Some(&folded_path),
                 Some(remaining_depth),
                 out,
-            );
-            return;
+            )
+            .map_err(|err| err.with_key_prefix(&folded.folded_key));
         }
     }
 
     let encoded_key = encode_key(key);
 
     match value {
-        JsonValue::Primitive(primitive) => {
-            let encoded = encode_primitive(primitive, options.delimiter);
+        JsonValueRef::Primitive(primitive) => {
+            let encoded = encode_primitive_ref(primitive, options.delimiter);
             out.push(indented_key_value_line(
                 depth,
                 &encoded_key,
@@ -158,12 +203,13 @@ fn encode_key_value_pair_lines(
                 options.indent,
             ));
         }
-        JsonValue::Array(items) => {
-            encode_array_lines(Some(key), items, depth, options, out);
+        JsonValueRef::Array(items) => {
+            encode_array_lines(Some(&encoded_key), items, depth, options, out)
+                .map_err(|err| err.with_key_prefix(key))?;
         }
-        JsonValue::Object(entries) => {
+        JsonValueRef::Object(entries) => {
             out.push(indented_key_colon_line(depth, &encoded_key, options.indent));
-            if !is_empty_object(entries) {
+            if !entries.is_empty() {
                 encode_object_lines(
                     entries,
                     depth + 1,
@@ -172,85 +218,146 @@ fn encode_key_value_pair_lines(
                     Some(&current_path),
                     Some(flatten_depth),
                     out,
-                );
+                )
+                .map_err(|err| err.with_key_prefix(key))?;
             }
         }
+        // The key of a raw entry is not written: the text stands in for the
+        // whole `key: value` line.
+        #[cfg(feature = "raw")]
+        JsonValueRef::Raw(text) => push_raw_lines(text, depth, options.indent, out),
     }
+    Ok(())
 }
 
+/// Write an array under `key`, which is already encoded (quoted as needed,
+/// or a bare folded path); the array functions below take it the same way.
 fn encode_array_lines(
     key: Option<&str>,
-    value: &JsonArray,
+    value: &[JsonValueRef<'_>],
     depth: usize,
     options: &ResolvedEncodeOptions,
     out: &mut Vec<String>,
-) {
+) -> Result<()> {
     if value.is_empty() {
-        let header = format_header(0, key, None, options.delimiter);
+        let header = format_header(0, key, None, options);
         out.push(indented_line(depth, &header, options.indent));
-        return;
+        return Ok(());
     }
 
     if is_array_of_primitives(value) {
-        let array_line = encode_inline_array_line(value, options.delimiter, key);
+        if is_sparse_array(value, options.sparse_threshold) {
+            encode_sparse_array_lines(key, value, depth, options, out);
+            return Ok(());
+        }
+        let array_line = encode_inline_array_line(value, options, key);
         out.push(indented_line(depth, &array_line, options.indent));
-        return;
+        return Ok(());
     }
 
     if is_array_of_arrays(value) {
-        let all_primitive_arrays = value.iter().all(|item| match item {
-            JsonValue::Array(items) => is_array_of_primitives(items),
-            _ => false,
-        });
-        if all_primitive_arrays {
-            encode_array_of_arrays_as_list_items_lines(key, value, depth, options, out);
-            return;
-        }
+        return encode_array_of_arrays_as_list_items_lines(key, value, depth, options, out);
     }
 
     if is_array_of_objects(value) {
         if let Some(header) = extract_tabular_header(value) {
-            encode_array_of_objects_as_tabular_lines(key, value, &header, depth, options, out);
-        } else {
-            encode_mixed_array_as_list_items_lines(key, value, depth, options, out);
+            return encode_array_of_objects_as_tabular_lines(
+                key, value, &header, depth, options, out,
+            );
         }
-        return;
+        return encode_mixed_array_as_list_items_lines(key, value, depth, options, out);
     }
 
-    encode_mixed_array_as_list_items_lines(key, value, depth, options, out);
+    encode_mixed_array_as_list_items_lines(key, value, depth, options, out)
 }
 
-fn encode_array_of_arrays_as_list_items_lines(
+/// An array is written sparsely when its share of `null` entries reaches the
+/// threshold. At least one entry must be non-null: a header with no entry
+/// lines already means an empty list.
+#[allow(clippy::cast_precision_loss)]
+fn is_sparse_array(values: &[JsonValueRef<'_>], threshold: Option<f64>) -> bool {
+    let Some(threshold) = threshold else {
+        return false;
+    };
+    let nulls = values
+        .iter()
+        .filter(|item| matches!(item, JsonValueRef::Primitive(JsonPrimitiveRef::Null)))
+        .count();
+    nulls < values.len() && nulls as f64 / values.len() as f64 >= threshold
+}
+
+fn encode_sparse_array_lines(
     key: Option<&str>,
-    values: &JsonArray,
+    values: &[JsonValueRef<'_>],
     depth: usize,
     options: &ResolvedEncodeOptions,
     out: &mut Vec<String>,
 ) {
-    let header = format_header(values.len(), key, None, options.delimiter);
+    let header = format_header(values.len(), key, None, options);
     out.push(indented_line(depth, &header, options.indent));
 
-    for item in values {
-        if let JsonValue::Array(items) = item {
-            let line = encode_inline_array_line(items, options.delimiter, None);
-            out.push(indented_list_item(depth + 1, &line, options.indent));
+    for (index, item) in values.iter().enumerate() {
+        if let JsonValueRef::Primitive(primitive) = item
+            && !matches!(primitive, JsonPrimitiveRef::Null)
+        {
+            let encoded = encode_primitive_ref(primitive, options.delimiter);
+            out.push(indented_key_value_line(
+                depth + 1,
+                &index.to_string(),
+                &encoded,
+                options.indent,
+            ));
         }
     }
 }
 
-fn encode_inline_array_line(values: &JsonArray, delimiter: char, key: Option<&str>) -> String {
-    let primitives: Vec<JsonPrimitive> = values
+/// Write an array of arrays, of any depth, as list items: each inner array
+/// of primitives inline (`- [2]: 1,2`), and each deeper one as a `- [N]:`
+/// header over its own list items, one level further in.
+fn encode_array_of_arrays_as_list_items_lines(
+    key: Option<&str>,
+    values: &[JsonValueRef<'_>],
+    depth: usize,
+    options: &ResolvedEncodeOptions,
+    out: &mut Vec<String>,
+) -> Result<()> {
+    let header = format_header(values.len(), key, None, options);
+    out.push(indented_line(depth, &header, options.indent));
+
+    for (index, item) in values.iter().enumerate() {
+        encode_list_item_value_lines(item, depth + 1, options, out)
+            .map_err(|err| err.with_index_prefix(index))?;
+    }
+    Ok(())
+}
+
+fn encode_inline_array_line(
+    values: &[JsonValueRef<'_>],
+    options: &ResolvedEncodeOptions,
+    key: Option<&str>,
+) -> String {
+    let primitives: Vec<JsonPrimitiveRef> = values
         .iter()
         .filter_map(|item| match item {
-            JsonValue::Primitive(primitive) => Some(primitive.clone()),
+            JsonValueRef::Primitive(primitive) => Some(primitive.clone()),
             _ => None,
         })
         .collect();
-    let header = format_header(values.len(), key, None, delimiter);
+    let header = format_header(values.len(), key, None, options);
     if primitives.is_empty() {
         return header;
     }
-    let joined = encode_and_join_primitives(&primitives, delimiter);
+    let joined = options.run_length.map_or_else(
+        || encode_and_join_primitives(&primitives, options.delimiter, options.empty_string),
+        |min_run| {
+            encode_and_join_runs(
+                &primitives,
+                options.delimiter,
+                options.empty_string,
+                min_run,
+            )
+        },
+    );
     // Build "header joined" without format!
     let mut out = String::with_capacity(header.len() + 1 + joined.len());
     out.push_str(&header);
@@ -261,47 +368,130 @@ fn encode_inline_array_line(values: &JsonArray, delimiter: char, key: Option<&st
 
 fn encode_array_of_objects_as_tabular_lines(
     key: Option<&str>,
-    rows: &JsonArray,
+    rows: &[JsonValueRef<'_>],
     header: &[String],
     depth: usize,
     options: &ResolvedEncodeOptions,
     out: &mut Vec<String>,
-) {
-    let formatted_header = format_header(rows.len(), key, Some(header), options.delimiter);
+) -> Result<()> {
+    let formatted_header = format_tabular_header(rows, key, header, options);
     out.push(indented_line(depth, &formatted_header, options.indent));
-    write_tabular_rows_lines(rows, header, depth + 1, options, out);
+    write_tabular_rows_lines(rows, header, depth + 1, options, out)
+}
+
+fn format_tabular_header(
+    rows: &[JsonValueRef<'_>],
+    key: Option<&str>,
+    header: &[String],
+    options: &ResolvedEncodeOptions,
+) -> String {
+    if !options.typed_headers {
+        return format_header(rows.len(), key, Some(header), options);
+    }
+    let types: Vec<Option<ColumnType>> = header
+        .iter()
+        .map(|field| infer_column_type(rows, field))
+        .collect();
+    format_header_with_encoded_key(
+        rows.len(),
+        key,
+        Some(header),
+        &types,
+        options.delimiter,
+        options.always_mark_delimiter,
+    )
+}
+
+/// Infer a column's type from its non-null cells; `None` for mixed or
+/// all-null columns.
+fn infer_column_type(rows: &[JsonValueRef<'_>], field: &str) -> Option<ColumnType> {
+    let mut inferred: Option<ColumnType> = None;
+    for row in rows {
+        let JsonValueRef::Object(entries) = row else {
+            return None;
+        };
+        let cell_type = match object_get(entries, field)? {
+            JsonValueRef::Primitive(JsonPrimitiveRef::Null) => continue,
+            JsonValueRef::Primitive(JsonPrimitiveRef::String(_)) => ColumnType::String,
+            JsonValueRef::Primitive(JsonPrimitiveRef::Bool(_)) => ColumnType::Bool,
+            JsonValueRef::Primitive(JsonPrimitiveRef::Number(number)) if number.fract() == 0.0 => {
+                ColumnType::Integer
+            }
+            JsonValueRef::Primitive(JsonPrimitiveRef::Int(_) | JsonPrimitiveRef::UInt(_)) => {
+                ColumnType::Integer
+            }
+            JsonValueRef::Primitive(JsonPrimitiveRef::Number(_)) => ColumnType::Number,
+            _ => return None,
+        };
+        inferred = match (inferred, cell_type) {
+            (None, _) => Some(cell_type),
+            (Some(previous), current) if previous == current => Some(previous),
+            (
+                Some(ColumnType::Integer | ColumnType::Number),
+                ColumnType::Integer | ColumnType::Number,
+            ) => Some(ColumnType::Number),
+            _ => return None,
+        };
+    }
+    inferred
 }
 
 fn write_tabular_rows_lines(
-    rows: &JsonArray,
+    rows: &[JsonValueRef<'_>],
     header: &[String],
     depth: usize,
     options: &ResolvedEncodeOptions,
     out: &mut Vec<String>,
-) {
-    for row in rows {
-        if let JsonValue::Object(entries) = row {
+) -> Result<()> {
+    let row_encoder: Option<&dyn RowEncoder> = options
+        .row_encoder
+        .as_deref()
+        .or_else(|| options.align_columns.then_some(&AlignedRows));
+    let mut cells = Vec::new();
+    for (row_index, row) in rows.iter().enumerate() {
+        if let JsonValueRef::Object(entries) = row {
             let mut values = Vec::with_capacity(header.len());
             for key in header {
-                let value = object_get(entries, key).expect("tabular header missing key");
-                if let JsonValue::Primitive(primitive) = value {
-                    values.push(primitive.clone());
-                } else {
-                    panic!("tabular row contains non-primitive value");
-                }
+                let Some(value) = object_get(entries, key) else {
+                    return Err(ToonError::tabular_missing_field(
+                        format!("[{row_index}]"),
+                        key,
+                    ));
+                };
+                let JsonValueRef::Primitive(primitive) = value else {
+                    return Err(ToonError::tabular_non_primitive(format!(
+                        "[{row_index}].{key}"
+                    )));
+                };
+                values.push(primitive.clone());
             }
-            let joined = encode_and_join_primitives(&values, options.delimiter);
+            if row_encoder.is_some() {
+                cells.push(encode_primitive_cells(
+                    &values,
+                    options.delimiter,
+                    options.empty_string,
+                ));
+                continue;
+            }
+            let joined =
+                encode_and_join_primitives(&values, options.delimiter, options.empty_string);
             out.push(indented_line(depth, &joined, options.indent));
         }
     }
+    if let Some(encoder) = row_encoder {
+        for line in render_rows(encoder, &cells, options.delimiter)? {
+            out.push(indented_line(depth, &line, options.indent));
+        }
+    }
+    Ok(())
 }
 
-fn extract_tabular_header(rows: &JsonArray) -> Option<Vec<String>> {
+fn extract_tabular_header(rows: &[JsonValueRef<'_>]) -> Option<Vec<String>> {
     if rows.is_empty() {
         return None;
     }
 
-    let JsonValue::Object(first) = &rows[0] else {
+    let JsonValueRef::Object(first) = &rows[0] else {
         return None;
     };
 
@@ -309,7 +499,7 @@ fn extract_tabular_header(rows: &JsonArray) -> Option<Vec<String>> {
         return None;
     }
 
-    let header: Vec<String> = first.iter().map(|(key, _)| key.clone()).collect();
+    let header: Vec<String> = first.iter().map(|(key, _)| (*key).to_string()).collect();
     if is_tabular_array(rows, &header) {
         Some(header)
     } else {
@@ -317,9 +507,9 @@ fn extract_tabular_header(rows: &JsonArray) -> Option<Vec<String>> {
     }
 }
 
-fn is_tabular_array(rows: &JsonArray, header: &[String]) -> bool {
+fn is_tabular_array(rows: &[JsonValueRef<'_>], header: &[String]) -> bool {
     for row in rows {
-        let JsonValue::Object(entries) = row else {
+        let JsonValueRef::Object(entries) = row else {
             return false;
         };
 
@@ -331,7 +521,7 @@ fn is_tabular_array(rows: &JsonArray, header: &[String]) -> bool {
             let Some(value) = object_get(entries, key) else {
                 return false;
             };
-            if !is_json_primitive(value) {
+            if !matches!(value, JsonValueRef::Primitive(_)) {
                 return false;
             }
         }
@@ -341,62 +531,64 @@ fn is_tabular_array(rows: &JsonArray, header: &[String]) -> bool {
 
 fn encode_mixed_array_as_list_items_lines(
     key: Option<&str>,
-    items: &JsonArray,
+    items: &[JsonValueRef<'_>],
     depth: usize,
     options: &ResolvedEncodeOptions,
     out: &mut Vec<String>,
-) {
-    let header = format_header(items.len(), key, None, options.delimiter);
+) -> Result<()> {
+    // Raw items are written without a hyphen and are not counted, as they
+    // were when decoded.
+    #[cfg(feature = "raw")]
+    let length = items
+        .iter()
+        .filter(|item| !matches!(item, JsonValueRef::Raw(_)))
+        .count();
+    #[cfg(not(feature = "raw"))]
+    let length = items.len();
+    let header = format_header(length, key, None, options);
     out.push(indented_line(depth, &header, options.indent));
 
-    for item in items {
-        encode_list_item_value_lines(item, depth + 1, options, out);
+    for (index, item) in items.iter().enumerate() {
+        encode_list_item_value_lines(item, depth + 1, options, out)
+            .map_err(|err| err.with_index_prefix(index))?;
     }
+    Ok(())
 }
 
 fn encode_object_as_list_item_lines(
-    obj: &JsonObject,
+    obj: &[(&str, JsonValueRef<'_>)],
     depth: usize,
     options: &ResolvedEncodeOptions,
     out: &mut Vec<String>,
-) {
+) -> Result<()> {
     if obj.is_empty() {
         out.push(indented_line(depth, LIST_ITEM_MARKER, options.indent));
-        return;
+        return Ok(());
     }
 
-    let first = obj[0].clone();
-    let rest = if obj.len() > 1 {
-        obj[1..].to_vec()
-    } else {
-        Vec::new()
-    };
-    let (first_key, first_value) = first;
-
-    if let JsonValue::Array(items) = &first_value {
-        if is_array_of_objects(items) {
-            if let Some(header) = extract_tabular_header(items) {
-                let formatted = format_header(
-                    items.len(),
-                    Some(&first_key),
-                    Some(&header),
-                    options.delimiter,
-                );
-                out.push(indented_list_item(depth, &formatted, options.indent));
-                write_tabular_rows_lines(items, &header, depth + 2, options, out);
-                if !rest.is_empty() {
-                    encode_object_lines(&rest, depth + 1, options, None, None, None, out);
-                }
-                return;
-            }
+    let (first_key, first_value) = &obj[0];
+    let rest = &obj[1..];
+
+    if let JsonValueRef::Array(items) = first_value
+        && is_array_of_objects(items)
+        && let Some(header) = extract_tabular_header(items)
+    {
+        let formatted =
+            format_tabular_header(items, Some(&encode_key(first_key)), &header, options);
+        out.push(indented_list_item(depth, &formatted, options.indent));
+        write_tabular_rows_lines(items, &header, depth + 2, options, out)
+            .map_err(|err| err.with_key_prefix(first_key))?;
+        if !rest.is_empty() {
+            encode_object_lines(rest, depth + 1, options, None, None, None, out)?;
         }
+        return Ok(());
     }
 
-    let encoded_key = encode_key(&first_key);
+    let encoded_key = encode_key(first_key);
 
     match first_value {
-        JsonValue::Primitive(primitive) => {
-            let encoded = encode_primitive(&primitive, options.delimiter);
+        JsonValueRef::Primitive(primitive) => {
+            let encoded = encode_primitive_ref(primitive, options.delimiter);
             out.push(indented_list_item_key_value(
                 depth,
                 &encoded_key,
@@ -404,17 +596,17 @@ fn encode_object_as_list_item_lines(
                 options.indent,
             ));
         }
-        JsonValue::Array(items) => {
+        JsonValueRef::Array(items) => {
             if items.is_empty() {
-                let header = format_header(0, None, None, options.delimiter);
+                let header = format_header(0, None, None, options);
                 out.push(indented_list_item_key_header(
                     depth,
                     &encoded_key,
                     &header,
                     options.indent,
                 ));
-            } else if is_array_of_primitives(&items) {
-                let line = encode_inline_array_line(&items, options.delimiter, None);
+            } else if is_array_of_primitives(items) {
+                let line = encode_inline_array_line(items, options, None);
                 out.push(indented_list_item_key_header(
                     depth,
                     &encoded_key,
@@ -422,66 +614,128 @@ fn encode_object_as_list_item_lines(
                     options.indent,
                 ));
             } else {
-                let header = format_header(items.len(), None, None, options.delimiter);
+                let header = format_header(items.len(), None, None, options);
                 out.push(indented_list_item_key_header(
                     depth,
                     &encoded_key,
                     &header,
                     options.indent,
                 ));
-                for item in &items {
-                    encode_list_item_value_lines(item, depth + 2, options, out);
+                for (index, item) in items.iter().enumerate() {
+                    encode_list_item_value_lines(item, depth + 2, options, out)
+                        .map_err(|err| err.with_index_prefix(index).with_key_prefix(first_key))?;
                 }
             }
         }
-        JsonValue::Object(entries) => {
+        JsonValueRef::Object(entries) => {
             out.push(indented_list_item_key_colon(
                 depth,
                 &encoded_key,
                 options.indent,
             ));
-            if !is_empty_object(&entries) {
-                encode_object_lines(&entries, depth + 2, options, None, None, None, out);
+            if !entries.is_empty() {
+                encode_object_lines(entries, depth + 2, options, None, None, None, out)
+                    .map_err(|err| err.with_key_prefix(first_key))?;
+            }
+        }
+        #[cfg(feature = "raw")]
+        JsonValueRef::Raw(text) => {
+            let mut lines = text.split('\n');
+            let first_line = lines.next().unwrap_or_default();
+            out.push(indented_list_item(depth, first_line, options.indent));
+            for line in lines {
+                out.push(indented_line(depth + 1, line, options.indent));
             }
         }
     }
 
     if !rest.is_empty() {
-        encode_object_lines(&rest, depth + 1, options, None, None, None, out);
+        encode_object_lines(rest, depth + 1, options, None, None, None, out)?;
     }
+    Ok(())
 }
 
 fn encode_list_item_value_lines(
-    value: &JsonValue,
+    value: &JsonValueRef<'_>,
     depth: usize,
     options: &ResolvedEncodeOptions,
     out: &mut Vec<String>,
-) {
+) -> Result<()> {
     match value {
-        JsonValue::Primitive(primitive) => {
-            let encoded = encode_primitive(primitive, options.delimiter);
+        JsonValueRef::Primitive(primitive) => {
+            let encoded = encode_primitive_ref(primitive, options.delimiter);
             out.push(indented_list_item(depth, &encoded, options.indent));
         }
-        JsonValue::Array(items) => {
+        JsonValueRef::Array(items) => {
             if is_array_of_primitives(items) {
-                let line = encode_inline_array_line(items, options.delimiter, None);
+                let line = encode_inline_array_line(items, options, None);
                 out.push(indented_list_item(depth, &line, options.indent));
             } else {
-                let header = format_header(items.len(), None, None, options.delimiter);
+                let header = format_header(items.len(), None, None, options);
                 out.push(indented_list_item(depth, &header, options.indent));
-                for item in items {
-                    encode_list_item_value_lines(item, depth + 1, options, out);
+                for (index, item) in items.iter().enumerate() {
+                    encode_list_item_value_lines(item, depth + 1, options, out)
+                        .map_err(|err| err.with_index_prefix(index))?;
                 }
             }
         }
-        JsonValue::Object(entries) => {
-            encode_object_as_list_item_lines(entries, depth, options, out);
+        JsonValueRef::Object(entries) => {
+            encode_object_as_list_item_lines(entries, depth, options, out)?;
         }
+        #[cfg(feature = "raw")]
+        JsonValueRef::Raw(text) => push_raw_lines(text, depth, options.indent, out),
     }
+    Ok(())
 }
 
-fn object_get<'a>(entries: &'a JsonObject, key: &str) -> Option<&'a JsonValue> {
-    entries.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+/// Write raw text verbatim, each line indented to `depth`.
+#[cfg(feature = "raw")]
+fn push_raw_lines(text: &str, depth: usize, indent_size: usize, out: &mut Vec<String>) {
+    for line in text.split('\n') {
+        out.push(indented_line(depth, line, indent_size));
+    }
+}
+
+fn object_get<'v, 'a>(
+    entries: &'v [(&'a str, JsonValueRef<'a>)],
+    key: &str,
+) -> Option<&'v JsonValueRef<'a>> {
+    entries.iter().find(|(k, _)| *k == key).map(|(_, v)| v)
+}
+
+fn is_array_of_primitives(items: &[JsonValueRef<'_>]) -> bool {
+    items
+        .iter()
+        .all(|item| matches!(item, JsonValueRef::Primitive(_)))
+}
+
+fn is_array_of_arrays(items: &[JsonValueRef<'_>]) -> bool {
+    items
+        .iter()
+        .all(|item| matches!(item, JsonValueRef::Array(_)))
+}
+
+fn is_array_of_objects(items: &[JsonValueRef<'_>]) -> bool {
+    items
+        .iter()
+        .all(|item| matches!(item, JsonValueRef::Object(_)))
+}
+
+/// Format an untyped array header, marking the delimiter as the options ask.
+fn format_header(
+    length: usize,
+    key: Option<&str>,
+    fields: Option<&[String]>,
+    options: &ResolvedEncodeOptions,
+) -> String {
+    format_header_with_encoded_key(
+        length,
+        key,
+        fields,
+        &[],
+        options.delimiter,
+        options.always_mark_delimiter,
+    )
 }
 
 fn indented_line(depth: usize, content: &str, indent_size: usize) -> String {
@@ -620,14 +874,14 @@ fn indented_list_item_key_header(
 
 /// Estimate the number of output lines for pre-allocation.
 /// This is a rough heuristic - over-estimation is fine, under-estimation causes reallocation.
-fn estimate_line_count(value: &JsonValue) -> usize {
+fn estimate_line_count(value: &JsonValueRef<'_>) -> usize {
     match value {
-        JsonValue::Primitive(_) => 1,
-        JsonValue::Array(items) => {
+        JsonValueRef::Primitive(_) => 1,
+        JsonValueRef::Array(items) => {
             // Header line + recursively estimate each item
             1 + items.iter().map(estimate_line_count).sum::<usize>()
         }
-        JsonValue::Object(entries) => {
+        JsonValueRef::Object(entries) => {
             // Each entry produces at least one line
             entries
                 .iter()
@@ -635,5 +889,7 @@ fn estimate_line_count(value: &JsonValue) -> usize {
                 .sum::<usize>()
                 .max(1)
         }
+        #[cfg(feature = "raw")]
+        JsonValueRef::Raw(text) => text.split('\n').count(),
     }
 }
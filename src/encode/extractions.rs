@@ -0,0 +1,49 @@
+//! Side tables describing what an encode removed or replaced.
+//!
+//! The replacer and the long string policy both change the document before it
+//! is written. [`Extractions`] records those changes so a caller can
+//! reconcile the encoded text with its input: which values the replacer
+//! dropped or rewrote, which strings were truncated, and which were moved out
+//! behind references.
+
+use crate::JsonValue;
+use crate::encode::long_string::ExtractedBlob;
+use crate::options::PathSegment;
+
+/// Everything an encode took out of the document, in document order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Extractions {
+    /// Strings replaced with references by
+    /// [`LongStringPolicy::ExternalRef`](crate::options::LongStringPolicy::ExternalRef).
+    pub blobs: Vec<ExtractedBlob>,
+    /// Values the replacer removed or changed.
+    pub redactions: Vec<Redaction>,
+    /// Strings cut by
+    /// [`LongStringPolicy::Truncate`](crate::options::LongStringPolicy::Truncate).
+    pub trims: Vec<Trim>,
+}
+
+impl Extractions {
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.blobs.is_empty() && self.redactions.is_empty() && self.trims.is_empty()
+    }
+}
+
+/// A value the replacer removed (`replacement` is `None`) or changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redaction {
+    /// The path passed to the replacer, indexing the input document.
+    pub path: Vec<PathSegment>,
+    pub original: JsonValue,
+    pub replacement: Option<JsonValue>,
+}
+
+/// A string shortened by the truncate policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trim {
+    /// Path of the string in the document after the replacer ran.
+    pub path: Vec<PathSegment>,
+    /// The full string before truncation.
+    pub original: String,
+}
@@ -1,35 +1,34 @@
 use std::collections::HashSet;
 
-use crate::JsonValue;
-use crate::encode::normalize::{is_empty_object, is_json_object};
+use crate::JsonValueRef;
 use crate::options::{KeyFoldingMode, ResolvedEncodeOptions};
 use crate::shared::constants::DOT;
 use crate::shared::validation::is_identifier_segment;
 
 #[derive(Debug, Clone)]
-pub struct FoldResult {
+pub struct FoldResult<'a> {
     pub folded_key: String,
-    pub remainder: Option<JsonValue>,
-    pub leaf_value: JsonValue,
+    pub remainder: Option<&'a JsonValueRef<'a>>,
+    pub leaf_value: &'a JsonValueRef<'a>,
     pub segment_count: usize,
 }
 
 #[must_use]
 #[allow(clippy::implicit_hasher)]
-pub fn try_fold_key_chain(
-    key: &str,
-    value: &JsonValue,
+pub fn try_fold_key_chain<'a>(
+    key: &'a str,
+    value: &'a JsonValueRef<'a>,
     siblings: &[&str],
     options: &ResolvedEncodeOptions,
     root_literal_keys: Option<&HashSet<String>>,
     path_prefix: Option<&str>,
     flatten_depth: usize,
-) -> Option<FoldResult> {
+) -> Option<FoldResult<'a>> {
     if options.key_folding != KeyFoldingMode::Safe {
         return None;
     }
 
-    if !is_json_object(value) {
+    if !matches!(value, JsonValueRef::Object(_)) {
         return None;
     }
 
@@ -49,7 +48,7 @@ pub fn try_fold_key_chain(
     }
 
     let mut folded_key =
-        String::with_capacity(segments.iter().map(String::len).sum::<usize>() + segments.len());
+        String::with_capacity(segments.iter().map(|seg| seg.len()).sum::<usize>() + segments.len());
     for (i, seg) in segments.iter().enumerate() {
         if i > 0 {
             folded_key.push(DOT);
@@ -66,10 +65,10 @@ pub fn try_fold_key_chain(
         |prefix| format!("{prefix}{DOT}{folded_key}"),
     );
 
-    if let Some(root_keys) = root_literal_keys {
-        if root_keys.contains(&absolute_path) {
-            return None;
-        }
+    if let Some(root_keys) = root_literal_keys
+        && root_keys.contains(&absolute_path)
+    {
+        return None;
     }
 
     Some(FoldResult {
@@ -80,16 +79,20 @@ pub fn try_fold_key_chain(
     })
 }
 
-fn collect_single_key_chain(
-    start_key: &str,
-    start_value: &JsonValue,
+fn collect_single_key_chain<'a>(
+    start_key: &'a str,
+    start_value: &'a JsonValueRef<'a>,
     max_depth: usize,
-) -> (Vec<String>, Option<JsonValue>, JsonValue) {
-    let mut segments = vec![start_key.to_string()];
-    let mut current_value = start_value.clone();
+) -> (
+    Vec<&'a str>,
+    Option<&'a JsonValueRef<'a>>,
+    &'a JsonValueRef<'a>,
+) {
+    let mut segments = vec![start_key];
+    let mut current_value = start_value;
 
     while segments.len() < max_depth {
-        let JsonValue::Object(ref obj) = current_value else {
+        let JsonValueRef::Object(obj) = current_value else {
             break;
         };
 
@@ -97,19 +100,14 @@ fn collect_single_key_chain(
             break;
         }
 
-        let (next_key, next_value) = obj[0].clone();
+        let (next_key, next_value) = &obj[0];
         segments.push(next_key);
         current_value = next_value;
     }
 
     match current_value {
-        JsonValue::Object(entries) if is_empty_object(&entries) => {
-            let obj = JsonValue::Object(entries);
-            (segments, None, obj)
-        }
-        JsonValue::Object(entries) => {
-            let remainder = JsonValue::Object(entries);
-            (segments, Some(remainder.clone()), remainder)
+        JsonValueRef::Object(entries) if !entries.is_empty() => {
+            (segments, Some(current_value), current_value)
         }
         other => (segments, None, other),
     }
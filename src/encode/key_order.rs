@@ -0,0 +1,41 @@
+use crate::JsonValue;
+use crate::options::SerdeKeyOrder;
+
+/// Reorder the keys of every object in `value` according to `order`.
+///
+/// Sorting is stable, so keys that `order` does not rank keep their
+/// relative order.
+#[must_use]
+pub fn apply_key_order(value: JsonValue, order: &SerdeKeyOrder) -> JsonValue {
+    if *order == SerdeKeyOrder::AsIs {
+        return value;
+    }
+    reorder(value, order)
+}
+
+fn reorder(value: JsonValue, order: &SerdeKeyOrder) -> JsonValue {
+    match value {
+        JsonValue::Object(entries) => {
+            let mut entries: Vec<_> = entries
+                .into_iter()
+                .map(|(key, value)| (key, reorder(value, order)))
+                .collect();
+            match order {
+                SerdeKeyOrder::AsIs => {}
+                SerdeKeyOrder::Sorted => entries.sort_by(|(a, _), (b, _)| a.cmp(b)),
+                SerdeKeyOrder::SchemaOrder(keys) => entries.sort_by_key(|(key, _)| {
+                    keys.iter()
+                        .position(|listed| listed == key)
+                        .unwrap_or(keys.len())
+                }),
+            }
+            JsonValue::Object(entries)
+        }
+        JsonValue::Array(items) => {
+            JsonValue::Array(items.into_iter().map(|item| reorder(item, order)).collect())
+        }
+        JsonValue::Primitive(_) => value,
+        #[cfg(feature = "raw")]
+        JsonValue::Raw(_) => value,
+    }
+}
@@ -0,0 +1,229 @@
+//! Re-encoding that follows the layout of a previous encoding.
+//!
+//! Documents kept under version control are re-encoded whenever their data
+//! changes, and a small data change should make a small diff. [`encode_like`]
+//! reads the layout of the previous TOON text and encodes the new value the
+//! same way:
+//!
+//! - the indentation, delimiter (and whether a comma is marked), and
+//!   `#%toon` directive
+//! - key folding, typed headers, aligned columns, and bare empty cells
+//! - the order of keys in each object and of fields in each tabular header,
+//!   matched by key and array index, with new keys after the old ones
+//!
+//! Quoting follows from the delimiter, so it matches as well. Options given
+//! explicitly take precedence over what is read from the previous text.
+//!
+//! ```
+//! use toon::encode_like;
+//!
+//! let previous = "users[1|]{name|id}:\n  Ada|1";
+//! let value = serde_json::json!({"users": [
+//!     {"id": 1, "name": "Ada"},
+//!     {"id": 2, "name": "Bob"},
+//! ]});
+//! assert_eq!(
+//!     encode_like(value, previous, None),
+//!     "users[2|]{name|id}:\n  Ada|1\n  Bob|2"
+//! );
+//! ```
+
+use crate::decode::parser::parse_delimited_values;
+use crate::error::Result;
+use crate::header::parse_header;
+use crate::options::{
+    DecodeOptions, EmptyStringStyle, EncodeOptions, ExpandPathsMode, KeyFoldingMode,
+};
+use crate::shared::constants::{
+    CLOSE_BRACKET, COMMA, DEFAULT_DELIMITER, DOUBLE_QUOTE, LIST_ITEM_PREFIX, OPEN_BRACKET,
+};
+use crate::shared::directive::Directive;
+use crate::shared::string_utils::find_unquoted_char;
+use crate::{JsonValue, try_decode, try_encode};
+
+/// Encode `value` with the layout of `previous`, a TOON encoding of similar
+/// data.
+///
+/// # Panics
+///
+/// Panics if the value cannot be encoded. Use [`try_encode_like`] to handle
+/// encode errors without panicking.
+pub fn encode_like(
+    value: impl Into<JsonValue>,
+    previous: &str,
+    options: Option<EncodeOptions>,
+) -> String {
+    try_encode_like(value, previous, options).unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// Encode `value` with the layout of `previous`, a TOON encoding of similar
+/// data. Text that does not decode still lends its formatting, but not its
+/// key order.
+///
+/// # Errors
+///
+/// Returns [`ToonError::Encode`](crate::error::ToonError::Encode) with the
+/// path of the offending value if it cannot be encoded.
+pub fn try_encode_like(
+    value: impl Into<JsonValue>,
+    previous: &str,
+    options: Option<EncodeOptions>,
+) -> Result<String> {
+    let layout = infer_layout(previous);
+    let mut options = options.unwrap_or_default();
+    options.indent = options.indent.or(layout.indent);
+    options.delimiter = options.delimiter.or(layout.delimiter);
+    options.key_folding = options.key_folding.or(layout.key_folding);
+    options.typed_headers = options.typed_headers.or(layout.typed_headers);
+    options.align_columns = options.align_columns.or(layout.align_columns);
+    options.always_mark_delimiter = options
+        .always_mark_delimiter
+        .or(layout.always_mark_delimiter);
+    options.empty_string = options.empty_string.or(layout.empty_string);
+    options.emit_directive = options.emit_directive.or(layout.emit_directive);
+
+    let decode_options = DecodeOptions {
+        indent: layout.indent,
+        expand_paths: (layout.key_folding == Some(KeyFoldingMode::Safe))
+            .then_some(ExpandPathsMode::Safe),
+        typed_headers: layout.typed_headers,
+        ..DecodeOptions::default()
+    };
+    let value = value.into();
+    let value = match try_decode(previous, Some(decode_options)) {
+        Ok(previous) => follow_key_order(value, &previous),
+        Err(_) => value,
+    };
+    try_encode(value, Some(options))
+}
+
+/// The layout options of a TOON text.
+///
+/// These are the indentation, delimiter and its marking, key folding, typed
+/// headers, aligned columns, bare empty cells, and directive. Options the
+/// text gives no evidence for are left unset.
+#[must_use]
+pub fn infer_layout(previous: &str) -> EncodeOptions {
+    let mut layout = EncodeOptions::default();
+    let mut lines = previous.lines().filter(|line| !line.trim().is_empty());
+    let mut first = lines.next();
+    if let Some(Ok(Some(directive))) = first.map(Directive::parse) {
+        layout.emit_directive = Some(true);
+        layout.delimiter = directive.delimiter;
+        layout.indent = directive.indent;
+        first = lines.next();
+    }
+
+    let mut min_indent = None;
+    let mut tabular_depth = None;
+    for line in first.into_iter().chain(lines) {
+        let content = line.trim_start_matches(' ');
+        let depth = line.len() - content.len();
+        if depth > 0 {
+            min_indent = Some(min_indent.map_or(depth, |min: usize| min.min(depth)));
+        }
+        if tabular_depth.is_some_and(|header_depth| depth > header_depth) {
+            let delimiter = layout.delimiter.unwrap_or(DEFAULT_DELIMITER);
+            if padded_before_delimiter(content, delimiter) {
+                layout.align_columns = Some(true);
+            }
+            let cells = parse_delimited_values(content, delimiter);
+            if cells.len() > 1 && cells.iter().any(String::is_empty) {
+                layout.empty_string = Some(EmptyStringStyle::Bare);
+            }
+            continue;
+        }
+        tabular_depth = None;
+
+        let content = content.strip_prefix(LIST_ITEM_PREFIX).unwrap_or(content);
+        if let Ok(Some(parsed)) = parse_header(content) {
+            if parsed.header.delimiter != DEFAULT_DELIMITER || layout.delimiter.is_none() {
+                layout.delimiter.get_or_insert(parsed.header.delimiter);
+            }
+            if parsed.header.delimiter == COMMA && marks_comma(content) {
+                layout.always_mark_delimiter = Some(true);
+            }
+            if let Some(fields) = &parsed.header.fields {
+                if fields.iter().any(|field| field.column_type.is_some()) {
+                    layout.typed_headers = Some(true);
+                }
+                if parsed.inline_values.is_none() {
+                    tabular_depth = Some(depth);
+                }
+            }
+        }
+        if is_folded_key(content) {
+            layout.key_folding = Some(KeyFoldingMode::Safe);
+        }
+    }
+    layout.indent = layout.indent.or(min_indent);
+    layout
+}
+
+/// Whether an unquoted delimiter in a tabular row follows a space.
+fn padded_before_delimiter(row: &str, delimiter: char) -> bool {
+    let mut in_quotes = false;
+    let mut previous = None;
+    for c in row.chars() {
+        if c == DOUBLE_QUOTE {
+            in_quotes = !in_quotes;
+        } else if c == delimiter && !in_quotes && previous == Some(' ') {
+            return true;
+        }
+        previous = Some(c);
+    }
+    false
+}
+
+/// Whether an array header writes its comma delimiter out (`[2,]`).
+fn marks_comma(header: &str) -> bool {
+    find_unquoted_char(header, OPEN_BRACKET, 0)
+        .and_then(|open| find_unquoted_char(header, CLOSE_BRACKET, open))
+        .is_some_and(|close| header[..close].ends_with(COMMA))
+}
+
+/// Whether a line starts with an unquoted dotted key (`a.b: 1` or
+/// `a.b[2]: ...`).
+fn is_folded_key(content: &str) -> bool {
+    if content.starts_with(DOUBLE_QUOTE) {
+        return false;
+    }
+    let end = content.find([':', '[']).unwrap_or(content.len());
+    let key = &content[..end];
+    end < content.len() && key.contains('.') && !key.contains(' ')
+}
+
+/// Order the keys of each object in `value` as in the object at the same
+/// place in `previous`; keys `previous` lacks follow in their own order.
+/// Array items are matched by index, and items past the end of the previous
+/// array follow its last item.
+fn follow_key_order(value: JsonValue, previous: &JsonValue) -> JsonValue {
+    match (value, previous) {
+        (JsonValue::Object(entries), JsonValue::Object(old)) => {
+            let mut entries: Vec<(String, JsonValue)> = entries
+                .into_iter()
+                .map(|(key, value)| {
+                    let value = match old.iter().find(|(old_key, _)| *old_key == key) {
+                        Some((_, old_value)) => follow_key_order(value, old_value),
+                        None => value,
+                    };
+                    (key, value)
+                })
+                .collect();
+            entries.sort_by_key(|(key, _)| {
+                old.iter()
+                    .position(|(old_key, _)| old_key == key)
+                    .unwrap_or(old.len())
+            });
+            JsonValue::Object(entries)
+        }
+        (JsonValue::Array(items), JsonValue::Array(old)) if !old.is_empty() => JsonValue::Array(
+            items
+                .into_iter()
+                .enumerate()
+                .map(|(index, item)| follow_key_order(item, &old[index.min(old.len() - 1)]))
+                .collect(),
+        ),
+        (value, _) => value,
+    }
+}
@@ -0,0 +1,284 @@
+//! Advice on output that language models tend to misread.
+//!
+//! Valid TOON can still be hard for a model to follow. [`lint_for_llm`]
+//! walks a value as it would be encoded with the given options and reports
+//! constructs that are known to cause trouble, each with the option change
+//! that avoids it:
+//!
+//! - very long unquoted strings, whose end a model loses track of
+//! - chains of single-key objects that key folding would write as one line
+//! - primitive arrays long enough to make a single unwieldy line
+//! - arrays whose strings keep colliding with the delimiter, so most cells
+//!   end up quoted
+//!
+//! The pass is advisory and opt-in: encoding never runs it, and nothing it
+//! reports is an error.
+
+use std::fmt;
+
+use crate::options::{
+    EncodeOptions, KeyFoldingMode, LongStringPolicy, PathSegment, ResolvedEncodeOptions,
+    format_path, resolve_encode_options,
+};
+use crate::shared::validation::{is_identifier_segment, is_safe_unquoted};
+use crate::{JsonValue, StringOrNumberOrBoolOrNull};
+
+/// Strings longer than this many characters are reported when unquoted.
+pub const LONG_STRING_CHARS: usize = 500;
+/// Single-key chains with at least this many keys are reported.
+pub const FOLDABLE_CHAIN_KEYS: usize = 3;
+/// Inline primitive arrays with more items than this are reported.
+pub const INLINE_ARRAY_ITEMS: usize = 100;
+/// Arrays with at least this many strings are checked for delimiter
+/// collisions.
+const COLLISION_MIN_STRINGS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdviceKind {
+    LongUnquotedString,
+    FoldableKeyChain,
+    HugeInlineArray,
+    DelimiterCollisions,
+}
+
+impl AdviceKind {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::LongUnquotedString => "long unquoted string",
+            Self::FoldableKeyChain => "foldable key chain",
+            Self::HugeInlineArray => "huge inline array",
+            Self::DelimiterCollisions => "delimiter collisions",
+        }
+    }
+}
+
+/// One construct worth changing, and how.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Advice {
+    /// Path to the value (e.g. `users[1].bio`); empty for the root.
+    pub path: String,
+    pub kind: AdviceKind,
+    /// What was found.
+    pub message: String,
+    /// The option change that avoids it.
+    pub suggestion: String,
+}
+
+impl fmt::Display for Advice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "(root)"
+        } else {
+            &self.path
+        };
+        write!(
+            f,
+            "{path}: {} ({}); {}",
+            self.kind.as_str(),
+            self.message,
+            self.suggestion
+        )
+    }
+}
+
+/// Report the constructs in `value` that are hard for a model to read when
+/// encoded with `options`, in document order.
+#[must_use]
+pub fn lint_for_llm(value: &JsonValue, options: Option<EncodeOptions>) -> Vec<Advice> {
+    let mut linter = Linter {
+        options: resolve_encode_options(options),
+        path: Vec::new(),
+        advice: Vec::new(),
+    };
+    linter.value(value);
+    linter.advice
+}
+
+struct Linter {
+    options: ResolvedEncodeOptions,
+    path: Vec<PathSegment>,
+    advice: Vec<Advice>,
+}
+
+impl Linter {
+    fn report(&mut self, kind: AdviceKind, message: String, suggestion: String) {
+        self.advice.push(Advice {
+            path: format_path(&self.path),
+            kind,
+            message,
+            suggestion,
+        });
+    }
+
+    fn value(&mut self, value: &JsonValue) {
+        match value {
+            JsonValue::Primitive(StringOrNumberOrBoolOrNull::String(text)) => self.string(text),
+            JsonValue::Primitive(_) => {}
+            JsonValue::Array(items) => self.array(items),
+            JsonValue::Object(entries) => self.object(entries),
+            #[cfg(feature = "raw")]
+            JsonValue::Raw(_) => {}
+        }
+    }
+
+    fn string(&mut self, text: &str) {
+        let chars = text.chars().count();
+        if chars > LONG_STRING_CHARS
+            && self.options.long_string == LongStringPolicy::KeepInline
+            && is_safe_unquoted(text, self.options.delimiter)
+        {
+            self.report(
+                AdviceKind::LongUnquotedString,
+                format!("{chars} characters"),
+                format!(
+                    "set long_string: LongStringPolicy::ExternalRef {{ max: {LONG_STRING_CHARS} }} \
+                     to move it out of line, or Truncate to shorten it"
+                ),
+            );
+        }
+    }
+
+    fn object(&mut self, entries: &[(String, JsonValue)]) {
+        for (key, value) in entries {
+            self.path.push(PathSegment::Key(key.clone()));
+            let (keys, end) = single_key_chain(key, value);
+            if keys.len() >= FOLDABLE_CHAIN_KEYS && self.options.key_folding == KeyFoldingMode::Off
+            {
+                self.report(
+                    AdviceKind::FoldableKeyChain,
+                    format!("{} nested single-key objects", keys.len()),
+                    format!(
+                        "set key_folding: KeyFoldingMode::Safe (--key-folding safe) to write `{}` on one line",
+                        keys.join(".")
+                    ),
+                );
+            }
+            // Continue from the end of the chain so it is reported once.
+            for key in &keys[1..] {
+                self.path.push(PathSegment::Key((*key).to_string()));
+            }
+            self.value(end);
+            for _ in 1..keys.len() {
+                self.path.pop();
+            }
+            self.path.pop();
+        }
+    }
+
+    fn array(&mut self, items: &[JsonValue]) {
+        let primitives = items
+            .iter()
+            .all(|item| matches!(item, JsonValue::Primitive(_)));
+        if primitives && items.len() > INLINE_ARRAY_ITEMS {
+            let message = format!("{} items on one line", items.len());
+            let suggestion = self.inline_array_suggestion(items);
+            self.report(AdviceKind::HugeInlineArray, message, suggestion);
+        }
+        self.delimiter_collisions(items);
+        for (index, item) in items.iter().enumerate() {
+            self.path.push(PathSegment::Index(index));
+            self.value(item);
+            self.path.pop();
+        }
+    }
+
+    fn inline_array_suggestion(&self, items: &[JsonValue]) -> String {
+        let nulls = items
+            .iter()
+            .filter(|item| matches!(item, JsonValue::Primitive(StringOrNumberOrBoolOrNull::Null)))
+            .count();
+        if self.options.sparse_threshold.is_none() && nulls * 2 >= items.len() {
+            return "set sparse_threshold: Some(0.5) (--sparse-threshold 0.5) to write only the non-null items"
+                .to_string();
+        }
+        let repeats = items.windows(2).filter(|pair| pair[0] == pair[1]).count();
+        if self.options.run_length.is_none() && repeats * 2 >= items.len() {
+            return "set run_length: Some(3) (--run-length) to write repeated runs as value*count"
+                .to_string();
+        }
+        "split the value with paginate::split_by_token_budget, or move the array into a tabular column"
+            .to_string()
+    }
+
+    /// Strings in `items` (or in its rows, for an array of objects) that
+    /// the delimiter forces into quotes.
+    fn delimiter_collisions(&mut self, items: &[JsonValue]) {
+        let mut strings = Vec::new();
+        for item in items {
+            match item {
+                JsonValue::Primitive(StringOrNumberOrBoolOrNull::String(text)) => {
+                    strings.push(text.as_str());
+                }
+                JsonValue::Object(entries) => {
+                    strings.extend(entries.iter().filter_map(|(_, value)| match value {
+                        JsonValue::Primitive(StringOrNumberOrBoolOrNull::String(text)) => {
+                            Some(text.as_str())
+                        }
+                        _ => None,
+                    }));
+                }
+                _ => {}
+            }
+        }
+        if strings.len() < COLLISION_MIN_STRINGS {
+            return;
+        }
+        let collisions = |delimiter: char| {
+            strings
+                .iter()
+                .filter(|text| text.contains(delimiter))
+                .count()
+        };
+        let current = collisions(self.options.delimiter);
+        if current * 4 < strings.len() {
+            return;
+        }
+        let (best, best_count) = [',', '\t', '|']
+            .into_iter()
+            .map(|delimiter| (delimiter, collisions(delimiter)))
+            .min_by_key(|(_, count)| *count)
+            .unwrap_or((self.options.delimiter, current));
+        if best_count >= current {
+            return;
+        }
+        let name = match best {
+            '\t' => "tab",
+            '|' => "pipe",
+            _ => "comma",
+        };
+        self.report(
+            AdviceKind::DelimiterCollisions,
+            format!(
+                "{current} of {} strings contain {:?} and must be quoted",
+                strings.len(),
+                self.options.delimiter
+            ),
+            format!(
+                "set delimiter: Some({best:?}) (--delimiter {name}), which {best_count} contain"
+            ),
+        );
+    }
+}
+
+/// The keys of the chain of single-key objects starting at `key: value`,
+/// and the value at its end. A chain stops at a key that could not be
+/// folded safely.
+fn single_key_chain<'a>(key: &'a str, value: &'a JsonValue) -> (Vec<&'a str>, &'a JsonValue) {
+    let mut keys = vec![key];
+    let mut end = value;
+    if !is_identifier_segment(key) {
+        return (keys, end);
+    }
+    while let JsonValue::Object(entries) = end {
+        let [(next, inner)] = entries.as_slice() else {
+            break;
+        };
+        if !is_identifier_segment(next) {
+            break;
+        }
+        keys.push(next);
+        end = inner;
+    }
+    (keys, end)
+}
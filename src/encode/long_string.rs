@@ -0,0 +1,100 @@
+use crate::encode::extractions::{Extractions, Trim};
+use crate::options::{LongStringPolicy, PathSegment};
+use crate::{JsonValue, StringOrNumberOrBoolOrNull};
+
+/// Prefix of references written by [`LongStringPolicy::ExternalRef`].
+pub const BLOB_REFERENCE_PREFIX: &str = "@blob_";
+
+/// A string moved out of the document by [`LongStringPolicy::ExternalRef`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractedBlob {
+    /// The reference written in its place (`@blob_1`).
+    pub reference: String,
+    pub text: String,
+}
+
+/// Apply `policy` to every string value in `value`, recording extracted and
+/// truncated strings in `extractions` in document order.
+pub fn apply_long_string_policy(
+    value: JsonValue,
+    policy: &LongStringPolicy,
+    extractions: &mut Extractions,
+) -> JsonValue {
+    shorten_value(value, policy, &mut Vec::new(), extractions)
+}
+
+fn shorten_value(
+    value: JsonValue,
+    policy: &LongStringPolicy,
+    path: &mut Vec<PathSegment>,
+    extractions: &mut Extractions,
+) -> JsonValue {
+    match value {
+        JsonValue::Object(entries) => JsonValue::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| {
+                    path.push(PathSegment::Key(key.clone()));
+                    let value = shorten_value(value, policy, path, extractions);
+                    path.pop();
+                    (key, value)
+                })
+                .collect(),
+        ),
+        JsonValue::Array(items) => JsonValue::Array(
+            items
+                .into_iter()
+                .enumerate()
+                .map(|(index, item)| {
+                    path.push(PathSegment::Index(index));
+                    let item = shorten_value(item, policy, path, extractions);
+                    path.pop();
+                    item
+                })
+                .collect(),
+        ),
+        JsonValue::Primitive(StringOrNumberOrBoolOrNull::String(text)) => JsonValue::Primitive(
+            StringOrNumberOrBoolOrNull::String(shorten(text, policy, path, extractions)),
+        ),
+        primitive @ JsonValue::Primitive(_) => primitive,
+        #[cfg(feature = "raw")]
+        raw @ JsonValue::Raw(_) => raw,
+    }
+}
+
+fn shorten(
+    text: String,
+    policy: &LongStringPolicy,
+    path: &[PathSegment],
+    extractions: &mut Extractions,
+) -> String {
+    match policy {
+        LongStringPolicy::KeepInline => text,
+        LongStringPolicy::Truncate { max, suffix } => match text.char_indices().nth(*max) {
+            Some((cut, _)) => {
+                let truncated = format!("{}{suffix}", &text[..cut]);
+                extractions.trims.push(Trim {
+                    path: path.to_vec(),
+                    original: text,
+                });
+                truncated
+            }
+            None => text,
+        },
+        LongStringPolicy::ExternalRef { max } => {
+            if text.chars().nth(*max).is_none() {
+                return text;
+            }
+            let blobs = &mut extractions.blobs;
+            if let Some(blob) = blobs.iter().find(|blob| blob.text == text) {
+                return blob.reference.clone();
+            }
+            let reference = format!("{BLOB_REFERENCE_PREFIX}{}", blobs.len() + 1);
+            blobs.push(ExtractedBlob {
+                reference: reference.clone(),
+                text,
+            });
+            reference
+        }
+    }
+}
@@ -1,38 +1,396 @@
+pub mod columnar;
+pub mod columns;
+#[cfg_attr(not(feature = "internals"), doc(hidden))]
 pub mod encoders;
+pub mod extractions;
 pub mod folding;
+pub mod key_order;
+pub mod like;
+pub mod lint;
+pub mod long_string;
 pub mod normalize;
+#[cfg_attr(not(feature = "internals"), doc(hidden))]
 pub mod primitives;
 pub mod replacer;
+pub mod row_filter;
+pub mod rows;
+#[cfg(feature = "serde")]
+pub mod serde;
+pub mod writer;
 
 #[cfg(feature = "async-stream")]
 pub mod async_encode;
 
+pub use like::{encode_like, try_encode_like};
+#[cfg(feature = "serde")]
+pub use serde::{to_toon_lines, to_toon_string};
+pub use writer::ToonWriter;
+
 #[cfg(feature = "async-stream")]
 pub use async_encode::{
     AsyncEncodeEventStream, AsyncEncodeStream, encode_async, encode_events_async,
-    encode_lines_async,
+    encode_lines_async, try_encode_async, try_encode_lines_async,
 };
 
-use crate::encode::normalize::normalize_json_value;
-use crate::encode::replacer::apply_replacer;
-use crate::options::{EncodeOptions, resolve_encode_options};
-use crate::shared::validation::is_valid_unquoted_key;
-use crate::{JsonStreamEvent, JsonValue};
+use std::collections::HashSet;
+
+use crate::anchors::emit_anchors;
+use crate::codec::Encoder;
+use crate::encode::columnar::to_columnar;
+use crate::encode::columns::{apply_column_order, apply_column_projection};
+use crate::encode::extractions::Extractions;
+use crate::encode::key_order::apply_key_order;
+use crate::encode::long_string::apply_long_string_policy;
+use crate::encode::normalize::{NormalizeOptions, normalize_number_lexemes, normalize_with};
+use crate::encode::replacer::apply_replacer_with_report;
+use crate::encode::row_filter::apply_row_filter;
+use crate::error::{Result, ToonError};
+use crate::options::{
+    EncodeOptions, LongStringPolicy, PathSegment, ResolvedEncodeOptions, SerdeKeyOrder,
+    format_path, resolve_encode_options,
+};
+use crate::schema::Schema;
+use crate::shared::directive::Directive;
+use crate::shared::validation::{is_invisible_char, is_valid_unquoted_key};
+use crate::{JsonPrimitiveRef, JsonStreamEvent, JsonValue, JsonValueRef};
 
+/// Encode a JSON value into a TOON string.
+///
+/// # Panics
+///
+/// Panics if the value cannot be encoded. Use [`try_encode`] to handle
+/// encode errors without panicking.
 pub fn encode(input: impl Into<JsonValue>, options: Option<EncodeOptions>) -> String {
-    let lines = encode_lines(input, options);
-    lines.join("\n")
+    try_encode(input, options).unwrap_or_else(|err| panic!("{err}"))
 }
 
+/// Encode a JSON value into TOON lines.
+///
+/// # Panics
+///
+/// Panics if the value cannot be encoded. Use [`try_encode_lines`] to handle
+/// encode errors without panicking.
 pub fn encode_lines(input: impl Into<JsonValue>, options: Option<EncodeOptions>) -> Vec<String> {
+    try_encode_lines(input, options).unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// Encode a JSON value into a TOON string.
+///
+/// # Errors
+///
+/// Returns [`ToonError::Encode`](crate::error::ToonError::Encode) with the
+/// path of the offending value if it cannot be encoded.
+pub fn try_encode(input: impl Into<JsonValue>, options: Option<EncodeOptions>) -> Result<String> {
+    let lines = try_encode_lines(input, options)?;
+    Ok(lines.join("\n"))
+}
+
+/// Encode a JSON value into TOON lines.
+///
+/// # Errors
+///
+/// Returns [`ToonError::Encode`](crate::error::ToonError::Encode) with the
+/// path of the offending value if it cannot be encoded.
+pub fn try_encode_lines(
+    input: impl Into<JsonValue>,
+    options: Option<EncodeOptions>,
+) -> Result<Vec<String>> {
+    Encoder::new(options).try_encode_lines(input)
+}
+
+/// Encode a borrowed `serde_json::Value` into a TOON string.
+///
+/// The output is the same as [`encode`] gives for the value, but the value
+/// is not converted first: its keys and strings are written from where they
+/// are. Options that rewrite the document (a replacer, key order, row
+/// filter, column projection or order, long string policy, columnar layout)
+/// still work on a converted copy.
+///
+/// # Panics
+///
+/// Panics if the value cannot be encoded. Use [`try_encode_serde`] to handle
+/// encode errors without panicking.
+#[must_use]
+pub fn encode_serde(input: &serde_json::Value, options: Option<EncodeOptions>) -> String {
+    try_encode_serde(input, options).unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// Encode a borrowed `serde_json::Value` into a TOON string (see
+/// [`encode_serde`]).
+///
+/// # Errors
+///
+/// Returns [`ToonError::Encode`](crate::error::ToonError::Encode) with the
+/// path of the offending value if it cannot be encoded.
+pub fn try_encode_serde(
+    input: &serde_json::Value,
+    options: Option<EncodeOptions>,
+) -> Result<String> {
+    let resolved = resolve_encode_options(options);
+    let lines = if rewrites_document(&resolved) {
+        encode_document(&prepare_value(input.into(), &resolved), &resolved)?
+    } else {
+        encode_document_ref(&input.into(), &resolved)?
+    };
+    Ok(lines.join("\n"))
+}
+
+/// Encode a JSON value into a TOON string, also returning what the replacer
+/// and long string policy took out of the document.
+///
+/// # Panics
+///
+/// Panics if the value cannot be encoded. Use [`try_encode_with_extractions`]
+/// to handle encode errors without panicking.
+pub fn encode_with_extractions(
+    input: impl Into<JsonValue>,
+    options: Option<EncodeOptions>,
+) -> (String, Extractions) {
+    try_encode_with_extractions(input, options).unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// Encode a JSON value into a TOON string, also returning what the replacer
+/// and long string policy took out of the document.
+///
+/// # Errors
+///
+/// Returns [`ToonError::Encode`](crate::error::ToonError::Encode) with the
+/// path of the offending value if it cannot be encoded.
+pub fn try_encode_with_extractions(
+    input: impl Into<JsonValue>,
+    options: Option<EncodeOptions>,
+) -> Result<(String, Extractions)> {
     let resolved = resolve_encode_options(options);
-    let normalized = normalize_json_value(input.into());
+    let mut extractions = Extractions::default();
+    let prepared = prepare_value_with_report(input.into(), &resolved, Some(&mut extractions));
+    let lines = encode_document(&prepared, &resolved)?;
+    Ok((lines.join("\n"), extractions))
+}
+
+/// Encode a JSON value into a TOON string after checking it against
+/// `schema`.
+///
+/// # Panics
+///
+/// Panics if the value does not match the schema or cannot be encoded. Use
+/// [`try_encode_with_schema`] for a fallible version that returns `Result`.
+#[must_use]
+pub fn encode_with_schema(
+    input: impl Into<JsonValue>,
+    schema: &Schema,
+    options: Option<EncodeOptions>,
+) -> String {
+    try_encode_with_schema(input, schema, options).unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// Encode a JSON value into a TOON string after checking it against
+/// `schema`.
+///
+/// Missing required fields, type mismatches, and unknown keys are caught
+/// before anything is written. To encode despite violations and only report
+/// them, use [`Schema::validate`] and [`try_encode`].
+///
+/// # Errors
+///
+/// Returns [`ToonError::Encode`](crate::error::ToonError::Encode) at the
+/// first violation, listing all of them, or if the value cannot be encoded.
+pub fn try_encode_with_schema(
+    input: impl Into<JsonValue>,
+    schema: &Schema,
+    options: Option<EncodeOptions>,
+) -> Result<String> {
+    let input = input.into();
+    let violations = schema.validate(&input);
+    if let Some(first) = violations.first() {
+        let listed = violations
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("; ");
+        let count = violations.len();
+        let noun = if count == 1 {
+            "violation"
+        } else {
+            "violations"
+        };
+        return Err(ToonError::encode(
+            format_path(&first.path),
+            format!("{count} schema {noun}: {listed}"),
+        ));
+    }
+    try_encode(input, options)
+}
+
+/// Encode a prepared value, applying the line-level anchor pass and starting
+/// with the `#%toon` directive line when requested.
+pub(crate) fn encode_document(
+    value: &JsonValue,
+    resolved: &ResolvedEncodeOptions,
+) -> Result<Vec<String>> {
+    encode_document_ref(&value.into(), resolved)
+}
+
+fn encode_document_ref(
+    value: &JsonValueRef<'_>,
+    resolved: &ResolvedEncodeOptions,
+) -> Result<Vec<String>> {
+    if resolved.reject_invisible {
+        reject_invisible(value, &mut Vec::new())?;
+    }
+    if resolved.nfc {
+        reject_merged_keys(value, &mut Vec::new())?;
+    }
+    let mut lines = encoders::encode_value_ref(value, resolved)?;
+    if resolved.anchors {
+        lines = emit_anchors(lines, resolved.indent);
+    }
+    if resolved.emit_directive {
+        let directive = Directive::new(resolved.delimiter, resolved.indent);
+        lines.insert(0, directive.to_line());
+    }
+    Ok(lines)
+}
+
+/// Fail on the first key or string holding an invisible character.
+fn reject_invisible(value: &JsonValueRef<'_>, path: &mut Vec<PathSegment>) -> Result<()> {
+    let invisible = |text: &str, what: &str, path: &[PathSegment]| {
+        text.chars()
+            .find(|&ch| is_invisible_char(ch))
+            .map_or(Ok(()), |ch| {
+                Err(ToonError::encode(
+                    format_path(path),
+                    format!(
+                        "{what} contains invisible character U+{:04X}",
+                        u32::from(ch)
+                    ),
+                ))
+            })
+    };
+    match value {
+        JsonValueRef::Primitive(JsonPrimitiveRef::String(text)) => invisible(text, "String", path),
+        JsonValueRef::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                path.push(PathSegment::Index(index));
+                reject_invisible(item, path)?;
+                path.pop();
+            }
+            Ok(())
+        }
+        JsonValueRef::Object(fields) => {
+            for (key, field) in fields {
+                path.push(PathSegment::Key((*key).to_string()));
+                invisible(key, "Key", path)?;
+                reject_invisible(field, path)?;
+                path.pop();
+            }
+            Ok(())
+        }
+        JsonValueRef::Primitive(_) => Ok(()),
+        #[cfg(feature = "raw")]
+        JsonValueRef::Raw(_) => Ok(()),
+    }
+}
+
+/// Fail on an object with a key twice. After the NFC pass these are keys
+/// that differ only in normalization, which would otherwise be written as
+/// duplicates and read back as one.
+fn reject_merged_keys(value: &JsonValueRef<'_>, path: &mut Vec<PathSegment>) -> Result<()> {
+    match value {
+        JsonValueRef::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                path.push(PathSegment::Index(index));
+                reject_merged_keys(item, path)?;
+                path.pop();
+            }
+            Ok(())
+        }
+        JsonValueRef::Object(fields) => {
+            let mut seen = HashSet::with_capacity(fields.len());
+            for (key, field) in fields {
+                path.push(PathSegment::Key((*key).to_string()));
+                if !seen.insert(*key) {
+                    return Err(ToonError::encode(
+                        format_path(path),
+                        "Key appears more than once in its object after NFC normalization",
+                    ));
+                }
+                reject_merged_keys(field, path)?;
+                path.pop();
+            }
+            Ok(())
+        }
+        JsonValueRef::Primitive(_) => Ok(()),
+        #[cfg(feature = "raw")]
+        JsonValueRef::Raw(_) => Ok(()),
+    }
+}
+
+/// Normalize an input value and apply the replacer and layout options.
+pub(crate) fn prepare_value(input: JsonValue, resolved: &ResolvedEncodeOptions) -> JsonValue {
+    prepare_value_with_report(input, resolved, None)
+}
+
+/// Whether preparing a value does more than normalize it. A value converted
+/// from serde is already normalized, so without these passes it can be
+/// encoded as it is.
+fn rewrites_document(resolved: &ResolvedEncodeOptions) -> bool {
+    resolved.serde_key_order != SerdeKeyOrder::AsIs
+        || resolved.row_filter.is_some()
+        || resolved.columns.is_some()
+        || resolved.replacer.is_some()
+        || resolved.long_string != LongStringPolicy::KeepInline
+        || resolved.column_order.is_some()
+        || resolved.columnar
+        || resolved.nfc
+}
+
+fn prepare_value_with_report(
+    input: JsonValue,
+    resolved: &ResolvedEncodeOptions,
+    mut extractions: Option<&mut Extractions>,
+) -> JsonValue {
+    let normalize = NormalizeOptions {
+        nfc: resolved.nfc,
+        ..NormalizeOptions::default()
+    };
+    let normalized = apply_key_order(normalize_with(input, normalize), &resolved.serde_key_order);
+    let normalized = match &resolved.row_filter {
+        Some(filter) => apply_row_filter(normalized, filter),
+        None => normalized,
+    };
+    let normalized = match &resolved.columns {
+        Some(columns) => apply_column_projection(normalized, columns),
+        None => normalized,
+    };
     let replaced = if let Some(replacer) = &resolved.replacer {
-        apply_replacer(&normalized, replacer)
+        let redactions = extractions
+            .as_deref_mut()
+            .map(|extractions| &mut extractions.redactions);
+        apply_replacer_with_report(&normalized, replacer, redactions)
     } else {
         normalized
     };
-    encoders::encode_json_value(&replaced, &resolved)
+    let replaced = if resolved.lossless_numbers {
+        replaced
+    } else {
+        normalize_number_lexemes(replaced)
+    };
+    let shortened = if resolved.long_string == LongStringPolicy::KeepInline {
+        replaced
+    } else {
+        let mut discarded = Extractions::default();
+        let extractions = extractions.unwrap_or(&mut discarded);
+        apply_long_string_policy(replaced, &resolved.long_string, extractions)
+    };
+    let ordered = match &resolved.column_order {
+        Some(order) => apply_column_order(shortened, order),
+        None => shortened,
+    };
+    if resolved.columnar {
+        to_columnar(ordered)
+    } else {
+        ordered
+    }
 }
 
 /// Encode a JSON value into a stream of events.
@@ -45,19 +403,14 @@ pub fn encode_stream_events(
     options: Option<EncodeOptions>,
 ) -> Vec<JsonStreamEvent> {
     let resolved = resolve_encode_options(options);
-    let normalized = normalize_json_value(input.into());
-    let replaced = if let Some(replacer) = &resolved.replacer {
-        apply_replacer(&normalized, replacer)
-    } else {
-        normalized
-    };
+    let prepared = prepare_value(input.into(), &resolved);
 
     let mut events = Vec::new();
-    emit_events(&replaced, &mut events);
+    emit_events(&prepared, &mut events);
     events
 }
 
-fn emit_events(value: &JsonValue, events: &mut Vec<JsonStreamEvent>) {
+pub(crate) fn emit_events(value: &JsonValue, events: &mut Vec<JsonStreamEvent>) {
     match value {
         JsonValue::Primitive(p) => {
             events.push(JsonStreamEvent::Primitive { value: p.clone() });
@@ -80,5 +433,7 @@ fn emit_events(value: &JsonValue, events: &mut Vec<JsonStreamEvent>) {
             }
             events.push(JsonStreamEvent::EndObject);
         }
+        #[cfg(feature = "raw")]
+        JsonValue::Raw(text) => events.push(JsonStreamEvent::Raw { text: text.clone() }),
     }
 }
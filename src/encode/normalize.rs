@@ -1,36 +1,165 @@
+use unicode_normalization::{UnicodeNormalization, is_nfc};
+
+use crate::shared::literal_utils::is_numeric_literal;
 use crate::{JsonArray, JsonObject, JsonPrimitive, JsonValue, StringOrNumberOrBoolOrNull};
 
+/// How [`normalize_with`] rewrites a value. The default is the pass the
+/// encoder makes itself.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NormalizeOptions {
+    /// What `NaN` and the infinities become.
+    pub non_finite: NonFiniteNumbers,
+    /// Keep `-0` instead of making it `0`. TOON has no negative zero, so
+    /// the encoder writes `0` either way; this is for values passed on as
+    /// JSON.
+    pub keep_negative_zero: bool,
+    /// Put strings and keys in Unicode Normalization Form C, so text that
+    /// looks the same is also written the same. Keys of one object that
+    /// differ only in normalization become duplicates;
+    /// [`scan_nfc_key_collisions`](crate::lossy::scan_nfc_key_collisions)
+    /// reports them beforehand.
+    pub nfc: bool,
+    /// What to do with strings that spell a number.
+    pub numeric_strings: NumericStrings,
+}
+
+/// What [`normalize_with`] turns `NaN`, `Infinity`, and `-Infinity` into.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NonFiniteNumbers {
+    /// `null`, as `JSON.stringify` writes them.
+    #[default]
+    Null,
+    /// The strings `"NaN"`, `"Infinity"`, and `"-Infinity"`.
+    String,
+}
+
+/// What [`normalize_with`] does with a string that is exactly a number
+/// literal, such as `"42"` or `"-1.5e3"`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NumericStrings {
+    /// Leave it a string, which the encoder quotes.
+    #[default]
+    Keep,
+    /// Replace it with the number it spells. Strings the decoder would not
+    /// read as a number unquoted, such as `"007"` or `" 1"`, and numbers
+    /// too large to hold stay strings.
+    Parse,
+}
+
+/// Normalize a value as the encoder does before writing it.
+#[must_use]
 pub fn normalize_json_value(value: JsonValue) -> JsonValue {
+    normalize_with(value, NormalizeOptions::default())
+}
+
+/// Normalize a value with the given options, independently of encoding.
+///
+/// Normalizing is the first thing encoding does, and a normalized value
+/// comes through it unchanged, so a value can be normalized once, kept,
+/// and encoded any number of times with different layout options.
+#[must_use]
+pub fn normalize_with(value: JsonValue, options: NormalizeOptions) -> JsonValue {
+    match value {
+        JsonValue::Primitive(primitive) => {
+            JsonValue::Primitive(normalize_primitive_with(primitive, options))
+        }
+        JsonValue::Array(items) => JsonValue::Array(
+            items
+                .into_iter()
+                .map(|item| normalize_with(item, options))
+                .collect(),
+        ),
+        JsonValue::Object(entries) => JsonValue::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| {
+                    let key = if options.nfc { to_nfc(key) } else { key };
+                    (key, normalize_with(value, options))
+                })
+                .collect(),
+        ),
+        #[cfg(feature = "raw")]
+        JsonValue::Raw(text) => JsonValue::Raw(text),
+    }
+}
+
+/// Replace every [`NumberLexeme`](StringOrNumberOrBoolOrNull::NumberLexeme)
+/// with the number it spells, so it is written in canonical form.
+#[must_use]
+pub fn normalize_number_lexemes(value: JsonValue) -> JsonValue {
     match value {
-        JsonValue::Primitive(primitive) => JsonValue::Primitive(normalize_primitive(primitive)),
+        JsonValue::Primitive(primitive) => JsonValue::Primitive(primitive.into_number()),
         JsonValue::Array(items) => {
-            JsonValue::Array(items.into_iter().map(normalize_json_value).collect())
+            JsonValue::Array(items.into_iter().map(normalize_number_lexemes).collect())
         }
         JsonValue::Object(entries) => JsonValue::Object(
             entries
                 .into_iter()
-                .map(|(key, value)| (key, normalize_json_value(value)))
+                .map(|(key, value)| (key, normalize_number_lexemes(value)))
                 .collect(),
         ),
+        #[cfg(feature = "raw")]
+        JsonValue::Raw(text) => JsonValue::Raw(text),
     }
 }
 
 #[must_use]
 pub fn normalize_primitive(value: JsonPrimitive) -> JsonPrimitive {
+    normalize_primitive_with(value, NormalizeOptions::default())
+}
+
+fn normalize_primitive_with(value: JsonPrimitive, options: NormalizeOptions) -> JsonPrimitive {
     match value {
         StringOrNumberOrBoolOrNull::Number(value) => {
             if !value.is_finite() {
-                StringOrNumberOrBoolOrNull::Null
-            } else if value == 0.0 {
+                match options.non_finite {
+                    NonFiniteNumbers::Null => StringOrNumberOrBoolOrNull::Null,
+                    NonFiniteNumbers::String => {
+                        let name = if value.is_nan() {
+                            "NaN"
+                        } else if value > 0.0 {
+                            "Infinity"
+                        } else {
+                            "-Infinity"
+                        };
+                        StringOrNumberOrBoolOrNull::String(name.to_string())
+                    }
+                }
+            } else if value == 0.0 && !options.keep_negative_zero {
                 StringOrNumberOrBoolOrNull::Number(0.0)
             } else {
                 StringOrNumberOrBoolOrNull::Number(value)
             }
         }
+        StringOrNumberOrBoolOrNull::String(text) => {
+            let text = if options.nfc { to_nfc(text) } else { text };
+            if options.numeric_strings == NumericStrings::Parse
+                && text.trim() == text
+                && is_numeric_literal(&text)
+            {
+                #[cfg(feature = "arbitrary-precision")]
+                let number = StringOrNumberOrBoolOrNull::from_decimal(&text);
+                #[cfg(not(feature = "arbitrary-precision"))]
+                let number = StringOrNumberOrBoolOrNull::from_number_lexeme(&text);
+                // A literal too large for `f64` stays the string it was.
+                if number.as_f64().is_some_and(f64::is_finite) {
+                    return normalize_primitive_with(number, options);
+                }
+            }
+            StringOrNumberOrBoolOrNull::String(text)
+        }
         _ => value,
     }
 }
 
+fn to_nfc(text: String) -> String {
+    if is_nfc(&text) {
+        text
+    } else {
+        text.nfc().collect()
+    }
+}
+
 #[must_use]
 pub const fn is_json_primitive(value: &JsonValue) -> bool {
     matches!(value, JsonValue::Primitive(_))
@@ -47,7 +176,7 @@ pub const fn is_json_object(value: &JsonValue) -> bool {
 }
 
 #[must_use]
-pub fn is_empty_object(value: &JsonObject) -> bool {
+pub const fn is_empty_object(value: &JsonObject) -> bool {
     value.is_empty()
 }
 
@@ -1,18 +1,30 @@
 use std::fmt::Write;
 
-use crate::JsonPrimitive;
-use crate::StringOrNumberOrBoolOrNull;
-use crate::shared::constants::{DEFAULT_DELIMITER, DOUBLE_QUOTE};
+use crate::options::{ColumnType, EmptyStringStyle};
+use crate::shared::constants::{DEFAULT_DELIMITER, DOUBLE_QUOTE, RUN_MARKER};
+use crate::shared::literal_utils::is_number_lexeme;
 use crate::shared::string_utils::escape_string;
 use crate::shared::validation::{is_safe_unquoted, is_valid_unquoted_key};
+use crate::{JsonPrimitive, JsonPrimitiveRef};
 
 #[must_use]
 pub fn encode_primitive(value: &JsonPrimitive, delimiter: char) -> String {
+    encode_primitive_ref(&value.into(), delimiter)
+}
+
+#[must_use]
+pub fn encode_primitive_ref(value: &JsonPrimitiveRef<'_>, delimiter: char) -> String {
     match value {
-        StringOrNumberOrBoolOrNull::Null => "null".to_string(),
-        StringOrNumberOrBoolOrNull::Bool(value) => value.to_string(),
-        StringOrNumberOrBoolOrNull::Number(value) => format_number(*value),
-        StringOrNumberOrBoolOrNull::String(value) => encode_string_literal(value, delimiter),
+        JsonPrimitiveRef::Null => "null".to_string(),
+        JsonPrimitiveRef::Bool(value) => value.to_string(),
+        JsonPrimitiveRef::Number(value) => format_number(*value),
+        JsonPrimitiveRef::Int(value) => value.to_string(),
+        JsonPrimitiveRef::UInt(value) => value.to_string(),
+        JsonPrimitiveRef::NumberLexeme(text) if is_number_lexeme(text) => text.to_string(),
+        JsonPrimitiveRef::NumberLexeme(text) => {
+            encode_primitive(&JsonPrimitive::from_number_lexeme(text), delimiter)
+        }
+        JsonPrimitiveRef::String(value) => encode_string_literal(value, delimiter),
     }
 }
 
@@ -32,8 +44,33 @@ pub fn encode_key(key: &str) -> String {
     format!("{DOUBLE_QUOTE}{}{DOUBLE_QUOTE}", escape_string(key))
 }
 
+/// Whether an empty string at `index` of a row of `len` values is written as
+/// an empty cell.
+///
+/// With [`EmptyStringStyle::Bare`] it is, except in a single-value row, which
+/// would read back as empty, in the last cell, where it would leave a
+/// trailing delimiter, and with a whitespace delimiter, which the decoder
+/// trims from the ends of the row. Those keep an explicit `""`.
+const fn bare_empty_cell(
+    empty_string: EmptyStringStyle,
+    len: usize,
+    index: usize,
+    delimiter: char,
+) -> bool {
+    matches!(empty_string, EmptyStringStyle::Bare)
+        && len > 1
+        && index + 1 < len
+        && !delimiter.is_whitespace()
+}
+
+/// Encode primitives as a single delimited row, with empty strings written
+/// as empty cells where [`bare_empty_cell`] allows.
 #[must_use]
-pub fn encode_and_join_primitives(values: &[JsonPrimitive], delimiter: char) -> String {
+pub fn encode_and_join_primitives(
+    values: &[JsonPrimitiveRef<'_>],
+    delimiter: char,
+    empty_string: EmptyStringStyle,
+) -> String {
     if values.is_empty() {
         return String::new();
     }
@@ -43,7 +80,82 @@ pub fn encode_and_join_primitives(values: &[JsonPrimitive], delimiter: char) ->
         if idx > 0 {
             out.push(delimiter);
         }
-        out.push_str(&encode_primitive(value, delimiter));
+        if matches!(value, JsonPrimitiveRef::String(s) if s.is_empty())
+            && bare_empty_cell(empty_string, values.len(), idx, delimiter)
+        {
+            continue;
+        }
+        out.push_str(&encode_primitive_ref(value, delimiter));
+    }
+    out
+}
+
+/// Encode primitives as the cells of a delimited row, as
+/// [`encode_and_join_primitives`] would before joining them.
+#[must_use]
+pub fn encode_primitive_cells(
+    values: &[JsonPrimitiveRef<'_>],
+    delimiter: char,
+    empty_string: EmptyStringStyle,
+) -> Vec<String> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(idx, value)| match value {
+            JsonPrimitiveRef::String(s)
+                if s.is_empty() && bare_empty_cell(empty_string, values.len(), idx, delimiter) =>
+            {
+                String::new()
+            }
+            _ => encode_primitive_ref(value, delimiter),
+        })
+        .collect()
+}
+
+/// Encode primitives as a delimited row, writing each run of at least
+/// `min_run` identical values as a single `value*count` cell.
+///
+/// Unquoted strings containing [`RUN_MARKER`] are quoted so they cannot be
+/// read back as runs.
+#[must_use]
+pub fn encode_and_join_runs(
+    values: &[JsonPrimitiveRef<'_>],
+    delimiter: char,
+    empty_string: EmptyStringStyle,
+    min_run: usize,
+) -> String {
+    let mut out = String::with_capacity(values.len() * 4);
+    let mut start = 0;
+    while start < values.len() {
+        let value = &values[start];
+        let run = values[start..]
+            .iter()
+            .take_while(|other| *other == value)
+            .count();
+        let cell = match value {
+            JsonPrimitiveRef::String(s) if s.contains(RUN_MARKER) => {
+                format!("{DOUBLE_QUOTE}{}{DOUBLE_QUOTE}", escape_string(s))
+            }
+            _ => encode_primitive_ref(value, delimiter),
+        };
+
+        if run >= min_run.max(2) {
+            if start > 0 {
+                out.push(delimiter);
+            }
+            let _ = write!(out, "{cell}{RUN_MARKER}{run}");
+        } else {
+            let is_empty = matches!(value, JsonPrimitiveRef::String(s) if s.is_empty());
+            for idx in start..start + run {
+                if idx > 0 {
+                    out.push(delimiter);
+                }
+                if !(is_empty && bare_empty_cell(empty_string, values.len(), idx, delimiter)) {
+                    out.push_str(&cell);
+                }
+            }
+        }
+        start += run;
     }
     out
 }
@@ -54,14 +166,52 @@ pub fn format_header(
     key: Option<&str>,
     fields: Option<&[String]>,
     delimiter: char,
+) -> String {
+    format_typed_header(length, key, fields, &[], delimiter, false)
+}
+
+/// Format an array header whose fields carry type annotations
+/// (`users[2]{id:int,name:str}:`). Fields without a type are left bare.
+///
+/// The default comma delimiter is marked (`[2,]`) only if `mark_delimiter`
+/// is set; other delimiters always are.
+#[must_use]
+pub fn format_typed_header(
+    length: usize,
+    key: Option<&str>,
+    fields: Option<&[String]>,
+    types: &[Option<ColumnType>],
+    delimiter: char,
+    mark_delimiter: bool,
+) -> String {
+    let key = key.map(encode_key);
+    format_header_with_encoded_key(
+        length,
+        key.as_deref(),
+        fields,
+        types,
+        delimiter,
+        mark_delimiter,
+    )
+}
+
+/// [`format_typed_header`] with `key` already encoded, such as a folded
+/// path, which is written bare.
+pub(crate) fn format_header_with_encoded_key(
+    length: usize,
+    key: Option<&str>,
+    fields: Option<&[String]>,
+    types: &[Option<ColumnType>],
+    delimiter: char,
+    mark_delimiter: bool,
 ) -> String {
     let mut header = String::new();
 
     if let Some(key) = key {
-        header.push_str(&encode_key(key));
+        header.push_str(key);
     }
 
-    if delimiter == DEFAULT_DELIMITER {
+    if delimiter == DEFAULT_DELIMITER && !mark_delimiter {
         let _ = write!(header, "[{length}]");
     } else {
         let _ = write!(header, "[{length}{delimiter}]");
@@ -74,6 +224,10 @@ pub fn format_header(
                 header.push(delimiter);
             }
             header.push_str(&encode_key(field));
+            if let Some(Some(column_type)) = types.get(idx) {
+                header.push(':');
+                header.push_str(column_type.annotation());
+            }
         }
         header.push('}');
     }
@@ -1,26 +1,234 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::encode::extractions::Redaction;
 use crate::encode::normalize::normalize_json_value;
-use crate::options::{EncodeReplacer, PathSegment};
-use crate::{JsonArray, JsonObject, JsonValue};
+use crate::error::{Result, ToonError};
+use crate::options::{EncodeReplacer, PathPattern, PathSegment};
+use crate::{JsonArray, JsonObject, JsonValue, StringOrNumberOrBoolOrNull};
+
+/// Declarative replacer rules, read from JSON or TOML with [`from_spec`].
+///
+/// ```toml
+/// [[rules]]
+/// path = "users.password"
+/// drop = true
+///
+/// [[rules]]
+/// path = "users.bio"
+/// truncate = 80
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransformSpec {
+    #[serde(default)]
+    pub rules: Vec<TransformRule>,
+}
+
+/// What to do with the values whose path matches `path`. A path names keys
+/// as they read after the keys enclosing it were renamed. Every matching
+/// rule applies, in order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct TransformRule {
+    pub path: PathPattern,
+    /// Leave the value out.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub drop: bool,
+    /// Write the value under this key instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rename: Option<String>,
+    /// Keep at most this many characters of a string.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub truncate: Option<usize>,
+    /// Round a number to the nearest multiple of this step (`0.01`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quantize: Option<f64>,
+}
+
+impl TransformSpec {
+    /// Parse a spec from JSON (text starting with `{`) or TOML.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the text is not a valid spec or a `quantize` step
+    /// is not a positive finite number.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let parsed: Self = if spec.trim_start().starts_with('{') {
+            serde_json::from_str(spec).map_err(|err| invalid_spec(&err))?
+        } else {
+            toml::from_str(spec).map_err(|err| invalid_spec(&err))?
+        };
+        if let Some(rule) = parsed.rules.iter().find(|rule| {
+            rule.quantize
+                .is_some_and(|step| !(step.is_finite() && step > 0.0))
+        }) {
+            return Err(invalid_spec(&format_args!(
+                "quantize step for {} must be a positive number",
+                rule.path
+            )));
+        }
+        Ok(parsed)
+    }
+
+    /// Compile the rules into a replacer for [`crate::EncodeOptions::replacer`].
+    #[must_use]
+    pub fn into_replacer(self) -> EncodeReplacer {
+        Arc::new(move |_key, value, path| self.replace(value, path))
+    }
+
+    fn replace(&self, value: &JsonValue, path: &[PathSegment]) -> Option<JsonValue> {
+        let mut value = value.clone();
+        // Object entries are handled by their parent below, which can rename
+        // them; the root and array items are handled here.
+        if !matches!(path.last(), Some(PathSegment::Key(_))) {
+            let rules = self.matching(path);
+            if rules.iter().any(|rule| rule.drop) {
+                return None;
+            }
+            for rule in rules {
+                rule.apply(&mut value);
+            }
+        }
+        if let JsonValue::Object(entries) = &mut value {
+            let mut child_path = path.to_vec();
+            *entries = std::mem::take(entries)
+                .into_iter()
+                .filter_map(|(mut key, mut child)| {
+                    child_path.push(PathSegment::Key(key.clone()));
+                    let rules = self.matching(&child_path);
+                    child_path.pop();
+                    if rules.iter().any(|rule| rule.drop) {
+                        return None;
+                    }
+                    for rule in rules {
+                        rule.apply(&mut child);
+                        if let Some(rename) = &rule.rename {
+                            key.clone_from(rename);
+                        }
+                    }
+                    Some((key, child))
+                })
+                .collect();
+        }
+        Some(value)
+    }
+
+    fn matching(&self, path: &[PathSegment]) -> Vec<&TransformRule> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.path.matches(path))
+            .collect()
+    }
+}
+
+impl TransformRule {
+    fn apply(&self, value: &mut JsonValue) {
+        match value {
+            JsonValue::Primitive(StringOrNumberOrBoolOrNull::String(text)) => {
+                if let Some(max) = self.truncate
+                    && let Some((end, _)) = text.char_indices().nth(max)
+                {
+                    text.truncate(end);
+                }
+            }
+            JsonValue::Primitive(StringOrNumberOrBoolOrNull::Number(number)) => {
+                if let Some(step) = self.quantize {
+                    *number = quantize(*number, step);
+                }
+            }
+            JsonValue::Primitive(
+                primitive @ (StringOrNumberOrBoolOrNull::Int(_)
+                | StringOrNumberOrBoolOrNull::UInt(_)),
+            ) => {
+                if let (Some(step), Some(number)) = (self.quantize, primitive.as_f64()) {
+                    *primitive = StringOrNumberOrBoolOrNull::from_f64(quantize(number, step));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Round `number` to a multiple of `step`. Steps like `0.01` divide by their
+/// inverse so the result is the closest float to the decimal (`3.14`, not
+/// `3.1400000000000001`).
+fn quantize(number: f64, step: f64) -> f64 {
+    let inverse = step.recip();
+    if inverse.fract() == 0.0 {
+        (number * inverse).round() / inverse
+    } else {
+        (number / step).round() * step
+    }
+}
+
+/// Compile a [`TransformSpec`] from JSON or TOML into a replacer.
+///
+/// # Errors
+///
+/// Returns an error if the spec cannot be parsed; see [`TransformSpec::parse`].
+pub fn from_spec(spec: &str) -> Result<EncodeReplacer> {
+    TransformSpec::parse(spec).map(TransformSpec::into_replacer)
+}
+
+fn invalid_spec(err: &dyn std::fmt::Display) -> ToonError {
+    ToonError::message(format!("Invalid transform spec: {err}"))
+}
 
 pub fn apply_replacer(root: &JsonValue, replacer: &EncodeReplacer) -> JsonValue {
-    let replaced_root = replacer("", root, &[]);
+    apply_replacer_with_report(root, replacer, None)
+}
+
+/// Like [`apply_replacer`], also recording each value the replacer removed or
+/// changed in `redactions` when given.
+pub fn apply_replacer_with_report(
+    root: &JsonValue,
+    replacer: &EncodeReplacer,
+    mut redactions: Option<&mut Vec<Redaction>>,
+) -> JsonValue {
+    let replaced_root = replacer("", root, &[]).map(normalize_json_value);
+    record(redactions.as_deref_mut(), &[], root, replaced_root.as_ref());
     if let Some(value) = replaced_root {
-        let normalized = normalize_json_value(value);
-        return transform_children(normalized, replacer, &[]);
+        return transform_children(value, replacer, &[], redactions);
     }
 
-    transform_children(root.clone(), replacer, &[])
+    transform_children(root.clone(), replacer, &[], redactions)
+}
+
+fn record(
+    redactions: Option<&mut Vec<Redaction>>,
+    path: &[PathSegment],
+    original: &JsonValue,
+    replacement: Option<&JsonValue>,
+) {
+    if let Some(redactions) = redactions
+        && replacement != Some(original)
+    {
+        redactions.push(Redaction {
+            path: path.to_vec(),
+            original: original.clone(),
+            replacement: replacement.cloned(),
+        });
+    }
 }
 
 fn transform_children(
     value: JsonValue,
     replacer: &EncodeReplacer,
     path: &[PathSegment],
+    redactions: Option<&mut Vec<Redaction>>,
 ) -> JsonValue {
     match value {
-        JsonValue::Object(entries) => JsonValue::Object(transform_object(entries, replacer, path)),
-        JsonValue::Array(values) => JsonValue::Array(transform_array(values, replacer, path)),
+        JsonValue::Object(entries) => {
+            JsonValue::Object(transform_object(entries, replacer, path, redactions))
+        }
+        JsonValue::Array(values) => {
+            JsonValue::Array(transform_array(values, replacer, path, redactions))
+        }
         JsonValue::Primitive(value) => JsonValue::Primitive(value),
+        #[cfg(feature = "raw")]
+        JsonValue::Raw(text) => JsonValue::Raw(text),
     }
 }
 
@@ -28,6 +236,7 @@ fn transform_object(
     entries: JsonObject,
     replacer: &EncodeReplacer,
     path: &[PathSegment],
+    mut redactions: Option<&mut Vec<Redaction>>,
 ) -> JsonObject {
     let mut result = Vec::new();
 
@@ -35,10 +244,16 @@ fn transform_object(
         let mut next_path = path.to_vec();
         next_path.push(PathSegment::Key(key.clone()));
 
-        let replacement = replacer(&key, &value, &next_path);
+        let replacement = replacer(&key, &value, &next_path).map(normalize_json_value);
+        record(
+            redactions.as_deref_mut(),
+            &next_path,
+            &value,
+            replacement.as_ref(),
+        );
         if let Some(next_value) = replacement {
-            let normalized = normalize_json_value(next_value);
-            let transformed = transform_children(normalized, replacer, &next_path);
+            let transformed =
+                transform_children(next_value, replacer, &next_path, redactions.as_deref_mut());
             result.push((key, transformed));
         }
     }
@@ -50,6 +265,7 @@ fn transform_array(
     values: JsonArray,
     replacer: &EncodeReplacer,
     path: &[PathSegment],
+    mut redactions: Option<&mut Vec<Redaction>>,
 ) -> JsonArray {
     let mut result = Vec::new();
 
@@ -58,10 +274,16 @@ fn transform_array(
         next_path.push(PathSegment::Index(idx));
 
         let key = idx.to_string();
-        let replacement = replacer(&key, &value, &next_path);
+        let replacement = replacer(&key, &value, &next_path).map(normalize_json_value);
+        record(
+            redactions.as_deref_mut(),
+            &next_path,
+            &value,
+            replacement.as_ref(),
+        );
         if let Some(next_value) = replacement {
-            let normalized = normalize_json_value(next_value);
-            let transformed = transform_children(normalized, replacer, &next_path);
+            let transformed =
+                transform_children(next_value, replacer, &next_path, redactions.as_deref_mut());
             result.push(transformed);
         }
     }
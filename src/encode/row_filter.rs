@@ -0,0 +1,49 @@
+use crate::encode::normalize::is_array_of_objects;
+use crate::options::{PathSegment, RowFilter};
+use crate::{JsonArray, JsonValue};
+
+/// Drop the rows of arrays of objects that `filter` rejects.
+///
+/// The filter sees each row's path (`users[3]` as `[Key("users"),
+/// Index(3)]`, with the index into the unfiltered array) and its entries.
+/// Rows of nested arrays are filtered too, and a kept row's own arrays are
+/// filtered before the row is written. Array lengths follow the kept rows.
+#[must_use]
+pub fn apply_row_filter(value: JsonValue, filter: &RowFilter) -> JsonValue {
+    filter_value(value, filter, &mut Vec::new())
+}
+
+fn filter_value(value: JsonValue, filter: &RowFilter, path: &mut Vec<PathSegment>) -> JsonValue {
+    match value {
+        JsonValue::Object(entries) => JsonValue::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| {
+                    path.push(PathSegment::Key(key.clone()));
+                    let value = filter_value(value, filter, path);
+                    path.pop();
+                    (key, value)
+                })
+                .collect(),
+        ),
+        JsonValue::Array(items) => {
+            let rows = is_array_of_objects(&items);
+            let mut kept = JsonArray::with_capacity(items.len());
+            for (index, item) in items.into_iter().enumerate() {
+                path.push(PathSegment::Index(index));
+                let keep = match &item {
+                    JsonValue::Object(entries) if rows => filter(path, entries),
+                    _ => true,
+                };
+                if keep {
+                    kept.push(filter_value(item, filter, path));
+                }
+                path.pop();
+            }
+            JsonValue::Array(kept)
+        }
+        JsonValue::Primitive(_) => value,
+        #[cfg(feature = "raw")]
+        JsonValue::Raw(_) => value,
+    }
+}
@@ -0,0 +1,135 @@
+//! Custom rendering of tabular rows.
+//!
+//! A [`RowEncoder`] set as `EncodeOptions::row_encoder` lays out the rows of
+//! every tabular array, for example padding cells so columns line up for
+//! review; [`AlignedRows`], which `EncodeOptions::align_columns` selects,
+//! does exactly that. The encoder still writes the header, checks lengths, and quotes
+//! and escapes each cell; the row encoder only decides how the encoded cells
+//! of a row are put on one line. Each line it returns must read back as the
+//! same cells, so it may add spaces between cells but not change them, and
+//! may not start with a space, which would read as indentation.
+//!
+//! ```
+//! use std::sync::Arc;
+//! use toon::encode::rows::RowEncoder;
+//! use toon::{EncodeOptions, encode};
+//!
+//! /// Pads every cell but the last to its column's width.
+//! struct Padded;
+//!
+//! impl RowEncoder for Padded {
+//!     fn encode_rows(&self, rows: &[Vec<String>], delimiter: char) -> Vec<String> {
+//!         let widths: Vec<usize> = (0..rows[0].len())
+//!             .map(|column| rows.iter().map(|row| row[column].len()).max().unwrap_or(0))
+//!             .collect();
+//!         rows.iter()
+//!             .map(|row| {
+//!                 let last = row.len() - 1;
+//!                 let cells: Vec<String> = row
+//!                     .iter()
+//!                     .enumerate()
+//!                     .map(|(column, cell)| match column {
+//!                         _ if column == last => cell.clone(),
+//!                         _ => format!("{cell:<width$}", width = widths[column]),
+//!                     })
+//!                     .collect();
+//!                 cells.join(&delimiter.to_string())
+//!             })
+//!             .collect()
+//!     }
+//! }
+//!
+//! let value = serde_json::json!({"users": [
+//!     {"id": 1, "name": "Ada"},
+//!     {"id": 1000, "name": "Bob"},
+//! ]});
+//! let options = EncodeOptions {
+//!     row_encoder: Some(Arc::new(Padded)),
+//!     ..EncodeOptions::default()
+//! };
+//! assert_eq!(
+//!     encode(value, Some(options)),
+//!     "users[2]{id,name}:\n  1   ,Ada\n  1000,Bob"
+//! );
+//! ```
+
+use crate::decode::parser::parse_delimited_values;
+use crate::error::{Result, ToonError};
+
+/// Lays out the rows of a tabular array.
+pub trait RowEncoder: Send + Sync {
+    /// Render `rows`, one line per row without indentation. Each row holds
+    /// its encoded cells in header order, and `delimiter` must separate
+    /// them. Lines must not start with whitespace.
+    fn encode_rows(&self, rows: &[Vec<String>], delimiter: char) -> Vec<String>;
+}
+
+/// Pads every cell but the last of each row to the width of its column, so
+/// the delimiters of a table line up. Widths count characters.
+///
+/// Decoding trims the padding, so aligned tables read back unchanged.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AlignedRows;
+
+impl RowEncoder for AlignedRows {
+    fn encode_rows(&self, rows: &[Vec<String>], delimiter: char) -> Vec<String> {
+        let columns = rows.first().map_or(0, Vec::len);
+        let widths: Vec<usize> = (0..columns)
+            .map(|column| {
+                rows.iter()
+                    .map(|row| row[column].chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        rows.iter()
+            .map(|row| {
+                let mut line = String::new();
+                for (column, cell) in row.iter().enumerate() {
+                    line.push_str(cell);
+                    if column + 1 < row.len() {
+                        let padding = widths[column] - cell.chars().count();
+                        line.extend(std::iter::repeat_n(' ', padding));
+                        line.push(delimiter);
+                    }
+                }
+                line
+            })
+            .collect()
+    }
+}
+
+/// Run `encoder` on the encoded cells of a table's rows and check that each
+/// line reads back as its row.
+pub(crate) fn render_rows(
+    encoder: &dyn RowEncoder,
+    rows: &[Vec<String>],
+    delimiter: char,
+) -> Result<Vec<String>> {
+    let lines = encoder.encode_rows(rows, delimiter);
+    if lines.len() != rows.len() {
+        return Err(ToonError::encode(
+            "",
+            format!(
+                "row encoder returned {} lines for {} rows",
+                lines.len(),
+                rows.len()
+            ),
+        ));
+    }
+    for (index, (line, cells)) in lines.iter().zip(rows).enumerate() {
+        if line.starts_with(char::is_whitespace) {
+            return Err(ToonError::encode(
+                format!("[{index}]"),
+                "row encoder output starts with whitespace, which would change its indentation",
+            ));
+        }
+        if line.contains('\n') || parse_delimited_values(line, delimiter) != *cells {
+            return Err(ToonError::encode(
+                format!("[{index}]"),
+                "row encoder output does not read back as the row's cells",
+            ));
+        }
+    }
+    Ok(lines)
+}
@@ -0,0 +1,631 @@
+//! Encoding any `T: Serialize` straight to TOON.
+//!
+//! [`to_toon_string`] and [`to_toon_lines`] mirror [`crate::encode`] and
+//! [`crate::encode_lines`], taking the same [`EncodeOptions`], but start
+//! from a Rust value instead of a [`JsonValue`]. [`Serializer`] builds the
+//! [`JsonValue`] directly, without a `serde_json::Value` in between, and
+//! follows `serde_json`'s data model: structs and maps become objects in
+//! field order, sequences and tuples arrays, `None` and `()` null, and enum
+//! variants other than unit variants a single-key object named after the
+//! variant. Integers that fit in 64 bits stay exact; only `i128` and `u128`
+//! values beyond them become floats.
+//!
+//! ```
+//! use serde::Serialize;
+//! use toon::to_toon_string;
+//!
+//! #[derive(Serialize)]
+//! struct User {
+//!     id: u32,
+//!     name: &'static str,
+//! }
+//!
+//! let users = vec![User { id: 1, name: "Ada" }, User { id: 2, name: "Bob" }];
+//! assert_eq!(
+//!     to_toon_string(&users, None)?,
+//!     "[2]{id,name}:\n  1,Ada\n  2,Bob"
+//! );
+//! # Ok::<(), toon::error::ToonError>(())
+//! ```
+
+use std::fmt::Display;
+
+use ::serde::ser::{self, Serialize};
+
+use crate::error::{Result, ToonError};
+use crate::options::EncodeOptions;
+use crate::{JsonValue, StringOrNumberOrBoolOrNull, try_encode, try_encode_lines};
+
+/// Serialize `value` and encode it as a TOON string.
+///
+/// # Errors
+///
+/// Returns an error if `value` fails to serialize, has a map key that is
+/// not a string, integer, or unit variant, or cannot be encoded.
+pub fn to_toon_string<T: Serialize + ?Sized>(
+    value: &T,
+    options: Option<EncodeOptions>,
+) -> Result<String> {
+    try_encode(to_value(value)?, options)
+}
+
+/// Serialize `value` and encode it as TOON lines.
+///
+/// # Errors
+///
+/// Returns an error if `value` fails to serialize, has a map key that is
+/// not a string, integer, or unit variant, or cannot be encoded.
+pub fn to_toon_lines<T: Serialize + ?Sized>(
+    value: &T,
+    options: Option<EncodeOptions>,
+) -> Result<Vec<String>> {
+    try_encode_lines(to_value(value)?, options)
+}
+
+/// Serialize `value` into a [`JsonValue`].
+///
+/// # Errors
+///
+/// Returns an error if `value` fails to serialize or has a map key that is
+/// not a string, integer, or unit variant. Errors inside a field or item
+/// carry its path.
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<JsonValue> {
+    value.serialize(Serializer)
+}
+
+impl ser::Error for ToonError {
+    fn custom<T: Display>(message: T) -> Self {
+        Self::encode("", message.to_string())
+    }
+}
+
+/// A `serde::Serializer` whose output is a [`JsonValue`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Serializer;
+
+const fn primitive(value: StringOrNumberOrBoolOrNull) -> JsonValue {
+    JsonValue::Primitive(value)
+}
+
+fn number(value: f64) -> JsonValue {
+    primitive(StringOrNumberOrBoolOrNull::from_f64(value))
+}
+
+fn string(value: impl Into<String>) -> JsonValue {
+    primitive(StringOrNumberOrBoolOrNull::String(value.into()))
+}
+
+const fn null() -> JsonValue {
+    primitive(StringOrNumberOrBoolOrNull::Null)
+}
+
+/// `{variant: value}`, the externally tagged form of an enum variant.
+fn tagged(variant: &str, value: JsonValue) -> JsonValue {
+    JsonValue::Object(vec![(variant.to_string(), value)])
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = JsonValue;
+    type Error = ToonError;
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeArray;
+    type SerializeMap = SerializeObject;
+    type SerializeStruct = SerializeObject;
+    type SerializeStructVariant = SerializeObject;
+
+    fn serialize_bool(self, value: bool) -> Result<JsonValue> {
+        Ok(primitive(StringOrNumberOrBoolOrNull::Bool(value)))
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<JsonValue> {
+        Ok(JsonValue::from(value))
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<JsonValue> {
+        Ok(JsonValue::from(value))
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<JsonValue> {
+        Ok(JsonValue::from(value))
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<JsonValue> {
+        Ok(JsonValue::from(value))
+    }
+
+    fn serialize_i128(self, value: i128) -> Result<JsonValue> {
+        Ok(JsonValue::from(value))
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<JsonValue> {
+        Ok(JsonValue::from(value))
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<JsonValue> {
+        Ok(JsonValue::from(value))
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<JsonValue> {
+        Ok(JsonValue::from(value))
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<JsonValue> {
+        Ok(JsonValue::from(value))
+    }
+
+    fn serialize_u128(self, value: u128) -> Result<JsonValue> {
+        Ok(JsonValue::from(value))
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<JsonValue> {
+        Ok(number(f64::from(value)))
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<JsonValue> {
+        Ok(number(value))
+    }
+
+    fn serialize_char(self, value: char) -> Result<JsonValue> {
+        Ok(string(value))
+    }
+
+    fn serialize_str(self, value: &str) -> Result<JsonValue> {
+        Ok(string(value))
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<JsonValue> {
+        Ok(JsonValue::Array(
+            value.iter().copied().map(JsonValue::from).collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> Result<JsonValue> {
+        Ok(null())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<JsonValue> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<JsonValue> {
+        Ok(null())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<JsonValue> {
+        Ok(null())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<JsonValue> {
+        Ok(string(variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<JsonValue> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<JsonValue> {
+        let value = value
+            .serialize(self)
+            .map_err(|err| err.with_key_prefix(variant))?;
+        Ok(tagged(variant, value))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeArray> {
+        Ok(SerializeArray {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeArray> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SerializeArray> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray> {
+        Ok(SerializeArray {
+            items: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<SerializeObject> {
+        Ok(SerializeObject {
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            next_key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeObject> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeObject> {
+        Ok(SerializeObject {
+            entries: Vec::with_capacity(len),
+            next_key: None,
+            variant: Some(variant),
+        })
+    }
+}
+
+/// Collects the items of a sequence, tuple, or tuple variant.
+#[derive(Debug)]
+pub struct SerializeArray {
+    items: Vec<JsonValue>,
+    variant: Option<&'static str>,
+}
+
+impl SerializeArray {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let index = self.items.len();
+        let value = value.serialize(Serializer).map_err(|err| {
+            let err = err.with_index_prefix(index);
+            match self.variant {
+                Some(variant) => err.with_key_prefix(variant),
+                None => err,
+            }
+        })?;
+        self.items.push(value);
+        Ok(())
+    }
+
+    fn finish(self) -> JsonValue {
+        let array = JsonValue::Array(self.items);
+        match self.variant {
+            Some(variant) => tagged(variant, array),
+            None => array,
+        }
+    }
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = JsonValue;
+    type Error = ToonError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<JsonValue> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = JsonValue;
+    type Error = ToonError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<JsonValue> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = JsonValue;
+    type Error = ToonError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<JsonValue> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeArray {
+    type Ok = JsonValue;
+    type Error = ToonError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<JsonValue> {
+        Ok(self.finish())
+    }
+}
+
+/// Collects the entries of a map, struct, or struct variant.
+#[derive(Debug)]
+pub struct SerializeObject {
+    entries: Vec<(String, JsonValue)>,
+    next_key: Option<String>,
+    variant: Option<&'static str>,
+}
+
+impl SerializeObject {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<()> {
+        let value = value.serialize(Serializer).map_err(|err| {
+            let err = err.with_key_prefix(&key);
+            match self.variant {
+                Some(variant) => err.with_key_prefix(variant),
+                None => err,
+            }
+        })?;
+        // A repeated key replaces the earlier value in place, as it would in
+        // a JSON object.
+        match self
+            .entries
+            .iter_mut()
+            .find(|(existing, _)| *existing == key)
+        {
+            Some((_, existing)) => *existing = value,
+            None => self.entries.push((key, value)),
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> JsonValue {
+        let object = JsonValue::Object(self.entries);
+        match self.variant {
+            Some(variant) => tagged(variant, object),
+            None => object,
+        }
+    }
+}
+
+impl ser::SerializeMap for SerializeObject {
+    type Ok = JsonValue;
+    type Error = ToonError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.next_key = Some(key.serialize(MapKeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self
+            .next_key
+            .take()
+            .ok_or_else(|| ToonError::encode("", "map value serialized before its key"))?;
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<JsonValue> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStruct for SerializeObject {
+    type Ok = JsonValue;
+    type Error = ToonError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<JsonValue> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeObject {
+    type Ok = JsonValue;
+    type Error = ToonError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<JsonValue> {
+        Ok(self.finish())
+    }
+}
+
+/// Serializes map keys, which must become strings: strings and chars as
+/// they are, integers and unit variants by their text.
+struct MapKeySerializer;
+
+fn key_must_be_a_string() -> ToonError {
+    ToonError::encode("", "map key must be a string, integer, or unit variant")
+}
+
+impl ser::Serializer for MapKeySerializer {
+    type Ok = String;
+    type Error = ToonError;
+    type SerializeSeq = ser::Impossible<String, ToonError>;
+    type SerializeTuple = ser::Impossible<String, ToonError>;
+    type SerializeTupleStruct = ser::Impossible<String, ToonError>;
+    type SerializeTupleVariant = ser::Impossible<String, ToonError>;
+    type SerializeMap = ser::Impossible<String, ToonError>;
+    type SerializeStruct = ser::Impossible<String, ToonError>;
+    type SerializeStructVariant = ser::Impossible<String, ToonError>;
+
+    fn serialize_bool(self, _value: bool) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<String> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<String> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<String> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<String> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_i128(self, value: i128) -> Result<String> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<String> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<String> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<String> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<String> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_u128(self, value: u128) -> Result<String> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_f32(self, _value: f32) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_f64(self, _value: f64) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_char(self, value: char) -> Result<String> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_str(self, value: &str) -> Result<String> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_bytes(self, _value: &[u8]) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_none(self) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit(self) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<String> {
+        Ok(variant.to_string())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(key_must_be_a_string())
+    }
+}
@@ -0,0 +1,562 @@
+//! Encoding a document as it is described, one event at a time.
+//!
+//! [`ToonWriter`] takes a document the way a streaming JSON writer does:
+//! open objects and arrays, write keys and primitive values into them, and
+//! close them again. Each line goes to the underlying [`io::Write`] as soon
+//! as it is known, so neither a [`JsonValue`](crate::JsonValue) nor the
+//! encoded lines are held in memory.
+//!
+//! Without the whole array in hand, the writer cannot tell whether its
+//! objects share one set of keys, so arrays of objects are written as list
+//! items rather than tabular rows. Primitive items are held back until the
+//! array ends or a non-primitive item arrives, and written inline
+//! (`tags[2]: a,b`) if the array has nothing else. Everything else is laid
+//! out as [`encode`](crate::encode) lays it out, and the output decodes to
+//! the described value.
+//!
+//! ```
+//! use toon::encode::writer::ToonWriter;
+//! use toon::StringOrNumberOrBoolOrNull::{Int, String as Str};
+//!
+//! let mut writer = ToonWriter::new(Vec::new(), None);
+//! writer.start_object()?;
+//! writer.key("name")?;
+//! writer.value(Str("report".into()))?;
+//! writer.key("tags")?;
+//! writer.start_array(2)?;
+//! writer.value(Str("a".into()))?;
+//! writer.value(Str("b c".into()))?;
+//! writer.end_array()?;
+//! writer.key("rows")?;
+//! writer.start_array(1)?;
+//! writer.start_object()?;
+//! writer.key("id")?;
+//! writer.value(Int(1))?;
+//! writer.end_object()?;
+//! writer.end_array()?;
+//! writer.end_object()?;
+//! let out = String::from_utf8(writer.finish()?).unwrap();
+//! assert_eq!(out, "name: report\ntags[2]: a,b c\nrows[1]:\n  - id: 1\n");
+//! # Ok::<(), toon::error::ToonError>(())
+//! ```
+
+use std::io::{self, Write};
+
+use crate::encode::primitives::{
+    encode_and_join_primitives, encode_and_join_runs, encode_key, encode_primitive,
+    format_typed_header,
+};
+use crate::error::{Result, ToonError};
+use crate::options::{
+    EmptyStringStyle, EncodeOptions, PathSegment, format_path, resolve_encode_options,
+};
+use crate::shared::constants::{LIST_ITEM_MARKER, LIST_ITEM_PREFIX};
+use crate::shared::directive::Directive;
+use crate::sink::EventSink;
+use crate::{JsonPrimitive, JsonPrimitiveRef, JsonStreamEvent};
+
+/// Writes a TOON document to an [`io::Write`] as it is described.
+///
+/// The document is one value: a primitive, or an object or array opened
+/// with [`start_object`] or [`start_array`] and closed with [`end_object`]
+/// or [`end_array`]. Inside an object, each value follows its [`key`].
+/// [`finish`] checks that the value is complete.
+///
+/// Of the encode options, `indent`, `delimiter`, `always_mark_delimiter`,
+/// `empty_string`, `run_length`, and `emit_directive` apply. The others
+/// rewrite or look over the whole document and have no effect here.
+///
+/// The writer also takes [`JsonStreamEvent`]s as an [`EventSink`], so a
+/// decoder can feed it directly.
+///
+/// Lines are written one at a time, so pass a buffered writer.
+///
+/// [`start_object`]: ToonWriter::start_object
+/// [`start_array`]: ToonWriter::start_array
+/// [`end_object`]: ToonWriter::end_object
+/// [`end_array`]: ToonWriter::end_array
+/// [`key`]: ToonWriter::key
+/// [`finish`]: ToonWriter::finish
+#[derive(Debug)]
+pub struct ToonWriter<W: Write> {
+    writer: W,
+    indent: usize,
+    delimiter: char,
+    mark_delimiter: bool,
+    empty_string: EmptyStringStyle,
+    run_length: Option<usize>,
+    /// The `#%toon` line, until it is written before the first line.
+    directive: Option<String>,
+    open: Vec<Frame>,
+    complete: bool,
+}
+
+#[derive(Debug)]
+enum Frame {
+    Object {
+        /// Depth of the object's fields.
+        depth: usize,
+        /// The key last written, whose value is being or is to be written.
+        key: Option<String>,
+        awaiting_value: bool,
+        /// A list item whose first field still has to go on the hyphen line.
+        first_on_hyphen: bool,
+    },
+    Array {
+        /// What comes before the header on its line: indentation, and the
+        /// hyphen or key.
+        prefix: String,
+        /// Depth of the items.
+        depth: usize,
+        length: usize,
+        count: usize,
+        /// Primitive items held back to be written inline, until an item
+        /// that cannot be arrives and the header is written on its own.
+        inline: Option<Vec<JsonPrimitive>>,
+    },
+}
+
+/// Where the next value goes.
+struct Slot {
+    kind: SlotKind,
+    /// Depth of the value's line.
+    depth: usize,
+    /// What comes before the value on its line.
+    prefix: String,
+    /// Whether the line starts with the hyphen of the list item the value
+    /// is the first field of.
+    #[cfg_attr(not(feature = "raw"), allow(dead_code))]
+    hyphen: bool,
+}
+
+/// What the next value is, which decides whether an array holding its
+/// primitives back can go on doing so, and whether the value is counted.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Claim {
+    Primitive,
+    Container,
+    #[cfg(feature = "raw")]
+    Raw,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SlotKind {
+    Root,
+    Field,
+    Item,
+}
+
+impl<W: Write> ToonWriter<W> {
+    /// A writer using the line-level settings of `options` (see
+    /// [`ToonWriter`]).
+    #[must_use]
+    pub fn new(writer: W, options: Option<EncodeOptions>) -> Self {
+        let resolved = resolve_encode_options(options);
+        let directive = resolved
+            .emit_directive
+            .then(|| Directive::new(resolved.delimiter, resolved.indent).to_line());
+        Self {
+            writer,
+            indent: resolved.indent,
+            delimiter: resolved.delimiter,
+            mark_delimiter: resolved.always_mark_delimiter,
+            empty_string: resolved.empty_string,
+            run_length: resolved.run_length,
+            directive,
+            open: Vec::new(),
+            complete: false,
+        }
+    }
+
+    /// Open an object. Its fields are written with [`key`](Self::key) and a
+    /// value each.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no value can go here (see [`value`](Self::value)),
+    /// or if writing fails.
+    pub fn start_object(&mut self) -> Result<()> {
+        let slot = self.next_slot(Claim::Container)?;
+        let (depth, first_on_hyphen) = match slot.kind {
+            SlotKind::Root => (0, false),
+            SlotKind::Field => {
+                self.write_line(&format!("{}:", slot.prefix))?;
+                (slot.depth + 1, false)
+            }
+            // The first field goes on the hyphen line, so nothing is written
+            // until it arrives.
+            SlotKind::Item => (slot.depth + 1, true),
+        };
+        self.open.push(Frame::Object {
+            depth,
+            key: None,
+            awaiting_value: false,
+            first_on_hyphen,
+        });
+        Ok(())
+    }
+
+    /// Write the key of the next field of the open object.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error outside an object, or if the previous key has no
+    /// value yet.
+    pub fn key(&mut self, key: &str) -> Result<()> {
+        let location = self.location();
+        match self.open.last_mut() {
+            Some(Frame::Object {
+                key: current,
+                awaiting_value,
+                ..
+            }) => {
+                if *awaiting_value {
+                    let previous = current.as_deref().unwrap_or_default();
+                    return Err(ToonError::encode(
+                        location,
+                        format!("key '{key}' written before a value for '{previous}'"),
+                    ));
+                }
+                *current = Some(key.to_string());
+                *awaiting_value = true;
+                Ok(())
+            }
+            _ => Err(ToonError::encode(
+                location,
+                format!("key '{key}' written outside an object"),
+            )),
+        }
+    }
+
+    /// Close the innermost open object.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the innermost open value is not an object, if
+    /// its last key has no value, or if writing fails.
+    pub fn end_object(&mut self) -> Result<()> {
+        let location = self.location();
+        let (depth, first_on_hyphen) = match self.open.last() {
+            Some(Frame::Object {
+                key: Some(key),
+                awaiting_value: true,
+                ..
+            }) => {
+                return Err(ToonError::encode(
+                    location,
+                    format!("object ended before a value for '{key}'"),
+                ));
+            }
+            Some(Frame::Object {
+                depth,
+                first_on_hyphen,
+                ..
+            }) => (*depth, *first_on_hyphen),
+            _ => {
+                return Err(ToonError::encode(location, "no object is open to end"));
+            }
+        };
+        // An empty list item object is a hyphen on its own.
+        if first_on_hyphen {
+            let line = self.indentation(depth - 1) + LIST_ITEM_MARKER;
+            self.write_line(&line)?;
+        }
+        self.open.pop();
+        self.complete = self.open.is_empty();
+        Ok(())
+    }
+
+    /// Open an array of exactly `length` items.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no value can go here (see [`value`](Self::value)),
+    /// or if writing fails.
+    pub fn start_array(&mut self, length: usize) -> Result<()> {
+        let slot = self.next_slot(Claim::Container)?;
+        self.open.push(Frame::Array {
+            prefix: slot.prefix,
+            depth: slot.depth + 1,
+            length,
+            count: 0,
+            inline: Some(Vec::new()),
+        });
+        Ok(())
+    }
+
+    /// Close the innermost open array.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the innermost open value is not an array, if it
+    /// has fewer items than it was opened with, or if writing fails.
+    pub fn end_array(&mut self) -> Result<()> {
+        let location = self.location();
+        match self.open.last() {
+            Some(Frame::Array { length, count, .. }) if count != length => {
+                return Err(ToonError::encode(
+                    location,
+                    format!("expected {length} items, but got {count}"),
+                ));
+            }
+            Some(Frame::Array { .. }) => {}
+            _ => return Err(ToonError::encode(location, "no array is open to end")),
+        }
+        let Some(Frame::Array {
+            prefix,
+            length,
+            inline,
+            ..
+        }) = self.open.pop()
+        else {
+            unreachable!("the innermost open value was just checked to be an array");
+        };
+        if let Some(items) = inline {
+            let mut line = prefix + &self.header(length);
+            if !items.is_empty() {
+                line.push(' ');
+                line.push_str(&self.join_inline(&items));
+            }
+            self.write_line(&line)?;
+        }
+        self.complete = self.open.is_empty();
+        Ok(())
+    }
+
+    /// Write a primitive value: the whole document, the value of the last
+    /// key, or the next array item.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the document is already complete, if the open
+    /// object has no key awaiting a value, if the open array already has
+    /// all its items, or if writing fails.
+    pub fn value(&mut self, value: JsonPrimitive) -> Result<()> {
+        if let Some(Frame::Array {
+            length,
+            count,
+            inline: Some(items),
+            ..
+        }) = self.open.last_mut()
+            && count < length
+        {
+            *count += 1;
+            items.push(value);
+            return Ok(());
+        }
+        let slot = self.next_slot(Claim::Primitive)?;
+        let encoded = encode_primitive(&value, self.delimiter);
+        let line = match slot.kind {
+            SlotKind::Field => format!("{}: {encoded}", slot.prefix),
+            SlotKind::Root | SlotKind::Item => slot.prefix + &encoded,
+        };
+        self.write_line(&line)?;
+        self.complete = self.open.is_empty();
+        Ok(())
+    }
+
+    /// Write raw TOON text in place of the next value, each line indented
+    /// to its depth. As the encoder does with [`JsonValue::Raw`], the key
+    /// of a raw field is not written and a raw array item is not counted.
+    ///
+    /// [`JsonValue::Raw`]: crate::JsonValue::Raw
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no value can go here (see [`value`](Self::value)),
+    /// or if writing fails.
+    #[cfg(feature = "raw")]
+    pub fn raw(&mut self, text: &str) -> Result<()> {
+        let slot = self.next_slot(Claim::Raw)?;
+        let mut lines = text.split('\n');
+        let first = lines.next().unwrap_or_default();
+        // The key is dropped, and the hyphen of a list item kept.
+        let first = if slot.hyphen {
+            self.indentation(slot.depth - 1) + LIST_ITEM_PREFIX + first
+        } else {
+            self.indentation(slot.depth) + first
+        };
+        self.write_line(&first)?;
+        for line in lines {
+            let line = self.indentation(slot.depth) + line;
+            self.write_line(&line)?;
+        }
+        self.complete = self.open.is_empty();
+        Ok(())
+    }
+
+    /// Check that the document is complete and flush, returning the writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no value was written, if an object or array is
+    /// still open, or if flushing fails.
+    pub fn finish(mut self) -> Result<W> {
+        if !self.open.is_empty() {
+            return Err(ToonError::encode(
+                self.location(),
+                "not ended before finish",
+            ));
+        }
+        if !self.complete {
+            return Err(ToonError::message(
+                "Nothing to finish: no value was written",
+            ));
+        }
+        // A document without lines, such as an empty root object, still
+        // gets its directive.
+        if let Some(directive) = self.directive.take() {
+            writeln!(self.writer, "{directive}").map_err(write_error)?;
+        }
+        self.writer.flush().map_err(write_error)?;
+        Ok(self.writer)
+    }
+
+    /// Claim the place of the next value, checking that one can go there.
+    /// An array holding primitives back writes them out first, as list
+    /// items under its header, unless the value is a primitive too.
+    fn next_slot(&mut self, claim: Claim) -> Result<Slot> {
+        if self.complete {
+            return Err(ToonError::message(
+                "Document already complete: only one value can be written",
+            ));
+        }
+        let location = self.location();
+        let indent = self.indent;
+        match self.open.last_mut() {
+            None => Ok(Slot {
+                kind: SlotKind::Root,
+                depth: 0,
+                prefix: String::new(),
+                hyphen: false,
+            }),
+            Some(Frame::Object {
+                depth,
+                key,
+                awaiting_value,
+                first_on_hyphen,
+            }) => {
+                let Some(key) = key.as_deref().filter(|_| *awaiting_value) else {
+                    return Err(ToonError::encode(location, "value written without a key"));
+                };
+                *awaiting_value = false;
+                let hyphen = std::mem::take(first_on_hyphen);
+                let mut prefix = if hyphen {
+                    " ".repeat((*depth - 1) * indent) + LIST_ITEM_PREFIX
+                } else {
+                    " ".repeat(*depth * indent)
+                };
+                prefix.push_str(&encode_key(key));
+                Ok(Slot {
+                    kind: SlotKind::Field,
+                    depth: *depth,
+                    prefix,
+                    hyphen,
+                })
+            }
+            Some(Frame::Array {
+                prefix,
+                depth,
+                length,
+                count,
+                inline,
+            }) => {
+                // Raw items are not counted, as they were not when decoded.
+                #[cfg(feature = "raw")]
+                let counted = claim != Claim::Raw;
+                #[cfg(not(feature = "raw"))]
+                let counted = true;
+                if counted {
+                    if count >= length {
+                        return Err(ToonError::encode(
+                            format!("{location}[{count}]"),
+                            format!("header declares {length} items"),
+                        ));
+                    }
+                    *count += 1;
+                }
+                let depth = *depth;
+                let held = if claim == Claim::Primitive {
+                    None
+                } else {
+                    inline
+                        .take()
+                        .map(|items| (std::mem::take(prefix), *length, items))
+                };
+                if let Some((prefix, length, items)) = held {
+                    let header = prefix + &self.header(length);
+                    self.write_line(&header)?;
+                    for item in &items {
+                        let line = self.indentation(depth)
+                            + LIST_ITEM_PREFIX
+                            + &encode_primitive(item, self.delimiter);
+                        self.write_line(&line)?;
+                    }
+                }
+                Ok(Slot {
+                    kind: SlotKind::Item,
+                    depth,
+                    prefix: self.indentation(depth) + LIST_ITEM_PREFIX,
+                    hyphen: false,
+                })
+            }
+        }
+    }
+
+    /// The path of the innermost open value.
+    fn location(&self) -> String {
+        let Some((_, outer)) = self.open.split_last() else {
+            return String::new();
+        };
+        let path: Vec<PathSegment> = outer
+            .iter()
+            .filter_map(|frame| match frame {
+                Frame::Object { key, .. } => key.clone().map(PathSegment::Key),
+                Frame::Array { count, .. } => count.checked_sub(1).map(PathSegment::Index),
+            })
+            .collect();
+        format_path(&path)
+    }
+
+    fn header(&self, length: usize) -> String {
+        format_typed_header(length, None, None, &[], self.delimiter, self.mark_delimiter)
+    }
+
+    fn join_inline(&self, items: &[JsonPrimitive]) -> String {
+        let items: Vec<JsonPrimitiveRef> = items.iter().map(Into::into).collect();
+        self.run_length.map_or_else(
+            || encode_and_join_primitives(&items, self.delimiter, self.empty_string),
+            |min_run| encode_and_join_runs(&items, self.delimiter, self.empty_string, min_run),
+        )
+    }
+
+    fn indentation(&self, depth: usize) -> String {
+        " ".repeat(depth * self.indent)
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        if let Some(directive) = self.directive.take() {
+            writeln!(self.writer, "{directive}").map_err(write_error)?;
+        }
+        writeln!(self.writer, "{line}").map_err(write_error)
+    }
+}
+
+impl<W: Write> EventSink for ToonWriter<W> {
+    fn event(&mut self, event: JsonStreamEvent) -> Result<()> {
+        match event {
+            JsonStreamEvent::StartObject => self.start_object(),
+            JsonStreamEvent::EndObject => self.end_object(),
+            JsonStreamEvent::StartArray { length } => self.start_array(length),
+            JsonStreamEvent::EndArray => self.end_array(),
+            JsonStreamEvent::Key { key, .. } => self.key(&key),
+            JsonStreamEvent::Primitive { value } => self.value(value),
+            #[cfg(feature = "raw")]
+            JsonStreamEvent::Raw { text } => self.raw(&text),
+        }
+    }
+}
+
+fn write_error(err: io::Error) -> ToonError {
+    ToonError::io("Failed to write TOON output", None, err)
+}
@@ -20,6 +20,26 @@ pub enum ToonError {
     #[error("Path expansion error for '{path}': {message}")]
     PathExpansion { path: String, message: String },
 
+    /// Encoding failure at a location in the input value
+    #[error("Encode error at '{path}': {message}")]
+    Encode { path: String, message: String },
+
+    /// Deserialization failure at a location in the decoded value
+    #[error("Deserialize error at '{path}': {message}")]
+    Deserialize { path: String, message: String },
+
+    /// A configured resource limit (depth, size, budget) was exceeded
+    #[error("{kind} limit exceeded: {actual} > {limit}")]
+    Limit {
+        kind: String,
+        limit: usize,
+        actual: usize,
+    },
+
+    /// The operation was cancelled by the user (Ctrl-C)
+    #[error("Interrupted")]
+    Interrupted,
+
     /// I/O error with operation context
     #[error("{operation}{}: {source}", path.as_ref().map(|p| format!(" '{}'", p.display())).unwrap_or_default())]
     Io {
@@ -84,6 +104,18 @@ impl ToonError {
         Self::parse(line, format!("Invalid array length: {value}"))
     }
 
+    // =========================================================================
+    // Option error constructors
+    // =========================================================================
+
+    /// Create an error for an unsupported delimiter name.
+    #[must_use]
+    pub fn invalid_delimiter(name: &str) -> Self {
+        Self::message(format!(
+            "Invalid delimiter \"{name}\". Valid delimiters are: comma (,), tab (\\t), pipe (|)"
+        ))
+    }
+
     // =========================================================================
     // Validation error constructors
     // =========================================================================
@@ -157,6 +189,100 @@ impl ToonError {
         Self::path_expansion(path, format!("conflicts with existing key '{existing}'"))
     }
 
+    // =========================================================================
+    // Encode and deserialize error constructors
+    // =========================================================================
+
+    /// Create an encode error at a path (e.g. `users[1].name`).
+    #[must_use]
+    pub fn encode(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::Encode {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+
+    /// Create an encode error for a tabular row missing a header field.
+    #[must_use]
+    pub fn tabular_missing_field(row_path: impl Into<String>, field: &str) -> Self {
+        Self::encode(
+            row_path,
+            format!("tabular row is missing header field '{field}'"),
+        )
+    }
+
+    /// Create an encode error for a non-primitive tabular cell.
+    #[must_use]
+    pub fn tabular_non_primitive(cell_path: impl Into<String>) -> Self {
+        Self::encode(cell_path, "tabular row contains non-primitive value")
+    }
+
+    /// Create a deserialize error at a path (e.g. `users[1].name`).
+    #[must_use]
+    pub fn deserialize(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::Deserialize {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+
+    /// Prefix the path of an encode or deserialize error with a parent
+    /// object key.
+    ///
+    /// Encoders build error paths while unwinding, so the success path never
+    /// pays for path tracking. Other error variants are returned unchanged.
+    #[must_use]
+    pub fn with_key_prefix(self, key: &str) -> Self {
+        self.map_path(|path| {
+            if path.is_empty() || path.starts_with('[') {
+                format!("{key}{path}")
+            } else {
+                format!("{key}.{path}")
+            }
+        })
+    }
+
+    /// Prefix the path of an encode or deserialize error with a parent array
+    /// index.
+    #[must_use]
+    pub fn with_index_prefix(self, index: usize) -> Self {
+        self.map_path(|path| {
+            if path.is_empty() || path.starts_with('[') {
+                format!("[{index}]{path}")
+            } else {
+                format!("[{index}].{path}")
+            }
+        })
+    }
+
+    fn map_path(self, prefix: impl FnOnce(&str) -> String) -> Self {
+        match self {
+            Self::Encode { path, message } => Self::Encode {
+                path: prefix(&path),
+                message,
+            },
+            Self::Deserialize { path, message } => Self::Deserialize {
+                path: prefix(&path),
+                message,
+            },
+            other => other,
+        }
+    }
+
+    // =========================================================================
+    // Limit error constructors
+    // =========================================================================
+
+    /// Create a limit error (e.g. `limit("depth", 64, 65)`).
+    #[must_use]
+    pub fn limit(kind: impl Into<String>, limit: usize, actual: usize) -> Self {
+        Self::Limit {
+            kind: kind.into(),
+            limit,
+            actual,
+        }
+    }
+
     // =========================================================================
     // I/O error constructors
     // =========================================================================
@@ -224,6 +350,19 @@ impl ToonError {
     pub fn json_stringify(err: &serde_json::Error) -> Self {
         Self::json(format!("Failed to stringify JSON: {err}"))
     }
+
+    // =========================================================================
+    // Accessors
+    // =========================================================================
+
+    /// The 1-based input line of a parse or validation error.
+    #[must_use]
+    pub const fn line(&self) -> Option<usize> {
+        match self {
+            Self::Parse { line, .. } | Self::Validation { line, .. } => Some(*line),
+            _ => None,
+        }
+    }
 }
 
 impl From<std::io::Error> for ToonError {
@@ -0,0 +1,209 @@
+//! Structure commentary for TOON documents.
+//!
+//! [`explain_toon`] re-prints a document with `#` comment lines above each
+//! array and nested object, describing what a reader (or a model) will see
+//! there: element counts, inferred column types, and the byte and estimated
+//! token size of the section. TOON has no comment syntax, so the annotated
+//! text is for reading only and does not decode.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+use crate::decode::parser::{
+    parse_array_header_line, parse_delimited_values, parse_field_type_annotations, parse_key_token,
+    parse_primitive_token,
+};
+use crate::decode::scanner::{ParsedLine, create_scan_state, parse_lines_sync};
+use crate::error::Result;
+use crate::options::{ColumnType, DecodeOptions, resolve_decode_options};
+use crate::shared::constants::{DEFAULT_DELIMITER, LIST_ITEM_MARKER, LIST_ITEM_PREFIX};
+use crate::shared::string_utils::estimate_tokens;
+use crate::{JsonValue, StringOrNumberOrBoolOrNull, try_decode};
+
+/// Prefix of annotation lines.
+pub const COMMENT_PREFIX: &str = "# ";
+
+/// Annotate a TOON document with structure commentary.
+///
+/// The first line summarizes the whole document. Every array header and
+/// nested object key is preceded by a comment at the same indentation, for
+/// example `# 3 rows × 2 fields (id: number, name: string) · 52 bytes · ~13 tokens`.
+/// Other lines are copied unchanged.
+///
+/// # Errors
+///
+/// Returns an error if the document does not decode with `options`.
+pub fn explain_toon(input: &str, options: Option<DecodeOptions>) -> Result<String> {
+    let resolved = resolve_decode_options(options.clone());
+    let value = try_decode(input, options)?;
+
+    let mut state = create_scan_state();
+    let lines = parse_lines_sync(
+        input.split('\n').map(str::to_string),
+        resolved.indent,
+        resolved.strict,
+        &mut state,
+    )?;
+
+    let mut notes = HashMap::new();
+    for (index, line) in lines.iter().enumerate() {
+        if let Some(note) = describe_section(&lines, index, resolved.typed_headers) {
+            notes.insert(line.line_number, note);
+        }
+    }
+
+    let mut out = String::with_capacity(input.len() * 2);
+    let _ = writeln!(
+        out,
+        "{COMMENT_PREFIX}document: {}{}",
+        describe_value(&value),
+        size_note(input.trim_end_matches('\n'))
+    );
+    for (index, raw) in input.split('\n').enumerate() {
+        if index > 0 {
+            out.push('\n');
+        }
+        if let Some(note) = notes.get(&(index + 1)) {
+            let indent = raw.len() - raw.trim_start_matches(' ').len();
+            let _ = writeln!(out, "{}{COMMENT_PREFIX}{note}", &raw[..indent]);
+        }
+        out.push_str(raw);
+    }
+    Ok(out)
+}
+
+fn describe_value(value: &JsonValue) -> String {
+    match value {
+        JsonValue::Object(entries) => format!("object with {}", plural(entries.len(), "key")),
+        JsonValue::Array(items) => format!("array of {}", plural(items.len(), "item")),
+        JsonValue::Primitive(primitive) => primitive_kind(primitive).to_string(),
+        #[cfg(feature = "raw")]
+        JsonValue::Raw(_) => "raw text".to_string(),
+    }
+}
+
+/// Describe the array or nested object opened by `lines[index]`, if any.
+fn describe_section(lines: &[ParsedLine], index: usize, typed_headers: bool) -> Option<String> {
+    let line = &lines[index];
+    let (content, body_depth) = match line.content.strip_prefix(LIST_ITEM_PREFIX) {
+        // `- [N]: ...` is an array item; `- key...` opens an object whose
+        // fields continue one level below the hyphen.
+        Some(rest) if rest.trim_start().starts_with('[') => (rest, line.depth + 1),
+        Some(rest) => (rest, line.depth + 2),
+        None if line.content == LIST_ITEM_MARKER => return None,
+        None => (line.content.as_str(), line.depth + 1),
+    };
+
+    let end = lines[index + 1..]
+        .iter()
+        .position(|next| next.depth < body_depth)
+        .map_or(lines.len(), |offset| index + 1 + offset);
+    let body = lines[index + 1..end]
+        .iter()
+        .filter(|next| next.depth == body_depth)
+        .collect::<Vec<_>>();
+
+    let summary = if let Ok(Some(parsed)) = parse_array_header_line(content, DEFAULT_DELIMITER) {
+        let header = parsed.header;
+        if let Some(mut fields) = header.fields {
+            if typed_headers {
+                parse_field_type_annotations(&mut fields, line.line_number).ok()?;
+            }
+            let rows = body
+                .iter()
+                .map(|row| parse_delimited_values(&row.content, header.delimiter))
+                .collect::<Vec<_>>();
+            let columns = fields
+                .iter()
+                .enumerate()
+                .map(|(column, field)| {
+                    let kind = match field.column_type {
+                        Some(column_type) if column_type != ColumnType::Auto => {
+                            column_type.as_str().to_string()
+                        }
+                        _ => combine_kinds(rows.iter().filter_map(|row| row.get(column))),
+                    };
+                    format!("{}: {kind}", field.name)
+                })
+                .collect::<Vec<_>>();
+            format!(
+                "{} × {} ({})",
+                plural(header.length, "row"),
+                plural(fields.len(), "field"),
+                columns.join(", ")
+            )
+        } else if let Some(inline) = parsed.inline_values.filter(|values| !values.is_empty()) {
+            let values = parse_delimited_values(&inline, header.delimiter);
+            format!(
+                "{} ({})",
+                plural(header.length, "item"),
+                combine_kinds(values.iter())
+            )
+        } else {
+            plural(header.length, "item")
+        }
+    } else {
+        let (_, end_of_key, _) = parse_key_token(content, 0).ok()?;
+        if !content[end_of_key..].trim().is_empty() {
+            return None;
+        }
+        format!("object with {}", plural(body.len(), "key"))
+    };
+
+    let section = lines[index..end]
+        .iter()
+        .map(|line| line.raw.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    Some(format!("{summary}{}", size_note(&section)))
+}
+
+/// Name the kind shared by `cells`: `number`, `number?` when some cells are
+/// null, or `mixed` when non-null cells disagree.
+fn combine_kinds<'a>(cells: impl Iterator<Item = &'a String>) -> String {
+    let mut kinds = Vec::new();
+    let mut nullable = false;
+    for cell in cells {
+        let kind =
+            parse_primitive_token(cell).map_or("string", |primitive| primitive_kind(&primitive));
+        if kind == "null" {
+            nullable = true;
+        } else if !kinds.contains(&kind) {
+            kinds.push(kind);
+        }
+    }
+    match kinds.as_slice() {
+        [] => "null".to_string(),
+        [kind] if nullable => format!("{kind}?"),
+        [kind] => (*kind).to_string(),
+        _ => "mixed".to_string(),
+    }
+}
+
+const fn primitive_kind(primitive: &StringOrNumberOrBoolOrNull) -> &'static str {
+    match primitive {
+        StringOrNumberOrBoolOrNull::Null => "null",
+        StringOrNumberOrBoolOrNull::Bool(_) => "bool",
+        StringOrNumberOrBoolOrNull::Number(_)
+        | StringOrNumberOrBoolOrNull::Int(_)
+        | StringOrNumberOrBoolOrNull::UInt(_)
+        | StringOrNumberOrBoolOrNull::NumberLexeme(_) => "number",
+        StringOrNumberOrBoolOrNull::String(_) => "string",
+    }
+}
+
+fn size_note(text: &str) -> String {
+    format!(
+        " · {} · ~{}",
+        plural(text.len(), "byte"),
+        plural(estimate_tokens(text), "token")
+    )
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("{count} {noun}")
+    } else {
+        format!("{count} {noun}s")
+    }
+}
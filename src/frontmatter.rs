@@ -0,0 +1,88 @@
+//! TOON frontmatter in markdown documents.
+//!
+//! Prompt templates often mix prose with structured data. A markdown file can
+//! carry that data as TOON between a `---toon` line and a `---` line at the
+//! top of the file:
+//!
+//! ```text
+//! ---toon
+//! model: small
+//! temperature: 0.2
+//! ---
+//! # Prompt
+//! ...
+//! ```
+
+use crate::error::{Result, ToonError};
+use crate::options::{DecodeOptions, EncodeOptions};
+use crate::{JsonValue, try_decode, try_encode};
+
+/// First line of a TOON frontmatter block.
+pub const FRONTMATTER_OPEN: &str = "---toon";
+/// Line ending a frontmatter block.
+pub const FRONTMATTER_CLOSE: &str = "---";
+
+/// Encode `value` as TOON frontmatter followed by the markdown `body`.
+///
+/// # Errors
+///
+/// Returns an error if the value cannot be encoded.
+pub fn to_markdown_frontmatter(
+    value: impl Into<JsonValue>,
+    body: &str,
+    options: Option<EncodeOptions>,
+) -> Result<String> {
+    let toon = try_encode(value, options)?;
+    let mut doc = String::with_capacity(toon.len() + body.len() + 16);
+    doc.push_str(FRONTMATTER_OPEN);
+    doc.push('\n');
+    if !toon.is_empty() {
+        doc.push_str(&toon);
+        doc.push('\n');
+    }
+    doc.push_str(FRONTMATTER_CLOSE);
+    doc.push('\n');
+    doc.push_str(body);
+    Ok(doc)
+}
+
+/// Decode the TOON frontmatter of a markdown document, returning the value
+/// and the body that follows it.
+///
+/// # Errors
+///
+/// Returns an error if the document does not start with a `---toon` block or
+/// the block is not valid TOON.
+pub fn from_markdown_frontmatter(
+    doc: &str,
+    options: Option<DecodeOptions>,
+) -> Result<(JsonValue, &str)> {
+    let (toon, body) = split_markdown_frontmatter(doc)
+        .ok_or_else(|| ToonError::message("Document has no ---toon frontmatter"))?;
+    let value = try_decode(toon, options)?;
+    Ok((value, body))
+}
+
+/// Split a markdown document into its TOON frontmatter (without the fence
+/// lines) and body, or `None` if it has no closed `---toon` block.
+#[must_use]
+pub fn split_markdown_frontmatter(doc: &str) -> Option<(&str, &str)> {
+    let doc = doc.strip_prefix('\u{feff}').unwrap_or(doc);
+    let (first, mut rest) = doc.split_once('\n')?;
+    if first.trim_end_matches('\r') != FRONTMATTER_OPEN {
+        return None;
+    }
+    let start = rest;
+    let mut len = 0;
+    loop {
+        let Some((line, next)) = rest.split_once('\n') else {
+            return (rest.trim_end_matches('\r') == FRONTMATTER_CLOSE)
+                .then_some((&start[..len], ""));
+        };
+        if line.trim_end_matches('\r') == FRONTMATTER_CLOSE {
+            return Some((&start[..len], next));
+        }
+        len += line.len() + 1;
+        rest = next;
+    }
+}
@@ -0,0 +1,141 @@
+//! Array header syntax: `key[N|]{field,...}:`.
+//!
+//! Tools that generate or inspect TOON snippets can parse and write headers
+//! here instead of reimplementing the syntax:
+//!
+//! ```
+//! use toon::header::{HeaderBuilder, parse_header};
+//!
+//! let line = HeaderBuilder::new(2).key("users").fields(["id", "name"]).delimiter('|').build();
+//! assert_eq!(line, "users[2|]{id|name}:");
+//!
+//! let parsed = parse_header(&line).unwrap().unwrap();
+//! assert_eq!(parsed.header.length, 2);
+//! assert_eq!(HeaderBuilder::from(parsed.header).build(), line);
+//! ```
+
+pub use crate::decode::parser::{
+    ArrayHeaderInfo, ArrayHeaderParseResult, FieldName, parse_array_header_line,
+};
+pub use crate::encode::primitives::{format_header, format_typed_header};
+
+use crate::error::Result;
+use crate::options::ColumnType;
+use crate::shared::constants::DEFAULT_DELIMITER;
+
+/// Parse a header line, including field type annotations (`{id:int}`).
+/// Returns `None` if the line is not an array header.
+///
+/// # Errors
+///
+/// Returns an error for a malformed quoted key or field, or an unknown type
+/// annotation.
+pub fn parse_header(line: &str) -> Result<Option<ArrayHeaderParseResult>> {
+    let Some(mut parsed) = parse_array_header_line(line, DEFAULT_DELIMITER)? else {
+        return Ok(None);
+    };
+    if let Some(fields) = &mut parsed.header.fields {
+        crate::decode::parser::parse_field_type_annotations(fields, 1)?;
+    }
+    Ok(Some(parsed))
+}
+
+/// Builds an array header line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderBuilder {
+    pub(crate) length: usize,
+    pub(crate) key: Option<String>,
+    pub(crate) fields: Option<Vec<(String, Option<ColumnType>)>>,
+    pub(crate) delimiter: char,
+}
+
+impl HeaderBuilder {
+    /// A header for an array of `length` items, with no key or fields and
+    /// the default comma delimiter.
+    #[must_use]
+    pub const fn new(length: usize) -> Self {
+        Self {
+            length,
+            key: None,
+            fields: None,
+            delimiter: DEFAULT_DELIMITER,
+        }
+    }
+
+    #[must_use]
+    pub fn key(mut self, key: impl Into<String>) -> Self {
+        self.key = Some(key.into());
+        self
+    }
+
+    /// Make the header tabular, with these field names.
+    #[must_use]
+    pub fn fields<I, S>(mut self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.fields = Some(
+            fields
+                .into_iter()
+                .map(|field| (field.into(), None))
+                .collect(),
+        );
+        self
+    }
+
+    /// Add a field with no type annotation.
+    #[must_use]
+    pub fn field(mut self, field: impl Into<String>) -> Self {
+        self.fields
+            .get_or_insert_with(Vec::new)
+            .push((field.into(), None));
+        self
+    }
+
+    /// Add a field with a type annotation (`id:int`).
+    #[must_use]
+    pub fn typed_field(mut self, field: impl Into<String>, column_type: ColumnType) -> Self {
+        self.fields
+            .get_or_insert_with(Vec::new)
+            .push((field.into(), Some(column_type)));
+        self
+    }
+
+    #[must_use]
+    pub const fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// The header line, ending in `:`.
+    #[must_use]
+    pub fn build(&self) -> String {
+        let (names, types): (Vec<String>, Vec<Option<ColumnType>>) =
+            self.fields.iter().flatten().cloned().unzip();
+        format_typed_header(
+            self.length,
+            self.key.as_deref(),
+            self.fields.as_ref().map(|_| names.as_slice()),
+            &types,
+            self.delimiter,
+            false,
+        )
+    }
+}
+
+impl From<ArrayHeaderInfo> for HeaderBuilder {
+    fn from(info: ArrayHeaderInfo) -> Self {
+        Self {
+            length: info.length,
+            key: info.key,
+            fields: info.fields.map(|fields| {
+                fields
+                    .into_iter()
+                    .map(|field| (field.name, field.column_type))
+                    .collect()
+            }),
+            delimiter: info.delimiter,
+        }
+    }
+}
@@ -0,0 +1,235 @@
+//! Key-indexed access to a [`JsonObject`].
+//!
+//! Objects are stored as ordered `(key, value)` vectors, so every lookup is a
+//! linear scan. [`IndexedObject`] wraps an object with a key index that is
+//! built on the first lookup and kept in sync (or dropped and rebuilt) as the
+//! object is mutated, so repeated lookups on wide objects stay cheap.
+
+use std::cell::OnceCell;
+use std::collections::HashMap;
+
+use crate::{JsonObject, JsonValue};
+
+/// An object with a lazily built key index.
+///
+/// When a key appears more than once, lookups resolve to its first entry,
+/// matching [`JsonValue::get`].
+#[derive(Debug, Clone, Default)]
+pub struct IndexedObject {
+    entries: JsonObject,
+    index: OnceCell<HashMap<String, usize>>,
+}
+
+impl IndexedObject {
+    #[must_use]
+    pub const fn new(entries: JsonObject) -> Self {
+        Self {
+            entries,
+            index: OnceCell::new(),
+        }
+    }
+
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entries in insertion order.
+    #[must_use]
+    pub fn entries(&self) -> &[(String, JsonValue)] {
+        &self.entries
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> JsonObject {
+        self.entries
+    }
+
+    #[must_use]
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.position(key).is_some()
+    }
+
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        self.position(key).map(|index| &self.entries[index].1)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut JsonValue> {
+        self.position(key).map(|index| &mut self.entries[index].1)
+    }
+
+    /// Set `key`, returning the previous value. A new key is appended.
+    pub fn insert(
+        &mut self,
+        key: impl Into<String>,
+        value: impl Into<JsonValue>,
+    ) -> Option<JsonValue> {
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => Some(entry.insert(value.into())),
+            Entry::Vacant(entry) => {
+                entry.insert(value.into());
+                None
+            }
+        }
+    }
+
+    /// Remove `key`, keeping the order of the other entries.
+    ///
+    /// Removing shifts later entries, so the index is dropped and rebuilt on
+    /// the next lookup.
+    pub fn remove(&mut self, key: &str) -> Option<JsonValue> {
+        let index = self.position(key)?;
+        self.index.take();
+        Some(self.entries.remove(index).1)
+    }
+
+    /// Entry for `key`, for in-place insert-or-update.
+    pub fn entry(&mut self, key: impl Into<String>) -> Entry<'_> {
+        let key = key.into();
+        match self.position(&key) {
+            Some(index) => Entry::Occupied(OccupiedEntry {
+                object: self,
+                index,
+            }),
+            None => Entry::Vacant(VacantEntry { object: self, key }),
+        }
+    }
+
+    fn position(&self, key: &str) -> Option<usize> {
+        self.index
+            .get_or_init(|| {
+                let mut index = HashMap::with_capacity(self.entries.len());
+                for (position, (key, _)) in self.entries.iter().enumerate() {
+                    index.entry(key.clone()).or_insert(position);
+                }
+                index
+            })
+            .get(key)
+            .copied()
+    }
+
+    fn push(&mut self, key: String, value: JsonValue) -> usize {
+        let position = self.entries.len();
+        if let Some(index) = self.index.get_mut() {
+            index.insert(key.clone(), position);
+        }
+        self.entries.push((key, value));
+        position
+    }
+}
+
+impl From<JsonObject> for IndexedObject {
+    fn from(entries: JsonObject) -> Self {
+        Self::new(entries)
+    }
+}
+
+impl From<IndexedObject> for JsonValue {
+    fn from(object: IndexedObject) -> Self {
+        Self::Object(object.entries)
+    }
+}
+
+/// A view into a single key of an [`IndexedObject`].
+pub enum Entry<'a> {
+    Occupied(OccupiedEntry<'a>),
+    Vacant(VacantEntry<'a>),
+}
+
+impl<'a> Entry<'a> {
+    #[must_use]
+    pub fn key(&self) -> &str {
+        match self {
+            Self::Occupied(entry) => entry.key(),
+            Self::Vacant(entry) => &entry.key,
+        }
+    }
+
+    /// Value for the key, inserting `default` if it is absent.
+    pub fn or_insert(self, default: impl Into<JsonValue>) -> &'a mut JsonValue {
+        match self {
+            Self::Occupied(entry) => entry.into_mut(),
+            Self::Vacant(entry) => entry.insert(default.into()),
+        }
+    }
+
+    /// Value for the key, inserting the result of `default` if it is absent.
+    pub fn or_insert_with(self, default: impl FnOnce() -> JsonValue) -> &'a mut JsonValue {
+        match self {
+            Self::Occupied(entry) => entry.into_mut(),
+            Self::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Run `update` on the value if the key is present.
+    #[must_use]
+    pub fn and_modify(mut self, update: impl FnOnce(&mut JsonValue)) -> Self {
+        if let Self::Occupied(entry) = &mut self {
+            update(entry.get_mut());
+        }
+        self
+    }
+}
+
+pub struct OccupiedEntry<'a> {
+    object: &'a mut IndexedObject,
+    index: usize,
+}
+
+impl<'a> OccupiedEntry<'a> {
+    #[must_use]
+    pub fn key(&self) -> &str {
+        &self.object.entries[self.index].0
+    }
+
+    #[must_use]
+    pub fn get(&self) -> &JsonValue {
+        &self.object.entries[self.index].1
+    }
+
+    pub fn get_mut(&mut self) -> &mut JsonValue {
+        &mut self.object.entries[self.index].1
+    }
+
+    #[must_use]
+    pub fn into_mut(self) -> &'a mut JsonValue {
+        &mut self.object.entries[self.index].1
+    }
+
+    /// Replace the value, returning the previous one.
+    pub fn insert(&mut self, value: JsonValue) -> JsonValue {
+        std::mem::replace(self.get_mut(), value)
+    }
+
+    /// Remove the entry, returning its value.
+    #[must_use]
+    pub fn remove(self) -> JsonValue {
+        self.object.index.take();
+        self.object.entries.remove(self.index).1
+    }
+}
+
+pub struct VacantEntry<'a> {
+    object: &'a mut IndexedObject,
+    key: String,
+}
+
+impl<'a> VacantEntry<'a> {
+    #[must_use]
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Append the key with `value` and return a reference to the value.
+    #[allow(clippy::must_use_candidate)]
+    pub fn insert(self, value: JsonValue) -> &'a mut JsonValue {
+        let position = self.object.push(self.key, value);
+        &mut self.object.entries[position].1
+    }
+}
@@ -0,0 +1,250 @@
+//! Tabular arrays as Arrow record batches.
+//!
+//! [`read_record_batch`] turns one tabular section into a [`RecordBatch`]
+//! with a column per field, and [`write_record_batch`] writes a batch back as
+//! a tabular array, so model-facing tables can move into Polars and other
+//! Arrow-based tools without going through CSV.
+//!
+//! Column types come from typed header fields (`id:int`) when present and
+//! are otherwise inferred from the cells: whole numbers become `Int64`, other
+//! numbers `Float64`, booleans `Boolean`, all-null columns `Null`, and
+//! anything else `Utf8`. Written headers are typed, so a batch reads back
+//! with the same column types.
+
+use std::io::{BufRead, Write};
+use std::sync::Arc;
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::{
+    Float32Type, Float64Type, Int8Type, Int16Type, Int32Type, Int64Type, UInt8Type, UInt16Type,
+    UInt32Type, UInt64Type,
+};
+use arrow_array::{
+    Array, ArrayRef, BooleanArray, Float64Array, Int64Array, NullArray, RecordBatch, StringArray,
+};
+use arrow_schema::{DataType, Field, Schema};
+
+use crate::encode::primitives::encode_primitive;
+use crate::error::{Result, ToonError};
+use crate::header::HeaderBuilder;
+use crate::options::ColumnType;
+use crate::shared::constants::DEFAULT_DELIMITER;
+use crate::tabular::{TabularReader, TabularWriter};
+use crate::{JsonPrimitive, StringOrNumberOrBoolOrNull as P};
+
+/// Read the tabular array for `key` (or the first one, if `None`) into a
+/// record batch.
+///
+/// # Errors
+///
+/// Returns an error if no matching tabular array is found or a row is
+/// invalid.
+pub fn read_record_batch<R: BufRead>(reader: R, key: Option<&str>) -> Result<RecordBatch> {
+    record_batch_from_rows(TabularReader::find(reader, key)?)
+}
+
+/// Read the remaining rows of a tabular reader into a record batch.
+///
+/// # Errors
+///
+/// Returns an error if a row is invalid.
+pub fn record_batch_from_rows<R: BufRead>(mut rows: TabularReader<R>) -> Result<RecordBatch> {
+    let fields = rows.fields().to_vec();
+    let mut columns: Vec<Vec<JsonPrimitive>> = vec![Vec::new(); fields.len()];
+    for row in &mut rows {
+        for (column, cell) in columns.iter_mut().zip(row?) {
+            column.push(cell);
+        }
+    }
+
+    let mut schema_fields = Vec::with_capacity(fields.len());
+    let mut arrays = Vec::with_capacity(fields.len());
+    for (field, cells) in fields.iter().zip(columns) {
+        let array = column_array(field.column_type, cells);
+        schema_fields.push(Field::new(&field.name, array.data_type().clone(), true));
+        arrays.push(array);
+    }
+    RecordBatch::try_new(Arc::new(Schema::new(schema_fields)), arrays)
+        .map_err(|err| ToonError::message(format!("Arrow error: {err}")))
+}
+
+/// Write `batch` as a tabular array under `key`, with rows indented by
+/// `indent` spaces, and return the writer.
+///
+/// # Errors
+///
+/// Returns an error if a column has a type other than integer, float,
+/// boolean, string, or null, or if writing fails.
+pub fn write_record_batch<W: Write>(
+    writer: W,
+    key: Option<&str>,
+    batch: &RecordBatch,
+    indent: usize,
+) -> Result<W> {
+    let mut header = HeaderBuilder::new(batch.num_rows());
+    if let Some(key) = key {
+        header = header.key(key);
+    }
+    let schema = batch.schema();
+    let mut columns = Vec::with_capacity(batch.num_columns());
+    for (field, array) in schema.fields().iter().zip(batch.columns()) {
+        let (column_type, cells) = array_cells(field.name(), array)?;
+        header = match column_type {
+            Some(column_type) => header.typed_field(field.name(), column_type),
+            None => header.field(field.name()),
+        };
+        columns.push(cells);
+    }
+    if columns.is_empty() {
+        header = header.fields(Vec::<String>::new());
+    }
+
+    let mut writer = TabularWriter::new(writer, &header, indent)?;
+    let mut columns: Vec<_> = columns.into_iter().map(Vec::into_iter).collect();
+    for _ in 0..batch.num_rows() {
+        let row: Vec<JsonPrimitive> = columns
+            .iter_mut()
+            .map(|cells| cells.next().unwrap_or(P::Null))
+            .collect();
+        writer.write_row(&row)?;
+    }
+    writer.finish()
+}
+
+/// Build the Arrow array for one column of cells.
+#[allow(clippy::cast_possible_truncation)]
+fn column_array(column_type: Option<ColumnType>, cells: Vec<JsonPrimitive>) -> ArrayRef {
+    let cells: Vec<JsonPrimitive> = cells.into_iter().map(JsonPrimitive::into_number).collect();
+    let column_type = match column_type {
+        Some(ColumnType::Auto) | None => infer_column_type(&cells),
+        Some(column_type) => Some(column_type),
+    };
+    match column_type {
+        None => Arc::new(NullArray::new(cells.len())),
+        Some(ColumnType::Integer) => Arc::new(
+            cells
+                .iter()
+                .map(|cell| match cell {
+                    P::Number(number) => Some(*number as i64),
+                    P::Int(value) => Some(*value),
+                    P::UInt(value) => Some(i64::try_from(*value).unwrap_or(i64::MAX)),
+                    _ => None,
+                })
+                .collect::<Int64Array>(),
+        ),
+        Some(ColumnType::Number) => Arc::new(
+            cells
+                .iter()
+                .map(JsonPrimitive::as_f64)
+                .collect::<Float64Array>(),
+        ),
+        Some(ColumnType::Bool) => Arc::new(
+            cells
+                .iter()
+                .map(|cell| match cell {
+                    P::Bool(value) => Some(*value),
+                    _ => None,
+                })
+                .collect::<BooleanArray>(),
+        ),
+        Some(ColumnType::String | ColumnType::Auto) => Arc::new(
+            cells
+                .into_iter()
+                .map(|cell| match cell {
+                    P::Null => None,
+                    P::String(text) => Some(text),
+                    cell => Some(encode_primitive(&cell, DEFAULT_DELIMITER)),
+                })
+                .collect::<StringArray>(),
+        ),
+    }
+}
+
+/// The narrowest type that holds every non-null cell, or `None` if all
+/// cells are null.
+fn infer_column_type(cells: &[JsonPrimitive]) -> Option<ColumnType> {
+    let mut inferred = None;
+    for cell in cells {
+        let cell_type = match cell {
+            P::Null => continue,
+            P::Number(number) if number.fract() == 0.0 => ColumnType::Integer,
+            P::Int(_) => ColumnType::Integer,
+            P::Number(_) | P::UInt(_) | P::NumberLexeme(_) => ColumnType::Number,
+            P::Bool(_) => ColumnType::Bool,
+            P::String(_) => ColumnType::String,
+        };
+        inferred = Some(match (inferred, cell_type) {
+            (None, cell_type) => cell_type,
+            (Some(current), cell_type) if current == cell_type => current,
+            (
+                Some(ColumnType::Integer | ColumnType::Number),
+                ColumnType::Integer | ColumnType::Number,
+            ) => ColumnType::Number,
+            _ => ColumnType::String,
+        });
+    }
+    inferred
+}
+
+/// The cells of an Arrow column, with the header type that preserves it.
+#[allow(clippy::cast_lossless, clippy::cast_precision_loss)]
+fn array_cells(name: &str, array: &ArrayRef) -> Result<(Option<ColumnType>, Vec<JsonPrimitive>)> {
+    macro_rules! numbers {
+        ($arrow_type:ty, $column_type:expr, $primitive:expr) => {
+            (
+                Some($column_type),
+                array
+                    .as_primitive::<$arrow_type>()
+                    .iter()
+                    .map(|value| value.map_or(P::Null, $primitive))
+                    .collect(),
+            )
+        };
+    }
+    let strings = |values: &mut dyn Iterator<Item = Option<&str>>| {
+        values
+            .map(|value| value.map_or(P::Null, |value| P::String(value.to_string())))
+            .collect()
+    };
+
+    Ok(match array.data_type() {
+        DataType::Null => (None, vec![P::Null; array.len()]),
+        DataType::Int8 => numbers!(Int8Type, ColumnType::Integer, P::from),
+        DataType::Int16 => numbers!(Int16Type, ColumnType::Integer, P::from),
+        DataType::Int32 => numbers!(Int32Type, ColumnType::Integer, P::from),
+        DataType::Int64 => numbers!(Int64Type, ColumnType::Integer, P::from),
+        DataType::UInt8 => numbers!(UInt8Type, ColumnType::Integer, P::from),
+        DataType::UInt16 => numbers!(UInt16Type, ColumnType::Integer, P::from),
+        DataType::UInt32 => numbers!(UInt32Type, ColumnType::Integer, P::from),
+        DataType::UInt64 => numbers!(UInt64Type, ColumnType::Integer, P::from),
+        DataType::Float32 => numbers!(Float32Type, ColumnType::Number, |value| {
+            P::Number(f64::from(value))
+        }),
+        DataType::Float64 => numbers!(Float64Type, ColumnType::Number, P::Number),
+        DataType::Boolean => (
+            Some(ColumnType::Bool),
+            array
+                .as_boolean()
+                .iter()
+                .map(|value| value.map_or(P::Null, P::Bool))
+                .collect(),
+        ),
+        DataType::Utf8 => (
+            Some(ColumnType::String),
+            strings(&mut array.as_string::<i32>().iter()),
+        ),
+        DataType::LargeUtf8 => (
+            Some(ColumnType::String),
+            strings(&mut array.as_string::<i64>().iter()),
+        ),
+        DataType::Utf8View => (
+            Some(ColumnType::String),
+            strings(&mut array.as_string_view().iter()),
+        ),
+        data_type => {
+            return Err(ToonError::message(format!(
+                "Arrow column '{name}' has unsupported type {data_type}"
+            )));
+        }
+    })
+}
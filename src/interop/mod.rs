@@ -0,0 +1,5 @@
+//! Conversions between TOON and other data tooling.
+
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod proto_json;
@@ -0,0 +1,626 @@
+//! Protobuf's canonical JSON mapping, for gRPC payloads.
+//!
+//! Protobuf JSON has conventions a plain JSON round trip loses: 64-bit
+//! integers are strings (a value above 2^53 does not survive as an `f64`),
+//! enums are written by name, and well-known types such as `Timestamp` and
+//! `Duration` have fixed string forms. Given a [`ProtoMessage`] describing
+//! the payload, [`from_proto_json`] turns protobuf JSON into a value ready to
+//! encode, and [`to_proto_json`] turns a decoded value back into canonical
+//! protobuf JSON, so a message survives a trip through TOON-based prompting
+//! even when a model writes `id: 42` or an enum's number instead of its name.
+//!
+//! Both directions apply the same rules:
+//!
+//! - `int64`/`uint64` become decimal strings; a number is accepted only if
+//!   it is exact.
+//! - `int32`/`uint32` become numbers, from a number or a numeric string.
+//! - Enum numbers become their names; unknown numbers are kept.
+//! - Timestamps are normalized to UTC (`Z`), durations to 0, 3, 6, or 9
+//!   fractional digits, and bytes to padded standard base64.
+//! - Fields are written under their JSON name (`userId`) but read under
+//!   either name, and `null` fields are dropped.
+//!
+//! Wrapper types (`Int64Value`, ...) map to the type they wrap, `FieldMask`
+//! to [`ProtoType::String`], and `Struct`, `Value`, `ListValue`, and `Any` to
+//! [`ProtoType::Json`].
+//!
+//! ```
+//! use toon::interop::proto_json::{ProtoMessage, ProtoType, encode_proto_json};
+//!
+//! let message = ProtoMessage::new()
+//!     .field("user_id", ProtoType::Int64)
+//!     .field("created_at", ProtoType::Timestamp);
+//! let toon = encode_proto_json(
+//!     r#"{"userId": "9007199254740993", "createdAt": "2024-05-01T12:00:00+02:00"}"#,
+//!     &message,
+//!     None,
+//! )?;
+//! assert_eq!(toon, "userId: \"9007199254740993\"\ncreatedAt: \"2024-05-01T10:00:00Z\"");
+//! # Ok::<(), toon::error::ToonError>(())
+//! ```
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::error::{Result, ToonError};
+use crate::options::{DecodeOptions, EncodeOptions, PathSegment, format_path};
+use crate::shared::constants::MAX_EXACT_INTEGER;
+use crate::{JsonValue, StringOrNumberOrBoolOrNull as P, try_decode, try_encode};
+
+/// The JSON-mapped type of a protobuf field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProtoType {
+    /// `int64`, `sint64`, `sfixed64`: a decimal string.
+    Int64,
+    /// `uint64`, `fixed64`: a decimal string.
+    UInt64,
+    /// `int32`, `sint32`, `sfixed32`.
+    Int32,
+    /// `uint32`, `fixed32`.
+    UInt32,
+    /// `double` and `float`: a number, or `"NaN"`, `"Infinity"`, or
+    /// `"-Infinity"`.
+    Double,
+    Bool,
+    String,
+    /// `bytes`: base64, standard or URL-safe.
+    Bytes,
+    Enum(ProtoEnum),
+    Message(ProtoMessage),
+    /// A `repeated` field of this type.
+    Repeated(Box<Self>),
+    /// A `map<_, V>` field whose values have this type. Map keys are
+    /// strings in JSON whatever their protobuf type.
+    Map(Box<Self>),
+    /// `google.protobuf.Timestamp`: an RFC 3339 string.
+    Timestamp,
+    /// `google.protobuf.Duration`: seconds with an `s` suffix (`"1.5s"`).
+    Duration,
+    /// Passed through unchanged, for `Struct`, `Value`, `ListValue`, and
+    /// `Any`.
+    Json,
+}
+
+/// The values of a protobuf enum.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProtoEnum {
+    pub values: Vec<(String, i32)>,
+}
+
+impl ProtoEnum {
+    #[must_use]
+    pub fn new<I, S>(values: I) -> Self
+    where
+        I: IntoIterator<Item = (S, i32)>,
+        S: Into<String>,
+    {
+        Self {
+            values: values
+                .into_iter()
+                .map(|(name, number)| (name.into(), number))
+                .collect(),
+        }
+    }
+
+    fn name_of(&self, number: i32) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(_, value)| *value == number)
+            .map(|(name, _)| name.as_str())
+    }
+
+    fn has_name(&self, name: &str) -> bool {
+        self.values.iter().any(|(value, _)| value == name)
+    }
+}
+
+/// One field of a message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtoField {
+    /// The name in the `.proto` file (`user_id`).
+    pub name: String,
+    /// The name in JSON (`userId`), used for output.
+    pub json_name: String,
+    pub ty: ProtoType,
+}
+
+impl ProtoField {
+    /// A field whose JSON name is derived from `name` as protoc derives it.
+    #[must_use]
+    pub fn new(name: impl Into<String>, ty: ProtoType) -> Self {
+        let name = name.into();
+        Self {
+            json_name: json_name(&name),
+            name,
+            ty,
+        }
+    }
+}
+
+/// The fields of a message.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProtoMessage {
+    pub fields: Vec<ProtoField>,
+    /// Pass fields that are not listed through unchanged instead of
+    /// rejecting them.
+    pub allow_unknown: bool,
+}
+
+impl ProtoMessage {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a field named as in the `.proto` file.
+    #[must_use]
+    pub fn field(mut self, name: impl Into<String>, ty: ProtoType) -> Self {
+        self.fields.push(ProtoField::new(name, ty));
+        self
+    }
+
+    /// Pass unlisted fields through instead of rejecting them.
+    #[must_use]
+    pub const fn allow_unknown(mut self) -> Self {
+        self.allow_unknown = true;
+        self
+    }
+
+    fn find(&self, key: &str) -> Option<usize> {
+        self.fields
+            .iter()
+            .position(|field| field.json_name == key || field.name == key)
+    }
+}
+
+/// Convert a protobuf JSON payload into a value for encoding.
+///
+/// # Errors
+///
+/// Returns an error naming the path of the first value that does not fit
+/// `message`.
+pub fn from_proto_json(value: &serde_json::Value, message: &ProtoMessage) -> Result<JsonValue> {
+    convert_message(value, message, &mut Vec::new())
+}
+
+/// Convert a decoded value back into canonical protobuf JSON.
+///
+/// # Errors
+///
+/// Returns an error naming the path of the first value that does not fit
+/// `message`.
+pub fn to_proto_json(value: &JsonValue, message: &ProtoMessage) -> Result<serde_json::Value> {
+    convert_message(value, message, &mut Vec::new())
+}
+
+/// Parse a protobuf JSON payload and encode it as TOON.
+///
+/// # Errors
+///
+/// Returns an error if `json` is not valid JSON, does not fit `message`, or
+/// cannot be encoded.
+pub fn encode_proto_json(
+    json: &str,
+    message: &ProtoMessage,
+    options: Option<EncodeOptions>,
+) -> Result<String> {
+    let value: serde_json::Value =
+        serde_json::from_str(json).map_err(|err| ToonError::json_parse(&err))?;
+    try_encode(from_proto_json(&value, message)?, options)
+}
+
+/// Decode TOON into a canonical protobuf JSON payload.
+///
+/// # Errors
+///
+/// Returns an error if `toon` cannot be decoded or does not fit `message`.
+pub fn decode_proto_json(
+    toon: &str,
+    message: &ProtoMessage,
+    options: Option<DecodeOptions>,
+) -> Result<serde_json::Value> {
+    to_proto_json(&try_decode(toon, options)?, message)
+}
+
+/// protoc's JSON name: underscores removed, each following letter
+/// capitalized.
+fn json_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut capitalize = false;
+    for ch in name.chars() {
+        if ch == '_' {
+            capitalize = true;
+        } else if capitalize {
+            out.push(ch.to_ascii_uppercase());
+            capitalize = false;
+        } else {
+            out.push(ch);
+        }
+    }
+    out
+}
+
+/// A leaf of the input, with integers from JSON text kept exact.
+enum Leaf<'a> {
+    Null,
+    Bool(bool),
+    /// An integer as written in the source JSON.
+    Integer(String),
+    Float(f64),
+    String(&'a str),
+}
+
+/// A leaf in canonical form.
+enum Canonical {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+}
+
+enum View<'a, T> {
+    Leaf(Leaf<'a>),
+    Array(&'a [T]),
+    Object(Vec<(&'a str, &'a T)>),
+}
+
+/// A value read by the conversion.
+trait Input: Sized {
+    type Output: Output;
+
+    fn view(&self) -> View<'_, Self>;
+
+    /// The value unchanged, for [`ProtoType::Json`].
+    fn pass_through(&self) -> Self::Output;
+}
+
+/// A value written by the conversion.
+trait Output: Sized {
+    fn canonical(leaf: Canonical) -> Self;
+    fn array(items: Vec<Self>) -> Self;
+    fn object(entries: Vec<(String, Self)>) -> Self;
+}
+
+impl Input for serde_json::Value {
+    type Output = JsonValue;
+
+    fn view(&self) -> View<'_, Self> {
+        match self {
+            Self::Null => View::Leaf(Leaf::Null),
+            Self::Bool(value) => View::Leaf(Leaf::Bool(*value)),
+            Self::Number(number) if number.is_i64() || number.is_u64() => {
+                View::Leaf(Leaf::Integer(number.to_string()))
+            }
+            Self::Number(number) => View::Leaf(Leaf::Float(number.as_f64().unwrap_or(f64::NAN))),
+            Self::String(value) => View::Leaf(Leaf::String(value)),
+            Self::Array(items) => View::Array(items),
+            Self::Object(map) => View::Object(map.iter().map(|(k, v)| (k.as_str(), v)).collect()),
+        }
+    }
+
+    fn pass_through(&self) -> JsonValue {
+        JsonValue::from(self)
+    }
+}
+
+impl Input for JsonValue {
+    type Output = serde_json::Value;
+
+    fn view(&self) -> View<'_, Self> {
+        match self {
+            Self::Primitive(P::Null) => View::Leaf(Leaf::Null),
+            Self::Primitive(P::Bool(value)) => View::Leaf(Leaf::Bool(*value)),
+            Self::Primitive(P::Number(value)) => View::Leaf(Leaf::Float(*value)),
+            Self::Primitive(P::Int(value)) => View::Leaf(Leaf::Integer(value.to_string())),
+            Self::Primitive(P::UInt(value)) => View::Leaf(Leaf::Integer(value.to_string())),
+            Self::Primitive(P::NumberLexeme(text)) => match P::from_number_lexeme(text) {
+                P::Number(value) => View::Leaf(Leaf::Float(value)),
+                _ => View::Leaf(Leaf::Integer(text.clone())),
+            },
+            Self::Primitive(P::String(value)) => View::Leaf(Leaf::String(value)),
+            Self::Array(items) => View::Array(items),
+            Self::Object(entries) => {
+                View::Object(entries.iter().map(|(k, v)| (k.as_str(), v)).collect())
+            }
+            #[cfg(feature = "raw")]
+            Self::Raw(text) => View::Leaf(Leaf::String(text)),
+        }
+    }
+
+    fn pass_through(&self) -> serde_json::Value {
+        serde_json::Value::from(self.clone())
+    }
+}
+
+impl Output for JsonValue {
+    fn canonical(leaf: Canonical) -> Self {
+        match leaf {
+            Canonical::Bool(value) => Self::from(value),
+            Canonical::Integer(value) => Self::from(value),
+            Canonical::Float(value) => Self::from(value),
+            Canonical::String(value) => Self::from(value),
+        }
+    }
+
+    fn array(items: Vec<Self>) -> Self {
+        Self::Array(items)
+    }
+
+    fn object(entries: Vec<(String, Self)>) -> Self {
+        Self::Object(entries)
+    }
+}
+
+impl Output for serde_json::Value {
+    fn canonical(leaf: Canonical) -> Self {
+        match leaf {
+            Canonical::Bool(value) => Self::Bool(value),
+            Canonical::Integer(value) => Self::from(value),
+            Canonical::Float(value) => serde_json::Number::from_f64(value)
+                .map_or_else(|| Self::String(float_name(value).to_string()), Self::Number),
+            Canonical::String(value) => Self::String(value),
+        }
+    }
+
+    fn array(items: Vec<Self>) -> Self {
+        Self::Array(items)
+    }
+
+    fn object(entries: Vec<(String, Self)>) -> Self {
+        Self::Object(entries.into_iter().collect())
+    }
+}
+
+fn proto_error(path: &[PathSegment], message: impl std::fmt::Display) -> ToonError {
+    ToonError::json(format!("{}: {message}", format_path(path)))
+}
+
+fn convert_message<I: Input>(
+    value: &I,
+    message: &ProtoMessage,
+    path: &mut Vec<PathSegment>,
+) -> Result<I::Output> {
+    let View::Object(entries) = value.view() else {
+        return Err(proto_error(path, "expected an object"));
+    };
+    let mut seen = vec![false; message.fields.len()];
+    let mut out = Vec::with_capacity(entries.len());
+    for (key, value) in entries {
+        path.push(PathSegment::Key(key.to_string()));
+        match message.find(key) {
+            Some(index) => {
+                if std::mem::replace(&mut seen[index], true) {
+                    return Err(proto_error(path, "field is given twice"));
+                }
+                let field = &message.fields[index];
+                if field.ty == ProtoType::Json || !matches!(value.view(), View::Leaf(Leaf::Null)) {
+                    out.push((field.json_name.clone(), convert(value, &field.ty, path)?));
+                }
+            }
+            None if message.allow_unknown => out.push((key.to_string(), value.pass_through())),
+            None => return Err(proto_error(path, "unknown field")),
+        }
+        path.pop();
+    }
+    Ok(I::Output::object(out))
+}
+
+fn convert<I: Input>(value: &I, ty: &ProtoType, path: &mut Vec<PathSegment>) -> Result<I::Output> {
+    match (ty, value.view()) {
+        (ProtoType::Json, _) => Ok(value.pass_through()),
+        (ProtoType::Message(message), _) => convert_message(value, message, path),
+        (ProtoType::Repeated(item), View::Array(items)) => {
+            let mut out = Vec::with_capacity(items.len());
+            for (index, item_value) in items.iter().enumerate() {
+                path.push(PathSegment::Index(index));
+                out.push(convert_element(item_value, item, path)?);
+                path.pop();
+            }
+            Ok(I::Output::array(out))
+        }
+        (ProtoType::Map(item), View::Object(entries)) => {
+            let mut out = Vec::with_capacity(entries.len());
+            for (key, entry) in entries {
+                path.push(PathSegment::Key(key.to_string()));
+                out.push((key.to_string(), convert_element(entry, item, path)?));
+                path.pop();
+            }
+            Ok(I::Output::object(out))
+        }
+        (ProtoType::Repeated(_), _) => Err(proto_error(path, "expected an array")),
+        (ProtoType::Map(_), _) => Err(proto_error(path, "expected an object")),
+        (_, View::Leaf(leaf)) => Ok(I::Output::canonical(
+            convert_leaf(leaf, ty).map_err(|message| proto_error(path, message))?,
+        )),
+        (_, _) => Err(proto_error(path, format!("expected {}", type_name(ty)))),
+    }
+}
+
+/// An array item or map value, which unlike a field cannot be `null`.
+fn convert_element<I: Input>(
+    value: &I,
+    ty: &ProtoType,
+    path: &mut Vec<PathSegment>,
+) -> Result<I::Output> {
+    if *ty != ProtoType::Json && matches!(value.view(), View::Leaf(Leaf::Null)) {
+        return Err(proto_error(path, "null is not allowed here"));
+    }
+    convert(value, ty, path)
+}
+
+fn convert_leaf(leaf: Leaf<'_>, ty: &ProtoType) -> std::result::Result<Canonical, String> {
+    let mismatch = || format!("expected {}", type_name(ty));
+    match (ty, leaf) {
+        (ProtoType::Int64, Leaf::String(text)) => text
+            .parse::<i64>()
+            .map(|value| Canonical::String(value.to_string()))
+            .map_err(|_| mismatch()),
+        (ProtoType::UInt64, Leaf::String(text)) => text
+            .parse::<u64>()
+            .map(|value| Canonical::String(value.to_string()))
+            .map_err(|_| mismatch()),
+        (
+            ProtoType::Int64 | ProtoType::UInt64 | ProtoType::Int32 | ProtoType::UInt32,
+            Leaf::Integer(text),
+        ) => convert_leaf(Leaf::String(&text), ty),
+        (ProtoType::Int64 | ProtoType::UInt64, Leaf::Float(value)) => {
+            if value.fract() != 0.0 || value.abs() >= MAX_EXACT_INTEGER {
+                return Err(format!(
+                    "{value} is not an exact {}; write it as a string",
+                    type_name(ty)
+                ));
+            }
+            convert_leaf(Leaf::String(&format!("{value:.0}")), ty)
+        }
+        (ProtoType::Int32 | ProtoType::UInt32, Leaf::String(text)) => {
+            small_integer(text.parse::<f64>().map_err(|_| mismatch())?, ty).map(Canonical::Integer)
+        }
+        (ProtoType::Int32 | ProtoType::UInt32, Leaf::Float(value)) => {
+            small_integer(value, ty).map(Canonical::Integer)
+        }
+        (ProtoType::Double, Leaf::Float(value)) if !value.is_finite() => {
+            Ok(Canonical::String(float_name(value).to_string()))
+        }
+        (ProtoType::Double, Leaf::Float(value)) => Ok(Canonical::Float(value)),
+        (ProtoType::Double, Leaf::Integer(text)) => text
+            .parse::<f64>()
+            .map(Canonical::Float)
+            .map_err(|_| mismatch()),
+        (ProtoType::Double, Leaf::String(text)) => match text {
+            "NaN" | "Infinity" | "-Infinity" => Ok(Canonical::String(text.to_string())),
+            _ => text
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .map(Canonical::Float)
+                .ok_or_else(mismatch),
+        },
+        (ProtoType::Bool, Leaf::Bool(value)) => Ok(Canonical::Bool(value)),
+        (ProtoType::String, Leaf::String(text)) => Ok(Canonical::String(text.to_string())),
+        (ProtoType::Bytes, Leaf::String(text)) => canonical_base64(text)
+            .map(Canonical::String)
+            .ok_or_else(mismatch),
+        (ProtoType::Enum(values), Leaf::String(name)) => {
+            if values.has_name(name) {
+                Ok(Canonical::String(name.to_string()))
+            } else {
+                Err(format!("unknown enum value '{name}'"))
+            }
+        }
+        (ProtoType::Enum(values), Leaf::Integer(text)) => {
+            enum_number(values, text.parse::<f64>().map_err(|_| mismatch())?)
+        }
+        (ProtoType::Enum(values), Leaf::Float(value)) => enum_number(values, value),
+        (ProtoType::Timestamp, Leaf::String(text)) => DateTime::parse_from_rfc3339(text)
+            .map(|time| {
+                Canonical::String(
+                    time.with_timezone(&Utc)
+                        .to_rfc3339_opts(SecondsFormat::AutoSi, true),
+                )
+            })
+            .map_err(|_| mismatch()),
+        (ProtoType::Duration, Leaf::String(text)) => canonical_duration(text)
+            .map(Canonical::String)
+            .ok_or_else(mismatch),
+        _ => Err(mismatch()),
+    }
+}
+
+fn small_integer(value: f64, ty: &ProtoType) -> std::result::Result<i64, String> {
+    let (min, max) = if *ty == ProtoType::Int32 {
+        (f64::from(i32::MIN), f64::from(i32::MAX))
+    } else {
+        (0.0, f64::from(u32::MAX))
+    };
+    if value.fract() != 0.0 || value < min || value > max {
+        return Err(format!("{value} is out of range for {}", type_name(ty)));
+    }
+    #[allow(clippy::cast_possible_truncation)] // checked against the range above
+    Ok(value as i64)
+}
+
+fn enum_number(values: &ProtoEnum, number: f64) -> std::result::Result<Canonical, String> {
+    let number = small_integer(number, &ProtoType::Int32)?;
+    #[allow(clippy::cast_possible_truncation)] // an int32 by small_integer
+    let name = values.name_of(number as i32);
+    Ok(name.map_or_else(
+        || Canonical::Integer(number),
+        |name| Canonical::String(name.to_string()),
+    ))
+}
+
+const fn float_name(value: f64) -> &'static str {
+    if value.is_nan() {
+        "NaN"
+    } else if value.is_sign_negative() {
+        "-Infinity"
+    } else {
+        "Infinity"
+    }
+}
+
+/// Base64 in either alphabet, padded or not, as padded standard base64.
+fn canonical_base64(text: &str) -> Option<String> {
+    let body = text.trim_end_matches('=');
+    if body.len() % 4 == 1 || text.len() - body.len() > 2 {
+        return None;
+    }
+    let mut out = String::with_capacity(body.len() + 2);
+    for ch in body.chars() {
+        match ch {
+            'A'..='Z' | 'a'..='z' | '0'..='9' | '+' | '/' => out.push(ch),
+            '-' => out.push('+'),
+            '_' => out.push('/'),
+            _ => return None,
+        }
+    }
+    while !out.len().is_multiple_of(4) {
+        out.push('=');
+    }
+    Some(out)
+}
+
+/// A duration with its fraction written in 0, 3, 6, or 9 digits.
+fn canonical_duration(text: &str) -> Option<String> {
+    let body = text.strip_suffix('s')?;
+    let (sign, unsigned) = body
+        .strip_prefix('-')
+        .map_or(("", body), |rest| ("-", rest));
+    let (seconds, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    let all_digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+    if seconds.is_empty() || !all_digits(seconds) || !all_digits(fraction) || fraction.len() > 9 {
+        return None;
+    }
+    let seconds = seconds.parse::<u64>().ok()?;
+    let fraction = fraction.trim_end_matches('0');
+    let width = match fraction.len() {
+        0 => 0,
+        1..=3 => 3,
+        4..=6 => 6,
+        _ => 9,
+    };
+    let fraction = if width == 0 {
+        String::new()
+    } else {
+        format!(".{fraction:0<width$}")
+    };
+    Some(format!("{sign}{seconds}{fraction}s"))
+}
+
+const fn type_name(ty: &ProtoType) -> &'static str {
+    match ty {
+        ProtoType::Int64 => "int64",
+        ProtoType::UInt64 => "uint64",
+        ProtoType::Int32 => "int32",
+        ProtoType::UInt32 => "uint32",
+        ProtoType::Double => "a number",
+        ProtoType::Bool => "a boolean",
+        ProtoType::String => "a string",
+        ProtoType::Bytes => "base64 bytes",
+        ProtoType::Enum(_) => "an enum name or number",
+        ProtoType::Message(_) | ProtoType::Map(_) => "an object",
+        ProtoType::Repeated(_) => "an array",
+        ProtoType::Timestamp => "an RFC 3339 timestamp",
+        ProtoType::Duration => "a duration such as \"1.5s\"",
+        ProtoType::Json => "JSON",
+    }
+}
@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, ToonError};
+use crate::{JsonStreamEvent, JsonValue};
+
+/// One line of the JSON Lines form, e.g. `{"type":"key","key":"id","wasQuoted":false}`.
+#[derive(Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase",
+    deny_unknown_fields
+)]
+enum EventRecord {
+    StartObject,
+    EndObject,
+    StartArray {
+        length: usize,
+    },
+    EndArray,
+    Key {
+        key: String,
+        #[serde(default)]
+        was_quoted: bool,
+    },
+    Primitive {
+        value: serde_json::Value,
+    },
+    #[cfg(feature = "raw")]
+    Raw {
+        text: String,
+    },
+}
+
+/// Serialize events as JSON Lines, one record per event, each followed by a
+/// newline.
+///
+/// Records have a camelCase `type` (`startObject`, `endObject`,
+/// `startArray`, `endArray`, `key`, `primitive`) plus the event's fields.
+/// Numbers that JSON cannot represent (NaN and infinities) are written as
+/// `null`.
+#[must_use]
+pub fn events_to_jsonl(events: impl IntoIterator<Item = JsonStreamEvent>) -> String {
+    let mut out = String::new();
+    for event in events {
+        let record = match event {
+            JsonStreamEvent::StartObject => EventRecord::StartObject,
+            JsonStreamEvent::EndObject => EventRecord::EndObject,
+            JsonStreamEvent::StartArray { length } => EventRecord::StartArray { length },
+            JsonStreamEvent::EndArray => EventRecord::EndArray,
+            JsonStreamEvent::Key { key, was_quoted } => EventRecord::Key { key, was_quoted },
+            JsonStreamEvent::Primitive { value } => EventRecord::Primitive {
+                value: JsonValue::Primitive(value).into(),
+            },
+            #[cfg(feature = "raw")]
+            JsonStreamEvent::Raw { text } => EventRecord::Raw { text },
+        };
+        // An enum of strings, numbers, and JSON values always serializes.
+        out.push_str(&serde_json::to_string(&record).unwrap_or_default());
+        out.push('\n');
+    }
+    out
+}
+
+/// Parse events written by [`events_to_jsonl`]. Blank lines are skipped and
+/// a missing `wasQuoted` is read as `false`.
+///
+/// The events are not checked for balance; feed them to a decoder or
+/// [`crate::json::json_stream_from_events`] to validate their structure.
+///
+/// # Errors
+///
+/// Returns an error naming the line of the first record that is not valid
+/// JSON, has an unknown `type` or field, or has an array or object `value`.
+pub fn events_from_jsonl(text: &str) -> Result<Vec<JsonStreamEvent>> {
+    let mut events = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let invalid = |message: &dyn std::fmt::Display| {
+            ToonError::event_stream(format!("Invalid event on line {}: {message}", index + 1))
+        };
+        let record: EventRecord = serde_json::from_str(line).map_err(|err| invalid(&err))?;
+        events.push(match record {
+            EventRecord::StartObject => JsonStreamEvent::StartObject,
+            EventRecord::EndObject => JsonStreamEvent::EndObject,
+            EventRecord::StartArray { length } => JsonStreamEvent::StartArray { length },
+            EventRecord::EndArray => JsonStreamEvent::EndArray,
+            EventRecord::Key { key, was_quoted } => JsonStreamEvent::Key { key, was_quoted },
+            EventRecord::Primitive { value } => match JsonValue::from(value) {
+                JsonValue::Primitive(value) => JsonStreamEvent::Primitive { value },
+                _ => return Err(invalid(&"primitive value must not be an array or object")),
+            },
+            #[cfg(feature = "raw")]
+            EventRecord::Raw { text } => JsonStreamEvent::Raw { text },
+        });
+    }
+    Ok(events)
+}
@@ -0,0 +1,23 @@
+//! JSON output for decoded values and event streams.
+//!
+//! [`json_stringify_lines`] and [`json_stream_from_events`] return string
+//! chunks; [`write_json_value`] and [`write_json_from_events`] write straight
+//! to any [`std::io::Write`]. An `indent` of 0 produces compact JSON.
+//! [`JsonWriterSink`] writes events as a decoder pushes them.
+//! [`events_to_jsonl`] and [`events_from_jsonl`] carry the events themselves
+//! as JSON Lines, for piping between processes or replaying later.
+//!
+//! With the `serde` feature, [`JsonValue`](crate::JsonValue),
+//! [`JsonPrimitive`](crate::JsonPrimitive), and
+//! [`JsonStreamEvent`](crate::JsonStreamEvent) implement `Serialize` and
+//! `Deserialize` in the same shapes.
+
+pub mod events;
+#[cfg(feature = "serde")]
+mod serde;
+pub mod stream;
+pub mod stringify;
+
+pub use events::{events_from_jsonl, events_to_jsonl};
+pub use stream::{JsonWriterSink, json_stream_from_events, write_json_from_events};
+pub use stringify::{json_stringify_lines, write_json_value};
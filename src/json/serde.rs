@@ -0,0 +1,217 @@
+//! `Serialize` and `Deserialize` for [`JsonValue`] and [`JsonPrimitive`].
+//!
+//! Both take the shape of the JSON they stand for, so a value serialized
+//! with any serde format reads the same as through `serde_json::Value`:
+//! objects keep their entry order, integers that fit in 64 bits are exact,
+//! other numbers are `f64` with NaN and the infinities written as null,
+//! numbers kept as written are the number they spell, and raw text is a
+//! string. When a map repeats a key, the last value wins in the position of
+//! the first, as in `serde_json`.
+//!
+//! With the `arbitrary-precision` feature, `serde_json` hands numbers over
+//! as text, and a number `f64` cannot hold reads as a
+//! [`NumberLexeme`](StringOrNumberOrBoolOrNull::NumberLexeme) in canonical
+//! form. Serializing still writes the number it spells.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use ::serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use ::serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+
+use crate::{JsonPrimitive, JsonValue, StringOrNumberOrBoolOrNull};
+
+/// The key `serde_json` wraps a number in when it keeps numbers as text.
+#[cfg(feature = "arbitrary-precision")]
+const NUMBER_TOKEN: &str = "$serde_json::private::Number";
+
+impl Serialize for StringOrNumberOrBoolOrNull {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::String(value) => serializer.serialize_str(value),
+            Self::Number(value) if value.is_finite() => serializer.serialize_f64(*value),
+            Self::Int(value) => serializer.serialize_i64(*value),
+            Self::UInt(value) => serializer.serialize_u64(*value),
+            Self::NumberLexeme(text) => Self::from_number_lexeme(text).serialize(serializer),
+            Self::Bool(value) => serializer.serialize_bool(*value),
+            Self::Number(_) | Self::Null => serializer.serialize_unit(),
+        }
+    }
+}
+
+impl Serialize for JsonValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Primitive(value) => value.serialize(serializer),
+            Self::Array(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            }
+            Self::Object(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+            #[cfg(feature = "raw")]
+            Self::Raw(text) => serializer.serialize_str(text),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for StringOrNumberOrBoolOrNull {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(PrimitiveVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for JsonValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct PrimitiveVisitor;
+
+impl<'de> Visitor<'de> for PrimitiveVisitor {
+    type Value = JsonPrimitive;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON primitive")
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<JsonPrimitive, E> {
+        Ok(StringOrNumberOrBoolOrNull::Bool(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<JsonPrimitive, E> {
+        Ok(StringOrNumberOrBoolOrNull::Int(value))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<JsonPrimitive, E> {
+        Ok(StringOrNumberOrBoolOrNull::from_u64(value))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<JsonPrimitive, E> {
+        Ok(StringOrNumberOrBoolOrNull::from_f64(value))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<JsonPrimitive, E> {
+        self.visit_string(value.to_string())
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> Result<JsonPrimitive, E> {
+        Ok(StringOrNumberOrBoolOrNull::String(value))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<JsonPrimitive, E> {
+        Ok(StringOrNumberOrBoolOrNull::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<JsonPrimitive, E> {
+        self.visit_unit()
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<JsonPrimitive, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    #[cfg(feature = "arbitrary-precision")]
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<JsonPrimitive, A::Error> {
+        match map.next_key::<String>()? {
+            Some(key) if key == NUMBER_TOKEN => {
+                let text: String = map.next_value()?;
+                Ok(StringOrNumberOrBoolOrNull::from_decimal(&text))
+            }
+            _ => Err(de::Error::invalid_type(de::Unexpected::Map, &self)),
+        }
+    }
+}
+
+/// Reads primitives with [`PrimitiveVisitor`], and arrays and objects.
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = JsonValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON value")
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<JsonValue, E> {
+        PrimitiveVisitor.visit_bool(value).map(JsonValue::Primitive)
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<JsonValue, E> {
+        PrimitiveVisitor.visit_i64(value).map(JsonValue::Primitive)
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<JsonValue, E> {
+        PrimitiveVisitor.visit_u64(value).map(JsonValue::Primitive)
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<JsonValue, E> {
+        PrimitiveVisitor.visit_f64(value).map(JsonValue::Primitive)
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<JsonValue, E> {
+        PrimitiveVisitor.visit_str(value).map(JsonValue::Primitive)
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> Result<JsonValue, E> {
+        PrimitiveVisitor
+            .visit_string(value)
+            .map(JsonValue::Primitive)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<JsonValue, E> {
+        PrimitiveVisitor.visit_unit().map(JsonValue::Primitive)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<JsonValue, E> {
+        self.visit_unit()
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<JsonValue, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<JsonValue, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(JsonValue::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<JsonValue, A::Error> {
+        let mut entries: Vec<(String, JsonValue)> =
+            Vec::with_capacity(map.size_hint().unwrap_or(0));
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        let Some(first_key) = map.next_key::<String>()? else {
+            return Ok(JsonValue::Object(entries));
+        };
+        #[cfg(feature = "arbitrary-precision")]
+        if first_key == NUMBER_TOKEN {
+            let text: String = map.next_value()?;
+            return Ok(JsonValue::Primitive(
+                StringOrNumberOrBoolOrNull::from_decimal(&text),
+            ));
+        }
+        positions.insert(first_key.clone(), 0);
+        entries.push((first_key, map.next_value()?));
+        while let Some((key, value)) = map.next_entry::<String, JsonValue>()? {
+            if let Some(&position) = positions.get(&key) {
+                entries[position].1 = value;
+            } else {
+                positions.insert(key.clone(), entries.len());
+                entries.push((key, value));
+            }
+        }
+        Ok(JsonValue::Object(entries))
+    }
+}
@@ -1,5 +1,9 @@
+use std::io::Write;
+
 use crate::JsonStreamEvent;
 use crate::error::{Result, ToonError};
+use crate::shared::literal_utils::is_number_lexeme;
+use crate::sink::EventSink;
 
 #[derive(Debug, Clone)]
 enum JsonContext {
@@ -18,16 +22,123 @@ enum JsonContext {
 ///
 /// Returns an error if the event stream is malformed (mismatched start/end
 /// events or primitives without keys in an object).
-#[allow(clippy::too_many_lines)]
 pub fn json_stream_from_events(
     events: impl IntoIterator<Item = JsonStreamEvent>,
     indent: usize,
 ) -> Result<Vec<String>> {
-    let mut stack: Vec<JsonContext> = Vec::new();
-    let mut depth = 0usize;
     let mut out = Vec::new();
+    emit_json_from_events(events, indent, &mut |chunk| {
+        out.push(chunk.to_string());
+        Ok(())
+    })?;
+    Ok(out)
+}
+
+/// Write JSON for an event stream to `writer` as the events arrive.
+///
+/// Output is written in small pieces, so pass a buffered writer.
+///
+/// # Errors
+///
+/// Returns an error if the event stream is malformed or writing fails.
+pub fn write_json_from_events<W: Write>(
+    writer: &mut W,
+    events: impl IntoIterator<Item = JsonStreamEvent>,
+    indent: usize,
+) -> Result<()> {
+    emit_json_from_events(events, indent, &mut |chunk| {
+        writer
+            .write_all(chunk.as_bytes())
+            .map_err(|e| ToonError::io("Failed to write JSON output", None, e))
+    })
+}
 
+fn emit_json_from_events(
+    events: impl IntoIterator<Item = JsonStreamEvent>,
+    indent: usize,
+    emit: &mut impl FnMut(&str) -> Result<()>,
+) -> Result<()> {
+    let mut state = JsonState::new(indent);
     for event in events {
+        state.push(event, emit)?;
+    }
+    state.finish()
+}
+
+/// An [`EventSink`] that writes JSON for the events to a writer as they
+/// arrive.
+///
+/// Output is written in small pieces, so pass a buffered writer.
+#[derive(Debug)]
+pub struct JsonWriterSink<W: Write> {
+    writer: W,
+    state: JsonState,
+}
+
+impl<W: Write> JsonWriterSink<W> {
+    /// Write JSON with `indent` spaces per level (0 for compact output).
+    pub const fn new(writer: W, indent: usize) -> Self {
+        Self {
+            writer,
+            state: JsonState::new(indent),
+        }
+    }
+
+    /// Check that the events formed a complete value and return the writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if objects or arrays were left open.
+    pub fn finish(self) -> Result<W> {
+        self.state.finish()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> EventSink for JsonWriterSink<W> {
+    fn event(&mut self, event: JsonStreamEvent) -> Result<()> {
+        let writer = &mut self.writer;
+        self.state.push(event, &mut |chunk| {
+            writer
+                .write_all(chunk.as_bytes())
+                .map_err(|e| ToonError::io("Failed to write JSON output", None, e))
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct JsonState {
+    stack: Vec<JsonContext>,
+    depth: usize,
+    indent: usize,
+}
+
+impl JsonState {
+    const fn new(indent: usize) -> Self {
+        Self {
+            stack: Vec::new(),
+            depth: 0,
+            indent,
+        }
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn push(
+        &mut self,
+        event: JsonStreamEvent,
+        emit: &mut impl FnMut(&str) -> Result<()>,
+    ) -> Result<()> {
+        // JSON has no raw text; it is written as the string it holds.
+        #[cfg(feature = "raw")]
+        let event = match event {
+            JsonStreamEvent::Raw { text } => JsonStreamEvent::Primitive {
+                value: crate::StringOrNumberOrBoolOrNull::String(text),
+            },
+            event => event,
+        };
+        let indent = self.indent;
+        let stack = &mut self.stack;
+        let depth = &mut self.depth;
         let parent = stack.last_mut();
         match event {
             JsonStreamEvent::StartObject => {
@@ -35,23 +146,23 @@ pub fn json_stream_from_events(
                     match parent {
                         JsonContext::Array { needs_comma } => {
                             if *needs_comma {
-                                out.push(",".to_string());
+                                emit(",")?;
                             }
                             if indent > 0 {
-                                out.push("\n".to_string());
-                                out.push(" ".repeat(depth * indent));
+                                emit("\n")?;
+                                emit(&" ".repeat(*depth * indent))?;
                             }
                         }
                         JsonContext::Object { .. } => {}
                     }
                 }
 
-                out.push("{".to_string());
+                emit("{")?;
                 stack.push(JsonContext::Object {
                     needs_comma: false,
                     expect_value: false,
                 });
-                depth += 1;
+                *depth += 1;
             }
             JsonStreamEvent::EndObject => {
                 let Some(context) = stack.pop() else {
@@ -60,16 +171,15 @@ pub fn json_stream_from_events(
                 if !matches!(context, JsonContext::Object { .. }) {
                     return Err(ToonError::message("Mismatched endObject event"));
                 }
-                depth = depth.saturating_sub(1);
-                if indent > 0 {
-                    if let JsonContext::Object { needs_comma, .. } = context {
-                        if needs_comma {
-                            out.push("\n".to_string());
-                            out.push(" ".repeat(depth * indent));
-                        }
-                    }
+                *depth = depth.saturating_sub(1);
+                if indent > 0
+                    && let JsonContext::Object { needs_comma, .. } = context
+                    && needs_comma
+                {
+                    emit("\n")?;
+                    emit(&" ".repeat(*depth * indent))?;
                 }
-                out.push("}".to_string());
+                emit("}")?;
 
                 if let Some(parent) = stack.last_mut() {
                     match parent {
@@ -91,20 +201,20 @@ pub fn json_stream_from_events(
                     match parent {
                         JsonContext::Array { needs_comma } => {
                             if *needs_comma {
-                                out.push(",".to_string());
+                                emit(",")?;
                             }
                             if indent > 0 {
-                                out.push("\n".to_string());
-                                out.push(" ".repeat(depth * indent));
+                                emit("\n")?;
+                                emit(&" ".repeat(*depth * indent))?;
                             }
                         }
                         JsonContext::Object { .. } => {}
                     }
                 }
 
-                out.push("[".to_string());
+                emit("[")?;
                 stack.push(JsonContext::Array { needs_comma: false });
-                depth += 1;
+                *depth += 1;
             }
             JsonStreamEvent::EndArray => {
                 let Some(context) = stack.pop() else {
@@ -113,16 +223,15 @@ pub fn json_stream_from_events(
                 if !matches!(context, JsonContext::Array { .. }) {
                     return Err(ToonError::message("Mismatched endArray event"));
                 }
-                depth = depth.saturating_sub(1);
-                if indent > 0 {
-                    if let JsonContext::Array { needs_comma } = context {
-                        if needs_comma {
-                            out.push("\n".to_string());
-                            out.push(" ".repeat(depth * indent));
-                        }
-                    }
+                *depth = depth.saturating_sub(1);
+                if indent > 0
+                    && let JsonContext::Array { needs_comma } = context
+                    && needs_comma
+                {
+                    emit("\n")?;
+                    emit(&" ".repeat(*depth * indent))?;
                 }
-                out.push("]".to_string());
+                emit("]")?;
 
                 if let Some(parent) = stack.last_mut() {
                     match parent {
@@ -149,15 +258,15 @@ pub fn json_stream_from_events(
                 };
 
                 if *needs_comma {
-                    out.push(",".to_string());
+                    emit(",")?;
                 }
                 if indent > 0 {
-                    out.push("\n".to_string());
-                    out.push(" ".repeat(depth * indent));
+                    emit("\n")?;
+                    emit(&" ".repeat(*depth * indent))?;
                 }
 
-                out.push(serde_json::to_string(&key).unwrap_or_else(|_| "\"\"".to_string()));
-                out.push(if indent > 0 { ": " } else { ":" }.to_string());
+                emit(&serde_json::to_string(&key).unwrap_or_else(|_| "\"\"".to_string()))?;
+                emit(if indent > 0 { ": " } else { ":" })?;
 
                 *expect_value = true;
                 *needs_comma = true;
@@ -167,11 +276,11 @@ pub fn json_stream_from_events(
                     match parent {
                         JsonContext::Array { needs_comma } => {
                             if *needs_comma {
-                                out.push(",".to_string());
+                                emit(",")?;
                             }
                             if indent > 0 {
-                                out.push("\n".to_string());
-                                out.push(" ".repeat(depth * indent));
+                                emit("\n")?;
+                                emit(&" ".repeat(*depth * indent))?;
                             }
                         }
                         JsonContext::Object { expect_value, .. } => {
@@ -184,7 +293,7 @@ pub fn json_stream_from_events(
                     }
                 }
 
-                out.push(stringify_primitive(&value));
+                emit(&stringify_primitive(&value))?;
 
                 if let Some(parent) = stack.last_mut() {
                     match parent {
@@ -197,16 +306,20 @@ pub fn json_stream_from_events(
                     }
                 }
             }
+            #[cfg(feature = "raw")]
+            JsonStreamEvent::Raw { .. } => unreachable!("raw events were rewritten above"),
         }
+        Ok(())
     }
 
-    if !stack.is_empty() {
-        return Err(ToonError::message(
-            "Incomplete event stream: unclosed objects or arrays",
-        ));
+    fn finish(&self) -> Result<()> {
+        if !self.stack.is_empty() {
+            return Err(ToonError::message(
+                "Incomplete event stream: unclosed objects or arrays",
+            ));
+        }
+        Ok(())
     }
-
-    Ok(out)
 }
 
 fn stringify_primitive(value: &crate::JsonPrimitive) -> String {
@@ -215,6 +328,14 @@ fn stringify_primitive(value: &crate::JsonPrimitive) -> String {
         crate::StringOrNumberOrBoolOrNull::Bool(value) => value.to_string(),
         crate::StringOrNumberOrBoolOrNull::Number(value) => serde_json::Number::from_f64(*value)
             .map_or_else(|| "null".to_string(), |num| num.to_string()),
+        crate::StringOrNumberOrBoolOrNull::Int(value) => value.to_string(),
+        crate::StringOrNumberOrBoolOrNull::UInt(value) => value.to_string(),
+        crate::StringOrNumberOrBoolOrNull::NumberLexeme(text) if is_number_lexeme(text) => {
+            text.clone()
+        }
+        crate::StringOrNumberOrBoolOrNull::NumberLexeme(text) => {
+            stringify_primitive(&crate::StringOrNumberOrBoolOrNull::from_number_lexeme(text))
+        }
         crate::StringOrNumberOrBoolOrNull::String(value) => {
             serde_json::to_string(value).unwrap_or_else(|_| "\"\"".to_string())
         }
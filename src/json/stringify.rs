@@ -1,6 +1,9 @@
 use std::fmt::Write;
+use std::io;
 
 use crate::JsonValue;
+use crate::error::{Result, ToonError};
+use crate::shared::literal_utils::is_number_lexeme;
 
 /// Stream JSON stringification chunks for a `JsonValue`.
 /// Returns a Vec with a single string (optimized to avoid many small allocations).
@@ -13,15 +16,37 @@ pub fn json_stringify_lines(value: &JsonValue, indent: usize) -> Vec<String> {
     vec![buf]
 }
 
+/// Write JSON for a `JsonValue` to `writer`.
+///
+/// # Errors
+///
+/// Returns an error if writing fails.
+pub fn write_json_value<W: io::Write>(
+    writer: &mut W,
+    value: &JsonValue,
+    indent: usize,
+) -> Result<()> {
+    let mut buf = String::with_capacity(estimate_json_size(value, indent));
+    stringify_value_to_buf(value, 0, indent, &mut buf);
+    writer
+        .write_all(buf.as_bytes())
+        .map_err(|e| ToonError::io("Failed to write JSON output", None, e))
+}
+
 /// Estimate the JSON output size for pre-allocation
 fn estimate_json_size(value: &JsonValue, indent: usize) -> usize {
     match value {
         JsonValue::Primitive(p) => match p {
             crate::StringOrNumberOrBoolOrNull::Null => 4,
             crate::StringOrNumberOrBoolOrNull::Bool(_) => 5,
-            crate::StringOrNumberOrBoolOrNull::Number(_) => 20,
+            crate::StringOrNumberOrBoolOrNull::Number(_)
+            | crate::StringOrNumberOrBoolOrNull::Int(_)
+            | crate::StringOrNumberOrBoolOrNull::UInt(_) => 20,
+            crate::StringOrNumberOrBoolOrNull::NumberLexeme(text) => text.len(),
             crate::StringOrNumberOrBoolOrNull::String(s) => s.len() + 10,
         },
+        #[cfg(feature = "raw")]
+        JsonValue::Raw(text) => text.len() + 10,
         JsonValue::Array(items) => {
             let base = items
                 .iter()
@@ -46,6 +71,8 @@ fn stringify_value_to_buf(value: &JsonValue, depth: usize, indent: usize, buf: &
         }
         JsonValue::Array(values) => stringify_array_to_buf(values, depth, indent, buf),
         JsonValue::Object(entries) => stringify_object_to_buf(entries, depth, indent, buf),
+        #[cfg(feature = "raw")]
+        JsonValue::Raw(text) => push_json_string(buf, text),
     }
 }
 
@@ -131,6 +158,19 @@ fn stringify_primitive_to_buf(value: &crate::JsonPrimitive, buf: &mut String) {
                 buf.push_str("null");
             }
         }
+        crate::StringOrNumberOrBoolOrNull::Int(n) => {
+            let _ = write!(buf, "{n}");
+        }
+        crate::StringOrNumberOrBoolOrNull::UInt(n) => {
+            let _ = write!(buf, "{n}");
+        }
+        crate::StringOrNumberOrBoolOrNull::NumberLexeme(text) if is_number_lexeme(text) => {
+            buf.push_str(text);
+        }
+        crate::StringOrNumberOrBoolOrNull::NumberLexeme(text) => stringify_primitive_to_buf(
+            &crate::StringOrNumberOrBoolOrNull::from_number_lexeme(text),
+            buf,
+        ),
         crate::StringOrNumberOrBoolOrNull::String(s) => {
             push_json_string(buf, s);
         }
@@ -0,0 +1,161 @@
+//! JSON-RPC over stdio (`toon --jsonrpc`).
+//!
+//! Reads JSON-RPC 2.0 requests from stdin, one message per line, and writes
+//! one reply line per request, in order. An editor or other process can
+//! keep one `toon` running instead of starting one per conversion.
+//! [`run_jsonrpc_parallel`] answers requests on several threads, for clients
+//! that send many requests without waiting for each reply.
+//!
+//! Methods:
+//! - `encode` with `{"value": <json>, "options": {...}}` returns `{"toon": "..."}`
+//! - `decode` with `{"toon": "...", "options": {...}}` returns `{"value": <json>}`
+//! - `stats` with `{"toon": "...", "options": {...}}` returns the `toon stats` report
+//!
+//! `options` are the serde forms of [`EncodeOptions`] and [`DecodeOptions`]
+//! (camelCase fields such as `keyFolding` and `expandPaths`). Messages
+//! without an `id` are notifications and get no reply.
+
+use std::io::{BufRead, Write};
+
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+
+use crate::decode::decode_into_sink;
+use crate::error::{Result, ToonError};
+use crate::options::{DecodeOptions, EncodeOptions, resolve_decode_options};
+use crate::parallel::write_ordered;
+use crate::sink::StatsSink;
+use crate::{JsonValue, try_decode, try_encode};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Encoding or decoding failed; the message is the conversion error.
+const CONVERSION_FAILED: i64 = -32000;
+
+type RpcResult<T> = std::result::Result<T, (i64, String)>;
+
+/// Answer requests from `reader` until it ends.
+///
+/// # Errors
+///
+/// Returns an error if reading or writing fails.
+pub fn run_jsonrpc(reader: impl BufRead, mut writer: impl Write) -> Result<()> {
+    let mut buffer = Vec::new();
+    for line in reader.lines() {
+        buffer.clear();
+        answer(&line?, &mut buffer)?;
+        writer.write_all(&buffer)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// Answer requests from `reader` on up to `jobs` worker threads (0: one per
+/// core), writing the replies in request order.
+///
+/// # Errors
+///
+/// Returns an error if reading or writing fails.
+pub fn run_jsonrpc_parallel(
+    reader: impl BufRead + Send,
+    mut writer: impl Write,
+    jobs: usize,
+) -> Result<()> {
+    let lines = reader.lines().map(|line| line.map_err(ToonError::from));
+    write_ordered(
+        lines,
+        jobs,
+        |line, buffer| answer(&line, buffer),
+        &mut writer,
+    )
+}
+
+/// Append the reply line to one message line, if it gets a reply.
+fn answer(line: &str, buffer: &mut Vec<u8>) -> Result<()> {
+    if line.trim().is_empty() {
+        return Ok(());
+    }
+    let reply = match serde_json::from_str(line) {
+        Ok(message) => handle(&message),
+        Err(err) => Some(reply(&Value::Null, Err((PARSE_ERROR, err.to_string())))),
+    };
+    if let Some(reply) = reply {
+        serde_json::to_writer(&mut *buffer, &reply)?;
+        buffer.push(b'\n');
+    }
+    Ok(())
+}
+
+/// Handle one message, returning the reply unless it is a notification.
+fn handle(message: &Value) -> Option<Value> {
+    let id = message.get("id").cloned();
+    let result = match message["method"].as_str() {
+        Some("encode") => encode(&message["params"]),
+        Some("decode") => decode(&message["params"]),
+        Some("stats") => stats(&message["params"]),
+        Some(method) => Err((METHOD_NOT_FOUND, format!("Unknown method: {method}"))),
+        None => Err((INVALID_REQUEST, "Missing method".to_string())),
+    };
+    Some(reply(&id?, result))
+}
+
+fn reply(id: &Value, result: RpcResult<Value>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message },
+        }),
+    }
+}
+
+fn encode(params: &Value) -> RpcResult<Value> {
+    let value = params
+        .get("value")
+        .ok_or_else(|| (INVALID_PARAMS, "Missing params.value".to_string()))?;
+    let options: EncodeOptions = parse_options(&params["options"])?;
+    let toon =
+        try_encode(JsonValue::from(value.clone()), Some(options)).map_err(|err| failed(&err))?;
+    Ok(json!({ "toon": toon }))
+}
+
+fn decode(params: &Value) -> RpcResult<Value> {
+    let toon = toon_param(params)?;
+    let options: DecodeOptions = parse_options(&params["options"])?;
+    let value = try_decode(toon, Some(options)).map_err(|err| failed(&err))?;
+    Ok(json!({ "value": Value::from(value) }))
+}
+
+fn stats(params: &Value) -> RpcResult<Value> {
+    let toon = toon_param(params)?;
+    let options = resolve_decode_options(Some(parse_options(&params["options"])?));
+    let mut stats = StatsSink::new();
+    decode_into_sink(
+        toon.split('\n').map(str::to_string),
+        Some(options.stream_options()),
+        &mut stats,
+    )
+    .map_err(|err| failed(&err))?;
+    Ok(Value::from(stats.to_json()))
+}
+
+fn toon_param(params: &Value) -> RpcResult<&str> {
+    params["toon"]
+        .as_str()
+        .ok_or_else(|| (INVALID_PARAMS, "Missing params.toon string".to_string()))
+}
+
+/// Deserialize an `options` object; a missing object gives the defaults.
+fn parse_options<T: DeserializeOwned + Default>(options: &Value) -> RpcResult<T> {
+    if options.is_null() {
+        return Ok(T::default());
+    }
+    T::deserialize(options).map_err(|err| (INVALID_PARAMS, format!("Invalid options: {err}")))
+}
+
+fn failed(err: &ToonError) -> (i64, String) {
+    (CONVERSION_FAILED, err.to_string())
+}
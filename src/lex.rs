@@ -0,0 +1,185 @@
+//! Lexical token stream for TOON text.
+//!
+//! [`lex_toon`] splits a document into tokens with byte spans (indentation,
+//! keys, colons, array headers, delimiters, values, and row cells) without
+//! decoding it, so partial or invalid documents still lex. It is meant for
+//! highlighters and editor integrations that need the lexical structure but
+//! not a full grammar.
+
+use std::ops::Range;
+
+use crate::decode::parser::parse_array_header_line;
+use crate::shared::constants::{
+    CLOSE_BRACE, CLOSE_BRACKET, COLON, DEFAULT_DELIMITER, DOUBLE_QUOTE, LIST_ITEM_MARKER,
+    LIST_ITEM_PREFIX, OPEN_BRACE, OPEN_BRACKET,
+};
+use crate::shared::string_utils::{find_closing_quote, find_unquoted_char};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// Leading spaces of a line.
+    Indent,
+    /// The `-` of a list item.
+    ListMarker,
+    /// An object key, including its quotes if quoted.
+    Key,
+    /// The `:` after a key or array header.
+    Colon,
+    /// Array header brackets and field list: `[N]`, `[N|]`, `[N]{a,b}`.
+    Header,
+    /// A delimiter between inline array values or row cells.
+    Delimiter,
+    /// The value after a key or list marker.
+    Value,
+    /// An inline array value or a tabular row cell.
+    Cell,
+}
+
+impl TokenKind {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Indent => "indent",
+            Self::ListMarker => "list_marker",
+            Self::Key => "key",
+            Self::Colon => "colon",
+            Self::Header => "header",
+            Self::Delimiter => "delimiter",
+            Self::Value => "value",
+            Self::Cell => "cell",
+        }
+    }
+}
+
+/// A token and its byte span in the lexed input.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Range<usize>,
+}
+
+/// Split `input` into tokens, in order.
+///
+/// Whitespace between tokens and line breaks are not tokens. Rows are split
+/// with the delimiter of the most recent array header.
+#[must_use]
+pub fn lex_toon(input: &str) -> Vec<Token> {
+    let mut lexer = Lexer {
+        tokens: Vec::new(),
+        delimiter: DEFAULT_DELIMITER,
+    };
+    let mut offset = 0;
+    for line in input.split('\n') {
+        lexer.line(line, offset);
+        offset += line.len() + 1;
+    }
+    lexer.tokens
+}
+
+struct Lexer {
+    tokens: Vec<Token>,
+    /// Delimiter of the most recent array header, applied to following rows.
+    delimiter: char,
+}
+
+impl Lexer {
+    fn line(&mut self, line: &str, offset: usize) {
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        let content = &line[indent..];
+        if content.trim().is_empty() {
+            return;
+        }
+        self.push(TokenKind::Indent, offset, 0..indent);
+
+        let mut start = indent;
+        let is_list_item = content == LIST_ITEM_MARKER || content.starts_with(LIST_ITEM_PREFIX);
+        if is_list_item {
+            self.push(TokenKind::ListMarker, offset, indent..indent + 1);
+            start = (indent + LIST_ITEM_PREFIX.len()).min(line.len());
+        }
+        let rest = &line[start..];
+
+        if let Ok(Some(parsed)) = parse_array_header_line(rest, self.delimiter)
+            && let Some(header) = header_span(rest)
+        {
+            self.delimiter = parsed.header.delimiter;
+            self.push(TokenKind::Key, offset, start..start + header.start);
+            self.push(
+                TokenKind::Header,
+                offset,
+                start + header.start..start + header.end,
+            );
+            let colon = start + header.end;
+            self.push(TokenKind::Colon, offset, colon..colon + 1);
+            self.cells(line, offset, colon + 1);
+        } else if let Some(colon) = find_unquoted_char(rest, COLON, 0) {
+            let colon = start + colon;
+            self.push(TokenKind::Key, offset, trimmed(line, start..colon));
+            self.push(TokenKind::Colon, offset, colon..colon + 1);
+            self.push(
+                TokenKind::Value,
+                offset,
+                trimmed(line, colon + 1..line.len()),
+            );
+        } else if is_list_item {
+            self.push(TokenKind::Value, offset, trimmed(line, start..line.len()));
+        } else {
+            self.cells(line, offset, start);
+        }
+    }
+
+    /// Push the delimited cells of `line[start..]` with the delimiters
+    /// between them.
+    fn cells(&mut self, line: &str, offset: usize, start: usize) {
+        if line[start..].trim().is_empty() {
+            return;
+        }
+        let mut cell_start = start;
+        loop {
+            let cell_end = find_unquoted_char(line, self.delimiter, cell_start);
+            let end = cell_end.unwrap_or(line.len());
+            self.push(TokenKind::Cell, offset, trimmed(line, cell_start..end));
+            let Some(cell_end) = cell_end else {
+                break;
+            };
+            let next = cell_end + self.delimiter.len_utf8();
+            self.push(TokenKind::Delimiter, offset, cell_end..next);
+            cell_start = next;
+        }
+    }
+
+    /// Push a token for `range` within the line at `offset`, skipping empty
+    /// ranges.
+    fn push(&mut self, kind: TokenKind, offset: usize, range: Range<usize>) {
+        if !range.is_empty() {
+            self.tokens.push(Token {
+                kind,
+                span: offset + range.start..offset + range.end,
+            });
+        }
+    }
+}
+
+/// Byte range of the `[N]{fields}` part of a header line, excluding the
+/// trailing colon.
+fn header_span(content: &str) -> Option<Range<usize>> {
+    let key_end = if content.starts_with(DOUBLE_QUOTE) {
+        find_closing_quote(content, 0)? + 1
+    } else {
+        0
+    };
+    let open = key_end + content[key_end..].find(OPEN_BRACKET)?;
+    let mut end = open + content[open..].find(CLOSE_BRACKET)? + 1;
+    if content[end..].starts_with(OPEN_BRACE) {
+        end = find_unquoted_char(content, CLOSE_BRACE, end)? + 1;
+    }
+    content[end..].starts_with(COLON).then_some(open..end)
+}
+
+/// Shrink `range` to exclude surrounding whitespace.
+fn trimmed(line: &str, range: Range<usize>) -> Range<usize> {
+    let text = &line[range.clone()];
+    let start = range.start + (text.len() - text.trim_start().len());
+    let end = range.end - (text.len() - text.trim_end().len());
+    start..end.max(start)
+}
@@ -1,24 +1,87 @@
 #![forbid(unsafe_code)]
 
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hash, Hasher};
+
+#[cfg(feature = "arbitrary-precision")]
+use crate::shared::literal_utils::{canonical_decimal, parses_exactly};
+
+pub mod anchors;
+pub mod batch;
+pub mod bench;
+pub mod capabilities;
+#[cfg(feature = "cli")]
 pub mod cli;
+pub mod codec;
 pub mod decode;
+pub mod detect;
+pub mod determinism;
+pub mod diff;
 pub mod encode;
 pub mod error;
+pub mod explain;
+pub mod frontmatter;
+pub mod header;
+pub mod indexed;
+pub mod interop;
+pub mod json;
+pub mod jsonrpc;
+pub mod lex;
+pub mod line_writer;
+pub mod lossy;
+pub mod manifest;
+pub mod merge;
 pub mod options;
+pub mod paginate;
+pub mod parallel;
+pub mod patch;
+pub mod prelude;
+pub mod render;
+pub mod schema;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg_attr(not(feature = "internals"), doc(hidden))]
 pub mod shared;
+pub mod sink;
+#[cfg(feature = "sqlite")]
+pub mod sql;
+pub mod tabular;
+pub mod tokens;
+pub mod value_ref;
+pub mod viz;
 
 #[cfg(feature = "wasm")]
 pub mod wasm;
 
+#[cfg(feature = "difftest")]
+pub mod difftest;
+
+#[cfg(feature = "lsp")]
+pub mod lsp;
+
+pub use capabilities::{Capabilities, capabilities};
+pub use codec::{Decoder, Encoder};
+#[cfg(feature = "serde")]
+pub use decode::from_toon_str;
 pub use decode::{
-    decode, decode_from_lines, decode_stream, decode_stream_sync, try_decode,
-    try_decode_from_lines, try_decode_stream, try_decode_stream_sync,
+    decode, decode_events_iter, decode_from_lines, decode_into_sink, decode_stream,
+    decode_stream_sync, decode_until, try_decode, try_decode_from_lines, try_decode_stream,
+    try_decode_stream_sync,
+};
+pub use encode::{
+    encode, encode_like, encode_lines, encode_serde, encode_stream_events, encode_with_extractions,
+    try_encode, try_encode_like, try_encode_lines, try_encode_serde, try_encode_with_extractions,
 };
-pub use encode::{encode, encode_lines, encode_stream_events};
+#[cfg(feature = "serde")]
+pub use encode::{to_toon_lines, to_toon_string};
+pub use indexed::IndexedObject;
 pub use options::{
-    DecodeOptions, DecodeStreamOptions, EncodeOptions, EncodeReplacer, ResolvedDecodeOptions,
-    ResolvedEncodeOptions,
+    DecodeOptions, DecodeProfile, DecodeStreamOptions, EncodeOptions, EncodeReplacer,
+    IncludeResolver, ResolvedDecodeOptions, ResolvedEncodeOptions, VariableResolver,
 };
+pub use sink::EventSink;
+pub use value_ref::{JsonPrimitiveRef, JsonValueRef};
 
 /// Convenience wrapper: parse JSON text and encode to TOON.
 ///
@@ -29,7 +92,7 @@ pub use options::{
 pub fn json_to_toon(json: &str) -> crate::error::Result<String> {
     let value: serde_json::Value =
         serde_json::from_str(json).map_err(|e| crate::error::ToonError::json_parse(&e))?;
-    Ok(encode(value, None))
+    try_encode_serde(&value, None)
 }
 
 /// Convenience wrapper: decode TOON and return compact JSON text.
@@ -48,31 +111,409 @@ pub type JsonPrimitive = StringOrNumberOrBoolOrNull;
 pub type JsonObject = Vec<(String, JsonValue)>;
 pub type JsonArray = Vec<JsonValue>;
 
-#[derive(Debug, Clone, PartialEq)]
+/// A JSON value with ordered object entries.
+///
+/// Values are totally ordered and hashable, so they can be used as
+/// `HashSet`/`BTreeMap` keys. Objects compare entry by entry in order: the
+/// same entries in a different order are a different value. Primitives order
+/// as `null < bool < number < string` (see [`StringOrNumberOrBoolOrNull`]).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum JsonValue {
     Primitive(JsonPrimitive),
     Array(JsonArray),
     Object(JsonObject),
+    /// TOON text the decoder did not understand, kept verbatim so it can be
+    /// written back out unchanged.
+    ///
+    /// Without strict validation, a line that is neither a field nor a list
+    /// item decodes to raw text holding the line and the lines nested under
+    /// it; in an object it is keyed by its first line. The encoder writes
+    /// the text in place of the field or item, and JSON output holds it as a
+    /// string.
+    #[cfg(feature = "raw")]
+    Raw(String),
+}
+
+impl JsonValue {
+    /// Value stored under `key`, if this is an object that has it.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&Self> {
+        match self {
+            Self::Object(entries) => entries
+                .iter()
+                .find(|(existing, _)| existing == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Mutable value stored under `key`, if this is an object that has it.
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Self> {
+        match self {
+            Self::Object(entries) => entries
+                .iter_mut()
+                .find(|(existing, _)| existing == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Set `key` on an object, returning the previous value.
+    ///
+    /// An existing key keeps its position; a new key is appended.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is not an object.
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<Self>) -> Option<Self> {
+        let key = key.into();
+        let value = value.into();
+        let Self::Object(entries) = self else {
+            panic!("cannot insert key '{key}' into a non-object JsonValue");
+        };
+        if let Some((_, existing)) = entries.iter_mut().find(|(existing, _)| *existing == key) {
+            return Some(std::mem::replace(existing, value));
+        }
+        entries.push((key, value));
+        None
+    }
+
+    /// Remove `key` from an object, keeping the order of the other entries.
+    ///
+    /// Returns `None` if the key is absent or the value is not an object.
+    pub fn remove(&mut self, key: &str) -> Option<Self> {
+        let Self::Object(entries) = self else {
+            return None;
+        };
+        let index = entries.iter().position(|(existing, _)| existing == key)?;
+        Some(entries.remove(index).1)
+    }
+
+    /// Keep only the object entries for which `keep` returns `true`.
+    ///
+    /// Does nothing if the value is not an object.
+    pub fn retain(&mut self, mut keep: impl FnMut(&str, &mut Self) -> bool) {
+        if let Self::Object(entries) = self {
+            entries.retain_mut(|(key, value)| keep(key, value));
+        }
+    }
+
+    /// Append an item to an array.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is not an array.
+    pub fn push(&mut self, value: impl Into<Self>) {
+        let Self::Array(items) = self else {
+            panic!("cannot push onto a non-array JsonValue");
+        };
+        items.push(value.into());
+    }
+
+    /// Value under `key`, inserting an empty object first if the key is
+    /// absent. An existing value is returned as-is, whatever its type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is not an object.
+    pub fn get_or_insert_object(&mut self, key: impl Into<String>) -> &mut Self {
+        let key = key.into();
+        let Self::Object(entries) = self else {
+            panic!("cannot insert key '{key}' into a non-object JsonValue");
+        };
+        let index = entries.iter().position(|(existing, _)| *existing == key);
+        let index = index.unwrap_or_else(|| {
+            entries.push((key, Self::Object(Vec::new())));
+            entries.len() - 1
+        });
+        &mut entries[index].1
+    }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// One step of a document as the decoder reads it.
+///
+/// With the `serde` feature, events serialize as the records of
+/// [`json::events_to_jsonl`], such as `{"type":"startArray","length":2}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(
+        tag = "type",
+        rename_all = "camelCase",
+        rename_all_fields = "camelCase",
+        deny_unknown_fields
+    )
+)]
 pub enum JsonStreamEvent {
     StartObject,
     EndObject,
-    StartArray { length: usize },
+    StartArray {
+        length: usize,
+    },
+    EndArray,
+    Key {
+        key: String,
+        #[cfg_attr(feature = "serde", serde(default))]
+        was_quoted: bool,
+    },
+    Primitive {
+        value: JsonPrimitive,
+    },
+    /// A line the decoder did not understand, with the lines nested under it.
+    #[cfg(feature = "raw")]
+    Raw {
+        text: String,
+    },
+}
+
+/// Version 2 of [`JsonStreamEvent`]: the events that open containers also
+/// carry where they came from, so a consumer can check counts and report
+/// locations without going back to the source lines.
+///
+/// Collect these with a [`sink::MetadataSink`]; see
+/// [`EventSink::event_with_metadata`]. Metadata is `None` for events that
+/// were not decoded from TOON, such as those produced by encoding a value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonStreamEventV2 {
+    StartObject {
+        /// 1-based line the object starts on.
+        source_line: Option<usize>,
+    },
+    EndObject,
+    StartArray {
+        length: usize,
+        /// The length written in the array header.
+        declared_length: Option<usize>,
+        /// 1-based line of the array header.
+        source_line: Option<usize>,
+    },
     EndArray,
-    Key { key: String, was_quoted: bool },
-    Primitive { value: JsonPrimitive },
+    Key {
+        key: String,
+        was_quoted: bool,
+    },
+    Primitive {
+        value: JsonPrimitive,
+    },
+    #[cfg(feature = "raw")]
+    Raw {
+        text: String,
+    },
+}
+
+impl JsonStreamEventV2 {
+    /// `event` with `metadata` attached where the event has room for it.
+    #[must_use]
+    pub fn with_metadata(event: JsonStreamEvent, metadata: sink::EventMetadata) -> Self {
+        match event {
+            JsonStreamEvent::StartObject => Self::StartObject {
+                source_line: metadata.source_line,
+            },
+            JsonStreamEvent::EndObject => Self::EndObject,
+            JsonStreamEvent::StartArray { length } => Self::StartArray {
+                length,
+                declared_length: metadata.declared_length,
+                source_line: metadata.source_line,
+            },
+            JsonStreamEvent::EndArray => Self::EndArray,
+            JsonStreamEvent::Key { key, was_quoted } => Self::Key { key, was_quoted },
+            JsonStreamEvent::Primitive { value } => Self::Primitive { value },
+            #[cfg(feature = "raw")]
+            JsonStreamEvent::Raw { text } => Self::Raw { text },
+        }
+    }
+}
+
+impl From<JsonStreamEvent> for JsonStreamEventV2 {
+    fn from(event: JsonStreamEvent) -> Self {
+        Self::with_metadata(event, sink::EventMetadata::default())
+    }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl From<JsonStreamEventV2> for JsonStreamEvent {
+    fn from(event: JsonStreamEventV2) -> Self {
+        match event {
+            JsonStreamEventV2::StartObject { .. } => Self::StartObject,
+            JsonStreamEventV2::EndObject => Self::EndObject,
+            JsonStreamEventV2::StartArray { length, .. } => Self::StartArray { length },
+            JsonStreamEventV2::EndArray => Self::EndArray,
+            JsonStreamEventV2::Key { key, was_quoted } => Self::Key { key, was_quoted },
+            JsonStreamEventV2::Primitive { value } => Self::Primitive { value },
+            #[cfg(feature = "raw")]
+            JsonStreamEventV2::Raw { text } => Self::Raw { text },
+        }
+    }
+}
+
+/// A JSON primitive.
+///
+/// Integers that fit in 64 bits are kept exactly, as [`Int`](Self::Int) or,
+/// above `i64::MAX`, [`UInt`](Self::UInt); other numbers are `f64`. In
+/// lossless number mode, numbers are kept as written instead, as
+/// [`NumberLexeme`](Self::NumberLexeme).
+///
+/// Equality, ordering and hashing compare numbers by value, whichever
+/// variant holds them (`Int(1)` equals `Number(1.0)`), and use a canonical
+/// form of floats: `-0.0` equals `0.0` and every NaN equals every other NaN,
+/// which makes the ordering total. Across kinds,
+/// `null < bool < number < string`.
+#[derive(Debug, Clone)]
 pub enum StringOrNumberOrBoolOrNull {
     String(String),
     Number(f64),
+    /// An integer that fits in `i64`.
+    Int(i64),
+    /// An integer above `i64::MAX`.
+    UInt(u64),
+    /// A number exactly as written in the source, such as `1.230`, `1e10`
+    /// or `-0`: a TOON numeric literal, which is also a JSON number. Text
+    /// that is not one is written as the number it parses as, or `null`.
+    NumberLexeme(String),
     Bool(bool),
     Null,
 }
 
+impl StringOrNumberOrBoolOrNull {
+    const fn rank(&self) -> u8 {
+        match self {
+            Self::Null => 0,
+            Self::Bool(_) => 1,
+            Self::Number(_) | Self::Int(_) | Self::UInt(_) | Self::NumberLexeme(_) => 2,
+            Self::String(_) => 3,
+        }
+    }
+
+    /// The number as an exact integer where it is one, for comparing and
+    /// hashing numbers across variants.
+    #[allow(clippy::cast_possible_truncation)]
+    fn number_key(&self) -> Option<NumberKey> {
+        match *self {
+            Self::NumberLexeme(ref text) => text.parse::<i128>().map_or_else(
+                |_| Self::from_number_lexeme(text).number_key(),
+                |value| Some(NumberKey::Exact(value)),
+            ),
+            Self::Int(value) => Some(NumberKey::Exact(i128::from(value))),
+            Self::UInt(value) => Some(NumberKey::Exact(i128::from(value))),
+            Self::Number(value)
+                if value.fract() == 0.0 && (-I128_LIMIT..I128_LIMIT).contains(&value) =>
+            {
+                Some(NumberKey::Exact(value as i128))
+            }
+            Self::Number(value) => Some(NumberKey::Float(canonical_number(value))),
+            _ => None,
+        }
+    }
+}
+
+/// 2^127: floats in `-2^127..2^127` convert to `i128` exactly.
+const I128_LIMIT: f64 = 170_141_183_460_469_231_731_687_303_715_884_105_728.0;
+
+/// A number reduced to the form its comparisons use: an integer, or a float
+/// that is not one.
+#[derive(Clone, Copy)]
+enum NumberKey {
+    Exact(i128),
+    Float(f64),
+}
+
+impl NumberKey {
+    fn cmp(self, other: Self) -> Ordering {
+        match (self, other) {
+            (Self::Exact(a), Self::Exact(b)) => a.cmp(&b),
+            (Self::Float(a), Self::Float(b)) => a.total_cmp(&b),
+            (Self::Exact(a), Self::Float(b)) => exact_cmp_float(a, b),
+            (Self::Float(a), Self::Exact(b)) => exact_cmp_float(b, a).reverse(),
+        }
+    }
+}
+
+/// Compare an integer with a float without rounding either. Casting the
+/// integer to `f64` would make distinct integers near 2^127 equal to the
+/// same float.
+#[allow(clippy::cast_possible_truncation)]
+fn exact_cmp_float(exact: i128, float: f64) -> Ordering {
+    if float.is_nan() {
+        // As `total_cmp` orders them: a NaN sorts by its sign.
+        return if float.is_sign_negative() {
+            Ordering::Greater
+        } else {
+            Ordering::Less
+        };
+    }
+    if float >= I128_LIMIT {
+        return Ordering::Less;
+    }
+    if float < -I128_LIMIT {
+        return Ordering::Greater;
+    }
+    // In range, the floor converts exactly; a float with a fraction lies
+    // strictly above its floor.
+    let floor = float.floor();
+    exact.cmp(&(floor as i128)).then(if float > floor {
+        Ordering::Less
+    } else {
+        Ordering::Equal
+    })
+}
+
+/// Collapse the `f64` values that compare equal numerically (`0.0`/`-0.0`)
+/// or not at all (NaN) onto one representative each.
+const fn canonical_number(value: f64) -> f64 {
+    if value.is_nan() {
+        f64::NAN
+    } else if value == 0.0 {
+        0.0
+    } else {
+        value
+    }
+}
+
+impl PartialEq for StringOrNumberOrBoolOrNull {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for StringOrNumberOrBoolOrNull {}
+
+impl PartialOrd for StringOrNumberOrBoolOrNull {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for StringOrNumberOrBoolOrNull {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::String(a), Self::String(b)) => a.cmp(b),
+            (Self::Bool(a), Self::Bool(b)) => a.cmp(b),
+            _ => match (self.number_key(), other.number_key()) {
+                (Some(a), Some(b)) => a.cmp(b),
+                _ => self.rank().cmp(&other.rank()),
+            },
+        }
+    }
+}
+
+impl Hash for StringOrNumberOrBoolOrNull {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rank().hash(state);
+        match self {
+            Self::String(value) => value.hash(state),
+            Self::Number(_) | Self::Int(_) | Self::UInt(_) | Self::NumberLexeme(_) => {
+                match self.number_key() {
+                    Some(NumberKey::Exact(value)) => value.hash(state),
+                    Some(NumberKey::Float(value)) => value.to_bits().hash(state),
+                    None => {}
+                }
+            }
+            Self::Bool(value) => value.hash(state),
+            Self::Null => {}
+        }
+    }
+}
+
 impl StringOrNumberOrBoolOrNull {
     #[must_use]
     pub fn from_f64(value: f64) -> Self {
@@ -84,6 +525,103 @@ impl StringOrNumberOrBoolOrNull {
         }
         Self::Number(value)
     }
+
+    /// `Int`, the variant for every `i64`.
+    #[must_use]
+    pub const fn from_i64(value: i64) -> Self {
+        Self::Int(value)
+    }
+
+    /// `Int` when `value` fits in `i64`, `UInt` above that.
+    #[must_use]
+    pub fn from_u64(value: u64) -> Self {
+        i64::try_from(value).map_or(Self::UInt(value), Self::Int)
+    }
+
+    /// The number `text` spells: exact as `Int` or `UInt` when it is an
+    /// integer that fits in 64 bits, otherwise the `f64` it parses as (NaN
+    /// if it does not), with `-0` read as `0`.
+    #[must_use]
+    pub fn from_number_lexeme(text: &str) -> Self {
+        let digits = text.strip_prefix('-').unwrap_or(text);
+        if !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit()) {
+            if let Ok(value) = text.parse::<i64>() {
+                return Self::Int(value);
+            }
+            if let Ok(value) = text.parse::<u64>() {
+                return Self::UInt(value);
+            }
+        }
+        let parsed = text.parse::<f64>().unwrap_or(f64::NAN);
+        if parsed == 0.0 {
+            Self::Number(0.0)
+        } else {
+            Self::Number(parsed)
+        }
+    }
+
+    /// The number `text` spells, without losing precision: as
+    /// [`from_number_lexeme`](Self::from_number_lexeme) where that is exact,
+    /// otherwise a `NumberLexeme` of `text` in canonical form (no exponent,
+    /// no trailing zeros).
+    #[cfg(feature = "arbitrary-precision")]
+    #[must_use]
+    pub fn from_decimal(text: &str) -> Self {
+        let number = Self::from_number_lexeme(text);
+        if matches!(number, Self::Int(_) | Self::UInt(_)) || parses_exactly(text) {
+            return number;
+        }
+        canonical_decimal(text).map_or(number, Self::NumberLexeme)
+    }
+
+    /// This value with a [`NumberLexeme`](Self::NumberLexeme) replaced by
+    /// the number it spells. With the `arbitrary-precision` feature, a
+    /// number `f64` cannot hold stays a lexeme, in canonical form (see
+    /// [`from_decimal`](Self::from_decimal)).
+    #[must_use]
+    pub fn into_number(self) -> Self {
+        match self {
+            #[cfg(feature = "arbitrary-precision")]
+            Self::NumberLexeme(text) => Self::from_decimal(&text),
+            #[cfg(not(feature = "arbitrary-precision"))]
+            Self::NumberLexeme(text) => Self::from_number_lexeme(&text),
+            other => other,
+        }
+    }
+
+    /// Whether this is a number, in any of its variants.
+    #[must_use]
+    pub const fn is_number(&self) -> bool {
+        matches!(
+            self,
+            Self::Number(_) | Self::Int(_) | Self::UInt(_) | Self::NumberLexeme(_)
+        )
+    }
+
+    /// The number as an `f64`, rounding integers beyond ±2^53 to the
+    /// nearest float; `None` if this is not a number.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Self::Number(value) => Some(value),
+            Self::Int(value) => Some(value as f64),
+            Self::UInt(value) => Some(value as f64),
+            Self::NumberLexeme(ref text) => Self::from_number_lexeme(text).as_f64(),
+            _ => None,
+        }
+    }
+
+    /// Whether this is a number with no fractional part.
+    #[must_use]
+    pub fn is_integer(&self) -> bool {
+        match *self {
+            Self::Int(_) | Self::UInt(_) => true,
+            Self::Number(value) => value.fract() == 0.0,
+            Self::NumberLexeme(ref text) => Self::from_number_lexeme(text).is_integer(),
+            _ => false,
+        }
+    }
 }
 
 impl From<StringOrNumberOrBoolOrNull> for JsonValue {
@@ -116,17 +654,89 @@ impl From<f64> for JsonValue {
     }
 }
 
-#[allow(clippy::cast_precision_loss)]
-impl From<i64> for JsonValue {
-    fn from(value: i64) -> Self {
-        Self::Primitive(StringOrNumberOrBoolOrNull::Number(value as f64))
+impl From<f32> for JsonValue {
+    fn from(value: f32) -> Self {
+        Self::from(f64::from(value))
     }
 }
 
-#[allow(clippy::use_self)]
-impl From<Vec<JsonValue>> for JsonValue {
-    fn from(value: Vec<JsonValue>) -> Self {
-        Self::Array(value)
+macro_rules! impl_from_integer {
+    ($($int:ty),*) => {
+        $(
+            /// Exact as `Int` or `UInt` when it fits in 64 bits; an `f64`
+            /// otherwise.
+            impl From<$int> for StringOrNumberOrBoolOrNull {
+                #[allow(
+                    clippy::cast_lossless,
+                    clippy::cast_precision_loss,
+                    irrefutable_let_patterns
+                )]
+                fn from(value: $int) -> Self {
+                    if let Ok(value) = i64::try_from(value) {
+                        Self::Int(value)
+                    } else if let Ok(value) = u64::try_from(value) {
+                        Self::UInt(value)
+                    } else {
+                        Self::Number(value as f64)
+                    }
+                }
+            }
+
+            impl From<$int> for JsonValue {
+                fn from(value: $int) -> Self {
+                    Self::Primitive(value.into())
+                }
+            }
+        )*
+    };
+}
+
+impl_from_integer!(
+    i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize
+);
+
+/// `None` becomes `null`.
+impl<T: Into<Self>> From<Option<T>> for JsonValue {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(
+            Self::Primitive(StringOrNumberOrBoolOrNull::Null),
+            Into::into,
+        )
+    }
+}
+
+impl<T: Into<Self>> From<Vec<T>> for JsonValue {
+    fn from(value: Vec<T>) -> Self {
+        Self::Array(value.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Clone + Into<Self>> From<&[T]> for JsonValue {
+    fn from(value: &[T]) -> Self {
+        Self::Array(value.iter().cloned().map(Into::into).collect())
+    }
+}
+
+impl<K: Into<String>, V: Into<Self>> From<BTreeMap<K, V>> for JsonValue {
+    fn from(value: BTreeMap<K, V>) -> Self {
+        Self::Object(
+            value
+                .into_iter()
+                .map(|(key, value)| (key.into(), value.into()))
+                .collect(),
+        )
+    }
+}
+
+/// Keys are sorted so the encoded output does not depend on hash order.
+impl<K: Into<String>, V: Into<Self>, S: BuildHasher> From<HashMap<K, V, S>> for JsonValue {
+    fn from(value: HashMap<K, V, S>) -> Self {
+        let mut entries: JsonObject = value
+            .into_iter()
+            .map(|(key, value)| (key.into(), value.into()))
+            .collect();
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Self::Object(entries)
     }
 }
 
@@ -136,6 +746,61 @@ impl From<JsonObject> for JsonValue {
     }
 }
 
+/// Collect `(key, value)` pairs into an object. Entries are kept in
+/// iteration order, duplicates included.
+impl FromIterator<(String, Self)> for JsonValue {
+    fn from_iter<I: IntoIterator<Item = (String, Self)>>(iter: I) -> Self {
+        Self::Object(iter.into_iter().collect())
+    }
+}
+
+/// Collect values into an array.
+impl FromIterator<Self> for JsonValue {
+    fn from_iter<I: IntoIterator<Item = Self>>(iter: I) -> Self {
+        Self::Array(iter.into_iter().collect())
+    }
+}
+
+/// Append entries to an object.
+///
+/// # Panics
+///
+/// Panics if the value is not an object.
+impl Extend<(String, Self)> for JsonValue {
+    fn extend<I: IntoIterator<Item = (String, Self)>>(&mut self, iter: I) {
+        match self {
+            Self::Object(entries) => entries.extend(iter),
+            _ => panic!("cannot extend a non-object JsonValue with entries"),
+        }
+    }
+}
+
+/// Append items to an array.
+///
+/// # Panics
+///
+/// Panics if the value is not an array.
+impl Extend<Self> for JsonValue {
+    fn extend<I: IntoIterator<Item = Self>>(&mut self, iter: I) {
+        match self {
+            Self::Array(items) => items.extend(iter),
+            _ => panic!("cannot extend a non-array JsonValue with items"),
+        }
+    }
+}
+
+impl From<&Self> for JsonValue {
+    fn from(value: &Self) -> Self {
+        value.clone()
+    }
+}
+
+impl From<&serde_json::Value> for JsonValue {
+    fn from(value: &serde_json::Value) -> Self {
+        Self::from(value.clone())
+    }
+}
+
 impl From<serde_json::Value> for JsonValue {
     fn from(value: serde_json::Value) -> Self {
         match value {
@@ -144,10 +809,7 @@ impl From<serde_json::Value> for JsonValue {
                 Self::Primitive(StringOrNumberOrBoolOrNull::Bool(value))
             }
             serde_json::Value::Number(value) => {
-                let number = value
-                    .as_f64()
-                    .unwrap_or_else(|| value.to_string().parse::<f64>().unwrap_or(f64::NAN));
-                Self::Primitive(StringOrNumberOrBoolOrNull::from_f64(number))
+                Self::Primitive(JsonPrimitiveRef::from(&value).into_primitive())
             }
             serde_json::Value::String(value) => {
                 Self::Primitive(StringOrNumberOrBoolOrNull::String(value))
@@ -174,6 +836,16 @@ impl From<JsonValue> for serde_json::Value {
                 StringOrNumberOrBoolOrNull::Number(value) => {
                     serde_json::Number::from_f64(value).map_or(Self::Null, Self::Number)
                 }
+                StringOrNumberOrBoolOrNull::Int(value) => Self::Number(value.into()),
+                StringOrNumberOrBoolOrNull::UInt(value) => Self::Number(value.into()),
+                #[cfg(feature = "arbitrary-precision")]
+                StringOrNumberOrBoolOrNull::NumberLexeme(text) => text
+                    .parse::<serde_json::Number>()
+                    .map_or(Self::Null, Self::Number),
+                #[cfg(not(feature = "arbitrary-precision"))]
+                StringOrNumberOrBoolOrNull::NumberLexeme(text) => Self::from(JsonValue::Primitive(
+                    StringOrNumberOrBoolOrNull::from_number_lexeme(&text),
+                )),
                 StringOrNumberOrBoolOrNull::Bool(value) => Self::Bool(value),
                 StringOrNumberOrBoolOrNull::Null => Self::Null,
             },
@@ -185,6 +857,8 @@ impl From<JsonValue> for serde_json::Value {
                 }
                 Self::Object(map)
             }
+            #[cfg(feature = "raw")]
+            JsonValue::Raw(text) => Self::String(text),
         }
     }
 }
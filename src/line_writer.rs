@@ -0,0 +1,255 @@
+//! Writing TOON line by line, without a value tree.
+//!
+//! [`ToonLineWriter`] is for code generators and exporters that know the
+//! shape of their output as they go: open objects and tabular arrays, write
+//! fields and rows into them, and close them again. Keys and values are
+//! quoted as the encoder would quote them, and nesting sets the indentation.
+//!
+//! ```
+//! use toon::line_writer::ToonLineWriter;
+//! use toon::StringOrNumberOrBoolOrNull::{Number, String as Str};
+//!
+//! let mut writer = ToonLineWriter::new(Vec::new());
+//! writer.write_field("name", &Str("report".into()))?;
+//! writer.begin_tabular("rows", ["id", "label"], 2)?;
+//! writer.write_row(&[Number(1.0), Str("a b".into())])?;
+//! writer.write_row(&[Number(2.0), Str("c,d".into())])?;
+//! writer.end()?;
+//! let out = String::from_utf8(writer.finish()?).unwrap();
+//! assert_eq!(out, "name: report\nrows[2]{id,label}:\n  1,a b\n  2,\"c,d\"\n");
+//! # Ok::<(), toon::error::ToonError>(())
+//! ```
+
+use std::io::Write;
+
+use crate::encode::primitives::{
+    encode_and_join_primitives, encode_key, encode_primitive, format_header,
+};
+use crate::error::{Result, ToonError};
+use crate::options::EmptyStringStyle;
+use crate::shared::constants::DEFAULT_DELIMITER;
+use crate::{JsonPrimitive, JsonPrimitiveRef};
+
+/// Writes TOON lines as they are described.
+///
+/// The document starts as the root object. Each [`begin_object`] or
+/// [`begin_tabular`] must be closed by an [`end`], and [`finish`] checks
+/// that nothing is left open.
+///
+/// [`begin_object`]: ToonLineWriter::begin_object
+/// [`begin_tabular`]: ToonLineWriter::begin_tabular
+/// [`end`]: ToonLineWriter::end
+/// [`finish`]: ToonLineWriter::finish
+#[derive(Debug)]
+pub struct ToonLineWriter<W: Write> {
+    writer: W,
+    indent: usize,
+    delimiter: char,
+    open: Vec<Section>,
+}
+
+#[derive(Debug)]
+enum Section {
+    Object {
+        key: String,
+    },
+    Tabular {
+        key: String,
+        fields: usize,
+        length: usize,
+        rows: usize,
+    },
+}
+
+impl Section {
+    fn key(&self) -> &str {
+        match self {
+            Self::Object { key } | Self::Tabular { key, .. } => key,
+        }
+    }
+}
+
+impl<W: Write> ToonLineWriter<W> {
+    /// A writer with two-space indentation and comma delimiters.
+    #[must_use]
+    pub const fn new(writer: W) -> Self {
+        Self {
+            writer,
+            indent: 2,
+            delimiter: DEFAULT_DELIMITER,
+            open: Vec::new(),
+        }
+    }
+
+    /// Indent each level by `indent` spaces.
+    #[must_use]
+    pub const fn indent(mut self, indent: usize) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Separate tabular cells with `delimiter` (`,`, `\t`, or `|`).
+    #[must_use]
+    pub const fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Write `key:` and open a nested object under it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error inside a tabular array, or if writing fails.
+    pub fn begin_object(&mut self, key: &str) -> Result<()> {
+        self.expect_object(key)?;
+        self.write_line(&format!("{}:", encode_key(key)))?;
+        self.open.push(Section::Object {
+            key: key.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Write `key: value` in the current object.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error inside a tabular array, or if writing fails.
+    pub fn write_field(&mut self, key: &str, value: &JsonPrimitive) -> Result<()> {
+        self.expect_object(key)?;
+        let value = encode_primitive(value, self.delimiter);
+        self.write_line(&format!("{}: {value}", encode_key(key)))
+    }
+
+    /// Write a tabular array header (`key[length]{fields}:`) and open the
+    /// array for exactly `length` rows.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are no fields, inside a tabular array, or
+    /// if writing fails.
+    pub fn begin_tabular<I, S>(&mut self, key: &str, fields: I, length: usize) -> Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.expect_object(key)?;
+        let fields: Vec<String> = fields.into_iter().map(Into::into).collect();
+        if fields.is_empty() {
+            return Err(ToonError::message(
+                "Tabular header needs at least one field",
+            ));
+        }
+        let header = format_header(length, Some(key), Some(&fields), self.delimiter);
+        self.write_line(&header)?;
+        self.open.push(Section::Tabular {
+            key: key.to_string(),
+            fields: fields.len(),
+            length,
+            rows: 0,
+        });
+        Ok(())
+    }
+
+    /// Write a row of the open tabular array, cells in header order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error outside a tabular array, if the row has the wrong
+    /// number of cells, if the header's row count has already been written,
+    /// or if writing fails.
+    pub fn write_row(&mut self, cells: &[JsonPrimitive]) -> Result<()> {
+        let path = self.path();
+        let Some(Section::Tabular {
+            fields,
+            length,
+            rows,
+            ..
+        }) = self.open.last_mut()
+        else {
+            return Err(ToonError::encode(
+                path,
+                "row written outside a tabular array",
+            ));
+        };
+        let path = format!("{path}[{rows}]");
+        if rows == length {
+            return Err(ToonError::encode(
+                path,
+                format!("header declares {length} rows"),
+            ));
+        }
+        if cells.len() != *fields {
+            return Err(ToonError::encode(
+                path,
+                format!("expected {fields} tabular row values, got {}", cells.len()),
+            ));
+        }
+        *rows += 1;
+        let cells: Vec<JsonPrimitiveRef> = cells.iter().map(Into::into).collect();
+        let line = encode_and_join_primitives(&cells, self.delimiter, EmptyStringStyle::Quoted);
+        self.write_line(&line)
+    }
+
+    /// Close the innermost open object or tabular array.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if nothing is open, or if a tabular array has fewer
+    /// rows than its header declares.
+    pub fn end(&mut self) -> Result<()> {
+        let path = self.path();
+        match self.open.pop() {
+            None => Err(ToonError::message(
+                "Nothing to end: no object or array is open",
+            )),
+            Some(Section::Tabular { length, rows, .. }) if rows != length => {
+                Err(ToonError::encode(
+                    path,
+                    format!("expected {length} tabular rows, but got {rows}"),
+                ))
+            }
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Check that every section was ended and flush, returning the writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an object or tabular array is still open, or if
+    /// flushing fails.
+    pub fn finish(mut self) -> Result<W> {
+        if !self.open.is_empty() {
+            return Err(ToonError::encode(self.path(), "not ended before finish"));
+        }
+        self.writer.flush().map_err(write_error)?;
+        Ok(self.writer)
+    }
+
+    fn expect_object(&self, key: &str) -> Result<()> {
+        if matches!(self.open.last(), Some(Section::Tabular { .. })) {
+            return Err(ToonError::encode(
+                self.path(),
+                format!("cannot write '{key}' inside a tabular array"),
+            ));
+        }
+        Ok(())
+    }
+
+    fn path(&self) -> String {
+        self.open
+            .iter()
+            .map(Section::key)
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    fn write_line(&mut self, content: &str) -> Result<()> {
+        let indent = " ".repeat(self.open.len() * self.indent);
+        writeln!(self.writer, "{indent}{content}").map_err(write_error)
+    }
+}
+
+fn write_error(err: std::io::Error) -> ToonError {
+    ToonError::io("Failed to write TOON output", None, err)
+}
@@ -0,0 +1,530 @@
+//! Detection of lossy transformations during conversion.
+//!
+//! TOON stores integers that fit in 64 bits exactly, other numbers as `f64`,
+//! and objects as key/value lists, so some inputs cannot survive a
+//! conversion unchanged:
+//! - number literals with more precision than `f64` can hold, other than
+//!   64-bit integers, unless the `arbitrary-precision` feature keeps them
+//!   as decimal strings
+//! - non-finite numbers, which are written as `null`
+//! - duplicate object keys, which collapse to a single entry in JSON
+//! - object keys that do not read back as written
+//! - object keys that Unicode NFC normalization merges with a sibling
+//!
+//! The scanners here report every such site so callers (for example the CLI
+//! `--fail-on-lossy` flag) can refuse to convert instead of silently
+//! degrading the data.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::decode::decoders::decode_stream_sync;
+use crate::error::{Result, ToonError};
+use crate::options::{DecodeOptions, DecodeStreamOptions, ExpandPathsMode};
+use crate::shared::constants::MAX_EXACT_INTEGER;
+use crate::shared::literal_utils::parses_exactly;
+use crate::{JsonStreamEvent, JsonValue, StringOrNumberOrBoolOrNull, try_decode, try_encode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LossyKind {
+    /// A number that is not finite and will be written as `null`.
+    NonFiniteNumber,
+    /// A number literal that does not round-trip through `f64`.
+    PrecisionLoss,
+    /// An object key that appears more than once.
+    DuplicateKey,
+    /// An object key that does not read back as written.
+    UnstableKey,
+    /// An object key that NFC normalization makes equal to a sibling key.
+    NfcKeyCollision,
+    /// Text the decoder kept verbatim, which JSON can only hold as a string.
+    #[cfg(feature = "raw")]
+    RawText,
+}
+
+impl LossyKind {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::NonFiniteNumber => "non-finite number becomes null",
+            Self::PrecisionLoss => "number loses precision as f64",
+            Self::DuplicateKey => "duplicate key collapsed",
+            Self::UnstableKey => "key does not read back as written",
+            Self::NfcKeyCollision => "key collides with a sibling under NFC",
+            #[cfg(feature = "raw")]
+            Self::RawText => "raw text becomes a string",
+        }
+    }
+}
+
+/// A location in the input where conversion would lose information.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LossySite {
+    /// Path to the value (e.g. `users[1].id`); empty for the root.
+    pub path: String,
+    pub kind: LossyKind,
+    /// The offending literal or key as it appeared in the input.
+    pub detail: String,
+}
+
+impl fmt::Display for LossySite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "(root)"
+        } else {
+            &self.path
+        };
+        write!(f, "{path}: {} ({})", self.kind.as_str(), self.detail)
+    }
+}
+
+/// Scan JSON text for values that would not survive encoding unchanged.
+///
+/// Number literals other than 64-bit integers are checked against their
+/// `f64` value digit by digit, so only literals that actually change are
+/// reported.
+///
+/// # Errors
+///
+/// Returns an error if the input is not valid JSON.
+pub fn scan_json(input: &str) -> Result<Vec<LossySite>> {
+    serde_json::from_str::<serde::de::IgnoredAny>(input)
+        .map_err(|err| ToonError::json_parse(&err))?;
+
+    let mut scanner = JsonScanner {
+        bytes: input.as_bytes(),
+        input,
+        pos: 0,
+        sites: Vec::new(),
+    };
+    scanner.value("");
+    Ok(scanner.sites)
+}
+
+/// Scan TOON text for values that would not survive decoding unchanged.
+///
+/// Number literals are checked against their `f64` value digit by digit,
+/// as in [`scan_json`], unless `options` keep numbers as written.
+///
+/// # Errors
+///
+/// Returns an error if the input cannot be decoded.
+pub fn scan_toon(input: &str, options: Option<DecodeStreamOptions>) -> Result<Vec<LossySite>> {
+    let mut options = options.unwrap_or_default();
+    let keeps_lexemes = options.lossless_numbers.unwrap_or(false);
+    // Decode numbers as written so each literal can be checked exactly,
+    // as `scan_json` does.
+    options.lossless_numbers = Some(true);
+    let lines = input.split('\n').map(str::to_string).collect::<Vec<_>>();
+    let events = decode_stream_sync(lines, Some(options))?;
+    Ok(scan_decoded(&events, keeps_lexemes))
+}
+
+/// Scan a decoded event stream for lossy sites.
+///
+/// Events carry 64-bit integers exactly and other numbers as `f64`, so
+/// precision loss is reported for `f64` integers beyond its exact range
+/// (2^53); [`scan_toon`] checks every literal instead. Numbers kept as
+/// written in lossless number mode lose nothing.
+#[must_use]
+pub fn scan_events(events: &[JsonStreamEvent]) -> Vec<LossySite> {
+    scan_decoded(events, true)
+}
+
+/// Scan `events`, checking each number lexeme against the `f64` it parses
+/// to unless the conversion keeps lexemes as written.
+fn scan_decoded(events: &[JsonStreamEvent], keeps_lexemes: bool) -> Vec<LossySite> {
+    enum Frame {
+        Object {
+            path: String,
+            seen: HashSet<String>,
+            key: Option<String>,
+        },
+        Array {
+            path: String,
+            index: usize,
+        },
+    }
+
+    fn value_path(stack: &mut [Frame]) -> String {
+        match stack.last_mut() {
+            None => String::new(),
+            Some(Frame::Object { path, key, .. }) => {
+                child_key_path(path, key.as_deref().unwrap_or_default())
+            }
+            Some(Frame::Array { path, index }) => {
+                let child = child_index_path(path, *index);
+                *index += 1;
+                child
+            }
+        }
+    }
+
+    let mut sites = Vec::new();
+    let mut stack: Vec<Frame> = Vec::new();
+
+    for event in events {
+        match event {
+            JsonStreamEvent::StartObject => {
+                let path = value_path(&mut stack);
+                stack.push(Frame::Object {
+                    path,
+                    seen: HashSet::new(),
+                    key: None,
+                });
+            }
+            JsonStreamEvent::StartArray { .. } => {
+                let path = value_path(&mut stack);
+                stack.push(Frame::Array { path, index: 0 });
+            }
+            JsonStreamEvent::EndObject | JsonStreamEvent::EndArray => {
+                stack.pop();
+            }
+            JsonStreamEvent::Key { key: name, .. } => {
+                if let Some(Frame::Object { path, seen, key }) = stack.last_mut() {
+                    if !seen.insert(name.clone()) {
+                        sites.push(LossySite {
+                            path: child_key_path(path, name),
+                            kind: LossyKind::DuplicateKey,
+                            detail: name.clone(),
+                        });
+                    }
+                    *key = Some(name.clone());
+                }
+            }
+            JsonStreamEvent::Primitive { value } => {
+                let path = value_path(&mut stack);
+                let (kind, detail) = match value {
+                    StringOrNumberOrBoolOrNull::Number(number) => {
+                        let kind = if !number.is_finite() {
+                            Some(LossyKind::NonFiniteNumber)
+                        } else if number.fract() == 0.0 && number.abs() > MAX_EXACT_INTEGER {
+                            Some(LossyKind::PrecisionLoss)
+                        } else {
+                            None
+                        };
+                        (kind, number.to_string())
+                    }
+                    StringOrNumberOrBoolOrNull::NumberLexeme(text) if !keeps_lexemes => {
+                        (number_literal_loss(text), text.clone())
+                    }
+                    _ => (None, String::new()),
+                };
+                if let Some(kind) = kind {
+                    sites.push(LossySite { path, kind, detail });
+                }
+            }
+            #[cfg(feature = "raw")]
+            JsonStreamEvent::Raw { text } => {
+                let path = value_path(&mut stack);
+                sites.push(LossySite {
+                    path,
+                    kind: LossyKind::RawText,
+                    detail: text.lines().next().unwrap_or_default().to_string(),
+                });
+            }
+        }
+    }
+
+    sites
+}
+
+/// Report every object key in `value` that would not read back as written.
+///
+/// Each distinct key is encoded as an object key, as the key of an array
+/// header, and as a tabular field, then decoded with path expansion on. Keys
+/// with brackets, braces, colons, quotes, dots, or a leading `-` are quoted,
+/// so every key the encoder writes today survives; this is the check that
+/// keeps it so.
+#[must_use]
+pub fn validate_roundtrip_keys(value: &JsonValue) -> Vec<LossySite> {
+    let mut checked = HashMap::new();
+    let mut sites = Vec::new();
+    collect_unstable_keys(value, "", &mut checked, &mut sites);
+    sites
+}
+
+fn collect_unstable_keys(
+    value: &JsonValue,
+    path: &str,
+    checked: &mut HashMap<String, bool>,
+    sites: &mut Vec<LossySite>,
+) {
+    match value {
+        JsonValue::Object(entries) => {
+            for (key, child) in entries {
+                let child_path = child_key_path(path, key);
+                let stable = *checked
+                    .entry(key.clone())
+                    .or_insert_with(|| key_round_trips(key));
+                if !stable {
+                    sites.push(LossySite {
+                        path: child_path.clone(),
+                        kind: LossyKind::UnstableKey,
+                        detail: key.clone(),
+                    });
+                }
+                collect_unstable_keys(child, &child_path, checked, sites);
+            }
+        }
+        JsonValue::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                collect_unstable_keys(item, &child_index_path(path, index), checked, sites);
+            }
+        }
+        JsonValue::Primitive(_) => {}
+        #[cfg(feature = "raw")]
+        JsonValue::Raw(_) => {}
+    }
+}
+
+/// Whether `key` survives encoding and decoding in every position a key
+/// can take.
+fn key_round_trips(key: &str) -> bool {
+    let number = |n: i64| JsonValue::Primitive(StringOrNumberOrBoolOrNull::Int(n));
+    let entry = |value: JsonValue| JsonValue::Object(vec![(key.to_string(), value)]);
+    let options = DecodeOptions {
+        expand_paths: Some(ExpandPathsMode::Safe),
+        ..DecodeOptions::default()
+    };
+    [
+        entry(number(1)),
+        entry(JsonValue::Array(vec![number(1), number(2)])),
+        JsonValue::Array(vec![entry(number(1)), entry(number(2))]),
+    ]
+    .into_iter()
+    .all(|value| {
+        try_encode(value.clone(), None)
+            .and_then(|toon| try_decode(&toon, Some(options.clone())))
+            .is_ok_and(|decoded| decoded == value)
+    })
+}
+
+/// Report every object key in `value` that Unicode NFC normalization would
+/// make equal to an earlier key of the same object, such as `e` plus a
+/// combining acute accent next to a precomposed `é`.
+///
+/// Normalizing with [`NormalizeOptions::nfc`] turns such keys into
+/// duplicates, which collapse to one entry when read back; encoding with
+/// [`EncodeOptions::nfc`] fails on the first of them.
+///
+/// [`NormalizeOptions::nfc`]: crate::encode::normalize::NormalizeOptions::nfc
+/// [`EncodeOptions::nfc`]: crate::EncodeOptions::nfc
+#[must_use]
+pub fn scan_nfc_key_collisions(value: &JsonValue) -> Vec<LossySite> {
+    let mut sites = Vec::new();
+    collect_nfc_collisions(value, "", &mut sites);
+    sites
+}
+
+fn collect_nfc_collisions(value: &JsonValue, path: &str, sites: &mut Vec<LossySite>) {
+    match value {
+        JsonValue::Object(entries) => {
+            let mut seen: HashMap<String, &str> = HashMap::new();
+            for (key, child) in entries {
+                let child_path = child_key_path(path, key);
+                let first = *seen.entry(key.nfc().collect()).or_insert(key);
+                // Keys equal as written are duplicates before normalizing,
+                // which the other scanners already report.
+                if first != key {
+                    sites.push(LossySite {
+                        path: child_path.clone(),
+                        kind: LossyKind::NfcKeyCollision,
+                        detail: key.clone(),
+                    });
+                }
+                collect_nfc_collisions(child, &child_path, sites);
+            }
+        }
+        JsonValue::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                collect_nfc_collisions(item, &child_index_path(path, index), sites);
+            }
+        }
+        JsonValue::Primitive(_) => {}
+        #[cfg(feature = "raw")]
+        JsonValue::Raw(_) => {}
+    }
+}
+
+/// Turn a list of lossy sites into an error listing all of them.
+///
+/// # Errors
+///
+/// Returns an error if `sites` is not empty.
+pub fn ensure_lossless(sites: &[LossySite]) -> Result<()> {
+    if sites.is_empty() {
+        return Ok(());
+    }
+    let mut message = format!("Lossy conversion at {} site(s):", sites.len());
+    for site in sites {
+        message.push_str("\n  ");
+        message.push_str(&site.to_string());
+    }
+    Err(ToonError::message(message))
+}
+
+/// Check a JSON number literal against the `f64` it parses to. With
+/// arbitrary precision, a finite literal is kept exactly unless its exponent
+/// is too large to write out.
+fn number_literal_loss(lexeme: &str) -> Option<LossyKind> {
+    if lexeme.parse::<i64>().is_ok() || lexeme.parse::<u64>().is_ok() {
+        return None;
+    }
+    #[cfg(feature = "arbitrary-precision")]
+    if crate::shared::literal_utils::canonical_decimal(lexeme).is_some() {
+        return None;
+    }
+    let value = lexeme.parse::<f64>().ok()?;
+    if !value.is_finite() {
+        return Some(LossyKind::NonFiniteNumber);
+    }
+    if parses_exactly(lexeme) {
+        None
+    } else {
+        Some(LossyKind::PrecisionLoss)
+    }
+}
+
+fn child_key_path(parent: &str, key: &str) -> String {
+    if parent.is_empty() {
+        key.to_string()
+    } else {
+        format!("{parent}.{key}")
+    }
+}
+
+fn child_index_path(parent: &str, index: usize) -> String {
+    format!("{parent}[{index}]")
+}
+
+/// Minimal walker over JSON text that has already been validated.
+struct JsonScanner<'a> {
+    bytes: &'a [u8],
+    input: &'a str,
+    pos: usize,
+    sites: Vec<LossySite>,
+}
+
+impl JsonScanner<'_> {
+    fn value(&mut self, path: &str) {
+        self.skip_whitespace();
+        match self.bytes.get(self.pos) {
+            Some(b'{') => self.object(path),
+            Some(b'[') => self.array(path),
+            Some(b'"') => {
+                self.string();
+            }
+            Some(b't' | b'f' | b'n') => {
+                while self
+                    .bytes
+                    .get(self.pos)
+                    .is_some_and(u8::is_ascii_alphabetic)
+                {
+                    self.pos += 1;
+                }
+            }
+            Some(_) => self.number(path),
+            None => {}
+        }
+    }
+
+    fn object(&mut self, path: &str) {
+        self.pos += 1;
+        let mut seen = HashSet::new();
+        loop {
+            self.skip_whitespace();
+            match self.bytes.get(self.pos) {
+                Some(b'}') | None => {
+                    self.pos += 1;
+                    return;
+                }
+                Some(b',') => {
+                    self.pos += 1;
+                    continue;
+                }
+                _ => {}
+            }
+            let raw_key = self.string();
+            let key = serde_json::from_str::<String>(raw_key).unwrap_or_default();
+            let child = child_key_path(path, &key);
+            if !seen.insert(key.clone()) {
+                self.sites.push(LossySite {
+                    path: child.clone(),
+                    kind: LossyKind::DuplicateKey,
+                    detail: key,
+                });
+            }
+            self.skip_whitespace();
+            self.pos += 1; // ':'
+            self.value(&child);
+        }
+    }
+
+    fn array(&mut self, path: &str) {
+        self.pos += 1;
+        let mut index = 0;
+        loop {
+            self.skip_whitespace();
+            match self.bytes.get(self.pos) {
+                Some(b']') | None => {
+                    self.pos += 1;
+                    return;
+                }
+                Some(b',') => {
+                    self.pos += 1;
+                    continue;
+                }
+                _ => {}
+            }
+            self.value(&child_index_path(path, index));
+            index += 1;
+        }
+    }
+
+    /// Consume a string literal and return it including its quotes.
+    fn string(&mut self) -> &str {
+        let start = self.pos;
+        self.pos += 1;
+        while let Some(&byte) = self.bytes.get(self.pos) {
+            self.pos += 1;
+            match byte {
+                b'\\' => self.pos += 1,
+                b'"' => break,
+                _ => {}
+            }
+        }
+        &self.input[start..self.pos.min(self.bytes.len())]
+    }
+
+    fn number(&mut self, path: &str) {
+        let start = self.pos;
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|byte| matches!(byte, b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E'))
+        {
+            self.pos += 1;
+        }
+        let lexeme = &self.input[start..self.pos];
+        if let Some(kind) = number_literal_loss(lexeme) {
+            self.sites.push(LossySite {
+                path: path.to_string(),
+                kind,
+                detail: lexeme.to_string(),
+            });
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|byte| matches!(byte, b' ' | b'\t' | b'\n' | b'\r'))
+        {
+            self.pos += 1;
+        }
+    }
+}
@@ -0,0 +1,352 @@
+//! Language Server Protocol server for `.toon` files.
+//!
+//! A small JSON-RPC server over stdio (`toon lsp`) providing:
+//! - diagnostics for the first decode error in the document
+//! - document formatting (decode, then re-encode canonically)
+//! - folding ranges for nested objects and arrays
+//! - hover showing the decoded JSON of the line under the cursor
+//!
+//! Documents are synced in full on every change.
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+use serde_json::{Value, json};
+
+use crate::decode::scanner::{ParsedLine, create_scan_state, parse_lines_sync};
+use crate::error::{Result, ToonError};
+use crate::json::json_stringify_lines;
+use crate::options::{DecodeOptions, EncodeOptions};
+use crate::shared::constants::{
+    CLOSE_BRACKET, COLON, LIST_ITEM_MARKER, LIST_ITEM_PREFIX, OPEN_BRACE, OPEN_BRACKET,
+};
+use crate::{JsonValue, try_decode, try_encode};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Indentation assumed for documents opened in the editor.
+const INDENT: usize = 2;
+
+/// Serve LSP requests from `reader` until the client sends `exit` or closes
+/// the stream.
+///
+/// # Errors
+///
+/// Returns an error if a message is malformed or the transport fails.
+pub fn run_server(mut reader: impl BufRead, mut writer: impl Write) -> Result<()> {
+    let mut server = Server::default();
+    while let Some(message) = read_message(&mut reader)? {
+        for reply in server.handle(&message) {
+            write_message(&mut writer, &reply)?;
+        }
+        if server.exited {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Read one `Content-Length` framed message, or `None` at end of input.
+fn read_message(reader: &mut impl BufRead) -> Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            length = Some(value.trim().parse::<usize>().map_err(|_| {
+                ToonError::message(format!("Invalid Content-Length header: {header}"))
+            })?);
+        }
+    }
+
+    let length = length.ok_or_else(|| ToonError::message("Missing Content-Length header"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+fn write_message(writer: &mut impl Write, message: &Value) -> Result<()> {
+    let body = serde_json::to_string(message)?;
+    write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[derive(Default)]
+struct Server {
+    documents: HashMap<String, String>,
+    exited: bool,
+}
+
+impl Server {
+    /// Handle one message, returning the responses and notifications to send.
+    fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let Some(id) = message.get("id").cloned() else {
+            return self.notify(method, params);
+        };
+
+        let result = match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "documentFormattingProvider": true,
+                    "foldingRangeProvider": true,
+                    "hoverProvider": true,
+                },
+                "serverInfo": { "name": "toon", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => Ok(Value::Null),
+            "textDocument/formatting" => self.document(params).map(formatting_edits),
+            "textDocument/foldingRange" => self.document(params).map(folding_ranges),
+            "textDocument/hover" => self.document(params).map(|text| {
+                let line = params["position"]["line"].as_u64().unwrap_or_default();
+                hover(text, usize::try_from(line).unwrap_or(usize::MAX))
+            }),
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method: {method}"))),
+        };
+
+        vec![match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            }),
+        }]
+    }
+
+    fn notify(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.to_string(), text.to_string());
+                vec![publish_diagnostics(uri, text)]
+            }
+            "textDocument/didChange" => {
+                let Some(text) = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                else {
+                    return Vec::new();
+                };
+                self.documents.insert(uri.to_string(), text.to_string());
+                vec![publish_diagnostics(uri, text)]
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                vec![publish_notification(uri, &[])]
+            }
+            "exit" => {
+                self.exited = true;
+                Vec::new()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn document(&self, params: &Value) -> std::result::Result<&str, (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        self.documents
+            .get(uri)
+            .map(String::as_str)
+            .ok_or_else(|| (INVALID_PARAMS, format!("Unknown document: {uri}")))
+    }
+}
+
+fn decode_options() -> DecodeOptions {
+    DecodeOptions {
+        indent: Some(INDENT),
+        ..DecodeOptions::default()
+    }
+}
+
+fn publish_diagnostics(uri: &str, text: &str) -> Value {
+    let diagnostics = match try_decode(text, Some(decode_options())) {
+        Ok(_) => Vec::new(),
+        Err(err) => {
+            // Errors without a line number are reported on the first line.
+            let line = err.line().map_or(0, |line| line.saturating_sub(1));
+            vec![json!({
+                "range": line_range(text, line),
+                "severity": 1,
+                "source": "toon",
+                "message": err.to_string(),
+            })]
+        }
+    };
+    publish_notification(uri, &diagnostics)
+}
+
+fn publish_notification(uri: &str, diagnostics: &[Value]) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+/// Range covering all of line `line` (0-based), in UTF-16 code units.
+fn line_range(text: &str, line: usize) -> Value {
+    let width = text
+        .split('\n')
+        .nth(line)
+        .map_or(0, |content| content.encode_utf16().count());
+    json!({
+        "start": { "line": line, "character": 0 },
+        "end": { "line": line, "character": width },
+    })
+}
+
+/// A single edit replacing the document with its canonical encoding, or no
+/// edits if the document does not decode.
+fn formatting_edits(text: &str) -> Value {
+    let Ok(value) = try_decode(text, Some(decode_options())) else {
+        return json!([]);
+    };
+    let options = EncodeOptions {
+        indent: Some(INDENT),
+        ..EncodeOptions::default()
+    };
+    let Ok(mut formatted) = try_encode(value, Some(options)) else {
+        return json!([]);
+    };
+    if text.ends_with('\n') {
+        formatted.push('\n');
+    }
+    if formatted == text {
+        return json!([]);
+    }
+
+    let last_line = text.split('\n').count() - 1;
+    let end = line_range(text, last_line)["end"].clone();
+    json!([{
+        "range": { "start": { "line": 0, "character": 0 }, "end": end },
+        "newText": formatted,
+    }])
+}
+
+fn parsed_lines(text: &str) -> Vec<ParsedLine> {
+    let mut state = create_scan_state();
+    parse_lines_sync(
+        text.split('\n').map(str::to_string),
+        INDENT,
+        false,
+        &mut state,
+    )
+    .unwrap_or_default()
+}
+
+/// Index one past the last line nested under `lines[index]`.
+fn section_end(lines: &[ParsedLine], index: usize) -> usize {
+    let depth = lines[index].depth;
+    lines[index + 1..]
+        .iter()
+        .position(|line| line.depth <= depth)
+        .map_or(lines.len(), |offset| index + 1 + offset)
+}
+
+fn folding_ranges(text: &str) -> Value {
+    let lines = parsed_lines(text);
+    let ranges = (0..lines.len())
+        .filter_map(|index| {
+            let end = section_end(&lines, index);
+            (end > index + 1).then(|| {
+                json!({
+                    "startLine": lines[index].line_number - 1,
+                    "endLine": lines[end - 1].line_number - 1,
+                })
+            })
+        })
+        .collect::<Vec<_>>();
+    Value::Array(ranges)
+}
+
+/// Decoded JSON of the line at `line` (0-based) and the lines nested under
+/// it, as a Markdown code block.
+fn hover(text: &str, line: usize) -> Value {
+    let lines = parsed_lines(text);
+    let Some(index) = lines
+        .iter()
+        .position(|parsed| parsed.line_number == line + 1)
+    else {
+        return Value::Null;
+    };
+    let Some(value) = decode_section(&lines, index) else {
+        return Value::Null;
+    };
+    json!({
+        "contents": {
+            "kind": "markdown",
+            "value": format!("```json\n{}\n```", json_stringify_lines(&value, 2).concat()),
+        },
+    })
+}
+
+/// Decode `lines[index]` on its own, together with its nested lines.
+///
+/// A tabular row is decoded under its parent header, giving the row object.
+fn decode_section(lines: &[ParsedLine], index: usize) -> Option<JsonValue> {
+    let line = &lines[index];
+    if let Some(row) = decode_tabular_row(lines, index) {
+        return Some(row);
+    }
+
+    // A list item's own fields continue one level below the hyphen.
+    let (first, base) = match line.content.strip_prefix(LIST_ITEM_PREFIX) {
+        Some(rest) if rest.starts_with(OPEN_BRACKET) => (rest, line.indent),
+        Some(rest) => (rest, line.indent + INDENT),
+        None if line.content == LIST_ITEM_MARKER => return Some(JsonValue::Object(Vec::new())),
+        None => (line.content.as_str(), line.indent),
+    };
+    let mut source = first.to_string();
+    for nested in &lines[index + 1..section_end(lines, index)] {
+        source.push('\n');
+        source.push_str(nested.raw.get(base..).unwrap_or_default());
+    }
+    try_decode(&source, Some(decode_options())).ok()
+}
+
+/// Decode `lines[index]` as a row object if it sits under a tabular header.
+fn decode_tabular_row(lines: &[ParsedLine], index: usize) -> Option<JsonValue> {
+    let line = &lines[index];
+    let parent = lines[..index]
+        .iter()
+        .rev()
+        .find(|parent| parent.depth < line.depth)?;
+    let (header, row_depth) = match parent.content.strip_prefix(LIST_ITEM_PREFIX) {
+        Some(rest) if rest.starts_with(OPEN_BRACKET) => (rest, parent.depth + 1),
+        Some(rest) => (rest, parent.depth + 2),
+        None => (parent.content.as_str(), parent.depth + 1),
+    };
+    if line.depth != row_depth {
+        return None;
+    }
+
+    let fields_start = header.find(CLOSE_BRACKET)? + 1;
+    let fields_end = header.rfind(COLON)?;
+    if !header[fields_start..].starts_with(OPEN_BRACE) {
+        return None;
+    }
+    let source = format!(
+        "[1]{}:\n  {}",
+        &header[fields_start..fields_end],
+        line.content
+    );
+    match try_decode(&source, Some(decode_options())).ok()? {
+        JsonValue::Array(mut rows) if rows.len() == 1 => rows.pop(),
+        _ => None,
+    }
+}
@@ -1,7 +1,15 @@
+use toon::cli::interrupt::EXIT_INTERRUPTED;
+use toon::error::ToonError;
+
 fn main() {
     let result = toon::cli::run();
     if let Err(err) = result {
         eprintln!("{err}");
-        std::process::exit(1);
+        let code = if matches!(err, ToonError::Interrupted) {
+            EXIT_INTERRUPTED
+        } else {
+            1
+        };
+        std::process::exit(code);
     }
 }
@@ -0,0 +1,188 @@
+//! Manifests of converted document trees.
+//!
+//! [`build_manifest`] lists every `.toon` and `.json` file under a directory
+//! with its size, estimated token count, and a SHA-256 of its canonical
+//! content. The canonical content is the document re-encoded as TOON with
+//! [`EncodeOptions::canonical`], so the hash identifies the data rather than its
+//! formatting: a JSON file and its TOON conversion hash the same, and two
+//! runs of a conversion pipeline can be compared file by file.
+
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::batch::SymlinkPolicy;
+use crate::error::{Result, ToonError};
+use crate::options::EncodeOptions;
+use crate::shared::string_utils::estimate_tokens;
+use crate::{JsonValue, try_decode, try_encode};
+
+/// One file in a manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// Path relative to the manifest root, with `/` separators.
+    pub path: String,
+    pub bytes: usize,
+    /// Lowercase hex SHA-256 of the canonical TOON encoding.
+    pub sha256: String,
+    /// Estimated LLM tokens of the file as stored.
+    pub tokens: usize,
+}
+
+impl ManifestEntry {
+    /// Describe a file from its path and content, reading `.json` paths as
+    /// JSON and anything else as TOON.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the content does not parse.
+    pub fn new(path: impl Into<String>, content: &str) -> Result<Self> {
+        let path = path.into();
+        let value = if path.to_ascii_lowercase().ends_with(".json") {
+            let value: serde_json::Value =
+                serde_json::from_str(content).map_err(|err| ToonError::json_parse(&err))?;
+            JsonValue::from(value)
+        } else {
+            try_decode(content, None)?
+        };
+        Ok(Self {
+            sha256: canonical_sha256(value)?,
+            bytes: content.len(),
+            tokens: estimate_tokens(content),
+            path,
+        })
+    }
+
+    /// The entry as a JSON object (`path`, `bytes`, `sha256`, `tokens`).
+    #[must_use]
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::Object(vec![
+            ("path".to_string(), self.path.as_str().into()),
+            ("bytes".to_string(), self.bytes.into()),
+            ("sha256".to_string(), self.sha256.as_str().into()),
+            ("tokens".to_string(), self.tokens.into()),
+        ])
+    }
+}
+
+/// Hex SHA-256 of the canonical TOON encoding of `value`.
+///
+/// # Errors
+///
+/// Returns an error if the value cannot be encoded.
+pub fn canonical_sha256(value: JsonValue) -> Result<String> {
+    let canonical = try_encode(value, Some(EncodeOptions::canonical()))?;
+    let mut hex = String::with_capacity(64);
+    for byte in Sha256::digest(canonical.as_bytes()) {
+        let _ = write!(hex, "{byte:02x}");
+    }
+    Ok(hex)
+}
+
+/// List every `.toon` and `.json` file under `root`, sorted by path.
+///
+/// Symbolic links are not followed.
+///
+/// # Errors
+///
+/// Returns an error if a directory or file cannot be read, or a file does not
+/// parse.
+pub fn build_manifest(root: &Path) -> Result<Vec<ManifestEntry>> {
+    let mut files = Vec::new();
+    collect_files(root, SymlinkPolicy::Skip, &mut files)?;
+    files.sort();
+
+    files
+        .into_iter()
+        .map(|file| {
+            let content = std::fs::read_to_string(&file)
+                .map_err(|err| ToonError::file_read(file.clone(), err))?;
+            let relative = file
+                .strip_prefix(root)
+                .unwrap_or(&file)
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            ManifestEntry::new(relative, &content)
+                .map_err(|err| ToonError::message(format!("{}: {err}", file.display())))
+        })
+        .collect()
+}
+
+/// Add every `.toon` and `.json` file under `dir` to `files`, visiting each
+/// directory's entries in path order.
+///
+/// Symbolic links are skipped, or with [`SymlinkPolicy::Follow`] followed
+/// when they resolve inside `dir`. Following a link that resolves outside
+/// `dir`, or a dangling one, is an error, and a file or directory reached
+/// twice is only collected once.
+pub(crate) fn collect_files(
+    dir: &Path,
+    symlinks: SymlinkPolicy,
+    files: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let root = match symlinks {
+        SymlinkPolicy::Skip => None,
+        SymlinkPolicy::Follow => Some(
+            dir.canonicalize()
+                .map_err(|err| ToonError::file_read(dir.to_path_buf(), err))?,
+        ),
+    };
+    let mut visited = HashSet::new();
+    collect_under(dir, root.as_deref(), &mut visited, files)
+}
+
+/// `root` is the canonical walk root when links are followed.
+fn collect_under(
+    dir: &Path,
+    root: Option<&Path>,
+    visited: &mut HashSet<PathBuf>,
+    files: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let read_error = |err| ToonError::io("Failed to read directory", Some(dir.to_path_buf()), err);
+    let mut entries = std::fs::read_dir(dir)
+        .map_err(read_error)?
+        .collect::<std::io::Result<Vec<_>>>()
+        .map_err(read_error)?;
+    entries.sort_by_key(std::fs::DirEntry::path);
+    for entry in entries {
+        let path = entry.path();
+        let mut file_type = entry
+            .file_type()
+            .map_err(|err| ToonError::file_read(path.clone(), err))?;
+        if let Some(root) = root {
+            let target = path
+                .canonicalize()
+                .map_err(|err| ToonError::file_read(path.clone(), err))?;
+            if !target.starts_with(root) {
+                return Err(ToonError::message(format!(
+                    "{}: symlink resolves to {}, outside {}",
+                    path.display(),
+                    target.display(),
+                    root.display()
+                )));
+            }
+            if !visited.insert(target) {
+                continue;
+            }
+            if file_type.is_symlink() {
+                file_type = std::fs::metadata(&path)
+                    .map_err(|err| ToonError::file_read(path.clone(), err))?
+                    .file_type();
+            }
+        }
+        if file_type.is_dir() {
+            collect_under(&path, root, visited, files)?;
+        } else if file_type.is_file()
+            && path.extension().is_some_and(|ext| {
+                ext.eq_ignore_ascii_case("toon") || ext.eq_ignore_ascii_case("json")
+            })
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,153 @@
+//! Structural three-way merge of JSON values.
+//!
+//! [`merge3`] merges two edited versions of a document against their common
+//! ancestor key by key, so unrelated edits to the same TOON or JSON file
+//! combine cleanly where a line-based merge would conflict. Objects merge
+//! per key; arrays merge per index when all three sides have the same
+//! length, and otherwise only if one side left the array unchanged.
+
+use std::fmt;
+
+use crate::JsonValue;
+use crate::options::{PathSegment, format_path};
+
+/// A value both sides changed in different ways. `None` means the value is
+/// absent on that side (a key that was never there or was deleted).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub path: Vec<PathSegment>,
+    pub base: Option<JsonValue>,
+    pub ours: Option<JsonValue>,
+    pub theirs: Option<JsonValue>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = format_path(&self.path);
+        let path = if path.is_empty() { "(root)" } else { &path };
+        let kind = match (&self.ours, &self.theirs) {
+            (None, _) => "deleted by ours, modified by theirs",
+            (_, None) => "modified by ours, deleted by theirs",
+            _ if self.base.is_none() => "added on both sides",
+            _ => "modified on both sides",
+        };
+        write!(f, "{path}: {kind}")
+    }
+}
+
+/// Merge `ours` and `theirs`, both derived from `base`.
+///
+/// A change made on only one side is taken; identical changes on both sides
+/// are taken once. Keys keep the order of `ours`, with keys added only by
+/// `theirs` appended in their order.
+///
+/// # Errors
+///
+/// Returns every conflict, in document order, if both sides changed the
+/// same value differently.
+pub fn merge3(
+    base: &JsonValue,
+    ours: &JsonValue,
+    theirs: &JsonValue,
+) -> Result<JsonValue, Vec<Conflict>> {
+    let mut state = Merger {
+        path: Vec::new(),
+        conflicts: Vec::new(),
+    };
+    let merged = state.merge(Some(base), Some(ours), Some(theirs));
+    if state.conflicts.is_empty() {
+        Ok(merged.unwrap_or(JsonValue::Object(Vec::new())))
+    } else {
+        Err(state.conflicts)
+    }
+}
+
+struct Merger {
+    path: Vec<PathSegment>,
+    conflicts: Vec<Conflict>,
+}
+
+impl Merger {
+    /// Merge one value; `None` is an absent value on input and a deletion on
+    /// output.
+    fn merge(
+        &mut self,
+        base: Option<&JsonValue>,
+        ours: Option<&JsonValue>,
+        theirs: Option<&JsonValue>,
+    ) -> Option<JsonValue> {
+        if ours == theirs || theirs == base {
+            return ours.cloned();
+        }
+        if ours == base {
+            return theirs.cloned();
+        }
+
+        match (base, ours, theirs) {
+            (base, Some(JsonValue::Object(ours)), Some(JsonValue::Object(theirs))) => {
+                let empty = Vec::new();
+                let base = match base {
+                    Some(JsonValue::Object(base)) => base,
+                    _ => &empty,
+                };
+                Some(JsonValue::Object(self.merge_objects(base, ours, theirs)))
+            }
+            (
+                Some(JsonValue::Array(base)),
+                Some(JsonValue::Array(ours)),
+                Some(JsonValue::Array(theirs)),
+            ) if base.len() == ours.len() && base.len() == theirs.len() => {
+                let mut merged = Vec::with_capacity(base.len());
+                for (index, ((base, ours), theirs)) in base.iter().zip(ours).zip(theirs).enumerate()
+                {
+                    self.path.push(PathSegment::Index(index));
+                    if let Some(value) = self.merge(Some(base), Some(ours), Some(theirs)) {
+                        merged.push(value);
+                    }
+                    self.path.pop();
+                }
+                Some(JsonValue::Array(merged))
+            }
+            _ => {
+                self.conflicts.push(Conflict {
+                    path: self.path.clone(),
+                    base: base.cloned(),
+                    ours: ours.cloned(),
+                    theirs: theirs.cloned(),
+                });
+                ours.cloned()
+            }
+        }
+    }
+
+    fn merge_objects(
+        &mut self,
+        base: &[(String, JsonValue)],
+        ours: &[(String, JsonValue)],
+        theirs: &[(String, JsonValue)],
+    ) -> Vec<(String, JsonValue)> {
+        // Keys missing from both sides were deleted (or never existed) and
+        // need no merge.
+        let keys = ours
+            .iter()
+            .chain(theirs.iter().filter(|(key, _)| get(ours, key).is_none()))
+            .map(|(key, _)| key);
+
+        let mut merged = Vec::with_capacity(ours.len());
+        for key in keys {
+            self.path.push(PathSegment::Key(key.clone()));
+            if let Some(value) = self.merge(get(base, key), get(ours, key), get(theirs, key)) {
+                merged.push((key.clone(), value));
+            }
+            self.path.pop();
+        }
+        merged
+    }
+}
+
+fn get<'a>(entries: &'a [(String, JsonValue)], key: &str) -> Option<&'a JsonValue> {
+    entries
+        .iter()
+        .find(|(existing, _)| existing == key)
+        .map(|(_, value)| value)
+}
@@ -1,59 +1,643 @@
+use std::collections::HashMap;
+use std::fmt::{self, Write as _};
+use std::panic::RefUnwindSafe;
+use std::path::PathBuf;
 use std::sync::Arc;
 
-use crate::JsonValue;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::encode::primitives::encode_primitive;
+use crate::encode::rows::RowEncoder;
+use crate::error::{Result, ToonError};
 use crate::shared::constants::DEFAULT_DELIMITER;
+use crate::{JsonObject, JsonValue, StringOrNumberOrBoolOrNull};
 
 pub type EncodeReplacer =
     Arc<dyn Fn(&str, &JsonValue, &[PathSegment]) -> Option<JsonValue> + Send + Sync>;
 
-#[derive(Clone)]
+/// Chooses the header order of a tabular array from its key (empty for the
+/// root or a list item) and its fields in object key order. See
+/// [`crate::encode::columns::apply_column_order`].
+pub type ColumnOrder = Arc<dyn Fn(&str, &[String]) -> Vec<String> + Send + Sync>;
+
+/// Decides whether a row of an array of objects is encoded, from the row's
+/// path and entries. See [`crate::encode::row_filter::apply_row_filter`].
+pub type RowFilter = Arc<dyn Fn(&[PathSegment], &JsonObject) -> bool + Send + Sync>;
+
+/// Encoding options. Every field is optional; unset fields take their
+/// defaults in [`resolve_encode_options`].
+///
+/// With serde, fields use camelCase names (`keyFolding`), the delimiter may
+/// also be given by name (`"tab"`), and the replacer, column order, row
+/// filter, and row encoder are skipped.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct EncodeOptions {
     pub indent: Option<usize>,
+    #[serde(default, deserialize_with = "deserialize_delimiter")]
     pub delimiter: Option<char>,
     pub key_folding: Option<KeyFoldingMode>,
     pub flatten_depth: Option<usize>,
+    #[serde(skip)]
     pub replacer: Option<EncodeReplacer>,
+    pub empty_string: Option<EmptyStringStyle>,
+    /// Annotate tabular header fields with inferred column types
+    /// (`users[2]{id:int,name:str}:`).
+    pub typed_headers: Option<bool>,
+    /// Encode tabular arrays of objects as one inline array per field,
+    /// under a `"@rows"` count that marks the section.
+    pub columnar: Option<bool>,
+    /// Write primitive arrays whose share of `null` entries is at least this
+    /// fraction as a header plus `index: value` lines for the other entries.
+    pub sparse_threshold: Option<f64>,
+    /// Collapse runs of at least this many identical values in inline
+    /// primitive arrays into `value*count` cells.
+    pub run_length: Option<usize>,
+    /// How long string values are written (see [`LongStringPolicy`]).
+    pub long_string: Option<LongStringPolicy>,
+    /// Start the document with a `#%toon version=1 delimiter=, indent=2`
+    /// line recording its delimiter and indentation.
+    pub emit_directive: Option<bool>,
+    /// Write repeated nested objects once, marked with an `&a1` anchor, and
+    /// replace later copies with `*a1` aliases. See [`crate::anchors`].
+    pub anchors: Option<bool>,
+    /// Order of object keys, applied to the input right after it is
+    /// converted (see [`SerdeKeyOrder`]).
+    pub serde_key_order: Option<SerdeKeyOrder>,
+    /// Order of tabular header fields, e.g. `id` and `name` first with
+    /// [`crate::encode::columns::column_priority`].
+    #[serde(skip)]
+    pub column_order: Option<ColumnOrder>,
+    /// Keep only the listed fields, in list order, in the objects of each
+    /// array matching a pattern. See
+    /// [`crate::encode::columns::apply_column_projection`].
+    pub columns: Option<HashMap<PathPattern, Vec<String>>>,
+    /// Encode only the rows of arrays of objects that this returns `true`
+    /// for, e.g. only active users. Runs before `columns`, so it can test
+    /// fields that projection drops.
+    #[serde(skip)]
+    pub row_filter: Option<RowFilter>,
+    /// Lay out tabular rows, e.g. padded into aligned columns. See
+    /// [`crate::encode::rows`].
+    #[serde(skip)]
+    pub row_encoder: Option<Arc<dyn RowEncoder>>,
+    /// Pad tabular cells so the delimiters of each table line up (see
+    /// [`crate::encode::rows::AlignedRows`]). Ignored when `row_encoder` is
+    /// set.
+    pub align_columns: Option<bool>,
+    /// Write numbers decoded in lossless number mode exactly as they were
+    /// written (see `DecodeOptions::lossless_numbers`). Off, they are
+    /// written in canonical form.
+    pub lossless_numbers: Option<bool>,
+    /// Mark the delimiter in every array header, the default comma included
+    /// (`items[2,]: a,b`), for readers that do not assume the default.
+    pub always_mark_delimiter: Option<bool>,
+    /// Normalize keys and strings to Unicode NFC, so text that looks the
+    /// same is written the same. Encoding fails on an object whose keys
+    /// differ only in normalization, which would otherwise be written as
+    /// duplicates (see [`crate::lossy::scan_nfc_key_collisions`]).
+    pub nfc: Option<bool>,
+    /// Fail on keys and strings containing zero-width or bidi control
+    /// characters (see [`crate::shared::validation::is_invisible_char`])
+    /// instead of quoting them.
+    pub reject_invisible: Option<bool>,
+}
+
+impl EncodeOptions {
+    /// Fewest tokens for a model prompt while staying plain TOON: tab
+    /// delimiters and safe key folding. Empty strings stay quoted, since a
+    /// bare one next to a tab would be trimmed on decode. Decode with
+    /// `expand_paths: Some(ExpandPathsMode::Safe)` to unfold the keys.
+    #[must_use]
+    pub fn llm_compact() -> Self {
+        Self {
+            delimiter: Some('\t'),
+            key_folding: Some(KeyFoldingMode::Safe),
+            ..Self::default()
+        }
+    }
+
+    /// For people reading and editing the output: four-space indentation,
+    /// recorded in a leading `#%toon` directive so the decoder picks it up,
+    /// and no key folding.
+    #[must_use]
+    pub fn human_readable() -> Self {
+        Self {
+            indent: Some(4),
+            key_folding: Some(KeyFoldingMode::Off),
+            emit_directive: Some(true),
+            ..Self::default()
+        }
+    }
+
+    /// The spec's canonical form with every extension off, so equal data
+    /// always encodes to the same bytes. This is what manifests hash.
+    #[must_use]
+    pub fn canonical() -> Self {
+        Self {
+            indent: Some(2),
+            delimiter: Some(DEFAULT_DELIMITER),
+            key_folding: Some(KeyFoldingMode::Off),
+            empty_string: Some(EmptyStringStyle::Quoted),
+            typed_headers: Some(false),
+            columnar: Some(false),
+            long_string: Some(LongStringPolicy::KeepInline),
+            emit_directive: Some(false),
+            anchors: Some(false),
+            lossless_numbers: Some(false),
+            always_mark_delimiter: Some(false),
+            ..Self::default()
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// How string values longer than a limit are written. Keys are never
+/// changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LongStringPolicy {
+    /// Write every string in full (the default).
+    KeepInline,
+    /// Cut strings longer than `max` characters to their first `max`
+    /// characters followed by `suffix`.
+    Truncate { max: usize, suffix: String },
+    /// Replace strings longer than `max` characters with a reference
+    /// (`@blob_1`, `@blob_2`, ...) and return the text separately; see
+    /// [`crate::encode::encode_with_extractions`]. Repeated strings share a
+    /// reference.
+    ExternalRef { max: usize },
+}
+
+/// How the keys of each object are ordered before encoding.
+///
+/// A `serde_json::Map` iterates in insertion order only when `serde_json`'s
+/// `preserve_order` feature is on (this crate enables it); otherwise its
+/// keys come out sorted. Setting an order here makes the output independent
+/// of that feature.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SerdeKeyOrder {
+    /// Keep the order of the converted value (the default).
+    #[default]
+    AsIs,
+    /// Sort keys by their UTF-8 bytes.
+    Sorted,
+    /// Put the listed keys first, in list order, at every level; other keys
+    /// follow in their existing order.
+    SchemaOrder(Vec<String>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub enum KeyFoldingMode {
     Off,
     Safe,
 }
 
-#[derive(Debug, Clone)]
+/// How empty strings are written in delimited rows (inline arrays and
+/// tabular rows).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EmptyStringStyle {
+    /// Always write an explicit `""`.
+    Quoted,
+    /// Write an empty cell (`a,,b`). A lone empty string is still quoted,
+    /// since an empty row would read back as zero cells, and so is one in
+    /// the last cell or in a row with a whitespace delimiter, where the
+    /// empty cell would be trimmed away.
+    Bare,
+}
+
+/// Decoding options. Every field is optional; unset fields take their
+/// defaults in [`resolve_decode_options`].
+///
+/// With serde, fields use camelCase names (`expandPaths`) and the include
+/// and variable resolvers are skipped.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct DecodeOptions {
     pub indent: Option<usize>,
     pub strict: Option<bool>,
     pub expand_paths: Option<ExpandPathsMode>,
+    pub missing_cell: Option<MissingCellMode>,
+    pub empty_cell: Option<EmptyCellMode>,
+    /// Per-column type coercion for tabular arrays, keyed by field name.
+    pub column_types: Option<HashMap<String, ColumnType>>,
+    /// Read column type annotations from tabular headers
+    /// (`users[2]{id:int,name:str}:`) and coerce cells to them.
+    pub typed_headers: Option<bool>,
+    /// Reassemble rows from sections marked with the columnar layout's
+    /// `"@rows"` count (see `EncodeOptions::columnar`). Any other object
+    /// with an unescaped `"@rows"` key is an error.
+    pub columnar: Option<bool>,
+    /// Expand `value*count` cells in inline arrays (see `EncodeOptions::run_length`).
+    pub run_length: Option<bool>,
+    /// Read `index: value` entry lines under an array header as a sparse
+    /// array, filling the unlisted indices with `null` (see
+    /// `EncodeOptions::sparse_threshold`).
+    pub sparse: Option<bool>,
+    /// Reject input that decodes but is not in canonical form (extra or
+    /// trailing whitespace, loosely formatted headers), reporting every
+    /// violation. See [`crate::decode::pedantic`].
+    pub pedantic: Option<bool>,
+    /// Accept tabs in indentation, each advancing to the next multiple of
+    /// this many columns. Requires `strict: Some(false)`.
+    pub tab_width: Option<usize>,
+    /// Infer omitted array lengths (`tags[]: a,b`) from the values or rows
+    /// that follow the header.
+    pub infer_lengths: Option<bool>,
+    /// Expand `key: *name` aliases to the object anchored with `key: &name`.
+    /// See [`crate::anchors`].
+    pub anchors: Option<bool>,
+    /// Replace `key: !include path` values with the decoded document the
+    /// resolver returns for `path`. Without a resolver they are plain
+    /// strings.
+    #[serde(skip)]
+    pub include_resolver: Option<IncludeResolver>,
+    /// Replace `${NAME}` in string values with the environment variable
+    /// `NAME`. See [`crate::decode::interpolate`].
+    pub interpolate_env: Option<bool>,
+    /// Replace `${NAME}` in string values with the resolver's value for
+    /// `NAME`, instead of the environment.
+    #[serde(skip)]
+    pub variable_resolver: Option<VariableResolver>,
+    /// Keep numbers as written (`1.230`, `1e10`, `-0`) as
+    /// [`NumberLexeme`](crate::StringOrNumberOrBoolOrNull::NumberLexeme)
+    /// values, so re-encoding does not reformat them.
+    pub lossless_numbers: Option<bool>,
+    /// Delimiter assumed for array headers that do not declare one (`[2]`
+    /// rather than `[2|]`), for documents written without the marker. A
+    /// `#%toon` directive naming a delimiter takes precedence. Defaults to
+    /// a comma.
+    #[serde(default, deserialize_with = "deserialize_delimiter")]
+    pub delimiter: Option<char>,
+}
+
+impl DecodeOptions {
+    /// See [`DecodeProfile::Strict`].
+    #[must_use]
+    pub fn strict() -> Self {
+        DecodeProfile::Strict.options()
+    }
+
+    /// See [`DecodeProfile::Lenient`].
+    #[must_use]
+    pub fn lenient() -> Self {
+        DecodeProfile::Lenient.options()
+    }
+
+    /// See [`DecodeProfile::Repair`].
+    #[must_use]
+    pub fn repair() -> Self {
+        DecodeProfile::Repair.options()
+    }
+}
+
+/// Loads the text of the document named by a `!include path` value.
+///
+/// Included documents are decoded with the same options and may include
+/// others; an include cycle is an error.
+#[derive(Clone)]
+pub struct IncludeResolver(Arc<ResolveFn>);
+
+type ResolveFn = dyn Fn(&str) -> Result<String> + Send + Sync + RefUnwindSafe;
+
+impl IncludeResolver {
+    pub fn new(
+        resolve: impl Fn(&str) -> Result<String> + Send + Sync + RefUnwindSafe + 'static,
+    ) -> Self {
+        Self(Arc::new(resolve))
+    }
+
+    /// Load include paths as files relative to `base`, refusing any that
+    /// resolve outside `root`: absolute paths, `..` segments, and symbolic
+    /// links may only lead to files under it.
+    pub fn sandboxed(base: impl Into<PathBuf>, root: impl Into<PathBuf>) -> Self {
+        let base = base.into();
+        let root = root.into();
+        Self::new(move |path| {
+            let sandbox = root
+                .canonicalize()
+                .map_err(|err| ToonError::file_read(root.clone(), err))?;
+            let candidate = base.join(path);
+            let resolved = candidate
+                .canonicalize()
+                .map_err(|err| ToonError::file_read(candidate, err))?;
+            if !resolved.starts_with(&sandbox) {
+                return Err(ToonError::message(format!(
+                    "Include '{path}' resolves to {}, outside {}",
+                    resolved.display(),
+                    sandbox.display()
+                )));
+            }
+            std::fs::read_to_string(&resolved).map_err(|err| ToonError::file_read(resolved, err))
+        })
+    }
+
+    /// Load the document named `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the resolver's error, e.g. for a missing file.
+    pub fn resolve(&self, path: &str) -> Result<String> {
+        (self.0)(path)
+    }
+}
+
+impl fmt::Debug for IncludeResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("IncludeResolver(..)")
+    }
+}
+
+/// Looks up the value of a `${NAME}` variable; `None` means undefined.
+#[derive(Clone)]
+pub struct VariableResolver(Arc<LookupFn>);
+
+type LookupFn = dyn Fn(&str) -> Option<String> + Send + Sync + RefUnwindSafe;
+
+impl VariableResolver {
+    pub fn new(
+        lookup: impl Fn(&str) -> Option<String> + Send + Sync + RefUnwindSafe + 'static,
+    ) -> Self {
+        Self(Arc::new(lookup))
+    }
+
+    /// Resolve variables from the process environment.
+    #[must_use]
+    pub fn env() -> Self {
+        Self::new(|name| std::env::var(name).ok())
+    }
+
+    /// Resolve variables from a document: `${a.b}` names the value at key
+    /// `b` of the object at key `a`. Strings are substituted as they are and
+    /// other primitives in their TOON form; arrays and objects are undefined.
+    #[must_use]
+    pub fn from_value(vars: JsonValue) -> Self {
+        Self::new(move |name| {
+            let value = name
+                .split('.')
+                .try_fold(&vars, |value, key| value.get(key))?;
+            match value {
+                JsonValue::Primitive(StringOrNumberOrBoolOrNull::String(text)) => {
+                    Some(text.clone())
+                }
+                JsonValue::Primitive(primitive) => {
+                    Some(encode_primitive(primitive, DEFAULT_DELIMITER))
+                }
+                _ => None,
+            }
+        })
+    }
+
+    #[must_use]
+    pub fn lookup(&self, name: &str) -> Option<String> {
+        (self.0)(name)
+    }
+}
+
+impl fmt::Debug for VariableResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("VariableResolver(..)")
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Preset decode settings for common kinds of input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DecodeProfile {
+    /// The defaults: spec-conformant input with strict validation.
+    Strict,
+    /// Hand-typed TOON, e.g. pasted into a chat UI: strict validation off,
+    /// tabs in indentation count as two columns, and omitted array lengths
+    /// are inferred. Extra spaces around delimiters are always accepted.
+    Lenient,
+    /// Output damaged on its way out of a model or truncated by a limit: the
+    /// lenient settings, with missing and empty cells decoded as `null` on
+    /// the assumption that their values were lost.
+    Repair,
+}
+
+impl DecodeProfile {
+    #[must_use]
+    pub fn options(self) -> DecodeOptions {
+        match self {
+            Self::Strict => DecodeOptions::default(),
+            Self::Lenient => DecodeOptions {
+                strict: Some(false),
+                tab_width: Some(2),
+                infer_lengths: Some(true),
+                ..DecodeOptions::default()
+            },
+            Self::Repair => DecodeOptions {
+                missing_cell: Some(MissingCellMode::Null),
+                empty_cell: Some(EmptyCellMode::Null),
+                ..Self::Lenient.options()
+            },
+        }
+    }
+}
+
+impl From<DecodeProfile> for DecodeOptions {
+    fn from(profile: DecodeProfile) -> Self {
+        profile.options()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub enum ExpandPathsMode {
     Off,
     Safe,
 }
 
-#[derive(Debug, Clone, Default)]
+/// How a tabular row with fewer cells than header fields is decoded.
+///
+/// Strict mode rejects short rows regardless; this applies when strict
+/// validation is disabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MissingCellMode {
+    /// Fill missing cells with `null` (indistinguishable from an explicit null).
+    Null,
+    /// Reject the row with an error naming the missing field.
+    Error,
+    /// Leave missing fields out of the decoded object.
+    Omit,
+}
+
+/// How an unquoted empty cell (`a,,b`) in a delimited row is decoded.
+///
+/// A quoted `""` always decodes to an empty string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EmptyCellMode {
+    /// Decode as an empty string.
+    EmptyString,
+    /// Decode as `null`.
+    Null,
+    /// Reject the row with an error.
+    Error,
+}
+
+/// Target type for a tabular column.
+///
+/// `null` cells stay `null` for every type; other cells that cannot be
+/// converted are rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ColumnType {
+    /// Keep the cell text as a string, even if it looks numeric or boolean.
+    String,
+    /// Parse the cell as a number.
+    Number,
+    /// Parse the cell as a number with no fractional part.
+    Integer,
+    /// Parse the cell as `true` or `false`.
+    Bool,
+    /// Infer the type from the cell (the default).
+    Auto,
+}
+
+impl ColumnType {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::String => "string",
+            Self::Number => "number",
+            Self::Integer => "integer",
+            Self::Bool => "bool",
+            Self::Auto => "auto",
+        }
+    }
+
+    /// Annotation used in typed tabular headers (`{id:int}`).
+    #[must_use]
+    pub const fn annotation(self) -> &'static str {
+        match self {
+            Self::String => "str",
+            Self::Number => "num",
+            Self::Integer => "int",
+            Self::Bool => "bool",
+            Self::Auto => "auto",
+        }
+    }
+
+    /// Parse a typed header annotation.
+    #[must_use]
+    pub fn from_annotation(annotation: &str) -> Option<Self> {
+        match annotation {
+            "str" => Some(Self::String),
+            "num" => Some(Self::Number),
+            "int" => Some(Self::Integer),
+            "bool" => Some(Self::Bool),
+            "auto" => Some(Self::Auto),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct DecodeStreamOptions {
     pub indent: Option<usize>,
     pub strict: Option<bool>,
+    pub missing_cell: Option<MissingCellMode>,
+    pub empty_cell: Option<EmptyCellMode>,
+    pub column_types: Option<HashMap<String, ColumnType>>,
+    pub typed_headers: Option<bool>,
+    pub run_length: Option<bool>,
+    pub sparse: Option<bool>,
+    pub pedantic: Option<bool>,
+    pub tab_width: Option<usize>,
+    pub infer_lengths: Option<bool>,
+    pub anchors: Option<bool>,
+    #[serde(skip)]
+    pub include_resolver: Option<IncludeResolver>,
+    pub interpolate_env: Option<bool>,
+    #[serde(skip)]
+    pub variable_resolver: Option<VariableResolver>,
+    pub lossless_numbers: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_delimiter")]
+    pub delimiter: Option<char>,
 }
 
 #[derive(Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct ResolvedEncodeOptions {
     pub indent: usize,
     pub delimiter: char,
     pub key_folding: KeyFoldingMode,
     pub flatten_depth: usize,
     pub replacer: Option<EncodeReplacer>,
+    pub empty_string: EmptyStringStyle,
+    pub typed_headers: bool,
+    pub columnar: bool,
+    pub sparse_threshold: Option<f64>,
+    pub run_length: Option<usize>,
+    pub long_string: LongStringPolicy,
+    pub emit_directive: bool,
+    pub anchors: bool,
+    pub serde_key_order: SerdeKeyOrder,
+    pub column_order: Option<ColumnOrder>,
+    pub columns: Option<HashMap<PathPattern, Vec<String>>>,
+    pub row_filter: Option<RowFilter>,
+    pub row_encoder: Option<Arc<dyn RowEncoder>>,
+    pub align_columns: bool,
+    pub lossless_numbers: bool,
+    pub always_mark_delimiter: bool,
+    pub nfc: bool,
+    pub reject_invisible: bool,
 }
 
 #[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct ResolvedDecodeOptions {
     pub indent: usize,
     pub strict: bool,
     pub expand_paths: ExpandPathsMode,
+    pub missing_cell: MissingCellMode,
+    pub empty_cell: EmptyCellMode,
+    pub column_types: Option<HashMap<String, ColumnType>>,
+    pub typed_headers: bool,
+    pub columnar: bool,
+    pub run_length: bool,
+    pub sparse: bool,
+    pub pedantic: bool,
+    pub tab_width: Option<usize>,
+    pub infer_lengths: bool,
+    pub anchors: bool,
+    pub include_resolver: Option<IncludeResolver>,
+    pub interpolate_env: bool,
+    pub variable_resolver: Option<VariableResolver>,
+    pub lossless_numbers: bool,
+    pub delimiter: Option<char>,
+}
+
+impl ResolvedDecodeOptions {
+    /// Stream options carrying the event-level settings of these options.
+    #[must_use]
+    pub fn stream_options(&self) -> DecodeStreamOptions {
+        DecodeStreamOptions {
+            indent: Some(self.indent),
+            strict: Some(self.strict),
+            missing_cell: Some(self.missing_cell),
+            empty_cell: Some(self.empty_cell),
+            column_types: self.column_types.clone(),
+            typed_headers: Some(self.typed_headers),
+            run_length: Some(self.run_length),
+            sparse: Some(self.sparse),
+            pedantic: Some(self.pedantic),
+            tab_width: self.tab_width,
+            infer_lengths: Some(self.infer_lengths),
+            anchors: Some(self.anchors),
+            include_resolver: self.include_resolver.clone(),
+            interpolate_env: Some(self.interpolate_env),
+            variable_resolver: self.variable_resolver.clone(),
+            lossless_numbers: Some(self.lossless_numbers),
+            delimiter: self.delimiter,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -62,15 +646,111 @@ pub enum PathSegment {
     Index(usize),
 }
 
+/// Selects arrays by the keys leading to them: `users`, `data.users`, or
+/// `orders.*.items`, where `*` matches any one key.
+///
+/// Array indices are skipped, so `orders.items` matches the `items` array in
+/// every element of `orders`. The empty pattern matches the root array.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub struct PathPattern {
+    keys: Vec<String>,
+}
+
+impl PathPattern {
+    #[must_use]
+    pub fn new(pattern: &str) -> Self {
+        let keys = if pattern.is_empty() {
+            Vec::new()
+        } else {
+            pattern.split('.').map(str::to_string).collect()
+        };
+        Self { keys }
+    }
+
+    /// Whether the keys of `path` match, ignoring its indices.
+    #[must_use]
+    pub fn matches(&self, path: &[PathSegment]) -> bool {
+        let mut keys = path.iter().filter_map(|segment| match segment {
+            PathSegment::Key(key) => Some(key),
+            PathSegment::Index(_) => None,
+        });
+        self.keys.iter().all(|pattern| {
+            keys.next()
+                .is_some_and(|key| pattern == "*" || pattern == key)
+        }) && keys.next().is_none()
+    }
+}
+
+impl From<&str> for PathPattern {
+    fn from(pattern: &str) -> Self {
+        Self::new(pattern)
+    }
+}
+
+impl From<String> for PathPattern {
+    fn from(pattern: String) -> Self {
+        Self::new(&pattern)
+    }
+}
+
+impl From<PathPattern> for String {
+    fn from(pattern: PathPattern) -> Self {
+        pattern.to_string()
+    }
+}
+
+impl fmt::Display for PathPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.keys.join("."))
+    }
+}
+
+/// Render a path the way encode errors report it (`users[1].name`).
+#[must_use]
+pub fn format_path(path: &[PathSegment]) -> String {
+    let mut out = String::new();
+    for segment in path {
+        match segment {
+            PathSegment::Key(key) => {
+                if !out.is_empty() {
+                    out.push('.');
+                }
+                out.push_str(key);
+            }
+            PathSegment::Index(index) => {
+                let _ = write!(out, "[{index}]");
+            }
+        }
+    }
+    out
+}
+
+/// Parse a delimiter name (`comma`, `tab`, `pipe`) or the character itself.
+///
+/// # Errors
+///
+/// Returns an error if `s` names no supported delimiter.
+pub fn parse_delimiter(s: &str) -> Result<char> {
+    match s {
+        "," | "comma" => Ok(','),
+        "|" | "pipe" => Ok('|'),
+        "\\t" | "\t" | "tab" => Ok('\t'),
+        _ => Err(ToonError::invalid_delimiter(s)),
+    }
+}
+
+fn deserialize_delimiter<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<char>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|name| parse_delimiter(&name).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
 #[must_use]
 pub fn resolve_encode_options(options: Option<EncodeOptions>) -> ResolvedEncodeOptions {
-    let options = options.unwrap_or(EncodeOptions {
-        indent: None,
-        delimiter: None,
-        key_folding: None,
-        flatten_depth: None,
-        replacer: None,
-    });
+    let options = options.unwrap_or_default();
 
     ResolvedEncodeOptions {
         indent: options.indent.unwrap_or(2),
@@ -78,20 +758,50 @@ pub fn resolve_encode_options(options: Option<EncodeOptions>) -> ResolvedEncodeO
         key_folding: options.key_folding.unwrap_or(KeyFoldingMode::Off),
         flatten_depth: options.flatten_depth.unwrap_or(usize::MAX),
         replacer: options.replacer,
+        empty_string: options.empty_string.unwrap_or(EmptyStringStyle::Quoted),
+        typed_headers: options.typed_headers.unwrap_or(false),
+        columnar: options.columnar.unwrap_or(false),
+        sparse_threshold: options.sparse_threshold,
+        run_length: options.run_length,
+        long_string: options.long_string.unwrap_or(LongStringPolicy::KeepInline),
+        emit_directive: options.emit_directive.unwrap_or(false),
+        anchors: options.anchors.unwrap_or(false),
+        serde_key_order: options.serde_key_order.unwrap_or_default(),
+        column_order: options.column_order,
+        columns: options.columns,
+        row_filter: options.row_filter,
+        row_encoder: options.row_encoder,
+        align_columns: options.align_columns.unwrap_or(false),
+        lossless_numbers: options.lossless_numbers.unwrap_or(false),
+        always_mark_delimiter: options.always_mark_delimiter.unwrap_or(false),
+        nfc: options.nfc.unwrap_or(false),
+        reject_invisible: options.reject_invisible.unwrap_or(false),
     }
 }
 
 #[must_use]
 pub fn resolve_decode_options(options: Option<DecodeOptions>) -> ResolvedDecodeOptions {
-    let options = options.unwrap_or(DecodeOptions {
-        indent: None,
-        strict: None,
-        expand_paths: None,
-    });
+    let options = options.unwrap_or_default();
 
     ResolvedDecodeOptions {
         indent: options.indent.unwrap_or(2),
         strict: options.strict.unwrap_or(true),
         expand_paths: options.expand_paths.unwrap_or(ExpandPathsMode::Off),
+        missing_cell: options.missing_cell.unwrap_or(MissingCellMode::Null),
+        empty_cell: options.empty_cell.unwrap_or(EmptyCellMode::EmptyString),
+        column_types: options.column_types,
+        typed_headers: options.typed_headers.unwrap_or(false),
+        columnar: options.columnar.unwrap_or(false),
+        run_length: options.run_length.unwrap_or(false),
+        sparse: options.sparse.unwrap_or(false),
+        pedantic: options.pedantic.unwrap_or(false),
+        tab_width: options.tab_width,
+        infer_lengths: options.infer_lengths.unwrap_or(false),
+        anchors: options.anchors.unwrap_or(false),
+        include_resolver: options.include_resolver,
+        interpolate_env: options.interpolate_env.unwrap_or(false),
+        variable_resolver: options.variable_resolver,
+        lossless_numbers: options.lossless_numbers.unwrap_or(false),
+        delimiter: options.delimiter,
     }
 }
@@ -0,0 +1,516 @@
+//! Splitting a large document across several TOON documents.
+//!
+//! [`encode_paginated`] finds the longest array with more than `page_size`
+//! items and spreads it over pages of at most `page_size` items, so a value
+//! too large for one message can be sent as several. Each page starts with a
+//! `#%toon` directive carrying `page=N total=M` and, when an array was split,
+//! `path=` (a JSON Pointer in URI fragment form, e.g. `#/data/users`).
+//!
+//! The first page holds the whole value with the first slice of the array.
+//! Later pages hold only the objects along the path down to their slice, so
+//! the keys that name the array stay visible without repeating the rest.
+//! [`decode_paginated`] puts the pages back together.
+//!
+//! [`split_by_token_budget`] instead splits a value into plain values that
+//! each encode under an estimated token budget, for feeding a long table to
+//! a model over several calls. Arrays are cut at row boundaries and every
+//! chunk repeats the primitive fields of the objects around its rows, so a
+//! chunk still says which record its rows belong to.
+
+use std::fmt::Write as _;
+
+use crate::error::{Result, ToonError};
+use crate::options::{DecodeOptions, EncodeOptions, resolve_encode_options};
+use crate::shared::directive::{DIRECTIVE_PREFIX, Directive};
+use crate::shared::string_utils::estimate_tokens;
+use crate::{JsonValue, try_decode, try_encode};
+
+/// Page metadata read from the directive line of a paginated document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageInfo {
+    /// 1-based page number.
+    pub page: usize,
+    /// Number of pages the value was split into.
+    pub total: usize,
+    /// Object keys leading to the split array; empty when the root array was
+    /// split or nothing needed splitting.
+    pub path: Vec<String>,
+}
+
+/// Encode `value` as one or more TOON documents of at most `page_size` array
+/// items each.
+///
+/// # Panics
+///
+/// Panics if `page_size` is zero or a page fails to encode. Use
+/// [`try_encode_paginated`] to handle these errors without panicking.
+#[must_use]
+pub fn encode_paginated(
+    value: impl Into<JsonValue>,
+    page_size: usize,
+    options: Option<EncodeOptions>,
+) -> Vec<String> {
+    try_encode_paginated(value, page_size, options).unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// Encode `value` as one or more TOON documents of at most `page_size` array
+/// items each.
+///
+/// Only the longest array reachable through object keys is split (the first
+/// one in document order on a tie). A value without an array longer than
+/// `page_size` is returned as a single page. `page_size` counts items before
+/// `options` filters or rewrites them. Each page is encoded with `options`;
+/// `emit_directive` is ignored because every page gets its own directive.
+/// Pages written with key folding must be decoded with `expand_paths` on.
+///
+/// # Errors
+///
+/// Returns an error if `page_size` is zero or a page fails to encode.
+pub fn try_encode_paginated(
+    value: impl Into<JsonValue>,
+    page_size: usize,
+    options: Option<EncodeOptions>,
+) -> Result<Vec<String>> {
+    if page_size == 0 {
+        return Err(ToonError::message("Page size must be at least 1"));
+    }
+    let mut value = value.into();
+    let options = EncodeOptions {
+        emit_directive: Some(false),
+        ..options.unwrap_or_default()
+    };
+    let resolved = resolve_encode_options(Some(options.clone()));
+    let directive = Directive::new(resolved.delimiter, resolved.indent).to_line();
+
+    let mut longest = None;
+    find_longest_array(&value, &mut Vec::new(), page_size, &mut longest);
+    let Some((path, _)) = longest else {
+        let body = try_encode(value, Some(options))?;
+        return Ok(vec![page_document(&directive, 1, 1, None, &body)]);
+    };
+
+    let Some(JsonValue::Array(items)) = value_at_mut(&mut value, &path) else {
+        unreachable!("the longest array was found at this path");
+    };
+    let rest = items.split_off(page_size);
+    let total = 1 + rest.len().div_ceil(page_size);
+
+    let mut pages = Vec::with_capacity(total);
+    let body = try_encode(value, Some(options.clone()))?;
+    pages.push(page_document(&directive, 1, total, Some(&path), &body));
+    for (index, slice) in rest.chunks(page_size).enumerate() {
+        let skeleton = path
+            .iter()
+            .rev()
+            .fold(JsonValue::Array(slice.to_vec()), |inner, key| {
+                JsonValue::Object(vec![(key.clone(), inner)])
+            });
+        let body = try_encode(skeleton, Some(options.clone()))?;
+        pages.push(page_document(
+            &directive,
+            index + 2,
+            total,
+            Some(&path),
+            &body,
+        ));
+    }
+    Ok(pages)
+}
+
+/// Decode pages written by [`encode_paginated`] and rejoin the split array.
+///
+/// # Panics
+///
+/// Panics if the pages are incomplete, inconsistent, or fail to decode. Use
+/// [`try_decode_paginated`] to handle these errors without panicking.
+#[must_use]
+pub fn decode_paginated<S: AsRef<str>>(pages: &[S], options: Option<DecodeOptions>) -> JsonValue {
+    try_decode_paginated(pages, options).unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// Decode pages written by [`encode_paginated`] and rejoin the split array.
+///
+/// Pages may be given in any order, but every page from 1 to `total` must be
+/// present exactly once and all pages must name the same path.
+///
+/// # Errors
+///
+/// Returns an error if a document has no page metadata, pages are missing,
+/// duplicated, or disagree on `total` or `path`, a later page has no array
+/// at the path, or a page fails to decode.
+pub fn try_decode_paginated<S: AsRef<str>>(
+    pages: &[S],
+    options: Option<DecodeOptions>,
+) -> Result<JsonValue> {
+    let mut numbered = pages
+        .iter()
+        .map(|document| {
+            let document = document.as_ref();
+            let info = page_info(document)?.ok_or_else(|| {
+                ToonError::message("Document has no page metadata in its #%toon line")
+            })?;
+            Ok((info, document))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    numbered.sort_by_key(|(info, _)| info.page);
+
+    let Some((first, _)) = numbered.first() else {
+        return Err(ToonError::message("No pages to decode"));
+    };
+    let (total, path) = (first.total, first.path.clone());
+    for (expected, (info, _)) in numbered.iter().enumerate() {
+        if info.total != total || info.path != path {
+            return Err(ToonError::message(format!(
+                "Page {} does not belong with page {}: total or path differs",
+                info.page, first.page
+            )));
+        }
+        if info.page != expected + 1 {
+            return Err(ToonError::message(format!(
+                "Expected page {} of {total}, found page {}",
+                expected + 1,
+                info.page
+            )));
+        }
+    }
+    if numbered.len() != total {
+        return Err(ToonError::message(format!(
+            "Expected {total} pages, found {}",
+            numbered.len()
+        )));
+    }
+
+    let mut documents = numbered.into_iter().map(|(_, document)| document);
+    let Some(first) = documents.next() else {
+        return Err(ToonError::message("No pages to decode"));
+    };
+    let mut rest = Vec::new();
+    for (index, document) in documents.enumerate() {
+        let mut page = try_decode(document, options.clone())?;
+        let Some(JsonValue::Array(slice)) = value_at_mut(&mut page, &path) else {
+            return Err(ToonError::message(format!(
+                "Page {} has no array at {}",
+                index + 2,
+                encode_pointer(&path)
+            )));
+        };
+        rest.append(slice);
+    }
+    let mut value = try_decode(first, options)?;
+    if total == 1 {
+        return Ok(value);
+    }
+    let Some(JsonValue::Array(items)) = value_at_mut(&mut value, &path) else {
+        return Err(ToonError::message(format!(
+            "Page 1 has no array at {}",
+            encode_pointer(&path)
+        )));
+    };
+    items.append(&mut rest);
+    Ok(value)
+}
+
+/// Read the page metadata from the directive line of `document`.
+///
+/// Returns `Ok(None)` when the document has no directive or the directive
+/// has no `page=` setting.
+///
+/// # Errors
+///
+/// Returns an error if `page`, `total`, or `path` has an invalid value or
+/// `total` is missing.
+pub fn page_info(document: &str) -> Result<Option<PageInfo>> {
+    let first_line = document.split('\n').next().unwrap_or_default();
+    if Directive::parse(first_line)?.is_none() {
+        return Ok(None);
+    }
+    let settings = first_line
+        .strip_prefix('\u{feff}')
+        .unwrap_or(first_line)
+        .trim_end()
+        .strip_prefix(DIRECTIVE_PREFIX)
+        .unwrap_or_default();
+
+    let (mut page, mut total, mut path) = (None, None, Vec::new());
+    for setting in settings.split(' ').filter(|setting| !setting.is_empty()) {
+        let Some((key, value)) = setting.split_once('=') else {
+            continue;
+        };
+        let invalid = || ToonError::message(format!("Invalid page setting: {setting}"));
+        match key {
+            "page" => page = Some(value.parse::<usize>().map_err(|_| invalid())?),
+            "total" => total = Some(value.parse::<usize>().map_err(|_| invalid())?),
+            "path" => path = decode_pointer(value).ok_or_else(invalid)?,
+            _ => {}
+        }
+    }
+    let Some(page) = page else {
+        return Ok(None);
+    };
+    let total = total.ok_or_else(|| ToonError::message("Page metadata has no total= setting"))?;
+    if page == 0 || page > total {
+        return Err(ToonError::message(format!(
+            "Invalid page setting: page={page} total={total}"
+        )));
+    }
+    Ok(Some(PageInfo { page, total, path }))
+}
+
+/// Split `value` into chunks that each encode in at most `budget` estimated
+/// tokens.
+///
+/// # Panics
+///
+/// Panics if `budget` is zero or a chunk fails to encode. Use
+/// [`try_split_by_token_budget`] to handle these errors without panicking.
+#[must_use]
+pub fn split_by_token_budget(
+    value: impl Into<JsonValue>,
+    budget: usize,
+    options: Option<EncodeOptions>,
+) -> Vec<JsonValue> {
+    try_split_by_token_budget(value, budget, options).unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// Split `value` into chunks that each encode in at most `budget` estimated
+/// tokens.
+///
+/// Tokens are counted with the same estimate as `--stats`, on the chunk
+/// encoded with `options`. A value that fits is returned whole. Otherwise
+/// arrays are packed into runs of consecutive items, and objects keep their
+/// primitive fields in every chunk while their nested arrays and objects are
+/// spread over the chunks (split further when one does not fit on its own).
+/// Concatenating the chunks' arrays and merging their objects gives back the
+/// original value.
+///
+/// A chunk exceeds the budget only when it cannot be cut smaller: a single
+/// array item, or an object's primitive fields alone.
+///
+/// # Errors
+///
+/// Returns an error if `budget` is zero or a chunk fails to encode.
+pub fn try_split_by_token_budget(
+    value: impl Into<JsonValue>,
+    budget: usize,
+    options: Option<EncodeOptions>,
+) -> Result<Vec<JsonValue>> {
+    if budget == 0 {
+        return Err(ToonError::message("Token budget must be at least 1"));
+    }
+    let options = options.unwrap_or_default();
+    let measure = |chunk: &JsonValue| -> Result<usize> {
+        Ok(estimate_tokens(&try_encode(
+            chunk.clone(),
+            Some(options.clone()),
+        )?))
+    };
+    split_to_fit(value.into(), budget, &measure)
+}
+
+/// Split `value` into parts that measure at most `budget` tokens where
+/// possible; `measure` counts a part in place, wrapped in whatever context
+/// surrounds it.
+fn split_to_fit(
+    value: JsonValue,
+    budget: usize,
+    measure: &dyn Fn(&JsonValue) -> Result<usize>,
+) -> Result<Vec<JsonValue>> {
+    let tokens = measure(&value)?;
+    if tokens <= budget {
+        return Ok(vec![value]);
+    }
+    match value {
+        JsonValue::Array(items) => split_items(&items, tokens, budget, measure),
+        JsonValue::Object(entries) => {
+            if entries
+                .iter()
+                .all(|(_, child)| matches!(child, JsonValue::Primitive(_)))
+            {
+                return Ok(vec![JsonValue::Object(entries)]);
+            }
+            // Primitive fields go in every chunk; `selected` holds the nested
+            // fields of the chunk being filled, by index into `entries`.
+            let with_context = |selected: &[(usize, JsonValue)]| {
+                let mut selected = selected.iter().peekable();
+                let mut chunk = Vec::new();
+                for (index, (key, child)) in entries.iter().enumerate() {
+                    if matches!(child, JsonValue::Primitive(_)) {
+                        chunk.push((key.clone(), child.clone()));
+                    } else if let Some((_, part)) = selected.next_if(|(at, _)| *at == index) {
+                        chunk.push((key.clone(), part.clone()));
+                    }
+                }
+                JsonValue::Object(chunk)
+            };
+
+            let mut chunks = Vec::new();
+            let mut current: Vec<(usize, JsonValue)> = Vec::new();
+            for (index, (_, child)) in entries.iter().enumerate() {
+                if matches!(child, JsonValue::Primitive(_)) {
+                    continue;
+                }
+                current.push((index, child.clone()));
+                if measure(&with_context(&current))? <= budget {
+                    continue;
+                }
+                current.pop();
+                if !current.is_empty() {
+                    chunks.push(with_context(&current));
+                    current.clear();
+                }
+                let part_measure =
+                    |part: &JsonValue| measure(&with_context(&[(index, part.clone())]));
+                for part in split_to_fit(child.clone(), budget, &part_measure)? {
+                    chunks.push(with_context(&[(index, part)]));
+                }
+            }
+            if !current.is_empty() {
+                chunks.push(with_context(&current));
+            }
+            Ok(chunks)
+        }
+        JsonValue::Primitive(_) => Ok(vec![value]),
+        #[cfg(feature = "raw")]
+        JsonValue::Raw(_) => Ok(vec![value]),
+    }
+}
+
+/// Pack `items`, which measure `total` tokens together, into runs of
+/// consecutive items that fit `budget`.
+///
+/// Each item is measured once on its own, and the measures scaled so that
+/// they add up to `total`; a shared header such as a tabular field list then
+/// counts once rather than once per item. Runs are grown on these estimates
+/// and encoded only to confirm the cut, dropping items from the end while
+/// the estimate fell short.
+fn split_items(
+    items: &[JsonValue],
+    total: usize,
+    budget: usize,
+    measure: &dyn Fn(&JsonValue) -> Result<usize>,
+) -> Result<Vec<JsonValue>> {
+    let overhead = measure(&JsonValue::Array(Vec::new()))?;
+    let mut alone = Vec::with_capacity(items.len() + 1);
+    alone.push(0usize);
+    for item in items {
+        let tokens = measure(&JsonValue::Array(vec![item.clone()]))?.saturating_sub(overhead);
+        alone.push(alone[alone.len() - 1] + tokens);
+    }
+    let alone_total = alone[items.len()].max(1);
+    let items_total = total.saturating_sub(overhead);
+    // `before[i]` estimates the tokens of the first `i` items.
+    let before: Vec<usize> = alone
+        .into_iter()
+        .map(|tokens| tokens.saturating_mul(items_total) / alone_total)
+        .collect();
+
+    let room = budget.saturating_sub(overhead);
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < items.len() {
+        let mut end = start + 1;
+        while end < items.len() && before[end + 1] - before[start] <= room {
+            end += 1;
+        }
+        let mut run = JsonValue::Array(items[start..end].to_vec());
+        while end - start > 1 && measure(&run)? > budget {
+            end -= 1;
+            run = JsonValue::Array(items[start..end].to_vec());
+        }
+        chunks.push(run);
+        start = end;
+    }
+    Ok(chunks)
+}
+
+fn page_document(
+    directive: &str,
+    page: usize,
+    total: usize,
+    path: Option<&[String]>,
+    body: &str,
+) -> String {
+    let mut document = format!("{directive} page={page} total={total}");
+    if let Some(path) = path {
+        let _ = write!(document, " path={}", encode_pointer(path));
+    }
+    document.push('\n');
+    document.push_str(body);
+    document
+}
+
+/// Record the longest array longer than `page_size` below `value`.
+fn find_longest_array(
+    value: &JsonValue,
+    path: &mut Vec<String>,
+    page_size: usize,
+    longest: &mut Option<(Vec<String>, usize)>,
+) {
+    match value {
+        JsonValue::Array(items) => {
+            let beaten = longest.as_ref().is_none_or(|(_, len)| items.len() > *len);
+            if items.len() > page_size && beaten {
+                *longest = Some((path.clone(), items.len()));
+            }
+        }
+        JsonValue::Object(entries) => {
+            for (key, child) in entries {
+                path.push(key.clone());
+                find_longest_array(child, path, page_size, longest);
+                path.pop();
+            }
+        }
+        JsonValue::Primitive(_) => {}
+        #[cfg(feature = "raw")]
+        JsonValue::Raw(_) => {}
+    }
+}
+
+fn value_at_mut<'a>(value: &'a mut JsonValue, path: &[String]) -> Option<&'a mut JsonValue> {
+    path.iter()
+        .try_fold(value, |current, key| current.get_mut(key))
+}
+
+/// `#/a/b` with `~` and `/` escaped as in RFC 6901 and every byte outside
+/// `A-Za-z0-9-._~` percent-encoded, so the pointer never contains a space.
+fn encode_pointer(path: &[String]) -> String {
+    let mut pointer = String::from("#");
+    for key in path {
+        pointer.push('/');
+        for byte in key.replace('~', "~0").replace('/', "~1").bytes() {
+            if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+                pointer.push(char::from(byte));
+            } else {
+                let _ = write!(pointer, "%{byte:02X}");
+            }
+        }
+    }
+    pointer
+}
+
+fn decode_pointer(pointer: &str) -> Option<Vec<String>> {
+    let pointer = pointer.strip_prefix('#')?;
+    if pointer.is_empty() {
+        return Some(Vec::new());
+    }
+    pointer
+        .strip_prefix('/')?
+        .split('/')
+        .map(|token| {
+            let mut bytes = Vec::with_capacity(token.len());
+            let mut rest = token.as_bytes();
+            while let Some((&byte, tail)) = rest.split_first() {
+                if byte == b'%' {
+                    let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+                    bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                    rest = &tail[2..];
+                } else {
+                    bytes.push(byte);
+                    rest = tail;
+                }
+            }
+            let token = String::from_utf8(bytes).ok()?;
+            Some(token.replace("~1", "/").replace("~0", "~"))
+        })
+        .collect()
+}
@@ -0,0 +1,194 @@
+//! Ordered output from a pool of worker threads.
+//!
+//! [`write_ordered`] runs independent units of work, such as the lines of a
+//! JSON-RPC stream, on worker threads and writes what each unit produces in
+//! input order, as soon as every unit before it is written. Each unit writes
+//! into a byte buffer that is recycled once its contents reach the writer,
+//! so a long stream allocates buffers for the units in flight, not for every
+//! unit. At most a few units per worker are read ahead of the writer.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Condvar, Mutex, PoisonError};
+use std::thread;
+
+use crate::error::Result;
+
+/// Units each worker may be handed ahead of the writer.
+const UNITS_PER_WORKER: usize = 4;
+
+/// Worker threads for `jobs`: 0 means one per available core.
+#[must_use]
+pub fn worker_count(jobs: usize) -> usize {
+    if jobs == 0 {
+        thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+    } else {
+        jobs
+    }
+}
+
+/// Run `work` on every input on up to `jobs` worker threads (0: one per
+/// core), writing what it appends to its buffer to `writer` in input order.
+///
+/// The writer is flushed whenever it catches up. With one worker, inputs
+/// are handled in turn on the calling thread.
+///
+/// Inputs are read on their own thread, so a call that stops early returns
+/// once the read in progress completes.
+///
+/// # Errors
+///
+/// Returns the first error in input order, from reading an input, from
+/// `work`, or from writing, after writing the output of every unit before
+/// it. Nothing after it is written.
+pub fn write_ordered<T: Send>(
+    inputs: impl Iterator<Item = Result<T>> + Send,
+    jobs: usize,
+    work: impl Fn(T, &mut Vec<u8>) -> Result<()> + Sync,
+    writer: &mut impl Write,
+) -> Result<()> {
+    let workers = worker_count(jobs);
+    if workers == 1 {
+        let mut buffer = Vec::new();
+        for input in inputs {
+            buffer.clear();
+            work(input?, &mut buffer)?;
+            writer.write_all(&buffer)?;
+            writer.flush()?;
+        }
+        return Ok(());
+    }
+
+    let window = workers * UNITS_PER_WORKER;
+    let flow = Flow::default();
+    let buffers: Mutex<Vec<Vec<u8>>> = Mutex::new(Vec::new());
+    let (unit_tx, unit_rx) = mpsc::channel::<(usize, Result<T>)>();
+    let unit_rx = Mutex::new(unit_rx);
+    let (done_tx, done_rx) = mpsc::channel();
+
+    thread::scope(|scope| {
+        let (flow, buffers, unit_rx, work) = (&flow, &buffers, &unit_rx, &work);
+        scope.spawn(move || {
+            for (index, input) in inputs.enumerate() {
+                if !flow.wait_for_room(index, window) || unit_tx.send((index, input)).is_err() {
+                    return;
+                }
+            }
+        });
+        for _ in 0..workers {
+            let done_tx = done_tx.clone();
+            scope.spawn(move || {
+                let _guard = StopOnPanic(flow);
+                loop {
+                    let next = unit_rx
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .recv();
+                    let Ok((index, input)) = next else {
+                        break;
+                    };
+                    let mut buffer = buffers
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .pop()
+                        .unwrap_or_default();
+                    buffer.clear();
+                    let output = input
+                        .and_then(|input| work(input, &mut buffer))
+                        .map(|()| buffer);
+                    if done_tx.send((index, output)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(done_tx);
+        let written = write_in_order(done_rx, writer, flow, buffers);
+        flow.stop();
+        written
+    })
+}
+
+/// Write each unit's output once every unit before it is written.
+fn write_in_order(
+    done: Receiver<(usize, Result<Vec<u8>>)>,
+    writer: &mut impl Write,
+    flow: &Flow,
+    buffers: &Mutex<Vec<Vec<u8>>>,
+) -> Result<()> {
+    let mut pending = BTreeMap::new();
+    let mut next = 0;
+    for (index, output) in done {
+        pending.insert(index, output);
+        while let Some(output) = pending.remove(&next) {
+            let buffer = output?;
+            writer.write_all(&buffer)?;
+            buffers
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(buffer);
+            next += 1;
+            flow.written(next);
+        }
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// How far the writer has got, so the reader stays at most a window ahead.
+#[derive(Default)]
+struct Flow {
+    state: Mutex<FlowState>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct FlowState {
+    written: usize,
+    stopped: bool,
+}
+
+impl Flow {
+    /// Wait until the unit at `index` is within `window` of the writer;
+    /// `false` if the writer stopped.
+    fn wait_for_room(&self, index: usize, window: usize) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        while !state.stopped && index >= state.written + window {
+            state = self
+                .changed
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        !state.stopped
+    }
+
+    fn written(&self, count: usize) {
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .written = count;
+        self.changed.notify_all();
+    }
+
+    fn stop(&self) {
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .stopped = true;
+        self.changed.notify_all();
+    }
+}
+
+/// Stops the reader when a worker panics, so the writer is not left waiting
+/// for a unit that will never arrive; the panic resurfaces when the worker
+/// is joined.
+struct StopOnPanic<'a>(&'a Flow);
+
+impl Drop for StopOnPanic<'_> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.0.stop();
+        }
+    }
+}
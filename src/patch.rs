@@ -0,0 +1,456 @@
+//! Incremental edits to encoded TOON.
+//!
+//! [`patch_encoded`] replaces the value at a path inside an existing TOON
+//! document without re-encoding the whole document. Only the smallest
+//! enclosing section is rewritten: the entry for a key, a list array item,
+//! or (for inline and tabular arrays, whose values share lines) the whole
+//! array. Every other line is kept byte-for-byte, and the parent header's
+//! length is updated when an item is appended to a list array. A path that
+//! meets a folded key (`a.b.c: 1`) rewrites the whole document instead.
+
+use std::ops::Range;
+
+use crate::decode::parser::{
+    is_array_header_content, is_key_value_content, parse_array_header_line, parse_key_token,
+};
+use crate::decode::scanner::{ParsedLine, create_scan_state, parse_lines_sync};
+use crate::encode::encoders::{encode_json_value, encode_list_item};
+use crate::encode::prepare_value;
+use crate::error::{Result, ToonError};
+use crate::options::{
+    DecodeOptions, EncodeOptions, ExpandPathsMode, KeyFoldingMode, PathSegment,
+    ResolvedEncodeOptions, format_path, resolve_encode_options,
+};
+use crate::shared::constants::{
+    DEFAULT_DELIMITER, DOT, LIST_ITEM_MARKER, LIST_ITEM_PREFIX, OPEN_BRACKET,
+};
+use crate::shared::string_utils::find_unquoted_char;
+use crate::{JsonValue, try_decode, try_encode};
+
+/// Replace the value at `path` in `original` and return the patched TOON.
+///
+/// A path that ends one past the last item of an array appends an item, and
+/// a path that ends at a missing key adds the entry at the end of its object.
+///
+/// # Panics
+///
+/// Panics if the path does not exist or the document cannot be decoded. Use
+/// [`try_patch_encoded`] to handle these errors without panicking.
+#[must_use]
+pub fn patch_encoded(
+    original: &str,
+    path: &[PathSegment],
+    new_value: impl Into<JsonValue>,
+    options: Option<EncodeOptions>,
+) -> String {
+    try_patch_encoded(original, path, new_value, options).unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// Replace the value at `path` in `original` and return the patched TOON.
+///
+/// `original` is read with the indentation of `options`, and the rewritten
+/// section is encoded with `options` as if it were a whole document.
+///
+/// # Errors
+///
+/// Returns an error if the path does not exist in the document, or the
+/// rewritten section cannot be decoded or encoded.
+pub fn try_patch_encoded(
+    original: &str,
+    path: &[PathSegment],
+    new_value: impl Into<JsonValue>,
+    options: Option<EncodeOptions>,
+) -> Result<String> {
+    let new_value = new_value.into();
+    if path.is_empty() {
+        return try_encode(new_value, options);
+    }
+    let resolved = resolve_encode_options(options.clone());
+    let decode_options = DecodeOptions {
+        indent: Some(resolved.indent),
+        strict: Some(false),
+        ..DecodeOptions::default()
+    };
+
+    let raw = original.split('\n').collect::<Vec<_>>();
+    let mut state = create_scan_state();
+    let lines = parse_lines_sync(
+        raw.iter().map(|line| (*line).to_string()),
+        resolved.indent,
+        false,
+        &mut state,
+    )?;
+
+    let section = match root_container(&lines) {
+        Some(container) => locate(&lines, container, path)?,
+        None => Section::Document,
+    };
+
+    let (splice, replacement) = match section {
+        Section::Document => {
+            // Folded keys (`a.b.c: 1`) are expanded so the path can reach
+            // through them, and folded again on the way out.
+            let folded = lines.iter().any(is_folded);
+            let decode_options = DecodeOptions {
+                expand_paths: folded.then_some(ExpandPathsMode::Safe),
+                ..decode_options
+            };
+            let options = options.unwrap_or_default();
+            let options = EncodeOptions {
+                key_folding: options
+                    .key_folding
+                    .or_else(|| folded.then_some(KeyFoldingMode::Safe)),
+                ..options
+            };
+            let mut value = try_decode(original, Some(decode_options))?;
+            set_at_path(&mut value, path, new_value).ok_or_else(|| not_found(path))?;
+            return try_encode(value, Some(options));
+        }
+        Section::Entry { lines: range, rest } => {
+            let depth = lines[range.start].depth;
+            let source = section_source(&lines[range.clone()], depth * resolved.indent, "");
+            let JsonValue::Object(mut entries) = try_decode(&source, Some(decode_options))? else {
+                return Err(not_found(path));
+            };
+            let Some((key, mut value)) = entries.pop() else {
+                return Err(not_found(path));
+            };
+            set_at_path(&mut value, &path[rest..], new_value).ok_or_else(|| not_found(path))?;
+            let encoded = encode_entry(key, value, depth, &resolved)?;
+            (raw_range(&lines[range]), encoded)
+        }
+        Section::Item { lines: range, rest } => {
+            let depth = lines[range.start].depth;
+            let base = (depth - 1) * resolved.indent;
+            let source = section_source(&lines[range.clone()], base, "[1]:\n");
+            let JsonValue::Array(mut items) = try_decode(&source, Some(decode_options))? else {
+                return Err(not_found(path));
+            };
+            let Some(mut value) = items.pop() else {
+                return Err(not_found(path));
+            };
+            set_at_path(&mut value, &path[rest..], new_value).ok_or_else(|| not_found(path))?;
+            let encoded = encode_list_item(&prepare_value(value, &resolved), depth, &resolved)?;
+            (raw_range(&lines[range]), encoded)
+        }
+        Section::NewEntry { after, depth, key } => {
+            let encoded = encode_entry(key, new_value, depth, &resolved)?;
+            (after..after, encoded)
+        }
+        Section::NewItem {
+            after,
+            depth,
+            header,
+        } => {
+            let encoded = encode_list_item(&prepare_value(new_value, &resolved), depth, &resolved)?;
+            let mut patched = raw
+                .iter()
+                .map(|line| (*line).to_string())
+                .collect::<Vec<_>>();
+            let header_index = lines[header].line_number - 1;
+            patched[header_index] = increment_length(&patched[header_index]);
+            patched.splice(after..after, encoded);
+            return Ok(patched.join("\n"));
+        }
+    };
+
+    let mut patched = Vec::with_capacity(raw.len() + replacement.len());
+    patched.extend(raw[..splice.start].iter().map(|line| (*line).to_string()));
+    patched.extend(replacement);
+    patched.extend(raw[splice.end..].iter().map(|line| (*line).to_string()));
+    Ok(patched.join("\n"))
+}
+
+/// The part of the document a patch rewrites. Line ranges index parsed
+/// lines; `after` is a raw line index to insert at.
+enum Section {
+    /// The whole document, re-encoded.
+    Document,
+    /// A `key: ...` entry and its nested lines; `rest` is the index into the
+    /// path of the first segment below the key.
+    Entry { lines: Range<usize>, rest: usize },
+    /// A list array item and its nested lines.
+    Item { lines: Range<usize>, rest: usize },
+    /// A new entry appended to an object.
+    NewEntry {
+        after: usize,
+        depth: usize,
+        key: String,
+    },
+    /// A new item appended to the list array whose header is `header`.
+    NewItem {
+        after: usize,
+        depth: usize,
+        header: usize,
+    },
+}
+
+/// Lines nested under an object or list array header, at `depth`.
+#[derive(Clone, Copy)]
+enum Container {
+    Object { depth: usize, header: Option<usize> },
+    List { depth: usize, header: usize },
+}
+
+/// The container of the document's top-level values, or `None` if the
+/// document is a single primitive or an inline or tabular root array.
+fn root_container(lines: &[ParsedLine]) -> Option<Container> {
+    let first = lines.first()?;
+    if is_array_header_content(&first.content) {
+        let parsed = parse_array_header_line(&first.content, DEFAULT_DELIMITER).ok()??;
+        let is_list = parsed.header.fields.is_none() && parsed.inline_values.is_none();
+        return is_list.then_some(Container::List {
+            depth: 1,
+            header: 0,
+        });
+    }
+    (lines.len() > 1 || is_key_value_content(&first.content)).then_some(Container::Object {
+        depth: 0,
+        header: None,
+    })
+}
+
+fn locate(lines: &[ParsedLine], root: Container, path: &[PathSegment]) -> Result<Section> {
+    let mut container = root;
+    for (index, segment) in path.iter().enumerate() {
+        let is_last = index + 1 == path.len();
+        let (depth, header) = match container {
+            Container::Object { depth, header } => (depth, header),
+            Container::List { depth, header } => (depth, Some(header)),
+        };
+        let start = header.map_or(0, |header| header + 1);
+        let end = header.map_or(lines.len(), |header| section_end(lines, header));
+        let children = (start..end).filter(|&child| lines[child].depth == depth);
+
+        match (container, segment) {
+            (Container::Object { .. }, PathSegment::Key(key)) => {
+                // A folded key may hold the path's next segments, so the
+                // whole document is rewritten.
+                if children.clone().any(|child| is_folded(&lines[child])) {
+                    return Ok(Section::Document);
+                }
+                let Some(child) = children.into_iter().find(|&child| {
+                    !is_list_item(&lines[child]) && entry_key(&lines[child]).as_ref() == Some(key)
+                }) else {
+                    if !is_last {
+                        return Err(not_found(path));
+                    }
+                    return Ok(Section::NewEntry {
+                        after: append_point(lines, header, end),
+                        depth,
+                        key: key.clone(),
+                    });
+                };
+                let child_end = section_end(lines, child);
+                match (is_last, nested_container(&lines[child], child)) {
+                    (false, Some(nested @ Container::Object { .. }))
+                        if matches!(path[index + 1], PathSegment::Key(_)) =>
+                    {
+                        container = nested;
+                    }
+                    (false, Some(nested @ Container::List { .. }))
+                        if matches!(path[index + 1], PathSegment::Index(_)) =>
+                    {
+                        container = nested;
+                    }
+                    _ => {
+                        return Ok(section_unless_folded(
+                            lines,
+                            Section::Entry {
+                                lines: child..child_end,
+                                rest: index + 1,
+                            },
+                            path,
+                        ));
+                    }
+                }
+            }
+            (Container::List { header, .. }, PathSegment::Index(item)) => {
+                let items = children.collect::<Vec<_>>();
+                if let Some(&child) = items.get(*item) {
+                    return Ok(section_unless_folded(
+                        lines,
+                        Section::Item {
+                            lines: child..section_end(lines, child),
+                            rest: index + 1,
+                        },
+                        path,
+                    ));
+                }
+                if *item == items.len() && is_last {
+                    return Ok(Section::NewItem {
+                        after: append_point(lines, Some(header), end),
+                        depth,
+                        header,
+                    });
+                }
+                return Err(not_found(path));
+            }
+            _ => return Err(not_found(path)),
+        }
+    }
+    Err(not_found(path))
+}
+
+/// The container opened by an entry line: a nested object (`key:`) or a
+/// list array (`key[N]:` with items below).
+fn nested_container(line: &ParsedLine, index: usize) -> Option<Container> {
+    if let Ok(Some(parsed)) = parse_array_header_line(&line.content, DEFAULT_DELIMITER) {
+        let is_list = parsed.header.fields.is_none() && parsed.inline_values.is_none();
+        return is_list.then_some(Container::List {
+            depth: line.depth + 1,
+            header: index,
+        });
+    }
+    let (_, end, _) = parse_key_token(&line.content, 0).ok()?;
+    line.content[end..]
+        .trim()
+        .is_empty()
+        .then_some(Container::Object {
+            depth: line.depth + 1,
+            header: Some(index),
+        })
+}
+
+fn entry_key(line: &ParsedLine) -> Option<String> {
+    if let Ok(Some(parsed)) = parse_array_header_line(&line.content, DEFAULT_DELIMITER) {
+        return parsed.header.key;
+    }
+    parse_key_token(&line.content, 0)
+        .ok()
+        .map(|(key, _, _)| key)
+}
+
+/// `section`, or the whole document if the path continues into it and it
+/// holds a folded key.
+fn section_unless_folded(lines: &[ParsedLine], section: Section, path: &[PathSegment]) -> Section {
+    let (Section::Entry { lines: range, rest } | Section::Item { lines: range, rest }) = &section
+    else {
+        return section;
+    };
+    if *rest < path.len() && lines[range.clone()].iter().any(is_folded) {
+        Section::Document
+    } else {
+        section
+    }
+}
+
+/// Whether the key of an entry or list item line is folded: unquoted and
+/// dotted, as `a.b.c: 1` or `- a.b[2]: 1,2`.
+fn is_folded(line: &ParsedLine) -> bool {
+    let content = line
+        .content
+        .strip_prefix(LIST_ITEM_PREFIX)
+        .unwrap_or(&line.content);
+    if let Ok(Some(parsed)) = parse_array_header_line(content, DEFAULT_DELIMITER) {
+        return !parsed.header.key_was_quoted
+            && parsed.header.key.is_some_and(|key| key.contains(DOT));
+    }
+    is_key_value_content(content)
+        && parse_key_token(content, 0)
+            .is_ok_and(|(key, _, was_quoted)| !was_quoted && key.contains(DOT))
+}
+
+fn is_list_item(line: &ParsedLine) -> bool {
+    line.content == LIST_ITEM_MARKER || line.content.starts_with(LIST_ITEM_PREFIX)
+}
+
+/// Index one past the last line nested under `lines[index]`.
+fn section_end(lines: &[ParsedLine], index: usize) -> usize {
+    let depth = lines[index].depth;
+    lines[index + 1..]
+        .iter()
+        .position(|line| line.depth <= depth)
+        .map_or(lines.len(), |offset| index + 1 + offset)
+}
+
+/// Raw line index just after the last line of a container.
+fn append_point(lines: &[ParsedLine], header: Option<usize>, end: usize) -> usize {
+    match (end.checked_sub(1), header) {
+        (Some(last), _) => lines[last].line_number,
+        (None, Some(header)) => lines[header].line_number,
+        (None, None) => 0,
+    }
+}
+
+/// Raw line indices spanned by `lines`, including blank lines between them.
+fn raw_range(lines: &[ParsedLine]) -> Range<usize> {
+    let start = lines.first().map_or(0, |line| line.line_number - 1);
+    let end = lines.last().map_or(start, |line| line.line_number);
+    start..end
+}
+
+/// Source text of `lines` with `base` columns of indentation removed, after
+/// `prefix`.
+fn section_source(lines: &[ParsedLine], base: usize, prefix: &str) -> String {
+    let mut source = prefix.to_string();
+    for (index, line) in lines.iter().enumerate() {
+        if index > 0 {
+            source.push('\n');
+        }
+        source.push_str(line.raw.get(base..).unwrap_or_default());
+    }
+    source
+}
+
+fn encode_entry(
+    key: String,
+    value: JsonValue,
+    depth: usize,
+    options: &ResolvedEncodeOptions,
+) -> Result<Vec<String>> {
+    let entry = prepare_value(JsonValue::Object(vec![(key, value)]), options);
+    let indent = " ".repeat(depth * options.indent);
+    Ok(encode_json_value(&entry, options)?
+        .into_iter()
+        .map(|line| format!("{indent}{line}"))
+        .collect())
+}
+
+/// Add one to the declared length in an array header line.
+fn increment_length(line: &str) -> String {
+    let Some(open) = find_unquoted_char(line, OPEN_BRACKET, 0) else {
+        return line.to_string();
+    };
+    let digits_end = line[open + 1..]
+        .find(|ch: char| !ch.is_ascii_digit())
+        .map_or(line.len(), |offset| open + 1 + offset);
+    let Ok(length) = line[open + 1..digits_end].parse::<usize>() else {
+        return line.to_string();
+    };
+    format!("{}{}{}", &line[..=open], length + 1, &line[digits_end..])
+}
+
+/// Replace the value at `path` within `value`, appending when the last
+/// segment is one past the end of an array or a missing object key.
+/// Returns `None` if the path does not exist.
+fn set_at_path(value: &mut JsonValue, path: &[PathSegment], new_value: JsonValue) -> Option<()> {
+    let Some((segment, rest)) = path.split_first() else {
+        *value = new_value;
+        return Some(());
+    };
+    match (value, segment) {
+        (JsonValue::Object(entries), PathSegment::Key(key)) => {
+            match entries.iter_mut().find(|(existing, _)| existing == key) {
+                Some((_, child)) => set_at_path(child, rest, new_value),
+                None if rest.is_empty() => {
+                    entries.push((key.clone(), new_value));
+                    Some(())
+                }
+                None => None,
+            }
+        }
+        (JsonValue::Array(items), PathSegment::Index(index)) => {
+            if *index == items.len() && rest.is_empty() {
+                items.push(new_value);
+                return Some(());
+            }
+            set_at_path(items.get_mut(*index)?, rest, new_value)
+        }
+        _ => None,
+    }
+}
+
+fn not_found(path: &[PathSegment]) -> ToonError {
+    ToonError::message(format!("Path not found in document: {}", format_path(path)))
+}
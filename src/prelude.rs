@@ -0,0 +1,32 @@
+//! The stable API, for glob import.
+//!
+//! ```
+//! use toon::prelude::*;
+//!
+//! let value = JsonValue::from(serde_json::json!({"tags": ["a", "b"]}));
+//! let text = try_encode(value.clone(), None).unwrap();
+//! assert_eq!(text, "tags[2]: a,b");
+//! assert_eq!(try_decode(&text, None).unwrap(), value);
+//! ```
+//!
+//! Everything here follows semver: it changes incompatibly only in a major
+//! release. Modules hidden from the documentation (the decoder's parser,
+//! scanner, and validation, the encoder's internals, and `shared`) are not
+//! covered and may change in any release; build the docs with the
+//! `internals` feature to see them.
+//!
+//! The crate's `Result` alias is left out so that the glob does not shadow
+//! `std::result::Result`; use `toon::error::Result` by name.
+
+pub use crate::codec::{Decoder, Encoder};
+pub use crate::error::ToonError;
+pub use crate::options::{
+    ColumnType, DecodeOptions, DecodeProfile, DecodeStreamOptions, EmptyCellMode, EmptyStringStyle,
+    EncodeOptions, ExpandPathsMode, KeyFoldingMode, MissingCellMode, SerdeKeyOrder,
+};
+pub use crate::sink::EventSink;
+pub use crate::{
+    JsonArray, JsonObject, JsonPrimitive, JsonStreamEvent, JsonValue, StringOrNumberOrBoolOrNull,
+    decode, decode_events_iter, decode_into_sink, decode_stream_sync, encode, encode_lines,
+    json_to_toon, toon_to_json, try_decode, try_decode_stream_sync, try_encode, try_encode_lines,
+};
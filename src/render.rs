@@ -0,0 +1,139 @@
+//! Syntax-highlighted rendering of TOON text.
+//!
+//! [`highlight_toon`] classifies the tokens from [`lex_toon`] (no decoding,
+//! so partial or invalid documents still render) and wraps keys, values, array headers,
+//! and list markers in ANSI colors or HTML spans.
+
+use crate::lex::{TokenKind, lex_toon};
+use crate::shared::constants::{DOUBLE_QUOTE, NULL_LITERAL};
+use crate::shared::literal_utils::{is_boolean_or_null_literal, is_numeric_literal};
+
+/// Output format for [`highlight_toon`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    /// ANSI color escapes for terminals.
+    Ansi,
+    /// HTML-escaped text with `<span class="toon-…">` around each token.
+    /// The caller supplies the surrounding `<pre>` and the stylesheet.
+    Html,
+}
+
+/// Highlight class of a token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenClass {
+    Key,
+    /// Array header: `[N]`, `[N|]`, `{fields}`.
+    Header,
+    ListMarker,
+    String,
+    Number,
+    Bool,
+    Null,
+}
+
+impl TokenClass {
+    /// HTML class name (`toon-key`, `toon-header`, ...).
+    #[must_use]
+    pub const fn css_class(self) -> &'static str {
+        match self {
+            Self::Key => "toon-key",
+            Self::Header => "toon-header",
+            Self::ListMarker => "toon-list-marker",
+            Self::String => "toon-string",
+            Self::Number => "toon-number",
+            Self::Bool => "toon-bool",
+            Self::Null => "toon-null",
+        }
+    }
+
+    const fn ansi_color(self) -> &'static str {
+        match self {
+            Self::Key => "\x1b[34m",
+            Self::Header => "\x1b[35m",
+            Self::ListMarker => "\x1b[1;33m",
+            Self::String => "\x1b[32m",
+            Self::Number => "\x1b[36m",
+            Self::Bool => "\x1b[33m",
+            Self::Null => "\x1b[90m",
+        }
+    }
+}
+
+const ANSI_RESET: &str = "\x1b[0m";
+
+/// Render `input` with syntax highlighting.
+///
+/// Text outside highlighted tokens (indentation, colons, delimiters, line
+/// breaks) is copied unchanged, HTML-escaped for [`Theme::Html`].
+#[must_use]
+pub fn highlight_toon(input: &str, theme: Theme) -> String {
+    let mut out = String::with_capacity(input.len() * 2);
+    let mut cursor = 0;
+    for token in lex_toon(input) {
+        let text = &input[token.span.clone()];
+        let class = match token.kind {
+            TokenKind::Key => TokenClass::Key,
+            TokenKind::Header => TokenClass::Header,
+            TokenKind::ListMarker => TokenClass::ListMarker,
+            TokenKind::Value | TokenKind::Cell => value_class(text),
+            TokenKind::Indent | TokenKind::Colon | TokenKind::Delimiter => continue,
+        };
+        push_plain(&mut out, &input[cursor..token.span.start], theme);
+        push_token(&mut out, text, class, theme);
+        cursor = token.span.end;
+    }
+    push_plain(&mut out, &input[cursor..], theme);
+    out
+}
+
+fn value_class(token: &str) -> TokenClass {
+    if token.starts_with(DOUBLE_QUOTE) {
+        TokenClass::String
+    } else if is_boolean_or_null_literal(token) {
+        if token == NULL_LITERAL {
+            TokenClass::Null
+        } else {
+            TokenClass::Bool
+        }
+    } else if is_numeric_literal(token) {
+        TokenClass::Number
+    } else {
+        TokenClass::String
+    }
+}
+
+fn push_token(out: &mut String, text: &str, class: TokenClass, theme: Theme) {
+    match theme {
+        Theme::Ansi => {
+            out.push_str(class.ansi_color());
+            out.push_str(text);
+            out.push_str(ANSI_RESET);
+        }
+        Theme::Html => {
+            out.push_str("<span class=\"");
+            out.push_str(class.css_class());
+            out.push_str("\">");
+            push_html_escaped(out, text);
+            out.push_str("</span>");
+        }
+    }
+}
+
+fn push_plain(out: &mut String, text: &str, theme: Theme) {
+    match theme {
+        Theme::Ansi => out.push_str(text),
+        Theme::Html => push_html_escaped(out, text),
+    }
+}
+
+fn push_html_escaped(out: &mut String, text: &str) {
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(ch),
+        }
+    }
+}
@@ -0,0 +1,584 @@
+//! Schemas describing the expected shape of a document.
+//!
+//! A [`Schema`] is written by hand or inferred from a sample with
+//! [`Schema::infer`]. Decoding with
+//! [`try_decode_with_schema`](crate::decode::try_decode_with_schema) types
+//! the document while its events are built: primitives are coerced to the
+//! declared type (a quoted `"42"` read as an integer, an unquoted `7` read as
+//! a string), enums are checked, and [`TypeHook`]s convert values such as
+//! dates. Anything that does not fit is reported as a [`Violation`] rather
+//! than failing the decode.
+//!
+//! [`Schema::validate`] checks a value without coercing it, and
+//! [`try_encode_with_schema`](crate::encode::try_encode_with_schema) rejects
+//! a value with violations before writing any TOON.
+//!
+//! `null` fits every type, as in typed tabular columns.
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::decode::parser::coerce_to_column_type;
+use crate::encode::primitives::encode_primitive;
+use crate::error::Result;
+use crate::options::{ColumnType, PathSegment, format_path};
+use crate::shared::constants::DEFAULT_DELIMITER;
+use crate::sink::EventSink;
+use crate::{JsonObject, JsonPrimitive, JsonStreamEvent, JsonValue, StringOrNumberOrBoolOrNull};
+
+/// The expected type of a value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schema {
+    /// Any value.
+    Any,
+    String,
+    Number,
+    /// A number with no fractional part.
+    Integer,
+    Bool,
+    /// One of the listed values, compared by their TOON text.
+    Enum(Vec<String>),
+    /// A primitive converted or checked by a hook.
+    Custom(TypeHook),
+    /// An array whose items all match the schema.
+    Array(Box<Self>),
+    Object(ObjectSchema),
+}
+
+/// The fields of an object.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ObjectSchema {
+    pub fields: Vec<Field>,
+    /// Accept keys that are not listed in `fields`.
+    pub allow_unknown: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub schema: Schema,
+    pub required: bool,
+}
+
+impl Field {
+    pub fn required(name: impl Into<String>, schema: Schema) -> Self {
+        Self {
+            name: name.into(),
+            schema,
+            required: true,
+        }
+    }
+
+    pub fn optional(name: impl Into<String>, schema: Schema) -> Self {
+        Self {
+            name: name.into(),
+            schema,
+            required: false,
+        }
+    }
+}
+
+type ConvertFn = dyn Fn(&JsonPrimitive) -> std::result::Result<JsonPrimitive, String> + Send + Sync;
+
+/// A named conversion for [`Schema::Custom`], such as parsing dates. The
+/// hook returns the converted primitive, or a message describing why the
+/// value does not fit.
+#[derive(Clone)]
+pub struct TypeHook {
+    name: String,
+    convert: Arc<ConvertFn>,
+}
+
+impl TypeHook {
+    pub fn new(
+        name: impl Into<String>,
+        convert: impl Fn(&JsonPrimitive) -> std::result::Result<JsonPrimitive, String>
+        + Send
+        + Sync
+        + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            convert: Arc::new(convert),
+        }
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Run the hook on `value`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the hook's message if the value does not fit.
+    pub fn convert(&self, value: &JsonPrimitive) -> std::result::Result<JsonPrimitive, String> {
+        (self.convert)(value)
+    }
+}
+
+impl fmt::Debug for TypeHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TypeHook({})", self.name)
+    }
+}
+
+/// Hooks are equal when their names are.
+impl PartialEq for TypeHook {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+/// A value that does not match its schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub path: Vec<PathSegment>,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            f.write_str(&self.message)
+        } else {
+            write!(f, "{}: {}", format_path(&self.path), self.message)
+        }
+    }
+}
+
+static ANY: Schema = Schema::Any;
+
+impl Schema {
+    /// Infer a schema from a sample value. Every key of an object is
+    /// required unless some items of the same array lack it; integral numbers
+    /// are inferred as [`Schema::Integer`].
+    #[must_use]
+    pub fn infer(value: &JsonValue) -> Self {
+        infer_values(&[value]).unwrap_or(Self::Any)
+    }
+
+    /// Check `value` against this schema without coercing it: a quoted
+    /// `"42"` does not match [`Schema::Integer`]. Missing required fields are
+    /// reported after the object's other violations, as when decoding.
+    #[must_use]
+    pub fn validate(&self, value: &JsonValue) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        validate_value(self, value, &mut Vec::new(), &mut violations);
+        violations
+    }
+
+    /// Name of the type, as used in violation messages.
+    #[must_use]
+    pub fn type_name(&self) -> &str {
+        match self {
+            Self::Any => "any",
+            Self::String => "string",
+            Self::Number => "number",
+            Self::Integer => "integer",
+            Self::Bool => "bool",
+            Self::Enum(_) => "enum",
+            Self::Custom(hook) => hook.name(),
+            Self::Array(_) => "array",
+            Self::Object(_) => "object",
+        }
+    }
+
+    /// Type `value` against this schema: the value to keep, or a message
+    /// describing why it does not fit.
+    fn type_primitive(&self, value: &JsonPrimitive) -> std::result::Result<JsonPrimitive, String> {
+        if matches!(value, StringOrNumberOrBoolOrNull::Null) {
+            return Ok(value.clone());
+        }
+        let column_type = match self {
+            Self::Any => return Ok(value.clone()),
+            Self::String => ColumnType::String,
+            Self::Number => ColumnType::Number,
+            Self::Integer => ColumnType::Integer,
+            Self::Bool => ColumnType::Bool,
+            Self::Enum(allowed) => {
+                let text = primitive_text(value);
+                return if allowed.contains(&text) {
+                    Ok(value.clone())
+                } else {
+                    Err(format!(
+                        "expected one of {}, found {text}",
+                        allowed.join(", ")
+                    ))
+                };
+            }
+            Self::Custom(hook) => return hook.convert(value),
+            Self::Array(_) | Self::Object(_) => {
+                return Err(format!(
+                    "expected {}, found {}",
+                    self.type_name(),
+                    primitive_type_name(value)
+                ));
+            }
+        };
+        coerce_to_column_type(value, &primitive_text(value), column_type).ok_or_else(|| {
+            format!(
+                "expected {}, found {} {}",
+                self.type_name(),
+                primitive_type_name(value),
+                primitive_text(value)
+            )
+        })
+    }
+}
+
+fn validate_value(
+    schema: &Schema,
+    value: &JsonValue,
+    path: &mut Vec<PathSegment>,
+    violations: &mut Vec<Violation>,
+) {
+    let mut violation = |message: String| {
+        violations.push(Violation {
+            path: path.clone(),
+            message,
+        });
+    };
+    match (schema, value) {
+        (Schema::Any, _) => {}
+        (_, JsonValue::Primitive(primitive)) => match schema.type_primitive(primitive) {
+            Ok(typed) if typed == *primitive || matches!(schema, Schema::Custom(_)) => {}
+            Ok(_) => violation(format!(
+                "expected {}, found {} {}",
+                schema.type_name(),
+                primitive_type_name(primitive),
+                primitive_text(primitive)
+            )),
+            Err(message) => violation(message),
+        },
+        (Schema::Array(item), JsonValue::Array(items)) => {
+            for (index, item_value) in items.iter().enumerate() {
+                path.push(PathSegment::Index(index));
+                validate_value(item, item_value, path, violations);
+                path.pop();
+            }
+        }
+        (Schema::Object(object), JsonValue::Object(entries)) => {
+            for (key, field_value) in entries {
+                path.push(PathSegment::Key(key.clone()));
+                match object.fields.iter().find(|field| field.name == *key) {
+                    Some(field) => validate_value(&field.schema, field_value, path, violations),
+                    None if !object.allow_unknown => violations.push(Violation {
+                        path: path.clone(),
+                        message: "unknown field".to_string(),
+                    }),
+                    None => {}
+                }
+                path.pop();
+            }
+            for field in &object.fields {
+                if field.required && !entries.iter().any(|(key, _)| *key == field.name) {
+                    violations.push(Violation {
+                        path: path.clone(),
+                        message: format!("missing required field {}", field.name),
+                    });
+                }
+            }
+        }
+        (_, found) => violation(format!(
+            "expected {}, found {}",
+            schema.type_name(),
+            value_kind(found)
+        )),
+    }
+}
+
+/// The narrowest schema matching all of `values`, or `None` if they are all
+/// `null`, which fits any type.
+fn infer_values(values: &[&JsonValue]) -> Option<Schema> {
+    let values: Vec<&JsonValue> = values
+        .iter()
+        .copied()
+        .filter(|value| {
+            !matches!(
+                value,
+                JsonValue::Primitive(StringOrNumberOrBoolOrNull::Null)
+            )
+        })
+        .collect();
+    let kind = value_kind(values.first()?);
+    if values.iter().any(|value| value_kind(value) != kind) {
+        return Some(Schema::Any);
+    }
+
+    let mut numbers = values.iter().filter_map(|value| match value {
+        JsonValue::Primitive(primitive) if primitive.is_number() => Some(primitive),
+        _ => None,
+    });
+    let arrays = values.iter().filter_map(|value| match value {
+        JsonValue::Array(items) => Some(items),
+        _ => None,
+    });
+    let objects: Vec<&JsonObject> = values
+        .iter()
+        .filter_map(|value| match value {
+            JsonValue::Object(entries) => Some(entries),
+            _ => None,
+        })
+        .collect();
+
+    Some(match kind {
+        "string" => Schema::String,
+        "bool" => Schema::Bool,
+        "number" if numbers.all(JsonPrimitive::is_integer) => Schema::Integer,
+        "number" => Schema::Number,
+        "array" => {
+            let items: Vec<&JsonValue> = arrays.flatten().collect();
+            Schema::Array(Box::new(infer_values(&items).unwrap_or(Schema::Any)))
+        }
+        _ => {
+            let mut names: Vec<&str> = Vec::new();
+            for (name, _) in objects.iter().copied().flatten() {
+                if !names.contains(&name.as_str()) {
+                    names.push(name);
+                }
+            }
+            let fields = names
+                .into_iter()
+                .map(|name| {
+                    let present: Vec<&JsonValue> = objects
+                        .iter()
+                        .filter_map(|entries| {
+                            entries
+                                .iter()
+                                .find(|(key, _)| key == name)
+                                .map(|(_, value)| value)
+                        })
+                        .collect();
+                    Field {
+                        name: name.to_string(),
+                        required: present.len() == objects.len(),
+                        schema: infer_values(&present).unwrap_or(Schema::Any),
+                    }
+                })
+                .collect();
+            Schema::Object(ObjectSchema {
+                fields,
+                allow_unknown: false,
+            })
+        }
+    })
+}
+
+const fn value_kind(value: &JsonValue) -> &'static str {
+    match value {
+        JsonValue::Primitive(primitive) => primitive_type_name(primitive),
+        JsonValue::Array(_) => "array",
+        JsonValue::Object(_) => "object",
+        #[cfg(feature = "raw")]
+        JsonValue::Raw(_) => "raw text",
+    }
+}
+
+/// The primitive as written in TOON, without quotes for strings.
+fn primitive_text(value: &JsonPrimitive) -> String {
+    match value {
+        StringOrNumberOrBoolOrNull::String(text) => text.clone(),
+        other => encode_primitive(other, DEFAULT_DELIMITER),
+    }
+}
+
+const fn primitive_type_name(value: &JsonPrimitive) -> &'static str {
+    match value {
+        StringOrNumberOrBoolOrNull::String(_) => "string",
+        StringOrNumberOrBoolOrNull::Number(_)
+        | StringOrNumberOrBoolOrNull::Int(_)
+        | StringOrNumberOrBoolOrNull::UInt(_)
+        | StringOrNumberOrBoolOrNull::NumberLexeme(_) => "number",
+        StringOrNumberOrBoolOrNull::Bool(_) => "bool",
+        StringOrNumberOrBoolOrNull::Null => "null",
+    }
+}
+
+/// An [`EventSink`] that types events against a schema before passing them
+/// on, collecting a [`Violation`] for each value that does not fit. Values
+/// that do not fit are passed on unchanged.
+#[derive(Debug)]
+pub struct SchemaSink<'a, S> {
+    inner: S,
+    root: &'a Schema,
+    stack: Vec<Frame<'a>>,
+    path: Vec<PathSegment>,
+    violations: Vec<Violation>,
+}
+
+#[derive(Debug)]
+enum Frame<'a> {
+    Object {
+        /// `None` when the object's fields are not checked.
+        schema: Option<&'a ObjectSchema>,
+        seen: Vec<bool>,
+        /// The current key and the schema of its value.
+        pending: Option<(String, &'a Schema)>,
+    },
+    Array {
+        item: &'a Schema,
+        index: usize,
+    },
+}
+
+impl<'a, S: EventSink> SchemaSink<'a, S> {
+    pub const fn new(inner: S, schema: &'a Schema) -> Self {
+        Self {
+            inner,
+            root: schema,
+            stack: Vec::new(),
+            path: Vec::new(),
+            violations: Vec::new(),
+        }
+    }
+
+    /// The inner sink and the violations found.
+    pub fn into_parts(self) -> (S, Vec<Violation>) {
+        (self.inner, self.violations)
+    }
+
+    /// Enter the next value, returning its schema.
+    fn begin_value(&mut self) -> &'a Schema {
+        match self.stack.last_mut() {
+            None => self.root,
+            Some(Frame::Object { pending, .. }) => {
+                let (key, schema) = pending.take().unwrap_or((String::new(), &ANY));
+                self.path.push(PathSegment::Key(key));
+                schema
+            }
+            Some(Frame::Array { item, index }) => {
+                self.path.push(PathSegment::Index(*index));
+                *index += 1;
+                item
+            }
+        }
+    }
+
+    fn end_value(&mut self) {
+        if !self.stack.is_empty() {
+            self.path.pop();
+        }
+    }
+
+    fn violation(&mut self, message: String) {
+        self.violations.push(Violation {
+            path: self.path.clone(),
+            message,
+        });
+    }
+
+    fn mismatch(&mut self, schema: &Schema, found: &str) {
+        self.violation(format!("expected {}, found {found}", schema.type_name()));
+    }
+}
+
+impl<S: EventSink> EventSink for SchemaSink<'_, S> {
+    #[allow(clippy::too_many_lines)]
+    fn event(&mut self, event: JsonStreamEvent) -> Result<()> {
+        match &event {
+            JsonStreamEvent::StartObject => {
+                let schema = match self.begin_value() {
+                    Schema::Object(object) => Some(object),
+                    Schema::Any => None,
+                    other => {
+                        self.mismatch(other, "object");
+                        None
+                    }
+                };
+                self.stack.push(Frame::Object {
+                    schema,
+                    seen: vec![false; schema.map_or(0, |object| object.fields.len())],
+                    pending: None,
+                });
+            }
+            JsonStreamEvent::StartArray { .. } => {
+                let item = match self.begin_value() {
+                    Schema::Array(item) => item,
+                    Schema::Any => &ANY,
+                    other => {
+                        self.mismatch(other, "array");
+                        &ANY
+                    }
+                };
+                self.stack.push(Frame::Array { item, index: 0 });
+            }
+            JsonStreamEvent::EndObject => {
+                if let Some(Frame::Object {
+                    schema: Some(object),
+                    seen,
+                    ..
+                }) = self.stack.pop()
+                {
+                    for (field, seen) in object.fields.iter().zip(seen) {
+                        if field.required && !seen {
+                            self.violation(format!("missing required field {}", field.name));
+                        }
+                    }
+                }
+                self.end_value();
+            }
+            JsonStreamEvent::EndArray => {
+                self.stack.pop();
+                self.end_value();
+            }
+            JsonStreamEvent::Key { key, .. } => {
+                if let Some(Frame::Object {
+                    schema,
+                    seen,
+                    pending,
+                }) = self.stack.last_mut()
+                {
+                    let field = schema.and_then(|object| {
+                        object
+                            .fields
+                            .iter()
+                            .position(|field| field.name == *key)
+                            .map(|index| (index, &object.fields[index].schema))
+                    });
+                    let value_schema = match field {
+                        Some((index, schema)) => {
+                            seen[index] = true;
+                            schema
+                        }
+                        None => &ANY,
+                    };
+                    *pending = Some((key.clone(), value_schema));
+                    let unknown =
+                        field.is_none() && schema.is_some_and(|object| !object.allow_unknown);
+                    if unknown {
+                        self.path.push(PathSegment::Key(key.clone()));
+                        self.violation("unknown field".to_string());
+                        self.path.pop();
+                    }
+                }
+            }
+            JsonStreamEvent::Primitive { value } => {
+                let schema = self.begin_value();
+                let typed = match schema.type_primitive(value) {
+                    Ok(typed) => Some(typed),
+                    Err(message) => {
+                        self.violation(message);
+                        None
+                    }
+                };
+                self.end_value();
+                if let Some(typed) = typed {
+                    return self
+                        .inner
+                        .event(JsonStreamEvent::Primitive { value: typed });
+                }
+            }
+            #[cfg(feature = "raw")]
+            JsonStreamEvent::Raw { .. } => {
+                let schema = self.begin_value();
+                if !matches!(schema, Schema::Any) {
+                    self.mismatch(schema, "raw text");
+                }
+                self.end_value();
+            }
+        }
+        self.inner.event(event)
+    }
+}
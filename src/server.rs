@@ -0,0 +1,528 @@
+//! Conversion over HTTP (`toon serve`).
+//!
+//! A small HTTP/1.1 server with two endpoints:
+//! - `POST /encode` takes a JSON body and returns TOON.
+//! - `POST /decode` takes a TOON body and returns JSON.
+//!
+//! Options are query parameters named like the CLI flags: `indent`,
+//! `delimiter`, `key-folding`, and `flatten-depth` for encoding; `indent`
+//! and `strict` for decoding. The JSON of a decoded body is written through
+//! [`JsonWriterSink`] as it is produced rather than built as a value, but
+//! the request itself is held in memory: the decoder collects the TOON lines
+//! before decoding them, and `/encode` parses the whole JSON value. Bodies
+//! are therefore capped at [`ServeOptions::max_body_bytes`]. Responses up to
+//! [`CHUNK_SIZE`] bytes get a `Content-Length` and a proper error status;
+//! longer ones are sent with chunked encoding, and an error after the first
+//! chunk closes the connection without the final chunk.
+//!
+//! Each connection carries one request and is handled on its own thread, up
+//! to [`ServeOptions::max_connections`] at a time; further connections wait
+//! to be accepted. Reads and writes that stall for longer than
+//! [`ServeOptions::timeout`] close the connection.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use crate::JsonValue;
+use crate::decode::decode_into_sink;
+use crate::encode::try_encode_lines;
+use crate::error::{Result, ToonError};
+use crate::json::JsonWriterSink;
+use crate::options::{DecodeStreamOptions, EncodeOptions, KeyFoldingMode, parse_delimiter};
+
+/// Response bytes buffered before switching to chunked encoding.
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Longest accepted request or header line.
+const MAX_LINE: usize = 8 * 1024;
+
+/// Most accepted header lines.
+const MAX_HEADERS: usize = 100;
+
+/// Limits on what the server accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServeOptions {
+    /// Largest accepted request body; a longer `Content-Length` is answered
+    /// with `413`. Defaults to 16 MiB.
+    pub max_body_bytes: u64,
+    /// Most connections handled at once. Defaults to 64.
+    pub max_connections: usize,
+    /// How long a read from or write to a connection may stall before the
+    /// connection is closed. Defaults to 30 seconds.
+    pub timeout: Duration,
+}
+
+impl Default for ServeOptions {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: 16 * 1024 * 1024,
+            max_connections: 64,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Accept connections on `listener` until it fails, with the default
+/// [`ServeOptions`].
+///
+/// # Errors
+///
+/// Returns an error if accepting a connection fails.
+pub fn serve(listener: &TcpListener) -> Result<()> {
+    serve_with(listener, ServeOptions::default())
+}
+
+/// Accept connections on `listener` until it fails, handling each on its
+/// own thread within the limits of `options`.
+///
+/// # Errors
+///
+/// Returns an error if accepting a connection fails.
+pub fn serve_with(listener: &TcpListener, options: ServeOptions) -> Result<()> {
+    let slots = Arc::new(Slots::new(options.max_connections.max(1)));
+    for stream in listener.incoming() {
+        let stream = stream.map_err(|e| ToonError::io("Failed to accept connection", None, e))?;
+        slots.acquire();
+        let slots = Arc::clone(&slots);
+        thread::spawn(move || {
+            // The client may have gone away; there is nobody to report to.
+            let _ = handle_connection(&stream, &options);
+            slots.release();
+        });
+    }
+    Ok(())
+}
+
+/// Counts free connection slots; accepting waits while there are none.
+struct Slots {
+    free: Mutex<usize>,
+    released: Condvar,
+}
+
+impl Slots {
+    const fn new(count: usize) -> Self {
+        Self {
+            free: Mutex::new(count),
+            released: Condvar::new(),
+        }
+    }
+
+    fn acquire(&self) {
+        let free = self.free.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut free = self
+            .released
+            .wait_while(free, |free| *free == 0)
+            .unwrap_or_else(PoisonError::into_inner);
+        *free -= 1;
+    }
+
+    fn release(&self) {
+        *self.free.lock().unwrap_or_else(PoisonError::into_inner) += 1;
+        self.released.notify_one();
+    }
+}
+
+fn handle_connection(stream: &TcpStream, options: &ServeOptions) -> Result<()> {
+    let timeout = Some(options.timeout).filter(|timeout| !timeout.is_zero());
+    stream
+        .set_read_timeout(timeout)
+        .and_then(|()| stream.set_write_timeout(timeout))
+        .map_err(|e| ToonError::io("Failed to configure connection", None, e))?;
+    let mut reader = BufReader::new(stream);
+    let mut writer = stream;
+    handle_request_with(&mut reader, &mut writer, options)
+}
+
+/// Read one request from `reader` and write its response to `writer`, with
+/// the default [`ServeOptions`].
+///
+/// # Errors
+///
+/// See [`handle_request_with`].
+pub fn handle_request(reader: &mut impl BufRead, writer: &mut impl Write) -> Result<()> {
+    handle_request_with(reader, writer, &ServeOptions::default())
+}
+
+/// Read one request from `reader` and write its response to `writer`.
+///
+/// Bad requests, bodies over `options.max_body_bytes`, and conversion errors
+/// are answered with an error status.
+///
+/// # Errors
+///
+/// Returns an error if the connection fails, or if conversion fails after
+/// part of a chunked response was sent.
+pub fn handle_request_with(
+    reader: &mut impl BufRead,
+    writer: &mut impl Write,
+    options: &ServeOptions,
+) -> Result<()> {
+    let request = match read_head(reader) {
+        Ok(request) => request,
+        Err(HttpError::Io(e)) => return Err(e),
+        Err(HttpError::Status(status, message)) => return write_error(writer, status, &message),
+    };
+    let (path, query) = request
+        .target
+        .split_once('?')
+        .unwrap_or((request.target.as_str(), ""));
+
+    let endpoint = match path {
+        "/encode" => Endpoint::Encode,
+        "/decode" => Endpoint::Decode,
+        _ => return write_error(writer, 404, "Not found"),
+    };
+    if request.method != "POST" {
+        return write_error(writer, 405, "Use POST");
+    }
+    let Some(length) = request.content_length else {
+        return write_error(writer, 411, "Content-Length is required");
+    };
+    if length > options.max_body_bytes {
+        return write_error(
+            writer,
+            413,
+            &format!(
+                "Request body of {length} bytes exceeds the limit of {} bytes",
+                options.max_body_bytes
+            ),
+        );
+    }
+    let params = match parse_query(query) {
+        Ok(params) => params,
+        Err(message) => return write_error(writer, 400, &message),
+    };
+    let body = reader.take(length);
+
+    let mut response = Response::new(writer, endpoint.content_type());
+    let result =
+        match endpoint {
+            Endpoint::Encode => encode_options(&params)
+                .and_then(|options| encode_body(body, options, &mut response)),
+            Endpoint::Decode => decode_options(&params)
+                .and_then(|options| decode_body(body, options, &mut response)),
+        };
+    match result {
+        Ok(()) => response.finish(),
+        Err(err) if response.streaming => Err(err),
+        Err(err) => write_error(writer, 400, &err.to_string()),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Endpoint {
+    Encode,
+    Decode,
+}
+
+impl Endpoint {
+    const fn content_type(self) -> &'static str {
+        match self {
+            Self::Encode => "text/plain; charset=utf-8",
+            Self::Decode => "application/json",
+        }
+    }
+}
+
+struct Request {
+    method: String,
+    target: String,
+    content_length: Option<u64>,
+}
+
+enum HttpError {
+    Io(ToonError),
+    Status(u16, String),
+}
+
+fn read_head(reader: &mut impl BufRead) -> std::result::Result<Request, HttpError> {
+    let line = read_line(reader)?;
+    let mut parts = line.split(' ');
+    let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(HttpError::Status(400, "Malformed request line".to_string()));
+    };
+    if !version.starts_with("HTTP/1.") {
+        return Err(HttpError::Status(
+            505,
+            "Only HTTP/1.x is supported".to_string(),
+        ));
+    }
+    let mut request = Request {
+        method: method.to_string(),
+        target: target.to_string(),
+        content_length: None,
+    };
+
+    for _ in 0..MAX_HEADERS {
+        let line = read_line(reader)?;
+        if line.is_empty() {
+            return Ok(request);
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(HttpError::Status(400, "Malformed header".to_string()));
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            let length = value
+                .parse()
+                .map_err(|_| HttpError::Status(400, "Invalid Content-Length".to_string()))?;
+            request.content_length = Some(length);
+        } else if name.eq_ignore_ascii_case("transfer-encoding") {
+            return Err(HttpError::Status(
+                411,
+                "Content-Length is required".to_string(),
+            ));
+        }
+    }
+    Err(HttpError::Status(431, "Too many headers".to_string()))
+}
+
+fn read_line(reader: &mut impl BufRead) -> std::result::Result<String, HttpError> {
+    let mut line = Vec::new();
+    let read = (&mut *reader)
+        .take(MAX_LINE as u64 + 2)
+        .read_until(b'\n', &mut line)
+        .map_err(|e| HttpError::Io(ToonError::io("Failed to read request", None, e)))?;
+    if read == 0 || !line.ends_with(b"\n") {
+        let message = if read > MAX_LINE {
+            "Request line too long"
+        } else {
+            "Incomplete request"
+        };
+        return Err(HttpError::Status(400, message.to_string()));
+    }
+    let line = String::from_utf8(line)
+        .map_err(|_| HttpError::Status(400, "Request head is not UTF-8".to_string()))?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Split a query string into decoded name/value pairs.
+fn parse_query(query: &str) -> std::result::Result<Vec<(String, String)>, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Ok((percent_decode(name)?, percent_decode(value)?))
+        })
+        .collect()
+}
+
+fn percent_decode(text: &str) -> std::result::Result<String, String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut iter = text.bytes();
+    while let Some(byte) = iter.next() {
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = [iter.next(), iter.next()];
+                let value = match hex {
+                    [Some(high), Some(low)] => std::str::from_utf8(&[high, low])
+                        .ok()
+                        .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+                    _ => None,
+                };
+                bytes.push(value.ok_or_else(|| format!("Invalid escape in query: {text}"))?);
+            }
+            byte => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| format!("Query is not UTF-8: {text}"))
+}
+
+fn encode_options(params: &[(String, String)]) -> Result<EncodeOptions> {
+    let mut options = EncodeOptions::default();
+    for (name, value) in params {
+        match name.as_str() {
+            "indent" => options.indent = Some(parse_param(name, value)?),
+            "delimiter" => {
+                options.delimiter = Some(parse_delimiter(value)?);
+            }
+            "key-folding" => {
+                options.key_folding = Some(match value.as_str() {
+                    "off" => KeyFoldingMode::Off,
+                    "safe" => KeyFoldingMode::Safe,
+                    _ => return Err(invalid_param(name, value)),
+                });
+            }
+            "flatten-depth" => options.flatten_depth = Some(parse_param(name, value)?),
+            _ => return Err(ToonError::message(format!("Unknown parameter '{name}'"))),
+        }
+    }
+    Ok(options)
+}
+
+fn decode_options(params: &[(String, String)]) -> Result<DecodeStreamOptions> {
+    let mut options = DecodeStreamOptions::default();
+    for (name, value) in params {
+        match name.as_str() {
+            "indent" => options.indent = Some(parse_param(name, value)?),
+            "strict" => options.strict = Some(parse_param(name, value)?),
+            _ => return Err(ToonError::message(format!("Unknown parameter '{name}'"))),
+        }
+    }
+    Ok(options)
+}
+
+fn parse_param<T: std::str::FromStr>(name: &str, value: &str) -> Result<T> {
+    value.parse().map_err(|_| invalid_param(name, value))
+}
+
+fn invalid_param(name: &str, value: &str) -> ToonError {
+    ToonError::message(format!("Invalid value '{value}' for parameter '{name}'"))
+}
+
+fn encode_body(
+    body: impl Read,
+    options: EncodeOptions,
+    response: &mut Response<'_, impl Write>,
+) -> Result<()> {
+    let value: serde_json::Value =
+        serde_json::from_reader(body).map_err(|err| ToonError::json_parse(&err))?;
+    for line in try_encode_lines(JsonValue::from(value), Some(options))? {
+        response.write_all(line.as_bytes())?;
+        response.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+fn decode_body(
+    body: impl BufRead,
+    options: DecodeStreamOptions,
+    response: &mut Response<'_, impl Write>,
+) -> Result<()> {
+    let indent = options.indent.unwrap_or(2);
+    // A read error ends the lines early; report it rather than the decode
+    // error the truncated body may cause.
+    let mut read_error = None;
+    let lines = body
+        .lines()
+        .map_while(|line| line.map_err(|e| read_error = Some(e)).ok());
+    let mut sink = JsonWriterSink::new(ResponseWriter(response), indent);
+    let decoded = decode_into_sink(lines, Some(options), &mut sink);
+    if let Some(e) = read_error {
+        return Err(ToonError::io("Failed to read request body", None, e));
+    }
+    decoded?;
+    sink.finish()?.0.write_all(b"\n")
+}
+
+/// A response body: buffered until it outgrows [`CHUNK_SIZE`], then sent in
+/// chunks.
+struct Response<'a, W: Write> {
+    writer: &'a mut W,
+    content_type: &'static str,
+    buffer: Vec<u8>,
+    streaming: bool,
+}
+
+impl<'a, W: Write> Response<'a, W> {
+    const fn new(writer: &'a mut W, content_type: &'static str) -> Self {
+        Self {
+            writer,
+            content_type,
+            buffer: Vec::new(),
+            streaming: false,
+        }
+    }
+
+    fn write_all(&mut self, bytes: &[u8]) -> Result<()> {
+        self.buffer.extend_from_slice(bytes);
+        if self.buffer.len() >= CHUNK_SIZE {
+            self.send_chunk()?;
+        }
+        Ok(())
+    }
+
+    fn send_chunk(&mut self) -> Result<()> {
+        if !self.streaming {
+            self.streaming = true;
+            write!(
+                self.writer,
+                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n",
+                self.content_type
+            )
+            .map_err(response_error)?;
+        }
+        write!(self.writer, "{:x}\r\n", self.buffer.len()).map_err(response_error)?;
+        self.writer
+            .write_all(&self.buffer)
+            .map_err(response_error)?;
+        self.writer.write_all(b"\r\n").map_err(response_error)?;
+        self.buffer.clear();
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        if !self.streaming {
+            return write_response(self.writer, 200, self.content_type, &self.buffer);
+        }
+        if !self.buffer.is_empty() {
+            self.send_chunk()?;
+        }
+        self.writer
+            .write_all(b"0\r\n\r\n")
+            .map_err(response_error)?;
+        self.writer.flush().map_err(response_error)
+    }
+}
+
+/// Adapts a [`Response`] to [`Write`] for [`JsonWriterSink`].
+struct ResponseWriter<'r, 'a, W: Write>(&'r mut Response<'a, W>);
+
+impl<W: Write> Write for ResponseWriter<'_, '_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .write_all(buf)
+            .map_err(|err| io::Error::other(err.to_string()))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn write_error(writer: &mut impl Write, status: u16, message: &str) -> Result<()> {
+    let body = format!("{message}\n");
+    write_response(writer, status, "text/plain; charset=utf-8", body.as_bytes())
+}
+
+fn write_response(
+    writer: &mut impl Write,
+    status: u16,
+    content_type: &str,
+    body: &[u8],
+) -> Result<()> {
+    write!(
+        writer,
+        "HTTP/1.1 {status} {}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        reason(status),
+        body.len()
+    )
+    .map_err(response_error)?;
+    writer.write_all(body).map_err(response_error)?;
+    writer.flush().map_err(response_error)
+}
+
+const fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        411 => "Length Required",
+        413 => "Content Too Large",
+        431 => "Request Header Fields Too Large",
+        505 => "HTTP Version Not Supported",
+        _ => "Error",
+    }
+}
+
+fn response_error(err: io::Error) -> ToonError {
+    ToonError::io("Failed to write response", None, err)
+}
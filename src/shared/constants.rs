@@ -6,6 +6,12 @@ pub const COLON: char = ':';
 pub const SPACE: char = ' ';
 pub const PIPE: char = '|';
 pub const DOT: char = '.';
+pub const RUN_MARKER: char = '*';
+
+pub const INCLUDE_PREFIX: &str = "!include ";
+
+/// Key marking a columnar section; its value is the row count.
+pub const COLUMNAR_MARKER: &str = "@rows";
 
 pub const OPEN_BRACKET: char = '[';
 pub const CLOSE_BRACKET: char = ']';
@@ -23,3 +29,6 @@ pub const CARRIAGE_RETURN: char = '\r';
 pub const TAB: char = '\t';
 
 pub const DEFAULT_DELIMITER: char = COMMA;
+
+/// Largest integer magnitude that `f64` represents exactly (2^53).
+pub const MAX_EXACT_INTEGER: f64 = 9_007_199_254_740_992.0;
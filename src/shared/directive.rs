@@ -0,0 +1,106 @@
+//! The optional `#%toon` directive line.
+//!
+//! A document may start with `#%toon version=1 delimiter=| indent=2` to record
+//! the settings it was written with. The encoder writes it when
+//! `EncodeOptions::emit_directive` is set; the decoder reads it from the first
+//! line and decodes the rest of the document with its indentation.
+
+use std::fmt::Write as _;
+
+use crate::error::{Result, ToonError};
+use crate::shared::constants::{COMMA, PIPE, TAB};
+
+pub const DIRECTIVE_PREFIX: &str = "#%toon";
+
+/// The directive version this crate writes and the newest it reads.
+pub const DIRECTIVE_VERSION: u32 = 1;
+
+/// Settings declared by a `#%toon` line. Unknown `key=value` pairs are
+/// ignored so newer writers can add settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Directive {
+    pub version: u32,
+    pub delimiter: Option<char>,
+    pub indent: Option<usize>,
+}
+
+impl Directive {
+    #[must_use]
+    pub const fn new(delimiter: char, indent: usize) -> Self {
+        Self {
+            version: DIRECTIVE_VERSION,
+            delimiter: Some(delimiter),
+            indent: Some(indent),
+        }
+    }
+
+    /// The directive as a line, e.g. `#%toon version=1 delimiter=, indent=2`.
+    /// A tab delimiter is written as `tab`.
+    #[must_use]
+    pub fn to_line(&self) -> String {
+        let mut line = format!("{DIRECTIVE_PREFIX} version={}", self.version);
+        if let Some(delimiter) = self.delimiter {
+            line.push_str(" delimiter=");
+            if delimiter == TAB {
+                line.push_str("tab");
+            } else {
+                line.push(delimiter);
+            }
+        }
+        if let Some(indent) = self.indent {
+            let _ = write!(line, " indent={indent}");
+        }
+        line
+    }
+
+    /// Parse `line` if it is a directive.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the line starts with `#%toon` but a known setting
+    /// has an invalid value or the version is newer than
+    /// [`DIRECTIVE_VERSION`].
+    pub fn parse(line: &str) -> Result<Option<Self>> {
+        let line = line.strip_prefix('\u{feff}').unwrap_or(line).trim_end();
+        let Some(settings) = line.strip_prefix(DIRECTIVE_PREFIX) else {
+            return Ok(None);
+        };
+        if !settings.is_empty() && !settings.starts_with(' ') {
+            return Ok(None);
+        }
+
+        let mut directive = Self {
+            version: DIRECTIVE_VERSION,
+            delimiter: None,
+            indent: None,
+        };
+        for setting in settings.split(' ').filter(|setting| !setting.is_empty()) {
+            let Some((key, value)) = setting.split_once('=') else {
+                continue;
+            };
+            let invalid = || ToonError::parse(1, format!("Invalid #%toon {key}: \"{value}\""));
+            match key {
+                "version" => {
+                    directive.version = value.parse().map_err(|_| invalid())?;
+                    if directive.version > DIRECTIVE_VERSION {
+                        return Err(ToonError::parse(
+                            1,
+                            format!("Unsupported #%toon version {}", directive.version),
+                        ));
+                    }
+                }
+                "delimiter" => {
+                    directive.delimiter = Some(match value {
+                        "," | "comma" => COMMA,
+                        "|" | "pipe" => PIPE,
+                        "tab" | "\\t" => TAB,
+                        _ => return Err(invalid()),
+                    });
+                }
+                "indent" => directive.indent = Some(value.parse().map_err(|_| invalid())?),
+                _ => {}
+            }
+        }
+        Ok(Some(directive))
+    }
+}
@@ -81,6 +81,13 @@ pub fn is_numeric_like(value: &str) -> bool {
     true
 }
 
+/// Whether `text` is exactly a JSON number, with nothing around it, and so
+/// safe to write verbatim as a number lexeme.
+#[must_use]
+pub fn is_number_lexeme(text: &str) -> bool {
+    text.len() == text.trim().len() && is_numeric_literal(text)
+}
+
 #[must_use]
 pub fn is_numeric_literal(value: &str) -> bool {
     let trimmed = value.trim();
@@ -143,3 +150,96 @@ pub fn is_numeric_literal(value: &str) -> bool {
 
     trimmed.parse::<f64>().is_ok_and(f64::is_finite)
 }
+
+/// Largest number of zeros [`canonical_decimal`] writes out for an exponent.
+/// It covers the whole range of `f64`, whose smallest subnormal has 323
+/// zeros after the point.
+#[cfg(feature = "arbitrary-precision")]
+const MAX_EXPONENT_ZEROS: i64 = 400;
+
+/// Normalize a decimal literal to `(negative, significant digits, exponent)`,
+/// where the digits have no leading or trailing zeros. Zero has no digits.
+#[must_use]
+pub fn decimal_digits(literal: &str) -> (bool, String, i64) {
+    let (mantissa, exponent) =
+        literal
+            .split_once(['e', 'E'])
+            .map_or((literal, 0), |(mantissa, exponent)| {
+                let fallback = if exponent.starts_with('-') {
+                    i64::MIN / 2
+                } else {
+                    i64::MAX / 2
+                };
+                (mantissa, exponent.parse::<i64>().unwrap_or(fallback))
+            });
+    let negative = mantissa.starts_with('-');
+    let mantissa = mantissa.trim_start_matches(['-', '+']);
+    let (int_part, frac_part) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+
+    let mut digits: String = int_part.chars().chain(frac_part.chars()).collect();
+    let mut exponent = exponent.saturating_sub(i64::try_from(frac_part.len()).unwrap_or(0));
+    while digits.ends_with('0') {
+        digits.pop();
+        exponent = exponent.saturating_add(1);
+    }
+    let digits = digits.trim_start_matches('0').to_string();
+    if digits.is_empty() {
+        return (false, digits, 0);
+    }
+    (negative, digits, exponent)
+}
+
+/// Whether `literal` parses to a finite `f64` whose shortest form has the
+/// same digits, so reading it as a float loses nothing.
+#[must_use]
+pub fn parses_exactly(literal: &str) -> bool {
+    literal.parse::<f64>().is_ok_and(|value| {
+        value.is_finite() && decimal_digits(literal) == decimal_digits(&format!("{value:e}"))
+    })
+}
+
+/// A number literal in canonical form: no exponent, no leading zeros, no
+/// trailing zeros after the point, and `-0` as `0`.
+///
+/// `None` if `literal` is not a number, or its exponent would take more than
+/// 400 zeros to write out.
+#[cfg(feature = "arbitrary-precision")]
+#[must_use]
+pub fn canonical_decimal(literal: &str) -> Option<String> {
+    if !is_numeric_literal(literal) {
+        return None;
+    }
+    let (negative, digits, exponent) = decimal_digits(literal.trim());
+    if digits.is_empty() {
+        return Some("0".to_string());
+    }
+    let point = i64::try_from(digits.len()).ok()?.checked_add(exponent)?;
+    let zeros = if exponent >= 0 {
+        exponent
+    } else {
+        point.min(0).checked_neg()?
+    };
+    if zeros > MAX_EXPONENT_ZEROS {
+        return None;
+    }
+    let zeros = usize::try_from(zeros).ok()?;
+
+    let mut out = String::with_capacity(digits.len() + zeros + 3);
+    if negative {
+        out.push('-');
+    }
+    if exponent >= 0 {
+        out.push_str(&digits);
+        out.extend(std::iter::repeat_n('0', zeros));
+    } else if point > 0 {
+        let (int_part, frac_part) = digits.split_at(usize::try_from(point).ok()?);
+        out.push_str(int_part);
+        out.push('.');
+        out.push_str(frac_part);
+    } else {
+        out.push_str("0.");
+        out.extend(std::iter::repeat_n('0', zeros));
+        out.push_str(&digits);
+    }
+    Some(out)
+}
@@ -1,4 +1,5 @@
 pub mod constants;
+pub mod directive;
 pub mod literal_utils;
 pub mod string_utils;
 pub mod validation;
@@ -104,3 +104,13 @@ pub fn find_unquoted_char(content: &str, target: char, start: usize) -> Option<u
     }
     None
 }
+
+/// Simple token estimation heuristic (roughly 4 chars per token for English/code).
+/// This matches the behavior of tokenx used in the legacy CLI.
+#[must_use]
+pub fn estimate_tokens(text: &str) -> usize {
+    // Simple heuristic: count non-whitespace chars / 4, with minimum of word count
+    let char_estimate = text.chars().filter(|c| !c.is_whitespace()).count() / 4;
+    let word_estimate = text.split_whitespace().count();
+    char_estimate.max(word_estimate).max(1)
+}
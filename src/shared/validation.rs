@@ -1,6 +1,8 @@
 use crate::shared::constants::{DEFAULT_DELIMITER, LIST_ITEM_MARKER};
 use crate::shared::literal_utils::{is_boolean_or_null_literal, is_numeric_like};
 
+/// Whether `key` can be written bare. A dotted key is always quoted, so it
+/// cannot be read back as a folded path.
 #[must_use]
 pub fn is_valid_unquoted_key(key: &str) -> bool {
     let mut chars = key.chars();
@@ -13,7 +15,7 @@ pub fn is_valid_unquoted_key(key: &str) -> bool {
     }
 
     for ch in chars {
-        if ch.is_ascii_alphanumeric() || ch == '_' || ch == '.' {
+        if ch.is_ascii_alphanumeric() || ch == '_' {
             continue;
         }
         return false;
@@ -81,9 +83,33 @@ pub fn is_safe_unquoted(value: &str, delimiter: char) -> bool {
         return false;
     }
 
+    if value.chars().any(is_invisible_char) {
+        return false;
+    }
+
     true
 }
 
+/// Whether `ch` is a zero-width or bidirectional control character.
+///
+/// These change how the text around them displays without showing
+/// themselves, so an unquoted value holding one can pass for a different
+/// value. Quoting it at least makes its extent visible.
+#[must_use]
+pub const fn is_invisible_char(ch: char) -> bool {
+    matches!(
+        ch,
+        '\u{00AD}'
+            | '\u{061C}'
+            | '\u{180E}'
+            | '\u{200B}'..='\u{200F}'
+            | '\u{202A}'..='\u{202E}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{2066}'..='\u{2069}'
+            | '\u{FEFF}'
+    )
+}
+
 #[must_use]
 pub const fn default_delimiter() -> char {
     DEFAULT_DELIMITER
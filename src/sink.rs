@@ -0,0 +1,256 @@
+//! Consumers of JSON event streams.
+//!
+//! The decoder pushes each [`JsonStreamEvent`] into an [`EventSink`] as soon
+//! as it is produced, so a consumer can write, count, or build values without
+//! the whole event stream being buffered first. Sinks provided by the crate:
+//!
+//! - `Vec<JsonStreamEvent>` collects the events.
+//! - [`MetadataSink`] collects them as [`JsonStreamEventV2`], with their
+//!   [`EventMetadata`].
+//! - [`JsonWriterSink`](crate::json::JsonWriterSink) writes JSON text.
+//! - [`ValueSink`](crate::decode::event_builder::ValueSink) builds a
+//!   [`JsonValue`](crate::JsonValue).
+//! - [`CountingSink`] counts events by kind.
+//! - [`StatsSink`] measures the document's shape.
+//! - [`TeeSink`] passes every event to two sinks.
+//!
+//! Decode with [`decode_into_sink`](crate::decode::decode_into_sink).
+
+use crate::error::Result;
+use crate::{JsonStreamEvent, JsonStreamEventV2, JsonValue, StringOrNumberOrBoolOrNull};
+
+/// Where the decoder found an event that opens an object or array.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EventMetadata {
+    /// The length written in an array header.
+    pub declared_length: Option<usize>,
+    /// 1-based line the object or array starts on.
+    pub source_line: Option<usize>,
+}
+
+/// A consumer of JSON stream events.
+pub trait EventSink {
+    /// Consume the next event.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error to stop the producer; the error is passed back to its
+    /// caller.
+    fn event(&mut self, event: JsonStreamEvent) -> Result<()>;
+
+    /// Consume the next event along with its metadata. The decoder calls
+    /// this for `StartObject` and `StartArray`; the default ignores the
+    /// metadata and calls [`EventSink::event`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error to stop the producer; the error is passed back to its
+    /// caller.
+    fn event_with_metadata(
+        &mut self,
+        event: JsonStreamEvent,
+        metadata: EventMetadata,
+    ) -> Result<()> {
+        let _ = metadata;
+        self.event(event)
+    }
+}
+
+impl EventSink for Vec<JsonStreamEvent> {
+    fn event(&mut self, event: JsonStreamEvent) -> Result<()> {
+        self.push(event);
+        Ok(())
+    }
+}
+
+impl<S: EventSink + ?Sized> EventSink for &mut S {
+    fn event(&mut self, event: JsonStreamEvent) -> Result<()> {
+        (**self).event(event)
+    }
+
+    fn event_with_metadata(
+        &mut self,
+        event: JsonStreamEvent,
+        metadata: EventMetadata,
+    ) -> Result<()> {
+        (**self).event_with_metadata(event, metadata)
+    }
+}
+
+/// Collects events along with the metadata the decoder attaches to them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MetadataSink {
+    pub events: Vec<JsonStreamEventV2>,
+}
+
+impl EventSink for MetadataSink {
+    fn event(&mut self, event: JsonStreamEvent) -> Result<()> {
+        self.events.push(event.into());
+        Ok(())
+    }
+
+    fn event_with_metadata(
+        &mut self,
+        event: JsonStreamEvent,
+        metadata: EventMetadata,
+    ) -> Result<()> {
+        self.events
+            .push(JsonStreamEventV2::with_metadata(event, metadata));
+        Ok(())
+    }
+}
+
+/// Counts events by kind.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CountingSink {
+    pub objects: usize,
+    pub arrays: usize,
+    pub keys: usize,
+    pub primitives: usize,
+}
+
+impl CountingSink {
+    /// Number of values: objects, arrays, and primitives.
+    #[must_use]
+    pub const fn values(&self) -> usize {
+        self.objects + self.arrays + self.primitives
+    }
+}
+
+impl EventSink for CountingSink {
+    fn event(&mut self, event: JsonStreamEvent) -> Result<()> {
+        match event {
+            JsonStreamEvent::StartObject => self.objects += 1,
+            JsonStreamEvent::StartArray { .. } => self.arrays += 1,
+            JsonStreamEvent::Key { .. } => self.keys += 1,
+            JsonStreamEvent::Primitive { .. } => self.primitives += 1,
+            JsonStreamEvent::EndObject | JsonStreamEvent::EndArray => {}
+            #[cfg(feature = "raw")]
+            JsonStreamEvent::Raw { .. } => self.primitives += 1,
+        }
+        Ok(())
+    }
+}
+
+/// Passes every event to both sinks, so two outputs are produced from a
+/// single parse and always describe the same content.
+#[derive(Debug, Clone, Default)]
+pub struct TeeSink<A, B>(pub A, pub B);
+
+impl<A: EventSink, B: EventSink> EventSink for TeeSink<A, B> {
+    fn event(&mut self, event: JsonStreamEvent) -> Result<()> {
+        self.0.event(event.clone())?;
+        self.1.event(event)
+    }
+
+    fn event_with_metadata(
+        &mut self,
+        event: JsonStreamEvent,
+        metadata: EventMetadata,
+    ) -> Result<()> {
+        self.0.event_with_metadata(event.clone(), metadata)?;
+        self.1.event_with_metadata(event, metadata)
+    }
+}
+
+/// Measures a document's shape (nesting, sizes, and value types) from its
+/// events without building the value.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatsSink {
+    /// Deepest nesting of objects and arrays; 0 for a primitive document.
+    pub max_depth: usize,
+    pub objects: usize,
+    pub arrays: usize,
+    pub keys: usize,
+    pub strings: usize,
+    pub numbers: usize,
+    pub bools: usize,
+    pub nulls: usize,
+    /// Most items in one array.
+    pub max_array_length: usize,
+    /// Most keys in one object.
+    pub max_object_keys: usize,
+    /// Longest string value, in characters.
+    pub max_string_length: usize,
+    /// Total size of string values, in bytes.
+    pub string_bytes: usize,
+    /// Keys seen so far in each open object, or `None` for an open array.
+    open: Vec<Option<usize>>,
+}
+
+impl StatsSink {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of primitive values.
+    #[must_use]
+    pub const fn primitives(&self) -> usize {
+        self.strings + self.numbers + self.bools + self.nulls
+    }
+
+    /// The measurements as an object with camelCase keys, as printed by
+    /// `toon stats`.
+    #[must_use]
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::Object(vec![
+            ("maxDepth".to_string(), self.max_depth.into()),
+            ("objects".to_string(), self.objects.into()),
+            ("arrays".to_string(), self.arrays.into()),
+            ("keys".to_string(), self.keys.into()),
+            ("strings".to_string(), self.strings.into()),
+            ("numbers".to_string(), self.numbers.into()),
+            ("bools".to_string(), self.bools.into()),
+            ("nulls".to_string(), self.nulls.into()),
+            ("maxArrayLength".to_string(), self.max_array_length.into()),
+            ("maxObjectKeys".to_string(), self.max_object_keys.into()),
+            ("maxStringLength".to_string(), self.max_string_length.into()),
+            ("stringBytes".to_string(), self.string_bytes.into()),
+        ])
+    }
+}
+
+impl EventSink for StatsSink {
+    fn event(&mut self, event: JsonStreamEvent) -> Result<()> {
+        match event {
+            JsonStreamEvent::StartObject => {
+                self.objects += 1;
+                self.open.push(Some(0));
+                self.max_depth = self.max_depth.max(self.open.len());
+            }
+            JsonStreamEvent::StartArray { length } => {
+                self.arrays += 1;
+                self.max_array_length = self.max_array_length.max(length);
+                self.open.push(None);
+                self.max_depth = self.max_depth.max(self.open.len());
+            }
+            JsonStreamEvent::EndObject | JsonStreamEvent::EndArray => {
+                self.open.pop();
+            }
+            JsonStreamEvent::Key { .. } => {
+                self.keys += 1;
+                if let Some(Some(count)) = self.open.last_mut() {
+                    *count += 1;
+                    self.max_object_keys = self.max_object_keys.max(*count);
+                }
+            }
+            JsonStreamEvent::Primitive { value } => match value {
+                StringOrNumberOrBoolOrNull::String(text) => {
+                    self.strings += 1;
+                    self.string_bytes += text.len();
+                    self.max_string_length = self.max_string_length.max(text.chars().count());
+                }
+                StringOrNumberOrBoolOrNull::Number(_)
+                | StringOrNumberOrBoolOrNull::Int(_)
+                | StringOrNumberOrBoolOrNull::UInt(_)
+                | StringOrNumberOrBoolOrNull::NumberLexeme(_) => self.numbers += 1,
+                StringOrNumberOrBoolOrNull::Bool(_) => self.bools += 1,
+                StringOrNumberOrBoolOrNull::Null => self.nulls += 1,
+            },
+            #[cfg(feature = "raw")]
+            JsonStreamEvent::Raw { .. } => {}
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+//! Import and export of tabular arrays through a `rusqlite` connection.
+//!
+//! [`import_rows`] inserts the rows of a tabular array into a table,
+//! creating it if needed, and [`export_query`] writes the result of a query
+//! as a tabular array. Typed header fields (`id:int`) become column types on
+//! import; untyped fields are created without one, so the database keeps each
+//! value's own type.
+
+use std::io::{BufRead, Write};
+
+use rusqlite::Connection;
+use rusqlite::types::{Value, ValueRef};
+
+use crate::error::{Result, ToonError};
+use crate::header::HeaderBuilder;
+use crate::options::ColumnType;
+use crate::shared::constants::MAX_EXACT_INTEGER;
+use crate::tabular::{TabularReader, TabularWriter};
+use crate::{JsonPrimitive, StringOrNumberOrBoolOrNull as P};
+
+/// Insert the remaining rows of `rows` into `table` in one transaction,
+/// creating the table from the header if it does not exist. Returns the
+/// number of rows inserted.
+///
+/// # Errors
+///
+/// Returns an error if a row is invalid or a statement fails; no rows are
+/// inserted in that case.
+pub fn import_rows<R: BufRead>(
+    conn: &mut Connection,
+    table: &str,
+    rows: TabularReader<R>,
+) -> Result<usize> {
+    let fields = rows.fields().to_vec();
+    let columns = fields
+        .iter()
+        .map(|field| {
+            let mut column = quote_identifier(&field.name);
+            if let Some(sql_type) = field.column_type.and_then(sql_type) {
+                column.push(' ');
+                column.push_str(sql_type);
+            }
+            column
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let names = fields
+        .iter()
+        .map(|field| quote_identifier(&field.name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let placeholders = vec!["?"; fields.len()].join(", ");
+    let table = quote_identifier(table);
+
+    let tx = conn.transaction().map_err(|err| sql_error(&err))?;
+    tx.execute(
+        &format!("CREATE TABLE IF NOT EXISTS {table} ({columns})"),
+        [],
+    )
+    .map_err(|err| sql_error(&err))?;
+    let mut count = 0;
+    {
+        let mut insert = tx
+            .prepare(&format!(
+                "INSERT INTO {table} ({names}) VALUES ({placeholders})"
+            ))
+            .map_err(|err| sql_error(&err))?;
+        for row in rows {
+            let values = row?.into_iter().map(sql_value);
+            insert
+                .execute(rusqlite::params_from_iter(values))
+                .map_err(|err| sql_error(&err))?;
+            count += 1;
+        }
+    }
+    tx.commit().map_err(|err| sql_error(&err))?;
+    Ok(count)
+}
+
+/// Run `query` and write its result as a tabular array under `key`, with
+/// rows indented by `indent` spaces, and return the writer.
+///
+/// # Errors
+///
+/// Returns an error if the query fails, a column holds a BLOB, or writing
+/// fails.
+pub fn export_query<W: Write>(
+    conn: &Connection,
+    query: &str,
+    key: Option<&str>,
+    writer: W,
+    indent: usize,
+) -> Result<W> {
+    let mut statement = conn.prepare(query).map_err(|err| sql_error(&err))?;
+    let names: Vec<String> = statement
+        .column_names()
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    let mut rows = statement.query([]).map_err(|err| sql_error(&err))?;
+    let mut cells = Vec::new();
+    while let Some(row) = rows.next().map_err(|err| sql_error(&err))? {
+        let mut cells_row = Vec::with_capacity(names.len());
+        for (index, name) in names.iter().enumerate() {
+            cells_row.push(toon_cell(
+                name,
+                row.get_ref(index).map_err(|err| sql_error(&err))?,
+            )?);
+        }
+        cells.push(cells_row);
+    }
+
+    let mut header = HeaderBuilder::new(cells.len()).fields(names);
+    if let Some(key) = key {
+        header = header.key(key);
+    }
+    let mut writer = TabularWriter::new(writer, &header, indent)?;
+    for row in &cells {
+        writer.write_row(row)?;
+    }
+    writer.finish()
+}
+
+/// Quote an SQL identifier, doubling embedded quotes.
+#[must_use]
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+const fn sql_type(column_type: ColumnType) -> Option<&'static str> {
+    match column_type {
+        ColumnType::String => Some("TEXT"),
+        ColumnType::Number => Some("REAL"),
+        ColumnType::Integer | ColumnType::Bool => Some("INTEGER"),
+        ColumnType::Auto => None,
+    }
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+fn sql_value(cell: JsonPrimitive) -> Value {
+    match cell {
+        P::Null => Value::Null,
+        P::Bool(value) => Value::Integer(i64::from(value)),
+        P::Number(number) if number.fract() == 0.0 && number.abs() <= MAX_EXACT_INTEGER => {
+            Value::Integer(number as i64)
+        }
+        P::Number(number) => Value::Real(number),
+        P::Int(value) => Value::Integer(value),
+        P::UInt(value) => Value::Real(value as f64),
+        P::NumberLexeme(text) => sql_value(P::from_number_lexeme(&text)),
+        P::String(text) => Value::Text(text),
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn toon_cell(name: &str, value: ValueRef<'_>) -> Result<JsonPrimitive> {
+    Ok(match value {
+        ValueRef::Null => P::Null,
+        ValueRef::Integer(value) => P::Int(value),
+        ValueRef::Real(value) => P::Number(value),
+        ValueRef::Text(text) => P::String(String::from_utf8_lossy(text).into_owned()),
+        ValueRef::Blob(_) => {
+            return Err(ToonError::message(format!(
+                "Column '{name}' holds a BLOB, which TOON cannot represent"
+            )));
+        }
+    })
+}
+
+pub(crate) fn sql_error(err: &rusqlite::Error) -> ToonError {
+    ToonError::message(format!("SQLite error: {err}"))
+}
@@ -0,0 +1,409 @@
+//! Row-at-a-time access to tabular arrays.
+//!
+//! [`TabularReader`] finds a tabular header (`users[N]{id,name}:`) in a
+//! reader and decodes its rows one line at a time, so a large table can be
+//! processed without decoding the rest of the document or holding every row
+//! in memory. [`TabularWriter`] writes a header and then rows as they are
+//! produced. Rows are either `Vec<JsonPrimitive>` in header order or serde
+//! types whose fields are named after the columns, which makes the pair a
+//! lightweight bridge to CSV-like and dataframe tooling.
+
+use std::io::{BufRead, Write};
+use std::marker::PhantomData;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::decode::parser::{
+    coerce_to_column_type, map_row_values_to_primitives, parse_array_header_line,
+    parse_delimited_values, parse_field_type_annotations,
+};
+use crate::encode::primitives::encode_and_join_primitives;
+use crate::error::{Result, ToonError};
+use crate::header::{ArrayHeaderInfo, FieldName, HeaderBuilder};
+use crate::options::{EmptyCellMode, EmptyStringStyle};
+use crate::shared::constants::{DEFAULT_DELIMITER, MAX_EXACT_INTEGER};
+use crate::{JsonPrimitive, JsonPrimitiveRef, JsonValue, StringOrNumberOrBoolOrNull};
+
+/// Reads the rows of one tabular array.
+///
+/// Rows are checked against the header: each must have one cell per field
+/// and typed fields (`id:int`) are coerced. Reading stops after the declared
+/// number of rows; a section that ends early is an error.
+#[derive(Debug)]
+pub struct TabularReader<R> {
+    reader: R,
+    header: ArrayHeaderInfo,
+    fields: Vec<FieldName>,
+    line: String,
+    line_number: usize,
+    header_indent: usize,
+    row_indent: Option<usize>,
+    rows: usize,
+    done: bool,
+}
+
+impl<R: BufRead> TabularReader<R> {
+    /// Read a tabular header from the next non-blank line.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the line is not a tabular array header, or if
+    /// reading fails.
+    pub fn new(reader: R) -> Result<Self> {
+        Self::open(reader, None, false)
+    }
+
+    /// Skip lines until a tabular header for `key` (or any key, if `None`)
+    /// is found.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no matching header is found, or if reading fails.
+    pub fn find(reader: R, key: Option<&str>) -> Result<Self> {
+        Self::open(reader, key, true)
+    }
+
+    fn open(reader: R, key: Option<&str>, search: bool) -> Result<Self> {
+        let mut this = Self {
+            reader,
+            header: ArrayHeaderInfo {
+                key: None,
+                key_was_quoted: false,
+                length: 0,
+                delimiter: DEFAULT_DELIMITER,
+                fields: None,
+            },
+            fields: Vec::new(),
+            line: String::new(),
+            line_number: 0,
+            header_indent: 0,
+            row_indent: None,
+            rows: 0,
+            done: false,
+        };
+        while this.read_line()? {
+            let content = this.line.trim_start_matches(' ');
+            if content.trim().is_empty() {
+                continue;
+            }
+            let indent = this.line.len() - content.len();
+            let parsed = parse_array_header_line(content, DEFAULT_DELIMITER);
+            let parsed = if search {
+                parsed.ok().flatten()
+            } else {
+                parsed?
+            };
+            let tabular = parsed.filter(|parsed| {
+                parsed.header.fields.is_some()
+                    && parsed.inline_values.is_none()
+                    && key.is_none_or(|key| parsed.header.key.as_deref() == Some(key))
+            });
+            if let Some(mut parsed) = tabular {
+                let mut fields = parsed.header.fields.take().unwrap_or_default();
+                parse_field_type_annotations(&mut fields, this.line_number)?;
+                parsed.header.fields = Some(fields.clone());
+                this.header = parsed.header;
+                this.fields = fields;
+                this.header_indent = indent;
+                return Ok(this);
+            }
+            if !search {
+                return Err(ToonError::parse(
+                    this.line_number,
+                    "Expected a tabular array header",
+                ));
+            }
+        }
+        Err(ToonError::message(key.map_or_else(
+            || "No tabular array header found".to_string(),
+            |key| format!("No tabular array header found for '{key}'"),
+        )))
+    }
+
+    #[must_use]
+    pub const fn header(&self) -> &ArrayHeaderInfo {
+        &self.header
+    }
+
+    #[must_use]
+    pub fn fields(&self) -> &[FieldName] {
+        &self.fields
+    }
+
+    /// Read the next row into a serde type whose fields are the columns.
+    pub fn next_as<T: DeserializeOwned>(&mut self) -> Option<Result<T>> {
+        let row = self.next()?;
+        Some(row.and_then(|row| {
+            let object = self
+                .fields
+                .iter()
+                .zip(row)
+                .map(|(field, cell)| (field.name.clone(), primitive_to_serde(cell)))
+                .collect();
+            serde_json::from_value(serde_json::Value::Object(object))
+                .map_err(|err| ToonError::validation(self.line_number, err.to_string()))
+        }))
+    }
+
+    /// Read the remaining rows as serde types.
+    pub fn deserialize<T: DeserializeOwned>(self) -> DeserializeRows<R, T> {
+        DeserializeRows {
+            reader: self,
+            row: PhantomData,
+        }
+    }
+
+    /// Read one line into `self.line`, without its line ending. Returns
+    /// `false` at the end of input.
+    fn read_line(&mut self) -> Result<bool> {
+        self.line.clear();
+        let read = self
+            .reader
+            .read_line(&mut self.line)
+            .map_err(|e| ToonError::io("Failed to read TOON input", None, e))?;
+        if read == 0 {
+            return Ok(false);
+        }
+        self.line_number += 1;
+        let trimmed = self.line.trim_end_matches(['\n', '\r']).len();
+        self.line.truncate(trimmed);
+        Ok(true)
+    }
+
+    fn read_row(&mut self) -> Result<Vec<JsonPrimitive>> {
+        loop {
+            if !self.read_line()? {
+                return Err(self.too_few_rows());
+            }
+            let content = self.line.trim_start_matches(' ');
+            if content.trim().is_empty() {
+                continue;
+            }
+            let indent = self.line.len() - content.len();
+            if indent <= self.header_indent {
+                return Err(self.too_few_rows());
+            }
+            let expected = *self.row_indent.get_or_insert(indent);
+            if indent != expected {
+                return Err(ToonError::invalid_indentation(
+                    self.line_number,
+                    expected,
+                    indent,
+                ));
+            }
+            return self.parse_row();
+        }
+    }
+
+    fn parse_row(&self) -> Result<Vec<JsonPrimitive>> {
+        let values = parse_delimited_values(self.line.trim(), self.header.delimiter);
+        if values.len() != self.fields.len() {
+            return Err(ToonError::validation(
+                self.line_number,
+                format!(
+                    "Expected {} tabular row values, but got {}",
+                    self.fields.len(),
+                    values.len()
+                ),
+            ));
+        }
+        let mut row =
+            map_row_values_to_primitives(&values, EmptyCellMode::EmptyString, self.line_number)?;
+        for ((cell, raw), field) in row.iter_mut().zip(&values).zip(&self.fields) {
+            let Some(column_type) = field.column_type else {
+                continue;
+            };
+            *cell = coerce_to_column_type(cell, raw, column_type).ok_or_else(|| {
+                ToonError::validation(
+                    self.line_number,
+                    format!(
+                        "Column '{}' expects {}, found '{}'",
+                        field.name,
+                        column_type.as_str(),
+                        raw.trim()
+                    ),
+                )
+            })?;
+        }
+        Ok(row)
+    }
+
+    fn too_few_rows(&self) -> ToonError {
+        ToonError::message(format!(
+            "Expected {} tabular rows, but got {}",
+            self.header.length, self.rows
+        ))
+    }
+}
+
+impl<R: BufRead> Iterator for TabularReader<R> {
+    type Item = Result<Vec<JsonPrimitive>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.rows == self.header.length {
+            return None;
+        }
+        let row = self.read_row();
+        match row {
+            Ok(_) => self.rows += 1,
+            Err(_) => self.done = true,
+        }
+        Some(row)
+    }
+}
+
+/// Iterator returned by [`TabularReader::deserialize`].
+#[derive(Debug)]
+pub struct DeserializeRows<R, T> {
+    reader: TabularReader<R>,
+    row: PhantomData<fn() -> T>,
+}
+
+impl<R: BufRead, T: DeserializeOwned> Iterator for DeserializeRows<R, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.reader.next_as()
+    }
+}
+
+/// Writes one tabular array, a row at a time.
+///
+/// The header, including the row count, is written up front; [`finish`]
+/// checks that exactly that many rows followed.
+///
+/// [`finish`]: TabularWriter::finish
+#[derive(Debug)]
+pub struct TabularWriter<W: Write> {
+    writer: W,
+    fields: Vec<String>,
+    delimiter: char,
+    length: usize,
+    indent: String,
+    rows: usize,
+}
+
+impl<W: Write> TabularWriter<W> {
+    /// Write `header` and prepare to write rows indented by `indent` spaces.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the header has no fields, or if writing fails.
+    pub fn new(mut writer: W, header: &HeaderBuilder, indent: usize) -> Result<Self> {
+        let Some(fields) = &header.fields else {
+            return Err(ToonError::message(
+                "Tabular header needs at least one field",
+            ));
+        };
+        let fields = fields.iter().map(|(name, _)| name.clone()).collect();
+        writeln!(writer, "{}", header.build()).map_err(write_error)?;
+        Ok(Self {
+            writer,
+            fields,
+            delimiter: header.delimiter,
+            length: header.length,
+            indent: " ".repeat(indent),
+            rows: 0,
+        })
+    }
+
+    /// Write a row of cells in header order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the row has the wrong number of cells, if the
+    /// header's row count has already been written, or if writing fails.
+    pub fn write_row(&mut self, row: &[JsonPrimitive]) -> Result<()> {
+        let path = format!("[{}]", self.rows);
+        if self.rows == self.length {
+            return Err(ToonError::encode(
+                path,
+                format!("header declares {} rows", self.length),
+            ));
+        }
+        if row.len() != self.fields.len() {
+            return Err(ToonError::encode(
+                path,
+                format!(
+                    "expected {} tabular row values, got {}",
+                    self.fields.len(),
+                    row.len()
+                ),
+            ));
+        }
+        let row: Vec<JsonPrimitiveRef> = row.iter().map(Into::into).collect();
+        let line = encode_and_join_primitives(&row, self.delimiter, EmptyStringStyle::Quoted);
+        writeln!(self.writer, "{}{line}", self.indent).map_err(write_error)?;
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Write a serde value whose fields are the columns.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value is not an object with exactly the
+    /// header's fields, if a field is not a primitive, or if
+    /// [`write_row`](Self::write_row) fails.
+    pub fn serialize_row<T: Serialize + ?Sized>(&mut self, row: &T) -> Result<()> {
+        let path = format!("[{}]", self.rows);
+        let value = serde_json::to_value(row).map_err(|err| ToonError::json_stringify(&err))?;
+        let JsonValue::Object(mut entries) = JsonValue::from(value) else {
+            return Err(ToonError::encode(path, "tabular row is not an object"));
+        };
+        let mut cells = Vec::with_capacity(self.fields.len());
+        for field in &self.fields {
+            let index = entries
+                .iter()
+                .position(|(key, _)| key == field)
+                .ok_or_else(|| ToonError::tabular_missing_field(path.clone(), field))?;
+            match entries.swap_remove(index).1 {
+                JsonValue::Primitive(cell) => cells.push(cell),
+                _ => return Err(ToonError::tabular_non_primitive(format!("{path}.{field}"))),
+            }
+        }
+        if let Some((key, _)) = entries.first() {
+            return Err(ToonError::encode(
+                path,
+                format!("tabular row has field '{key}' that is not in the header"),
+            ));
+        }
+        self.write_row(&cells)
+    }
+
+    /// Check the row count and flush, returning the writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if fewer rows were written than the header declares,
+    /// or if flushing fails.
+    pub fn finish(mut self) -> Result<W> {
+        if self.rows != self.length {
+            return Err(ToonError::message(format!(
+                "Expected {} tabular rows, but got {}",
+                self.length, self.rows
+            )));
+        }
+        self.writer.flush().map_err(write_error)?;
+        Ok(self.writer)
+    }
+}
+
+fn write_error(err: std::io::Error) -> ToonError {
+    ToonError::io("Failed to write TOON output", None, err)
+}
+
+/// Whole numbers become JSON integers, so that they deserialize into
+/// integer fields.
+#[allow(clippy::cast_possible_truncation)]
+fn primitive_to_serde(cell: JsonPrimitive) -> serde_json::Value {
+    match cell {
+        StringOrNumberOrBoolOrNull::Number(number)
+            if number.fract() == 0.0 && number.abs() <= MAX_EXACT_INTEGER =>
+        {
+            serde_json::Value::from(number as i64)
+        }
+        cell => JsonValue::Primitive(cell).into(),
+    }
+}
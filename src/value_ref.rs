@@ -0,0 +1,160 @@
+//! Borrowed views of JSON values.
+//!
+//! [`JsonValueRef`] holds the same tree as a [`JsonValue`] but borrows its
+//! keys and strings from the value it was built from, so a large
+//! `serde_json::Value` can be encoded without copying every string. The
+//! encoder works on this view; owned values are viewed before encoding.
+
+use std::borrow::Cow;
+
+use crate::{JsonPrimitive, JsonValue, StringOrNumberOrBoolOrNull};
+
+/// A borrowed [`JsonPrimitive`].
+///
+/// Equality compares numbers by value, as [`JsonPrimitive`] does.
+#[derive(Debug, Clone)]
+pub enum JsonPrimitiveRef<'a> {
+    String(&'a str),
+    Number(f64),
+    Int(i64),
+    UInt(u64),
+    /// A number as written. Owned only when it was put in canonical form
+    /// while viewing (see the `arbitrary-precision` feature).
+    NumberLexeme(Cow<'a, str>),
+    Bool(bool),
+    Null,
+}
+
+impl JsonPrimitiveRef<'_> {
+    /// Whether this is a number, in any of its variants.
+    #[must_use]
+    pub const fn is_number(&self) -> bool {
+        matches!(
+            self,
+            Self::Number(_) | Self::Int(_) | Self::UInt(_) | Self::NumberLexeme(_)
+        )
+    }
+
+    /// The owned primitive, copying the string if there is one.
+    #[must_use]
+    pub fn into_primitive(self) -> JsonPrimitive {
+        match self {
+            Self::String(value) => StringOrNumberOrBoolOrNull::String(value.to_string()),
+            Self::Number(value) => StringOrNumberOrBoolOrNull::Number(value),
+            Self::Int(value) => StringOrNumberOrBoolOrNull::Int(value),
+            Self::UInt(value) => StringOrNumberOrBoolOrNull::UInt(value),
+            Self::NumberLexeme(text) => StringOrNumberOrBoolOrNull::NumberLexeme(text.into_owned()),
+            Self::Bool(value) => StringOrNumberOrBoolOrNull::Bool(value),
+            Self::Null => StringOrNumberOrBoolOrNull::Null,
+        }
+    }
+}
+
+impl PartialEq for JsonPrimitiveRef<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::String(a), Self::String(b)) => a == b,
+            (Self::Bool(a), Self::Bool(b)) => a == b,
+            (Self::Null, Self::Null) => true,
+            _ if self.is_number() && other.is_number() => {
+                self.clone().into_primitive() == other.clone().into_primitive()
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<'a> From<&'a JsonPrimitive> for JsonPrimitiveRef<'a> {
+    fn from(value: &'a JsonPrimitive) -> Self {
+        match value {
+            StringOrNumberOrBoolOrNull::String(value) => Self::String(value),
+            StringOrNumberOrBoolOrNull::Number(value) => Self::Number(*value),
+            StringOrNumberOrBoolOrNull::Int(value) => Self::Int(*value),
+            StringOrNumberOrBoolOrNull::UInt(value) => Self::UInt(*value),
+            StringOrNumberOrBoolOrNull::NumberLexeme(text) => {
+                Self::NumberLexeme(Cow::Borrowed(text))
+            }
+            StringOrNumberOrBoolOrNull::Bool(value) => Self::Bool(*value),
+            StringOrNumberOrBoolOrNull::Null => Self::Null,
+        }
+    }
+}
+
+/// Exact as `Int` or `UInt` when the number is an integer that fits in 64
+/// bits, otherwise an `f64` (`null` if it is not finite). With the
+/// `arbitrary-precision` feature, a number `f64` cannot hold is kept as a
+/// lexeme instead (see [`StringOrNumberOrBoolOrNull::from_decimal`]).
+impl From<&serde_json::Number> for JsonPrimitiveRef<'_> {
+    fn from(value: &serde_json::Number) -> Self {
+        if let Some(value) = value.as_i64() {
+            return Self::Int(value);
+        }
+        if let Some(value) = value.as_u64() {
+            return Self::UInt(value);
+        }
+        #[cfg(feature = "arbitrary-precision")]
+        let number = StringOrNumberOrBoolOrNull::from_decimal(&value.to_string());
+        #[cfg(not(feature = "arbitrary-precision"))]
+        let number = StringOrNumberOrBoolOrNull::from_f64(
+            value
+                .as_f64()
+                .unwrap_or_else(|| value.to_string().parse::<f64>().unwrap_or(f64::NAN)),
+        );
+        match number {
+            StringOrNumberOrBoolOrNull::Number(value) => Self::Number(value),
+            StringOrNumberOrBoolOrNull::Int(value) => Self::Int(value),
+            StringOrNumberOrBoolOrNull::UInt(value) => Self::UInt(value),
+            StringOrNumberOrBoolOrNull::NumberLexeme(text) => Self::NumberLexeme(Cow::Owned(text)),
+            _ => Self::Null,
+        }
+    }
+}
+
+/// A borrowed [`JsonValue`]: the same tree, with keys and strings borrowed
+/// from the value it views.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValueRef<'a> {
+    Primitive(JsonPrimitiveRef<'a>),
+    Array(Vec<Self>),
+    Object(Vec<(&'a str, Self)>),
+    /// Raw TOON text, written in place of the field or item (see
+    /// [`JsonValue::Raw`]).
+    #[cfg(feature = "raw")]
+    Raw(&'a str),
+}
+
+impl<'a> From<&'a JsonValue> for JsonValueRef<'a> {
+    fn from(value: &'a JsonValue) -> Self {
+        match value {
+            JsonValue::Primitive(primitive) => Self::Primitive(primitive.into()),
+            JsonValue::Array(items) => Self::Array(items.iter().map(Self::from).collect()),
+            JsonValue::Object(entries) => Self::Object(
+                entries
+                    .iter()
+                    .map(|(key, value)| (key.as_str(), Self::from(value)))
+                    .collect(),
+            ),
+            #[cfg(feature = "raw")]
+            JsonValue::Raw(text) => Self::Raw(text),
+        }
+    }
+}
+
+/// Views a `serde_json::Value` as [`JsonValue::from`] would convert it,
+/// without copying its keys or strings.
+impl<'a> From<&'a serde_json::Value> for JsonValueRef<'a> {
+    fn from(value: &'a serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => Self::Primitive(JsonPrimitiveRef::Null),
+            serde_json::Value::Bool(value) => Self::Primitive(JsonPrimitiveRef::Bool(*value)),
+            serde_json::Value::Number(value) => Self::Primitive(value.into()),
+            serde_json::Value::String(value) => Self::Primitive(JsonPrimitiveRef::String(value)),
+            serde_json::Value::Array(items) => Self::Array(items.iter().map(Self::from).collect()),
+            serde_json::Value::Object(map) => Self::Object(
+                map.iter()
+                    .map(|(key, value)| (key.as_str(), Self::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
@@ -0,0 +1,281 @@
+//! Structure diagrams of documents (`toon viz`).
+//!
+//! [`visualize`] draws the shape of a value as a Graphviz DOT or Mermaid
+//! graph: one node per object and array, listing its primitive fields with
+//! their types, and edges to the containers nested in it. Values never
+//! appear, so a diagram of a real payload can go into docs that explain a
+//! prompt's schema.
+//!
+//! Arrays are labelled like TOON headers (`users[3]{id,name}`), and the
+//! items of an array are merged into one shape: a field with different types
+//! in different items shows every type seen (`string|null`), and one missing
+//! from some items is marked optional (`note: string?`).
+
+use std::fmt::Write as _;
+
+use crate::{JsonValue, StringOrNumberOrBoolOrNull};
+
+/// The diagram language to write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VizFormat {
+    /// Graphviz DOT (`dot -Tsvg`).
+    Dot,
+    /// A Mermaid flowchart, which renders in Markdown on most code hosts.
+    Mermaid,
+}
+
+/// Draw the structure of `value` in `format`.
+#[must_use]
+pub fn visualize(value: &JsonValue, format: VizFormat) -> String {
+    let root = if is_container(value) {
+        describe("root", &[value])
+    } else {
+        Node {
+            title: "root".to_string(),
+            fields: vec![("value".to_string(), type_union(&[value]))],
+            children: Vec::new(),
+        }
+    };
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+    flatten(root, None, &mut nodes, &mut edges);
+    match format {
+        VizFormat::Dot => render_dot(&nodes, &edges),
+        VizFormat::Mermaid => render_mermaid(&nodes, &edges),
+    }
+}
+
+/// The merged shape of the values found at one position.
+struct Node {
+    title: String,
+    /// Primitive fields and the types seen for them.
+    fields: Vec<(String, String)>,
+    children: Vec<Self>,
+}
+
+/// A node without its children, numbered in the order drawn.
+struct Flat {
+    title: String,
+    fields: Vec<(String, String)>,
+}
+
+const fn is_container(value: &JsonValue) -> bool {
+    matches!(value, JsonValue::Array(_) | JsonValue::Object(_))
+}
+
+/// Describe the values found under `key`, at least one of them a container.
+fn describe(key: &str, values: &[&JsonValue]) -> Node {
+    let mut objects = Vec::new();
+    let mut arrays = Vec::new();
+    let mut others = Vec::new();
+    for value in values {
+        match value {
+            JsonValue::Object(entries) => objects.push(entries),
+            JsonValue::Array(items) => arrays.push(items),
+            JsonValue::Primitive(_) => others.push(*value),
+            #[cfg(feature = "raw")]
+            JsonValue::Raw(_) => others.push(*value),
+        }
+    }
+
+    let mut node = if arrays.is_empty() {
+        describe_object(key.to_string(), &objects)
+    } else {
+        let items: Vec<&JsonValue> = arrays.iter().flat_map(|items| items.iter()).collect();
+        let length = length_label(arrays.iter().map(|items| items.len()));
+        let mut node = describe_items(key, &length, &items);
+        if !objects.is_empty() {
+            // Objects next to arrays at one position: show them as an
+            // alternative shape rather than mixing in their fields.
+            node.children
+                .push(describe_object(format!("{key} (object)"), &objects));
+        }
+        node
+    };
+    if !others.is_empty() {
+        node.fields
+            .insert(0, ("(or)".to_string(), type_union(&others)));
+    }
+    node
+}
+
+/// An array node, from the items of every array seen at this position.
+fn describe_items(key: &str, length: &str, items: &[&JsonValue]) -> Node {
+    let objects: Vec<&Vec<(String, JsonValue)>> = items
+        .iter()
+        .filter_map(|item| match item {
+            JsonValue::Object(entries) => Some(entries),
+            _ => None,
+        })
+        .collect();
+    let tabular = !objects.is_empty()
+        && objects.len() == items.len()
+        && objects
+            .iter()
+            .all(|entries| entries.iter().all(|(_, value)| !is_container(value)));
+
+    if tabular {
+        let mut node = describe_object(String::new(), &objects);
+        let names: Vec<&str> = node.fields.iter().map(|(name, _)| name.as_str()).collect();
+        node.title = format!("{key}[{length}]{{{}}}", names.join(","));
+        return node;
+    }
+
+    let mut node = Node {
+        title: format!("{key}[{length}]"),
+        fields: Vec::new(),
+        children: Vec::new(),
+    };
+    let primitives: Vec<&JsonValue> = items
+        .iter()
+        .copied()
+        .filter(|item| !is_container(item))
+        .collect();
+    if !primitives.is_empty() {
+        node.fields
+            .push(("items".to_string(), type_union(&primitives)));
+    }
+    let containers: Vec<&JsonValue> = items
+        .iter()
+        .copied()
+        .filter(|item| is_container(item))
+        .collect();
+    if !containers.is_empty() {
+        node.children.push(describe("item", &containers));
+    }
+    node
+}
+
+/// An object node merging every object seen at this position, with keys in
+/// the order they first appear.
+fn describe_object(title: String, objects: &[&Vec<(String, JsonValue)>]) -> Node {
+    let mut keys: Vec<(&str, Vec<&JsonValue>)> = Vec::new();
+    for entries in objects {
+        for (key, value) in *entries {
+            match keys.iter_mut().find(|(seen, _)| *seen == key) {
+                Some((_, values)) => values.push(value),
+                None => keys.push((key, vec![value])),
+            }
+        }
+    }
+
+    let mut node = Node {
+        title,
+        fields: Vec::new(),
+        children: Vec::new(),
+    };
+    for (key, values) in keys {
+        let optional = values.len() < objects.len();
+        if values.iter().any(|value| is_container(value)) {
+            let mut child = describe(key, &values);
+            if optional {
+                child.title.push('?');
+            }
+            node.children.push(child);
+            continue;
+        }
+        let mut type_name = type_union(&values);
+        if optional {
+            type_name.push('?');
+        }
+        node.fields.push((key.to_string(), type_name));
+    }
+    node
+}
+
+/// `3` when every array has 3 items, `1..4` when they differ.
+fn length_label(lengths: impl Iterator<Item = usize>) -> String {
+    let (min, max) = lengths.fold((usize::MAX, 0), |(min, max), length| {
+        (min.min(length), max.max(length))
+    });
+    if min == max {
+        min.to_string()
+    } else {
+        format!("{min}..{max}")
+    }
+}
+
+/// The types of primitive values, in a fixed order (`number|string|null`).
+fn type_union(values: &[&JsonValue]) -> String {
+    const NAMES: [&str; 4] = ["bool", "number", "string", "null"];
+    let mut seen = [false; 4];
+    for value in values {
+        let index = match value {
+            JsonValue::Primitive(StringOrNumberOrBoolOrNull::Bool(_)) => 0,
+            JsonValue::Primitive(
+                StringOrNumberOrBoolOrNull::Number(_)
+                | StringOrNumberOrBoolOrNull::Int(_)
+                | StringOrNumberOrBoolOrNull::UInt(_),
+            ) => 1,
+            JsonValue::Primitive(StringOrNumberOrBoolOrNull::Null) => 3,
+            _ => 2,
+        };
+        seen[index] = true;
+    }
+    NAMES
+        .iter()
+        .zip(seen)
+        .filter_map(|(name, seen)| seen.then_some(*name))
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+fn flatten(
+    node: Node,
+    parent: Option<usize>,
+    nodes: &mut Vec<Flat>,
+    edges: &mut Vec<(usize, usize)>,
+) {
+    let id = nodes.len();
+    nodes.push(Flat {
+        title: node.title,
+        fields: node.fields,
+    });
+    if let Some(parent) = parent {
+        edges.push((parent, id));
+    }
+    for child in node.children {
+        flatten(child, Some(id), nodes, edges);
+    }
+}
+
+fn render_dot(nodes: &[Flat], edges: &[(usize, usize)]) -> String {
+    let escape = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+    let mut out = String::from("digraph toon {\n");
+    out.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+    for (id, node) in nodes.iter().enumerate() {
+        let mut label = escape(&node.title);
+        if !node.fields.is_empty() {
+            label.push_str("\\n\\n");
+        }
+        for (name, type_name) in &node.fields {
+            let _ = write!(label, "{}: {type_name}\\l", escape(name));
+        }
+        let _ = writeln!(out, "  n{id} [label=\"{label}\"];");
+    }
+    for (from, to) in edges {
+        let _ = writeln!(out, "  n{from} -> n{to};");
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn render_mermaid(nodes: &[Flat], edges: &[(usize, usize)]) -> String {
+    let escape = |text: &str| {
+        text.replace('"', "#quot;")
+            .replace('<', "#lt;")
+            .replace('>', "#gt;")
+    };
+    let mut out = String::from("flowchart TD\n");
+    for (id, node) in nodes.iter().enumerate() {
+        let mut label = escape(&node.title);
+        for (name, type_name) in &node.fields {
+            let _ = write!(label, "<br/>{}: {type_name}", escape(name));
+        }
+        let _ = writeln!(out, "  n{id}[\"{label}\"]");
+    }
+    for (from, to) in edges {
+        let _ = writeln!(out, "  n{from} --> n{to}");
+    }
+    out
+}
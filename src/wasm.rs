@@ -59,7 +59,7 @@ pub fn init_panic_hook() {
 pub fn encode(json: &str) -> Result<String, JsError> {
     let value: serde_json::Value =
         serde_json::from_str(json).map_err(|e| JsError::new(&format!("Invalid JSON: {e}")))?;
-    Ok(crate::encode::encode(value, None))
+    crate::encode::try_encode(value, None).map_err(|e| JsError::new(&format!("Encode error: {e}")))
 }
 
 /// Encode a JSON string to TOON format with options.
@@ -82,7 +82,8 @@ pub fn encode_with_options(json: &str, options: JsValue) -> Result<String, JsErr
         serde_json::from_str(json).map_err(|e| JsError::new(&format!("Invalid JSON: {e}")))?;
 
     let encode_options = parse_encode_options(options)?;
-    Ok(crate::encode::encode(value, encode_options))
+    crate::encode::try_encode(value, encode_options)
+        .map_err(|e| JsError::new(&format!("Encode error: {e}")))
 }
 
 /// Decode a TOON string to JSON format.
@@ -149,6 +150,17 @@ pub fn decode_pretty(toon: &str) -> Result<String, JsError> {
         .map_err(|e| JsError::new(&format!("JSON stringify error: {e}")))
 }
 
+/// Render TOON as HTML with syntax-highlighting spans.
+///
+/// Tokens are wrapped in `<span class="toon-key">`, `toon-header`,
+/// `toon-list-marker`, `toon-string`, `toon-number`, `toon-bool`, and
+/// `toon-null`; wrap the result in a `<pre>` and style the classes.
+#[must_use]
+#[wasm_bindgen]
+pub fn highlight_html(toon: &str) -> String {
+    crate::render::highlight_toon(toon, crate::render::Theme::Html)
+}
+
 /// Get the library version.
 #[must_use]
 #[wasm_bindgen]
@@ -205,6 +217,7 @@ fn parse_encode_options(
         key_folding,
         flatten_depth,
         replacer: None,
+        ..EncodeOptions::default()
     }))
 }
 
@@ -247,6 +260,7 @@ fn parse_decode_options(
         indent,
         strict,
         expand_paths,
+        ..DecodeOptions::default()
     }))
 }
 
@@ -266,15 +280,26 @@ mod tests {
         assert_eq!(result, r#"{"name":"Alice"}"#);
     }
 
+    #[test]
+    fn test_highlight_html() {
+        assert_eq!(
+            highlight_html("a: 1"),
+            r#"<span class="toon-key">a</span>: <span class="toon-number">1</span>"#
+        );
+    }
+
     #[test]
     fn test_roundtrip() {
-        // Use floats in the original JSON since TOON uses f64 internally
-        let json = r#"{"users":[{"id":1.0,"name":"Alice"},{"id":2.0,"name":"Bob"}]}"#;
+        let json = r#"{"users":[{"id":1,"name":"Alice"},{"id":2,"name":"Bob"}]}"#;
         let toon = encode(json).unwrap();
         let decoded = decode(&toon).unwrap();
         // Parse both to compare values
         let original: serde_json::Value = serde_json::from_str(json).unwrap();
         let roundtrip: serde_json::Value = serde_json::from_str(&decoded).unwrap();
         assert_eq!(original, roundtrip);
+
+        // Integral floats are written as integers and read back as such.
+        let decoded = decode(&encode(r#"{"id":1.0}"#).unwrap()).unwrap();
+        assert_eq!(decoded, r#"{"id":1}"#);
     }
 }
@@ -0,0 +1,96 @@
+//! Arrow interop tests.
+//!
+//! Run with: cargo test --features arrow
+
+#![cfg(feature = "arrow")]
+
+use std::sync::Arc;
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float64Type, Int64Type};
+use arrow_array::{Array, ArrayRef, BooleanArray, Int32Array, ListArray, RecordBatch, StringArray};
+use arrow_schema::{DataType, Field, Schema};
+use toon::interop::arrow::{read_record_batch, write_record_batch};
+
+#[test]
+fn read_infers_column_types() {
+    let input = "meta:\n  n: 3\nrows[3]{id,score,ok,name,note}:\n  1,1.5,true,Ada,null\n  2,2,false,\"007\",null\n  3,null,true,Bob,null\n";
+    let batch = read_record_batch(input.as_bytes(), Some("rows")).unwrap();
+    let schema = batch.schema();
+    let types: Vec<_> = schema
+        .fields()
+        .iter()
+        .map(|f| f.data_type().clone())
+        .collect();
+    assert_eq!(
+        types,
+        [
+            DataType::Int64,
+            DataType::Float64,
+            DataType::Boolean,
+            DataType::Utf8,
+            DataType::Null,
+        ]
+    );
+    assert_eq!(batch.num_rows(), 3);
+    assert_eq!(batch.column(0).as_primitive::<Int64Type>().value(2), 3);
+    let scores = batch.column(1).as_primitive::<Float64Type>();
+    assert!((scores.value(1) - 2.0).abs() < f64::EPSILON);
+    assert!(scores.is_null(2));
+    assert_eq!(batch.column(3).as_string::<i32>().value(1), "007");
+}
+
+#[test]
+fn typed_headers_pick_column_types() {
+    let input = "rows[2]{id:num,code:str}:\n  1,42\n  2,7\n";
+    let batch = read_record_batch(input.as_bytes(), None).unwrap();
+    assert_eq!(batch.schema().field(0).data_type(), &DataType::Float64);
+    assert_eq!(batch.column(1).as_string::<i32>().value(0), "42");
+}
+
+#[test]
+fn write_then_read_round_trips() {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, true),
+        Field::new("active", DataType::Boolean, true),
+    ]));
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int32Array::from(vec![1, 2])),
+        Arc::new(StringArray::from(vec![Some("Ada, L"), None])),
+        Arc::new(BooleanArray::from(vec![Some(true), Some(false)])),
+    ];
+    let batch = RecordBatch::try_new(schema, columns).unwrap();
+
+    let written = write_record_batch(Vec::new(), Some("users"), &batch, 2).unwrap();
+    let written = String::from_utf8(written).unwrap();
+    assert_eq!(
+        written,
+        "users[2]{id:int,name:str,active:bool}:\n  1,\"Ada, L\",true\n  2,null,false\n"
+    );
+
+    let read = read_record_batch(written.as_bytes(), Some("users")).unwrap();
+    assert_eq!(read.schema().field(0).data_type(), &DataType::Int64);
+    assert_eq!(read.column(1).as_string::<i32>().value(0), "Ada, L");
+    assert!(read.column(1).is_null(1));
+    assert!(!read.column(2).as_boolean().value(1));
+}
+
+#[test]
+fn unsupported_column_types_are_rejected() {
+    let list =
+        ListArray::from_iter_primitive::<arrow_array::types::Int32Type, _, _>(vec![Some(vec![
+            Some(1),
+        ])]);
+    let schema = Arc::new(Schema::new(vec![Field::new(
+        "tags",
+        list.data_type().clone(),
+        true,
+    )]));
+    let batch = RecordBatch::try_new(schema, vec![Arc::new(list)]).unwrap();
+    let err = write_record_batch(Vec::new(), None, &batch, 2).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Arrow column 'tags' has unsupported type")
+    );
+}
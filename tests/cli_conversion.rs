@@ -1,3 +1,5 @@
+#![cfg(feature = "cli")]
+
 use toon::cli::conversion::{decode_to_json_chunks, encode_to_toon_lines};
 use toon::options::{DecodeOptions, EncodeOptions, ExpandPathsMode, KeyFoldingMode};
 
@@ -26,6 +28,7 @@ fn decode_to_json_chunks_streams_without_expand_paths() {
         indent: Some(2),
         strict: Some(true),
         expand_paths: Some(ExpandPathsMode::Off),
+        ..DecodeOptions::default()
     };
 
     let output = decode_to_json_chunks(input, Some(options))
@@ -42,6 +45,7 @@ fn decode_to_json_chunks_expands_paths_when_enabled() {
         indent: Some(0),
         strict: Some(true),
         expand_paths: Some(ExpandPathsMode::Safe),
+        ..DecodeOptions::default()
     };
 
     let output = decode_to_json_chunks(input, Some(options))
@@ -61,6 +65,7 @@ fn encode_to_toon_lines_respects_options() {
         key_folding: Some(KeyFoldingMode::Safe),
         flatten_depth: Some(usize::MAX),
         replacer: None,
+        ..EncodeOptions::default()
     };
 
     let output = encode_to_toon_lines(input, Some(options))
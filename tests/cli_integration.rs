@@ -3,6 +3,8 @@
 //! These tests exercise the actual binary using `assert_cmd` to ensure
 //! end-to-end functionality works correctly.
 
+#![cfg(feature = "cli")]
+
 use assert_cmd::Command;
 use predicates::prelude::*;
 use std::fs;
@@ -152,6 +154,115 @@ fn encode_rejects_invalid_json() {
         .stderr(predicate::str::contains("Failed to parse JSON"));
 }
 
+#[test]
+#[cfg(not(feature = "arbitrary-precision"))]
+fn encode_fail_on_lossy_rejects_precision_loss() {
+    toon()
+        .args(["--encode", "--fail-on-lossy"])
+        .write_stdin(r#"{"id":123456789012345678901,"name":"a","name":"b"}"#)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Lossy conversion at 2 site(s)"))
+        .stderr(predicate::str::contains("id: number loses precision"))
+        .stderr(predicate::str::contains("name: duplicate key collapsed"));
+}
+
+#[test]
+#[cfg(feature = "arbitrary-precision")]
+fn encode_fail_on_lossy_accepts_long_numbers_with_arbitrary_precision() {
+    toon()
+        .args(["--encode", "--fail-on-lossy"])
+        .write_stdin(r#"{"id":123456789012345678901,"price":0.10000000000000000001}"#)
+        .assert()
+        .success()
+        .stdout("id: 123456789012345678901\nprice: 0.10000000000000000001\n");
+}
+
+#[test]
+fn encode_fail_on_lossy_accepts_exact_input() {
+    toon()
+        .args(["--encode", "--fail-on-lossy"])
+        .write_stdin(r#"{"id":42,"ratio":0.1}"#)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ratio: 0.1"));
+}
+
+#[test]
+fn encode_fail_on_lossy_rejects_keys_nfc_merges() {
+    let input = "{\"e\u{301}\":1,\"\u{e9}\":2}";
+    toon()
+        .args(["--encode", "--fail-on-lossy", "--nfc"])
+        .write_stdin(input)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "key collides with a sibling under NFC",
+        ));
+    toon()
+        .args(["--encode", "--fail-on-lossy"])
+        .write_stdin(input)
+        .assert()
+        .success();
+}
+
+#[test]
+fn sixty_four_bit_integers_round_trip_exactly() {
+    let json = r#"{"id":9007199254740993,"min":-9223372036854775808,"max":18446744073709551615}"#;
+    let encoded = toon()
+        .args(["--encode", "--fail-on-lossy"])
+        .write_stdin(json)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    assert_eq!(
+        String::from_utf8_lossy(&encoded),
+        "id: 9007199254740993\nmin: -9223372036854775808\nmax: 18446744073709551615\n"
+    );
+    toon()
+        .args(["--decode", "--indent", "0"])
+        .write_stdin(encoded)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(json));
+}
+
+#[test]
+fn decode_delimiter_sets_default_for_headers_without_marker() {
+    toon()
+        .args(["--decode", "--delimiter", "|", "--indent", "0"])
+        .write_stdin("tags[3]: a|b|c\nids[2\t]: 1\t2")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            r#"{"tags":["a","b","c"],"ids":[1,2]}"#,
+        ));
+}
+
+#[test]
+fn decode_lossless_numbers_keeps_numbers_as_written() {
+    let toon_input = "price: 1.230\nbig: 1e10\nzero: -0\nids[2]: 1.0,2E+2";
+
+    toon()
+        .args(["--decode", "--lossless-numbers", "--indent", "0"])
+        .write_stdin(toon_input)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            r#"{"price":1.230,"big":1e10,"zero":-0,"ids":[1.0,2E+2]}"#,
+        ));
+    toon()
+        .args(["--decode", "--indent", "0"])
+        .write_stdin(toon_input)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            r#"{"price":1.23,"big":10000000000.0,"zero":0,"ids":[1.0,200.0]}"#,
+        ));
+}
+
 // ============================================================================
 // Decode Tests (TOON -> JSON)
 // ============================================================================
@@ -273,6 +384,38 @@ fn decode_with_no_strict_allows_invalid_indentation() {
         .success();
 }
 
+#[test]
+fn decode_fail_on_lossy_rejects_duplicate_keys() {
+    toon()
+        .args(["--decode", "--fail-on-lossy"])
+        .write_stdin("a: 1\na: 2")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("a: duplicate key collapsed"));
+}
+
+#[test]
+#[cfg(not(feature = "arbitrary-precision"))]
+fn decode_fail_on_lossy_rejects_inexact_decimals() {
+    toon()
+        .args(["--decode", "--fail-on-lossy"])
+        .write_stdin("x: 0.12345678901234567890123\ny: 1.00000000000000000001\nz: 0.5")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Lossy conversion at 2 site(s)"))
+        .stderr(predicate::str::contains(
+            "x: number loses precision as f64 (0.12345678901234567890123)",
+        ))
+        .stderr(predicate::str::contains("y: number loses precision"));
+
+    toon()
+        .args(["--decode", "--fail-on-lossy", "--lossless-numbers"])
+        .write_stdin("x: 0.12345678901234567890123")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("0.12345678901234567890123"));
+}
+
 // ============================================================================
 // Mode Auto-Detection Tests
 // ============================================================================
@@ -575,6 +718,27 @@ fn stdin_dash_argument() {
         .stdout(predicate::str::contains("stdin: dash"));
 }
 
+#[test]
+fn stdin_format_is_detected_without_mode_flags() {
+    toon()
+        .write_stdin("id: 7\ntags[2]: a,b\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"tags\": ["));
+    toon()
+        .arg("-")
+        .write_stdin(r#"{"id": 7}"#)
+        .assert()
+        .success()
+        .stdout("id: 7\n");
+    // An explicit flag wins over the content.
+    toon()
+        .arg("--encode")
+        .write_stdin("id: 7\n")
+        .assert()
+        .failure();
+}
+
 // ============================================================================
 // Help and Version
 // ============================================================================
@@ -599,3 +763,1351 @@ fn version_flag_shows_version() {
         .success()
         .stdout(predicate::str::contains("toon"));
 }
+
+#[test]
+fn typed_headers_flag_round_trips() {
+    let output = toon()
+        .args(["--encode", "--typed-headers"])
+        .write_stdin(r#"{"rows":[{"zip":"02134","n":1}]}"#)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("rows[1]{zip:str,n:int}:"))
+        .get_output()
+        .stdout
+        .clone();
+
+    toon()
+        .args(["--decode", "--typed-headers"])
+        .write_stdin(output)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"zip\": \"02134\""));
+}
+
+#[test]
+fn columnar_flag_round_trips() {
+    let output = toon()
+        .args(["--encode", "--columnar"])
+        .write_stdin(r#"{"rows":[{"id":1,"ok":true},{"id":2,"ok":false}]}"#)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("id[2]: 1,2"))
+        .get_output()
+        .stdout
+        .clone();
+
+    toon()
+        .args(["--decode", "--columnar"])
+        .write_stdin(output)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"ok\": false"));
+}
+
+#[test]
+fn align_columns_flag_pads_tabular_cells() {
+    let output = toon()
+        .args(["--encode", "--align-columns"])
+        .write_stdin(r#"{"rows":[{"id":1,"name":"Ada"},{"id":250,"name":"Bob"}]}"#)
+        .assert()
+        .success()
+        .stdout("rows[2]{id,name}:\n  1  ,Ada\n  250,Bob\n")
+        .get_output()
+        .stdout
+        .clone();
+
+    toon()
+        .arg("--decode")
+        .write_stdin(output)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"name\": \"Ada\""));
+}
+
+#[test]
+fn invisible_characters_are_quoted_or_rejected() {
+    toon()
+        .args(["--encode", "--nfc"])
+        .write_stdin("{\"user\":\"admin\\u200b\",\"name\":\"Jose\\u0301\"}")
+        .assert()
+        .success()
+        .stdout("user: \"admin\u{200B}\"\nname: Jos\u{00E9}\n");
+
+    toon()
+        .args(["--encode", "--reject-invisible"])
+        .write_stdin("{\"user\":\"admin\\u200b\"}")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("U+200B"));
+}
+
+#[test]
+fn always_mark_delimiter_flag_marks_comma_headers() {
+    let output = toon()
+        .args(["--encode", "--always-mark-delimiter"])
+        .write_stdin(r#"{"tags":["a","b"],"rows":[{"id":1}]}"#)
+        .assert()
+        .success()
+        .stdout("tags[2,]: a,b\nrows[1,]{id}:\n  1\n")
+        .get_output()
+        .stdout
+        .clone();
+
+    toon()
+        .arg("--decode")
+        .write_stdin(output)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"b\""));
+}
+
+#[test]
+fn sparse_threshold_flag_writes_index_lines() {
+    toon()
+        .args(["--encode", "--sparse-threshold", "0.6"])
+        .write_stdin(r#"{"v":[null,null,7,null]}"#)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("v[4]:\n  2: 7"));
+}
+
+#[test]
+fn run_length_flag_round_trips() {
+    let output = toon()
+        .args(["--encode", "--run-length"])
+        .write_stdin(r#"{"v":[7,7,7,7,1]}"#)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("v[5]: 7*4,1"))
+        .get_output()
+        .stdout
+        .clone();
+
+    let decoded = toon()
+        .args(["--decode", "--run-length"])
+        .write_stdin(output)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let value: serde_json::Value = serde_json::from_slice(&decoded).unwrap();
+    assert_eq!(value, serde_json::json!({"v": [7, 7, 7, 7, 1]}));
+}
+
+#[test]
+fn max_input_bytes_rejects_large_input() {
+    toon()
+        .args(["--encode", "--max-input-bytes", "8"])
+        .write_stdin(r#"{"name":"too long"}"#)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "input bytes limit exceeded: 9 > 8",
+        ));
+
+    let tmp = TempDir::new().unwrap();
+    let input = tmp.path().join("input.json");
+    fs::write(&input, r#"{"name":"too long"}"#).unwrap();
+    toon()
+        .arg(&input)
+        .args(["--max-input-bytes", "8"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "input bytes limit exceeded: 19 > 8",
+        ));
+}
+
+#[test]
+fn max_output_bytes_aborts_before_writing() {
+    let tmp = TempDir::new().unwrap();
+    let output = tmp.path().join("output.toon");
+    toon()
+        .args(["--encode", "--max-output-bytes", "10", "-o"])
+        .arg(&output)
+        .write_stdin(r#"{"name":"Alice","age":30}"#)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "output bytes limit exceeded: 20 > 10",
+        ));
+    assert!(!output.exists());
+
+    toon()
+        .args(["--encode", "--max-output-bytes", "20"])
+        .write_stdin(r#"{"name":"Alice","age":30}"#)
+        .assert()
+        .success();
+}
+
+#[test]
+fn output_file_is_replaced_only_on_success() {
+    let tmp = TempDir::new().unwrap();
+    let output = tmp.path().join("output.toon");
+    fs::write(&output, "previous: 1\n").unwrap();
+
+    toon()
+        .args(["--encode", "--max-output-bytes", "10", "-o"])
+        .arg(&output)
+        .write_stdin(r#"{"name":"Alice","age":30}"#)
+        .assert()
+        .failure();
+    assert_eq!(fs::read_to_string(&output).unwrap(), "previous: 1\n");
+
+    toon()
+        .args(["--encode", "-o"])
+        .arg(&output)
+        .write_stdin(r#"{"name":"Alice"}"#)
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&output).unwrap(), "name: Alice\n");
+    let entries = fs::read_dir(tmp.path()).unwrap().count();
+    assert_eq!(entries, 1, "temporary output file left behind");
+}
+
+#[cfg(unix)]
+#[test]
+fn sigint_exits_with_interrupted_code() {
+    use std::process::{Command as StdCommand, Stdio};
+    use std::thread;
+    use std::time::Duration;
+
+    // stdin stays open, so the CLI is still waiting for input when Ctrl-C arrives
+    let child = StdCommand::new(env!("CARGO_BIN_EXE_toon"))
+        .arg("--encode")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(500));
+
+    let status = StdCommand::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(130));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Interrupted"));
+}
+
+#[test]
+fn explain_subcommand_annotates_document() {
+    let temp = TempDir::new().unwrap();
+    let input = temp.path().join("data.toon");
+    fs::write(&input, "users[2]{id,name}:\n  1,Ada\n  2,Bob\n").unwrap();
+
+    toon()
+        .arg("explain")
+        .arg(&input)
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "# document: object with 1 key · ",
+        ))
+        .stdout(predicate::str::contains(
+            "# 2 rows × 2 fields (id: number, name: string) · ",
+        ))
+        .stdout(predicate::str::contains("  2,Bob\n"));
+
+    toon()
+        .args(["explain", "-"])
+        .write_stdin("items[3]: a,b")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn debug_decode_subcommand_traces_lines() {
+    toon()
+        .arg("debug-decode")
+        .write_stdin("a:\n  b: 1\nc[1]: x")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "line 1 depth 0: a:\n  event {\"type\":\"startObject\"}\n",
+        ))
+        .stdout(predicate::str::contains("line 2 depth 1 (from 0): b: 1\n"))
+        .stdout(predicate::str::contains(
+            "line 3 depth 0 (from 1): c[1]: x\n",
+        ))
+        .stdout(predicate::str::ends_with(
+            "  event {\"type\":\"endObject\"}\n  check inline array items: expected 1, found 1\n",
+        ));
+
+    toon()
+        .arg("debug-decode")
+        .write_stdin("items[3]: a,b")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "check inline array items: expected 3, found 2",
+        ))
+        .stderr(predicate::str::contains(
+            "Expected 3 inline array items, but got 2",
+        ));
+}
+
+#[test]
+fn tokens_subcommand_estimates_and_lexes() {
+    toon()
+        .arg("tokens")
+        .write_stdin("name: Alice")
+        .assert()
+        .success()
+        .stdout("2\n");
+
+    toon()
+        .args(["tokens", "--syntax"])
+        .write_stdin("name: Alice")
+        .assert()
+        .success()
+        .stdout(concat!(
+            r#"{"kind":"key","start":0,"end":4,"text":"name"}"#,
+            "\n",
+            r#"{"kind":"colon","start":4,"end":5,"text":":"}"#,
+            "\n",
+            r#"{"kind":"value","start":6,"end":11,"text":"Alice"}"#,
+            "\n",
+        ));
+
+    let temp = TempDir::new().unwrap();
+    let samples = temp.path().join("samples.json");
+    fs::write(&samples, r#"[{"text": "name: Alice", "tokens": 4}]"#).unwrap();
+    toon()
+        .args(["tokens", "--token-samples"])
+        .arg(&samples)
+        .write_stdin("name: Alice")
+        .assert()
+        .success()
+        .stdout("4\n");
+
+    fs::write(&samples, r#"[{"text": "name: Alice"}]"#).unwrap();
+    toon()
+        .args(["tokens", "--token-samples"])
+        .arg(&samples)
+        .write_stdin("name: Alice")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("sample 0 needs"));
+}
+
+#[test]
+fn lint_flag_warns_on_stderr_without_changing_output() {
+    let input = r#"{"a": {"b": {"c": 1}}}"#;
+    toon()
+        .args(["--encode", "--lint"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("a:\n  b:\n    c: 1\n")
+        .stderr(predicate::str::contains(
+            "warning: a: foldable key chain (3 nested single-key objects)",
+        ));
+
+    toon()
+        .args(["--encode", "--lint", "--key-folding", "safe"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stderr("");
+}
+
+#[test]
+fn stats_token_estimates_use_calibration_samples() {
+    let temp = TempDir::new().unwrap();
+    let samples = temp.path().join("samples.json");
+    fs::write(&samples, r#"[{"text": "abcdef", "tokens": 3}]"#).unwrap();
+
+    toon()
+        .args(["--encode", "--stats", "--token-samples"])
+        .arg(&samples)
+        .write_stdin(r#"{"key": "value"}"#)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Token estimates: ~7 (JSON) → ~6 (TOON)",
+        ));
+}
+
+#[test]
+fn pedantic_flag_rejects_non_canonical_toon() {
+    toon()
+        .args(["--decode", "--pedantic"])
+        .write_stdin("name:  Alice\n")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "line 1, column 6: expected a single space after ':'",
+        ));
+
+    toon()
+        .args(["--decode", "--pedantic"])
+        .write_stdin("name: Alice\n")
+        .assert()
+        .success();
+}
+
+#[test]
+fn merge3_subcommand_merges_and_reports_conflicts() {
+    let temp = TempDir::new().unwrap();
+    let base = temp.path().join("base.toon");
+    let ours = temp.path().join("ours.toon");
+    let theirs = temp.path().join("theirs.toon");
+    fs::write(&base, "a: 1\nb: 1\n").unwrap();
+    fs::write(&ours, "a: 2\nb: 1\n").unwrap();
+    fs::write(&theirs, "a: 1\nb: 2\n").unwrap();
+
+    toon()
+        .arg("merge3")
+        .args([&base, &ours, &theirs])
+        .assert()
+        .success()
+        .stdout("a: 2\nb: 2\n");
+
+    let output = temp.path().join("merged.json");
+    let ours_json = temp.path().join("ours.json");
+    fs::write(&ours_json, r#"{"a": 2, "b": 1}"#).unwrap();
+    toon()
+        .arg("merge3")
+        .args([&base, &ours_json, &theirs])
+        .arg("-o")
+        .arg(&output)
+        .assert()
+        .success();
+    let merged: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&output).unwrap()).unwrap();
+    assert_eq!(merged, serde_json::json!({"a": 2, "b": 2}));
+
+    fs::write(&theirs, "a: 3\nb: 1\n").unwrap();
+    toon()
+        .arg("merge3")
+        .args([&base, &ours, &theirs])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "CONFLICT a: modified on both sides",
+        ))
+        .stderr(predicate::str::contains("  theirs: 3"));
+}
+
+#[test]
+fn git_drivers_use_git_argument_conventions() {
+    let temp = TempDir::new().unwrap();
+    let base = temp.path().join("base");
+    let current = temp.path().join("current");
+    let other = temp.path().join("other");
+    fs::write(&base, "a: 1\nb: 1\n").unwrap();
+    fs::write(&current, "a: 2\nb: 1\n").unwrap();
+    fs::write(&other, "b: 1\na: 1\nc: 3\n").unwrap();
+
+    toon()
+        .arg("git-diff")
+        .arg("config.toon")
+        .arg(&base)
+        .args(["1111111", "100644"])
+        .arg(&current)
+        .args(["2222222", "100644"])
+        .assert()
+        .success()
+        .stdout("diff --toon a/config.toon b/config.toon\n~ a: 1 -> 2\n");
+
+    toon()
+        .arg("git-merge")
+        .args([&base, &current, &other])
+        .args(["7", "config.toon"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&current).unwrap(), "a: 2\nb: 1\nc: 3\n");
+
+    fs::write(&other, "a: 3\nb: 1\n").unwrap();
+    toon()
+        .arg("git-merge")
+        .args([&base, &current, &other])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "CONFLICT a: modified on both sides",
+        ));
+    assert_eq!(
+        fs::read_to_string(&current).unwrap(),
+        "<<<<<<< ours\na: 2\nb: 1\nc: 3\n=======\na: 3\nb: 1\n>>>>>>> theirs\n"
+    );
+}
+
+#[test]
+fn manifest_subcommand_lists_converted_files() {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir(temp.path().join("nested")).unwrap();
+    fs::write(temp.path().join("b.toon"), "a: 1\n").unwrap();
+    fs::write(temp.path().join("nested/a.json"), r#"{"a": 1}"#).unwrap();
+    fs::write(temp.path().join("notes.txt"), "skipped").unwrap();
+
+    let output = toon()
+        .arg("manifest")
+        .arg(temp.path())
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let manifest: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    let files = manifest["files"].as_array().unwrap();
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0]["path"], "b.toon");
+    assert_eq!(files[1]["path"], "nested/a.json");
+    assert_eq!(files[0]["bytes"], 5.0);
+    assert_eq!(files[0]["sha256"], files[1]["sha256"]);
+    assert_eq!(manifest["totals"]["files"], 2.0);
+
+    toon()
+        .args(["manifest", "--format", "toon"])
+        .arg(temp.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "files[2]{path,bytes,sha256,tokens}:\n  b.toon,5,",
+        ));
+}
+
+#[test]
+fn frontmatter_subcommand_reads_and_replaces_data() {
+    let temp = TempDir::new().unwrap();
+    let doc = temp.path().join("prompt.md");
+    fs::write(&doc, "---toon\nmodel: small\ntags[2]: a,b\n---\n# Prompt\n").unwrap();
+
+    toon()
+        .arg("frontmatter")
+        .arg(&doc)
+        .assert()
+        .success()
+        .stdout("model: small\ntags[2]: a,b\n");
+
+    let vars = temp.path().join("vars.json");
+    fs::write(&vars, r#"{"model": "large"}"#).unwrap();
+    toon()
+        .arg("frontmatter")
+        .arg(&doc)
+        .arg("--set")
+        .arg(&vars)
+        .arg("-o")
+        .arg(&doc)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(&doc).unwrap(),
+        "---toon\nmodel: large\n---\n# Prompt\n"
+    );
+
+    fs::write(&doc, "# No data\n").unwrap();
+    toon()
+        .arg("frontmatter")
+        .arg(&doc)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no ---toon frontmatter"));
+}
+
+#[test]
+fn directive_flag_writes_self_describing_output() {
+    let temp = TempDir::new().unwrap();
+    let output = temp.path().join("out.toon");
+    toon()
+        .args(["--encode", "--directive", "--indent", "4", "-o"])
+        .arg(&output)
+        .write_stdin(r#"{"a": {"b": 1}}"#)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(&output).unwrap(),
+        "#%toon version=1 delimiter=, indent=4\na:\n    b: 1\n"
+    );
+
+    toon()
+        .arg(&output)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"b\": 1"));
+}
+
+#[test]
+fn anchors_flag_aliases_repeated_blocks() {
+    let output = toon()
+        .args(["--encode", "--anchors"])
+        .write_stdin(r#"{"a": {"x": 1, "y": 2}, "b": {"x": 1, "y": 2}}"#)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let toon_text = String::from_utf8(output).unwrap();
+    assert_eq!(toon_text, "a: &a1\n  x: 1\n  y: 2\nb: *a1\n");
+
+    toon()
+        .args(["--decode", "--anchors"])
+        .write_stdin(toon_text)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"b\": {\n    \"x\": 1"));
+}
+
+#[test]
+fn allow_includes_resolves_paths_relative_to_input() {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir(temp.path().join("parts")).unwrap();
+    fs::write(temp.path().join("parts/persona.toon"), "name: Ada\n").unwrap();
+    let input = temp.path().join("prompt.toon");
+    fs::write(&input, "persona: !include parts/persona.toon\n").unwrap();
+
+    toon()
+        .arg(&input)
+        .arg("--allow-includes")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"name\": \"Ada\""));
+
+    toon()
+        .arg(&input)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"!include parts/persona.toon\""));
+}
+
+#[test]
+fn include_root_refuses_includes_that_escape_it() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path().join("root");
+    fs::create_dir(&root).unwrap();
+    fs::write(temp.path().join("secret.toon"), "token: hunter2\n").unwrap();
+    fs::write(root.join("part.toon"), "name: Ada\n").unwrap();
+    let input = root.join("prompt.toon");
+
+    fs::write(&input, "persona: !include part.toon\n").unwrap();
+    toon()
+        .arg(&input)
+        .args(["--allow-includes", "--include-root"])
+        .arg(&root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"name\": \"Ada\""));
+
+    fs::write(&input, "persona: !include ../secret.toon\n").unwrap();
+    toon()
+        .arg(&input)
+        .args(["--allow-includes", "--include-root"])
+        .arg(&root)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("outside"))
+        .stdout(predicate::str::contains("hunter2").not());
+}
+
+#[test]
+#[allow(clippy::literal_string_with_formatting_args)]
+fn interpolate_env_reads_environment_variables() {
+    toon()
+        .args(["--decode", "--interpolate-env"])
+        .env("TOON_TEST_HOST", "db.internal")
+        .write_stdin("host: ${TOON_TEST_HOST}\nport: ${TOON_TEST_PORT:-5432}\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"host\": \"db.internal\""))
+        .stdout(predicate::str::contains("\"port\": \"5432\""));
+
+    toon()
+        .args(["--decode", "--interpolate-env"])
+        .env_remove("TOON_TEST_UNSET")
+        .write_stdin("host: ${TOON_TEST_UNSET}\n")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Undefined variable ${TOON_TEST_UNSET}",
+        ));
+}
+
+#[test]
+#[allow(clippy::literal_string_with_formatting_args)]
+fn render_fills_placeholders_from_vars() {
+    let temp = TempDir::new().unwrap();
+    let vars = temp.path().join("vars.json");
+    fs::write(&vars, r#"{"user": {"name": "Ada"}, "limit": 5}"#).unwrap();
+    let template = temp.path().join("prompt.toon");
+    fs::write(
+        &template,
+        "greeting: \"Hello, ${user.name}!\"\nlimit: ${limit}\ntone: ${tone:-neutral}\n",
+    )
+    .unwrap();
+
+    toon()
+        .arg("render")
+        .arg(&template)
+        .arg("--vars")
+        .arg(&vars)
+        .assert()
+        .success()
+        .stdout("greeting: \"Hello, Ada!\"\nlimit: \"5\"\ntone: neutral\n");
+
+    toon()
+        .arg("render")
+        .arg("--vars")
+        .arg(&vars)
+        .arg("--json")
+        .write_stdin("missing: ${user.email}\n")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Undefined variable ${user.email}"));
+}
+
+#[test]
+fn stats_reports_shape_of_toon_and_json() {
+    let temp = TempDir::new().unwrap();
+    let toon_path = temp.path().join("data.toon");
+    fs::write(&toon_path, "users[2]{id,name}:\n  1,Ada\n  2,Bob\n").unwrap();
+    let json_path = temp.path().join("data.json");
+    fs::write(
+        &json_path,
+        r#"{"users": [{"id": 1, "name": "Ada"}, {"id": 2, "name": "Bob"}]}"#,
+    )
+    .unwrap();
+
+    for args in [
+        vec![toon_path.as_os_str()],
+        vec!["--streaming".as_ref(), toon_path.as_os_str()],
+        vec![json_path.as_os_str()],
+    ] {
+        toon()
+            .arg("stats")
+            .args(args)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("maxDepth: 3\n"))
+            .stdout(predicate::str::contains("keys: 5\n"))
+            .stdout(predicate::str::contains("maxArrayLength: 2\n"));
+    }
+
+    toon()
+        .args(["stats", "--streaming", "--json"])
+        .write_stdin("a: 1\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"numbers\": 1"));
+
+    toon()
+        .args(["stats", "--streaming"])
+        .arg(&json_path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--streaming reads TOON input only",
+        ));
+}
+
+#[test]
+fn viz_draws_structure_of_toon_and_json() {
+    let temp = TempDir::new().unwrap();
+    let toon_path = temp.path().join("data.toon");
+    fs::write(&toon_path, "users[2]{id,name}:\n  1,Ada\n  2,Bob\n").unwrap();
+    let json_path = temp.path().join("data.json");
+    fs::write(&json_path, r#"{"users": [{"id": 1, "name": "Ada"}]}"#).unwrap();
+
+    toon()
+        .arg("viz")
+        .arg(&toon_path)
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("digraph toon {"))
+        .stdout(predicate::str::contains("users[2]{id,name}"))
+        .stdout(predicate::str::contains("Ada").not());
+
+    let out = temp.path().join("schema.mmd");
+    toon()
+        .args(["viz", "--format", "mermaid", "-o"])
+        .arg(&out)
+        .arg(&json_path)
+        .assert()
+        .success()
+        .stdout("");
+    let diagram = fs::read_to_string(&out).unwrap();
+    assert!(diagram.starts_with("flowchart TD\n"), "{diagram}");
+    assert!(diagram.contains("users[1]{id,name}"), "{diagram}");
+}
+
+#[test]
+fn pipe_detects_input_format_and_transforms() {
+    toon()
+        .args(["pipe", "--to", "toon", "--key-folding", "safe"])
+        .write_stdin(r#"{"a": {"b": {"c": 1}}, "xs": [1, 2]}"#)
+        .assert()
+        .success()
+        .stdout("a.b.c: 1\nxs[2]: 1,2\n");
+
+    let output = toon()
+        .args(["pipe", "--to", "json", "--expand-paths", "safe"])
+        .write_stdin("a.b: 1\nc: x\n")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json, serde_json::json!({"a": {"b": 1}, "c": "x"}));
+
+    // A TOON root array is not mistaken for JSON, and output defaults to
+    // the input format.
+    toon()
+        .arg("pipe")
+        .write_stdin("[2]: 1,2\n")
+        .assert()
+        .success()
+        .stdout("[2]: 1,2\n");
+
+    let temp = TempDir::new().unwrap();
+    let rules = temp.path().join("rules.toml");
+    fs::write(
+        &rules,
+        "[[rules]]\npath = \"users.password\"\ndrop = true\n",
+    )
+    .unwrap();
+    let out = temp.path().join("out.toon");
+    toon()
+        .args(["pipe", "--to", "toon", "--transform"])
+        .arg(&rules)
+        .arg("-o")
+        .arg(&out)
+        .write_stdin("users[1]{id,password}:\n  1,x\n")
+        .assert()
+        .success()
+        .stdout("");
+    assert_eq!(fs::read_to_string(&out).unwrap(), "users[1]{id}:\n  1\n");
+}
+
+#[test]
+fn also_json_writes_normalized_json_next_to_toon() {
+    let temp = TempDir::new().unwrap();
+    let input = temp.path().join("data.json");
+    fs::write(&input, r#"{"id": 7, "tags": ["a", "b"], "zero": -0}"#).unwrap();
+    let toon_path = temp.path().join("out.toon");
+    let json_path = temp.path().join("out.json");
+
+    toon()
+        .arg(&input)
+        .arg("-o")
+        .arg(&toon_path)
+        .arg("--also-json")
+        .arg(&json_path)
+        .assert()
+        .success();
+
+    assert_eq!(
+        fs::read_to_string(&toon_path).unwrap(),
+        "id: 7\ntags[2]: a,b\nzero: 0\n"
+    );
+    let json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&json_path).unwrap()).unwrap();
+    // With arbitrary precision, `-0` keeps its integer spelling.
+    #[cfg(feature = "arbitrary-precision")]
+    let zero = serde_json::json!(0);
+    #[cfg(not(feature = "arbitrary-precision"))]
+    let zero = serde_json::json!(0.0);
+    assert_eq!(
+        json,
+        serde_json::json!({"id": 7, "tags": ["a", "b"], "zero": zero})
+    );
+}
+
+#[test]
+fn audit_determinism_reports_identical_runs() {
+    let temp = TempDir::new().unwrap();
+    let input = temp.path().join("data.json");
+    fs::write(&input, r#"{"users": [{"id": 1, "name": "Ada"}]}"#).unwrap();
+
+    toon()
+        .arg("audit-determinism")
+        .arg(&input)
+        .args(["--runs", "3", "--threads", "2"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Deterministic: 6 runs"));
+
+    toon()
+        .arg("audit-determinism")
+        .arg(&input)
+        .args(["--runs", "0"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn bench_prints_percentiles_and_rates() {
+    let temp = TempDir::new().unwrap();
+    let input = temp.path().join("data.json");
+    fs::write(&input, r#"{"users": [{"id": 1, "name": "Ada"}]}"#).unwrap();
+
+    toon()
+        .arg("bench")
+        .arg("--input")
+        .arg(&input)
+        .args(["--iterations", "3", "--delimiter", "|"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("3 iterations"))
+        .stdout(predicate::str::contains("events/s"))
+        .stdout(predicate::str::contains("encode"))
+        .stdout(predicate::str::contains("decode"));
+
+    toon()
+        .arg("bench")
+        .arg("--input")
+        .arg(&input)
+        .args(["--iterations", "0"])
+        .assert()
+        .failure();
+}
+
+// ============================================================================
+// JSON-RPC Mode
+// ============================================================================
+
+#[test]
+fn jsonrpc_answers_requests_in_order() {
+    let requests = [
+        r#"{"jsonrpc":"2.0","id":1,"method":"encode","params":{"value":{"a":[1,2]},"options":{"delimiter":"|"}}}"#,
+        r#"{"jsonrpc":"2.0","method":"encode","params":{"value":1}}"#,
+        r#"{"jsonrpc":"2.0","id":2,"method":"decode","params":{"toon":"name: Ada"}}"#,
+        r#"{"jsonrpc":"2.0","id":3,"method":"stats","params":{"toon":"a:\n  b: 1"}}"#,
+        r#"{"jsonrpc":"2.0","id":4,"method":"decode","params":{"toon":"rows[2]: 1"}}"#,
+        r#"{"jsonrpc":"2.0","id":5,"method":"nope"}"#,
+        "not json",
+    ];
+    let output = toon()
+        .arg("--jsonrpc")
+        .write_stdin(requests.join("\n"))
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let replies: Vec<serde_json::Value> = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    assert_eq!(replies.len(), 6);
+    assert_eq!(replies[0]["id"], 1);
+    assert_eq!(replies[0]["result"]["toon"], "a[2|]: 1|2");
+    assert_eq!(replies[1]["result"]["value"]["name"], "Ada");
+    assert_eq!(replies[2]["result"]["maxDepth"], 2.0);
+    assert_eq!(replies[3]["error"]["code"], -32000);
+    assert_eq!(replies[4]["error"]["code"], -32601);
+    assert_eq!(replies[5]["error"]["code"], -32700);
+    assert!(replies[5]["id"].is_null());
+}
+
+#[test]
+fn jsonrpc_jobs_reply_in_request_order() {
+    let requests: Vec<String> = (0..100)
+        .map(|id| {
+            if id % 10 == 9 {
+                format!(r#"{{"jsonrpc":"2.0","method":"encode","params":{{"value":{id}}}}}"#)
+            } else {
+                format!(
+                    r#"{{"jsonrpc":"2.0","id":{id},"method":"decode","params":{{"toon":"rows[{id}]: {}"}}}}"#,
+                    vec!["x"; id].join(",")
+                )
+            }
+        })
+        .collect();
+    let run = |jobs: &str| {
+        toon()
+            .args(["--jsonrpc", "--jobs", jobs])
+            .write_stdin(requests.join("\n"))
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone()
+    };
+    let serial = run("1");
+    assert_eq!(String::from_utf8_lossy(&serial).lines().count(), 90);
+    assert_eq!(run("4"), serial);
+    assert_eq!(run("0"), serial);
+
+    for args in [&["--jobs", "4"][..], &["--jobs", "4", "input.json"]] {
+        toon()
+            .args(args)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("--jobs"));
+    }
+}
+
+// ============================================================================
+// Option Profiles
+// ============================================================================
+
+#[test]
+fn profile_sets_options_and_flags_override_it() {
+    let input = r#"{"a":{"b":[1,2]},"rows":[{"k":1,"v":""}]}"#;
+    toon()
+        .args(["--encode", "--profile", "llm-compact"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("a.b[2\t]: 1\t2\nrows[1\t]{k\tv}:\n  1\t\"\"\n");
+
+    toon()
+        .args(["--encode", "--profile", "llm-compact", "--delimiter", "|"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("a.b[2|]: 1|2\n"));
+
+    // A flag naming the default still overrides the profile.
+    toon()
+        .args(["--encode", "--profile", "llm-compact", "--delimiter", ","])
+        .args(["--key-folding", "off"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("a:\n  b[2]: 1,2\nrows[1]{k,v}:\n  1,\"\"\n");
+    toon()
+        .args(["--encode", "--profile", "human-readable", "--indent", "2"])
+        .write_stdin(r#"{"a":{"b":1}}"#)
+        .assert()
+        .success()
+        .stdout(predicate::str::ends_with("\na:\n  b: 1\n"));
+
+    toon()
+        .args(["--decode", "--profile", "repair"])
+        .write_stdin("rows[2]{a,b}:\n\t1,\n  3")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"b\": null").count(2));
+
+    toon()
+        .args(["--decode", "--profile", "canonical"])
+        .write_stdin("a: 1")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--profile canonical applies to encoding only",
+        ));
+}
+
+// ============================================================================
+// Capabilities
+// ============================================================================
+
+#[test]
+fn capabilities_describe_the_build() {
+    toon()
+        .arg("--capabilities")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("spec: 3.0\n"))
+        .stdout(predicate::str::contains("features: cli"));
+
+    let output = toon()
+        .args(["--capabilities", "--json"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let capabilities: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(capabilities["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(capabilities["defaultEncodeOptions"]["delimiter"], ",");
+    assert_eq!(capabilities["defaultDecodeOptions"]["strict"], true);
+
+    toon()
+        .args(["--json", "data.json"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--capabilities"));
+}
+
+// ============================================================================
+// Column Projection
+// ============================================================================
+
+#[test]
+fn columns_projects_arrays_of_objects() {
+    let input = r#"{"users":[{"id":1,"name":"Ada","meta":{"x":1}},{"id":2,"name":"Bo","meta":{"x":2}}],"orders":[{"items":[{"sku":"a","qty":1,"note":"n"}]}]}"#;
+    toon()
+        .args([
+            "--encode",
+            "--columns",
+            "users=name,id",
+            "--columns",
+            "orders.items=sku",
+        ])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("users[2]{name,id}:\n  Ada,1\n  Bo,2\norders[1]:\n  - items[1]{sku}:\n      a\n");
+
+    toon()
+        .args(["--encode", "--columns", "users"])
+        .write_stdin(input)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Expected PATH=FIELDS"));
+}
+
+// ============================================================================
+// Transform Rules
+// ============================================================================
+
+#[test]
+fn transform_applies_rules_file() {
+    let temp = TempDir::new().unwrap();
+    let rules = temp.path().join("rules.toml");
+    fs::write(
+        &rules,
+        "[[rules]]\npath = \"users.password\"\ndrop = true\n\n[[rules]]\npath = \"users.email\"\nrename = \"contact\"\n",
+    )
+    .unwrap();
+    toon()
+        .arg("--encode")
+        .arg("--transform")
+        .arg(&rules)
+        .write_stdin(r#"{"users":[{"id":1,"password":"x","email":"a@x"}]}"#)
+        .assert()
+        .success()
+        .stdout("users[1]{id,contact}:\n  1,a@x\n");
+
+    fs::write(&rules, "[[rules]]\npath = \"a\"\nshout = true\n").unwrap();
+    toon()
+        .arg("--encode")
+        .arg("--transform")
+        .arg(&rules)
+        .write_stdin("{}")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid transform spec"));
+}
+
+// ============================================================================
+// Batch conversion
+// ============================================================================
+
+#[test]
+fn batch_converts_directory_and_reports_failures() {
+    let temp = TempDir::new().unwrap();
+    let data = temp.path().join("data");
+    fs::create_dir_all(&data).unwrap();
+    fs::write(data.join("a.json"), r#"{"ids":[1,2]}"#).unwrap();
+    fs::write(data.join("b.toon"), "name: Ada").unwrap();
+    let out = temp.path().join("out");
+
+    toon()
+        .arg("batch")
+        .arg(&data)
+        .arg("--out-dir")
+        .arg(&out)
+        .arg("--delimiter")
+        .arg("|")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("a.json ->"))
+        .stdout(predicate::str::contains("b.toon ->"));
+    assert_eq!(
+        fs::read_to_string(out.join("a.toon")).unwrap(),
+        "ids[2|]: 1|2\n"
+    );
+    assert!(
+        fs::read_to_string(out.join("b.json"))
+            .unwrap()
+            .contains("\"Ada\"")
+    );
+
+    fs::write(data.join("c.json"), "{").unwrap();
+    toon()
+        .arg("batch")
+        .arg(&data)
+        .arg("--out-dir")
+        .arg(&out)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("1 of 3 files failed to convert"))
+        .stderr(predicate::str::contains("c.json"));
+
+    toon()
+        .arg("batch")
+        .arg(&data)
+        .arg("--out-dir")
+        .arg(&out)
+        .args(["--jobs", "1"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("a.json ->"))
+        .stderr(predicate::str::contains("1 of 3 files failed to convert"));
+}
+
+#[test]
+fn batch_never_converts_inputs_over_each_other() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("x.json"), r#"{"price":1.50}"#).unwrap();
+    fs::write(temp.path().join("x.toon"), "name: Ada").unwrap();
+
+    toon()
+        .arg("batch")
+        .arg(temp.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("2 of 2 files failed to convert"))
+        .stderr(predicate::str::contains("is also an input of this batch"));
+    assert_eq!(
+        fs::read_to_string(temp.path().join("x.json")).unwrap(),
+        r#"{"price":1.50}"#
+    );
+    assert_eq!(
+        fs::read_to_string(temp.path().join("x.toon")).unwrap(),
+        "name: Ada"
+    );
+
+    // A differing file next to a lone input is replaced only on request.
+    fs::remove_file(temp.path().join("x.toon")).unwrap();
+    fs::write(temp.path().join("y.toon"), "stale: true\n").unwrap();
+    let input = temp.path().join("y.json");
+    fs::write(&input, r#"{"id":1}"#).unwrap();
+    toon()
+        .arg("batch")
+        .arg(&input)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("already exists"));
+    toon()
+        .args(["batch", "--overwrite"])
+        .arg(&input)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(temp.path().join("y.toon")).unwrap(),
+        "id: 1\n"
+    );
+}
+
+#[test]
+fn batch_refuses_two_inputs_with_one_output() {
+    let temp = TempDir::new().unwrap();
+    for dir in ["a", "b"] {
+        fs::create_dir(temp.path().join(dir)).unwrap();
+        fs::write(temp.path().join(dir).join("c.json"), r#"{"id":1}"#).unwrap();
+    }
+    let out = temp.path().join("out");
+
+    toon()
+        .arg("batch")
+        .arg(temp.path().join("a/c.json"))
+        .arg(temp.path().join("b/c.json"))
+        .arg("--out-dir")
+        .arg(&out)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("2 of 2 files failed to convert"))
+        .stderr(predicate::str::contains("is also the output of"));
+    assert!(!out.join("c.toon").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn batch_follow_symlinks_stays_inside_the_directory() {
+    use std::os::unix::fs::symlink;
+
+    let temp = TempDir::new().unwrap();
+    let data = temp.path().join("data");
+    fs::create_dir_all(data.join("real")).unwrap();
+    fs::write(data.join("real/a.json"), r#"{"id":1}"#).unwrap();
+    symlink(data.join("real"), data.join("linked")).unwrap();
+    let out = temp.path().join("out");
+
+    // Links are skipped by default, and followed once with the flag.
+    toon()
+        .args(["batch", "--dry-run"])
+        .arg(&data)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("real/a.json"))
+        .stdout(predicate::str::contains("linked").not());
+    toon()
+        .args(["batch", "--follow-symlinks", "--out-dir"])
+        .arg(&out)
+        .arg(&data)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("linked/a.json ->"))
+        .stdout(predicate::str::contains("real/a.json").not());
+
+    fs::write(temp.path().join("outside.json"), "{}").unwrap();
+    symlink(temp.path().join("outside.json"), data.join("escape.json")).unwrap();
+    toon()
+        .args(["batch", "--follow-symlinks"])
+        .arg(&data)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("outside"));
+    assert!(!temp.path().join("outside.toon").exists());
+}
+
+#[test]
+fn batch_dry_run_lists_actions_and_writes_nothing() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("a.json"), r#"{"ids":[1,2]}"#).unwrap();
+    fs::write(temp.path().join("b.json"), r#"{"id":1}"#).unwrap();
+    fs::write(temp.path().join("b.toon"), "id: 1\n").unwrap();
+
+    toon()
+        .args(["batch", "--dry-run"])
+        .arg(temp.path().join("a.json"))
+        .arg(temp.path().join("b.json"))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("convert   "))
+        .stdout(predicate::str::contains("a.toon (12 bytes)"))
+        .stdout(predicate::str::contains("b.toon (up to date)"))
+        .stdout(predicate::str::contains(
+            "dry run: 1 to convert, 0 to overwrite, 1 up to date; 12 bytes would be written",
+        ));
+    assert!(!temp.path().join("a.toon").exists());
+}
+
+#[test]
+fn batch_summary_reports_totals_on_stderr() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("a.json"), r#"{"ids":[1,2]}"#).unwrap();
+    fs::write(temp.path().join("b.json"), "{").unwrap();
+
+    toon()
+        .args(["batch", "--summary"])
+        .arg(temp.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "batch: 2 documents, 1 failed; 13 -> 11 bytes",
+        ))
+        .stderr(predicate::str::contains("documents/s"));
+
+    let output = toon()
+        .args(["batch", "--summary-json", "--out-dir"])
+        .arg(temp.path().join("out"))
+        .arg(temp.path().join("a.json"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let summary: serde_json::Value =
+        serde_json::from_slice(&output.stderr).expect("summary is JSON");
+    assert_eq!(summary["documents"], 1);
+    assert_eq!(summary["failures"], 0);
+    assert_eq!(summary["inputBytes"], 13);
+    assert!(summary["elapsedSeconds"].is_number());
+
+    toon()
+        .args(["batch", "--summary", "--dry-run"])
+        .arg(temp.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
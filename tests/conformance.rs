@@ -9,6 +9,7 @@ use std::path::{Path, PathBuf};
 
 use serde::Deserialize;
 
+#[allow(deprecated)]
 use toon::cli::json_stringify::json_stringify_lines;
 use toon::decode::decode;
 use toon::encode::encode;
@@ -41,6 +42,27 @@ struct FixtureCase {
     spec_section: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+struct OverrideFile {
+    overrides: Vec<ExpectedOverride>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExpectedOverride {
+    name: String,
+    expected: String,
+}
+
+/// Cases where this crate deliberately writes something else than the spec
+/// fixture expects, shared with `tests/encode_fixtures.rs`.
+fn expected_overrides() -> Vec<ExpectedOverride> {
+    let contents = fs::read_to_string("tests/fixtures/local/encode-overrides.json")
+        .expect("read encode overrides");
+    serde_json::from_str::<OverrideFile>(&contents)
+        .expect("parse encode overrides")
+        .overrides
+}
+
 #[test]
 fn conformance_encode_fixtures() {
     let root = fixture_root();
@@ -49,6 +71,7 @@ fn conformance_encode_fixtures() {
 
     assert!(!files.is_empty(), "no encode fixtures found");
 
+    let overrides = expected_overrides();
     let mut passed = 0;
     let mut failed = 0;
 
@@ -63,7 +86,12 @@ fn conformance_encode_fixtures() {
                 continue;
             }
 
-            if let Some(expected) = &case.expected {
+            let expected = overrides
+                .iter()
+                .find(|entry| entry.name == case.name)
+                .map(|entry| &entry.expected)
+                .or(case.expected.as_ref());
+            if let Some(expected) = expected {
                 if result == *expected {
                     passed += 1;
                 } else {
@@ -173,6 +201,7 @@ fn parse_encode_options(options: Option<&serde_json::Value>) -> Option<EncodeOpt
         key_folding,
         flatten_depth,
         replacer: None,
+        ..EncodeOptions::default()
     })
 }
 
@@ -191,9 +220,9 @@ fn parse_decode_options(options: Option<&serde_json::Value>) -> Option<DecodeOpt
         });
 
     Some(DecodeOptions {
-        indent: None,
         strict,
         expand_paths,
+        ..DecodeOptions::default()
     })
 }
 
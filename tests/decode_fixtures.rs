@@ -39,6 +39,21 @@ fn decode_spec_fixtures() {
     }
 }
 
+/// Cases this crate adds beyond the vendored spec fixtures, in the same
+/// format.
+#[test]
+fn decode_local_fixtures() {
+    let files = load_fixture_files(Path::new("tests/fixtures/local/decode"));
+
+    assert!(!files.is_empty(), "no local decode fixtures found");
+
+    for file in files {
+        for case in file.tests {
+            run_case(&case);
+        }
+    }
+}
+
 fn run_case(case: &FixtureCase) {
     let options = parse_decode_options(case.options.as_ref());
     let should_error = case.should_error.unwrap_or(false);
@@ -89,6 +104,7 @@ fn parse_decode_options(options: Option<&serde_json::Value>) -> Option<DecodeOpt
         indent,
         strict,
         expand_paths,
+        ..DecodeOptions::default()
     })
 }
 
@@ -99,6 +115,11 @@ fn json_value_to_serde(value: JsonValue) -> serde_json::Value {
             toon::StringOrNumberOrBoolOrNull::Bool(value) => serde_json::Value::Bool(value),
             toon::StringOrNumberOrBoolOrNull::Number(value) => serde_json::Number::from_f64(value)
                 .map_or(serde_json::Value::Null, serde_json::Value::Number),
+            toon::StringOrNumberOrBoolOrNull::Int(value) => serde_json::Value::from(value),
+            toon::StringOrNumberOrBoolOrNull::UInt(value) => serde_json::Value::from(value),
+            toon::StringOrNumberOrBoolOrNull::NumberLexeme(text) => {
+                serde_json::from_str(&text).unwrap_or(serde_json::Value::Null)
+            }
             toon::StringOrNumberOrBoolOrNull::String(value) => serde_json::Value::String(value),
         },
         JsonValue::Array(values) => {
@@ -111,6 +132,8 @@ fn json_value_to_serde(value: JsonValue) -> serde_json::Value {
             }
             serde_json::Value::Object(map)
         }
+        #[cfg(feature = "raw")]
+        JsonValue::Raw(text) => serde_json::Value::String(text),
     }
 }
 
@@ -154,9 +177,9 @@ fn json_eq(left: &serde_json::Value, right: &serde_json::Value) -> bool {
 fn expand_paths_respects_quoted_dotted_array_key() {
     let input = "\"a.b\"[1]: 1";
     let options = Some(DecodeOptions {
-        indent: None,
         strict: Some(true),
         expand_paths: Some(ExpandPathsMode::Safe),
+        ..DecodeOptions::default()
     });
 
     let value = decode(input, options);
@@ -170,9 +193,9 @@ fn expand_paths_respects_quoted_dotted_array_key() {
 fn expand_paths_respects_quoted_dotted_tabular_field() {
     let input = "items[1]{\"a.b\"}:\n  1";
     let options = Some(DecodeOptions {
-        indent: None,
         strict: Some(true),
         expand_paths: Some(ExpandPathsMode::Safe),
+        ..DecodeOptions::default()
     });
 
     let value = decode(input, options);
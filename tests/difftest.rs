@@ -0,0 +1,83 @@
+//! Differential testing harness tests.
+//!
+//! Run with: cargo test --features difftest
+
+#![cfg(all(feature = "difftest", feature = "cli"))]
+
+use toon::difftest::{
+    DiffDirection, DiffTestOptions, ReferenceCommand, generate_cases, run_difftest,
+};
+
+fn self_reference() -> ReferenceCommand {
+    ReferenceCommand::parse(env!("CARGO_BIN_EXE_toon")).unwrap()
+}
+
+#[test]
+fn generated_cases_are_deterministic_per_seed() {
+    let options = DiffTestOptions {
+        cases: 25,
+        seed: 42,
+        ..DiffTestOptions::default()
+    };
+    assert_eq!(generate_cases(&options), generate_cases(&options));
+
+    let other = DiffTestOptions {
+        seed: 43,
+        ..options
+    };
+    assert_ne!(generate_cases(&options), generate_cases(&other));
+}
+
+#[test]
+fn self_reference_has_no_divergences() {
+    let options = DiffTestOptions {
+        cases: 50,
+        ..DiffTestOptions::default()
+    };
+    let report = run_difftest(&self_reference(), &options).unwrap();
+    assert_eq!(report.cases_run, 50);
+    assert!(report.is_clean(), "{:?}", report.divergences);
+}
+
+#[test]
+fn self_reference_with_pipe_delimiter_has_no_divergences() {
+    let options = DiffTestOptions {
+        cases: 30,
+        seed: 7,
+        delimiter: '|',
+        indent: 4,
+        ..DiffTestOptions::default()
+    };
+    let report = run_difftest(&self_reference(), &options).unwrap();
+    assert!(report.is_clean(), "{:?}", report.divergences);
+}
+
+#[test]
+fn failing_reference_is_reported_per_case() {
+    let reference =
+        ReferenceCommand::parse(&format!("{} --no-such-flag", env!("CARGO_BIN_EXE_toon"))).unwrap();
+    let options = DiffTestOptions {
+        cases: 3,
+        ..DiffTestOptions::default()
+    };
+    let report = run_difftest(&reference, &options).unwrap();
+    assert_eq!(report.divergences.len(), 6);
+    assert!(
+        report
+            .divergences
+            .iter()
+            .any(|d| d.direction == DiffDirection::Decode)
+    );
+    assert!(report.divergences[0].reference.starts_with("error:"));
+}
+
+#[test]
+fn missing_reference_command_is_fatal() {
+    let reference = ReferenceCommand::parse("/nonexistent/toon-reference").unwrap();
+    assert!(run_difftest(&reference, &DiffTestOptions::default()).is_err());
+}
+
+#[test]
+fn empty_reference_command_is_rejected() {
+    assert!(ReferenceCommand::parse("   ").is_err());
+}
@@ -11,8 +11,17 @@
 //! - Key folding conflict scenarios
 
 use proptest::prelude::*;
-use toon::options::{DecodeOptions, EncodeOptions, ExpandPathsMode, KeyFoldingMode};
-use toon::{JsonValue, decode, encode, try_decode};
+use std::collections::HashMap;
+use std::sync::Arc;
+use toon::encode::columns::column_priority;
+use toon::options::{
+    ColumnType, DecodeOptions, DecodeProfile, EmptyCellMode, EmptyStringStyle, EncodeOptions,
+    ExpandPathsMode, KeyFoldingMode, LongStringPolicy, MissingCellMode, PathPattern, PathSegment,
+    SerdeKeyOrder, format_path,
+};
+use toon::{
+    JsonObject, JsonValue, decode, encode, encode_serde, try_decode, try_encode, try_encode_lines,
+};
 
 // ============================================================================
 // UNICODE EDGE CASES
@@ -79,6 +88,79 @@ fn unicode_combining_chars() {
     assert_eq!(json, decoded_json);
 }
 
+#[test]
+fn unicode_invisible_chars_are_quoted_or_rejected() {
+    let json = serde_json::json!({
+        "zwsp": "admin\u{200B}",
+        "override": "txt\u{202E}exe.sh",
+        "items": ["plain", "a\u{2066}b"],
+        "bom\u{FEFF}": 1
+    });
+    let toon = encode(json.clone(), None);
+    assert_eq!(
+        toon,
+        "zwsp: \"admin\u{200B}\"\noverride: \"txt\u{202E}exe.sh\"\nitems[2]: plain,\"a\u{2066}b\"\n\"bom\u{FEFF}\": 1"
+    );
+    assert_eq!(serde_json::Value::from(decode(&toon, None)), json);
+
+    let reject = EncodeOptions {
+        reject_invisible: Some(true),
+        ..Default::default()
+    };
+    let err = try_encode(json, Some(reject.clone())).unwrap_err();
+    assert!(err.to_string().contains("U+200B"), "{err}");
+    let err = try_encode(serde_json::json!({"a\u{200F}": "b"}), Some(reject.clone())).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Key contains invisible character U+200F"),
+        "{err}"
+    );
+    assert_eq!(
+        try_encode(serde_json::json!({"a": ["e\u{0301}"]}), Some(reject)).unwrap(),
+        "a[1]: e\u{0301}"
+    );
+}
+
+#[test]
+fn unicode_nfc_option_normalizes_keys_and_strings() {
+    let json = serde_json::json!({"cafe\u{0301}": ["e\u{0301}", "\u{00E9}"]});
+    let options = EncodeOptions {
+        nfc: Some(true),
+        ..Default::default()
+    };
+    assert_eq!(
+        try_encode(json.clone(), Some(options.clone())).unwrap(),
+        "\"caf\u{00E9}\"[2]: \u{00E9},\u{00E9}"
+    );
+    assert_eq!(
+        encode_serde(&json, Some(options)),
+        "\"caf\u{00E9}\"[2]: \u{00E9},\u{00E9}"
+    );
+    assert_eq!(
+        encode(json, None),
+        "\"cafe\u{0301}\"[2]: e\u{0301},\u{00E9}"
+    );
+}
+
+#[test]
+fn unicode_nfc_option_rejects_keys_it_would_merge() {
+    let json = serde_json::json!({"user": {"e\u{0301}": 1, "\u{00E9}": 2}});
+    let options = EncodeOptions {
+        nfc: Some(true),
+        ..Default::default()
+    };
+    let err = try_encode(json.clone(), Some(options.clone())).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Encode error at 'user.\u{00E9}': Key appears more than once in its object after NFC normalization"
+    );
+    assert!(toon::encode::try_encode_serde(&json, Some(options)).is_err());
+    assert_eq!(
+        encode(json, None),
+        "user:\n  \"e\u{0301}\": 1\n  \"\u{00E9}\": 2"
+    );
+}
+
 #[test]
 fn unicode_surrogate_pairs() {
     // Characters outside BMP (require surrogate pairs in UTF-16)
@@ -122,7 +204,7 @@ fn deeply_nested_arrays_100_levels() {
 
 #[test]
 fn deeply_nested_mixed_100_levels() {
-    let mut value: serde_json::Value = serde_json::json!(42.0);
+    let mut value: serde_json::Value = serde_json::json!(42);
     for i in 0..50 {
         if i % 2 == 0 {
             value = serde_json::json!({ "obj": value });
@@ -272,6 +354,278 @@ fn numeric_infinity_becomes_null() {
     assert!(decoded_json["neg_inf"].is_null());
 }
 
+#[test]
+fn numeric_64_bit_integers_round_trip_exactly() {
+    use toon::StringOrNumberOrBoolOrNull as P;
+
+    let json: serde_json::Value = serde_json::json!({
+        "above_f64": 9_007_199_254_740_993_u64,
+        "min": i64::MIN,
+        "max": u64::MAX,
+    });
+    let toon = encode(json.clone(), None);
+    assert_eq!(
+        toon,
+        "above_f64: 9007199254740993\nmin: -9223372036854775808\nmax: 18446744073709551615"
+    );
+    let decoded = decode(&toon, None);
+    assert_eq!(
+        decoded,
+        JsonValue::Object(vec![
+            (
+                "above_f64".to_string(),
+                JsonValue::from(9_007_199_254_740_993_i64)
+            ),
+            ("min".to_string(), JsonValue::Primitive(P::Int(i64::MIN))),
+            ("max".to_string(), JsonValue::Primitive(P::UInt(u64::MAX))),
+        ])
+    );
+    assert_eq!(serde_json::Value::from(decoded), json);
+
+    assert_eq!(decode("a: -0", None), decode("a: 0", None));
+    assert_eq!(
+        decode("a: 18446744073709551616", None),
+        JsonValue::Object(vec![(
+            "a".to_string(),
+            JsonValue::Primitive(P::Number(18_446_744_073_709_551_616.0))
+        )])
+    );
+}
+
+#[test]
+fn numeric_integers_compare_equal_to_equal_floats() {
+    use std::collections::HashSet;
+    use toon::StringOrNumberOrBoolOrNull as P;
+
+    assert_eq!(P::Int(1), P::Number(1.0));
+    assert_eq!(P::Int(0), P::Number(-0.0));
+    assert_ne!(
+        P::Int(9_007_199_254_740_993),
+        P::Number(9_007_199_254_740_992.0)
+    );
+    assert!(P::Int(9_007_199_254_740_993) > P::Number(9_007_199_254_740_992.0));
+    assert!(P::Int(i64::MAX) < P::UInt(u64::MAX));
+    assert!(P::Int(2) < P::Number(2.5));
+    assert!(P::UInt(u64::MAX) < P::Number(f64::INFINITY));
+
+    let unique: HashSet<_> = [P::Int(1), P::Number(1.0), P::from(1_u8), P::from_u64(1)]
+        .into_iter()
+        .collect();
+    assert_eq!(unique.len(), 1);
+    assert_eq!(P::from_u64(u64::MAX), P::UInt(u64::MAX));
+    assert!(matches!(P::from(u128::MAX), P::Number(_)));
+}
+
+#[test]
+fn numeric_integers_near_i128_limits_compare_exactly() {
+    use std::collections::HashSet;
+    use toon::StringOrNumberOrBoolOrNull as P;
+
+    let lexeme = |value: i128| P::NumberLexeme(value.to_string());
+    let two_127 = P::Number(2f64.powi(127));
+    // Both round to 2^127 as `f64`, but neither equals it.
+    assert!(lexeme(i128::MAX) < two_127);
+    assert!(lexeme(i128::MAX - 1) < lexeme(i128::MAX));
+    assert_ne!(lexeme(i128::MAX - 1), two_127);
+
+    assert_eq!(lexeme(i128::MIN), P::Number(-(2f64.powi(127))));
+    assert!(lexeme(i128::MIN + 1) > P::Number(-(2f64.powi(127))));
+    assert!(lexeme(-3) < P::Number(-2.5));
+    assert!(lexeme(-2) > P::Number(-2.5));
+    assert!(lexeme(i128::MAX) < P::Number(f64::NAN));
+
+    let unique: HashSet<_> = [
+        lexeme(i128::MAX),
+        lexeme(i128::MAX - 1),
+        two_127,
+        lexeme(i128::MIN),
+        P::Number(-(2f64.powi(127))),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(unique.len(), 4);
+}
+
+#[test]
+fn numeric_lossless_mode_keeps_numbers_as_written() {
+    use toon::StringOrNumberOrBoolOrNull as P;
+
+    let input = "price: 1.230\nbig: 1e10\nzero: -0\nids[3]: 1.0,2E+2,-0.50\nrows[2]{id,score}:\n  1,9.50\n  2,1e-3\nname: \"1.230\"";
+    let lossless = DecodeOptions {
+        lossless_numbers: Some(true),
+        ..DecodeOptions::default()
+    };
+    let value = try_decode(input, Some(lossless)).unwrap();
+    let JsonValue::Object(entries) = &value else {
+        panic!("expected an object");
+    };
+    assert_eq!(
+        entries[0].1,
+        JsonValue::Primitive(P::NumberLexeme("1.230".to_string()))
+    );
+    assert_eq!(
+        entries[5].1,
+        JsonValue::Primitive(P::String("1.230".to_string()))
+    );
+
+    let options = EncodeOptions {
+        lossless_numbers: Some(true),
+        ..EncodeOptions::default()
+    };
+    assert_eq!(try_encode(value.clone(), Some(options)).unwrap(), input);
+    assert_eq!(
+        toon::json::json_stringify_lines(&value, 0).concat(),
+        r#"{"price":1.230,"big":1e10,"zero":-0,"ids":[1.0,2E+2,-0.50],"rows":[{"id":1,"score":9.50},{"id":2,"score":1e-3}],"name":"1.230"}"#
+    );
+
+    // Without lossless encoding, numbers are written in canonical form.
+    assert_eq!(
+        try_encode(value, None).unwrap(),
+        "price: 1.23\nbig: 10000000000\nzero: 0\nids[3]: 1,200,-0.5\nrows[2]{id,score}:\n  1,9.5\n  2,0.001\nname: \"1.230\""
+    );
+}
+
+#[test]
+fn malformed_number_lexemes_are_written_as_numbers() {
+    use toon::StringOrNumberOrBoolOrNull as P;
+
+    let lexeme = |text: &str| JsonValue::Primitive(P::NumberLexeme(text.to_string()));
+    let value = JsonValue::Object(vec![
+        ("x".to_string(), JsonValue::Array(vec![lexeme("1, 2")])),
+        ("y".to_string(), lexeme("abc")),
+        ("z".to_string(), lexeme(" 7")),
+    ]);
+    let options = EncodeOptions {
+        lossless_numbers: Some(true),
+        ..EncodeOptions::default()
+    };
+    let encoded = try_encode(value.clone(), Some(options)).unwrap();
+    assert_eq!(encoded, "x[1]: null\ny: null\nz: null");
+    assert_eq!(
+        toon::json::json_stringify_lines(&value, 0).concat(),
+        r#"{"x":[null],"y":null,"z":null}"#
+    );
+}
+
+#[test]
+fn numeric_lexemes_compare_by_value() {
+    use toon::StringOrNumberOrBoolOrNull as P;
+
+    let lexeme = |text: &str| P::NumberLexeme(text.to_string());
+    assert_eq!(lexeme("1.230"), P::Number(1.23));
+    assert_eq!(lexeme("-0"), P::Int(0));
+    assert_eq!(lexeme("9007199254740993"), P::Int(9_007_199_254_740_993));
+    assert!(lexeme("1e10") > P::Int(9_999_999_999));
+    assert_eq!(lexeme("2E+2").as_f64(), Some(200.0));
+    assert!(lexeme("1.0").is_integer());
+    assert_eq!(
+        lexeme("18446744073709551615").into_number(),
+        P::UInt(u64::MAX)
+    );
+}
+
+#[test]
+#[cfg(feature = "arbitrary-precision")]
+fn numeric_arbitrary_precision_keeps_inexact_numbers() {
+    use toon::StringOrNumberOrBoolOrNull as P;
+
+    let input = "price: 0.10000000000000000001
+big: 123456789012345678901234567890
+small: 1.50e-30
+plain: 1.5";
+    let value = decode(input, None);
+    let JsonValue::Object(entries) = &value else {
+        panic!("expected an object");
+    };
+    assert!(matches!(
+        &entries[0].1,
+        JsonValue::Primitive(P::NumberLexeme(text)) if text == "0.10000000000000000001"
+    ));
+    // Numbers `f64` holds exactly stay floats.
+    assert!(matches!(entries[2].1, JsonValue::Primitive(P::Number(_))));
+    assert!(matches!(entries[3].1, JsonValue::Primitive(P::Number(_))));
+    assert_eq!(
+        encode(value.clone(), None),
+        "price: 0.10000000000000000001\nbig: 123456789012345678901234567890\nsmall: 0.0000000000000000000000000000015\nplain: 1.5"
+    );
+
+    let json: serde_json::Value = value.into();
+    assert_eq!(
+        json.to_string(),
+        r#"{"price":0.10000000000000000001,"big":123456789012345678901234567890,"small":1.5e-30,"plain":1.5}"#
+    );
+    let from_json = JsonValue::from(
+        serde_json::from_str::<serde_json::Value>(r#"{"total":12345678901234567890.12345}"#)
+            .unwrap(),
+    );
+    assert_eq!(encode(from_json, None), "total: 12345678901234567890.12345");
+}
+
+#[test]
+fn normalize_with_applies_each_option() {
+    use toon::StringOrNumberOrBoolOrNull as P;
+    use toon::encode::normalize::{
+        NonFiniteNumbers, NormalizeOptions, NumericStrings, normalize_json_value, normalize_with,
+    };
+
+    let value = JsonValue::Object(vec![
+        ("nan".to_string(), JsonValue::Primitive(P::Number(f64::NAN))),
+        (
+            "inf".to_string(),
+            JsonValue::Primitive(P::Number(f64::NEG_INFINITY)),
+        ),
+        ("zero".to_string(), JsonValue::Primitive(P::Number(-0.0))),
+        ("cafe\u{301}".to_string(), JsonValue::from("e\u{301}")),
+        (
+            "codes".to_string(),
+            JsonValue::from(vec!["42", "-1.5e3", "007", " 1"]),
+        ),
+    ]);
+
+    let default = normalize_with(value.clone(), NormalizeOptions::default());
+    assert_eq!(default, normalize_json_value(value.clone()));
+    assert_eq!(default.get("inf"), Some(&JsonValue::Primitive(P::Null)));
+    assert_eq!(
+        default.get("cafe\u{301}"),
+        Some(&JsonValue::from("e\u{301}"))
+    );
+
+    let options = NormalizeOptions {
+        non_finite: NonFiniteNumbers::String,
+        keep_negative_zero: true,
+        nfc: true,
+        numeric_strings: NumericStrings::Parse,
+    };
+    let normalized = normalize_with(value, options);
+    assert_eq!(normalized.get("nan"), Some(&JsonValue::from("NaN")));
+    assert_eq!(normalized.get("inf"), Some(&JsonValue::from("-Infinity")));
+    let Some(JsonValue::Primitive(P::Number(zero))) = normalized.get("zero") else {
+        panic!("zero should stay a number");
+    };
+    assert!(zero.is_sign_negative());
+    assert_eq!(
+        normalized.get("caf\u{e9}"),
+        Some(&JsonValue::from("\u{e9}"))
+    );
+    assert_eq!(
+        normalized.get("codes"),
+        Some(&JsonValue::Array(vec![
+            JsonValue::from(42),
+            JsonValue::from(-1500.0),
+            JsonValue::from("007"),
+            JsonValue::from(" 1"),
+        ]))
+    );
+
+    // A normalized value encodes the same with any layout, unchanged by the
+    // encoder's own pass.
+    assert_eq!(
+        encode(normalized.clone(), None),
+        "nan: NaN\ninf: \"-Infinity\"\nzero: 0\n\"caf\u{e9}\": \u{e9}\ncodes[4]: 42,-1500,\"007\",\" 1\""
+    );
+    assert_eq!(normalize_with(normalized.clone(), options), normalized);
+}
+
 // ============================================================================
 // EMPTY ARRAYS AND OBJECTS
 // ============================================================================
@@ -394,6 +748,7 @@ fn array_with_pipe_delimiter() {
         key_folding: None,
         flatten_depth: None,
         replacer: None,
+        ..EncodeOptions::default()
     });
     let toon = encode(json.clone(), options);
     assert!(toon.contains('|'));
@@ -402,6 +757,109 @@ fn array_with_pipe_delimiter() {
     assert_eq!(json, decoded_json);
 }
 
+#[test]
+fn decode_delimiter_applies_to_headers_without_marker() {
+    let input = "tags[3]: a|b|c\nrows[2]{id|name}:\n  1|Ada\n  2|\"B|b\"\nnested[2]:\n  - items[2]: x|y\n  - [2]: 1|2\nmarked[2\t]: p,q\tr";
+    let expected = serde_json::json!({
+        "tags": ["a", "b", "c"],
+        "rows": [{"id": 1, "name": "Ada"}, {"id": 2, "name": "B|b"}],
+        "nested": [{"items": ["x", "y"]}, [1, 2]],
+        "marked": ["p,q", "r"]
+    });
+    let options = DecodeOptions {
+        delimiter: Some('|'),
+        ..DecodeOptions::default()
+    };
+    let decoded: serde_json::Value = try_decode(input, Some(options)).unwrap().into();
+    assert_eq!(decoded, expected);
+
+    // Without the option, the pipe-separated values are one string.
+    assert!(try_decode(input, None).is_err());
+
+    // A directive naming a delimiter takes precedence over the option.
+    let directive = "#%toon version=1 delimiter=tab\ntags[2]: a|b\tc";
+    let options = DecodeOptions {
+        delimiter: Some('|'),
+        ..DecodeOptions::default()
+    };
+    let decoded: serde_json::Value = try_decode(directive, Some(options)).unwrap().into();
+    assert_eq!(decoded, serde_json::json!({"tags": ["a|b", "c"]}));
+}
+
+#[test]
+fn always_mark_delimiter_writes_comma_markers() {
+    let value = serde_json::json!({
+        "tags": ["a", "b"],
+        "rows": [{"id": 1, "name": "Ada"}, {"id": 2, "name": "Bob"}],
+        "nested": [[1, 2], {"items": []}],
+    });
+    let options = EncodeOptions {
+        always_mark_delimiter: Some(true),
+        ..EncodeOptions::default()
+    };
+    let toon = encode(value.clone(), Some(options));
+    assert_eq!(
+        toon,
+        "tags[2,]: a,b\nrows[2,]{id,name}:\n  1,Ada\n  2,Bob\nnested[2,]:\n  - [2,]: 1,2\n  - items[0,]:"
+    );
+
+    let decoded: serde_json::Value = decode(&toon, None).into();
+    assert_eq!(decoded, value);
+    let pedantic = DecodeOptions {
+        pedantic: Some(true),
+        ..DecodeOptions::default()
+    };
+    assert!(try_decode(&toon, Some(pedantic)).is_ok());
+
+    // The marker overrides a default delimiter given for unmarked headers.
+    let options = DecodeOptions {
+        delimiter: Some('|'),
+        ..DecodeOptions::default()
+    };
+    let decoded: serde_json::Value = try_decode("tags[2,]: a|b,c", Some(options)).unwrap().into();
+    assert_eq!(decoded, serde_json::json!({"tags": ["a|b", "c"]}));
+
+    // Other delimiters are marked either way.
+    let piped = EncodeOptions {
+        delimiter: Some('|'),
+        always_mark_delimiter: Some(true),
+        ..EncodeOptions::default()
+    };
+    assert_eq!(
+        encode(value, Some(piped)).lines().next(),
+        Some("tags[2|]: a|b")
+    );
+}
+
+#[test]
+fn tabular_fields_with_brackets_braces_and_colons_are_quoted() {
+    let json = serde_json::json!({
+        "items": [
+            {"a[1]": 1, "{b}": 2, "-c": 3},
+            {"a[1]": 4, "{b}": 5, "-c": 6}
+        ]
+    });
+    assert_eq!(
+        encode(json, None),
+        "items[2]{\"a[1]\",\"{b}\",\"-c\"}:\n  1,2,3\n  4,5,6"
+    );
+
+    let decoded: serde_json::Value =
+        decode("items[2]{\"{x}\",\"a}\",\"k:v\"}:\n  1,2,3\n  4,5,6", None).into();
+    assert_eq!(
+        decoded,
+        serde_json::json!({
+            "items": [
+                {"{x}": 1, "a}": 2, "k:v": 3},
+                {"{x}": 4, "a}": 5, "k:v": 6}
+            ]
+        })
+    );
+
+    let decoded: serde_json::Value = decode("\"a}\"[1]{\"}\"}:\n  1", None).into();
+    assert_eq!(decoded, serde_json::json!({"a}": [{"}": 1}]}));
+}
+
 #[test]
 fn tabular_array_with_special_chars() {
     let json: serde_json::Value = serde_json::json!([
@@ -429,14 +887,14 @@ fn key_folding_simple() {
         key_folding: Some(KeyFoldingMode::Safe),
         flatten_depth: None,
         replacer: None,
+        ..EncodeOptions::default()
     });
     let toon = encode(json.clone(), options);
     assert!(toon.contains("a.b.c"));
 
     let decode_options = Some(DecodeOptions {
-        indent: None,
-        strict: None,
         expand_paths: Some(ExpandPathsMode::Safe),
+        ..DecodeOptions::default()
     });
     let decoded = decode(&toon, decode_options);
     let decoded_json: serde_json::Value = decoded.into();
@@ -459,14 +917,14 @@ fn key_folding_with_sibling() {
         key_folding: Some(KeyFoldingMode::Safe),
         flatten_depth: None,
         replacer: None,
+        ..EncodeOptions::default()
     });
     let toon = encode(json.clone(), options);
 
     // Need to expand paths to reconstruct the nested structure
     let decode_options = Some(DecodeOptions {
-        indent: None,
-        strict: None,
         expand_paths: Some(ExpandPathsMode::Safe),
+        ..DecodeOptions::default()
     });
     let decoded = decode(&toon, decode_options);
     let decoded_json: serde_json::Value = decoded.into();
@@ -484,13 +942,13 @@ fn key_folding_depth_limit() {
         key_folding: Some(KeyFoldingMode::Safe),
         flatten_depth: Some(2), // Only fold 2 levels
         replacer: None,
+        ..EncodeOptions::default()
     });
     let toon = encode(json.clone(), options);
 
     let decode_options = Some(DecodeOptions {
-        indent: None,
-        strict: None,
         expand_paths: Some(ExpandPathsMode::Safe),
+        ..DecodeOptions::default()
     });
     let decoded = decode(&toon, decode_options);
     let decoded_json: serde_json::Value = decoded.into();
@@ -562,6 +1020,71 @@ proptest! {
         let decoded_json: serde_json::Value = decoded.into();
         prop_assert_eq!(value, decoded_json);
     }
+
+    #[test]
+    fn roundtrip_exotic_keys(
+        keys in proptest::collection::hash_set("[a-z\\[\\]{}:.\\-\" ,|#]{0,6}", 1..5)
+    ) {
+        let row: serde_json::Map<String, serde_json::Value> = keys
+            .iter()
+            .enumerate()
+            .map(|(index, key)| (key.clone(), serde_json::json!(index)))
+            .collect();
+        let json = serde_json::json!({
+            "object": row,
+            "rows": [row, row],
+        });
+        let expand = DecodeOptions {
+            expand_paths: Some(ExpandPathsMode::Safe),
+            ..DecodeOptions::default()
+        };
+        for value in [json, serde_json::Value::Object(row)] {
+            let toon = encode(value.clone(), None);
+            let decoded: serde_json::Value = decode(&toon, None).into();
+            prop_assert_eq!(&value, &decoded);
+            let expanded: serde_json::Value = decode(&toon, Some(expand.clone())).into();
+            prop_assert_eq!(&value, &expanded);
+            prop_assert!(toon::lossy::validate_roundtrip_keys(&value.into()).is_empty());
+        }
+    }
+
+    #[test]
+    fn dotted_keys_survive_key_folding_and_path_expansion(
+        segments in proptest::collection::vec("[a-c]{1,2}", 1..4),
+        value in any::<i32>(),
+    ) {
+        let dotted = segments.join(".");
+        let json = serde_json::json!({
+            dotted.clone(): value,
+            segments[0].clone(): {"z": {"w": value}},
+        });
+        let folding = EncodeOptions {
+            key_folding: Some(KeyFoldingMode::Safe),
+            ..EncodeOptions::default()
+        };
+        let expand = DecodeOptions {
+            expand_paths: Some(ExpandPathsMode::Safe),
+            ..DecodeOptions::default()
+        };
+        let toon = encode(json.clone(), Some(folding));
+        let quoted = format!("\"{dotted}\": {value}");
+        prop_assert!(!dotted.contains('.') || toon.contains(&quoted), "{}", toon);
+        let decoded: serde_json::Value = decode(&toon, Some(expand)).into();
+        prop_assert_eq!(json, decoded);
+    }
+
+    #[test]
+    fn roundtrip_ragged_multidimensional_arrays(
+        value in any::<i32>().prop_map(serde_json::Value::from).prop_recursive(6, 64, 4, |inner| {
+            proptest::collection::vec(inner, 0..4).prop_map(serde_json::Value::Array)
+        })
+    ) {
+        let json = serde_json::json!({ "grid": value });
+        let toon = encode(json.clone(), None);
+        let decoded = decode(&toon, None);
+        let decoded_json: serde_json::Value = decoded.into();
+        prop_assert_eq!(json, decoded_json);
+    }
 }
 
 // ============================================================================
@@ -574,9 +1097,8 @@ fn strict_mode_rejects_tabs() {
     let result = try_decode(
         toon_with_tabs,
         Some(DecodeOptions {
-            indent: None,
             strict: Some(true),
-            expand_paths: None,
+            ..DecodeOptions::default()
         }),
     );
     assert!(result.is_err());
@@ -588,9 +1110,8 @@ fn non_strict_mode_accepts_tabs() {
     let result = try_decode(
         toon_with_tabs,
         Some(DecodeOptions {
-            indent: None,
             strict: Some(false),
-            expand_paths: None,
+            ..DecodeOptions::default()
         }),
     );
     // Non-strict mode should at least not panic - we accept any result
@@ -663,3 +1184,4372 @@ fn boolean_like_keys() {
     let decoded_json: serde_json::Value = decoded.into();
     assert_eq!(json, decoded_json);
 }
+
+// ============================================================================
+// FALLIBLE ENCODING
+// ============================================================================
+
+#[test]
+fn try_encode_matches_encode() {
+    let json: serde_json::Value = serde_json::json!({
+        "users": [{"id": 1, "name": "Ada"}, {"id": 2, "name": "Bob"}],
+        "tags": ["a", "b"]
+    });
+    let toon = try_encode(json.clone(), None).unwrap();
+    assert_eq!(toon, encode(json.clone(), None));
+    assert_eq!(
+        try_encode_lines(json, None).unwrap(),
+        toon.lines().map(str::to_string).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn try_encode_with_replacer_editing_rows() {
+    let json: serde_json::Value = serde_json::json!({
+        "users": [{"id": 1, "name": "Ada"}, {"id": 2, "name": "Bob"}]
+    });
+    let replacer: toon::EncodeReplacer = std::sync::Arc::new(|key, value, _path| {
+        if key == "name" && value == &JsonValue::from(serde_json::json!("Bob")) {
+            return Some(JsonValue::from(serde_json::json!({"first": "Bob"})));
+        }
+        Some(value.clone())
+    });
+    let options = EncodeOptions {
+        indent: None,
+        delimiter: None,
+        key_folding: None,
+        flatten_depth: None,
+        replacer: Some(replacer),
+        ..EncodeOptions::default()
+    };
+    let toon = try_encode(json, Some(options)).unwrap();
+    let decoded: serde_json::Value = decode(&toon, None).into();
+    assert_eq!(decoded["users"][1]["name"]["first"], "Bob");
+}
+
+#[test]
+fn encode_error_paths_are_prefixed_while_unwinding() {
+    let err =
+        toon::error::ToonError::encode("[1].name", "tabular row contains non-primitive value")
+            .with_key_prefix("users")
+            .with_index_prefix(0)
+            .with_key_prefix("groups");
+    assert_eq!(
+        err.to_string(),
+        "Encode error at 'groups[0].users[1].name': tabular row contains non-primitive value"
+    );
+}
+
+#[test]
+fn limit_errors_are_structured() {
+    let err = toon::error::ToonError::limit("depth", 64, 65);
+    assert!(matches!(
+        err,
+        toon::error::ToonError::Limit {
+            ref kind,
+            limit: 64,
+            actual: 65
+        } if kind == "depth"
+    ));
+    assert_eq!(err.to_string(), "depth limit exceeded: 65 > 64");
+}
+
+// ============================================================================
+// TABULAR MISSING CELLS
+// ============================================================================
+
+fn decode_short_row(mode: Option<MissingCellMode>) -> toon::error::Result<serde_json::Value> {
+    let toon = "users[2]{id,name}:\n  1,Ada\n  2";
+    try_decode(
+        toon,
+        Some(DecodeOptions {
+            strict: Some(false),
+            missing_cell: mode,
+            ..DecodeOptions::default()
+        }),
+    )
+    .map(Into::into)
+}
+
+#[test]
+fn missing_cell_defaults_to_null() {
+    let decoded = decode_short_row(None).unwrap();
+    assert_eq!(
+        decoded,
+        serde_json::json!({"users": [{"id": 1, "name": "Ada"}, {"id": 2, "name": null}]})
+    );
+}
+
+#[test]
+fn missing_cell_omit_drops_absent_fields() {
+    let decoded = decode_short_row(Some(MissingCellMode::Omit)).unwrap();
+    assert_eq!(
+        decoded,
+        serde_json::json!({"users": [{"id": 1, "name": "Ada"}, {"id": 2}]})
+    );
+}
+
+#[test]
+fn missing_cell_error_names_the_field() {
+    let err = decode_short_row(Some(MissingCellMode::Error)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Validation error at line 3: Tabular row is missing a value for field 'name'"
+    );
+}
+
+#[test]
+fn missing_cell_error_accepts_explicit_null() {
+    let toon = "users[1]{id,name}:\n  1,null";
+    let decoded = try_decode(
+        toon,
+        Some(DecodeOptions {
+            missing_cell: Some(MissingCellMode::Error),
+            ..DecodeOptions::default()
+        }),
+    )
+    .unwrap();
+    let decoded: serde_json::Value = decoded.into();
+    assert_eq!(
+        decoded,
+        serde_json::json!({"users": [{"id": 1, "name": null}]})
+    );
+}
+
+// ============================================================================
+// EMPTY STRING VS NULL CELLS
+// ============================================================================
+
+fn decode_with_empty_cell(
+    toon: &str,
+    mode: EmptyCellMode,
+) -> toon::error::Result<serde_json::Value> {
+    try_decode(
+        toon,
+        Some(DecodeOptions {
+            empty_cell: Some(mode),
+            ..DecodeOptions::default()
+        }),
+    )
+    .map(Into::into)
+}
+
+#[test]
+fn empty_cell_defaults_to_empty_string() {
+    let decoded: serde_json::Value = decode("tags[3]: a,,c", None).into();
+    assert_eq!(decoded, serde_json::json!({"tags": ["a", "", "c"]}));
+}
+
+#[test]
+fn empty_cell_null_mode_keeps_quoted_empty_strings() {
+    let decoded =
+        decode_with_empty_cell("rows[1]{a,b,c}:\n  ,\"\",x", EmptyCellMode::Null).unwrap();
+    assert_eq!(
+        decoded,
+        serde_json::json!({"rows": [{"a": null, "b": "", "c": "x"}]})
+    );
+}
+
+#[test]
+fn empty_cell_error_mode_reports_line_and_position() {
+    let err = decode_with_empty_cell("a: 1\ntags[3]: a,,c", EmptyCellMode::Error).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Validation error at line 2: Empty cell at position 2; write \"\" for an empty string"
+    );
+}
+
+#[test]
+fn bare_empty_strings_round_trip() {
+    let json = serde_json::json!({
+        "tags": ["a", "", "c"],
+        "rows": [{"a": "", "b": 1}, {"a": "x", "b": 2}],
+        "lone": [""]
+    });
+    let options = EncodeOptions {
+        empty_string: Some(EmptyStringStyle::Bare),
+        ..EncodeOptions::default()
+    };
+    let toon = encode(json.clone(), Some(options));
+    assert_eq!(
+        toon,
+        "tags[3]: a,,c\nrows[2]{a,b}:\n  ,1\n  x,2\nlone[1]: \"\""
+    );
+    let decoded: serde_json::Value = decode(&toon, None).into();
+    assert_eq!(decoded["tags"], json["tags"]);
+    assert_eq!(decoded["rows"][0]["a"], "");
+    assert_eq!(decoded["lone"], json["lone"]);
+}
+
+#[test]
+fn bare_empty_strings_stay_quoted_where_a_cell_would_be_trimmed() {
+    let json = serde_json::json!({
+        "tags": ["a", ""],
+        "rows": [{"a": 1, "b": ""}, {"a": 2, "b": "x"}],
+        "spaced": ["", "b", ""]
+    });
+    let comma = encode(
+        json.clone(),
+        Some(EncodeOptions {
+            empty_string: Some(EmptyStringStyle::Bare),
+            ..EncodeOptions::default()
+        }),
+    );
+    assert_eq!(
+        comma,
+        "tags[2]: a,\"\"\nrows[2]{a,b}:\n  1,\"\"\n  2,x\nspaced[3]: ,b,\"\""
+    );
+
+    let tab = encode(
+        json.clone(),
+        Some(EncodeOptions {
+            delimiter: Some('\t'),
+            empty_string: Some(EmptyStringStyle::Bare),
+            ..EncodeOptions::default()
+        }),
+    );
+    assert_eq!(
+        tab,
+        "tags[2\t]: a\t\"\"\nrows[2\t]{a\tb}:\n  1\t\"\"\n  2\tx\nspaced[3\t]: \"\"\tb\t\"\""
+    );
+    for toon in [comma, tab] {
+        let decoded: serde_json::Value = decode(&toon, None).into();
+        assert_eq!(decoded, json, "{toon}");
+    }
+}
+
+#[test]
+fn quoted_empty_strings_are_the_default() {
+    let toon = encode(serde_json::json!({"tags": ["a", ""]}), None);
+    assert_eq!(toon, "tags[2]: a,\"\"");
+}
+
+// ============================================================================
+// TABULAR COLUMN TYPES
+// ============================================================================
+
+fn decode_with_column_types(
+    toon: &str,
+    column_types: &[(&str, ColumnType)],
+) -> toon::error::Result<serde_json::Value> {
+    try_decode(
+        toon,
+        Some(DecodeOptions {
+            column_types: Some(
+                column_types
+                    .iter()
+                    .map(|(name, ty)| ((*name).to_string(), *ty))
+                    .collect(),
+            ),
+            ..DecodeOptions::default()
+        }),
+    )
+    .map(Into::into)
+}
+
+#[test]
+fn column_types_keep_numeric_looking_strings() {
+    let toon = "places[2]{zip_code,count,open}:\n  02134,3,true\n  90210,5,false";
+    let decoded = decode_with_column_types(
+        toon,
+        &[
+            ("zip_code", ColumnType::String),
+            ("open", ColumnType::String),
+        ],
+    )
+    .unwrap();
+    assert_eq!(
+        decoded,
+        serde_json::json!({"places": [
+            {"zip_code": "02134", "count": 3, "open": "true"},
+            {"zip_code": "90210", "count": 5, "open": "false"}
+        ]})
+    );
+}
+
+#[test]
+fn column_types_parse_quoted_numbers_and_bools() {
+    let toon = "rows[2]{n,flag}:\n  \"1.5\",\"true\"\n  null,false";
+    let decoded = decode_with_column_types(
+        toon,
+        &[("n", ColumnType::Number), ("flag", ColumnType::Bool)],
+    )
+    .unwrap();
+    assert_eq!(
+        decoded,
+        serde_json::json!({"rows": [{"n": 1.5, "flag": true}, {"n": null, "flag": false}]})
+    );
+}
+
+#[test]
+fn column_types_reject_unconvertible_cells() {
+    let toon = "rows[2]{n}:\n  1\n  abc";
+    let err = decode_with_column_types(toon, &[("n", ColumnType::Number)]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Validation error at line 3: Column 'n' expects number, found 'abc'"
+    );
+}
+
+// ============================================================================
+// TYPED TABULAR HEADERS
+// ============================================================================
+
+fn typed_decode_options(strict: bool) -> DecodeOptions {
+    DecodeOptions {
+        strict: Some(strict),
+        typed_headers: Some(true),
+        ..DecodeOptions::default()
+    }
+}
+
+#[test]
+fn typed_headers_encode_inferred_column_types() {
+    let json = serde_json::json!({
+        "users": [
+            {"id": 1, "name": "Ada", "score": 1.5, "admin": true, "note": null},
+            {"id": 2, "name": "Bob", "score": 2, "admin": false, "note": null}
+        ]
+    });
+    let options = EncodeOptions {
+        typed_headers: Some(true),
+        ..EncodeOptions::default()
+    };
+    let toon = encode(json, Some(options));
+    assert_eq!(
+        toon,
+        "users[2]{id:int,name:str,score:num,admin:bool,note}:\n  1,Ada,1.5,true,null\n  2,Bob,2,false,null"
+    );
+    let decoded: serde_json::Value = decode(&toon, Some(typed_decode_options(true))).into();
+    assert_eq!(decoded["users"][1]["name"], "Bob");
+    assert_eq!(decoded["users"][0]["id"], 1.0);
+}
+
+#[test]
+fn typed_headers_coerce_numeric_looking_strings() {
+    let toon = "places[2]{zip:str,n:int}:\n  02134,1\n  90210,2";
+    let decoded: serde_json::Value = decode(toon, Some(typed_decode_options(true))).into();
+    assert_eq!(decoded["places"][0]["zip"], "02134");
+    assert_eq!(decoded["places"][1]["zip"], "90210");
+}
+
+#[test]
+#[allow(clippy::literal_string_with_formatting_args)]
+fn typed_headers_reject_mismatches_in_strict_mode() {
+    let toon = "rows[2]{n:int}:\n  1\n  1.5";
+    let err = try_decode(toon, Some(typed_decode_options(true))).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Validation error at line 3: Column 'n' expects integer, found '1.5'"
+    );
+
+    let decoded: serde_json::Value = decode(toon, Some(typed_decode_options(false))).into();
+    assert_eq!(decoded["rows"][1]["n"], 1.5);
+}
+
+#[test]
+#[allow(clippy::literal_string_with_formatting_args)]
+fn typed_headers_reject_unknown_annotations() {
+    let err = try_decode("rows[1]{n:date}:\n  x", Some(typed_decode_options(true))).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Line 1: Unknown column type 'date' for field 'n'"
+    );
+}
+
+#[test]
+#[allow(clippy::literal_string_with_formatting_args)]
+fn typed_headers_are_plain_field_names_when_disabled() {
+    let decoded: serde_json::Value = decode("rows[1]{n:int}:\n  1", None).into();
+    assert_eq!(decoded, serde_json::json!({"rows": [{"n:int": 1}]}));
+}
+
+// ============================================================================
+// COLUMNAR LAYOUT
+// ============================================================================
+
+#[test]
+fn columnar_encodes_one_inline_array_per_field() {
+    let input = serde_json::json!({
+        "users": [{"id": 1, "name": "a"}, {"id": 2, "name": "b"}]
+    });
+    let options = EncodeOptions {
+        columnar: Some(true),
+        ..EncodeOptions::default()
+    };
+    assert_eq!(
+        encode(input, Some(options)),
+        "users:\n  \"@rows\": 2\n  id[2]: 1,2\n  name[2]: a,b"
+    );
+}
+
+#[test]
+fn columnar_round_trips_through_decode() {
+    let input = serde_json::json!({
+        "users": [{"id": 1, "name": "a"}, {"id": 2, "name": null}],
+        "meta": {"count": 2}
+    });
+    let encoded = encode(
+        input.clone(),
+        Some(EncodeOptions {
+            columnar: Some(true),
+            ..EncodeOptions::default()
+        }),
+    );
+    let decoded: serde_json::Value = decode(
+        &encoded,
+        Some(DecodeOptions {
+            columnar: Some(true),
+            ..DecodeOptions::default()
+        }),
+    )
+    .into();
+    assert_eq!(decoded, input);
+}
+
+#[test]
+fn columnar_decode_reassembles_marked_sections_only() {
+    let columnar = Some(DecodeOptions {
+        columnar: Some(true),
+        ..DecodeOptions::default()
+    });
+    let decode_columnar =
+        |input: &str| -> serde_json::Value { try_decode(input, columnar.clone()).unwrap().into() };
+
+    // Objects of equal-length arrays are data unless marked.
+    assert_eq!(
+        decode_columnar("u:\n  ids[2]: 1,2\n  ns[2]: 3,4"),
+        serde_json::json!({"u": {"ids": [1, 2], "ns": [3, 4]}})
+    );
+    assert_eq!(
+        decode_columnar("id[2]: 1,2"),
+        serde_json::json!({"id": [1, 2]})
+    );
+    assert_eq!(
+        decode_columnar("\"@rows\": 1\nid[1]: 7"),
+        serde_json::json!([{"id": 7}])
+    );
+
+    // Keys that read like the marker round-trip with an extra `@`.
+    let input = serde_json::json!({
+        "@rows": 1,
+        "@@rows": [{"@rows": 2, "x": 3}, {"@rows": 4, "x": 5}],
+        "u": {"ids": [1, 2], "ns": [3, 4]}
+    });
+    let encoded = encode(
+        input.clone(),
+        Some(EncodeOptions {
+            columnar: Some(true),
+            ..EncodeOptions::default()
+        }),
+    );
+    assert!(encoded.starts_with("\"@@rows\": 1\n\"@@@rows\":\n  \"@rows\": 2\n"));
+    assert_eq!(decode_columnar(&encoded), input);
+
+    let err = try_decode("\"@rows\": 2\nid[1]: 7", columnar).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("'id' must hold 2 primitive values")
+    );
+}
+
+#[test]
+fn columnar_decode_rejects_marker_keys_the_encoder_would_not_write() {
+    let columnar = || {
+        Some(DecodeOptions {
+            columnar: Some(true),
+            ..DecodeOptions::default()
+        })
+    };
+
+    // Ordinary objects that merely have an `@rows` key.
+    for input in [
+        "name: x\n\"@rows\": 2\nid[2]: 1,2",
+        "\"@rows\": 2\nnote: hi",
+        "\"@rows\": 0",
+        "\"@rows\": 3",
+        "\"@rows\": many\nid[4]: 1,2,3,4",
+    ] {
+        let err = try_decode(input, columnar()).unwrap_err();
+        assert!(
+            err.to_string().contains("is not a columnar section"),
+            "{input}: {err}"
+        );
+    }
+
+    // The escaped form is how such data reaches the decoder.
+    let decoded: serde_json::Value = try_decode("\"@@rows\": 2\nid[2]: 1,2", columnar())
+        .unwrap()
+        .into();
+    assert_eq!(decoded, serde_json::json!({"@rows": 2, "id": [1, 2]}));
+}
+
+#[test]
+fn columnar_leaves_non_tabular_arrays_alone() {
+    let input = serde_json::json!({
+        "mixed": [{"id": 1}, {"name": "b"}],
+        "nested": [{"id": 1, "tags": ["x"]}],
+        "plain": [1, 2]
+    });
+    let options = EncodeOptions {
+        columnar: Some(true),
+        ..EncodeOptions::default()
+    };
+    assert_eq!(encode(input.clone(), Some(options)), encode(input, None));
+}
+
+// ============================================================================
+// SPARSE ARRAYS
+// ============================================================================
+
+fn sparse_options(threshold: f64) -> EncodeOptions {
+    EncodeOptions {
+        sparse_threshold: Some(threshold),
+        run_length: None,
+        ..EncodeOptions::default()
+    }
+}
+
+fn sparse_decode_options() -> DecodeOptions {
+    DecodeOptions {
+        sparse: Some(true),
+        ..DecodeOptions::default()
+    }
+}
+
+#[test]
+fn sparse_arrays_write_only_non_null_entries() {
+    let input = serde_json::json!({"features": [null, 1, null, null, "x", null]});
+    assert_eq!(
+        encode(input, Some(sparse_options(0.5))),
+        "features[6]:\n  1: 1\n  4: x"
+    );
+}
+
+#[test]
+fn sparse_arrays_round_trip() {
+    let mut features = vec![serde_json::Value::Null; 100];
+    features[3] = serde_json::json!(0.5);
+    features[99] = serde_json::json!("");
+    let input = serde_json::json!({"features": features, "root": [null, true]});
+
+    let encoded = encode(input.clone(), Some(sparse_options(0.9)));
+    let decoded: serde_json::Value = decode(&encoded, Some(sparse_decode_options())).into();
+    assert_eq!(decoded, input);
+}
+
+#[test]
+fn sparse_entries_are_only_read_when_enabled() {
+    assert!(try_decode("a[3]:\n  1: x", None).is_err());
+    let decoded: serde_json::Value = decode("a[3]:\n  1: x", Some(sparse_decode_options())).into();
+    assert_eq!(decoded, serde_json::json!({"a": [null, "x", null]}));
+}
+
+#[test]
+fn sparse_arrays_reject_implausible_lengths() {
+    let err = try_decode(
+        "x[18446744073709551615]:\n  0: 1",
+        Some(sparse_decode_options()),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Line 2: Sparse array fills 18446744073709551614 nulls, more than this input can plausibly declare"
+    );
+
+    // The budget is shared by every sparse array in the document.
+    let many = (0..8)
+        .map(|i| format!("a{i}[300000]:\n  0: 1"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    assert!(try_decode(&many, Some(sparse_decode_options())).is_err());
+    assert!(try_decode("a[300000]:\n  0: 1", Some(sparse_decode_options())).is_ok());
+}
+
+#[test]
+fn sparse_threshold_keeps_dense_and_all_null_arrays_inline() {
+    let input = serde_json::json!({"dense": [1, null, 2], "empty": [null, null]});
+    assert_eq!(
+        encode(input, Some(sparse_options(0.5))),
+        "dense[3]: 1,null,2\nempty[2]: null,null"
+    );
+}
+
+#[test]
+fn sparse_arrays_reject_bad_indices() {
+    let err = try_decode("a[2]:\n  2: x", Some(sparse_decode_options())).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Line 2: Sparse array index 2 is out of range for length 2"
+    );
+
+    let err = try_decode("a[3]:\n  1: x\n  0: y", Some(sparse_decode_options())).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Line 3: Sparse array index 0 is not in increasing order"
+    );
+}
+
+// ============================================================================
+// RUN-LENGTH ARRAYS
+// ============================================================================
+
+fn run_length_encode_options(min_run: usize) -> EncodeOptions {
+    EncodeOptions {
+        run_length: Some(min_run),
+        ..EncodeOptions::default()
+    }
+}
+
+fn run_length_decode_options() -> DecodeOptions {
+    DecodeOptions {
+        run_length: Some(true),
+        ..DecodeOptions::default()
+    }
+}
+
+#[test]
+fn run_length_collapses_long_runs() {
+    let mut flags = vec![0; 998];
+    flags.extend([1, 1]);
+    let input = serde_json::json!({ "flags": flags });
+    assert_eq!(
+        encode(input, Some(run_length_encode_options(3))),
+        "flags[1000]: 0*998,1,1"
+    );
+}
+
+#[test]
+fn run_length_round_trips_and_quotes_marker_strings() {
+    let input = serde_json::json!({
+        "values": ["a*3", "a*3", "a*3", "b", "", "", "", null, null, null]
+    });
+    let encoded = encode(input.clone(), Some(run_length_encode_options(3)));
+    assert_eq!(encoded, r#"values[10]: "a*3"*3,b,""*3,null*3"#);
+
+    let decoded: serde_json::Value = decode(&encoded, Some(run_length_decode_options())).into();
+    assert_eq!(decoded, input);
+
+    let plain: serde_json::Value = decode("v[1]: a*3", None).into();
+    assert_eq!(plain, serde_json::json!({"v": ["a*3"]}));
+}
+
+#[test]
+fn run_length_rejects_runs_past_the_header_length() {
+    let err = try_decode("a[2]: x*3", Some(run_length_decode_options())).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Line 1: Run 'x*3' expands past the array length 2"
+    );
+}
+
+#[test]
+fn run_length_rejects_runs_past_the_input_budget() {
+    let err = try_decode(
+        "a[200000000]: 0*200000000",
+        Some(run_length_decode_options()),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Line 1: Run '0*200000000' expands to more cells than this input can plausibly hold"
+    );
+
+    let decoded = decode("a[5000]: 0*5000", Some(run_length_decode_options()));
+    assert_eq!(serde_json::Value::from(decoded)["a"][4999], 0);
+}
+
+// ============================================================================
+// STANDARD LIBRARY CONVERSIONS
+// ============================================================================
+
+#[test]
+fn encodes_maps_directly() {
+    let mut scores = std::collections::HashMap::new();
+    scores.insert("bob".to_string(), 7u8);
+    scores.insert("alice".to_string(), 9u8);
+    assert_eq!(encode(scores, None), "alice: 9\nbob: 7");
+
+    let mut ordered = std::collections::BTreeMap::new();
+    ordered.insert("z", vec![Some(1i32), None]);
+    ordered.insert("a", vec![]);
+    assert_eq!(encode(ordered, None), "a[0]:\nz[2]: 1,null");
+}
+
+#[test]
+fn encodes_integers_slices_and_options_directly() {
+    assert_eq!(encode(4_000_000_000u32, None), "4000000000");
+    assert_eq!(encode(-3i8, None), "-3");
+    assert_eq!(encode(&["a", "b"][..], None), "[2]: a,b");
+    assert_eq!(encode(None::<String>, None), "null");
+    assert_eq!(encode(Some(2.5f32), None), "2.5");
+}
+
+// ============================================================================
+// COLLECTING AND EXTENDING VALUES
+// ============================================================================
+
+#[test]
+fn collects_objects_and_arrays() {
+    let users: JsonValue = ["ada", "bob"]
+        .iter()
+        .map(|name| JsonValue::from(*name))
+        .collect();
+    let mut doc: JsonValue = [
+        ("users".to_string(), users),
+        ("active".to_string(), JsonValue::from(true)),
+    ]
+    .into_iter()
+    .collect();
+    doc.extend([("count".to_string(), JsonValue::from(2))]);
+    assert_eq!(
+        encode(doc, None),
+        "users[2]: ada,bob\nactive: true\ncount: 2"
+    );
+
+    let mut items = JsonValue::Array(Vec::new());
+    items.extend((1..=3).map(JsonValue::from));
+    assert_eq!(encode(items, None), "[3]: 1,2,3");
+}
+
+#[test]
+#[should_panic(expected = "cannot extend a non-array JsonValue")]
+fn extending_a_non_array_with_items_panics() {
+    let mut value = JsonValue::from("text");
+    value.extend([JsonValue::from(1)]);
+}
+
+// ============================================================================
+// MUTATING DECODED VALUES
+// ============================================================================
+
+#[test]
+fn edits_decoded_values_before_reencoding() {
+    let mut doc = decode("name: Ada\nrole: admin\ntags[1]: a", None);
+
+    assert_eq!(doc.insert("name", "Grace"), Some(JsonValue::from("Ada")));
+    assert_eq!(doc.insert("active", true), None);
+    assert_eq!(doc.remove("role"), Some(JsonValue::from("admin")));
+    assert_eq!(doc.remove("missing"), None);
+    doc.get_mut("tags").unwrap().push("b");
+    doc.get_or_insert_object("meta").insert("version", 2);
+    doc.get_or_insert_object("meta").insert("draft", false);
+
+    assert_eq!(
+        encode(doc.clone(), None),
+        "name: Grace\ntags[2]: a,b\nactive: true\nmeta:\n  version: 2\n  draft: false"
+    );
+
+    doc.retain(|key, _| key != "meta");
+    assert_eq!(doc.get("meta"), None);
+    assert_eq!(doc.get("active"), Some(&JsonValue::from(true)));
+}
+
+#[test]
+#[should_panic(expected = "cannot insert key 'k' into a non-object JsonValue")]
+fn inserting_into_a_non_object_panics() {
+    let mut value = JsonValue::from(1);
+    value.insert("k", 2);
+}
+
+// ============================================================================
+// INDEXED OBJECTS
+// ============================================================================
+
+#[test]
+fn indexed_object_entry_api() {
+    let mut object = toon::IndexedObject::new(vec![
+        ("a".to_string(), JsonValue::from(1)),
+        ("b".to_string(), JsonValue::from(2)),
+        ("a".to_string(), JsonValue::from(3)),
+    ]);
+
+    assert_eq!(object.get("a"), Some(&JsonValue::from(1)));
+    *object.entry("b").or_insert(0) = JsonValue::from(20);
+    object
+        .entry("c")
+        .and_modify(|_| unreachable!())
+        .or_insert_with(|| JsonValue::from("new"));
+    assert_eq!(object.insert("c", "replaced"), Some(JsonValue::from("new")));
+
+    assert_eq!(object.remove("a"), Some(JsonValue::from(1)));
+    // The duplicate `a` is found again once the index is rebuilt.
+    assert_eq!(object.get("a"), Some(&JsonValue::from(3)));
+
+    match object.entry("b") {
+        toon::indexed::Entry::Occupied(entry) => assert_eq!(entry.remove(), JsonValue::from(20)),
+        toon::indexed::Entry::Vacant(_) => panic!("expected occupied entry"),
+    }
+
+    assert_eq!(encode(JsonValue::from(object), None), "a: 3\nc: replaced");
+}
+
+// ============================================================================
+// ORDERING AND HASHING VALUES
+// ============================================================================
+
+#[test]
+fn values_dedupe_in_hash_sets_and_sort_canonically() {
+    use std::collections::{BTreeSet, HashSet};
+    use toon::StringOrNumberOrBoolOrNull;
+
+    let nan = JsonValue::Primitive(StringOrNumberOrBoolOrNull::Number(f64::NAN));
+    let values = vec![
+        JsonValue::from("b"),
+        JsonValue::from(2),
+        nan.clone(),
+        JsonValue::from(0.0),
+        JsonValue::Primitive(StringOrNumberOrBoolOrNull::Number(-0.0)),
+        JsonValue::from(true),
+        JsonValue::Primitive(StringOrNumberOrBoolOrNull::Null),
+        nan,
+        decode("a: 1\nb: 2", None),
+        decode("b: 2\na: 1", None),
+        decode("b: 2\na: 1", None),
+    ];
+
+    let unique: HashSet<_> = values.iter().cloned().collect();
+    assert_eq!(unique.len(), 8);
+
+    let sorted: Vec<_> = values
+        .into_iter()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    assert_eq!(sorted.len(), 8);
+    assert_eq!(
+        sorted[0],
+        JsonValue::Primitive(StringOrNumberOrBoolOrNull::Null)
+    );
+    assert_eq!(sorted[1], JsonValue::from(true));
+    assert_eq!(sorted[2], JsonValue::from(0));
+    assert_eq!(sorted[5], JsonValue::from("b"));
+    assert_eq!(sorted[6], decode("a: 1\nb: 2", None));
+}
+
+// ============================================================================
+// EXPLAIN MODE
+// ============================================================================
+
+#[test]
+fn explain_annotates_sections() {
+    let input = "users[2]{id,name}:\n  1,Ada\n  2,null\ntags[3]: a,1,c\nmeta:\n  owner: x";
+    let explained = toon::explain::explain_toon(input, None).unwrap();
+    let lines: Vec<&str> = explained.lines().collect();
+
+    assert!(lines[0].starts_with("# document: object with 3 keys · 67 bytes"));
+    assert!(lines[1].starts_with("# 2 rows × 2 fields (id: number, name: string?) · 35 bytes"));
+    assert_eq!(lines[2], "users[2]{id,name}:");
+    assert!(lines[5].starts_with("# 3 items (mixed)"));
+    assert!(lines[7].starts_with("# object with 1 key · "));
+
+    // Removing the comments gives back the original document
+    let stripped: Vec<&str> = lines
+        .iter()
+        .copied()
+        .filter(|line| !line.trim_start().starts_with("# "))
+        .collect();
+    assert_eq!(stripped.join("\n"), input);
+}
+
+#[test]
+fn explain_indents_nested_comments() {
+    let input = "items[1]:\n  - id: 1\n    rows[1]{a}:\n      true";
+    let explained = toon::explain::explain_toon(input, None).unwrap();
+    assert!(explained.contains("\n    # 1 row × 1 field (a: bool) · "));
+}
+
+#[test]
+fn explain_rejects_invalid_documents() {
+    assert!(toon::explain::explain_toon("items[3]: a,b", None).is_err());
+}
+
+// ============================================================================
+// SYNTAX HIGHLIGHTING
+// ============================================================================
+
+#[test]
+fn highlight_html_wraps_tokens_in_spans() {
+    use toon::render::{Theme, highlight_toon};
+
+    let input = "users[2]{id,name}:\n  1,\"A <b>\"\n  2,null\nitems[2]:\n  - true\n  - x";
+    let html = highlight_toon(input, Theme::Html);
+    let lines: Vec<&str> = html.lines().collect();
+
+    assert_eq!(
+        lines[0],
+        r#"<span class="toon-key">users</span><span class="toon-header">[2]{id,name}</span>:"#
+    );
+    assert_eq!(
+        lines[1],
+        r#"  <span class="toon-number">1</span>,<span class="toon-string">&quot;A &lt;b&gt;&quot;</span>"#
+    );
+    assert_eq!(
+        lines[2],
+        r#"  <span class="toon-number">2</span>,<span class="toon-null">null</span>"#
+    );
+    assert_eq!(
+        lines[4],
+        r#"  <span class="toon-list-marker">-</span> <span class="toon-bool">true</span>"#
+    );
+}
+
+#[test]
+fn highlight_ansi_keeps_text_and_tracks_delimiters() {
+    use toon::render::{Theme, highlight_toon};
+
+    let input = "tags[2|]: a|b\nrows[1|]{x|y}:\n  1|2\nbroken: \"unterminated";
+    let ansi = highlight_toon(input, Theme::Ansi);
+
+    // Stripping the escapes gives back the input
+    let mut plain = String::new();
+    let mut in_escape = false;
+    for ch in ansi.chars() {
+        match ch {
+            '\x1b' => in_escape = true,
+            'm' if in_escape => in_escape = false,
+            _ if !in_escape => plain.push(ch),
+            _ => {}
+        }
+    }
+    assert_eq!(plain, input);
+    assert!(ansi.contains("\x1b[36m1\x1b[0m|\x1b[36m2\x1b[0m"));
+}
+
+// ============================================================================
+// LEXICAL TOKENS
+// ============================================================================
+
+#[test]
+fn lex_toon_reports_kinds_and_spans() {
+    use toon::lex::{TokenKind, lex_toon};
+
+    let input = "users[2|]{id|name}:\n  1|Ada\nitems[1]:\n  - x: true";
+    let tokens: Vec<(TokenKind, &str)> = lex_toon(input)
+        .into_iter()
+        .map(|token| (token.kind, &input[token.span]))
+        .collect();
+
+    assert_eq!(
+        tokens,
+        vec![
+            (TokenKind::Key, "users"),
+            (TokenKind::Header, "[2|]{id|name}"),
+            (TokenKind::Colon, ":"),
+            (TokenKind::Indent, "  "),
+            (TokenKind::Cell, "1"),
+            (TokenKind::Delimiter, "|"),
+            (TokenKind::Cell, "Ada"),
+            (TokenKind::Key, "items"),
+            (TokenKind::Header, "[1]"),
+            (TokenKind::Colon, ":"),
+            (TokenKind::Indent, "  "),
+            (TokenKind::ListMarker, "-"),
+            (TokenKind::Key, "x"),
+            (TokenKind::Colon, ":"),
+            (TokenKind::Value, "true"),
+        ]
+    );
+}
+
+#[test]
+fn lex_toon_handles_inline_values_and_invalid_text() {
+    use toon::lex::{TokenKind, lex_toon};
+
+    let input = "tags[3]: a,\"b,c\",d\n\"unterminated";
+    let kinds: Vec<TokenKind> = lex_toon(input).into_iter().map(|t| t.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            TokenKind::Key,
+            TokenKind::Header,
+            TokenKind::Colon,
+            TokenKind::Cell,
+            TokenKind::Delimiter,
+            TokenKind::Cell,
+            TokenKind::Delimiter,
+            TokenKind::Cell,
+            TokenKind::Cell,
+        ]
+    );
+}
+
+// ============================================================================
+// PEDANTIC MODE
+// ============================================================================
+
+fn pedantic() -> DecodeOptions {
+    DecodeOptions {
+        pedantic: Some(true),
+        ..DecodeOptions::default()
+    }
+}
+
+#[test]
+fn pedantic_accepts_encoder_output() {
+    let value = serde_json::json!({
+        "name": "Ada",
+        "users": [{"id": 1, "tags": "a b"}, {"id": 2, "tags": ""}],
+        "nested": {"list": [1, [2, 3], {"k": true}], "pipe": ["a|b", "c"]},
+    });
+    for delimiter in [',', '|', '\t'] {
+        let options = EncodeOptions {
+            delimiter: Some(delimiter),
+            ..EncodeOptions::default()
+        };
+        let toon = encode(value.clone(), Some(options));
+        assert!(
+            try_decode(&toon, Some(pedantic())).is_ok(),
+            "rejected canonical output:\n{toon}"
+        );
+    }
+}
+
+#[test]
+fn pedantic_reports_every_violation() {
+    let input = "a:  1\nusers[02]{id, name}:\n  1 ,Ada \n  2,Bob\nitems[1]:\n  -  x";
+    assert!(try_decode(input, None).is_ok());
+
+    let violations = toon::decode::pedantic::pedantic_violations(input);
+    let found: Vec<(usize, usize, &str)> = violations
+        .iter()
+        .map(|v| (v.line, v.column, v.message.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            (1, 3, "expected a single space after ':'"),
+            (2, 7, "array length '02' is not a canonical integer"),
+            (2, 14, "unexpected whitespace around field name"),
+            (3, 4, "unexpected whitespace before delimiter"),
+            (3, 9, "trailing whitespace"),
+            (6, 4, "expected a single space after '-'"),
+        ]
+    );
+
+    let err = try_decode(input, Some(pedantic())).unwrap_err().to_string();
+    assert!(err.starts_with("Validation error at line 1: 6 pedantic violations: "));
+    assert!(err.contains("line 6, column 4: expected a single space after '-'"));
+}
+
+// ============================================================================
+// LENIENT PROFILE
+// ============================================================================
+
+#[test]
+fn lenient_profile_accepts_tab_indentation() {
+    let input = "user:\n\tname: Ada\n\tmeta:\n\t  admin: true\n  \tlevel: 3";
+    assert!(try_decode(input, None).is_err());
+
+    let decoded = try_decode(input, Some(DecodeProfile::Lenient.into())).unwrap();
+    assert_eq!(
+        decoded,
+        decode(
+            "user:\n  name: Ada\n  meta:\n    admin: true\n    level: 3",
+            None
+        )
+    );
+}
+
+#[test]
+fn lenient_profile_infers_missing_lengths() {
+    let input = "tags[]: a , b ,c\nusers[]{id,name}:\n  1, Ada\n  2 ,Bob\nitems[]:\n  - x\n  - k[|]: 1|2\n    n: 1\nempty[]:";
+    assert!(try_decode(input, None).is_err());
+
+    let decoded = try_decode(input, Some(DecodeProfile::Lenient.into())).unwrap();
+    assert_eq!(
+        decoded,
+        decode(
+            "tags[3]: a,b,c\nusers[2]{id,name}:\n  1,Ada\n  2,Bob\nitems[2]:\n  - x\n  - k[2|]: 1|2\n    n: 1\nempty[0]:",
+            None
+        )
+    );
+}
+
+// ============================================================================
+// INCREMENTAL PATCHING
+// ============================================================================
+
+#[test]
+fn patch_encoded_rewrites_only_the_edited_section() {
+    use toon::options::PathSegment::{Index, Key};
+    use toon::patch::{patch_encoded, try_patch_encoded};
+
+    let key = |name: &str| Key(name.to_string());
+    let original = "name:  Ada\nuser:\n  tags[2]: a,b\n  role: admin\nitems[2]:\n  - id: 1\n    n: x\n  - 2\nz: 1";
+
+    assert_eq!(
+        patch_encoded(original, &[key("user"), key("role")], "owner", None),
+        "name:  Ada\nuser:\n  tags[2]: a,b\n  role: owner\nitems[2]:\n  - id: 1\n    n: x\n  - 2\nz: 1"
+    );
+    assert_eq!(
+        patch_encoded(original, &[key("user"), key("tags"), Index(2)], "c", None),
+        "name:  Ada\nuser:\n  tags[3]: a,b,c\n  role: admin\nitems[2]:\n  - id: 1\n    n: x\n  - 2\nz: 1"
+    );
+    assert_eq!(
+        patch_encoded(original, &[key("items"), Index(0), key("n")], "y", None),
+        "name:  Ada\nuser:\n  tags[2]: a,b\n  role: admin\nitems[2]:\n  - id: 1\n    n: y\n  - 2\nz: 1"
+    );
+    assert_eq!(
+        patch_encoded(original, &[key("items"), Index(2)], true, None),
+        "name:  Ada\nuser:\n  tags[2]: a,b\n  role: admin\nitems[3]:\n  - id: 1\n    n: x\n  - 2\n  - true\nz: 1"
+    );
+    assert_eq!(
+        patch_encoded(
+            original,
+            &[key("user"), key("email")],
+            "ada@example.com",
+            None
+        ),
+        "name:  Ada\nuser:\n  tags[2]: a,b\n  role: admin\n  email: ada@example.com\nitems[2]:\n  - id: 1\n    n: x\n  - 2\nz: 1"
+    );
+
+    let err = try_patch_encoded(original, &[key("user"), key("tags"), Index(5)], 1, None)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("Path not found in document: user.tags[5]"));
+}
+
+#[test]
+fn patch_encoded_reaches_through_folded_keys() {
+    use toon::options::PathSegment::{Index, Key};
+    use toon::patch::patch_encoded;
+
+    let key = |name: &str| Key(name.to_string());
+    let original = "a.b.c: 1\nz: 2";
+    assert_eq!(
+        patch_encoded(original, &[key("a"), key("b"), key("c")], 5, None),
+        "a.b.c: 5\nz: 2"
+    );
+    assert_eq!(
+        patch_encoded(original, &[key("a"), key("b"), key("d")], 6, None),
+        "a.b:\n  c: 1\n  d: 6\nz: 2"
+    );
+
+    let original = "items[1]:\n  - x.y[2]: 1,2\n    id: 3";
+    assert_eq!(
+        patch_encoded(
+            original,
+            &[key("items"), Index(0), key("x"), key("y"), Index(0)],
+            9,
+            None
+        ),
+        "items[1]:\n  - x:\n      y[2]: 9,2\n    id: 3"
+    );
+
+    // Quoted dotted keys are literal and are patched in place.
+    let original = "\"a.b\": 1\nz:  2";
+    assert_eq!(
+        patch_encoded(original, &[key("a.b")], 5, None),
+        "\"a.b\": 5\nz:  2"
+    );
+}
+
+#[test]
+fn patch_encoded_matches_full_reencode() {
+    use toon::options::PathSegment::{Index, Key};
+    use toon::patch::patch_encoded;
+
+    let original = encode(
+        serde_json::json!({
+            "users": [{"id": 1, "name": "Ada"}, {"id": 2, "name": "Bob"}],
+            "groups": [{"name": "admins", "members": ["ada"]}, [1, 2]],
+        }),
+        None,
+    );
+    let cases = [
+        (
+            vec![Key("users".into()), Index(1), Key("name".into())],
+            serde_json::json!({"first": "Eve"}),
+            serde_json::json!({
+                "users": [{"id": 1, "name": "Ada"}, {"id": 2, "name": {"first": "Eve"}}],
+                "groups": [{"name": "admins", "members": ["ada"]}, [1, 2]],
+            }),
+        ),
+        (
+            vec![
+                Key("groups".into()),
+                Index(0),
+                Key("members".into()),
+                Index(1),
+            ],
+            serde_json::json!("bob"),
+            serde_json::json!({
+                "users": [{"id": 1, "name": "Ada"}, {"id": 2, "name": "Bob"}],
+                "groups": [{"name": "admins", "members": ["ada", "bob"]}, [1, 2]],
+            }),
+        ),
+        (
+            vec![Key("groups".into()), Index(1)],
+            serde_json::json!({"k": [1, 2]}),
+            serde_json::json!({
+                "users": [{"id": 1, "name": "Ada"}, {"id": 2, "name": "Bob"}],
+                "groups": [{"name": "admins", "members": ["ada"]}, {"k": [1, 2]}],
+            }),
+        ),
+    ];
+    for (path, new_value, expected) in cases {
+        assert_eq!(
+            patch_encoded(&original, &path, new_value, None),
+            encode(expected, None)
+        );
+    }
+}
+
+// ============================================================================
+// THREE-WAY MERGE
+// ============================================================================
+
+#[test]
+fn merge3_combines_independent_edits() {
+    use toon::merge::merge3;
+
+    let base = decode(
+        "name: app\nport: 80\ntags[2]: a,b\nlimits:\n  cpu: 1\n  mem: 512\nold: x",
+        None,
+    );
+    let ours = decode(
+        "name: app\nport: 8080\ntags[2]: a,b\nlimits:\n  cpu: 2\n  mem: 512\nold: x",
+        None,
+    );
+    let theirs = decode(
+        "name: app\nport: 80\ntags[2]: a,c\nlimits:\n  cpu: 1\n  mem: 1024\nnew: y",
+        None,
+    );
+
+    assert_eq!(
+        merge3(&base, &ours, &theirs).unwrap(),
+        decode(
+            "name: app\nport: 8080\ntags[2]: a,c\nlimits:\n  cpu: 2\n  mem: 1024\nnew: y",
+            None
+        )
+    );
+}
+
+#[test]
+fn merge3_reports_conflicts_with_both_sides() {
+    use toon::merge::merge3;
+    use toon::options::PathSegment::{Index, Key};
+
+    let base = decode("a: 1\nb: 1\nitems[2]: x,y", None);
+    let ours = decode("a: 2\nitems[2]: x,z", None);
+    let theirs = decode("a: 3\nb: 2\nitems[2]: x,w", None);
+
+    let conflicts = merge3(&base, &ours, &theirs).unwrap_err();
+    let summary: Vec<String> = conflicts.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        summary,
+        vec![
+            "a: modified on both sides",
+            "items[1]: modified on both sides",
+            "b: deleted by ours, modified by theirs",
+        ]
+    );
+    assert_eq!(conflicts[1].path, vec![Key("items".into()), Index(1)]);
+    assert_eq!(conflicts[1].ours, Some(JsonValue::from("z")));
+    assert_eq!(conflicts[1].theirs, Some(JsonValue::from("w")));
+    assert_eq!(conflicts[2].ours, None);
+}
+
+// ============================================================================
+// STRUCTURAL DIFF
+// ============================================================================
+
+#[test]
+fn diff_lists_changes_by_path() {
+    use toon::diff::diff;
+
+    let old = decode("name: app\nport: 80\ntags[2]: a,b\nold: x", None);
+    let new = decode("port: 8080\nname: app\ntags[3]: a,c,d\nnew: null", None);
+
+    let changes: Vec<String> = diff(&old, &new).iter().map(ToString::to_string).collect();
+    assert_eq!(
+        changes,
+        vec![
+            "~ port: 80 -> 8080",
+            "~ tags[1]: \"b\" -> \"c\"",
+            "+ tags[2]: \"d\"",
+            "- old: \"x\"",
+            "+ new: null",
+        ]
+    );
+    assert!(diff(&old, &old).is_empty());
+}
+
+// ============================================================================
+// MANIFEST
+// ============================================================================
+
+#[test]
+fn manifest_hash_identifies_data_not_formatting() {
+    use toon::manifest::ManifestEntry;
+
+    let toon_entry =
+        ManifestEntry::new("data/users.toon", "users[2]{id,name}:\n  1,Ada\n  2,Bob").unwrap();
+    let json_entry = ManifestEntry::new(
+        "data/users.json",
+        r#"{ "users": [ {"id": 1, "name": "Ada"}, {"id": 2, "name": "Bob"} ] }"#,
+    )
+    .unwrap();
+    assert_eq!(toon_entry.sha256, json_entry.sha256);
+    assert_eq!(toon_entry.sha256.len(), 64);
+    assert_eq!(toon_entry.bytes, 34);
+    assert!(toon_entry.tokens < json_entry.tokens);
+
+    let changed =
+        ManifestEntry::new("data/users.toon", "users[2]{id,name}:\n  1,Ada\n  2,Eve").unwrap();
+    assert_ne!(toon_entry.sha256, changed.sha256);
+    assert!(ManifestEntry::new("bad.json", "{").is_err());
+}
+
+// ============================================================================
+// LONG STRING POLICY
+// ============================================================================
+
+#[test]
+fn long_string_truncate_cuts_values_not_keys() {
+    let options = EncodeOptions {
+        long_string: Some(LongStringPolicy::Truncate {
+            max: 5,
+            suffix: "…".to_string(),
+        }),
+        ..EncodeOptions::default()
+    };
+    let value = serde_json::json!({
+        "a_rather_long_key": "héllo wörld",
+        "short": "hello",
+        "tags": ["abcdefgh", "x"],
+    });
+    assert_eq!(
+        encode(value, Some(options)),
+        "a_rather_long_key: héllo…\nshort: hello\ntags[2]: abcde…,x"
+    );
+}
+
+#[test]
+fn long_string_external_ref_returns_blobs() {
+    use toon::encode::long_string::ExtractedBlob;
+
+    let options = EncodeOptions {
+        long_string: Some(LongStringPolicy::ExternalRef { max: 10 }),
+        ..EncodeOptions::default()
+    };
+    let body = "a long body of text, with commas";
+    let value = serde_json::json!({
+        "docs": [
+            {"id": 1, "body": body},
+            {"id": 2, "body": "short"},
+            {"id": 3, "body": body},
+        ],
+        "summary": "another long string",
+    });
+    let (toon, extractions) = toon::encode_with_extractions(value, Some(options));
+    assert_eq!(
+        toon,
+        "docs[3]{id,body}:\n  1,@blob_1\n  2,short\n  3,@blob_1\nsummary: @blob_2"
+    );
+    assert_eq!(
+        extractions.blobs,
+        vec![
+            ExtractedBlob {
+                reference: "@blob_1".to_string(),
+                text: body.to_string(),
+            },
+            ExtractedBlob {
+                reference: "@blob_2".to_string(),
+                text: "another long string".to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn encode_with_extractions_reports_redactions_and_trims() {
+    use std::sync::Arc;
+    use toon::encode::extractions::{Redaction, Trim};
+    use toon::options::PathSegment;
+
+    let options = EncodeOptions {
+        replacer: Some(Arc::new(
+            |key: &str, value: &JsonValue, _path: &[PathSegment]| match key {
+                "password" => None,
+                "email" => Some(JsonValue::from("[redacted]")),
+                _ => Some(value.clone()),
+            },
+        )),
+        long_string: Some(LongStringPolicy::Truncate {
+            max: 8,
+            suffix: "...".to_string(),
+        }),
+        ..EncodeOptions::default()
+    };
+    let value = serde_json::json!({
+        "user": {"name": "Ada", "email": "ada@example.com", "password": "hunter2"},
+        "bio": "Mathematician and writer",
+    });
+    let (toon, extractions) = toon::encode_with_extractions(value, Some(options));
+    assert_eq!(
+        toon,
+        "user:\n  name: Ada\n  email: \"[redacte...\"\nbio: Mathemat..."
+    );
+
+    let user = |key: &str| {
+        vec![
+            PathSegment::Key("user".to_string()),
+            PathSegment::Key(key.to_string()),
+        ]
+    };
+    assert_eq!(
+        extractions.redactions,
+        vec![
+            Redaction {
+                path: user("email"),
+                original: JsonValue::from("ada@example.com"),
+                replacement: Some(JsonValue::from("[redacted]")),
+            },
+            Redaction {
+                path: user("password"),
+                original: JsonValue::from("hunter2"),
+                replacement: None,
+            },
+        ]
+    );
+    assert_eq!(
+        extractions.trims,
+        vec![
+            Trim {
+                path: user("email"),
+                original: "[redacted]".to_string(),
+            },
+            Trim {
+                path: vec![PathSegment::Key("bio".to_string())],
+                original: "Mathematician and writer".to_string(),
+            },
+        ]
+    );
+    assert!(extractions.blobs.is_empty());
+}
+
+// ============================================================================
+// MARKDOWN FRONTMATTER
+// ============================================================================
+
+#[test]
+fn markdown_frontmatter_round_trips() {
+    use toon::frontmatter::{from_markdown_frontmatter, to_markdown_frontmatter};
+
+    let value = serde_json::json!({"model": "small", "stop": ["\n\n", "END"]});
+    let body = "# Prompt\n\nSummarize ---\n---\nthe text.\n";
+    let doc = to_markdown_frontmatter(value.clone(), body, None).unwrap();
+    assert_eq!(
+        doc,
+        format!("---toon\nmodel: small\nstop[2]: \"\\n\\n\",END\n---\n{body}")
+    );
+
+    let (decoded, rest) = from_markdown_frontmatter(&doc, None).unwrap();
+    assert_eq!(decoded, JsonValue::from(value));
+    assert_eq!(rest, body);
+
+    let crlf = "---toon\r\nmodel: small\r\n---\r\nbody";
+    let (decoded, rest) = from_markdown_frontmatter(crlf, None).unwrap();
+    assert_eq!(
+        decoded,
+        JsonValue::from(serde_json::json!({"model": "small"}))
+    );
+    assert_eq!(rest, "body");
+
+    assert!(from_markdown_frontmatter("# Prompt\n", None).is_err());
+    assert!(from_markdown_frontmatter("---toon\nmodel: small\n", None).is_err());
+    assert_eq!(
+        from_markdown_frontmatter("---toon\n---\n", None).unwrap(),
+        (JsonValue::Object(Vec::new()), "")
+    );
+}
+
+// ============================================================================
+// DIRECTIVE LINE
+// ============================================================================
+
+#[test]
+fn directive_is_emitted_and_configures_decode() {
+    let options = EncodeOptions {
+        delimiter: Some('|'),
+        indent: Some(4),
+        emit_directive: Some(true),
+        ..EncodeOptions::default()
+    };
+    let value = serde_json::json!({"user": {"tags": ["a", "b"]}});
+    let toon = encode(value.clone(), Some(options));
+    assert_eq!(
+        toon,
+        "#%toon version=1 delimiter=| indent=4\nuser:\n    tags[2|]: a|b"
+    );
+
+    // The directive's indent wins over the default of 2.
+    assert_eq!(decode(&toon, None), JsonValue::from(value));
+
+    let tab = EncodeOptions {
+        delimiter: Some('\t'),
+        emit_directive: Some(true),
+        ..EncodeOptions::default()
+    };
+    assert!(
+        encode(serde_json::json!([1, 2]), Some(tab))
+            .starts_with("#%toon version=1 delimiter=tab indent=2\n")
+    );
+}
+
+#[test]
+fn directive_errors_keep_input_line_numbers() {
+    let err = try_decode("#%toon version=1 indent=2\na: 1\n b: 2", None).unwrap_err();
+    assert!(err.to_string().contains("line 3"), "{err}");
+
+    let err = try_decode("#%toon version=2\na: 1", None).unwrap_err();
+    assert!(
+        err.to_string().contains("Unsupported #%toon version 2"),
+        "{err}"
+    );
+
+    let err = try_decode("#%toon indent=two\na: 1", None).unwrap_err();
+    assert!(err.to_string().contains("Invalid #%toon indent"), "{err}");
+
+    // Unknown settings are ignored.
+    assert_eq!(
+        decode("#%toon version=1 encoding=utf-8\na: 1", None),
+        JsonValue::from(serde_json::json!({"a": 1}))
+    );
+}
+
+// ============================================================================
+// ANCHORS AND ALIASES
+// ============================================================================
+
+#[test]
+fn anchors_alias_repeated_objects_and_round_trip() {
+    let value = serde_json::json!({
+        "staging": {"timeout": 30, "retries": 3},
+        "production": {"timeout": 30, "retries": 3},
+        "services": [
+            {"limits": {"cpu": 2, "memory": "1Gi"}, "name": "api"},
+            {"limits": {"cpu": 2, "memory": "1Gi"}, "name": "worker"},
+        ],
+        "note": "*a1",
+    });
+    let toon = encode(
+        value.clone(),
+        Some(EncodeOptions {
+            anchors: Some(true),
+            ..EncodeOptions::default()
+        }),
+    );
+    assert_eq!(
+        toon,
+        "staging: &a1\n  timeout: 30\n  retries: 3\nproduction: *a1\nservices[2]:\n  - limits: &a2\n      cpu: 2\n      memory: 1Gi\n    name: api\n  - limits: *a2\n    name: worker\nnote: \"*a1\""
+    );
+
+    let options = DecodeOptions {
+        anchors: Some(true),
+        ..DecodeOptions::default()
+    };
+    assert_eq!(decode(&toon, Some(options)), JsonValue::from(value));
+}
+
+#[test]
+fn anchors_reject_unknown_and_self_references() {
+    let options = || {
+        Some(DecodeOptions {
+            anchors: Some(true),
+            ..DecodeOptions::default()
+        })
+    };
+    let err = try_decode("a: *missing", options()).unwrap_err();
+    assert!(err.to_string().contains("Unknown anchor *missing"), "{err}");
+
+    let err = try_decode("a: &x\n  b: 1\n  c: *x", options()).unwrap_err();
+    assert!(err.to_string().contains("inside the block"), "{err}");
+
+    // Without the option, markers are plain strings.
+    assert_eq!(
+        decode("a: *x", None),
+        JsonValue::from(serde_json::json!({"a": "*x"}))
+    );
+}
+
+#[test]
+fn anchors_reject_exponential_alias_expansion() {
+    // Each anchor aliases the previous one twice, doubling the expanded size.
+    let doc = (1..25).fold(String::from("a0: &a0\n  x: 1"), |doc, i| {
+        let prev = i - 1;
+        format!("{doc}\na{i}: &a{i}\n  l: *a{prev}\n  r: *a{prev}")
+    });
+    let options = DecodeOptions {
+        anchors: Some(true),
+        ..DecodeOptions::default()
+    };
+    let err = try_decode(&doc, Some(options.clone())).unwrap_err();
+    assert!(
+        err.to_string().contains("expands the document past"),
+        "{err}"
+    );
+
+    // A few levels of the same nesting stay within the budget.
+    let shallow = doc.lines().take(1 + 3 * 4).collect::<Vec<_>>().join("\n");
+    let decoded = serde_json::Value::from(decode(&shallow, Some(options)));
+    assert_eq!(
+        decoded["a4"]["l"]["r"]["l"]["r"],
+        serde_json::json!({"x": 1})
+    );
+}
+
+// ============================================================================
+// INCLUDES
+// ============================================================================
+
+#[test]
+fn include_resolver_splices_documents() {
+    use std::collections::HashMap;
+    use toon::options::IncludeResolver;
+
+    let files: HashMap<&str, &str> = HashMap::from([
+        ("persona.toon", "name: Ada\nstyle: terse"),
+        ("examples.toon", "[2]: first,second"),
+    ]);
+    let options = DecodeOptions {
+        include_resolver: Some(IncludeResolver::new(move |path| {
+            files
+                .get(path)
+                .map(|text| (*text).to_string())
+                .ok_or_else(|| toon::error::ToonError::message(format!("no such file: {path}")))
+        })),
+        ..DecodeOptions::default()
+    };
+
+    let doc = "persona: !include persona.toon\nitems[1]:\n  - !include examples.toon\nquoted: \"!include persona.toon\"";
+    assert_eq!(
+        decode(doc, Some(options.clone())),
+        JsonValue::from(serde_json::json!({
+            "persona": {"name": "Ada", "style": "terse"},
+            "items": [["first", "second"]],
+            "quoted": "!include persona.toon",
+        }))
+    );
+
+    let err = try_decode("a: !include missing.toon", Some(options)).unwrap_err();
+    assert!(
+        err.to_string().contains("no such file: missing.toon"),
+        "{err}"
+    );
+
+    // Without a resolver the value is a plain string.
+    assert_eq!(
+        decode("a: !include persona.toon", None),
+        JsonValue::from(serde_json::json!({"a": "!include persona.toon"}))
+    );
+}
+
+#[test]
+fn include_cycles_are_rejected() {
+    use toon::options::IncludeResolver;
+
+    let options = DecodeOptions {
+        include_resolver: Some(IncludeResolver::new(|path| {
+            Ok(match path {
+                "a.toon" => "next: !include b.toon".to_string(),
+                _ => "next: !include a.toon".to_string(),
+            })
+        })),
+        ..DecodeOptions::default()
+    };
+    let err = try_decode("root: !include a.toon", Some(options)).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Include cycle: a.toon -> b.toon -> a.toon"),
+        "{err}"
+    );
+}
+
+// ============================================================================
+// ENV INTERPOLATION
+// ============================================================================
+
+#[test]
+#[allow(clippy::literal_string_with_formatting_args)]
+fn variable_resolver_interpolates_string_values() {
+    use toon::options::VariableResolver;
+
+    let options = DecodeOptions {
+        variable_resolver: Some(VariableResolver::new(|name| {
+            (name == "HOST").then(|| "db.internal".to_string())
+        })),
+        ..DecodeOptions::default()
+    };
+
+    let doc = "url: \"postgres://${HOST}:5432\"\nport: ${PORT:-5432}\nliteral: $${HOST}\n${HOST}: key\nrows[1]{name,host}:\n  a,${HOST}";
+    assert_eq!(
+        decode(doc, Some(options.clone())),
+        JsonValue::from(serde_json::json!({
+            "url": "postgres://db.internal:5432",
+            "port": "5432",
+            "literal": "${HOST}",
+            "${HOST}": "key",
+            "rows": [{"name": "a", "host": "db.internal"}],
+        }))
+    );
+
+    let err = try_decode("a: ${MISSING}", Some(options.clone())).unwrap_err();
+    assert!(
+        err.to_string().contains("Undefined variable ${MISSING}"),
+        "{err}"
+    );
+    let err = try_decode("a: ${HOST", Some(options.clone())).unwrap_err();
+    assert!(
+        err.to_string().contains("Unterminated variable reference"),
+        "{err}"
+    );
+
+    let lenient = DecodeOptions {
+        strict: Some(false),
+        ..options
+    };
+    assert_eq!(
+        decode("a: ${MISSING} and ${HOST\nb: ${HOST}", Some(lenient)),
+        JsonValue::from(serde_json::json!({
+            "a": "${MISSING} and ${HOST",
+            "b": "db.internal",
+        }))
+    );
+
+    // Without a resolver references are plain text.
+    assert_eq!(
+        decode("a: ${HOST}", None),
+        JsonValue::from(serde_json::json!({"a": "${HOST}"}))
+    );
+}
+
+// ============================================================================
+// EVENT SINKS
+// ============================================================================
+
+#[test]
+fn decode_into_sink_feeds_any_sink() {
+    use toon::decode::event_builder::ValueSink;
+    use toon::json::JsonWriterSink;
+    use toon::sink::CountingSink;
+
+    let doc = "name: Ada\ntags[2]: a,b\nrows[2]{x,y}:\n  1,2\n  3,4";
+    let lines = || doc.split('\n').map(str::to_string);
+
+    let mut counts = CountingSink::default();
+    toon::decode_into_sink(lines(), None, &mut counts).unwrap();
+    assert_eq!(
+        counts,
+        CountingSink {
+            objects: 3,
+            arrays: 2,
+            keys: 7,
+            primitives: 7,
+        }
+    );
+    assert_eq!(counts.values(), 12);
+
+    let mut json = JsonWriterSink::new(Vec::new(), 0);
+    toon::decode_into_sink(lines(), None, &mut json).unwrap();
+    assert_eq!(
+        String::from_utf8(json.finish().unwrap()).unwrap(),
+        r#"{"name":"Ada","tags":["a","b"],"rows":[{"x":1,"y":2},{"x":3,"y":4}]}"#
+    );
+
+    let mut value = ValueSink::new();
+    toon::decode_into_sink(lines(), None, &mut value).unwrap();
+    assert_eq!(value.finish().unwrap(), decode(doc, None));
+}
+
+#[test]
+fn event_sink_errors_stop_decoding() {
+    use toon::EventSink;
+
+    struct FirstKeys(Vec<String>);
+
+    impl EventSink for FirstKeys {
+        fn event(&mut self, event: toon::JsonStreamEvent) -> toon::error::Result<()> {
+            if let toon::JsonStreamEvent::Key { key, .. } = event {
+                if self.0.len() == 2 {
+                    return Err(toon::error::ToonError::message("enough keys"));
+                }
+                self.0.push(key);
+            }
+            Ok(())
+        }
+    }
+
+    let mut sink = FirstKeys(Vec::new());
+    let err = toon::decode_into_sink(
+        ["a: 1", "b: 2", "c: 3"].map(str::to_string),
+        None,
+        &mut sink,
+    )
+    .unwrap_err();
+    assert_eq!(err.to_string(), "enough keys");
+    assert_eq!(sink.0, ["a", "b"]);
+}
+
+#[test]
+fn stats_sink_measures_document_shape() {
+    use toon::sink::StatsSink;
+
+    let doc = "name: Ada\ntags[3]: a,bb,ccc\nprofile:\n  age: 36\n  active: true\n  team: null\n  skills[0]:";
+    let mut stats = StatsSink::new();
+    toon::decode_into_sink(doc.split('\n').map(str::to_string), None, &mut stats).unwrap();
+
+    assert_eq!(stats.max_depth, 3);
+    assert_eq!((stats.objects, stats.arrays, stats.keys), (2, 2, 7));
+    assert_eq!(
+        (stats.strings, stats.numbers, stats.bools, stats.nulls),
+        (4, 1, 1, 1)
+    );
+    assert_eq!(stats.primitives(), 7);
+    assert_eq!(stats.max_array_length, 3);
+    assert_eq!(stats.max_object_keys, 4);
+    assert_eq!(stats.max_string_length, 3);
+    assert_eq!(stats.string_bytes, 9);
+
+    let mut primitive = StatsSink::new();
+    toon::decode_into_sink(["42".to_string()], None, &mut primitive).unwrap();
+    assert_eq!((primitive.max_depth, primitive.numbers), (0, 1));
+}
+
+#[test]
+fn tee_sink_feeds_both_sinks() {
+    use toon::decode::event_builder::ValueSink;
+    use toon::sink::{CountingSink, TeeSink};
+
+    let doc = "a: 1\nb[2]: x,y";
+    let mut tee = TeeSink(CountingSink::default(), ValueSink::new());
+    toon::decode_into_sink(doc.split('\n').map(str::to_string), None, &mut tee).unwrap();
+
+    let TeeSink(counts, value) = tee;
+    assert_eq!(counts.values(), 5);
+    assert_eq!(value.finish().unwrap(), decode(doc, None));
+}
+
+#[test]
+fn decode_until_stops_when_the_visitor_breaks() {
+    use std::cell::Cell;
+    use std::ops::ControlFlow;
+    use toon::{JsonStreamEvent, decode_until};
+
+    let pulled = Cell::new(0);
+    let lines = ["id: 7".to_string(), "rows[500]{n}:".to_string()]
+        .into_iter()
+        .chain((0..500).map(|n| format!("  {n}")))
+        .inspect(|_| pulled.set(pulled.get() + 1));
+    let mut id = None;
+    let stopped = decode_until(lines, None, |event| match event {
+        JsonStreamEvent::Primitive { value } => {
+            id = Some(value);
+            ControlFlow::Break(())
+        }
+        _ => ControlFlow::Continue(()),
+    })
+    .unwrap();
+    assert!(stopped.is_break());
+    assert_eq!(id, Some(7.into()));
+    assert!(pulled.get() < 10, "pulled {} lines", pulled.get());
+
+    let mut events = Vec::new();
+    let finished = decode_until(["a[2]: x,y".to_string()], None, |event| {
+        events.push(event);
+        ControlFlow::Continue(())
+    })
+    .unwrap();
+    assert!(finished.is_continue());
+    assert_eq!(
+        events,
+        toon::decode_stream_sync(["a[2]: x,y".to_string()], None)
+    );
+
+    let err = decode_until(["a[2]: x".to_string()], None, |_| ControlFlow::Continue(()));
+    assert!(err.is_err());
+}
+
+// ============================================================================
+// DETERMINISM AUDIT
+// ============================================================================
+
+#[test]
+fn audit_determinism_finds_differing_runs() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use toon::determinism::{Mismatch, audit_determinism};
+    use toon::options::PathSegment;
+
+    let value = JsonValue::from(serde_json::json!({"id": 1, "tags": ["a", "b"]}));
+    let report = audit_determinism(&value, None, 4, 3).unwrap();
+    assert!(report.is_deterministic());
+    assert_eq!((report.runs, report.output_bytes), (12, 18));
+
+    // A replacer with hidden state makes the output depend on the run.
+    let calls = Arc::new(AtomicUsize::new(0));
+    let options = EncodeOptions {
+        replacer: Some(Arc::new(
+            move |key: &str, value: &JsonValue, _path: &[PathSegment]| {
+                if key == "id" {
+                    Some(JsonValue::from(calls.fetch_add(1, Ordering::SeqCst)))
+                } else {
+                    Some(value.clone())
+                }
+            },
+        )),
+        ..EncodeOptions::default()
+    };
+    let report = audit_determinism(&value, Some(&options), 2, 1).unwrap();
+    assert_eq!(
+        report.mismatch,
+        Some(Mismatch {
+            thread: 0,
+            run: 0,
+            line: 1,
+            expected: "id: 0".to_string(),
+            actual: "id: 1".to_string(),
+        })
+    );
+
+    assert!(audit_determinism(&value, None, 0, 1).is_err());
+}
+
+#[test]
+fn run_bench_reports_sizes_and_percentiles() {
+    use std::time::Duration;
+    use toon::bench::run_bench;
+
+    let value = JsonValue::from(serde_json::json!({"id": 1, "tags": ["a", "b"]}));
+    let report = run_bench(&value, None, None, 5).unwrap();
+    // `id: 1` and `tags[2]: a,b`; start and end of the object and array,
+    // two keys, and three primitives.
+    assert_eq!((report.toon_bytes, report.lines, report.events), (18, 2, 9));
+    assert_eq!(report.encode.samples().len(), 5);
+    assert_eq!(report.decode.samples().len(), 5);
+    assert!(report.encode.percentile(50.0) <= report.encode.percentile(99.0));
+    assert_eq!(report.decode.percentile(100.0), report.decode.samples()[4]);
+    assert_eq!(report.decode.percentile(0.0), report.decode.samples()[0]);
+
+    let rate = report.throughput(Duration::from_millis(500));
+    assert!((rate.megabytes_per_second - 0.000_036).abs() < 1e-12);
+    assert!((rate.lines_per_second - 4.0).abs() < f64::EPSILON);
+    assert!((rate.events_per_second - 18.0).abs() < f64::EPSILON);
+
+    assert!(run_bench(&value, None, None, 0).is_err());
+}
+
+// ============================================================================
+// SCHEMAS
+// ============================================================================
+
+#[test]
+fn decode_with_schema_types_values_and_reports_violations() {
+    use toon::decode::{decode_with_schema, try_decode_with_schema};
+    use toon::options::PathSegment;
+    use toon::schema::{Field, ObjectSchema, Schema, TypeHook, Violation};
+    use toon::{JsonPrimitive, StringOrNumberOrBoolOrNull};
+
+    let date = TypeHook::new("date", |value: &JsonPrimitive| match value {
+        StringOrNumberOrBoolOrNull::String(text)
+            if text.len() == 10 && text.as_bytes()[4] == b'-' && text.as_bytes()[7] == b'-' =>
+        {
+            Ok(StringOrNumberOrBoolOrNull::String(format!(
+                "{text}T00:00:00Z"
+            )))
+        }
+        _ => Err("expected a YYYY-MM-DD date".to_string()),
+    });
+    let schema = Schema::Object(ObjectSchema {
+        fields: vec![
+            Field::required("id", Schema::Integer),
+            Field::required("code", Schema::String),
+            Field::required("status", Schema::Enum(vec!["open".into(), "closed".into()])),
+            Field::optional("since", Schema::Custom(date)),
+            Field::required(
+                "rows",
+                Schema::Array(Box::new(Schema::Object(ObjectSchema {
+                    fields: vec![
+                        Field::required("n", Schema::Integer),
+                        Field::required("ok", Schema::Bool),
+                    ],
+                    allow_unknown: false,
+                }))),
+            ),
+            Field::required("owner", Schema::String),
+        ],
+        allow_unknown: false,
+    });
+
+    let doc = "id: \"42\"\ncode: 7\nstatus: pending\nsince: 2024-01-05\nrows[2]{n,ok}:\n  1,\"true\"\n  2.5,false\nextra: 1";
+    let (value, violations) = decode_with_schema(doc, &schema, None);
+    assert_eq!(
+        value,
+        JsonValue::from(serde_json::json!({
+            "id": 42,
+            "code": "7",
+            "status": "pending",
+            "since": "2024-01-05T00:00:00Z",
+            "rows": [{"n": 1, "ok": true}, {"n": 2.5, "ok": false}],
+            "extra": 1,
+        }))
+    );
+    let key = |key: &str| PathSegment::Key(key.to_string());
+    assert_eq!(
+        violations,
+        vec![
+            Violation {
+                path: vec![key("status")],
+                message: "expected one of open, closed, found pending".to_string(),
+            },
+            Violation {
+                path: vec![key("rows"), PathSegment::Index(1), key("n")],
+                message: "expected integer, found number 2.5".to_string(),
+            },
+            Violation {
+                path: vec![key("extra")],
+                message: "unknown field".to_string(),
+            },
+            Violation {
+                path: vec![],
+                message: "missing required field owner".to_string(),
+            },
+        ]
+    );
+    assert_eq!(
+        violations[1].to_string(),
+        "rows[1].n: expected integer, found number 2.5"
+    );
+
+    let (_, violations) = try_decode_with_schema("since: soon\nrows: 3", &schema, None).unwrap();
+    let messages: Vec<String> = violations.iter().map(ToString::to_string).collect();
+    assert!(messages.contains(&"since: expected a YYYY-MM-DD date".to_string()));
+    assert!(messages.contains(&"rows: expected array, found number".to_string()));
+    assert!(try_decode_with_schema("rows[3]: 1,2", &schema, None).is_err());
+}
+
+#[test]
+fn schema_infer_merges_array_items() {
+    use toon::schema::{Field, ObjectSchema, Schema};
+
+    let sample = JsonValue::from(serde_json::json!({
+        "users": [
+            {"id": 1, "name": "Ada", "score": 1},
+            {"id": 2, "name": null, "score": 2.5, "admin": true},
+        ],
+        "mixed": [1, "a"],
+    }));
+    assert_eq!(
+        Schema::infer(&sample),
+        Schema::Object(ObjectSchema {
+            fields: vec![
+                Field::required(
+                    "users",
+                    Schema::Array(Box::new(Schema::Object(ObjectSchema {
+                        fields: vec![
+                            Field::required("id", Schema::Integer),
+                            Field::required("name", Schema::String),
+                            Field::required("score", Schema::Number),
+                            Field::optional("admin", Schema::Bool),
+                        ],
+                        allow_unknown: false,
+                    }))),
+                ),
+                Field::required("mixed", Schema::Array(Box::new(Schema::Any))),
+            ],
+            allow_unknown: false,
+        })
+    );
+
+    // A document decodes against the schema inferred from it without violations.
+    let toon_text = encode(sample.clone(), None);
+    let (_, violations) =
+        toon::decode::decode_with_schema(&toon_text, &Schema::infer(&sample), None);
+    assert!(violations.is_empty(), "{violations:?}");
+}
+
+#[test]
+fn encode_with_schema_rejects_mismatched_payloads() {
+    use toon::encode::{encode_with_schema, try_encode_with_schema};
+    use toon::schema::{Field, ObjectSchema, Schema};
+
+    let schema = Schema::Object(ObjectSchema {
+        fields: vec![
+            Field::required("id", Schema::Integer),
+            Field::required("name", Schema::String),
+            Field::optional("tags", Schema::Array(Box::new(Schema::String))),
+        ],
+        allow_unknown: false,
+    });
+
+    let good = serde_json::json!({"id": 1, "name": "Ada", "tags": ["x"]});
+    assert_eq!(
+        encode_with_schema(good.clone(), &schema, None),
+        encode(good, None)
+    );
+
+    let bad = JsonValue::from(serde_json::json!({"id": "1", "tags": ["x", 2], "extra": true}));
+    let messages: Vec<String> = schema
+        .validate(&bad)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "id: expected integer, found string 1",
+            "tags[1]: expected string, found number 2",
+            "extra: unknown field",
+            "missing required field name",
+        ]
+    );
+
+    let err = try_encode_with_schema(bad, &schema, None).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Encode error at 'id': 4 schema violations: id: expected integer, found string 1; \
+         tags[1]: expected string, found number 2; extra: unknown field; \
+         missing required field name"
+    );
+}
+
+// ============================================================================
+// HEADERS
+// ============================================================================
+
+#[test]
+fn header_builder_round_trips_through_parse_header() {
+    use toon::header::{HeaderBuilder, format_header, parse_header};
+    use toon::options::ColumnType;
+
+    assert_eq!(HeaderBuilder::new(3).build(), "[3]:");
+    assert_eq!(
+        HeaderBuilder::new(2)
+            .key("my users")
+            .fields(["id", "name"])
+            .build(),
+        "\"my users\"[2]{id,name}:"
+    );
+    assert_eq!(
+        HeaderBuilder::new(2).key("rows").fields(["a", "b"]).build(),
+        format_header(
+            2,
+            Some("rows"),
+            Some(&["a".to_string(), "b".to_string()]),
+            ','
+        )
+    );
+
+    let line = HeaderBuilder::new(4)
+        .key("events")
+        .typed_field("id", ColumnType::Integer)
+        .field("note")
+        .delimiter('\t')
+        .build();
+    let parsed = parse_header(&line).unwrap().unwrap();
+    assert_eq!(parsed.header.key.as_deref(), Some("events"));
+    assert_eq!(parsed.header.length, 4);
+    assert_eq!(parsed.header.delimiter, '\t');
+    let fields = parsed.header.fields.clone().unwrap();
+    assert_eq!(fields[0].name, "id");
+    assert_eq!(fields[0].column_type, Some(ColumnType::Integer));
+    assert_eq!(fields[1].column_type, None);
+    assert_eq!(HeaderBuilder::from(parsed.header).build(), line);
+
+    let inline = parse_header("tags[2]: a,b").unwrap().unwrap();
+    assert_eq!(inline.inline_values.as_deref(), Some("a,b"));
+    assert!(parse_header("name: Ada").unwrap().is_none());
+}
+
+// ============================================================================
+// TABULAR ROWS
+// ============================================================================
+
+#[test]
+fn tabular_reader_streams_rows_and_writer_round_trips() {
+    use serde::{Deserialize, Serialize};
+    use toon::StringOrNumberOrBoolOrNull as P;
+    use toon::header::HeaderBuilder;
+    use toon::tabular::{TabularReader, TabularWriter};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct User {
+        id: u32,
+        name: String,
+    }
+
+    let input = "meta:\n  source: test\ndata:\n  users[2|]{id:int|name}:\n    1|Ada\n    2|\"Grace H\"\nafter: true\n";
+    let mut reader = TabularReader::find(input.as_bytes(), Some("users")).unwrap();
+    assert_eq!(reader.header().length, 2);
+    assert_eq!(reader.fields()[1].name, "name");
+    assert_eq!(
+        reader.next().unwrap().unwrap(),
+        [P::Number(1.0), P::String("Ada".to_string())]
+    );
+    let user: User = reader.next_as().unwrap().unwrap();
+    assert_eq!(user.name, "Grace H");
+    assert!(reader.next().is_none());
+
+    let users: Vec<User> = TabularReader::find(input.as_bytes(), None)
+        .unwrap()
+        .deserialize()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        users[0],
+        User {
+            id: 1,
+            name: "Ada".to_string()
+        }
+    );
+
+    let header = HeaderBuilder::new(2).key("users").fields(["id", "name"]);
+    let mut writer = TabularWriter::new(Vec::new(), &header, 2).unwrap();
+    for user in &users {
+        writer.serialize_row(user).unwrap();
+    }
+    let written = String::from_utf8(writer.finish().unwrap()).unwrap();
+    assert_eq!(written, "users[2]{id,name}:\n  1,Ada\n  2,Grace H\n");
+    let reread: Vec<User> = TabularReader::new(written.as_bytes())
+        .unwrap()
+        .deserialize()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(reread, users);
+
+    let short = "rows[3]{a,b}:\n  1,2\n  3,4,5\n";
+    let mut reader = TabularReader::new(short.as_bytes()).unwrap();
+    assert!(reader.next().unwrap().is_ok());
+    let err = reader.next().unwrap().unwrap_err();
+    assert!(err.to_string().contains("Expected 2 tabular row values"));
+    assert!(reader.next().is_none());
+    let err = TabularReader::new(&b"rows[2]{a}:\n  1\n"[..])
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Expected 2 tabular rows, but got 1");
+    assert!(TabularReader::new(&b"name: Ada\n"[..]).is_err());
+
+    let mut writer = TabularWriter::new(Vec::new(), &header, 2).unwrap();
+    writer
+        .write_row(&[P::Number(1.0), P::String("Ada".to_string())])
+        .unwrap();
+    assert!(writer.write_row(&[P::Number(2.0)]).is_err());
+    assert!(writer.finish().is_err());
+}
+
+// ============================================================================
+// OPTIONS SERDE
+// ============================================================================
+
+#[test]
+fn options_load_from_json_config() {
+    let options: EncodeOptions = serde_json::from_str(
+        r#"{"indent":4,"delimiter":"tab","keyFolding":"safe","longString":{"truncate":{"max":3,"suffix":"~"}}}"#,
+    )
+    .unwrap();
+    assert_eq!(options.delimiter, Some('\t'));
+    assert_eq!(options.key_folding, Some(KeyFoldingMode::Safe));
+    let value: JsonValue = serde_json::json!({"a": {"b": "abcdef"}}).into();
+    assert_eq!(encode(value, Some(options.clone())), "a.b: abc~");
+
+    let echoed = serde_json::to_value(&options).unwrap();
+    assert_eq!(echoed["delimiter"], "\t");
+    assert_eq!(echoed["longString"]["truncate"]["max"], 3);
+    let reloaded: EncodeOptions = serde_json::from_value(echoed).unwrap();
+    assert_eq!(reloaded.indent, Some(4));
+
+    let options: DecodeOptions = serde_json::from_str(
+        r#"{"strict":false,"missingCell":"omit","columnTypes":{"id":"string"}}"#,
+    )
+    .unwrap();
+    assert_eq!(options.missing_cell, Some(MissingCellMode::Omit));
+    assert_eq!(options.column_types.unwrap()["id"], ColumnType::String);
+
+    let err = serde_json::from_str::<EncodeOptions>(r#"{"delimiter":";"}"#)
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("Invalid delimiter"));
+    assert!(serde_json::from_str::<DecodeOptions>(r#"{"stict":true}"#).is_err());
+}
+
+#[test]
+fn option_presets() {
+    let value: JsonValue = serde_json::json!({"a": {"b": ["x", ""]}}).into();
+    let compact = encode(value.clone(), Some(EncodeOptions::llm_compact()));
+    assert_eq!(compact, "a.b[2\t]: x\t\"\"");
+    let readable = encode(value.clone(), Some(EncodeOptions::human_readable()));
+    assert_eq!(
+        readable,
+        "#%toon version=1 delimiter=, indent=4\na:\n    b[2]: x,\"\""
+    );
+    assert_eq!(decode(&readable, None), value);
+    assert_eq!(
+        encode(value.clone(), Some(EncodeOptions::canonical())),
+        encode(value, None)
+    );
+
+    let damaged = "rows[3]{a,b}:\n\t1,\n  2";
+    assert!(try_decode(damaged, Some(DecodeOptions::strict())).is_err());
+    let lenient: serde_json::Value = try_decode(damaged, Some(DecodeOptions::lenient()))
+        .unwrap()
+        .into();
+    assert_eq!(lenient["rows"][0]["b"], "");
+    let repaired: serde_json::Value = try_decode(damaged, Some(DecodeOptions::repair()))
+        .unwrap()
+        .into();
+    assert!(repaired["rows"][0]["b"].is_null());
+    assert!(repaired["rows"][1]["b"].is_null());
+}
+
+// ============================================================================
+// SERDE KEY ORDER
+// ============================================================================
+
+#[test]
+fn serde_key_order_controls_emitted_keys() {
+    let value = serde_json::json!({
+        "name": "Ada",
+        "id": 1,
+        "tags": [{"b": 2, "a": 1}],
+    });
+    let encode_with = |order| {
+        let options = EncodeOptions {
+            serde_key_order: Some(order),
+            ..EncodeOptions::default()
+        };
+        encode(value.clone(), Some(options))
+    };
+
+    assert_eq!(
+        encode_with(SerdeKeyOrder::AsIs),
+        "name: Ada\nid: 1\ntags[1]{b,a}:\n  2,1"
+    );
+    assert_eq!(
+        encode_with(SerdeKeyOrder::Sorted),
+        "id: 1\nname: Ada\ntags[1]{a,b}:\n  1,2"
+    );
+    assert_eq!(
+        encode_with(SerdeKeyOrder::SchemaOrder(vec![
+            "id".to_string(),
+            "a".to_string()
+        ])),
+        "id: 1\nname: Ada\ntags[1]{a,b}:\n  1,2"
+    );
+
+    let options: EncodeOptions =
+        serde_json::from_str(r#"{"serdeKeyOrder":{"schemaOrder":["tags"]}}"#).unwrap();
+    assert!(encode(value, Some(options)).starts_with("tags[1]"));
+}
+
+// ============================================================================
+// BORROWED SERDE ENCODING
+// ============================================================================
+
+#[test]
+fn encode_serde_matches_encode() {
+    let value = serde_json::json!({
+        "name": "Ada, Countess",
+        "zero": -0.0,
+        "big": u64::MAX,
+        "ratio": 0.25,
+        "a": {"b": {"c": [1, 1, 1, null, 2]}},
+        "rows": [{"id": 1, "tag": ""}, {"id": 2, "tag": "x|y"}],
+        "mixed": [[1, 2], {"k": [{"x": 1}]}, "s"],
+        "empty": {},
+    });
+    let cases = [
+        EncodeOptions::default(),
+        EncodeOptions {
+            key_folding: Some(KeyFoldingMode::Safe),
+            delimiter: Some('|'),
+            typed_headers: Some(true),
+            run_length: Some(3),
+            sparse_threshold: Some(0.2),
+            empty_string: Some(EmptyStringStyle::Bare),
+            emit_directive: Some(true),
+            ..EncodeOptions::default()
+        },
+        EncodeOptions {
+            serde_key_order: Some(SerdeKeyOrder::Sorted),
+            columnar: Some(true),
+            ..EncodeOptions::default()
+        },
+    ];
+    for options in cases {
+        assert_eq!(
+            encode_serde(&value, Some(options.clone())),
+            encode(value.clone(), Some(options))
+        );
+    }
+}
+
+// ============================================================================
+// COLUMN ORDER
+// ============================================================================
+
+#[test]
+fn column_order_puts_priority_fields_first() {
+    let value = serde_json::json!({
+        "users": [
+            {"email": "a@x", "name": "Ada", "id": 1},
+            {"email": "g@x", "name": "Grace", "id": 2},
+        ],
+        "items": [{"b": 1, "a": [1]}, {"b": 2, "a": [2]}],
+    });
+    let options = EncodeOptions {
+        column_order: Some(column_priority(["id", "name", "missing"])),
+        ..EncodeOptions::default()
+    };
+    let toon = encode(value.clone(), Some(options));
+    assert!(toon.starts_with("users[2]{id,name,email}:\n  1,Ada,a@x\n  2,Grace,g@x\n"));
+    assert!(toon.ends_with("items[2]:\n  - b: 1\n    a[1]: 1\n  - b: 2\n    a[1]: 2"));
+
+    let options = EncodeOptions {
+        column_order: Some(Arc::new(|key: &str, fields: &[String]| {
+            assert_eq!(key, "users");
+            fields.iter().rev().cloned().collect()
+        })),
+        ..EncodeOptions::default()
+    };
+    let users = serde_json::json!({"users": value["users"].clone()});
+    assert_eq!(
+        encode(users, Some(options)),
+        "users[2]{id,name,email}:\n  1,Ada,a@x\n  2,Grace,g@x"
+    );
+}
+
+// ============================================================================
+// COLUMN PROJECTION
+// ============================================================================
+
+#[test]
+fn columns_keep_selected_fields_of_matching_arrays() {
+    let value = serde_json::json!({
+        "data": {"users": [
+            {"id": 1, "name": "Ada", "meta": {"admin": true}},
+            {"id": 2, "name": "Bo", "meta": {"admin": false}, "extra": 1},
+            "not an object",
+        ]},
+        "orders": [
+            {"id": 7, "items": [{"sku": "a", "qty": 1, "tags": ["x"]}]},
+        ],
+        "users": [{"id": 3, "meta": {}}],
+    });
+    let columns = HashMap::from([
+        (
+            PathPattern::new("data.users"),
+            vec!["name".to_string(), "id".to_string()],
+        ),
+        (
+            PathPattern::new("orders.*"),
+            vec!["sku".to_string(), "qty".to_string()],
+        ),
+    ]);
+    let options = EncodeOptions {
+        columns: Some(columns),
+        ..EncodeOptions::default()
+    };
+    assert_eq!(
+        encode(value, Some(options)),
+        "data:\n  users[3]:\n    - name: Ada\n      id: 1\n    - name: Bo\n      id: 2\n    - not an object\norders[1]:\n  - id: 7\n    items[1]{sku,qty}:\n      a,1\nusers[1]:\n  - id: 3\n    meta:"
+    );
+
+    assert!(PathPattern::new("").matches(&[]));
+    assert!(!PathPattern::new("users").matches(&[]));
+    let options: EncodeOptions = serde_json::from_str(r#"{"columns":{"":["id"]}}"#).unwrap();
+    assert_eq!(
+        encode(serde_json::json!([{"id": 1, "x": 2}]), Some(options)),
+        "[1]{id}:\n  1"
+    );
+}
+
+// ============================================================================
+// ROW FILTER
+// ============================================================================
+
+#[test]
+fn row_filter_drops_rows_and_recomputes_lengths() {
+    let value = serde_json::json!({
+        "users": [
+            {"id": 1, "active": true},
+            {"id": 2, "active": false},
+            {"id": 3, "active": true},
+        ],
+        "mixed": [{"active": false}, 1],
+    });
+    let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorder = Arc::clone(&seen);
+    let options = EncodeOptions {
+        row_filter: Some(Arc::new(move |path: &[PathSegment], row: &JsonObject| {
+            recorder.lock().unwrap().push(format_path(path));
+            row.iter()
+                .any(|(key, value)| key == "active" && *value == JsonValue::from(true))
+        })),
+        columns: Some(HashMap::from([(
+            PathPattern::new("users"),
+            vec!["id".to_string()],
+        )])),
+        ..EncodeOptions::default()
+    };
+    assert_eq!(
+        encode(value, Some(options)),
+        "users[2]{id}:\n  1\n  3\nmixed[2]:\n  - active: false\n  - 1"
+    );
+    assert_eq!(*seen.lock().unwrap(), ["users[0]", "users[1]", "users[2]"]);
+}
+
+// ============================================================================
+// ROW ENCODER
+// ============================================================================
+
+#[test]
+fn row_encoder_lays_out_rows_but_cannot_change_cells() {
+    use toon::encode::rows::RowEncoder;
+
+    /// Pads every cell but the last to its column's width.
+    struct Aligned;
+    impl RowEncoder for Aligned {
+        fn encode_rows(&self, rows: &[Vec<String>], delimiter: char) -> Vec<String> {
+            let widths: Vec<usize> = (0..rows[0].len())
+                .map(|column| rows.iter().map(|row| row[column].len()).max().unwrap())
+                .collect();
+            rows.iter()
+                .map(|row| {
+                    let (last, rest) = row.split_last().unwrap();
+                    let mut cells: Vec<String> = rest
+                        .iter()
+                        .zip(&widths)
+                        .map(|(cell, width)| format!("{cell:<width$}"))
+                        .collect();
+                    cells.push(last.clone());
+                    cells.join(&format!("{delimiter} "))
+                })
+                .collect()
+        }
+    }
+    struct Dropping;
+    impl RowEncoder for Dropping {
+        fn encode_rows(&self, rows: &[Vec<String>], _: char) -> Vec<String> {
+            rows.iter().skip(1).map(|row| row.join(",")).collect()
+        }
+    }
+    struct Rewriting;
+    impl RowEncoder for Rewriting {
+        fn encode_rows(&self, rows: &[Vec<String>], delimiter: char) -> Vec<String> {
+            rows.iter()
+                .map(|row| row.join(&delimiter.to_string()).replace("Ada", "Eve"))
+                .collect()
+        }
+    }
+    struct Indenting;
+    impl RowEncoder for Indenting {
+        fn encode_rows(&self, rows: &[Vec<String>], delimiter: char) -> Vec<String> {
+            rows.iter()
+                .map(|row| format!(" {}", row.join(&delimiter.to_string())))
+                .collect()
+        }
+    }
+
+    let value = serde_json::json!({
+        "users": [
+            {"id": 7, "name": "Ada", "note": "a|b"},
+            {"id": 1024, "name": "Grace Hopper", "note": ""},
+        ],
+        "teams": [{"crew": [{"id": 1, "x": true}, {"id": 22, "x": false}]}],
+    });
+    let with = |encoder: Arc<dyn RowEncoder>| EncodeOptions {
+        delimiter: Some('|'),
+        row_encoder: Some(encoder),
+        ..EncodeOptions::default()
+    };
+
+    let text = try_encode(value.clone(), Some(with(Arc::new(Aligned)))).unwrap();
+    assert_eq!(
+        text,
+        "users[2|]{id|name|note}:\n  7   | Ada         | \"a|b\"\n  1024| Grace Hopper| \"\"\n\
+         teams[1|]:\n  - crew[2|]{id|x}:\n      1 | true\n      22| false"
+    );
+    assert_eq!(decode(&text, None), JsonValue::from(value.clone()));
+
+    let error = try_encode(value.clone(), Some(with(Arc::new(Dropping)))).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("at 'users': row encoder returned 1 lines for 2 rows"),
+        "{error}"
+    );
+    let error = try_encode(value.clone(), Some(with(Arc::new(Rewriting)))).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("at 'users[0]': row encoder output does not read back"),
+        "{error}"
+    );
+    let error = try_encode(value, Some(with(Arc::new(Indenting)))).unwrap_err();
+    assert!(
+        error.to_string().contains("starts with whitespace"),
+        "{error}"
+    );
+}
+
+#[test]
+fn align_columns_pads_cells_and_strict_decode_reads_them_back() {
+    let value = serde_json::json!({
+        "rows": [
+            {"id": 7, "city": "Zürich", "tag": "a b", "note": ""},
+            {"id": 1024, "city": "Oslo", "tag": "x", "note": "n"},
+        ],
+    });
+    for (delimiter, expected) in [
+        (
+            ',',
+            "rows[2]{id,city,tag,note}:\n  7   ,Zürich,a b,\"\"\n  1024,Oslo  ,x  ,n",
+        ),
+        (
+            '\t',
+            "rows[2\t]{id\tcity\ttag\tnote}:\n  7   \tZürich\ta b\t\"\"\n  1024\tOslo  \tx  \tn",
+        ),
+    ] {
+        let options = EncodeOptions {
+            delimiter: Some(delimiter),
+            align_columns: Some(true),
+            ..EncodeOptions::default()
+        };
+        let text = encode(value.clone(), Some(options));
+        assert_eq!(text, expected);
+        let strict = DecodeOptions {
+            strict: Some(true),
+            ..DecodeOptions::default()
+        };
+        assert_eq!(
+            try_decode(&text, Some(strict)).unwrap(),
+            JsonValue::from(value.clone())
+        );
+    }
+
+    // Padding is not canonical, so pedantic decoding still reports it.
+    let aligned = encode(
+        value,
+        Some(EncodeOptions {
+            align_columns: Some(true),
+            ..EncodeOptions::default()
+        }),
+    );
+    let pedantic = DecodeOptions {
+        pedantic: Some(true),
+        ..DecodeOptions::default()
+    };
+    assert!(try_decode(&aligned, Some(pedantic)).is_err());
+
+    let options: EncodeOptions = serde_json::from_str(r#"{"alignColumns":true}"#).unwrap();
+    assert_eq!(
+        encode(
+            serde_json::json!([{"a": 10, "b": 1}, {"a": 1, "b": 2}]),
+            Some(options)
+        ),
+        "[2]{a,b}:\n  10,1\n  1 ,2"
+    );
+}
+
+// ============================================================================
+// PAGINATION
+// ============================================================================
+
+#[test]
+fn encode_paginated_splits_the_longest_array_and_reassembles() {
+    use toon::paginate::{
+        PageInfo, decode_paginated, encode_paginated, page_info, try_decode_paginated,
+    };
+
+    let value = serde_json::json!({
+        "meta": {"source": "db"},
+        "data": {"my users": [{"id": 1}, {"id": 2}, {"id": 3}, {"id": 4}, {"id": 5}]},
+        "tags": ["a", "b", "c"],
+    });
+    let pages = encode_paginated(value.clone(), 2, None);
+    assert_eq!(
+        pages,
+        [
+            "#%toon version=1 delimiter=, indent=2 page=1 total=3 path=#/data/my%20users\nmeta:\n  source: db\ndata:\n  \"my users\"[2]{id}:\n    1\n    2\ntags[3]: a,b,c",
+            "#%toon version=1 delimiter=, indent=2 page=2 total=3 path=#/data/my%20users\ndata:\n  \"my users\"[2]{id}:\n    3\n    4",
+            "#%toon version=1 delimiter=, indent=2 page=3 total=3 path=#/data/my%20users\ndata:\n  \"my users\"[1]{id}:\n    5",
+        ]
+    );
+    assert_eq!(
+        page_info(&pages[1]).unwrap(),
+        Some(PageInfo {
+            page: 2,
+            total: 3,
+            path: vec!["data".to_string(), "my users".to_string()],
+        })
+    );
+    let reversed = pages.iter().rev().collect::<Vec<_>>();
+    assert_eq!(decode_paginated(&reversed, None), JsonValue::from(value));
+
+    let err = try_decode_paginated(&pages[..2], None).unwrap_err();
+    assert!(
+        err.to_string().contains("Expected 3 pages, found 2"),
+        "{err}"
+    );
+    let err = try_decode_paginated(&[&pages[0], &pages[0], &pages[2]], None).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Expected page 2 of 3, found page 1"),
+        "{err}"
+    );
+    assert!(try_decode_paginated(&["a: 1"], None).is_err());
+
+    let root = serde_json::json!([1, 2, 3]);
+    let pages = encode_paginated(root.clone(), 2, None);
+    assert_eq!(
+        pages[1],
+        "#%toon version=1 delimiter=, indent=2 page=2 total=2 path=#\n[1]: 3"
+    );
+    assert_eq!(decode_paginated(&pages, None), JsonValue::from(root));
+
+    let small = encode_paginated(serde_json::json!({"a": [1]}), 5, None);
+    assert_eq!(
+        small,
+        ["#%toon version=1 delimiter=, indent=2 page=1 total=1\na[1]: 1"]
+    );
+    assert_eq!(
+        decode_paginated(&small, None),
+        JsonValue::from(serde_json::json!({"a": [1]}))
+    );
+    assert_eq!(page_info("a: 1").unwrap(), None);
+    assert!(toon::paginate::try_encode_paginated(serde_json::json!([]), 0, None).is_err());
+}
+
+#[test]
+fn split_by_token_budget_chunks_rows_and_repeats_context() {
+    use toon::paginate::{split_by_token_budget, try_split_by_token_budget};
+
+    let rows = (0..40)
+        .map(|id| serde_json::json!({"id": id, "name": format!("user number {id}")}))
+        .collect::<Vec<_>>();
+    let value = serde_json::json!({
+        "report": "weekly",
+        "data": {"region": "eu", "rows": rows},
+        "notes": ["checked"],
+    });
+    let chunks = split_by_token_budget(value, 60, None);
+    assert!(chunks.len() > 2, "{chunks:?}");
+
+    let mut ids = Vec::new();
+    for chunk in &chunks {
+        assert!(toon::shared::string_utils::estimate_tokens(&encode(chunk.clone(), None)) <= 60);
+        assert_eq!(chunk.get("report"), Some(&JsonValue::from("weekly")));
+        if let Some(data) = chunk.get("data") {
+            assert_eq!(data.get("region"), Some(&JsonValue::from("eu")));
+            let Some(JsonValue::Array(rows)) = data.get("rows") else {
+                panic!("chunk without rows: {chunk:?}");
+            };
+            ids.extend(rows.iter().map(|row| row.get("id").cloned()));
+        }
+    }
+    assert_eq!(
+        ids,
+        (0..40)
+            .map(|id| Some(JsonValue::from(f64::from(id))))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        chunks.last().unwrap(),
+        &JsonValue::from(serde_json::json!({"report": "weekly", "notes": ["checked"]}))
+    );
+
+    let root = split_by_token_budget(serde_json::json!(["a b c d", "e f g h", "i j"]), 5, None);
+    assert_eq!(
+        root,
+        [
+            JsonValue::from(serde_json::json!(["a b c d"])),
+            JsonValue::from(serde_json::json!(["e f g h"])),
+            JsonValue::from(serde_json::json!(["i j"])),
+        ]
+    );
+    let small = serde_json::json!({"a": [1, 2]});
+    assert_eq!(
+        split_by_token_budget(small.clone(), 100, None),
+        [JsonValue::from(small)]
+    );
+    assert!(try_split_by_token_budget(serde_json::json!([]), 0, None).is_err());
+}
+
+// ============================================================================
+// DECODE TRACE
+// ============================================================================
+
+#[test]
+fn trace_decode_attributes_events_and_checks_to_lines() {
+    use toon::JsonStreamEvent;
+    use toon::decode::trace::trace_decode;
+
+    let trace = trace_decode("#%toon version=1 indent=2\nrows[2]{id}:\n  1\n  2\n", None);
+    assert!(trace.error.is_none());
+    assert!(trace.leading_events.is_empty());
+    let numbers = trace
+        .lines
+        .iter()
+        .map(|line| line.line_number)
+        .collect::<Vec<_>>();
+    assert_eq!(numbers, [2, 3, 4]);
+    assert_eq!(trace.lines[1].depth, 1);
+    assert_eq!(
+        trace.lines[0].events,
+        [
+            JsonStreamEvent::StartObject,
+            JsonStreamEvent::Key {
+                key: "rows".to_string(),
+                was_quoted: false,
+            },
+            JsonStreamEvent::StartArray { length: 2 },
+        ]
+    );
+    assert_eq!(
+        trace.lines[2].events.last(),
+        Some(&JsonStreamEvent::EndObject)
+    );
+    assert_eq!(
+        trace.lines[2].checks,
+        [
+            "tabular row values: expected 1, found 1",
+            "tabular rows: expected 2, found 2",
+            "no blank lines inside tabular array (lines 3-4)",
+            "no tabular rows beyond 2",
+        ]
+    );
+
+    let lenient = trace_decode(
+        "rows[2]{id}:\n  1",
+        Some(DecodeOptions {
+            strict: Some(false),
+            ..DecodeOptions::default()
+        }),
+    );
+    assert!(lenient.error.is_none());
+    assert!(lenient.lines.iter().all(|line| line.checks.is_empty()));
+
+    let failed = trace_decode("a: 1\nb[2]: x", None);
+    assert_eq!(failed.lines.len(), 2);
+    assert!(
+        failed
+            .to_string()
+            .ends_with("error: Expected 2 inline array items, but got 1\n")
+    );
+    assert_eq!(trace_decode("", None).leading_events.len(), 2);
+}
+
+// ============================================================================
+// TRANSFORM SPEC
+// ============================================================================
+
+#[test]
+fn transform_spec_compiles_to_replacer() {
+    use toon::encode::replacer::{TransformSpec, from_spec};
+
+    let value = serde_json::json!({
+        "users": [
+            {"id": 1, "password": "x", "email": "a@x", "bio": "héllo world", "score": 4.56789},
+            {"id": 2, "password": "y", "email": "b@x", "bio": "hi", "score": 2.0},
+        ],
+        "tags": ["alpha", "beta"],
+        "total": 1234.5,
+    });
+    let toml = r#"
+        [[rules]]
+        path = "users.password"
+        drop = true
+
+        [[rules]]
+        path = "users.email"
+        rename = "contact"
+
+        [[rules]]
+        path = "users.bio"
+        truncate = 5
+
+        [[rules]]
+        path = "users.score"
+        quantize = 0.01
+
+        [[rules]]
+        path = "tags"
+        truncate = 2
+
+        [[rules]]
+        path = "total"
+        quantize = 100
+    "#;
+    let options = EncodeOptions {
+        replacer: Some(from_spec(toml).unwrap()),
+        ..EncodeOptions::default()
+    };
+    assert_eq!(
+        encode(value.clone(), Some(options)),
+        "users[2]{id,contact,bio,score}:\n  1,a@x,héllo,4.57\n  2,b@x,hi,2\ntags[2]: al,be\ntotal: 1200"
+    );
+
+    let json =
+        r#"{"rules": [{"path": "users.*", "drop": true}, {"path": "", "rename": "ignored"}]}"#;
+    let spec = TransformSpec::parse(json).unwrap();
+    assert_eq!(spec.rules.len(), 2);
+    let options = EncodeOptions {
+        replacer: Some(spec.into_replacer()),
+        ..EncodeOptions::default()
+    };
+    assert_eq!(
+        encode(value, Some(options)),
+        "users[2]:\n  -\n  -\ntags[2]: alpha,beta\ntotal: 1234.5"
+    );
+
+    let err = from_spec("[[rules]]\npath = \"a\"\nquantize = 0")
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("quantize step for a"), "{err}");
+    assert!(from_spec(r#"{"rules": [{"path": "a", "hide": true}]}"#).is_err());
+    assert!(from_spec("").unwrap()("", &JsonValue::from(1.0), &[]).is_some());
+}
+
+// ============================================================================
+// ENCODER AND DECODER HANDLES
+// ============================================================================
+
+#[test]
+fn encoder_and_decoder_are_shared_across_threads() {
+    use toon::{Decoder, Encoder};
+
+    fn assert_shareable<T: Send + Sync + Clone>() {}
+    assert_shareable::<Encoder>();
+    assert_shareable::<Decoder>();
+
+    let encoder = Arc::new(Encoder::new(Some(EncodeOptions::llm_compact())));
+    let decoder = Decoder::new(Some(DecodeOptions {
+        expand_paths: Some(ExpandPathsMode::Safe),
+        ..DecodeOptions::default()
+    }));
+    assert_eq!(encoder.options().delimiter, '\t');
+
+    let handles = (0..4)
+        .map(|id| {
+            let encoder = Arc::clone(&encoder);
+            let decoder = decoder.clone();
+            std::thread::spawn(move || {
+                let value = serde_json::json!({"req": {"id": id, "tags": ["a", "b"]}});
+                let text = encoder.encode(&value);
+                (text.clone(), decoder.decode(&text), JsonValue::from(value))
+            })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        let (text, round_trip, value) = handle.join().unwrap();
+        assert_eq!(round_trip, value);
+        assert_eq!(text, encode(value, Some(EncodeOptions::llm_compact())));
+    }
+
+    let decoder = Decoder::default();
+    assert!(decoder.try_decode("a[2]: 1").is_err());
+    let mut events = Vec::new();
+    decoder
+        .decode_into_sink(["a: 1".to_string()], &mut events)
+        .unwrap();
+    assert_eq!(events.len(), 4);
+    assert_eq!(Encoder::default().encode(JsonValue::from(1.0)), "1");
+}
+
+// ============================================================================
+// BATCH CONVERSION
+// ============================================================================
+
+#[test]
+fn convert_batch_keeps_job_order_and_collects_failures() {
+    use toon::batch::{
+        ConversionJob, Direction, SymlinkPolicy, batch_error, convert_batch, jobs_for_paths,
+    };
+
+    let temp = tempfile::TempDir::new().unwrap();
+    let data = temp.path().join("data");
+    std::fs::create_dir_all(data.join("nested")).unwrap();
+    std::fs::write(data.join("b.json"), r#"{"tags":["x","y"]}"#).unwrap();
+    std::fs::write(data.join("a.json"), "not json").unwrap();
+    std::fs::write(data.join("nested/c.toon"), "id: 7").unwrap();
+    std::fs::write(data.join("notes.txt"), "skipped").unwrap();
+
+    let out = temp.path().join("out");
+    let mut jobs =
+        jobs_for_paths(std::slice::from_ref(&data), Some(&out), SymlinkPolicy::Skip).unwrap();
+    assert_eq!(jobs.len(), 3);
+    assert_eq!(jobs[2].direction, Direction::Decode);
+    assert_eq!(jobs[2].output, Some(out.join("nested/c.json")));
+    jobs[1].encode_options = Some(EncodeOptions {
+        delimiter: Some('|'),
+        ..EncodeOptions::default()
+    });
+
+    let results = convert_batch(jobs, 2);
+    assert!(
+        results[0]
+            .as_ref()
+            .unwrap_err()
+            .to_string()
+            .contains("a.json")
+    );
+    assert_eq!(results[1].as_ref().unwrap().input_bytes, 18);
+    assert_eq!(
+        std::fs::read_to_string(out.join("b.toon")).unwrap(),
+        "tags[2|]: x|y\n"
+    );
+    assert!(
+        std::fs::read_to_string(out.join("nested/c.json"))
+            .unwrap()
+            .contains("\"id\"")
+    );
+    let error = batch_error(&results).unwrap().to_string();
+    assert!(error.starts_with("1 of 3 files failed to convert:"));
+
+    let mut job = ConversionJob::new(data.join("b.json"));
+    assert_eq!(job.output, Some(data.join("b.toon")));
+    job.output = None;
+    let results = convert_batch(vec![job.clone(), job], 0);
+    assert!(batch_error(&results).is_none());
+    assert_eq!(
+        results[1].as_ref().unwrap().text.as_deref(),
+        Some("tags[2]: x,y")
+    );
+    assert!(jobs_for_paths(&[temp.path().join("missing")], None, SymlinkPolicy::Skip).is_err());
+}
+
+#[test]
+fn plan_batch_reports_actions_without_writing() {
+    use toon::batch::{PlannedAction, SymlinkPolicy, batch_error, jobs_for_paths, plan_batch};
+
+    let temp = tempfile::TempDir::new().unwrap();
+    std::fs::write(temp.path().join("new.json"), r#"{"id":1}"#).unwrap();
+    std::fs::write(temp.path().join("same.json"), r#"{"id":2}"#).unwrap();
+    std::fs::write(temp.path().join("same.toon"), "id: 2\n").unwrap();
+    std::fs::write(temp.path().join("stale.json"), r#"{"id":3}"#).unwrap();
+    std::fs::write(temp.path().join("stale.toon"), "id: 0\n").unwrap();
+    std::fs::write(temp.path().join("broken.json"), "{").unwrap();
+
+    let paths =
+        ["broken.json", "new.json", "same.json", "stale.json"].map(|name| temp.path().join(name));
+    let mut jobs = jobs_for_paths(&paths, None, SymlinkPolicy::Skip).unwrap();
+    // Next to their inputs, jobs leave differing files alone unless told.
+    let refused = plan_batch(jobs.clone(), 2);
+    assert!(
+        refused[3]
+            .as_ref()
+            .unwrap_err()
+            .to_string()
+            .contains("already exists")
+    );
+    for job in &mut jobs {
+        job.overwrite = true;
+    }
+    let plan = plan_batch(jobs, 2);
+    assert!(
+        plan[0]
+            .as_ref()
+            .unwrap_err()
+            .to_string()
+            .contains("broken.json")
+    );
+    let actions: Vec<(PlannedAction, usize)> = plan[1..]
+        .iter()
+        .map(|job| {
+            let job = job.as_ref().unwrap();
+            (job.action, job.output_bytes)
+        })
+        .collect();
+    assert_eq!(
+        actions,
+        vec![
+            (PlannedAction::Convert, 6),
+            (PlannedAction::UpToDate, 6),
+            (PlannedAction::Overwrite, 6),
+        ]
+    );
+    assert!(batch_error(&plan).is_some());
+    assert!(!temp.path().join("new.toon").exists());
+    assert_eq!(
+        std::fs::read_to_string(temp.path().join("stale.toon")).unwrap(),
+        "id: 0\n"
+    );
+}
+
+#[test]
+fn convert_batch_refuses_outputs_that_are_inputs() {
+    use toon::batch::{ConversionJob, SymlinkPolicy, convert_batch, convert_file, jobs_for_paths};
+
+    let temp = tempfile::TempDir::new().unwrap();
+    std::fs::write(temp.path().join("x.json"), r#"{"price":1.50}"#).unwrap();
+    std::fs::write(temp.path().join("x.toon"), "name: Ada").unwrap();
+    std::fs::write(temp.path().join("y.json"), r#"{"id":1}"#).unwrap();
+
+    let jobs = jobs_for_paths(&[temp.path().to_path_buf()], None, SymlinkPolicy::Skip).unwrap();
+    let results = convert_batch(jobs, 0);
+    for result in &results[..2] {
+        let err = result.as_ref().unwrap_err().to_string();
+        assert!(err.contains("is also an input of this batch"), "{err}");
+    }
+    assert!(results[2].is_ok());
+    assert_eq!(
+        std::fs::read_to_string(temp.path().join("x.json")).unwrap(),
+        r#"{"price":1.50}"#
+    );
+    assert_eq!(
+        std::fs::read_to_string(temp.path().join("x.toon")).unwrap(),
+        "name: Ada"
+    );
+
+    // A rerun leaves unchanged outputs alone, but does not replace a
+    // differing file without `overwrite`.
+    assert!(convert_file(&ConversionJob::new(temp.path().join("y.json"))).is_ok());
+    std::fs::write(temp.path().join("y.json"), r#"{"id":2}"#).unwrap();
+    let mut job = ConversionJob::new(temp.path().join("y.json"));
+    assert!(convert_file(&job).is_err());
+    job.overwrite = true;
+    convert_file(&job).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(temp.path().join("y.toon")).unwrap(),
+        "id: 2\n"
+    );
+}
+
+#[test]
+fn convert_batch_refuses_outputs_shared_by_two_inputs() {
+    use toon::batch::{SymlinkPolicy, convert_batch, jobs_for_paths};
+
+    let temp = tempfile::TempDir::new().unwrap();
+    for dir in ["a", "b"] {
+        std::fs::create_dir(temp.path().join(dir)).unwrap();
+    }
+    std::fs::write(temp.path().join("a/c.json"), r#"{"from":"a"}"#).unwrap();
+    std::fs::write(temp.path().join("b/c.json"), r#"{"from":"b"}"#).unwrap();
+    std::fs::write(temp.path().join("b/d.json"), r#"{"id":1}"#).unwrap();
+    let out = temp.path().join("out");
+
+    let paths = ["a/c.json", "b/c.json", "b/d.json"].map(|name| temp.path().join(name));
+    let jobs = jobs_for_paths(&paths, Some(&out), SymlinkPolicy::Skip).unwrap();
+    let results = convert_batch(jobs, 0);
+    for (result, other) in results[..2].iter().zip(["b/c.json", "a/c.json"]) {
+        let err = result.as_ref().unwrap_err().to_string();
+        assert!(err.contains("is also the output of"), "{err}");
+        assert!(err.contains(other), "{err}");
+    }
+    assert!(results[2].is_ok());
+    assert!(!out.join("c.toon").exists());
+}
+
+#[test]
+fn batch_summary_totals_a_run() {
+    use std::time::Duration;
+    use toon::batch::{BatchSummary, ConversionJob, convert_batch};
+
+    let temp = tempfile::TempDir::new().unwrap();
+    let json = temp.path().join("a.json");
+    let toon_path = temp.path().join("b.toon");
+    std::fs::write(
+        &json,
+        r#"{"users":[{"id":1,"name":"a"},{"id":2,"name":"b"}]}"#,
+    )
+    .unwrap();
+    std::fs::write(&toon_path, "id: 7").unwrap();
+    std::fs::write(temp.path().join("c.json"), "{").unwrap();
+
+    let jobs =
+        ["a.json", "b.toon", "c.json"].map(|name| ConversionJob::new(temp.path().join(name)));
+    let results = convert_batch(jobs.to_vec(), 1);
+    let encoded = results[0].as_ref().unwrap();
+    assert!(encoded.toon_tokens < encoded.json_tokens);
+
+    let summary = BatchSummary::new(&results, Duration::from_millis(500));
+    assert_eq!(summary.documents, 3);
+    assert_eq!(summary.failures, 1);
+    assert_eq!(summary.input_bytes, 51 + 5);
+    assert_eq!(
+        summary.json_tokens,
+        encoded.json_tokens + results[1].as_ref().unwrap().json_tokens
+    );
+    assert!(summary.saved_tokens() > 0);
+    assert!((summary.documents_per_second() - 6.0).abs() < f64::EPSILON);
+    assert!((summary.bytes_per_second() - 112.0).abs() < f64::EPSILON);
+    assert!(
+        summary
+            .to_string()
+            .starts_with("3 documents, 1 failed; 56 -> ")
+    );
+    let json = serde_json::Value::from(summary.to_json());
+    assert_eq!(json["failures"], 1);
+    assert_eq!(json["elapsedSeconds"], 0.5);
+
+    let empty = BatchSummary::new(&[], Duration::ZERO);
+    assert!(empty.documents_per_second().abs() < f64::EPSILON);
+}
+
+#[cfg(unix)]
+#[test]
+fn batch_never_writes_through_an_output_symlink() {
+    use std::os::unix::fs::symlink;
+    use toon::batch::{ConversionJob, convert_file};
+
+    let temp = tempfile::TempDir::new().unwrap();
+    std::fs::write(temp.path().join("data.json"), r#"{"id":1}"#).unwrap();
+    std::fs::write(temp.path().join("target.txt"), "keep").unwrap();
+    symlink(
+        temp.path().join("target.txt"),
+        temp.path().join("data.toon"),
+    )
+    .unwrap();
+
+    let error = convert_file(&ConversionJob::new(temp.path().join("data.json"))).unwrap_err();
+    assert!(error.to_string().contains("symbolic link"), "{error}");
+    assert_eq!(
+        std::fs::read_to_string(temp.path().join("target.txt")).unwrap(),
+        "keep"
+    );
+}
+
+#[test]
+fn sandboxed_include_resolver_rejects_escapes() {
+    use toon::options::IncludeResolver;
+
+    let temp = tempfile::TempDir::new().unwrap();
+    let root = temp.path().join("root");
+    std::fs::create_dir_all(root.join("docs")).unwrap();
+    std::fs::write(root.join("shared.toon"), "name: Ada").unwrap();
+    std::fs::write(temp.path().join("secret.toon"), "token: x").unwrap();
+
+    let resolver = IncludeResolver::sandboxed(root.join("docs"), &root);
+    assert_eq!(resolver.resolve("../shared.toon").unwrap(), "name: Ada");
+    for escape in [
+        "../../secret.toon",
+        temp.path().join("secret.toon").to_str().unwrap(),
+    ] {
+        let error = resolver.resolve(escape).unwrap_err().to_string();
+        assert!(error.contains("outside"), "{error}");
+    }
+    assert!(resolver.resolve("missing.toon").is_err());
+}
+
+// ============================================================================
+// ORDERED PARALLEL OUTPUT
+// ============================================================================
+
+#[test]
+fn write_ordered_keeps_input_order_on_any_number_of_workers() {
+    use std::io::Write;
+    use toon::parallel::write_ordered;
+
+    let inputs: Vec<u64> = (0..200).collect();
+    for jobs in [0, 1, 3, 8] {
+        let mut out = Vec::new();
+        write_ordered(
+            inputs.iter().copied().map(Ok),
+            jobs,
+            |n, buffer: &mut Vec<u8>| {
+                // Later units finish first, so replies arrive out of order.
+                std::thread::sleep(std::time::Duration::from_micros((200 - n) * 5));
+                if n % 7 != 0 {
+                    writeln!(buffer, "{n}")?;
+                }
+                Ok(())
+            },
+            &mut out,
+        )
+        .unwrap();
+        let expected: String = inputs
+            .iter()
+            .filter(|n| *n % 7 != 0)
+            .map(|n| n.to_string() + "\n")
+            .collect();
+        assert_eq!(String::from_utf8(out).unwrap(), expected, "jobs={jobs}");
+    }
+}
+
+#[test]
+fn write_ordered_stops_at_the_first_error_in_input_order() {
+    use toon::error::ToonError;
+    use toon::parallel::write_ordered;
+
+    for jobs in [1, 4] {
+        let inputs = (0..50).map(|n| {
+            if n == 30 {
+                Err(ToonError::message("read failed at 30"))
+            } else {
+                Ok(n)
+            }
+        });
+        let mut out = Vec::new();
+        let err = write_ordered(
+            inputs,
+            jobs,
+            |n, buffer: &mut Vec<u8>| {
+                if n == 20 {
+                    return Err(ToonError::message("unit 20 failed"));
+                }
+                buffer.extend_from_slice(format!("{n},").as_bytes());
+                Ok(())
+            },
+            &mut out,
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "unit 20 failed", "jobs={jobs}");
+        let expected: String = (0..20).map(|n| n.to_string() + ",").collect();
+        assert_eq!(String::from_utf8(out).unwrap(), expected, "jobs={jobs}");
+    }
+}
+
+// ============================================================================
+// LINE WRITER
+// ============================================================================
+
+#[test]
+fn line_writer_output_decodes_to_the_described_value() {
+    use toon::StringOrNumberOrBoolOrNull::{Bool, Null, Number, String as Str};
+    use toon::line_writer::ToonLineWriter;
+
+    let mut writer = ToonLineWriter::new(Vec::new()).delimiter('|');
+    writer.write_field("id", &Number(7.0)).unwrap();
+    writer.begin_object("my key").unwrap();
+    writer.write_field("note", &Str("a|b".to_string())).unwrap();
+    writer.begin_object("inner").unwrap();
+    writer.write_field("flag", &Bool(true)).unwrap();
+    writer.end().unwrap();
+    writer.begin_tabular("rows", ["name", "x"], 2).unwrap();
+    writer.write_row(&[Str("a,b".to_string()), Null]).unwrap();
+    writer
+        .write_row(&[Str(String::new()), Number(1.5)])
+        .unwrap();
+    writer.end().unwrap();
+    writer.end().unwrap();
+    let text = String::from_utf8(writer.finish().unwrap()).unwrap();
+
+    assert_eq!(
+        text,
+        "id: 7\n\"my key\":\n  note: \"a|b\"\n  inner:\n    flag: true\n  \
+         rows[2|]{name|x}:\n    a,b|null\n    \"\"|1.5\n"
+    );
+    assert_eq!(
+        decode(&text, None),
+        JsonValue::from(serde_json::json!({
+            "id": 7,
+            "my key": {
+                "note": "a|b",
+                "inner": {"flag": true},
+                "rows": [{"name": "a,b", "x": null}, {"name": "", "x": 1.5}],
+            },
+        }))
+    );
+}
+
+#[test]
+fn line_writer_rejects_misplaced_and_miscounted_writes() {
+    use toon::StringOrNumberOrBoolOrNull::Number;
+    use toon::line_writer::ToonLineWriter;
+
+    let mut writer = ToonLineWriter::new(Vec::new()).indent(4);
+    assert!(writer.write_row(&[Number(1.0)]).is_err());
+    assert!(writer.end().is_err());
+    assert!(
+        writer
+            .begin_tabular("rows", Vec::<String>::new(), 1)
+            .is_err()
+    );
+
+    writer.begin_tabular("rows", ["a", "b"], 1).unwrap();
+    let error = writer.write_row(&[Number(1.0)]).unwrap_err().to_string();
+    assert!(error.contains("rows[0]"), "{error}");
+    assert!(writer.write_field("a", &Number(1.0)).is_err());
+    assert!(writer.begin_object("a").is_err());
+    assert!(writer.end().is_err());
+
+    let mut writer = ToonLineWriter::new(Vec::new()).indent(4);
+    writer.begin_tabular("rows", ["a"], 1).unwrap();
+    writer.write_row(&[Number(1.0)]).unwrap();
+    assert!(writer.write_row(&[Number(2.0)]).is_err());
+    writer.end().unwrap();
+    writer.begin_object("open").unwrap();
+    let error = writer.finish().unwrap_err().to_string();
+    assert!(error.contains("open"), "{error}");
+
+    let mut writer = ToonLineWriter::new(Vec::new()).indent(4);
+    writer.begin_tabular("rows", ["a"], 1).unwrap();
+    writer.write_row(&[Number(1.0)]).unwrap();
+    writer.end().unwrap();
+    assert_eq!(writer.finish().unwrap(), b"rows[1]{a}:\n    1\n");
+}
+
+// ============================================================================
+// TOON WRITER
+// ============================================================================
+
+fn write_events(value: &JsonValue, options: Option<EncodeOptions>) -> String {
+    use toon::EventSink;
+    use toon::encode::ToonWriter;
+
+    let mut writer = ToonWriter::new(Vec::new(), options);
+    for event in toon::encode_stream_events(value.clone(), None) {
+        writer.event(event).unwrap();
+    }
+    String::from_utf8(writer.finish().unwrap()).unwrap()
+}
+
+#[test]
+fn toon_writer_matches_encode_outside_tabular_arrays() {
+    let documents = [
+        serde_json::json!({
+            "name": "report",
+            "my key": {"a b": [1, 2], "empty": {}, "none": []},
+            "matrix": [[1, 2], [], [[3], "x"]],
+            "mixed": [1, {"id": 1, "tags": ["a", "b,c"]}, [true], {}],
+            "items": [
+                {"inner": {"deep": null}, "next": 1},
+                {"list": [{"a": 1}, 2], "after": "x"},
+            ],
+        }),
+        serde_json::json!([1, "two", null]),
+        serde_json::json!([{"a": [[1]]}]),
+        serde_json::json!("just text"),
+        serde_json::json!({}),
+    ];
+    let options = [
+        None,
+        Some(EncodeOptions {
+            indent: Some(4),
+            delimiter: Some('|'),
+            always_mark_delimiter: Some(true),
+            emit_directive: Some(true),
+            ..EncodeOptions::default()
+        }),
+    ];
+    for document in documents {
+        let value = JsonValue::from(document);
+        for options in &options {
+            let written = write_events(&value, options.clone());
+            let encoded = encode(value.clone(), options.clone());
+            let expected = if encoded.is_empty() {
+                String::new()
+            } else {
+                encoded + "\n"
+            };
+            assert_eq!(written, expected);
+        }
+    }
+}
+
+#[test]
+fn toon_writer_writes_uniform_rows_as_list_items() {
+    use toon::StringOrNumberOrBoolOrNull::{Int, String as Str};
+    use toon::encode::ToonWriter;
+
+    let mut writer = ToonWriter::new(Vec::new(), None);
+    writer.start_array(2).unwrap();
+    for (id, name) in [(1, "Ada"), (2, "Bob")] {
+        writer.start_object().unwrap();
+        writer.key("id").unwrap();
+        writer.value(Int(id)).unwrap();
+        writer.key("name").unwrap();
+        writer.value(Str(name.to_string())).unwrap();
+        writer.end_object().unwrap();
+    }
+    writer.end_array().unwrap();
+    let text = String::from_utf8(writer.finish().unwrap()).unwrap();
+
+    assert_eq!(
+        text,
+        "[2]:\n  - id: 1\n    name: Ada\n  - id: 2\n    name: Bob\n"
+    );
+    assert_eq!(
+        decode(&text, None),
+        JsonValue::from(serde_json::json!([{"id": 1, "name": "Ada"}, {"id": 2, "name": "Bob"}]))
+    );
+}
+
+#[test]
+fn toon_writer_takes_decoded_events() {
+    use toon::encode::ToonWriter;
+
+    let input = "id: 1\nusers[2]{name,age}:\n  Ada,36\n  Bob,41\ntags[2]: a,b";
+    let mut writer = ToonWriter::new(Vec::new(), None);
+    toon::decode_into_sink(input.lines().map(str::to_string), None, &mut writer).unwrap();
+    let text = String::from_utf8(writer.finish().unwrap()).unwrap();
+    assert_eq!(decode(&text, None), decode(input, None));
+}
+
+#[test]
+fn toon_writer_rejects_misplaced_and_miscounted_writes() {
+    use toon::StringOrNumberOrBoolOrNull::Int;
+    use toon::encode::ToonWriter;
+
+    let mut writer = ToonWriter::new(Vec::new(), None);
+    assert!(writer.key("a").is_err());
+    assert!(writer.end_object().is_err());
+    assert!(writer.end_array().is_err());
+    writer.start_object().unwrap();
+    assert!(writer.value(Int(1)).is_err());
+    writer.key("rows").unwrap();
+    assert!(writer.key("other").is_err());
+    writer.start_array(1).unwrap();
+    writer.value(Int(1)).unwrap();
+    let error = writer.value(Int(2)).unwrap_err().to_string();
+    assert!(error.contains("rows[1]"), "{error}");
+    assert!(writer.key("a").is_err());
+    assert!(writer.end_object().is_err());
+    writer.end_array().unwrap();
+    writer.key("short").unwrap();
+    writer.start_array(2).unwrap();
+    writer.value(Int(1)).unwrap();
+    let error = writer.end_array().unwrap_err().to_string();
+    assert!(error.contains("expected 2 items, but got 1"), "{error}");
+    let error = writer.finish().unwrap_err().to_string();
+    assert!(error.contains("short"), "{error}");
+
+    let writer = ToonWriter::new(Vec::new(), None);
+    assert!(writer.finish().is_err());
+
+    let mut writer = ToonWriter::new(Vec::new(), None);
+    writer.value(Int(1)).unwrap();
+    assert!(writer.start_object().is_err());
+    assert_eq!(writer.finish().unwrap(), b"1\n");
+}
+
+// ============================================================================
+// PROTOBUF JSON
+// ============================================================================
+
+#[test]
+fn proto_json_round_trips_through_toon_canonically() {
+    use toon::interop::proto_json::{
+        ProtoEnum, ProtoMessage, ProtoType, decode_proto_json, from_proto_json, to_proto_json,
+    };
+
+    let status = ProtoType::Enum(ProtoEnum::new([("UNKNOWN", 0), ("ACTIVE", 1)]));
+    let item = ProtoMessage::new()
+        .field("sku", ProtoType::String)
+        .field("count", ProtoType::UInt32);
+    let message = ProtoMessage::new()
+        .field("user_id", ProtoType::Int64)
+        .field("status", status)
+        .field("created_at", ProtoType::Timestamp)
+        .field("ttl", ProtoType::Duration)
+        .field("avatar", ProtoType::Bytes)
+        .field("score", ProtoType::Double)
+        .field(
+            "items",
+            ProtoType::Repeated(Box::new(ProtoType::Message(item))),
+        )
+        .field("labels", ProtoType::Map(Box::new(ProtoType::String)))
+        .field("extra", ProtoType::Json);
+
+    let payload = serde_json::json!({
+        "user_id": 9_007_199_254_740_993_u64,
+        "status": 1,
+        "createdAt": "2024-05-01T12:00:00.5+02:00",
+        "ttl": "90.10s",
+        "avatar": "-_8",
+        "score": "NaN",
+        "items": [{"sku": "a", "count": "3"}],
+        "labels": {"env": "prod"},
+        "extra": {"any": [1, null]},
+    });
+    let value = from_proto_json(&payload, &message).unwrap();
+    let canonical = serde_json::json!({
+        "userId": "9007199254740993",
+        "status": "ACTIVE",
+        "createdAt": "2024-05-01T10:00:00.500Z",
+        "ttl": "90.100s",
+        "avatar": "+/8=",
+        "score": "NaN",
+        "items": [{"sku": "a", "count": 3}],
+        "labels": {"env": "prod"},
+        "extra": {"any": [1, null]},
+    });
+    let toon = encode(value.clone(), None);
+    assert!(toon.contains("userId: \"9007199254740993\""), "{toon}");
+    assert_eq!(decode_proto_json(&toon, &message, None).unwrap(), canonical);
+
+    // What a model might write back: unquoted int64, enum by number.
+    let edited = "userId: 42\nstatus: 0\nitems[1]{sku,count}:\n  b,7\nunset: null";
+    let message = message.allow_unknown();
+    assert_eq!(
+        decode_proto_json(edited, &message, None).unwrap(),
+        serde_json::json!({
+            "userId": "42",
+            "status": "UNKNOWN",
+            "items": [{"sku": "b", "count": 7}],
+            "unset": null,
+        })
+    );
+    assert_eq!(to_proto_json(&value, &message).unwrap(), canonical);
+}
+
+#[test]
+fn proto_json_reports_mismatches_by_path() {
+    use toon::interop::proto_json::{ProtoMessage, ProtoType, from_proto_json, to_proto_json};
+
+    let inner = ProtoMessage::new().field("big_id", ProtoType::Int64);
+    let message = ProtoMessage::new()
+        .field("ids", ProtoType::Repeated(Box::new(ProtoType::Int32)))
+        .field("inner", ProtoType::Message(inner));
+
+    let error = |json: serde_json::Value| from_proto_json(&json, &message).unwrap_err().to_string();
+    assert!(error(serde_json::json!({"ids": [1, 2.5]})).contains("ids[1]"));
+    assert!(error(serde_json::json!({"ids": [null]})).contains("ids[0]"));
+    assert!(error(serde_json::json!({"inner": {"bigId": "x"}})).contains("inner.bigId"));
+    assert!(error(serde_json::json!({"other": 1})).contains("unknown field"));
+    assert!(
+        error(serde_json::json!({"inner": {"big_id": "1", "bigId": "2"}})).contains("given twice")
+    );
+
+    let imprecise = decode("inner:\n  bigId: 9007199254740993.0", None);
+    #[cfg(not(feature = "arbitrary-precision"))]
+    {
+        let error = to_proto_json(&imprecise, &message).unwrap_err().to_string();
+        assert!(error.contains("write it as a string"), "{error}");
+    }
+    // With arbitrary precision, the literal is kept exactly.
+    #[cfg(feature = "arbitrary-precision")]
+    assert_eq!(
+        to_proto_json(&imprecise, &message).unwrap(),
+        serde_json::json!({"inner": {"bigId": "9007199254740993"}})
+    );
+}
+
+// ============================================================================
+// STRUCTURE DIAGRAMS
+// ============================================================================
+
+#[test]
+fn visualize_draws_merged_shapes_without_values() {
+    use toon::viz::{VizFormat, visualize};
+
+    let value = JsonValue::from(serde_json::json!({
+        "name": "secret",
+        "users": [{"id": 1, "email": "a@x"}, {"id": 2, "email": null}],
+        "items": [
+            {"id": 1, "parts": ["a"]},
+            {"id": 2, "note": "say \"hi\""},
+        ],
+        "grid": [[1, 2], [3]],
+    }));
+
+    let dot = visualize(&value, VizFormat::Dot);
+    assert!(!dot.contains("secret") && !dot.contains("a@x"), "{dot}");
+    assert!(dot.starts_with("digraph toon {\n"));
+    assert!(
+        dot.contains(r#"n0 [label="root\n\nname: string\l"];"#),
+        "{dot}"
+    );
+    assert!(
+        dot.contains(r#"[label="users[2]{id,email}\n\nid: number\lemail: string|null\l"];"#),
+        "{dot}"
+    );
+    assert!(
+        dot.contains(r#"[label="item\n\nid: number\lnote: string?\l"];"#),
+        "{dot}"
+    );
+    assert!(
+        dot.contains(r#"[label="parts[1]?\n\nitems: string\l"];"#),
+        "{dot}"
+    );
+    assert!(
+        dot.contains(r#"[label="item[1..2]\n\nitems: number\l"];"#),
+        "{dot}"
+    );
+    assert!(dot.contains("  n0 -> n1;\n"));
+
+    let mermaid = visualize(&value, VizFormat::Mermaid);
+    assert!(mermaid.starts_with("flowchart TD\n  n0[\"root<br/>name: string\"]\n"));
+    assert!(mermaid.contains("  n0 --> n1\n"));
+
+    assert_eq!(
+        visualize(&JsonValue::from("x"), VizFormat::Mermaid),
+        "flowchart TD\n  n0[\"root<br/>value: string\"]\n"
+    );
+}
+
+// ============================================================================
+// TOKEN ESTIMATE CALIBRATION
+// ============================================================================
+
+#[test]
+fn token_calibration_fits_samples_and_defaults_to_the_heuristic() {
+    use toon::shared::string_utils::estimate_tokens;
+    use toon::tokens::{TokenEstimator, calibrate, samples_from_json};
+
+    for text in [
+        "",
+        "a",
+        "name: Alice",
+        "users[2]{id,name}:\n  1,Ada\n  2,Bob",
+        "a b c d e",
+    ] {
+        assert_eq!(
+            TokenEstimator::default().estimate(text),
+            estimate_tokens(text)
+        );
+    }
+
+    let samples = samples_from_json(
+        r#"[{"text": "id: 1\nname: Ada", "tokens": 8}, {"text": "tags[3]: a,b,c", "tokens": 7}]"#,
+    )
+    .unwrap();
+    let estimator = calibrate(&samples).unwrap();
+    assert!((estimator.chars_per_token - 25.0 / 15.0).abs() < 1e-9);
+    assert!((estimator.tokens_per_word - 2.0).abs() < 1e-9);
+    assert_eq!(estimator.estimate("id: 1\nname: Ada"), 8);
+    assert!(estimator.mean_relative_error(&samples) < 0.1);
+    assert!(TokenEstimator::default().mean_relative_error(&samples) > 0.5);
+
+    assert!(calibrate(&[]).is_err());
+    assert!(calibrate(&[("  ".to_string(), 1)]).is_err());
+    assert!(calibrate(&[("text".to_string(), 0)]).is_err());
+    assert!(samples_from_json(r#"{"text": "a", "tokens": 1}"#).is_err());
+    assert!(samples_from_json(r#"[{"text": "a", "tokens": -1}]"#).is_err());
+}
+
+// ============================================================================
+// LLM LINT
+// ============================================================================
+
+#[test]
+fn lint_for_llm_flags_confusing_constructs_with_suggestions() {
+    use toon::encode::lint::{AdviceKind, lint_for_llm};
+    use toon::options::{KeyFoldingMode, LongStringPolicy};
+
+    let long = "word ".repeat(120).trim_end().to_string();
+    let value = JsonValue::from(serde_json::json!({
+        "config": {"db": {"primary": {"host": "h", "port": 1}}},
+        "bio": long,
+        "quoted": format!("{long}: with a colon"),
+        "flags": vec![serde_json::Value::Null; 150],
+        "rows": (0..10).map(|i| serde_json::json!({"id": i, "name": format!("Doe, J{i}")})).collect::<Vec<_>>(),
+    }));
+
+    let advice = lint_for_llm(&value, None);
+    let kinds: Vec<(AdviceKind, &str)> = advice
+        .iter()
+        .map(|advice| (advice.kind, advice.path.as_str()))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            (AdviceKind::FoldableKeyChain, "config"),
+            (AdviceKind::LongUnquotedString, "bio"),
+            (AdviceKind::HugeInlineArray, "flags"),
+            (AdviceKind::DelimiterCollisions, "rows"),
+        ]
+    );
+    assert!(advice[0].suggestion.contains("`config.db.primary`"));
+    assert!(advice[2].suggestion.contains("sparse_threshold"));
+    assert!(
+        advice[3].suggestion.contains("--delimiter tab"),
+        "{}",
+        advice[3]
+    );
+    assert!(
+        advice[3]
+            .to_string()
+            .starts_with("rows: delimiter collisions (10 of 10 strings")
+    );
+
+    let options = EncodeOptions {
+        key_folding: Some(KeyFoldingMode::Safe),
+        long_string: Some(LongStringPolicy::ExternalRef { max: 100 }),
+        sparse_threshold: Some(0.5),
+        delimiter: Some('|'),
+        ..EncodeOptions::default()
+    };
+    let advice = lint_for_llm(&value, Some(options));
+    assert_eq!(advice.len(), 1, "{advice:?}");
+    assert_eq!(advice[0].kind, AdviceKind::HugeInlineArray);
+    assert!(advice[0].suggestion.contains("run_length"));
+
+    let numbers = JsonValue::from(serde_json::json!({"n": (0..101).collect::<Vec<_>>()}));
+    let advice = lint_for_llm(&numbers, None);
+    assert!(advice[0].suggestion.contains("split_by_token_budget"));
+    assert!(lint_for_llm(&JsonValue::from(serde_json::json!({"a": {"b": 1}})), None).is_empty());
+}
+
+// ============================================================================
+// FORMAT DETECTION
+// ============================================================================
+
+#[test]
+fn detect_format_guesses_from_structure() {
+    use toon::detect::{FormatGuess, detect_format};
+
+    for json in [
+        r#"{"a": 1}"#,
+        "  [1, 2]",
+        "[]",
+        "[\n  {\"id\": 1}\n]",
+        "[3]",
+    ] {
+        assert_eq!(detect_format(json), FormatGuess::Json, "{json}");
+    }
+    for toon_text in [
+        "a: 1",
+        "[2]: 1,2",
+        "[2|]{id|name}:\n  1|Ada\n  2|Bob",
+        "[0]:",
+        "\"a key\": 1",
+        "\"list\"[2]: x,y",
+        "users[1]{id}:\n  1",
+        "#%toon version=1\na: 1",
+        "true_value: 1",
+        "hello world",
+    ] {
+        assert_eq!(detect_format(toon_text), FormatGuess::Toon, "{toon_text}");
+    }
+    for ambiguous in [
+        "",
+        "  \n",
+        "42",
+        "-1.5e3",
+        "null",
+        "\"text\"",
+        "\"unterminated",
+    ] {
+        assert_eq!(
+            detect_format(ambiguous),
+            FormatGuess::Ambiguous,
+            "{ambiguous}"
+        );
+    }
+
+    // Every encoder output with a non-scalar root is recognized as TOON.
+    for value in [
+        serde_json::json!({"a": {"b": [1, 2]}}),
+        serde_json::json!([{"id": 1}, {"id": 2}]),
+        serde_json::json!([[1], [2, 3]]),
+        serde_json::json!(["x", "y"]),
+    ] {
+        let toon_text = encode(JsonValue::from(value.clone()), None);
+        assert_eq!(detect_format(&toon_text), FormatGuess::Toon, "{toon_text}");
+        assert_eq!(detect_format(&value.to_string()), FormatGuess::Json);
+    }
+}
+
+// ============================================================================
+// RESUMABLE DECODING
+// ============================================================================
+
+/// Decode `input` with a resumable decoder, resuming from each checkpoint
+/// in turn, and check every run against the whole-document decoder.
+fn assert_resumes_everywhere(input: &str, options: Option<&toon::options::DecodeStreamOptions>) {
+    use std::io::Cursor;
+    use toon::decode::resume::ResumableDecoder;
+
+    let expected =
+        toon::try_decode_stream_sync(input.split('\n').map(String::from), options.cloned())
+            .unwrap();
+    let mut events = Vec::new();
+    let mut checkpoints = Vec::new();
+    let mut decoder = ResumableDecoder::new(Cursor::new(input), options.cloned()).unwrap();
+    checkpoints.push((decoder.checkpoint(), 0));
+    while decoder.step(&mut events).unwrap() {
+        checkpoints.push((decoder.checkpoint(), events.len()));
+    }
+    assert_eq!(events, expected, "{input}");
+
+    let mut with_metadata = toon::sink::MetadataSink::default();
+    toon::decode_into_sink(
+        input.split('\n').map(String::from),
+        options.cloned(),
+        &mut with_metadata,
+    )
+    .unwrap();
+    let mut resumed_metadata = toon::sink::MetadataSink::default();
+    let mut decoder = ResumableDecoder::new(Cursor::new(input), options.cloned()).unwrap();
+    while decoder.step(&mut resumed_metadata).unwrap() {}
+    assert_eq!(resumed_metadata, with_metadata, "{input}");
+
+    for (checkpoint, decoded) in checkpoints {
+        let mut resumed = events[..decoded].to_vec();
+        let mut decoder =
+            ResumableDecoder::resume(Cursor::new(input), checkpoint.clone(), options.cloned())
+                .unwrap();
+        while decoder.step(&mut resumed).unwrap() {}
+        assert_eq!(resumed, expected, "{input} from {checkpoint:?}");
+    }
+}
+
+#[test]
+fn resumable_decoding_matches_whole_document_decoding() {
+    for input in [
+        "name: demo\nmeta:\n  owner: ops\n  tags[2]: a,b\n\nusers[3|]{id|name}:\n  1|Ada\n  2|Bob\n  3|Cy\ncount: 3",
+        "items[2]:\n  - id: 1\n    parts[2]{a,b}:\n      1,2\n      3,4\n  - plain\nempty[0]:\nlast: true",
+        "[3]{id,name}:\n  1,Ada\n  2,\"Bob, Jr.\"\n  3,Cy\n",
+        "[2]:\n  - a: 1\n  - [2]: x,y",
+        "[3]: 1,2,3",
+        "42",
+        "",
+        "\n\n",
+        "\"quoted key\"[1]{x}:\n  1\nafter: 1",
+        "#%toon version=1 indent=4\nouter:\n    inner: 1\nrows[2]{a}:\n    1\n    2",
+    ] {
+        assert_resumes_everywhere(input, None);
+    }
+    let sparse = toon::options::DecodeStreamOptions {
+        sparse: Some(true),
+        ..Default::default()
+    };
+    assert_resumes_everywhere("a: 1\nsparse[4]:\n  1: x\nlast: true", Some(&sparse));
+    let lenient = toon::options::DecodeStreamOptions {
+        strict: Some(false),
+        ..Default::default()
+    };
+    assert_resumes_everywhere("rows[2]{a}:\n  1\n\n  2\nx: 1", Some(&lenient));
+    let pipes = toon::options::DecodeStreamOptions {
+        delimiter: Some('|'),
+        ..Default::default()
+    };
+    assert_resumes_everywhere(
+        "tags[2]: a|b\nrows[2]{id|name}:\n  1|Ada\n  2|Bob\nempty[0]:",
+        Some(&pipes),
+    );
+    assert_resumes_everywhere("[2]{a|b}:\n  1|2\n  3|4", Some(&pipes));
+    assert_resumes_everywhere(
+        "#%toon version=1 delimiter=|\nrows[2]{id|name}:\n  1|Ada\n  2|Bob",
+        None,
+    );
+}
+
+#[test]
+fn resumable_decoding_reports_errors_like_whole_document_decoding() {
+    use std::io::Cursor;
+    use toon::decode::resume::ResumableDecoder;
+
+    for input in [
+        "rows[2]{a}:\n  1\n\n  2",
+        "rows[3]{a}:\n  1\n  2\nnext: 1",
+        "rows[1]{a}:\n  1\n  2",
+        "items[1]:\n  - 1\n  - 2",
+        "a: 1\n   b: 2",
+        "[2]{a,b}:\n  1,2\n  3",
+    ] {
+        let expected =
+            toon::try_decode_stream_sync(input.split('\n').map(String::from), None).unwrap_err();
+        let mut decoder = ResumableDecoder::new(Cursor::new(input), None).unwrap();
+        let mut events = Vec::new();
+        let err = loop {
+            match decoder.step(&mut events) {
+                Ok(true) => {}
+                Ok(false) => panic!("{input} decoded"),
+                Err(err) => break err,
+            }
+        };
+        assert_eq!(err.to_string(), expected.to_string(), "{input}");
+    }
+}
+
+#[test]
+fn resumable_decoding_checkpoints_by_line_count() {
+    use std::io::Cursor;
+    use toon::decode::resume::{DecodePosition, ResumableDecoder};
+
+    let rows: Vec<String> = (0..100).map(|i| format!("  {i},row{i}")).collect();
+    let input = format!("rows[100]{{id,name}}:\n{}", rows.join("\n"));
+    let mut decoder = ResumableDecoder::new(Cursor::new(input.as_str()), None).unwrap();
+    let mut events = Vec::new();
+    assert!(decoder.decode_lines(10, &mut events).unwrap());
+    let checkpoint = decoder.checkpoint();
+    assert_eq!(checkpoint.scan.line_number, 10);
+    assert_eq!(
+        checkpoint.offset,
+        input
+            .lines()
+            .take(10)
+            .map(|line| line.len() as u64 + 1)
+            .sum::<u64>()
+    );
+    assert!(matches!(
+        checkpoint.position,
+        DecodePosition::Items {
+            decoded: 9,
+            expected: 100,
+            ..
+        }
+    ));
+
+    let options = toon::options::DecodeStreamOptions {
+        anchors: Some(true),
+        ..Default::default()
+    };
+    let err = ResumableDecoder::new(Cursor::new(input.as_str()), Some(options)).err();
+    assert_eq!(
+        err.map(|err| err.to_string()),
+        Some("Resumable decoding does not support anchors".to_string())
+    );
+}
+
+// ============================================================================
+// PULL DECODING
+// ============================================================================
+
+#[test]
+fn toon_reader_yields_the_events_of_whole_document_decoding() {
+    use std::io::Cursor;
+    use toon::decode::ToonReader;
+
+    for input in [
+        "name: demo\nmeta:\n  tags[2]: a,b\nusers[2]{id,name}:\n  1,Ada\n  2,Bob\ncount: 2",
+        "[2]:\n  - a: 1\n  - [2]: x,y",
+        "42",
+        "",
+    ] {
+        let expected =
+            toon::try_decode_stream_sync(input.split('\n').map(String::from), None).unwrap();
+        let events = ToonReader::new(Cursor::new(input))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(events, expected, "{input}");
+    }
+
+    // The events before an error come first, then the error, then nothing.
+    let mut reader = ToonReader::new(Cursor::new("a: 1\nrows[1]{x}:\n  1\n  2"));
+    let mut events = Vec::new();
+    let err = loop {
+        match reader.next() {
+            Some(Ok(event)) => events.push(event),
+            Some(Err(err)) => break err,
+            None => panic!("decoded despite the extra row"),
+        }
+    };
+    assert!(err.to_string().contains("but found more"), "{err}");
+    assert_eq!(events.len(), 9);
+    assert!(reader.next().is_none());
+
+    let options = toon::options::DecodeStreamOptions {
+        pedantic: Some(true),
+        ..Default::default()
+    };
+    assert!(ToonReader::with_options(Cursor::new("a: 1"), Some(options)).is_err());
+}
+
+#[test]
+fn decode_events_iter_matches_collected_decoding_and_stops_early() {
+    use std::cell::Cell;
+    use toon::decode_events_iter;
+    use toon::options::DecodeStreamOptions;
+
+    let input = "name: demo\nusers[2]{id,name}:\n  1,Ada\n  2,Bob\n\nnested:\n  - [2]: x,y";
+    let lines = || input.split('\n').map(String::from);
+    let expected = toon::try_decode_stream_sync(lines(), None).unwrap();
+    let events = decode_events_iter(lines(), None)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(events, expected);
+
+    let pedantic = DecodeStreamOptions {
+        pedantic: Some(true),
+        ..Default::default()
+    };
+    let events = decode_events_iter(lines(), Some(pedantic.clone()))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(events, expected);
+    let err = decode_events_iter(["a:  1".to_string()], Some(pedantic))
+        .last()
+        .unwrap();
+    assert!(err.is_err());
+
+    // Stopping after the first field leaves the table rows unread.
+    let pulled = Cell::new(0);
+    let rows = (0..1000).map(|i| format!("  {i}"));
+    let lines = ["a: 1".to_string(), "rows[1000]{id}:".to_string()]
+        .into_iter()
+        .chain(rows)
+        .inspect(|_| pulled.set(pulled.get() + 1));
+    assert_eq!(decode_events_iter(lines, None).take(3).count(), 3);
+    assert!(pulled.get() < 10, "pulled {} lines", pulled.get());
+
+    // Events before an error come first, then the error, then nothing.
+    let mut events = decode_events_iter(
+        ["a: 1", "rows[1]{x}:", "  1", "  2"].map(String::from),
+        None,
+    );
+    assert_eq!(events.by_ref().take_while(Result::is_ok).count(), 9);
+    assert!(events.next().is_none());
+}
+
+#[test]
+fn toon_reader_reads_a_row_at_a_time() {
+    use std::cell::Cell;
+    use std::io::{BufRead, Cursor, Read};
+    use std::rc::Rc;
+    use toon::JsonStreamEvent;
+    use toon::decode::ToonReader;
+
+    /// A reader that counts the bytes consumed from it.
+    struct Counted<'a> {
+        inner: Cursor<&'a str>,
+        consumed: Rc<Cell<usize>>,
+    }
+    impl Read for Counted<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let read = self.inner.read(buf)?;
+            self.consumed.set(self.consumed.get() + read);
+            Ok(read)
+        }
+    }
+    impl BufRead for Counted<'_> {
+        fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+            self.inner.fill_buf()
+        }
+        fn consume(&mut self, amount: usize) {
+            self.consumed.set(self.consumed.get() + amount);
+            self.inner.consume(amount);
+        }
+    }
+
+    let rows: Vec<String> = (0..1000).map(|i| format!("  {i},row{i}")).collect();
+    let input = format!("rows[1000]{{id,name}}:\n{}", rows.join("\n"));
+    let consumed = Rc::new(Cell::new(0));
+    let mut reader = ToonReader::new(Counted {
+        inner: Cursor::new(input.as_str()),
+        consumed: Rc::clone(&consumed),
+    });
+    let mut rows_seen = 0;
+    for event in reader.by_ref() {
+        if event.unwrap() == JsonStreamEvent::EndObject {
+            rows_seen += 1;
+            if rows_seen == 2 {
+                break;
+            }
+        }
+    }
+    assert!(consumed.get() < 100, "read {} bytes", consumed.get());
+    assert_eq!(reader.flatten().count(), 998 * 6 + 2);
+}
+
+#[test]
+fn toon_reader_holds_one_item_of_a_streamed_array_at_a_time() {
+    use std::cell::Cell;
+    use std::io::{BufRead, Read};
+    use std::rc::Rc;
+    use toon::JsonStreamEvent;
+    use toon::decode::ToonReader;
+
+    /// A document produced a line at a time as it is read, so it is never
+    /// in memory whole, counting the lines handed out.
+    struct Generated {
+        items: usize,
+        next: usize,
+        buf: Vec<u8>,
+        pos: usize,
+        lines: Rc<Cell<usize>>,
+    }
+    impl Generated {
+        fn line(&self, index: usize) -> Option<String> {
+            match index {
+                0 => Some("name: demo".to_string()),
+                1 => Some(format!("items[{}]:", self.items)),
+                _ if index < 2 + 2 * self.items => {
+                    let item = (index - 2) / 2;
+                    Some(if index.is_multiple_of(2) {
+                        format!("  - id: {item}")
+                    } else {
+                        format!("    tags[2]: a{item},b")
+                    })
+                }
+                _ => None,
+            }
+        }
+    }
+    impl Read for Generated {
+        fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+            let available = self.fill_buf()?;
+            let read = available.len().min(out.len());
+            out[..read].copy_from_slice(&available[..read]);
+            self.consume(read);
+            Ok(read)
+        }
+    }
+    impl BufRead for Generated {
+        fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+            if self.pos == self.buf.len()
+                && let Some(line) = self.line(self.next)
+            {
+                self.buf = format!("{line}\n").into_bytes();
+                self.pos = 0;
+                self.next += 1;
+                self.lines.set(self.next);
+            }
+            Ok(&self.buf[self.pos..])
+        }
+        fn consume(&mut self, amount: usize) {
+            self.pos += amount;
+        }
+    }
+
+    let items = 20_000;
+    let lines = Rc::new(Cell::new(0));
+    let reader = ToonReader::new(Generated {
+        items,
+        next: 0,
+        buf: Vec::new(),
+        pos: 0,
+        lines: Rc::clone(&lines),
+    });
+    let mut depth = 0;
+    let mut done = 0;
+    for event in reader {
+        match event.unwrap() {
+            JsonStreamEvent::StartObject | JsonStreamEvent::StartArray { .. } => depth += 1,
+            JsonStreamEvent::EndObject | JsonStreamEvent::EndArray => {
+                depth -= 1;
+                if depth == 2 {
+                    done += 1;
+                    // The two header lines, the items yielded, and at most
+                    // the first line of the next item.
+                    assert!(
+                        lines.get() <= 2 + 2 * done + 1,
+                        "read {} lines after {done} items",
+                        lines.get()
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+    assert_eq!(done, items);
+}
+
+// ============================================================================
+// RE-ENCODING LIKE A PREVIOUS ENCODING
+// ============================================================================
+
+#[test]
+fn encode_like_reproduces_its_own_layout() {
+    use toon::encode_like;
+
+    let value = serde_json::json!({
+        "config": {"server": {"port": 8080}},
+        "users": [
+            {"id": 1, "name": "Ada", "note": ""},
+            {"id": 1000, "name": "Bob", "note": "x"},
+        ],
+        "tags": ["a", "b"],
+    });
+    let layouts = [
+        EncodeOptions::default(),
+        EncodeOptions {
+            indent: Some(4),
+            delimiter: Some('|'),
+            ..EncodeOptions::default()
+        },
+        EncodeOptions {
+            delimiter: Some('\t'),
+            key_folding: Some(KeyFoldingMode::Safe),
+            align_columns: Some(true),
+            ..EncodeOptions::default()
+        },
+        EncodeOptions {
+            typed_headers: Some(true),
+            empty_string: Some(EmptyStringStyle::Bare),
+            emit_directive: Some(true),
+            indent: Some(3),
+            ..EncodeOptions::default()
+        },
+        EncodeOptions {
+            always_mark_delimiter: Some(true),
+            ..EncodeOptions::default()
+        },
+    ];
+    for layout in layouts {
+        let previous = encode(value.clone(), Some(layout.clone()));
+        assert_eq!(encode_like(value.clone(), &previous, None), previous);
+    }
+}
+
+#[test]
+fn encode_like_keeps_layout_and_key_order_for_changed_data() {
+    use toon::encode_like;
+
+    let previous = "name: svc\nlimits:\n    cpu: 2\n    memory: 4\nusers[1|]{name|id}:\n    Ada|1";
+    let value = serde_json::json!({
+        "users": [
+            {"id": 1, "name": "Ada"},
+            {"id": 2, "name": "Bob, Jr."},
+        ],
+        "limits": {"memory": 8, "cpu": 2, "disk": 10},
+        "name": "svc",
+        "owner": "ops",
+    });
+    assert_eq!(
+        encode_like(value, previous, None),
+        "name: svc\nlimits:\n    cpu: 2\n    memory: 8\n    disk: 10\nusers[2|]{name|id}:\n    Ada|1\n    Bob, Jr.|2\nowner: ops"
+    );
+}
+
+#[test]
+fn encode_like_keeps_folded_keys_and_aligned_columns() {
+    use toon::encode_like;
+
+    let previous = "server.http.port: 80\nrows[2]{id,name}:\n  1 ,Ada\n  10,Bob";
+    let value = serde_json::json!({
+        "server": {"http": {"port": 8080}},
+        "rows": [{"id": 1, "name": "Ada"}, {"id": 100, "name": "Bob"}],
+    });
+    assert_eq!(
+        encode_like(value, previous, None),
+        "server.http.port: 8080\nrows[2]{id,name}:\n  1  ,Ada\n  100,Bob"
+    );
+}
+
+#[test]
+fn encode_like_lets_explicit_options_win() {
+    use toon::encode_like;
+
+    let previous = "rows[1|]{b|a}:\n    2|1";
+    let value = serde_json::json!({"rows": [{"a": 1, "b": 2}]});
+    let options = EncodeOptions {
+        delimiter: Some(','),
+        indent: Some(2),
+        ..EncodeOptions::default()
+    };
+    assert_eq!(
+        encode_like(value, previous, Some(options)),
+        "rows[1]{b,a}:\n  2,1"
+    );
+}
+
+#[test]
+fn encode_like_uses_formatting_of_text_that_does_not_decode() {
+    use toon::encode_like;
+
+    let previous = "items[3|]: a|b\nnested:\n    key: value";
+    let value = serde_json::json!({"nested": {"key": "v"}, "items": ["x", "y"]});
+    assert_eq!(
+        encode_like(value, previous, None),
+        "nested:\n    key: v\nitems[2|]: x|y"
+    );
+}
+
+// ============================================================================
+// CAPABILITIES
+// ============================================================================
+
+#[test]
+fn capabilities_report_defaults_that_round_trip() {
+    let capabilities = toon::capabilities();
+    assert_eq!(capabilities.spec_versions, ["3.0"]);
+    assert_eq!(capabilities.has_feature("cli"), cfg!(feature = "cli"));
+    assert!(!capabilities.has_feature("simd"));
+
+    let value: JsonValue = serde_json::json!({"a": {"b": [1, 2]}}).into();
+    assert_eq!(
+        encode(value.clone(), Some(capabilities.default_encode_options)),
+        encode(value.clone(), None)
+    );
+    let text = encode(value.clone(), None);
+    assert_eq!(
+        decode(&text, Some(capabilities.default_decode_options)),
+        value
+    );
+}
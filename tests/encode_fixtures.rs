@@ -3,7 +3,7 @@ use std::path::{Path, PathBuf};
 
 use serde::Deserialize;
 
-use toon::encode::encode;
+use toon::encode::{encode, encode_serde};
 use toon::options::{EncodeOptions, KeyFoldingMode};
 
 #[derive(Debug, Deserialize)]
@@ -27,21 +27,70 @@ fn encode_spec_fixtures() {
 
     assert!(!files.is_empty(), "no encode fixtures found");
 
+    let overrides = expected_overrides();
     for file in files {
         for case in file.tests {
-            run_case(&case);
+            run_case(&case, &overrides);
         }
     }
 }
 
-fn run_case(case: &FixtureCase) {
+/// Cases this crate adds beyond the vendored spec fixtures, in the same
+/// format.
+#[test]
+fn encode_local_fixtures() {
+    let files = load_fixture_files(Path::new("tests/fixtures/local/encode"));
+
+    assert!(!files.is_empty(), "no local encode fixtures found");
+
+    let overrides = expected_overrides();
+    for file in files {
+        for case in file.tests {
+            run_case(&case, &overrides);
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OverrideFile {
+    overrides: Vec<ExpectedOverride>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExpectedOverride {
+    name: String,
+    expected: String,
+}
+
+/// Cases where this crate deliberately writes something else than the spec
+/// fixture expects. The conformance harness reads the same file.
+fn expected_overrides() -> Vec<ExpectedOverride> {
+    let contents = fs::read_to_string("tests/fixtures/local/encode-overrides.json")
+        .expect("read encode overrides");
+    serde_json::from_str::<OverrideFile>(&contents)
+        .expect("parse encode overrides")
+        .overrides
+}
+
+fn run_case(case: &FixtureCase, overrides: &[ExpectedOverride]) {
     let options = parse_encode_options(case.options.as_ref());
-    let output = encode(case.input.clone(), options);
+    let output = encode(case.input.clone(), options.clone());
+    let expected = overrides
+        .iter()
+        .find(|entry| entry.name == case.name)
+        .map_or(case.expected.as_str(), |entry| entry.expected.as_str());
 
     assert_eq!(
-        output, case.expected,
+        output, expected,
         "fixture '{}' failed (expected {:?}, got {:?})",
-        case.name, case.expected, output
+        case.name, expected, output
+    );
+
+    let borrowed = encode_serde(&case.input, options);
+    assert_eq!(
+        borrowed, expected,
+        "fixture '{}' failed from a borrowed value",
+        case.name
     );
 }
 
@@ -78,6 +127,7 @@ fn parse_encode_options(options: Option<&serde_json::Value>) -> Option<EncodeOpt
         key_folding,
         flatten_depth,
         replacer: None,
+        ..EncodeOptions::default()
     })
 }
 
@@ -1,5 +1,8 @@
+#[allow(deprecated)]
 use toon::cli::json_stream::json_stream_from_events;
+#[allow(deprecated)]
 use toon::cli::json_stringify::json_stringify_lines;
+use toon::json::{events_from_jsonl, events_to_jsonl, write_json_from_events, write_json_value};
 use toon::{
     JsonStreamEvent, JsonValue, StringOrNumberOrBoolOrNull, decode_stream_sync, encode,
     encode_stream_events,
@@ -99,6 +102,26 @@ fn json_stream_from_events_rejects_mismatched_end() {
     assert!(err.to_string().contains("Mismatched endObject"));
 }
 
+#[test]
+fn writers_match_chunk_output() {
+    let value = toon::decode("a: 1\nb[2]: true,x", None);
+
+    let mut written = Vec::new();
+    write_json_value(&mut written, &value, 2).unwrap();
+    assert_eq!(
+        String::from_utf8(written).unwrap(),
+        json_stringify_lines(&value, 2).concat()
+    );
+
+    let events = toon::decode_stream_sync(["a: 1", "b[2]: true,x"].map(String::from), None);
+    let mut written = Vec::new();
+    write_json_from_events(&mut written, events.clone(), 0).unwrap();
+    assert_eq!(
+        String::from_utf8(written).unwrap(),
+        json_stream_from_events(events, 0).unwrap().concat()
+    );
+}
+
 fn serde_value(value: &JsonValue) -> serde_json::Value {
     match value {
         JsonValue::Primitive(primitive) => match primitive {
@@ -106,6 +129,11 @@ fn serde_value(value: &JsonValue) -> serde_json::Value {
             StringOrNumberOrBoolOrNull::Bool(value) => serde_json::Value::Bool(*value),
             StringOrNumberOrBoolOrNull::Number(value) => serde_json::Number::from_f64(*value)
                 .map_or(serde_json::Value::Null, serde_json::Value::Number),
+            StringOrNumberOrBoolOrNull::Int(value) => serde_json::Value::from(*value),
+            StringOrNumberOrBoolOrNull::UInt(value) => serde_json::Value::from(*value),
+            StringOrNumberOrBoolOrNull::NumberLexeme(text) => {
+                serde_json::from_str(text).unwrap_or(serde_json::Value::Null)
+            }
             StringOrNumberOrBoolOrNull::String(value) => serde_json::Value::String(value.clone()),
         },
         JsonValue::Array(values) => {
@@ -118,6 +146,8 @@ fn serde_value(value: &JsonValue) -> serde_json::Value {
             }
             serde_json::Value::Object(map)
         }
+        #[cfg(feature = "raw")]
+        JsonValue::Raw(text) => serde_json::Value::String(text.clone()),
     }
 }
 
@@ -245,7 +275,7 @@ fn encode_stream_events_roundtrip_with_decode() {
                 JsonValue::Object(vec![
                     (
                         "id".to_string(),
-                        JsonValue::Primitive(StringOrNumberOrBoolOrNull::Number(1.0)),
+                        JsonValue::Primitive(StringOrNumberOrBoolOrNull::Int(1)),
                     ),
                     (
                         "name".to_string(),
@@ -257,7 +287,7 @@ fn encode_stream_events_roundtrip_with_decode() {
                 JsonValue::Object(vec![
                     (
                         "id".to_string(),
-                        JsonValue::Primitive(StringOrNumberOrBoolOrNull::Number(2.0)),
+                        JsonValue::Primitive(StringOrNumberOrBoolOrNull::Int(2)),
                     ),
                     (
                         "name".to_string(),
@@ -268,7 +298,7 @@ fn encode_stream_events_roundtrip_with_decode() {
         ),
         (
             "count".to_string(),
-            JsonValue::Primitive(StringOrNumberOrBoolOrNull::Number(2.0)),
+            JsonValue::Primitive(StringOrNumberOrBoolOrNull::Int(2)),
         ),
     ]);
 
@@ -291,3 +321,109 @@ fn encode_stream_events_roundtrip_with_decode() {
 
     assert_eq!(decode_json, encode_json);
 }
+
+#[test]
+fn events_jsonl_round_trips_decode_events() {
+    let events = decode_stream_sync(["\"a b\"[2]: 1,x", "c: null"].map(str::to_string), None);
+    let jsonl = events_to_jsonl(events.clone());
+    assert_eq!(
+        jsonl,
+        concat!(
+            "{\"type\":\"startObject\"}\n",
+            "{\"type\":\"key\",\"key\":\"a b\",\"wasQuoted\":true}\n",
+            "{\"type\":\"startArray\",\"length\":2}\n",
+            "{\"type\":\"primitive\",\"value\":1}\n",
+            "{\"type\":\"primitive\",\"value\":\"x\"}\n",
+            "{\"type\":\"endArray\"}\n",
+            "{\"type\":\"key\",\"key\":\"c\",\"wasQuoted\":false}\n",
+            "{\"type\":\"primitive\",\"value\":null}\n",
+            "{\"type\":\"endObject\"}\n",
+        )
+    );
+    assert_eq!(events_from_jsonl(&jsonl).unwrap(), events);
+    assert_eq!(
+        events_from_jsonl("\n{\"type\":\"key\",\"key\":\"k\"}\n").unwrap(),
+        [JsonStreamEvent::Key {
+            key: "k".to_string(),
+            was_quoted: false,
+        }]
+    );
+
+    let err = events_from_jsonl("{\"type\":\"endArray\"}\n{\"type\":\"primitive\",\"value\":[1]}")
+        .unwrap_err();
+    assert!(err.to_string().contains("line 2"), "{err}");
+    assert!(events_from_jsonl("{\"type\":\"start\"}").is_err());
+}
+
+#[test]
+fn v2_events_carry_declared_lengths_and_source_lines() {
+    use toon::JsonStreamEventV2;
+    use toon::decode::decode_into_sink;
+    use toon::options::DecodeStreamOptions;
+    use toon::sink::MetadataSink;
+
+    let lines = "users[2]{id}:\n  1\n  2\nmeta:\n  tags[3]:\n    - a"
+        .split('\n')
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    let options = DecodeStreamOptions {
+        strict: Some(false),
+        ..DecodeStreamOptions::default()
+    };
+    let mut sink = MetadataSink::default();
+    decode_into_sink(lines.clone(), Some(options.clone()), &mut sink).unwrap();
+    let events = sink.events;
+
+    let starts = events
+        .iter()
+        .filter(|event| {
+            matches!(
+                event,
+                JsonStreamEventV2::StartObject { .. } | JsonStreamEventV2::StartArray { .. }
+            )
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    assert_eq!(
+        starts,
+        [
+            JsonStreamEventV2::StartObject {
+                source_line: Some(1)
+            },
+            JsonStreamEventV2::StartArray {
+                length: 2,
+                declared_length: Some(2),
+                source_line: Some(1),
+            },
+            JsonStreamEventV2::StartObject {
+                source_line: Some(2)
+            },
+            JsonStreamEventV2::StartObject {
+                source_line: Some(3)
+            },
+            JsonStreamEventV2::StartObject {
+                source_line: Some(4)
+            },
+            JsonStreamEventV2::StartArray {
+                length: 3,
+                declared_length: Some(3),
+                source_line: Some(5),
+            },
+        ]
+    );
+
+    let v1 = events
+        .into_iter()
+        .map(JsonStreamEvent::from)
+        .collect::<Vec<_>>();
+    assert_eq!(v1, decode_stream_sync(lines, Some(options)));
+    let encoded = encode_stream_events(serde_json::json!({"a": [1]}), None);
+    assert_eq!(
+        JsonStreamEventV2::from(encoded[2].clone()),
+        JsonStreamEventV2::StartArray {
+            length: 1,
+            declared_length: None,
+            source_line: None,
+        }
+    );
+}
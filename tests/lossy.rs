@@ -0,0 +1,150 @@
+//! Tests for lossy conversion detection.
+
+use toon::JsonStreamEvent;
+use toon::JsonValue;
+use toon::StringOrNumberOrBoolOrNull;
+use toon::lossy::{
+    LossyKind, scan_events, scan_json, scan_nfc_key_collisions, scan_toon, validate_roundtrip_keys,
+};
+
+#[test]
+fn exact_json_has_no_lossy_sites() {
+    let input = r#"{"a":1,"b":0.1,"c":-2.5e-3,"d":1e21,"e":[true,null,"x"],"f":9007199254740992}"#;
+    assert!(scan_json(input).unwrap().is_empty());
+}
+
+#[test]
+#[cfg(not(feature = "arbitrary-precision"))]
+fn json_precision_loss_is_reported_with_path() {
+    let input = r#"{"users":[{"id":123456789012345678901},{"id":1}],"pi":3.14159265358979323846}"#;
+    let sites = scan_json(input).unwrap();
+    assert_eq!(sites.len(), 2);
+    assert_eq!(sites[0].path, "users[0].id");
+    assert_eq!(sites[0].kind, LossyKind::PrecisionLoss);
+    assert_eq!(sites[0].detail, "123456789012345678901");
+    assert_eq!(sites[1].path, "pi");
+}
+
+#[test]
+fn sixty_four_bit_integers_are_exact() {
+    let input = "[9007199254740993,-9223372036854775808,18446744073709551615]";
+    assert!(scan_json(input).unwrap().is_empty());
+    assert!(
+        scan_toon("ids[2]: 9007199254740993,18446744073709551615", None)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+#[cfg(feature = "arbitrary-precision")]
+fn arbitrary_precision_keeps_long_numbers_exact() {
+    let input = r#"{"id":123456789012345678901,"pi":3.14159265358979323846,"tiny":1e-400}"#;
+    assert!(scan_json(input).unwrap().is_empty());
+    assert!(
+        scan_toon("ids[2]: 1,123456789012345678901", None)
+            .unwrap()
+            .is_empty()
+    );
+    let sites = scan_json("[1e-5000,1e400]").unwrap();
+    assert_eq!(sites[0].kind, LossyKind::PrecisionLoss);
+    assert_eq!(sites[1].kind, LossyKind::NonFiniteNumber);
+}
+
+#[test]
+fn json_duplicate_keys_are_reported() {
+    let sites = scan_json(r#"{"a":{"x":1,"x":2},"a":3}"#).unwrap();
+    let kinds: Vec<_> = sites.iter().map(|s| (s.path.as_str(), s.kind)).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            ("a.x", LossyKind::DuplicateKey),
+            ("a", LossyKind::DuplicateKey)
+        ]
+    );
+}
+
+#[test]
+#[cfg(not(feature = "arbitrary-precision"))]
+fn json_underflow_is_precision_loss() {
+    let sites = scan_json("[1e-400]").unwrap();
+    assert_eq!(sites[0].path, "[0]");
+    assert_eq!(sites[0].kind, LossyKind::PrecisionLoss);
+}
+
+#[test]
+fn invalid_json_is_an_error() {
+    assert!(scan_json("{").is_err());
+}
+
+#[test]
+#[cfg(not(feature = "arbitrary-precision"))]
+fn toon_duplicate_keys_and_large_integers_are_reported() {
+    let sites = scan_toon("a: 1\na: 2\nids[2]: 1,123456789012345678901", None).unwrap();
+    assert_eq!(sites.len(), 2);
+    assert_eq!(sites[0].kind, LossyKind::DuplicateKey);
+    assert_eq!(sites[1].path, "ids[1]");
+    assert_eq!(sites[1].kind, LossyKind::PrecisionLoss);
+}
+
+#[test]
+fn non_finite_event_numbers_are_reported() {
+    let events = vec![
+        JsonStreamEvent::StartArray { length: 1 },
+        JsonStreamEvent::Primitive {
+            value: StringOrNumberOrBoolOrNull::Number(f64::NAN),
+        },
+        JsonStreamEvent::EndArray,
+    ];
+    let sites = scan_events(&events);
+    assert_eq!(sites[0].kind, LossyKind::NonFiniteNumber);
+}
+
+#[test]
+#[cfg(not(feature = "arbitrary-precision"))]
+fn ensure_lossless_lists_every_site() {
+    use toon::lossy::ensure_lossless;
+
+    assert!(ensure_lossless(&[]).is_ok());
+    let sites = scan_json(r#"{"a":1,"a":2,"b":123456789012345678901}"#).unwrap();
+    let message = ensure_lossless(&sites).unwrap_err().to_string();
+    assert!(message.starts_with("Lossy conversion at 2 site(s):"));
+    assert!(message.contains("a: duplicate key collapsed (a)"));
+    assert!(message.contains("b: number loses precision as f64 (123456789012345678901)"));
+}
+
+#[test]
+fn exotic_keys_round_trip_including_dotted_paths() {
+    let value = JsonValue::from(serde_json::json!({
+        "a[1]": 1,
+        "{b}": {"c}": 2, "d:e": [{"-f": 1, "]": 2}, {"-f": 3, "]": 4}]},
+        "": 3,
+        "a.b": 4,
+        "a": {"c": 5},
+        "rows": [{"x.y": 1}, {"x.y": 2}],
+        "-": [1, 2]
+    }));
+    assert_eq!(validate_roundtrip_keys(&value), vec![]);
+
+    let toon = toon::encode::encode(value, None);
+    assert!(toon.contains("\"a.b\": 4"), "{toon}");
+    assert!(toon.contains("rows[2]{\"x.y\"}:"), "{toon}");
+}
+
+#[test]
+fn keys_merged_by_nfc_are_reported() {
+    let value = JsonValue::from(serde_json::json!({
+        "e\u{301}": 1,
+        "\u{e9}": 2,
+        "rows": [{"a": 1}, {"\u{212b}": 1, "\u{c5}": 2}]
+    }));
+    let sites = scan_nfc_key_collisions(&value);
+    assert_eq!(sites.len(), 2);
+    assert_eq!(sites[0].path, "\u{e9}");
+    assert_eq!(sites[0].kind, LossyKind::NfcKeyCollision);
+    assert_eq!(sites[1].path, "rows[1].\u{c5}");
+    assert_eq!(sites[1].detail, "\u{c5}");
+
+    let distinct = JsonValue::from(serde_json::json!({"\u{e9}": 1, "e": 2}));
+    assert!(scan_nfc_key_collisions(&distinct).is_empty());
+}
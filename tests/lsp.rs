@@ -0,0 +1,160 @@
+//! Language server tests.
+//!
+//! Run with: cargo test --features lsp
+
+#![cfg(feature = "lsp")]
+
+use serde_json::{Value, json};
+use toon::lsp::run_server;
+
+const URI: &str = "file:///data.toon";
+
+fn frame(messages: &[Value]) -> Vec<u8> {
+    let mut input = Vec::new();
+    for message in messages {
+        let body = message.to_string();
+        input.extend_from_slice(format!("Content-Length: {}\r\n\r\n{body}", body.len()).as_bytes());
+    }
+    input
+}
+
+fn unframe(output: &[u8]) -> Vec<Value> {
+    let mut messages = Vec::new();
+    let mut rest = std::str::from_utf8(output).unwrap();
+    while let Some((header, body)) = rest.split_once("\r\n\r\n") {
+        let length: usize = header
+            .strip_prefix("Content-Length: ")
+            .unwrap()
+            .parse()
+            .unwrap();
+        messages.push(serde_json::from_str(&body[..length]).unwrap());
+        rest = &body[length..];
+    }
+    messages
+}
+
+/// Open `text`, send `requests`, and return every message the server wrote.
+fn session(text: &str, requests: &[Value]) -> Vec<Value> {
+    let mut messages = vec![
+        json!({ "jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {} }),
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": URI, "languageId": "toon", "version": 1, "text": text } },
+        }),
+    ];
+    messages.extend_from_slice(requests);
+    messages.push(json!({ "jsonrpc": "2.0", "method": "exit" }));
+
+    let mut output = Vec::new();
+    run_server(frame(&messages).as_slice(), &mut output).unwrap();
+    unframe(&output)
+}
+
+fn request(id: u64, method: &str, params: &Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+fn response(messages: &[Value], id: u64) -> &Value {
+    messages
+        .iter()
+        .find(|message| message["id"] == id)
+        .map(|message| &message["result"])
+        .unwrap()
+}
+
+#[test]
+fn initialize_advertises_capabilities() {
+    let messages = session("a: 1", &[]);
+    let capabilities = &response(&messages, 0)["capabilities"];
+    assert_eq!(capabilities["hoverProvider"], true);
+    assert_eq!(capabilities["foldingRangeProvider"], true);
+    assert_eq!(capabilities["documentFormattingProvider"], true);
+}
+
+#[test]
+fn diagnostics_report_decode_errors() {
+    let messages = session("a:\n   b: 1", &[]);
+    let diagnostics = &messages[1]["params"]["diagnostics"];
+    assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+    assert_eq!(diagnostics[0]["range"]["start"]["line"], 1);
+    assert_eq!(diagnostics[0]["range"]["end"]["character"], 7);
+
+    // Errors without a line number fall back to the first line
+    let messages = session("a: 1\nitems[3]: x,y", &[]);
+    let diagnostics = &messages[1]["params"]["diagnostics"];
+    assert_eq!(diagnostics[0]["range"]["start"]["line"], 0);
+    assert!(
+        diagnostics[0]["message"]
+            .as_str()
+            .unwrap()
+            .contains("Expected 3 inline array items")
+    );
+
+    let messages = session("a: 1", &[]);
+    assert_eq!(messages[1]["params"]["diagnostics"], json!([]));
+}
+
+#[test]
+fn formatting_replaces_document_with_canonical_encoding() {
+    let params = json!({ "textDocument": { "uri": URI }, "options": { "tabSize": 2 } });
+    let messages = session(
+        "user:\n    name: Ada\n    tags[2]:  x,  y",
+        &[request(1, "textDocument/formatting", &params)],
+    );
+    let edits = response(&messages, 1);
+    assert_eq!(edits[0]["newText"], "user:\n  name: Ada\n  tags[2]: x,y");
+    assert_eq!(
+        edits[0]["range"]["end"],
+        json!({ "line": 2, "character": 19 })
+    );
+}
+
+#[test]
+fn folding_ranges_cover_nested_sections() {
+    let params = json!({ "textDocument": { "uri": URI } });
+    let messages = session(
+        "users[2]{id}:\n  1\n  2\nmeta:\n  a: 1\nb: 2",
+        &[request(1, "textDocument/foldingRange", &params)],
+    );
+    assert_eq!(
+        response(&messages, 1),
+        &json!([
+            { "startLine": 0, "endLine": 2 },
+            { "startLine": 3, "endLine": 4 },
+        ])
+    );
+}
+
+#[test]
+fn hover_shows_decoded_json_for_line() {
+    let text = "users[2]{id,name}:\n  1,Ada\n  2,Bob\nmeta:\n  a: 1";
+    let hover = |id: u64, line: u64| {
+        request(
+            id,
+            "textDocument/hover",
+            &json!({ "textDocument": { "uri": URI }, "position": { "line": line, "character": 0 } }),
+        )
+    };
+    let messages = session(text, &[hover(1, 2), hover(2, 3), hover(3, 5)]);
+
+    let row = response(&messages, 1)["contents"]["value"]
+        .as_str()
+        .unwrap();
+    assert!(row.contains("\"id\": 2"));
+    assert!(row.contains("\"name\": \"Bob\""));
+
+    let meta = response(&messages, 2)["contents"]["value"]
+        .as_str()
+        .unwrap();
+    assert!(meta.starts_with("```json\n{\n  \"meta\": {\n    \"a\": 1"));
+
+    assert_eq!(response(&messages, 3), &Value::Null);
+}
+
+#[test]
+fn unknown_requests_return_method_not_found() {
+    let messages = session("a: 1", &[request(1, "textDocument/rename", &json!({}))]);
+    let error = &messages.iter().find(|message| message["id"] == 1).unwrap()["error"];
+    assert_eq!(error["code"], -32601);
+}
@@ -0,0 +1,122 @@
+//! Raw pass-through tests.
+//!
+//! Run with: cargo test --features raw
+
+#![cfg(feature = "raw")]
+
+use toon::lossy::{LossyKind, scan_toon};
+use toon::options::{DecodeOptions, resolve_decode_options};
+use toon::{JsonStreamEvent, JsonValue, decode, decode_stream_sync, encode, try_decode};
+
+const DOCUMENT: &str = "\
+name: demo
+@future{x}
+  nested: 1
+    deeper
+items[1]:
+  - 1
+  ~~ odd item
+count: 2";
+
+#[test]
+fn unknown_lines_round_trip_in_lenient_mode() {
+    let value = decode(DOCUMENT, Some(DecodeOptions::lenient()));
+    assert_eq!(
+        value.get("@future{x}"),
+        Some(&JsonValue::Raw(
+            "@future{x}\n  nested: 1\n    deeper".to_string()
+        ))
+    );
+    assert_eq!(
+        value.get("items"),
+        Some(&JsonValue::Array(vec![
+            JsonValue::from(1.0),
+            JsonValue::Raw("~~ odd item".to_string()),
+        ]))
+    );
+    assert_eq!(encode(value.clone(), None), DOCUMENT);
+
+    let repaired = decode(DOCUMENT, Some(DecodeOptions::repair()));
+    assert_eq!(repaired, value);
+    assert!(try_decode(DOCUMENT, None).is_err());
+}
+
+#[test]
+fn raw_values_become_strings_in_json() {
+    let value = decode("a: 1\nwhat is this", Some(DecodeOptions::lenient()));
+    let json = serde_json::Value::from(value);
+    assert_eq!(
+        json,
+        serde_json::json!({"a": 1, "what is this": "what is this"})
+    );
+
+    let stream_options = resolve_decode_options(Some(DecodeOptions::lenient())).stream_options();
+    let events = decode_stream_sync(
+        ["a: 1".to_string(), "what is this".to_string()],
+        Some(stream_options.clone()),
+    );
+    assert!(events.contains(&JsonStreamEvent::Raw {
+        text: "what is this".to_string()
+    }));
+    let sites = scan_toon("a: 1\nwhat is this", Some(stream_options)).unwrap();
+    assert_eq!(sites.len(), 1);
+    assert_eq!(sites[0].kind, LossyKind::RawText);
+    assert_eq!(sites[0].path, "what is this");
+}
+
+#[test]
+fn raw_entries_are_not_key_folded_or_expanded() {
+    let value = JsonValue::Object(vec![(
+        "a".to_string(),
+        JsonValue::Object(vec![(
+            "x.y".to_string(),
+            JsonValue::Raw("!x.y".to_string()),
+        )]),
+    )]);
+    let options = toon::EncodeOptions {
+        key_folding: Some(toon::options::KeyFoldingMode::Safe),
+        ..toon::EncodeOptions::default()
+    };
+    assert_eq!(encode(value, Some(options)), "a:\n  !x.y");
+
+    let decoded = decode(
+        "a.b: 1\nc.d",
+        Some(DecodeOptions {
+            expand_paths: Some(toon::options::ExpandPathsMode::Safe),
+            ..DecodeOptions::lenient()
+        }),
+    );
+    assert_eq!(decoded.get("c.d"), Some(&JsonValue::Raw("c.d".to_string())));
+    assert!(decoded.get("a").is_some());
+}
+
+#[test]
+fn toon_writer_writes_raw_events_in_place() {
+    use toon::encode::ToonWriter;
+
+    let stream_options = resolve_decode_options(Some(DecodeOptions::lenient())).stream_options();
+    let mut writer = ToonWriter::new(Vec::new(), None);
+    toon::decode_into_sink(
+        DOCUMENT.lines().map(str::to_string),
+        Some(stream_options),
+        &mut writer,
+    )
+    .unwrap();
+    let text = String::from_utf8(writer.finish().unwrap()).unwrap();
+    assert_eq!(text, format!("{DOCUMENT}\n"));
+
+    let mut writer = ToonWriter::new(Vec::new(), None);
+    writer.start_array(1).unwrap();
+    writer.start_object().unwrap();
+    writer.key("note").unwrap();
+    writer.raw("@odd\n  more").unwrap();
+    writer.end_object().unwrap();
+    writer.end_array().unwrap();
+    let text = String::from_utf8(writer.finish().unwrap()).unwrap();
+    let value = JsonValue::Array(vec![JsonValue::Object(vec![(
+        "note".to_string(),
+        JsonValue::Raw("@odd\n  more".to_string()),
+    )])]);
+    assert_eq!(text, format!("{}\n", encode(value, None)));
+    assert_eq!(text, "[1]:\n  - @odd\n      more\n");
+}
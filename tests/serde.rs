@@ -0,0 +1,478 @@
+//! Serde serializer and deserializer tests.
+//!
+//! Run with: cargo test --features serde
+
+#![cfg(feature = "serde")]
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize, Serializer};
+use toon::encode::serde::to_value;
+use toon::error::ToonError;
+use toon::options::{DecodeOptions, EncodeOptions, ExpandPathsMode};
+use toon::{decode, encode, encode_lines, from_toon_str, to_toon_lines, to_toon_string};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Report {
+    title: String,
+    page_count: u32,
+    ratio: f64,
+    draft: bool,
+    reviewer: Option<String>,
+    tags: Vec<&'static str>,
+    shape: Shape,
+    users: Vec<User>,
+    limits: BTreeMap<u16, &'static str>,
+    point: (i32, i32),
+    empty: (),
+    #[serde(skip_serializing_if = "Option::is_none")]
+    skipped: Option<u8>,
+}
+
+#[derive(Serialize)]
+struct User {
+    id: u64,
+    name: &'static str,
+    role: Role,
+}
+
+#[derive(Serialize)]
+enum Role {
+    Admin,
+    Guest,
+}
+
+#[derive(Serialize)]
+enum Shape {
+    Circle { radius: f32 },
+}
+
+fn report() -> Report {
+    Report {
+        title: "Q3, final".to_string(),
+        page_count: 12,
+        ratio: 0.25,
+        draft: false,
+        reviewer: None,
+        tags: vec!["a", "b c"],
+        shape: Shape::Circle { radius: 1.5 },
+        users: vec![
+            User {
+                id: 1,
+                name: "Ada",
+                role: Role::Admin,
+            },
+            User {
+                id: 2,
+                name: "Bob",
+                role: Role::Guest,
+            },
+        ],
+        limits: BTreeMap::from([(1, "low"), (10, "high")]),
+        point: (-3, 4),
+        empty: (),
+        skipped: None,
+    }
+}
+
+#[test]
+fn serializer_matches_the_serde_json_route() {
+    let value = report();
+    let json = serde_json::to_value(&value).unwrap();
+    assert_eq!(to_value(&value).unwrap(), json.clone().into());
+    for options in [
+        None,
+        Some(EncodeOptions {
+            delimiter: Some('|'),
+            indent: Some(4),
+            ..EncodeOptions::default()
+        }),
+    ] {
+        assert_eq!(
+            to_toon_string(&value, options.clone()).unwrap(),
+            encode(json.clone(), options.clone())
+        );
+        assert_eq!(
+            to_toon_lines(&value, options.clone()).unwrap(),
+            encode_lines(json.clone(), options)
+        );
+    }
+}
+
+#[test]
+fn enum_variants_are_externally_tagged() {
+    #[derive(Serialize)]
+    enum Event {
+        Stop,
+        Move(i32),
+        Resize(u32, u32),
+        Rename {
+            from: &'static str,
+            to: &'static str,
+        },
+    }
+
+    let events = vec![
+        Event::Stop,
+        Event::Move(-2),
+        Event::Resize(3, 4),
+        Event::Rename { from: "a", to: "b" },
+    ];
+    assert_eq!(
+        to_toon_string(&events, None).unwrap(),
+        "[4]:\n  - Stop\n  - Move: -2\n  - Resize[2]: 3,4\n  - Rename:\n      from: a\n      to: b"
+    );
+}
+
+#[test]
+fn map_keys_must_be_strings_integers_or_unit_variants() {
+    let map = BTreeMap::from([((1, 2), "pair")]);
+    let err = to_toon_string(&map, None).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Encode error at '': map key must be a string, integer, or unit variant"
+    );
+}
+
+#[test]
+fn serialize_errors_carry_the_path_of_the_failing_value() {
+    struct Broken;
+
+    impl Serialize for Broken {
+        fn serialize<S: Serializer>(&self, _serializer: S) -> Result<S::Ok, S::Error> {
+            Err(serde::ser::Error::custom("cannot serialize"))
+        }
+    }
+
+    #[derive(Serialize)]
+    struct Row {
+        id: u8,
+        value: Broken,
+    }
+
+    #[derive(Serialize)]
+    struct Table {
+        rows: Vec<Row>,
+    }
+
+    let table = Table {
+        rows: vec![
+            Row {
+                id: 1,
+                value: Broken,
+            },
+            Row {
+                id: 2,
+                value: Broken,
+            },
+        ],
+    };
+    let err = to_toon_string(&table, None).unwrap_err();
+    assert!(matches!(
+        &err,
+        ToonError::Encode { path, message }
+            if path == "rows[0].value" && message == "cannot serialize"
+    ));
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Catalog {
+    title: String,
+    page_count: u32,
+    offset: i64,
+    ratio: f64,
+    draft: bool,
+    reviewer: Option<String>,
+    tags: Vec<String>,
+    shape: Shape2,
+    items: Vec<Item>,
+    limits: BTreeMap<u16, String>,
+    point: (i32, i32),
+    empty: (),
+    initial: char,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Item {
+    id: u64,
+    name: String,
+    kind: Kind,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Kind {
+    Book,
+    Map,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Shape2 {
+    Circle { radius: f32 },
+}
+
+fn catalog() -> Catalog {
+    Catalog {
+        title: "Q3, final".to_string(),
+        page_count: 12,
+        offset: -40,
+        ratio: 0.25,
+        draft: false,
+        reviewer: None,
+        tags: vec!["a".to_string(), "b c".to_string()],
+        shape: Shape2::Circle { radius: 1.5 },
+        items: vec![
+            Item {
+                id: 1,
+                name: "Atlas".to_string(),
+                kind: Kind::Map,
+            },
+            Item {
+                id: 2,
+                name: "true".to_string(),
+                kind: Kind::Book,
+            },
+        ],
+        limits: BTreeMap::from([(1, "low".to_string()), (10, "high".to_string())]),
+        point: (-3, 4),
+        empty: (),
+        initial: 'Q',
+    }
+}
+
+#[test]
+fn deserializer_round_trips_serialized_values() {
+    let value = catalog();
+    let toon = to_toon_string(&value, None).unwrap();
+    assert_eq!(from_toon_str::<Catalog>(&toon, None).unwrap(), value);
+    assert_eq!(
+        toon::decode::serde::from_value::<Catalog>(&decode(&toon, None)).unwrap(),
+        value
+    );
+}
+
+#[test]
+fn enum_variants_deserialize_from_both_tagged_forms() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    enum Event {
+        Stop,
+        Move(i32),
+        Resize(u32, u32),
+        Rename { from: String, to: String },
+    }
+
+    let events: Vec<Event> = from_toon_str(
+        "[5]:\n  - Stop\n  - Stop: null\n  - Move: -2\n  - Resize[2]: 3,4\n  - Rename:\n      from: a\n      to: b",
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        events,
+        [
+            Event::Stop,
+            Event::Stop,
+            Event::Move(-2),
+            Event::Resize(3, 4),
+            Event::Rename {
+                from: "a".to_string(),
+                to: "b".to_string()
+            },
+        ]
+    );
+}
+
+#[test]
+fn deserialize_errors_carry_the_path_of_the_failing_value() {
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Row {
+        id: u8,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Table {
+        rows: Vec<Row>,
+    }
+
+    let err = from_toon_str::<Table>("rows[3]{id}:\n  1\n  300\n  3", None).unwrap_err();
+    assert!(matches!(
+        &err,
+        ToonError::Deserialize { path, message }
+            if path == "rows[1].id"
+                && message == "invalid value: integer `300`, expected u8"
+    ));
+
+    let err = from_toon_str::<Table>("rows[1]:\n  - name: a", None).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Deserialize error at 'rows[0]': missing field `id`"
+    );
+
+    let err = from_toon_str::<(i32, i32)>("[3]: 1,2,3", None).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Deserialize error at '': invalid length 3, expected fewer elements in array"
+    );
+}
+
+#[test]
+fn value_level_decode_options_apply_before_deserializing() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Server {
+        port: u16,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Config {
+        server: Server,
+    }
+
+    let options = DecodeOptions {
+        expand_paths: Some(ExpandPathsMode::Safe),
+        ..DecodeOptions::default()
+    };
+    assert_eq!(
+        from_toon_str::<Config>("server.port: 8080", Some(options)).unwrap(),
+        Config {
+            server: Server { port: 8080 }
+        }
+    );
+    assert!(from_toon_str::<Config>("server.port: 8080", None).is_err());
+}
+
+#[test]
+fn json_values_serialize_like_serde_json_values() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Envelope {
+        id: u32,
+        payload: toon::JsonValue,
+    }
+
+    let payload = decode(
+        "zeta: 1.5\nalpha[2]: x,null\nnested:\n  ok: true\n  rows[1]{id}:\n    7",
+        None,
+    );
+    let text = serde_json::to_string(&payload).unwrap();
+    assert_eq!(
+        text,
+        serde_json::to_string(&serde_json::Value::from(payload.clone())).unwrap()
+    );
+    assert_eq!(
+        serde_json::from_str::<toon::JsonValue>(&text).unwrap(),
+        payload
+    );
+
+    let envelope = Envelope { id: 3, payload };
+    let text = serde_json::to_string(&envelope).unwrap();
+    assert!(
+        text.starts_with(r#"{"id":3,"payload":{"zeta":1.5,"alpha":"#),
+        "{text}"
+    );
+    assert_eq!(serde_json::from_str::<Envelope>(&text).unwrap(), envelope);
+}
+
+#[test]
+fn repeated_keys_keep_the_first_position_and_the_last_value() {
+    let value: toon::JsonValue = serde_json::from_str(r#"{"a":1,"b":2,"a":3}"#).unwrap();
+    assert_eq!(value, serde_json::json!({"a": 3, "b": 2}).into());
+    assert_eq!(serde_json::to_string(&value).unwrap(), r#"{"a":3,"b":2}"#);
+}
+
+#[test]
+fn sixty_four_bit_integers_stay_exact() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Ids {
+        above_f64: i64,
+        max: u64,
+    }
+
+    let ids = Ids {
+        above_f64: 9_007_199_254_740_993,
+        max: u64::MAX,
+    };
+    let text = to_toon_string(&ids, None).unwrap();
+    assert_eq!(
+        text,
+        "above_f64: 9007199254740993\nmax: 18446744073709551615"
+    );
+    assert_eq!(from_toon_str::<Ids>(&text, None).unwrap(), ids);
+
+    let json = r#"{"above_f64":9007199254740993,"max":18446744073709551615}"#;
+    let value: toon::JsonValue = serde_json::from_str(json).unwrap();
+    assert_eq!(serde_json::to_string(&value).unwrap(), json);
+}
+
+#[test]
+fn json_primitives_reject_arrays_and_objects() {
+    let primitive: toon::JsonPrimitive = serde_json::from_str(r#""text""#).unwrap();
+    assert_eq!(
+        primitive,
+        toon::StringOrNumberOrBoolOrNull::String("text".to_string())
+    );
+    assert_eq!(
+        serde_json::to_string(&toon::StringOrNumberOrBoolOrNull::Number(f64::NAN)).unwrap(),
+        "null"
+    );
+    let err = serde_json::from_str::<toon::JsonPrimitive>("[1]").unwrap_err();
+    assert!(
+        err.to_string().contains("expected a JSON primitive"),
+        "{err}"
+    );
+}
+
+#[test]
+fn stream_events_serialize_as_jsonl_records() {
+    let events = toon::decode_stream_sync(
+        "\"a b\": 1\nlist[2]: x,true".lines().map(String::from),
+        None,
+    );
+    let lines: Vec<String> = events
+        .iter()
+        .map(|event| serde_json::to_string(event).unwrap())
+        .collect();
+    assert_eq!(
+        lines.join("\n") + "\n",
+        toon::json::events_to_jsonl(events.clone())
+    );
+    let parsed: Vec<toon::JsonStreamEvent> = lines
+        .iter()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(parsed, events);
+    assert_eq!(
+        serde_json::from_str::<toon::JsonStreamEvent>(r#"{"type":"key","key":"id"}"#).unwrap(),
+        toon::JsonStreamEvent::Key {
+            key: "id".to_string(),
+            was_quoted: false
+        }
+    );
+}
+
+#[test]
+fn decode_checkpoints_round_trip_through_json() {
+    use std::io::Cursor;
+    use toon::decode::resume::{DecodeCheckpoint, ResumableDecoder};
+
+    let input = "name: demo\nrows[3]{id}:\n  1\n  2\n\n  3";
+    let options = Some(toon::options::DecodeStreamOptions {
+        strict: Some(false),
+        ..Default::default()
+    });
+    let mut events = Vec::new();
+    let mut decoder = ResumableDecoder::new(Cursor::new(input), options.clone()).unwrap();
+    decoder.decode_lines(4, &mut events).unwrap();
+    let saved = serde_json::to_string(&decoder.checkpoint()).unwrap();
+    assert!(saved.contains("\"offset\":"), "{saved}");
+
+    let checkpoint: DecodeCheckpoint = serde_json::from_str(&saved).unwrap();
+    assert_eq!(checkpoint, decoder.checkpoint());
+    let mut decoder =
+        ResumableDecoder::resume(Cursor::new(input), checkpoint, options.clone()).unwrap();
+    while decoder.step(&mut events).unwrap() {}
+    assert_eq!(
+        events,
+        toon::decode_stream_sync(input.lines().map(String::from), options)
+    );
+}
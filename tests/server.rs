@@ -0,0 +1,160 @@
+//! HTTP server tests.
+//!
+//! Run with: cargo test --features server
+
+#![cfg(feature = "server")]
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use toon::server::{
+    CHUNK_SIZE, ServeOptions, handle_request, handle_request_with, serve, serve_with,
+};
+
+fn request(method: &str, target: &str, body: &str) -> String {
+    let raw = format!(
+        "{method} {target} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    let mut output = Vec::new();
+    handle_request(&mut raw.as_bytes(), &mut output).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+fn split_response(response: &str) -> (&str, &str) {
+    response.split_once("\r\n\r\n").unwrap()
+}
+
+#[test]
+fn encode_endpoint_applies_query_options() {
+    let response = request(
+        "POST",
+        "/encode?delimiter=%7C&indent=4",
+        r#"{"rows":[{"a":1,"b":"x y"},{"a":2,"b":"z"}]}"#,
+    );
+    let (head, body) = split_response(&response);
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(head.contains("Content-Length: "));
+    assert_eq!(body, "rows[2|]{a|b}:\n    1|x y\n    2|z\n");
+}
+
+#[test]
+fn decode_endpoint_returns_json() {
+    let response = request("POST", "/decode?indent=0", "name: Ada\ntags[2]: a,b\n");
+    let (head, body) = split_response(&response);
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(head.contains("Content-Type: application/json"));
+    assert_eq!(body, "{\"name\":\"Ada\",\"tags\":[\"a\",\"b\"]}\n");
+}
+
+#[test]
+fn errors_get_status_codes() {
+    let bad_toon = request("POST", "/decode", "rows[3]: 1,2\n");
+    assert!(bad_toon.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    assert!(bad_toon.contains("Expected 3"));
+
+    let bad_json = request("POST", "/encode", "{");
+    assert!(bad_json.starts_with("HTTP/1.1 400 "));
+
+    let bad_param = request("POST", "/encode?colour=red", "{}");
+    assert!(bad_param.contains("Unknown parameter 'colour'"));
+
+    assert!(request("GET", "/encode", "").starts_with("HTTP/1.1 405 "));
+    assert!(request("POST", "/other", "").starts_with("HTTP/1.1 404 "));
+
+    let mut output = Vec::new();
+    let raw = "POST /encode HTTP/1.1\r\n\r\n";
+    handle_request(&mut raw.as_bytes(), &mut output).unwrap();
+    assert!(
+        String::from_utf8(output)
+            .unwrap()
+            .starts_with("HTTP/1.1 411 ")
+    );
+}
+
+#[test]
+fn large_responses_are_chunked() {
+    let items: Vec<String> = (0..CHUNK_SIZE / 4).map(|i| i.to_string()).collect();
+    let body = format!("{{\"items\":[{}]}}", items.join(","));
+    let response = request("POST", "/encode", &body);
+    let (head, chunks) = split_response(&response);
+    assert!(head.contains("Transfer-Encoding: chunked"));
+    assert!(chunks.ends_with("\r\n0\r\n\r\n"));
+
+    let mut decoded = String::new();
+    let mut rest = chunks;
+    loop {
+        let (size, tail) = rest.split_once("\r\n").unwrap();
+        let size = usize::from_str_radix(size, 16).unwrap();
+        if size == 0 {
+            break;
+        }
+        decoded.push_str(&tail[..size]);
+        rest = &tail[size + 2..];
+    }
+    assert!(decoded.starts_with(&format!("items[{}]: 0,1,2,", items.len())));
+}
+
+#[test]
+fn serves_over_tcp() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    thread::spawn(move || serve(&listener));
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    let body = r#"{"a":1}"#;
+    write!(
+        stream,
+        "POST /encode HTTP/1.1\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with("\r\n\r\na: 1\n"));
+}
+
+#[test]
+fn bodies_over_the_limit_are_refused() {
+    let options = ServeOptions {
+        max_body_bytes: 8,
+        ..ServeOptions::default()
+    };
+    let raw = "POST /decode HTTP/1.1\r\nContent-Length: 18446744073709551615\r\n\r\nx: 1";
+    let mut output = Vec::new();
+    handle_request_with(&mut raw.as_bytes(), &mut output, &options).unwrap();
+    let response = String::from_utf8(output).unwrap();
+    assert!(response.starts_with("HTTP/1.1 413 "), "{response}");
+    assert!(response.contains("exceeds the limit of 8 bytes"));
+}
+
+#[test]
+fn stalled_connections_time_out_and_free_their_slot() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let options = ServeOptions {
+        max_connections: 1,
+        timeout: Duration::from_millis(200),
+        ..ServeOptions::default()
+    };
+    thread::spawn(move || serve_with(&listener, options));
+
+    // The idle connection holds the only slot until it times out, and is
+    // closed without a response.
+    let mut idle = TcpStream::connect(address).unwrap();
+    let mut waiting = TcpStream::connect(address).unwrap();
+    write!(
+        waiting,
+        "POST /encode HTTP/1.1\r\nContent-Length: 7\r\n\r\n{{\"a\":1}}"
+    )
+    .unwrap();
+
+    let mut response = String::new();
+    idle.read_to_string(&mut response).unwrap();
+    assert_eq!(response, "");
+    waiting.read_to_string(&mut response).unwrap();
+    assert!(response.ends_with("\r\n\r\na: 1\n"), "{response}");
+}
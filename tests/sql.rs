@@ -0,0 +1,119 @@
+//! Database import/export tests (`toon sql`).
+//!
+//! Run with: cargo test --features sqlite
+
+#![cfg(feature = "sqlite")]
+
+use rusqlite::Connection;
+use toon::sql::{export_query, import_rows};
+use toon::tabular::TabularReader;
+
+#[cfg(feature = "cli")]
+use {assert_cmd::Command, std::fs, tempfile::TempDir};
+
+#[cfg(feature = "cli")]
+fn toon() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_toon"))
+}
+
+#[test]
+fn import_then_export_round_trips() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    let input = "users[3]{id:int,name,score}:\n  1,Ada,9.5\n  2,\"007\",null\n  3,\"Bob, Jr\",7\n";
+    let rows = TabularReader::new(input.as_bytes()).unwrap();
+    assert_eq!(import_rows(&mut conn, "users", rows).unwrap(), 3);
+
+    let name: String = conn
+        .query_row("SELECT name FROM users WHERE id = 2", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(name, "007");
+    let id_type: String = conn
+        .query_row("SELECT typeof(id) FROM users LIMIT 1", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(id_type, "integer");
+
+    let output = export_query(
+        &conn,
+        "SELECT id, name, score FROM users ORDER BY id",
+        Some("users"),
+        Vec::new(),
+        2,
+    )
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "users[3]{id,name,score}:\n  1,Ada,9.5\n  2,\"007\",null\n  3,\"Bob, Jr\",7\n"
+    );
+}
+
+#[test]
+fn failed_import_inserts_nothing() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    let rows = TabularReader::new(&b"rows[2]{a,b}:\n  1,2\n  3\n"[..]).unwrap();
+    assert!(import_rows(&mut conn, "rows", rows).is_err());
+    let tables: i64 = conn
+        .query_row("SELECT count(*) FROM sqlite_master", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(tables, 0);
+}
+
+#[test]
+fn blob_columns_are_rejected() {
+    let conn = Connection::open_in_memory().unwrap();
+    let err = export_query(&conn, "SELECT x'00' AS data", None, Vec::new(), 2).unwrap_err();
+    assert!(err.to_string().contains("Column 'data' holds a BLOB"));
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn cli_imports_and_exports_tables() {
+    let dir = TempDir::new().unwrap();
+    let db = dir.path().join("data.sqlite");
+    let input = dir.path().join("data.toon");
+    fs::write(
+        &input,
+        "source: test\nusers[2]{id,name}:\n  1,Ada\n  2,Grace\n",
+    )
+    .unwrap();
+
+    toon()
+        .arg("sql")
+        .arg("--db")
+        .arg(&db)
+        .args(["--table", "people", "--key", "users"])
+        .arg(&input)
+        .assert()
+        .success();
+
+    toon()
+        .arg("sql")
+        .arg("--db")
+        .arg(&db)
+        .args(["--table", "people"])
+        .assert()
+        .success()
+        .stdout("people[2]{id,name}:\n  1,Ada\n  2,Grace\n");
+
+    toon()
+        .arg("sql")
+        .arg("--db")
+        .arg(&db)
+        .args([
+            "--query",
+            "SELECT name FROM people WHERE id > 1",
+            "--key",
+            "rows",
+        ])
+        .assert()
+        .success()
+        .stdout("rows[1]{name}:\n  Grace\n");
+
+    toon()
+        .arg("sql")
+        .arg("--db")
+        .arg(&db)
+        .arg(&input)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("--table is required to import"));
+}